@@ -0,0 +1,150 @@
+//! Benchmarks of the paths run on every command or poll: the authorization check, the options of
+//! the polls, and the stats queries. They run against an in-memory database with the migrations
+//! applied, so no bot nor config is needed.
+//!
+//! Run with `cargo bench`, and compare with a previous run before deploying.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+use tokio::runtime::Runtime;
+
+// The modules are shared with the bot
+#[path = "../src/config.rs"]
+#[allow(dead_code)]
+mod config;
+#[path = "../src/db/mod.rs"]
+#[allow(dead_code)]
+mod db;
+#[path = "../src/poll_options.rs"]
+#[allow(dead_code)]
+mod poll_options;
+
+use db::{authorizations::authorization_cache, quotes::QuoteRepo};
+use poll_options::{quiz_options, split_candidates};
+
+const CHATS: usize = 50;
+const COMMANDS: &[&str] = &[
+    "poll", "bureau", "stats", "quote", "karma", "remind", "todo",
+];
+const AUTHORS: usize = 40;
+const QUOTES: usize = 2000;
+
+fn chat_id(index: usize) -> String {
+    format!("-100{}", index)
+}
+
+/// In-memory database with the migrations applied, and the data of a busy instance. A single
+/// connection, since each connection to `:memory:` opens a new database.
+async fn database() -> SqlitePool {
+    let db = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    sqlx::migrate!().run(&db).await.unwrap();
+
+    let mut tx = db.begin().await.unwrap();
+    for chat in 0..CHATS {
+        sqlx::query("INSERT INTO chats(chat_id) VALUES($1)")
+            .bind(chat_id(chat))
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        for command in COMMANDS {
+            sqlx::query("INSERT INTO authorizations(command, chat_id) VALUES($1, $2)")
+                .bind(command)
+                .bind(chat_id(chat))
+                .execute(&mut *tx)
+                .await
+                .unwrap();
+        }
+    }
+    for quote in 0..QUOTES {
+        let poll_id = format!("poll-{}", quote);
+        sqlx::query(
+            r#"INSERT INTO quotes(chat_id, author, "text", created_at, poll_id, correct_option)
+            VALUES($1, $2, 'Quote', 0, $3, 0)"#,
+        )
+        .bind(chat_id(quote % CHATS))
+        .bind(format!("Member {}", quote % AUTHORS))
+        .bind(&poll_id)
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+        for option in 0..4 {
+            sqlx::query(
+                "INSERT INTO poll_results(poll_id, chat_id, kind, question, option, option_text, voter_count, closed_at)
+                VALUES($1, $2, 'quiz', 'Who said it?', $3, 'Member', $4, 0)",
+            )
+            .bind(&poll_id)
+            .bind(chat_id(quote % CHATS))
+            .bind(option)
+            .bind((quote + option as usize) as i64 % 7)
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        }
+    }
+    tx.commit().await.unwrap();
+
+    db
+}
+
+fn authorization(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let db = runtime.block_on(database());
+    let chat = chat_id(7);
+
+    c.bench_function("authorization (cached)", |b| {
+        b.to_async(&runtime).iter(|| async {
+            authorization_cache()
+                .is_authorized(&db, black_box(&chat), black_box("stats"), None)
+                .await
+                .unwrap()
+        })
+    });
+    c.bench_function("authorization (uncached)", |b| {
+        b.to_async(&runtime).iter(|| async {
+            authorization_cache().invalidate(&chat).await;
+            authorization_cache()
+                .is_authorized(&db, black_box(&chat), black_box("stats"), None)
+                .await
+                .unwrap()
+        })
+    });
+}
+
+fn poll_options(c: &mut Criterion) {
+    let members = (0..AUTHORS)
+        .map(|i| format!("Member {} with a rather long name", i))
+        .collect::<Vec<_>>();
+    let candidates = (0..95).collect::<Vec<i64>>();
+
+    c.bench_function("quiz options", |b| {
+        b.iter_batched(
+            || members.clone(),
+            |members| quiz_options(members, black_box("Member 3 with a rather long name")),
+            BatchSize::SmallInput,
+        )
+    });
+    c.bench_function("split candidates", |b| {
+        b.iter(|| split_candidates(black_box(&candidates)).len())
+    });
+}
+
+fn stats(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let db = runtime.block_on(database());
+
+    c.bench_function("quotes by author", |b| {
+        b.to_async(&runtime)
+            .iter(|| async { db.quote_counts_by_author().await.unwrap() })
+    });
+    c.bench_function("quiz success rates", |b| {
+        b.to_async(&runtime)
+            .iter(|| async { db.quote_success_rates().await.unwrap() })
+    });
+}
+
+criterion_group!(benches, authorization, poll_options, stats);
+criterion_main!(benches);
@@ -0,0 +1,9 @@
+CREATE TABLE reminders(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    chat_id VARCHAR(50) NOT NULL,
+    author VARCHAR(200) NOT NULL,
+    "text" TEXT NOT NULL,
+    -- Unix timestamp (seconds)
+    due_at INTEGER NOT NULL
+);
+CREATE INDEX reminders_due_at ON reminders(due_at);
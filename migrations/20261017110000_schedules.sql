@@ -0,0 +1,10 @@
+CREATE TABLE schedules(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    chat_id VARCHAR(50) NOT NULL,
+    -- Cron expression, with the seconds field
+    cron VARCHAR(100) NOT NULL,
+    -- Either a text message, or a command (starting with '/')
+    payload TEXT NOT NULL,
+    -- Unix timestamp (seconds) of the next execution
+    next_run INTEGER NOT NULL
+);
@@ -0,0 +1,10 @@
+-- Senders are only identified by a salted hash of their Telegram id
+CREATE TABLE anon_messages(
+    sender_hash VARCHAR(64) NOT NULL,
+    -- Unix timestamp (seconds)
+    sent_at INTEGER NOT NULL
+);
+CREATE INDEX anon_messages_sender ON anon_messages(sender_hash, sent_at);
+CREATE TABLE anon_blocked(
+    sender_hash VARCHAR(64) PRIMARY KEY
+);
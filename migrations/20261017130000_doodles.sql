@@ -0,0 +1,21 @@
+CREATE TABLE doodles(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    chat_id VARCHAR(50) NOT NULL,
+    -- Message containing the availability grid, set once it has been sent
+    message_id INTEGER,
+    title TEXT NOT NULL,
+    closed BOOLEAN NOT NULL DEFAULT FALSE
+);
+CREATE TABLE doodle_options(
+    doodle_id INTEGER NOT NULL REFERENCES doodles(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    label TEXT NOT NULL,
+    PRIMARY KEY(doodle_id, position)
+);
+CREATE TABLE doodle_votes(
+    doodle_id INTEGER NOT NULL REFERENCES doodles(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    user_id VARCHAR(50) NOT NULL,
+    user_name VARCHAR(200) NOT NULL,
+    PRIMARY KEY(doodle_id, position, user_id)
+);
@@ -0,0 +1,8 @@
+CREATE TABLE todos(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    chat_id VARCHAR(50) NOT NULL,
+    "text" TEXT NOT NULL,
+    author VARCHAR(200) NOT NULL,
+    done BOOLEAN NOT NULL DEFAULT FALSE,
+    done_by VARCHAR(200)
+);
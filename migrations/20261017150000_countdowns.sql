@@ -0,0 +1,11 @@
+-- Pinned countdowns, updated daily by the scheduler
+CREATE TABLE countdowns(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    chat_id VARCHAR(50) NOT NULL,
+    message_id INTEGER NOT NULL,
+    label TEXT NOT NULL,
+    -- Unix timestamp (seconds)
+    target INTEGER NOT NULL,
+    -- Number of days displayed in the message
+    days_left INTEGER NOT NULL
+);
@@ -0,0 +1,7 @@
+CREATE TABLE random_picks(
+    chat_id VARCHAR(50) NOT NULL,
+    member VARCHAR(200) NOT NULL,
+    -- Unix timestamp (seconds)
+    picked_at INTEGER NOT NULL
+);
+CREATE INDEX random_picks_member ON random_picks(chat_id, member);
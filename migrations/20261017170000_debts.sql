@@ -0,0 +1,11 @@
+CREATE TABLE debts(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    chat_id VARCHAR(50) NOT NULL,
+    creditor VARCHAR(200) NOT NULL,
+    debtor VARCHAR(200) NOT NULL,
+    amount INTEGER NOT NULL,
+    reason TEXT NOT NULL,
+    -- Unix timestamp (seconds)
+    created_at INTEGER NOT NULL,
+    settled BOOLEAN NOT NULL DEFAULT FALSE
+);
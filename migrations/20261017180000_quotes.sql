@@ -0,0 +1,9 @@
+CREATE TABLE quotes(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    chat_id VARCHAR(50) NOT NULL,
+    -- Name of the committee member who said the quote
+    author VARCHAR(200) NOT NULL,
+    "text" TEXT NOT NULL,
+    -- Unix timestamp (seconds)
+    created_at INTEGER NOT NULL
+);
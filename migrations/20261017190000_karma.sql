@@ -0,0 +1,15 @@
+CREATE TABLE karma(
+    chat_id VARCHAR(50) NOT NULL,
+    -- "@username", or "id:<telegram id>" for users without username
+    user_key VARCHAR(200) NOT NULL,
+    user_name VARCHAR(200) NOT NULL,
+    points INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY(chat_id, user_key)
+);
+CREATE TABLE karma_votes(
+    chat_id VARCHAR(50) NOT NULL,
+    voter_id VARCHAR(50) NOT NULL,
+    -- Unix timestamp (seconds)
+    voted_at INTEGER NOT NULL
+);
+CREATE INDEX karma_votes_voter ON karma_votes(chat_id, voter_id, voted_at);
@@ -0,0 +1,4 @@
+CREATE TABLE transport_stops(
+    chat_id VARCHAR(50) PRIMARY KEY,
+    stop VARCHAR(200) NOT NULL
+);
@@ -0,0 +1,15 @@
+CREATE TABLE expenses(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    chat_id VARCHAR(50) NOT NULL,
+    author_id VARCHAR(50) NOT NULL,
+    author_name VARCHAR(200) NOT NULL,
+    -- In cents (CHF)
+    amount INTEGER NOT NULL,
+    description TEXT NOT NULL,
+    -- Telegram file id of the photo of the receipt
+    receipt_file_id VARCHAR(200),
+    -- Unix timestamp (seconds)
+    created_at INTEGER NOT NULL,
+    approved_by VARCHAR(200),
+    approved_at INTEGER
+);
@@ -0,0 +1,15 @@
+CREATE TABLE inventory(
+    item VARCHAR(200) PRIMARY KEY COLLATE NOCASE
+);
+CREATE TABLE loans(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    item VARCHAR(200) NOT NULL COLLATE NOCASE REFERENCES inventory(item) ON DELETE CASCADE,
+    chat_id VARCHAR(50) NOT NULL,
+    borrower_id VARCHAR(50) NOT NULL,
+    borrower_name VARCHAR(200) NOT NULL,
+    -- Unix timestamps (seconds)
+    taken_at INTEGER NOT NULL,
+    due_at INTEGER NOT NULL,
+    returned_at INTEGER,
+    reminded_at INTEGER
+);
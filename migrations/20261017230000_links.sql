@@ -0,0 +1,11 @@
+CREATE TABLE links(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    chat_id VARCHAR(50) NOT NULL,
+    url TEXT NOT NULL,
+    title TEXT,
+    -- Lowercase tags, separated by spaces
+    tags TEXT NOT NULL DEFAULT '',
+    -- Unix timestamp (seconds)
+    created_at INTEGER NOT NULL,
+    UNIQUE(chat_id, url)
+);
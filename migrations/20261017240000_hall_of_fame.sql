@@ -0,0 +1,19 @@
+-- Quiz poll of the quote, to know who guessed the author
+ALTER TABLE quotes ADD COLUMN poll_id VARCHAR(100);
+ALTER TABLE quotes ADD COLUMN correct_option INTEGER;
+CREATE TABLE bureau_polls(
+    poll_id VARCHAR(100) PRIMARY KEY,
+    chat_id VARCHAR(50) NOT NULL,
+    -- Unix timestamp (seconds)
+    sent_at INTEGER NOT NULL
+);
+-- Answers to the polls sent by the bot (quotes and bureau)
+CREATE TABLE poll_answers(
+    poll_id VARCHAR(100) NOT NULL,
+    user_id VARCHAR(50) NOT NULL,
+    user_name VARCHAR(200) NOT NULL,
+    option INTEGER NOT NULL,
+    -- Unix timestamp (seconds)
+    answered_at INTEGER NOT NULL,
+    PRIMARY KEY(poll_id, user_id)
+);
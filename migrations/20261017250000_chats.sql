@@ -0,0 +1,54 @@
+-- Registry of the chats in which the bot is used
+CREATE TABLE chats(
+    chat_id VARCHAR(50) PRIMARY KEY,
+    title VARCHAR(200),
+    -- Unix timestamp (seconds) of the last message received from the chat
+    last_seen INTEGER NOT NULL DEFAULT 0
+);
+INSERT OR IGNORE INTO chats(chat_id)
+    SELECT chat_id FROM authorizations
+    UNION SELECT chat_id FROM schedules
+    UNION SELECT chat_id FROM transport_stops
+    UNION SELECT chat_id FROM reminders
+    UNION SELECT chat_id FROM doodles
+    UNION SELECT chat_id FROM todos
+    UNION SELECT chat_id FROM countdowns
+    UNION SELECT chat_id FROM random_picks
+    UNION SELECT chat_id FROM debts
+    UNION SELECT chat_id FROM quotes
+    UNION SELECT chat_id FROM karma
+    UNION SELECT chat_id FROM expenses
+    UNION SELECT chat_id FROM loans
+    UNION SELECT chat_id FROM links
+    UNION SELECT chat_id FROM bureau_polls;
+
+-- Per-chat configuration, bound to the registry
+CREATE TABLE authorizations_new(
+    command VARCHAR(50) NOT NULL,
+    chat_id VARCHAR(50) NOT NULL REFERENCES chats(chat_id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+INSERT INTO authorizations_new SELECT command, chat_id FROM authorizations;
+DROP TABLE authorizations;
+ALTER TABLE authorizations_new RENAME TO authorizations;
+
+CREATE TABLE schedules_new(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    chat_id VARCHAR(50) NOT NULL REFERENCES chats(chat_id) ON UPDATE CASCADE ON DELETE CASCADE,
+    -- Cron expression, with the seconds field
+    cron VARCHAR(100) NOT NULL,
+    -- Either a text message, or a command (starting with '/')
+    payload TEXT NOT NULL,
+    -- Unix timestamp (seconds) of the next execution
+    next_run INTEGER NOT NULL
+);
+INSERT INTO schedules_new SELECT id, chat_id, cron, payload, next_run FROM schedules;
+DROP TABLE schedules;
+ALTER TABLE schedules_new RENAME TO schedules;
+
+CREATE TABLE transport_stops_new(
+    chat_id VARCHAR(50) PRIMARY KEY REFERENCES chats(chat_id) ON UPDATE CASCADE ON DELETE CASCADE,
+    stop VARCHAR(200) NOT NULL
+);
+INSERT INTO transport_stops_new SELECT chat_id, stop FROM transport_stops;
+DROP TABLE transport_stops;
+ALTER TABLE transport_stops_new RENAME TO transport_stops;
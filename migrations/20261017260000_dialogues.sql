@@ -0,0 +1,6 @@
+-- State of the dialogues (/poll, /newpoll), kept across restarts
+CREATE TABLE dialogues(
+    chat_id TEXT PRIMARY KEY NOT NULL,
+    "state" TEXT NOT NULL,
+    updated_at INTEGER NOT NULL
+);
@@ -0,0 +1,5 @@
+-- Details of the chats, tracked through the membership updates of the bot
+ALTER TABLE chats ADD COLUMN kind VARCHAR(20);
+ALTER TABLE chats ADD COLUMN member_count INTEGER;
+-- Unix timestamp (seconds) at which the bot was removed from the chat, NULL while it is a member
+ALTER TABLE chats ADD COLUMN left_at INTEGER;
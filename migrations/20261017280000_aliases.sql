@@ -0,0 +1,7 @@
+-- Shortcuts for commands, defined per chat (e.g. /b for /bureau)
+CREATE TABLE aliases(
+    chat_id TEXT NOT NULL,
+    alias TEXT NOT NULL,
+    command TEXT NOT NULL,
+    PRIMARY KEY(chat_id, alias)
+);
@@ -0,0 +1,2 @@
+-- Language of the replies of the bot in the chat ('fr' or 'en')
+ALTER TABLE chats ADD COLUMN language VARCHAR(2) NOT NULL DEFAULT 'fr';
@@ -0,0 +1,2 @@
+-- Timezone in which the dates of the chat are interpreted and displayed
+ALTER TABLE chats ADD COLUMN timezone VARCHAR(64) NOT NULL DEFAULT 'Europe/Zurich';
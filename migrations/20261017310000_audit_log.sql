@@ -0,0 +1,11 @@
+-- Administrative actions, done through the commands or the web dashboard
+CREATE TABLE audit_log(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    -- Unix timestamp (seconds)
+    created_at INTEGER NOT NULL,
+    -- Telegram user ("Name (id)") or dashboard user ("web:<user>")
+    actor VARCHAR(200) NOT NULL,
+    "action" VARCHAR(50) NOT NULL,
+    details TEXT NOT NULL
+);
+CREATE INDEX audit_log_created_at ON audit_log(created_at);
@@ -0,0 +1,9 @@
+-- Questions voted on by reacting with 👍 or 👎 to the message of the bot
+CREATE TABLE reaction_votes(
+    chat_id VARCHAR(50) NOT NULL,
+    message_id INTEGER NOT NULL,
+    question TEXT NOT NULL,
+    up INTEGER NOT NULL DEFAULT 0,
+    down INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY(chat_id, message_id)
+);
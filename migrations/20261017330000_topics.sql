@@ -0,0 +1,4 @@
+-- Forum topic (message_thread_id) to which a command is restricted, NULL for the whole chat
+ALTER TABLE authorizations ADD COLUMN thread_id INTEGER;
+-- Forum topic in which the scheduled job posts, NULL for the general one
+ALTER TABLE schedules ADD COLUMN thread_id INTEGER;
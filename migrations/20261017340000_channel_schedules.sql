@@ -0,0 +1,2 @@
+-- Channel in which the scheduled job posts instead of the chat, with the content of the chat
+ALTER TABLE schedules ADD COLUMN target_chat_id VARCHAR(50);
@@ -0,0 +1,3 @@
+-- Whether the bureau polls and the countdowns are pinned automatically in the chat
+ALTER TABLE chats ADD COLUMN pin_bureau BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE chats ADD COLUMN pin_countdowns BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,12 @@
+-- Quiz tournaments: the quizzes sent between the start and the end score points for the guessers
+CREATE TABLE tournaments(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    chat_id VARCHAR(50) NOT NULL,
+    "name" VARCHAR(200) NOT NULL,
+    -- Unix timestamps (seconds)
+    started_at INTEGER NOT NULL,
+    ends_at INTEGER NOT NULL,
+    -- Set once the recap was sent, possibly before ends_at if stopped early
+    closed_at INTEGER
+);
+CREATE INDEX tournaments_chat ON tournaments(chat_id);
@@ -0,0 +1,11 @@
+-- Wrong attributions of quotes reported by the members, reviewed by the admins
+CREATE TABLE quote_reports(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    quote_id INTEGER NOT NULL REFERENCES quotes(id) ON DELETE CASCADE,
+    chat_id VARCHAR(50) NOT NULL,
+    reporter_name VARCHAR(200) NOT NULL,
+    reason TEXT NOT NULL,
+    -- Unix timestamps (seconds)
+    created_at INTEGER NOT NULL,
+    resolved_at INTEGER
+);
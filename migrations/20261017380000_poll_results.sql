@@ -0,0 +1,16 @@
+-- Final counts of the options of the polls sent by the bot (quizzes and bureau), archived when
+-- they close since Telegram does not keep the polls forever. The answers of each user are kept
+-- in poll_answers.
+CREATE TABLE poll_results(
+    poll_id VARCHAR(100) NOT NULL,
+    chat_id VARCHAR(50) NOT NULL,
+    -- 'quiz' or 'bureau'
+    kind VARCHAR(10) NOT NULL,
+    question TEXT NOT NULL,
+    option INTEGER NOT NULL,
+    option_text VARCHAR(100) NOT NULL,
+    voter_count INTEGER NOT NULL,
+    -- Unix timestamp (seconds)
+    closed_at INTEGER NOT NULL,
+    PRIMARY KEY(poll_id, option)
+);
@@ -0,0 +1,10 @@
+-- Polls sent by the bot (quizzes, bureau and /newpoll), to be able to stop them
+CREATE TABLE sent_polls(
+    poll_id VARCHAR(100) PRIMARY KEY,
+    chat_id VARCHAR(50) NOT NULL,
+    message_id INTEGER NOT NULL,
+    -- Unix timestamps (seconds)
+    sent_at INTEGER NOT NULL,
+    closed_at INTEGER
+);
+CREATE INDEX sent_polls_chat ON sent_polls(chat_id, sent_at);
@@ -0,0 +1,11 @@
+-- Settings of the bureau polls and the quizzes of the chat. The durations are in seconds, NULL
+-- when the polls stay open until /closepoll
+ALTER TABLE chats ADD COLUMN bureau_anonymous BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE chats ADD COLUMN bureau_multiple_answers BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE chats ADD COLUMN bureau_close_after INTEGER;
+ALTER TABLE chats ADD COLUMN quiz_anonymous BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE chats ADD COLUMN quiz_close_after INTEGER;
+
+-- Unix timestamp (seconds) at which the poll is closed automatically
+ALTER TABLE sent_polls ADD COLUMN close_at INTEGER;
+CREATE INDEX sent_polls_close ON sent_polls(close_at) WHERE closed_at IS NULL;
@@ -0,0 +1,9 @@
+-- Members seen writing in the groups, proposed as answers of the "who wrote this?" quizzes
+CREATE TABLE chat_members(
+    chat_id VARCHAR(50) NOT NULL,
+    user_id VARCHAR(50) NOT NULL,
+    user_name VARCHAR(200) NOT NULL,
+    -- Unix timestamp (seconds) of the last message of the member
+    last_seen INTEGER NOT NULL,
+    PRIMARY KEY(chat_id, user_id)
+);
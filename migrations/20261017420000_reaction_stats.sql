@@ -0,0 +1,24 @@
+-- Telegram accounts of the committee members, linked by the admins
+CREATE TABLE committee_links(
+    name VARCHAR(200) PRIMARY KEY,
+    telegram_id VARCHAR(50) NOT NULL UNIQUE
+);
+
+-- Authors of the recent messages of the linked members, to attribute the reactions they receive
+CREATE TABLE message_authors(
+    chat_id VARCHAR(50) NOT NULL,
+    message_id INTEGER NOT NULL,
+    telegram_id VARCHAR(50) NOT NULL,
+    -- Unix timestamp (seconds)
+    sent_at INTEGER NOT NULL,
+    PRIMARY KEY(chat_id, message_id)
+);
+CREATE INDEX message_authors_sent ON message_authors(sent_at);
+
+CREATE TABLE reaction_stats(
+    chat_id VARCHAR(50) NOT NULL,
+    telegram_id VARCHAR(50) NOT NULL,
+    emoji VARCHAR(20) NOT NULL,
+    count INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY(chat_id, telegram_id, emoji)
+);
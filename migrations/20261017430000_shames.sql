@@ -0,0 +1,18 @@
+-- Shames and compliments ("gg") given to the members of the chat
+CREATE TABLE shames(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    chat_id VARCHAR(50) NOT NULL,
+    -- 'shame' or 'gg'
+    kind VARCHAR(10) NOT NULL,
+    -- "@username", or "id:<telegram id>" for users without username, as for the karma
+    user_key VARCHAR(200) NOT NULL,
+    user_name VARCHAR(200) NOT NULL,
+    reason TEXT NOT NULL,
+    given_by VARCHAR(200) NOT NULL,
+    -- Unix timestamp (seconds)
+    created_at INTEGER NOT NULL
+);
+CREATE INDEX shames_chat ON shames(chat_id, created_at);
+
+-- Whether the counters only include the current month
+ALTER TABLE chats ADD COLUMN shame_monthly_reset BOOLEAN NOT NULL DEFAULT FALSE;
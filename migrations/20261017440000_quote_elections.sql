@@ -0,0 +1,34 @@
+-- Elections of the best quote of the month, in rounds of polls of at most 10 quotes. The results
+-- of their polls are archived in poll_results with the 'election' kind
+CREATE TABLE quote_elections(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    chat_id VARCHAR(50) NOT NULL,
+    -- Month of the quotes, as "YYYY-MM"
+    month VARCHAR(7) NOT NULL,
+    -- Current round, starting at 1
+    round INTEGER NOT NULL DEFAULT 1,
+    -- Unix timestamps (seconds)
+    started_at INTEGER NOT NULL,
+    closed_at INTEGER,
+    UNIQUE(chat_id, month)
+);
+CREATE TABLE quote_election_polls(
+    poll_id VARCHAR(100) PRIMARY KEY,
+    election_id INTEGER NOT NULL REFERENCES quote_elections(id) ON DELETE CASCADE,
+    round INTEGER NOT NULL,
+    -- Ids of the quotes, comma separated in the order of the options
+    quote_ids TEXT NOT NULL
+);
+
+-- Winners of the elections, kept even if the quote is deleted
+CREATE TABLE quote_awards(
+    chat_id VARCHAR(50) NOT NULL,
+    month VARCHAR(7) NOT NULL,
+    quote_id INTEGER NOT NULL,
+    author VARCHAR(200) NOT NULL,
+    "text" TEXT NOT NULL,
+    votes INTEGER NOT NULL,
+    -- Unix timestamp (seconds)
+    awarded_at INTEGER NOT NULL,
+    PRIMARY KEY(chat_id, month)
+);
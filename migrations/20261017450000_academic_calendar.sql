@@ -0,0 +1,8 @@
+-- Periods of the academic calendar (semester, exam session, holidays), edited by the admins
+CREATE TABLE academic_periods(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    "name" VARCHAR(200) NOT NULL,
+    -- Dates as YYYY-MM-DD, both included
+    starts_on VARCHAR(10) NOT NULL,
+    ends_on VARCHAR(10) NOT NULL
+);
@@ -0,0 +1,19 @@
+-- Email addresses given by the members to /subscribe, reused for their next requests
+CREATE TABLE subscribers(
+    user_id VARCHAR(20) PRIMARY KEY NOT NULL,
+    email VARCHAR(320) NOT NULL
+);
+
+-- Subscriptions and unsubscriptions to the mailing lists, until they are synced with the backend
+CREATE TABLE mailing_requests(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id VARCHAR(20) NOT NULL,
+    email VARCHAR(320) NOT NULL,
+    list VARCHAR(100) NOT NULL,
+    -- Whether the member subscribes, or unsubscribes
+    subscribe BOOLEAN NOT NULL,
+    created_at INTEGER NOT NULL,
+    synced_at INTEGER
+);
+
+CREATE INDEX mailing_requests_pending ON mailing_requests(synced_at, id);
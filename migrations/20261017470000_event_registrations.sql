@@ -0,0 +1,9 @@
+-- Answers of the members to the registration buttons of the Directus events
+CREATE TABLE event_registrations(
+    event_id INTEGER NOT NULL,
+    user_id VARCHAR(20) NOT NULL,
+    user_name VARCHAR(200) NOT NULL,
+    attending BOOLEAN NOT NULL,
+    updated_at INTEGER NOT NULL,
+    PRIMARY KEY(event_id, user_id)
+);
@@ -0,0 +1,6 @@
+-- Check-in codes of the registrations, sent to the participants in private as QR codes
+ALTER TABLE event_registrations ADD COLUMN code VARCHAR(12);
+ALTER TABLE event_registrations ADD COLUMN code_sent_at INTEGER;
+ALTER TABLE event_registrations ADD COLUMN checked_in_at INTEGER;
+
+CREATE UNIQUE INDEX event_registrations_code ON event_registrations(code);
@@ -0,0 +1,21 @@
+-- Announcements composed with /newsletter, sent by the scheduler at the chosen time
+CREATE TABLE newsletters(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    -- Chat where the newsletter was composed, which receives the delivery report
+    author_chat_id VARCHAR(50) NOT NULL,
+    author VARCHAR(200) NOT NULL,
+    -- HTML text, with the title
+    "text" TEXT NOT NULL,
+    -- Telegram file of the image, if any
+    photo VARCHAR(200),
+    -- JSON list of the [text, url] of the link buttons
+    buttons TEXT NOT NULL,
+    send_at INTEGER NOT NULL,
+    sent_at INTEGER
+);
+
+CREATE TABLE newsletter_targets(
+    newsletter_id INTEGER NOT NULL REFERENCES newsletters(id) ON DELETE CASCADE,
+    chat_id VARCHAR(50) NOT NULL,
+    PRIMARY KEY(newsletter_id, chat_id)
+);
@@ -0,0 +1,8 @@
+-- Admin tokens generated after the token was exposed in a group. The last one replaces
+-- ADMIN_TOKEN as long as ADMIN_TOKEN is unchanged (identified by its hash).
+CREATE TABLE admin_token_rotations(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    token VARCHAR(64) NOT NULL,
+    replaced_hash VARCHAR(64) NOT NULL,
+    rotated_at INTEGER NOT NULL
+);
@@ -0,0 +1,8 @@
+-- Single-use admin invitations, redeemed through the /start deep links generated by /admininvite
+CREATE TABLE admin_invites(
+    nonce VARCHAR(16) PRIMARY KEY,
+    "name" VARCHAR(200) NOT NULL,
+    created_by VARCHAR(200) NOT NULL,
+    expires_at INTEGER NOT NULL,
+    used_at INTEGER
+);
@@ -0,0 +1,4 @@
+-- How and where each admin authenticated, unknown for the admins added before
+ALTER TABLE admins ADD COLUMN authenticated_at INTEGER;
+ALTER TABLE admins ADD COLUMN auth_chat_id VARCHAR(50);
+ALTER TABLE admins ADD COLUMN auth_method VARCHAR(50);
@@ -0,0 +1,15 @@
+-- Critical actions waiting for the approval of a second admin
+CREATE TABLE pending_approvals(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    "action" VARCHAR(50) NOT NULL,
+    payload TEXT NOT NULL,
+    "description" TEXT NOT NULL,
+    requested_by VARCHAR(200) NOT NULL,
+    requester_id VARCHAR(50) NOT NULL,
+    chat_id VARCHAR(50) NOT NULL,
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL,
+    resolved_by VARCHAR(200),
+    resolved_at INTEGER,
+    approved BOOLEAN
+);
@@ -0,0 +1,17 @@
+-- Verification of the new members of the chat: 'off', 'button' or 'trivia'. The members who do
+-- not answer within the delay (in minutes) stay muted, or are removed when `verification_kick`
+ALTER TABLE chats ADD COLUMN verification_mode VARCHAR(20) NOT NULL DEFAULT 'off';
+ALTER TABLE chats ADD COLUMN verification_minutes INTEGER NOT NULL DEFAULT 5;
+ALTER TABLE chats ADD COLUMN verification_kick BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Challenges sent to the new members, until they answer or the delay expires
+CREATE TABLE member_challenges(
+    chat_id VARCHAR(50) NOT NULL,
+    user_id VARCHAR(50) NOT NULL,
+    message_id INTEGER NOT NULL,
+    -- Index of the right option of the trivia question, NULL for a simple button
+    answer INTEGER,
+    expires_at INTEGER NOT NULL,
+    PRIMARY KEY(chat_id, user_id)
+);
+CREATE INDEX member_challenges_expiration ON member_challenges(expires_at);
@@ -0,0 +1,11 @@
+-- Commands received by the bot, without their arguments, for the audit exports
+CREATE TABLE command_log(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    -- Unix timestamp (seconds)
+    created_at INTEGER NOT NULL,
+    chat_id VARCHAR(50) NOT NULL,
+    -- Telegram user ("Name (id)")
+    actor VARCHAR(200) NOT NULL,
+    command VARCHAR(50) NOT NULL
+);
+CREATE INDEX command_log_created_at ON command_log(created_at);
@@ -0,0 +1,5 @@
+-- Whether the entry went through the detection of the unusual admin activity. The existing
+-- entries are not checked, so that the first run does not alert about the whole history
+ALTER TABLE audit_log ADD COLUMN checked BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE audit_log SET checked = TRUE;
+CREATE INDEX audit_log_unchecked ON audit_log(id) WHERE NOT checked;
@@ -0,0 +1,2 @@
+-- Whether the statistics of the committee are only shown to the linked committee members
+ALTER TABLE chats ADD COLUMN stats_committee_only BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+-- Inverse of the reversible actions (JSON), applied by /undo
+ALTER TABLE audit_log ADD COLUMN undo TEXT;
+ALTER TABLE audit_log ADD COLUMN undone BOOLEAN NOT NULL DEFAULT FALSE;
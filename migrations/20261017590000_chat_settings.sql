@@ -0,0 +1,10 @@
+-- Settings of the /settings menu which are not read on every update
+CREATE TABLE chat_settings(
+    chat_id VARCHAR(50) PRIMARY KEY REFERENCES chats(chat_id) ON UPDATE CASCADE ON DELETE CASCADE,
+    -- Schedules created by the menu for the bureau poll and the weekly digest, which can also be
+    -- removed with /scheduleremove
+    bureau_schedule_id INTEGER REFERENCES schedules(id) ON DELETE SET NULL,
+    digest_schedule_id INTEGER REFERENCES schedules(id) ON DELETE SET NULL,
+    -- Sent to the members joining the chat, once verified if the verification is enabled
+    welcome_message TEXT
+);
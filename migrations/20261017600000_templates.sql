@@ -0,0 +1,7 @@
+-- Wording of the phrases of the bot chosen by the chats with /settemplate
+CREATE TABLE templates(
+    chat_id VARCHAR(50) NOT NULL REFERENCES chats(chat_id) ON UPDATE CASCADE ON DELETE CASCADE,
+    "key" VARCHAR(50) NOT NULL,
+    "text" TEXT NOT NULL,
+    PRIMARY KEY(chat_id, "key")
+);
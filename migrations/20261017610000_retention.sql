@@ -0,0 +1,6 @@
+-- Retention durations set with /retention, overriding the defaults of the config
+CREATE TABLE retention_policies(
+    "policy" VARCHAR(50) PRIMARY KEY,
+    -- Number of days the data is kept, NULL to keep it forever
+    days INTEGER
+);
@@ -0,0 +1,2 @@
+-- The welcome message is only set with /settings, the template of the greeting was dropped
+DELETE FROM templates WHERE "key" = 'welcome';
@@ -0,0 +1,10 @@
+-- The @usernames are case-insensitive, their keys are now lowercase
+UPDATE shames SET user_key = LOWER(user_key);
+
+CREATE TABLE karma_merged AS
+SELECT chat_id, LOWER(user_key) AS user_key, MAX(user_name) AS user_name, SUM(points) AS points
+FROM karma GROUP BY chat_id, LOWER(user_key);
+DELETE FROM karma;
+INSERT INTO karma(chat_id, user_key, user_name, points)
+SELECT chat_id, user_key, user_name, points FROM karma_merged;
+DROP TABLE karma_merged;
@@ -0,0 +1,10 @@
+-- Delivery of each newsletter target, so that a failed or interrupted send is retried for the
+-- targets which did not receive it only
+ALTER TABLE newsletter_targets ADD COLUMN sent_at INTEGER;
+ALTER TABLE newsletter_targets ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE newsletter_targets ADD COLUMN error TEXT;
+
+-- The newsletters already marked as sent are not delivered again
+UPDATE newsletter_targets SET sent_at = (
+    SELECT sent_at FROM newsletters WHERE newsletters.id = newsletter_targets.newsletter_id
+);
@@ -0,0 +1,3 @@
+-- Telegram id of the author of the reversible actions, which /undo looks up, so that renaming the
+-- account does not lose its actions
+ALTER TABLE audit_log ADD COLUMN actor_id VARCHAR(50);
@@ -0,0 +1,6 @@
+-- Telegram id of the admin who generated the invitation, so that revoking them cancels it
+ALTER TABLE admin_invites ADD COLUMN created_by_id VARCHAR(50);
+-- The older invitations only have "name (id)": keep the digits between the last parentheses
+UPDATE admin_invites
+SET created_by_id = rtrim(substr(created_by, length(rtrim(created_by, '0123456789)')) + 1), ')')
+WHERE created_by LIKE '% (%)';
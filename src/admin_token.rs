@@ -0,0 +1,89 @@
+//! Admin token used by `/authenticate` and the dashboard: `ADMIN_TOKEN`, or the token generated
+//! by the last rotation after it was exposed. Changing `ADMIN_TOKEN` in the environment overrides
+//! the rotations again.
+//!
+//! The rotated tokens are stored encrypted, see [`crate::crypto`]. The salt of the anonymous
+//! senders and the key of the backups do not derive from the admin token, so that they are neither
+//! exposed with it nor changed by a rotation.
+
+use std::sync::{OnceLock, RwLock};
+
+use rand::{thread_rng, RngCore};
+use sqlx::SqlitePool;
+
+use crate::{
+    config::config,
+    crypto::{blind_index, decrypt, encrypt},
+    dates::now,
+};
+
+static TOKEN: OnceLock<RwLock<String>> = OnceLock::new();
+fn token() -> &'static RwLock<String> {
+    TOKEN.get_or_init(|| RwLock::new(config().admin_token.clone()))
+}
+
+fn config_token_hash() -> String {
+    blind_index(&config().admin_token)
+}
+
+/// Current admin token.
+pub fn admin_token() -> String {
+    token()
+        .read()
+        .expect("the token lock is not poisoned")
+        .clone()
+}
+
+/// Loads the last rotated token, if `ADMIN_TOKEN` did not change since, or else goes back to
+/// `ADMIN_TOKEN`.
+pub async fn load_admin_token(db: &SqlitePool) -> Result<(), sqlx::Error> {
+    let hash = config_token_hash();
+    let rotated = sqlx::query!(
+        "SELECT token FROM admin_token_rotations WHERE replaced_hash = $1
+        ORDER BY id DESC LIMIT 1",
+        hash
+    )
+    .fetch_optional(db)
+    .await?;
+    match rotated.map(|r| decrypt(&r.token)) {
+        Some(Some(rotated)) => {
+            log::info!("Using the rotated admin token");
+            *token().write().expect("the token lock is not poisoned") = rotated;
+        }
+        Some(None) => {
+            log::error!("Could not decrypt the rotated admin token, was DATA_KEY changed?")
+        }
+        None => {
+            *token().write().expect("the token lock is not poisoned") = config().admin_token.clone()
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the admin token with a random one, and returns it.
+pub async fn rotate_admin_token(db: &SqlitePool) -> Result<String, sqlx::Error> {
+    let mut bytes = [0u8; 24];
+    thread_rng().fill_bytes(&mut bytes);
+    let new_token = hex::encode(bytes);
+
+    let hash = config_token_hash();
+    let encrypted = encrypt(&new_token);
+    let timestamp = now().timestamp();
+    sqlx::query!(
+        "INSERT INTO admin_token_rotations(token, replaced_hash, rotated_at) VALUES($1, $2, $3)",
+        encrypted,
+        hash,
+        timestamp
+    )
+    .execute(db)
+    .await?;
+    *token().write().expect("the token lock is not poisoned") = new_token.clone();
+
+    Ok(new_token)
+}
+
+/// Whether the text or caption of the message contains the admin token.
+pub fn leaks_admin_token(text: Option<&str>) -> bool {
+    let token = admin_token();
+    !token.is_empty() && text.is_some_and(|t| t.contains(&token))
+}
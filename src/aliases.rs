@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use sqlx::{SqliteExecutor, SqlitePool};
+use teloxide::{
+    types::{MediaKind, MediaText, Message, MessageCommon, MessageKind},
+    utils::command::BotCommands,
+};
+
+use crate::commands::Command;
+
+/// Whether the name (without the leading `/`) is one of the commands of the bot.
+pub fn is_command(name: &str) -> bool {
+    let name = format!("/{}", name);
+    Command::bot_commands().iter().any(|c| c.command == name)
+}
+
+/// Command the alias stands for in the chat, if any.
+pub async fn alias_command(
+    db: impl SqliteExecutor<'_>,
+    chat_id: &str,
+    alias: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT command FROM aliases WHERE chat_id = $1 AND alias = $2",
+        chat_id,
+        alias
+    )
+    .fetch_optional(db)
+    .await
+}
+
+/// Makes the alias stand for the command in the chat, or removes it if `command` is `None`.
+pub async fn set_alias(
+    db: impl SqliteExecutor<'_>,
+    chat_id: &str,
+    alias: &str,
+    command: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    match command {
+        Some(command) => sqlx::query!(
+            "INSERT INTO aliases(chat_id, alias, command) VALUES($1, $2, $3)
+            ON CONFLICT(chat_id, alias) DO UPDATE SET command = excluded.command",
+            chat_id,
+            alias,
+            command
+        )
+        .execute(db)
+        .await
+        .map(|_| ()),
+        None => sqlx::query!(
+            "DELETE FROM aliases WHERE chat_id = $1 AND alias = $2",
+            chat_id,
+            alias
+        )
+        .execute(db)
+        .await
+        .map(|_| ()),
+    }
+}
+
+/// Replaces the alias starting the message, if any, by the command it stands for, so that the
+/// command parser handles it as usual. Aliases never shadow the commands of the bot.
+pub async fn resolve_alias(msg: Message, db: Arc<SqlitePool>) -> Message {
+    let Some(text) = msg.text() else {
+        return msg;
+    };
+    let Some(head) = text.split_whitespace().next() else {
+        return msg;
+    };
+    let Some(name) = head.strip_prefix('/') else {
+        return msg;
+    };
+    let (name, mention) = match name.split_once('@') {
+        Some((name, bot)) => (name, format!("@{}", bot)),
+        None => (name, String::new()),
+    };
+    let name = name.to_lowercase();
+    if is_command(&name) {
+        return msg;
+    }
+
+    let chat_id = msg.chat.id.to_string();
+    let command = match alias_command(db.as_ref(), &chat_id, &name).await {
+        Ok(Some(command)) => command,
+        Ok(None) => return msg,
+        Err(e) => {
+            log::error!("Could not resolve alias /{} in {}: {:?}", name, chat_id, e);
+            return msg;
+        }
+    };
+
+    let text = format!(
+        "/{}{}{}",
+        command,
+        mention,
+        &text[text.find(head).unwrap_or(0) + head.len()..]
+    );
+    let mut msg = msg;
+    if let MessageKind::Common(MessageCommon {
+        media_kind: MediaKind::Text(MediaText { text: t, .. }),
+        ..
+    }) = &mut msg.kind
+    {
+        *t = text;
+    }
+    msg
+}
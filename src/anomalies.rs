@@ -0,0 +1,183 @@
+//! Detection of the unusual admin activity in the audit log: bursts of authorization changes,
+//! repeated failed authentications, and actions in the middle of the night. The superadmin is
+//! alerted in private with the entries concerned.
+
+use std::ops::Range;
+
+use chrono::Timelike;
+use sqlx::SqlitePool;
+use teloxide::{
+    types::{ChatId, UserId},
+    Bot,
+};
+
+use crate::{
+    config::config,
+    dates::{format_datetime, from_timestamp, TIMEZONE},
+    format::{bold, escape, HtmlMessages, MessageBuilder},
+    outbox::Queued,
+};
+
+/// Actions of which an unusual number in a short time raises an alert.
+struct Burst {
+    title: &'static str,
+    actions: &'static [&'static str],
+    /// Number of entries by the same actor raising the alert.
+    threshold: usize,
+    /// Window in which they are counted, in seconds.
+    window: i64,
+}
+
+const BURSTS: &[Burst] = &[
+    Burst {
+        title: "Nombreux changements d'autorisations",
+        actions: &["authorize", "unauthorize", "topic_bind"],
+        threshold: 10,
+        window: 10 * 60,
+    },
+    Burst {
+        title: "Échecs d'authentification répétés",
+        actions: &["authenticate_failed"],
+        threshold: 3,
+        window: 60 * 60,
+    },
+];
+/// Local hours (in the default timezone) at which the admin actions are unusual.
+const QUIET_HOURS: Range<u32> = 2..6;
+/// Actions which are not done by admins, and thus ignored at the quiet hours.
+const NON_ADMIN_ACTIONS: &[&str] = &["authenticate_failed", "admin_token_leak"];
+/// Entries listed in an alert, the most recent ones.
+const MAX_ENTRIES: usize = 15;
+
+struct Entry {
+    id: i64,
+    created_at: i64,
+    actor: String,
+    action: String,
+    details: String,
+}
+
+struct Alert {
+    title: String,
+    entries: Vec<Entry>,
+}
+
+fn format_alert(alert: &Alert) -> String {
+    let skipped = alert.entries.len().saturating_sub(MAX_ENTRIES);
+    let mut lines = alert.entries[skipped..]
+        .iter()
+        .map(|e| {
+            let date = from_timestamp(e.created_at, TIMEZONE)
+                .map(|d| format_datetime(&d))
+                .unwrap_or_default();
+            format!(
+                "{} {}: {} {}",
+                date,
+                escape(&e.actor),
+                bold(&e.action),
+                escape(&e.details)
+            )
+        })
+        .collect::<Vec<_>>();
+    if skipped > 0 {
+        lines.insert(0, format!("… et {} entrées plus anciennes", skipped));
+    }
+    MessageBuilder::new()
+        .title(&format!("⚠️ {}", alert.title))
+        .items(lines)
+        .build()
+}
+
+/// Whether the entry crosses the threshold of a burst, with the entries of the burst.
+async fn burst_alert(
+    db: &SqlitePool,
+    burst: &Burst,
+    entry: &Entry,
+) -> Result<Option<Alert>, sqlx::Error> {
+    let since = entry.created_at - burst.window;
+    let recent = sqlx::query_as!(
+        Entry,
+        r#"SELECT id AS "id!", created_at, actor, "action", details FROM audit_log
+        WHERE actor = $1 AND created_at > $2 AND id <= $3 ORDER BY id"#,
+        entry.actor,
+        since,
+        entry.id
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .filter(|e| burst.actions.contains(&e.action.as_str()))
+    .collect::<Vec<_>>();
+
+    // Only once per burst, when the entry reaches the threshold
+    Ok((recent.len() == burst.threshold).then(|| Alert {
+        title: format!("{} par {}", burst.title, entry.actor),
+        entries: recent,
+    }))
+}
+
+/// Checks the new entries of the audit log, and alerts the superadmin about the unusual ones.
+pub async fn detect_anomalies(bot: &Bot, db: &SqlitePool) -> Result<(), sqlx::Error> {
+    let entries = sqlx::query_as!(
+        Entry,
+        r#"SELECT id AS "id!", created_at, actor, "action", details FROM audit_log
+        WHERE NOT checked ORDER BY id"#
+    )
+    .fetch_all(db)
+    .await?;
+    let Some(last) = entries.last().map(|e| e.id) else {
+        return Ok(());
+    };
+
+    let mut alerts = Vec::new();
+    for entry in &entries {
+        for burst in BURSTS {
+            if !burst.actions.contains(&entry.action.as_str()) {
+                continue;
+            }
+            if let Some(alert) = burst_alert(db, burst, entry).await? {
+                alerts.push(alert);
+            }
+        }
+    }
+    let night = entries
+        .into_iter()
+        .filter(|e| !NON_ADMIN_ACTIONS.contains(&e.action.as_str()))
+        .filter(|e| {
+            from_timestamp(e.created_at, TIMEZONE).is_some_and(|d| QUIET_HOURS.contains(&d.hour()))
+        })
+        .collect::<Vec<_>>();
+    if !night.is_empty() {
+        alerts.push(Alert {
+            title: format!(
+                "Actions admin entre {}h et {}h",
+                QUIET_HOURS.start, QUIET_HOURS.end
+            ),
+            entries: night,
+        });
+    }
+
+    // Checked before alerting, so that a failure to send does not repeat the alerts forever
+    sqlx::query!(
+        "UPDATE audit_log SET checked = TRUE WHERE id <= $1 AND NOT checked",
+        last
+    )
+    .execute(db)
+    .await?;
+
+    for alert in &alerts {
+        log::warn!("Unusual admin activity: {}", alert.title);
+        let Some(superadmin) = config().superadmin_id else {
+            continue;
+        };
+        if let Err(e) = bot
+            .send_html(ChatId::from(UserId(superadmin)), format_alert(alert))
+            .queued()
+            .await
+        {
+            log::error!("Could not alert the superadmin: {:?}", e);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,304 @@
+//! JSON API for the other services of the CLIC, served when `API_ADDRESS` and `API_TOKEN` are
+//! set. Requests must carry the token in an `Authorization: Bearer <token>` header.
+//!
+//! - `GET /api/chats`: chats of the registry
+//! - `GET /api/authorizations?chat_id=`: commands allowed in the chats
+//! - `POST /api/authorizations`, `DELETE /api/authorizations`: allows or forbids a command in a
+//!   chat, with a `{ "chat_id", "command" }` body
+//! - `GET /api/quotes?author=&chat_id=&limit=`: latest quotes
+//! - `GET /api/stats`: number of quotes by author and of polls by committee member
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query, State},
+    http::{header::AUTHORIZATION, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    aliases::is_command, audit::audit, committee::committee_repository, config::config,
+    http::serve, state::AppState,
+};
+
+/// Maximum number of quotes returned at once.
+const MAX_QUOTES: i64 = 500;
+/// Name under which the actions done through the API appear in the audit log.
+const ACTOR: &str = "api";
+
+#[derive(Debug)]
+enum Error {
+    Database(sqlx::Error),
+    NotFound(String),
+    Invalid(String),
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(value: sqlx::Error) -> Self {
+        Self::Database(value)
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            Error::Database(e) => {
+                log::error!("API error: {:?}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "database error".to_owned(),
+                )
+            }
+            Error::NotFound(e) => (StatusCode::NOT_FOUND, e),
+            Error::Invalid(e) => (StatusCode::BAD_REQUEST, e),
+        };
+        (status, Json(json!({ "error": message }))).into_response()
+    }
+}
+
+/// Request carrying the API token.
+struct ApiClient;
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ApiClient {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        let token = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.strip_prefix("Bearer "));
+
+        match (token, &config().api_token) {
+            (Some(token), Some(expected)) if token == expected => Ok(ApiClient),
+            _ => {
+                log::warn!(
+                    "Rejected API request {} {}, invalid token",
+                    parts.method,
+                    parts.uri.path()
+                );
+                Err((
+                    StatusCode::UNAUTHORIZED,
+                    Json(json!({ "error": "invalid token" })),
+                )
+                    .into_response())
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Chat {
+    chat_id: String,
+    title: Option<String>,
+    kind: Option<String>,
+    member_count: Option<i64>,
+    last_seen: i64,
+    left_at: Option<i64>,
+}
+
+async fn chats(_: ApiClient, State(state): State<AppState>) -> Result<Json<Vec<Chat>>, Error> {
+    let chats = sqlx::query_as!(
+        Chat,
+        r#"SELECT chat_id AS "chat_id!", title, kind, member_count, last_seen, left_at FROM chats
+        ORDER BY title"#
+    )
+    .fetch_all(state.db.as_ref())
+    .await?;
+
+    Ok(Json(chats))
+}
+
+#[derive(Deserialize)]
+struct ChatFilter {
+    chat_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Authorization {
+    chat_id: String,
+    command: String,
+}
+
+async fn authorizations(
+    _: ApiClient,
+    State(state): State<AppState>,
+    Query(filter): Query<ChatFilter>,
+) -> Result<Json<Vec<Authorization>>, Error> {
+    let chat_id = filter.chat_id.unwrap_or_default();
+    let authorizations = state
+        .authorization_repo
+        .authorizations(&chat_id)
+        .await?
+        .into_iter()
+        .map(|a| Authorization {
+            chat_id: a.chat_id,
+            command: a.command,
+        })
+        .collect();
+
+    Ok(Json(authorizations))
+}
+
+async fn authorization_add(
+    _: ApiClient,
+    State(state): State<AppState>,
+    Json(authorization): Json<Authorization>,
+) -> Result<StatusCode, Error> {
+    let Authorization { chat_id, command } = authorization;
+    if !is_command(&command) {
+        return Err(Error::Invalid(format!("unknown command /{}", command)));
+    }
+    let known = sqlx::query!(
+        "SELECT COUNT(*) AS count FROM chats WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_one(state.db.as_ref())
+    .await?
+    .count
+        > 0;
+    if !known {
+        return Err(Error::NotFound(format!("unknown chat {}", chat_id)));
+    }
+
+    if state.authorization_repo.grant(&chat_id, &command).await? {
+        let details = format!("/{} in {}", command, chat_id);
+        audit(state.db.as_ref(), ACTOR, "authorize", &details).await;
+        Ok(StatusCode::CREATED)
+    } else {
+        Ok(StatusCode::OK)
+    }
+}
+
+async fn authorization_remove(
+    _: ApiClient,
+    State(state): State<AppState>,
+    Json(authorization): Json<Authorization>,
+) -> Result<StatusCode, Error> {
+    let Authorization { chat_id, command } = authorization;
+    if state.authorization_repo.revoke(&chat_id, &command).await? {
+        let details = format!("/{} in {}", command, chat_id);
+        audit(state.db.as_ref(), ACTOR, "unauthorize", &details).await;
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(Error::NotFound(format!(
+            "/{} is not authorized in {}",
+            command, chat_id
+        )))
+    }
+}
+
+#[derive(Deserialize)]
+struct QuoteFilter {
+    author: Option<String>,
+    chat_id: Option<String>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct Quote {
+    id: i64,
+    chat_id: String,
+    author: String,
+    text: String,
+    /// Unix timestamp (seconds)
+    created_at: i64,
+}
+
+async fn quotes(
+    _: ApiClient,
+    State(state): State<AppState>,
+    Query(filter): Query<QuoteFilter>,
+) -> Result<Json<Vec<Quote>>, Error> {
+    let author = filter.author.unwrap_or_default();
+    let chat_id = filter.chat_id.unwrap_or_default();
+    let limit = filter.limit.unwrap_or(MAX_QUOTES).clamp(1, MAX_QUOTES);
+    let quotes = state
+        .quote_repo
+        .latest_quotes(&author, &chat_id, limit)
+        .await?
+        .into_iter()
+        .map(|q| Quote {
+            id: q.id,
+            chat_id: q.chat_id,
+            author: q.author,
+            text: q.text,
+            created_at: q.created_at,
+        })
+        .collect();
+
+    Ok(Json(quotes))
+}
+
+#[derive(Serialize)]
+struct Count {
+    name: String,
+    count: i64,
+}
+
+#[derive(Serialize)]
+struct Stats {
+    chats: i64,
+    quotes: i64,
+    quotes_by_author: Vec<Count>,
+    /// `None` if Directus could not be reached.
+    polls_by_member: Option<Vec<Count>>,
+}
+
+async fn stats(_: ApiClient, State(state): State<AppState>) -> Result<Json<Stats>, Error> {
+    let chats =
+        sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!: i64" FROM chats WHERE left_at IS NULL"#)
+            .fetch_one(state.db.as_ref())
+            .await?;
+    let quotes = state.quote_repo.count_quotes().await?;
+    let quotes_by_author = state
+        .quote_repo
+        .quote_counts_by_author()
+        .await?
+        .into_iter()
+        .map(|(name, count)| Count { name, count })
+        .collect();
+    let polls_by_member = match committee_repository().get().await {
+        Ok(committee) => Some(
+            committee
+                .into_iter()
+                .map(|c| Count {
+                    name: c.name,
+                    count: c.poll_count as i64,
+                })
+                .collect(),
+        ),
+        Err(e) => {
+            log::error!("Could not fetch committee: {e:#?}");
+            None
+        }
+    };
+
+    Ok(Json(Stats {
+        chats,
+        quotes,
+        quotes_by_author,
+        polls_by_member,
+    }))
+}
+
+/// Serves the API at the given address.
+pub async fn serve_api(address: String, state: AppState) {
+    let app = Router::new()
+        .route("/api/chats", get(chats))
+        .route(
+            "/api/authorizations",
+            get(authorizations)
+                .post(authorization_add)
+                .delete(authorization_remove),
+        )
+        .route("/api/quotes", get(quotes))
+        .route("/api/stats", get(stats))
+        .with_state(state);
+
+    serve("API", &address, app).await;
+}
@@ -0,0 +1,264 @@
+//! Two-person approval of the critical actions (`/restore`, `/import`, `/adminremove`). The
+//! request is sent in private to the other admins, except the one targeted by the action, and the
+//! action only runs once one of them approves it within [`APPROVAL_WINDOW`]. Without any other
+//! admin, the superadmin approves it, and without a superadmin either, the action is refused.
+
+use std::{error::Error, sync::Arc};
+
+use sqlx::SqlitePool;
+use teloxide::{
+    payloads::SendMessageSetters,
+    requests::Requester,
+    types::{CallbackQuery, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, User},
+    Bot,
+};
+
+use crate::{
+    audit::audit,
+    callbacks::{CallbackData, CallbackResult, APPROVAL},
+    cmd_authentication::remove_admin,
+    cmd_backup::restore_backup,
+    cmd_export::import_dump,
+    config::config,
+    dates::{chat_timezone, now, now_in},
+    db::admins::AdminRepo,
+    format::{bold, escape, HtmlMessages},
+    outbox::Queued,
+};
+
+/// Delay for another admin to approve a request, in seconds.
+const APPROVAL_WINDOW: i64 = 30 * 60;
+
+/// Runs the action, and returns the outcome to display.
+async fn execute(
+    bot: &Bot,
+    db: &SqlitePool,
+    action: &str,
+    payload: &str,
+    actor: &str,
+) -> Result<String, Box<dyn Error + Send + Sync>> {
+    match action {
+        "restore" => restore_backup(bot, db, actor, payload).await,
+        "import" => import_dump(bot, db, actor, payload).await,
+        "admin_remove" => Ok(remove_admin(db, actor, payload).await?),
+        _ => Ok(format!("Action inconnue: {}", action)),
+    }
+}
+
+/// Name of the admin targeted by the action, who may not approve it.
+fn target<'a>(action: &str, payload: &'a str) -> Option<&'a str> {
+    (action == "admin_remove").then_some(payload)
+}
+
+fn actor(user: &User) -> String {
+    format!("{} ({})", user.full_name(), user.id)
+}
+
+/// Asks the other admins, or the superadmin if there is none, to approve the action. The progress
+/// of the request is sent to `chat_id`.
+pub async fn require_approval(
+    bot: &Bot,
+    db: &SqlitePool,
+    requester: &User,
+    chat_id: ChatId,
+    action: &str,
+    payload: &str,
+    description: &str,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let requester_id = requester.id.to_string();
+    let (excluded, approvers) = db.admins().await?.into_iter().partition::<Vec<_>, _>(|a| {
+        a.telegram_id == requester_id || Some(a.name.as_str()) == target(action, payload)
+    });
+    let mut approvers = approvers
+        .into_iter()
+        .filter_map(|a| a.telegram_id.parse::<i64>().ok().map(ChatId))
+        .collect::<Vec<_>>();
+    // The superadmin stands in for the missing second admin, unless they are the requester or
+    // the target themselves
+    if approvers.is_empty() {
+        approvers.extend(
+            config()
+                .superadmin_id
+                .filter(|id| {
+                    let id = id.to_string();
+                    id != requester_id && !excluded.iter().any(|a| a.telegram_id == id)
+                })
+                .map(|id| ChatId(id as i64)),
+        );
+    }
+    if approvers.is_empty() {
+        audit(db, &actor(requester), "approval_refused", description).await;
+        bot.send_message(
+            chat_id,
+            "Action refusée: aucun autre admin ne peut l'approuver. Ajoute un admin, ou configure \
+            SUPERADMIN_ID.",
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    let timestamp = now().timestamp();
+    let expires_at = timestamp + APPROVAL_WINDOW;
+    let requested_by = actor(requester);
+    let target_chat = chat_id.to_string();
+    let id = sqlx::query!(
+        r#"INSERT INTO pending_approvals("action", payload, "description", requested_by,
+            requester_id, chat_id, created_at, expires_at)
+        VALUES($1, $2, $3, $4, $5, $6, $7, $8)"#,
+        action,
+        payload,
+        description,
+        requested_by,
+        requester_id,
+        target_chat,
+        timestamp,
+        expires_at
+    )
+    .execute(db)
+    .await?
+    .last_insert_rowid();
+    audit(db, &requested_by, "approval_request", description).await;
+
+    // The deadline is displayed in the timezone of each chat
+    let deadline = |timezone| {
+        (now_in(timezone) + chrono::Duration::seconds(APPROVAL_WINDOW))
+            .format("%H:%M")
+            .to_string()
+    };
+    let keyboard = InlineKeyboardMarkup::new([[
+        InlineKeyboardButton::callback(
+            "✅ Approuver",
+            CallbackData::format(APPROVAL, format!("approve:{}", id)),
+        ),
+        InlineKeyboardButton::callback(
+            "❌ Refuser",
+            CallbackData::format(APPROVAL, format!("reject:{}", id)),
+        ),
+    ]]);
+    for approver in approvers {
+        let text = format!(
+            "🔐 {} demande à {}.\nUn autre admin doit approuver avant {}.",
+            bold(&requester.full_name()),
+            escape(description),
+            deadline(chat_timezone(db, approver).await)
+        );
+        if let Err(e) = bot
+            .send_html(approver, &text)
+            .reply_markup(keyboard.clone())
+            .queued()
+            .await
+        {
+            log::warn!(
+                "Could not send approval #{} to admin {}: {:?}",
+                id,
+                approver,
+                e
+            );
+        }
+    }
+
+    bot.send_message(
+        chat_id,
+        format!(
+            "En attente de l'approbation d'un autre admin, avant {}",
+            deadline(chat_timezone(db, chat_id).await)
+        ),
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
+
+/// Buttons of the approval requests: runs or cancels the action.
+pub async fn resolve_approval(
+    bot: Bot,
+    query: CallbackQuery,
+    data: CallbackData,
+    db: Arc<SqlitePool>,
+    admins: Arc<dyn AdminRepo>,
+) -> CallbackResult {
+    let is_superadmin = config().superadmin_id == Some(query.from.id.0);
+    if !is_superadmin && !admins.is_admin(query.from.id).await {
+        return Ok(Some("Seuls les admins peuvent approuver".to_owned()));
+    }
+    let Some((decision, Ok(id))) = data
+        .payload
+        .split_once(':')
+        .map(|(decision, id)| (decision, id.parse::<i64>()))
+    else {
+        return Ok(None);
+    };
+    let approved = decision == "approve";
+
+    let approver = actor(&query.from);
+    let approver_id = query.from.id.to_string();
+    let timestamp = now().timestamp();
+    let pending = sqlx::query!(
+        r#"SELECT "action", payload FROM pending_approvals WHERE id = $1"#,
+        id
+    )
+    .fetch_optional(db.as_ref())
+    .await?;
+    let name = admins
+        .admins()
+        .await?
+        .into_iter()
+        .find(|a| a.telegram_id == approver_id)
+        .map(|a| a.name);
+    if pending.is_some_and(|p| name.is_some() && target(&p.action, &p.payload) == name.as_deref()) {
+        return Ok(Some(
+            "Tu ne peux pas approuver une action qui te vise".to_owned(),
+        ));
+    }
+    // The requester cannot approve their own request, even from another button
+    let Some(request) = sqlx::query!(
+        r#"UPDATE pending_approvals SET resolved_by = $1, resolved_at = $2, approved = $3
+        WHERE id = $4 AND resolved_at IS NULL AND expires_at > $2 AND requester_id != $5
+        RETURNING "action", payload, "description", requested_by, chat_id"#,
+        approver,
+        timestamp,
+        approved,
+        id,
+        approver_id
+    )
+    .fetch_optional(db.as_ref())
+    .await?
+    else {
+        return Ok(Some(
+            "Cette demande a déjà été traitée, a expiré, ou est la tienne".to_owned(),
+        ));
+    };
+
+    if let Some(message) = &query.message {
+        bot.edit_message_reply_markup(message.chat.id, message.id)
+            .queued()
+            .await?;
+    }
+    let chat_id = request.chat_id.parse::<i64>().map(ChatId).ok();
+    let outcome = if approved {
+        let details = format!("{} (par {})", request.description, request.requested_by);
+        audit(db.as_ref(), &approver, "approval_granted", &details).await;
+        let actor = format!("{}, approuvé par {}", request.requested_by, approver);
+        execute(&bot, db.as_ref(), &request.action, &request.payload, &actor).await?
+    } else {
+        audit(
+            db.as_ref(),
+            &approver,
+            "approval_rejected",
+            &request.description,
+        )
+        .await;
+        format!(
+            "Demande refusée par {}: {}",
+            query.from.full_name(),
+            request.description
+        )
+    };
+    if let Some(chat_id) = chat_id {
+        bot.send_message(chat_id, &outcome).queued().await?;
+    }
+
+    Ok(Some(outcome))
+}
@@ -0,0 +1,65 @@
+use serde::Serialize;
+use sqlx::SqlitePool;
+use teloxide::types::Message;
+
+use crate::dates::now;
+
+/// Name under which the author of the message appears in the audit log.
+pub fn actor(msg: &Message) -> String {
+    match msg.from() {
+        Some(user) => format!("{} ({})", user.full_name(), user.id),
+        None => format!("chat {}", msg.chat.id),
+    }
+}
+
+/// Records an administrative action in the audit log. Failures are only logged, so that they
+/// never prevent the action itself.
+pub async fn audit(db: &SqlitePool, actor: &str, action: &str, details: &str) {
+    record(db, actor, None, action, details, None).await
+}
+
+/// Records an action of the author of the message which `/undo` can revert, with its inverse
+/// `undo`.
+pub async fn audit_reversible(
+    db: &SqlitePool,
+    msg: &Message,
+    action: &str,
+    details: &str,
+    undo: &impl Serialize,
+) {
+    let actor_id = msg.from().map(|user| user.id.to_string());
+    let undo = serde_json::to_string(undo).ok();
+    record(db, &actor(msg), actor_id, action, details, undo).await
+}
+
+async fn record(
+    db: &SqlitePool,
+    actor: &str,
+    actor_id: Option<String>,
+    action: &str,
+    details: &str,
+    undo: Option<String>,
+) {
+    log::info!("[audit] {} {}: {}", actor, action, details);
+    let timestamp = now().timestamp();
+    if let Err(e) = sqlx::query!(
+        r#"INSERT INTO audit_log(created_at, actor, actor_id, "action", details, undo)
+        VALUES($1, $2, $3, $4, $5, $6)"#,
+        timestamp,
+        actor,
+        actor_id,
+        action,
+        details,
+        undo
+    )
+    .execute(db)
+    .await
+    {
+        log::error!(
+            "Could not record {} by {} in the audit log: {:?}",
+            action,
+            actor,
+            e
+        );
+    }
+}
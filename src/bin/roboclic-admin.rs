@@ -0,0 +1,185 @@
+//! Administration of the bot from the server shell, e.g. when the bot itself is down. Reads the
+//! same environment as the bot.
+
+use std::{env, error::Error, fs};
+
+use serde::Deserialize;
+use sqlx::SqlitePool;
+
+// The modules are shared with the bot, which uses more of them
+#[path = "../audit.rs"]
+#[allow(dead_code)]
+mod audit;
+#[path = "../config.rs"]
+#[allow(dead_code)]
+mod config;
+#[path = "../dates.rs"]
+#[allow(dead_code)]
+mod dates;
+#[path = "../db/mod.rs"]
+#[allow(dead_code)]
+mod db;
+#[path = "../directus.rs"]
+#[allow(dead_code)]
+mod directus;
+
+use audit::audit;
+use db::{admins::AdminRepo, authorizations::AuthorizationRepo};
+use directus::{get_committee, update_committee};
+
+const USAGE: &str = "Usage: roboclic-admin <command>
+
+Commands:
+    migrate                             Runs the pending migrations
+    admins                              Lists the admins
+    admin-add <telegram id> <name>      Adds an admin
+    admin-remove <name>                 Removes an admin
+    authorizations <chat id>            Lists the commands allowed in a chat
+    authorize <chat id> <command>       Allows a command in a chat
+    unauthorize <chat id> <command>     Forbids a command in a chat
+    committee                           Lists the committee members in Directus
+    committee-import <file>             Sets the poll counts of the committee members from a JSON
+                                        file: [{ \"name\": \"...\", \"poll_count\": 0 }, ...]";
+
+type AdminResult = Result<(), Box<dyn Error>>;
+
+/// Name under which the actions appear in the audit log.
+fn actor() -> String {
+    format!("cli:{}", env::var("USER").unwrap_or_default())
+}
+
+async fn admins(db: &SqlitePool) -> AdminResult {
+    for admin in db.admins().await? {
+        println!("{}\t{}", admin.telegram_id, admin.name);
+    }
+    Ok(())
+}
+
+async fn admin_add(db: &SqlitePool, telegram_id: &str, name: &str) -> AdminResult {
+    if telegram_id.parse::<i64>().is_err() {
+        return Err(format!("invalid Telegram id: {}", telegram_id).into());
+    }
+    let timestamp = dates::now().timestamp();
+    db.add_or_rename_admin(telegram_id, name, "cli", timestamp).await?;
+    let details = format!("{} ({})", name, telegram_id);
+    audit(db, &actor(), "admin_add", &details).await;
+    println!("{} is now admin", name);
+    Ok(())
+}
+
+async fn admin_remove(db: &SqlitePool, name: &str) -> AdminResult {
+    if db.remove_admins(name).await?.is_empty() {
+        return Err(format!("{} is not admin", name).into());
+    }
+    audit(db, &actor(), "admin_remove", name).await;
+    println!("{} is no longer admin", name);
+    Ok(())
+}
+
+async fn authorize(db: &SqlitePool, chat_id: &str, command: &str) -> AdminResult {
+    let command = command.trim_start_matches('/');
+    let known = sqlx::query!(
+        "SELECT COUNT(*) AS count FROM chats WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_one(db)
+    .await?
+    .count
+        > 0;
+    if !known {
+        return Err(format!("unknown chat {}, see /chats", chat_id).into());
+    }
+
+    if db.grant(chat_id, command).await? {
+        let details = format!("/{} in {}", command, chat_id);
+        audit(db, &actor(), "authorize", &details).await;
+    }
+    println!("/{} is allowed in {}", command, chat_id);
+    Ok(())
+}
+
+async fn unauthorize(db: &SqlitePool, chat_id: &str, command: &str) -> AdminResult {
+    let command = command.trim_start_matches('/');
+    if db.revoke(chat_id, command).await? {
+        let details = format!("/{} in {}", command, chat_id);
+        audit(db, &actor(), "unauthorize", &details).await;
+    }
+    println!("/{} is forbidden in {}", command, chat_id);
+    Ok(())
+}
+
+async fn committee() -> AdminResult {
+    for member in get_committee().await? {
+        println!("{}\t{}\t{}", member.id, member.name, member.poll_count);
+    }
+    Ok(())
+}
+
+async fn committee_import(db: &SqlitePool, path: &str) -> AdminResult {
+    #[derive(Deserialize)]
+    struct Entry {
+        name: String,
+        poll_count: i32,
+    }
+
+    let entries: Vec<Entry> = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let mut committee = get_committee().await?;
+    for entry in &entries {
+        match committee.iter_mut().find(|c| c.name == entry.name) {
+            Some(member) => member.poll_count = entry.poll_count,
+            None => eprintln!("{} is not a committee member in Directus", entry.name),
+        }
+    }
+    committee.retain(|c| entries.iter().any(|e| e.name == c.name));
+
+    let details = format!("{} member(s) from {}", committee.len(), path);
+    update_committee(committee).await;
+    audit(db, &actor(), "committee_import", &details).await;
+    println!("Imported {}", details);
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> AdminResult {
+    pretty_env_logger::init();
+
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    if matches!(args.as_slice(), [] | ["help" | "--help" | "-h", ..]) {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    let mut problems = config::check_config();
+    // The config can only be loaded once the variables are valid
+    if problems.is_empty() {
+        problems = config::check_storage().await;
+    }
+    if !problems.is_empty() {
+        return Err(format!("invalid configuration:\n - {}", problems.join("\n - ")).into());
+    }
+
+    let db = db::connect(&db::database_url()).await?;
+    match args.as_slice() {
+        ["migrate"] => {
+            sqlx::migrate!().run(&db).await?;
+            println!("Database up to date");
+            Ok(())
+        }
+        ["admins"] => admins(&db).await,
+        ["admin-add", telegram_id, name @ ..] if !name.is_empty() => {
+            admin_add(&db, telegram_id, &name.join(" ")).await
+        }
+        ["admin-remove", name @ ..] if !name.is_empty() => admin_remove(&db, &name.join(" ")).await,
+        ["authorizations", chat_id] => {
+            for command in db.chat_authorizations(chat_id).await? {
+                println!("/{}", command);
+            }
+            Ok(())
+        }
+        ["authorize", chat_id, command] => authorize(&db, chat_id, command).await,
+        ["unauthorize", chat_id, command] => unauthorize(&db, chat_id, command).await,
+        ["committee"] => committee().await,
+        ["committee-import", path] => committee_import(&db, path).await,
+        _ => Err(USAGE.into()),
+    }
+}
@@ -0,0 +1,146 @@
+use std::{error::Error, fmt::Display, ops::ControlFlow, sync::Arc};
+
+use teloxide::{
+    dispatching::DpHandlerDescription,
+    dptree::{
+        self,
+        di::{DependencyMap, DependencySupplier},
+        Handler, HandlerDescription,
+    },
+    payloads::AnswerCallbackQuerySetters,
+    requests::Requester,
+    types::CallbackQuery,
+    Bot,
+};
+
+use crate::{
+    cmd_poll::PollState,
+    errors::report_error,
+    i18n::{tr, Lang},
+    HandlerResult,
+};
+
+// Actions of the inline keyboard buttons. The data of a button is formatted as `action:payload`.
+pub const REMINDER_CANCEL: &str = "reminder_cancel";
+pub const DOODLE_VOTE: &str = "doodle";
+pub const TODO_DONE: &str = "todo_done";
+pub const POLL_TARGET: &str = "poll_target";
+pub const QUOTE_TOO_LONG: &str = "quote_long";
+pub const NEWPOLL: &str = "newpoll";
+pub const BROADCAST: &str = "broadcast";
+pub const RESTORE: &str = "restore";
+pub const QUOTE_REPORT: &str = "quote_report";
+pub const QUOTE_REPORT_RESOLVE: &str = "report_resolve";
+pub const SETTINGS: &str = "settings";
+pub const FORWARD_QUIZ: &str = "forward_quiz";
+pub const EVENT_REGISTRATION: &str = "event_registration";
+pub const NEWSLETTER: &str = "newsletter";
+pub const APPROVAL: &str = "approval";
+pub const MEMBER_CHALLENGE: &str = "member_challenge";
+pub const HELP: &str = "help";
+pub const WIZARD: &str = "wizard";
+pub const IMPORT: &str = "import";
+
+/// Result of a callback handler: the optional text displayed to the user as a notification.
+pub type CallbackResult = Result<Option<String>, Box<dyn Error + Send + Sync>>;
+pub type CallbackHandler = Handler<'static, DependencyMap, CallbackResult, DpHandlerDescription>;
+
+/// Data of a callback query, parsed from `action:payload`.
+#[derive(Clone, Debug)]
+pub struct CallbackData {
+    pub action: String,
+    pub payload: String,
+}
+
+impl CallbackData {
+    /// Formats the data of a button.
+    pub fn format(action: &str, payload: impl Display) -> String {
+        format!("{}:{}", action, payload)
+    }
+
+    fn parse(data: &str) -> Option<Self> {
+        let (action, payload) = data.split_once(':')?;
+        Some(Self {
+            action: action.to_owned(),
+            payload: payload.to_owned(),
+        })
+    }
+
+    /// Parses the payload, for actions whose payload is an id.
+    pub fn id(&self) -> Option<i64> {
+        self.payload.parse().ok()
+    }
+}
+
+/// Filters the callback queries of the given action, and injects their [`CallbackData`].
+pub fn action(name: &'static str) -> CallbackHandler {
+    dptree::filter_map(move |query: CallbackQuery| {
+        query
+            .data
+            .as_deref()
+            .and_then(CallbackData::parse)
+            .filter(|d| d.action == name)
+    })
+}
+
+/// Rejects the callbacks of a dialogue keyboard coming from another user than the one who started
+/// the dialogue.
+pub fn reject_non_initiators() -> CallbackHandler {
+    dptree::filter(|query: CallbackQuery, state: PollState| {
+        state.initiator().is_some_and(|id| id != query.from.id)
+    })
+    .endpoint(|lang: Lang| async move {
+        Ok(Some(tr!(
+            lang,
+            "Seule la personne ayant lancé la commande peut répondre",
+            "Only the person who started the command can answer"
+        )))
+    })
+}
+
+/// Runs the callback routes, and answers every callback query (with the text returned by the
+/// handler, if any), so that the Telegram client stops its loading indicator. Callbacks which do
+/// not match any route (e.g. a keyboard of an expired dialogue) are answered as well.
+pub fn answer_callbacks(
+    routes: CallbackHandler,
+) -> Handler<'static, DependencyMap, HandlerResult, DpHandlerDescription> {
+    dptree::from_fn_with_description(
+        DpHandlerDescription::entry(),
+        move |deps: DependencyMap, _| {
+            let routes = routes.clone();
+            async move {
+                let bot: Arc<Bot> = deps.get();
+                let query: Arc<CallbackQuery> = deps.get();
+                let lang: Arc<Lang> = deps.get();
+
+                let result = match routes.dispatch(deps).await {
+                    ControlFlow::Break(result) => result,
+                    ControlFlow::Continue(_) => {
+                        log::debug!("Unhandled callback data: {:?}", query.data);
+                        Ok(Some(tr!(
+                            *lang,
+                            "Ce bouton n'est plus actif",
+                            "This button is no longer active"
+                        )))
+                    }
+                };
+
+                let text = match &result {
+                    Ok(text) => text.clone(),
+                    Err(e) => Some(report_error(e.as_ref(), *lang)),
+                };
+                let answer = bot.answer_callback_query(query.id.clone());
+                let answer = match text {
+                    Some(text) => answer.text(text).await,
+                    None => answer.await,
+                };
+                if let Err(e) = answer {
+                    log::error!("Could not answer callback query: {:?}", e);
+                }
+
+                // Errors are already reported to the user and logged
+                ControlFlow::Break(Ok(()))
+            }
+        },
+    )
+}
@@ -0,0 +1,220 @@
+//! Publications to the Telegram channels.
+//!
+//! A channel is managed from its linked discussion group: the admins use `/publish` and
+//! `/scheduleadd channel ...` in the group, and the posts go to the channel, with the content
+//! (e.g. the quotes) of the group. The commands posted in the channel itself are not answered,
+//! since any reply would be published to its subscribers.
+
+use chrono::Duration;
+use sqlx::SqlitePool;
+use teloxide::{
+    requests::Requester,
+    types::{ChatId, InlineKeyboardMarkup, Message},
+    Bot, RequestError,
+};
+
+use crate::{
+    audit::{actor, audit},
+    cmd_registrations::registration_keyboard,
+    cmd_schedules::post_payload,
+    dates::{chat_timezone, format_datetime, now, now_in},
+    db::quotes::QuoteRepo,
+    directus::{get_upcoming_events, Event},
+    format::{bold, escape, italic, MessageBuilder},
+    i18n::{chat_language, tr, Lang},
+    outbox::{Priority, Queued},
+    state::AppState,
+    HandlerResult,
+};
+
+/// Commands generating a publication, which can be posted or scheduled in a channel.
+pub const PUBLICATIONS: &[&str] = &["/quote", "/events", "/digest"];
+/// Period covered by the events announcement and the digest.
+const PUBLICATION_DAYS: i64 = 7;
+
+/// Channel linked to the discussion group, if any.
+pub async fn linked_channel(bot: &Bot, chat_id: ChatId) -> Result<Option<ChatId>, RequestError> {
+    let chat = bot.get_chat(chat_id).await?;
+    if !chat.is_supergroup() {
+        return Ok(None);
+    }
+    Ok(chat.linked_chat_id().map(ChatId))
+}
+
+/// Random quote of the chat.
+async fn quote_of_the_day(
+    db: &SqlitePool,
+    chat_id: ChatId,
+    lang: Lang,
+) -> Result<Option<String>, sqlx::Error> {
+    let quote = db.random_quote(&chat_id.to_string()).await?;
+
+    Ok(quote.map(|q| {
+        tr!(
+            lang,
+            "💬 {}\n\n« {} »\n— {}",
+            "💬 {}\n\n“{}”\n— {}",
+            bold(tr!(lang, "Citation du jour", "Quote of the day").as_str()),
+            escape(&q.text),
+            italic(&q.author)
+        )
+    }))
+}
+
+/// Events of the coming days with the lines listing them, `None` if there is none (or Directus is
+/// down).
+async fn upcoming_events(chat_id: ChatId, db: &SqlitePool) -> Option<Vec<(String, Event)>> {
+    let timezone = chat_timezone(db, chat_id).await;
+    let horizon = now() + Duration::days(PUBLICATION_DAYS);
+    let events = match get_upcoming_events().await {
+        Ok(events) => events,
+        Err(e) => {
+            log::error!("Could not fetch events: {e:#?}");
+            return None;
+        }
+    };
+
+    let lines = events
+        .into_iter()
+        .filter_map(|e| {
+            let start = e.start()?.with_timezone(&timezone);
+            (start >= now_in(timezone) && start <= horizon).then(|| {
+                let line = format!(
+                    "{} {}{}",
+                    format_datetime(&start),
+                    bold(&e.title),
+                    e.location
+                        .as_ref()
+                        .map(|l| format!(" ({})", escape(l)))
+                        .unwrap_or_default()
+                );
+                (line, e)
+            })
+        })
+        .collect::<Vec<_>>();
+    (!lines.is_empty()).then_some(lines)
+}
+
+/// Announcement of the events of the week, with the buttons to register to the registrable ones.
+async fn events_announcement(
+    db: &SqlitePool,
+    chat_id: ChatId,
+    lang: Lang,
+) -> Option<(String, Option<InlineKeyboardMarkup>)> {
+    let events = upcoming_events(chat_id, db).await?;
+    let text = MessageBuilder::new()
+        .title(&tr!(
+            lang,
+            "📅 Événements de la semaine",
+            "📅 Events of the week"
+        ))
+        .items(events.iter().map(|(line, _)| line.clone()))
+        .build();
+    let keyboard = registration_keyboard(&events.iter().map(|(_, e)| e).collect::<Vec<_>>(), lang);
+    Some((text, keyboard))
+}
+
+/// Summary of the past week of the chat, followed by the coming events.
+async fn digest(db: &SqlitePool, chat_id: ChatId, lang: Lang) -> Result<String, sqlx::Error> {
+    let since = (now() - Duration::days(PUBLICATION_DAYS)).timestamp();
+    let authors = db.chat_quote_counts(&chat_id.to_string(), since).await?;
+
+    let quotes: i64 = authors.iter().map(|(_, count)| count).sum();
+    let mut text = MessageBuilder::new()
+        .title(&tr!(lang, "📰 Résumé de la semaine", "📰 Weekly digest"))
+        .separator()
+        .html(match authors.first() {
+            Some((author, count)) => tr!(
+                lang,
+                "{} nouvelle(s) citation(s), dont {} de {}",
+                "{} new quote(s), {} of which by {}",
+                quotes,
+                count,
+                bold(author)
+            ),
+            None => tr!(lang, "Aucune nouvelle citation", "No new quote"),
+        });
+    if let Some(events) = upcoming_events(chat_id, db).await {
+        text = text
+            .separator()
+            .title(&tr!(lang, "À venir", "Coming up"))
+            .items(events.into_iter().map(|(line, _)| line));
+    }
+
+    Ok(text.build())
+}
+
+/// HTML text of one of the [`PUBLICATIONS`], with the content of the chat `source`, and its
+/// buttons if any. `None` if there is nothing to publish.
+pub async fn publication(
+    db: &SqlitePool,
+    source: ChatId,
+    command: &str,
+) -> Result<Option<(String, Option<InlineKeyboardMarkup>)>, sqlx::Error> {
+    let lang = chat_language(db, source).await;
+    match command {
+        "/quote" => Ok(quote_of_the_day(db, source, lang)
+            .await?
+            .map(|text| (text, None))),
+        "/events" => Ok(events_announcement(db, source, lang).await),
+        "/digest" => Ok(Some((digest(db, source, lang).await?, None))),
+        _ => Ok(None),
+    }
+}
+
+/// `/publish <message or /quote, /events, /digest>` posts in the channel linked to the group.
+pub async fn publish(bot: Bot, msg: Message, payload: String, state: AppState) -> HandlerResult {
+    let payload = payload.trim();
+    if payload.is_empty() || (payload.starts_with('/') && !PUBLICATIONS.contains(&payload)) {
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "Utilisation: /publish <message ou {}>, dans le groupe de discussion du canal",
+                PUBLICATIONS.join(", ")
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+    let Some(channel) = linked_channel(&bot, msg.chat.id).await? else {
+        bot.send_message(
+            msg.chat.id,
+            "Ce groupe n'est le groupe de discussion d'aucun canal",
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    post_payload(
+        &bot,
+        &state.outbox,
+        state.db.as_ref(),
+        msg.chat.id,
+        channel,
+        None,
+        payload,
+        Priority::Interactive,
+    )
+    .await?;
+    let details = format!("{} in {}", payload, channel);
+    audit(state.db.as_ref(), &actor(&msg), "publish", &details).await;
+    bot.send_message(msg.chat.id, "Publié dans le canal")
+        .queued()
+        .await?;
+
+    Ok(())
+}
+
+/// Channel posts are only recorded in the registry (before this handler), the commands are
+/// ignored.
+pub async fn channel_post(msg: Message) -> HandlerResult {
+    if msg.text().is_some_and(|t| t.starts_with('/')) {
+        log::debug!(
+            "Ignoring command posted in channel {}, use its discussion group",
+            msg.chat.id
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,265 @@
+use std::sync::Arc;
+
+use sqlx::{SqliteConnection, SqlitePool};
+use teloxide::{
+    requests::Requester,
+    types::{Chat, ChatId, ChatMemberUpdated, Message, MessageCommon, MessageKind},
+    Bot,
+};
+
+use crate::{
+    dates::now,
+    db::{authorizations::authorization_cache, retry_busy},
+    HandlerResult,
+};
+
+/// Tables keyed by `chat_id`. Those bound to the `chats` registry by a foreign key (authorizations,
+/// schedules, transport_stops, chat_settings) are listed as well, so that a remap also merges their
+/// rows into an already known chat.
+const CHAT_TABLES: &[&str] = &[
+    "authorizations",
+    "schedules",
+    "transport_stops",
+    "chat_settings",
+    "templates",
+    "reminders",
+    "doodles",
+    "todos",
+    "countdowns",
+    "random_picks",
+    "debts",
+    "quotes",
+    "karma",
+    "karma_votes",
+    "expenses",
+    "loans",
+    "links",
+    "bureau_polls",
+    "dialogues",
+    "aliases",
+    "reaction_votes",
+    "tournaments",
+    "quote_reports",
+    "poll_results",
+    "sent_polls",
+    "chat_members",
+    "message_authors",
+    "reaction_stats",
+    "shames",
+    "quote_elections",
+    "quote_awards",
+    "newsletter_targets",
+    "member_challenges",
+];
+
+/// Forum topic in which the message was sent. The replies of a usual group also have a thread
+/// id, which is not a topic.
+pub fn topic(msg: &Message) -> Option<i32> {
+    match msg.kind {
+        MessageKind::Common(MessageCommon {
+            is_topic_message: true,
+            ..
+        }) => msg.thread_id,
+        _ => None,
+    }
+}
+
+fn chat_title(chat: &Chat) -> Option<String> {
+    chat.title()
+        .map(str::to_owned)
+        .or_else(|| chat.username().map(|u| format!("@{}", u)))
+}
+
+fn chat_kind(chat: &Chat) -> &'static str {
+    if chat.is_private() {
+        "private"
+    } else if chat.is_group() {
+        "group"
+    } else if chat.is_supergroup() {
+        "supergroup"
+    } else {
+        "channel"
+    }
+}
+
+/// Records the chat of every received message in the registry. Also follows the migration of a
+/// group to a supergroup, which changes its id.
+pub async fn register_chat(msg: Message, db: Arc<SqlitePool>) {
+    let chat_id = msg.chat.id.to_string();
+    let title = chat_title(&msg.chat);
+    let kind = chat_kind(&msg.chat);
+    let timestamp = now().timestamp();
+
+    if let Err(e) = sqlx::query!(
+        "INSERT INTO chats(chat_id, title, kind, last_seen) VALUES($1, $2, $3, $4)
+        ON CONFLICT(chat_id) DO UPDATE SET title = COALESCE(excluded.title, title), kind = excluded.kind,
+        last_seen = excluded.last_seen, left_at = NULL",
+        chat_id,
+        title,
+        kind,
+        timestamp
+    )
+    .execute(db.as_ref())
+    .await
+    {
+        log::error!("Could not register chat {}: {:?}", chat_id, e);
+    }
+
+    if let Some(new_id) = msg.migrate_to_chat_id() {
+        log::info!("Chat {} migrated to {}", msg.chat.id, new_id);
+        if let Err(e) = remap_chat(db.as_ref(), msg.chat.id, new_id).await {
+            log::error!(
+                "Could not remap chat {} to {}: {:?}",
+                msg.chat.id,
+                new_id,
+                e
+            );
+        }
+    }
+}
+
+/// Records the authors of the messages of the groups, with the name they currently display.
+pub async fn register_member(msg: Message, db: Arc<SqlitePool>) {
+    let Some(user) = msg.from().filter(|u| !u.is_bot) else {
+        return;
+    };
+    if msg.chat.is_private() || msg.chat.is_channel() {
+        return;
+    }
+
+    let chat_id = msg.chat.id.to_string();
+    let user_id = user.id.to_string();
+    let user_name = user.full_name();
+    let timestamp = now().timestamp();
+    if let Err(e) = sqlx::query!(
+        "INSERT INTO chat_members(chat_id, user_id, user_name, last_seen) VALUES($1, $2, $3, $4)
+        ON CONFLICT(chat_id, user_id) DO UPDATE SET user_name = excluded.user_name,
+        last_seen = excluded.last_seen",
+        chat_id,
+        user_id,
+        user_name,
+        timestamp
+    )
+    .execute(db.as_ref())
+    .await
+    {
+        log::error!("Could not register member of {}: {:?}", chat_id, e);
+    }
+}
+
+/// Tracks the chats the bot is added to or removed from, along with their member count.
+pub async fn track_membership(
+    bot: Bot,
+    update: ChatMemberUpdated,
+    db: Arc<SqlitePool>,
+) -> HandlerResult {
+    let chat_id = update.chat.id.to_string();
+    let title = chat_title(&update.chat);
+    let kind = chat_kind(&update.chat);
+    let timestamp = now().timestamp();
+
+    let (member_count, left_at) = if update.new_chat_member.is_present() {
+        log::info!("Added to chat {} ({:?})", chat_id, title);
+        let count = match bot.get_chat_member_count(update.chat.id).await {
+            Ok(count) => Some(count as i64),
+            Err(e) => {
+                log::warn!("Could not get the member count of {}: {:?}", chat_id, e);
+                None
+            }
+        };
+        (count, None)
+    } else {
+        log::info!("Removed from chat {} ({:?})", chat_id, title);
+        (None, Some(timestamp))
+    };
+
+    sqlx::query!(
+        "INSERT INTO chats(chat_id, title, kind, member_count, last_seen, left_at) VALUES($1, $2, $3, $4, $5, $6)
+        ON CONFLICT(chat_id) DO UPDATE SET title = COALESCE(excluded.title, title), kind = excluded.kind,
+        member_count = COALESCE(excluded.member_count, member_count), left_at = excluded.left_at",
+        chat_id,
+        title,
+        kind,
+        member_count,
+        timestamp,
+        left_at
+    )
+    .execute(db.as_ref())
+    .await?;
+
+    Ok(())
+}
+
+/// Moves all the data of the chat `from` to the chat `to`. When both chats have a row for the
+/// same key (e.g. a default transport stop), the one of `to` is kept. Returns the number of moved
+/// rows.
+pub async fn remap_chat(db: &SqlitePool, from: ChatId, to: ChatId) -> Result<u64, sqlx::Error> {
+    let (from, to) = (&from.to_string(), &to.to_string());
+
+    let moved = retry_busy(|| async move {
+        let mut tx = db.begin().await?;
+        sqlx::query!(
+            "INSERT OR IGNORE INTO chats(chat_id, title, kind, member_count, last_seen, left_at, language, timezone)
+            SELECT $1, title, kind, member_count, last_seen, left_at, language, timezone FROM chats WHERE chat_id = $2",
+            to,
+            from
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let mut moved = 0;
+        for table in CHAT_TABLES {
+            moved += sqlx::query(&format!(
+                "UPDATE OR IGNORE {} SET chat_id = $1 WHERE chat_id = $2",
+                table
+            ))
+            .bind(to)
+            .bind(from)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+
+        // The conflicting rows left behind are removed along with the old chat
+        delete_chat(&mut tx, from).await?;
+
+        tx.commit().await?;
+        Ok(moved)
+    })
+    .await?;
+    authorization_cache().invalidate(from).await;
+    authorization_cache().invalidate(to).await;
+
+    Ok(moved)
+}
+
+/// Deletes all the data of the chat. Returns the number of deleted rows.
+pub async fn purge_chat(db: &SqlitePool, chat_id: ChatId) -> Result<u64, sqlx::Error> {
+    let chat_id = &chat_id.to_string();
+    let deleted = retry_busy(|| async move {
+        let mut tx = db.begin().await?;
+        let deleted = delete_chat(&mut tx, chat_id).await?;
+        tx.commit().await?;
+        Ok(deleted)
+    })
+    .await?;
+    authorization_cache().invalidate(chat_id).await;
+
+    Ok(deleted)
+}
+
+async fn delete_chat(conn: &mut SqliteConnection, chat_id: &str) -> Result<u64, sqlx::Error> {
+    let mut deleted = 0;
+    for table in CHAT_TABLES {
+        deleted += sqlx::query(&format!("DELETE FROM {} WHERE chat_id = $1", table))
+            .bind(chat_id)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+    }
+    sqlx::query!("DELETE FROM chats WHERE chat_id = $1", chat_id)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(deleted)
+}
@@ -0,0 +1,219 @@
+//! Admin invitations. `/admininvite <name>` generates a `/start` deep link, signed with the admin
+//! token and valid for a day, which makes admin the first user opening it in private with the
+//! bot. The links are single-use, and stop working when the admin token is rotated.
+
+use std::sync::Arc;
+
+use hmac::{Hmac, Mac};
+use rand::{thread_rng, RngCore};
+use sha2::Sha256;
+use sqlx::SqlitePool;
+use teloxide::{requests::Requester, types::Message, Bot};
+
+use crate::{
+    admin_token::admin_token,
+    audit::{actor, audit},
+    crypto::blind_index,
+    dates::now,
+    db::admins::AdminRepo,
+    format::{bold, escape, link, HtmlMessages},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    HandlerResult,
+};
+
+/// Validity of the invitations, in seconds.
+const INVITE_VALIDITY: i64 = 24 * 60 * 60;
+/// Bytes of the signature kept in the payload, which Telegram limits to 64 characters.
+const SIGNATURE_BYTES: usize = 12;
+
+fn signer() -> Hmac<Sha256> {
+    Hmac::<Sha256>::new_from_slice(admin_token().as_bytes()).expect("HMAC accepts any key size")
+}
+
+/// Deep link payload `<nonce>-<expiration>-<signature>`.
+fn sign(nonce: &str, expires_at: i64) -> String {
+    let mut mac = signer();
+    mac.update(format!("{}-{}", nonce, expires_at).as_bytes());
+    let signature = mac.finalize().into_bytes();
+    format!(
+        "{}-{}-{}",
+        nonce,
+        expires_at,
+        hex::encode(&signature[..SIGNATURE_BYTES])
+    )
+}
+
+/// Nonce of the payload, if its signature is valid and it has not expired.
+fn verify(payload: &str) -> Option<&str> {
+    let mut parts = payload.splitn(3, '-');
+    let (nonce, expires_at, signature) = (parts.next()?, parts.next()?, parts.next()?);
+    let signature = hex::decode(signature).ok()?;
+
+    let mut mac = signer();
+    mac.update(format!("{}-{}", nonce, expires_at).as_bytes());
+    mac.verify_truncated_left(&signature).ok()?;
+
+    (expires_at.parse::<i64>().ok()? > now().timestamp()).then_some(nonce)
+}
+
+/// `/admininvite <name>` sends a single-use link making admin the user who opens it, under the
+/// given name. Only in private, so that nobody else can use the link first.
+pub async fn admin_invite(
+    bot: Bot,
+    msg: Message,
+    name: String,
+    db: Arc<SqlitePool>,
+) -> HandlerResult {
+    let name = name.trim();
+    if name.is_empty() {
+        bot.send_message(msg.chat.id, "Utilisation: /admininvite <nom>")
+            .queued()
+            .await?;
+        return Ok(());
+    }
+    if !msg.chat.is_private() {
+        bot.send_message(
+            msg.chat.id,
+            "Les invitations ne peuvent être générées qu'en message privé avec le bot",
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+    let Some(username) = bot.get_me().await?.user.username else {
+        log::error!("The bot has no username, cannot generate an invitation link");
+        return Ok(());
+    };
+
+    let mut bytes = [0u8; 8];
+    thread_rng().fill_bytes(&mut bytes);
+    let nonce = hex::encode(bytes);
+    let expires_at = now().timestamp() + INVITE_VALIDITY;
+    let created_by = actor(&msg);
+    let created_by_id = msg.from().map(|user| user.id.to_string());
+    // Only the keyed hash of the nonce is stored, the links cannot be rebuilt from the database
+    let index = blind_index(&nonce);
+    sqlx::query!(
+        r#"INSERT INTO admin_invites(nonce, "name", created_by, created_by_id, expires_at)
+        VALUES($1, $2, $3, $4, $5)"#,
+        index,
+        name,
+        created_by,
+        created_by_id,
+        expires_at
+    )
+    .execute(db.as_ref())
+    .await?;
+    audit(db.as_ref(), &created_by, "admin_invite", name).await;
+
+    let url = format!(
+        "https://t.me/{}?start={}",
+        username,
+        sign(&nonce, expires_at)
+    );
+    bot.send_html(
+        msg.chat.id,
+        format!(
+            "Lien d'invitation admin pour {}, valable 24h et utilisable une seule fois:\n{}\n\nTransmets-le en privé à la personne concernée.",
+            bold(name),
+            link(&url, &escape(&url))
+        ),
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
+
+/// `/start [payload]`, sent by Telegram when a user opens the bot, possibly through an admin
+/// invitation link.
+pub async fn start(
+    bot: Bot,
+    msg: Message,
+    payload: String,
+    db: Arc<SqlitePool>,
+    admins: Arc<dyn AdminRepo>,
+    lang: Lang,
+) -> HandlerResult {
+    let payload = payload.trim();
+    if payload.is_empty() || !msg.chat.is_private() {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Bonjour ! Utilise /help pour voir les commandes disponibles.",
+                "Hello! Use /help to list the available commands."
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+
+    let Some(nonce) = verify(payload) else {
+        audit(
+            db.as_ref(),
+            &actor(&msg),
+            "authenticate_failed",
+            "invalid invitation",
+        )
+        .await;
+        bot.send_message(msg.chat.id, "Ce lien d'invitation est invalide ou a expiré")
+            .queued()
+            .await?;
+        return Ok(());
+    };
+
+    // Consumed whatever happens next, so that the link cannot be replayed
+    let timestamp = now().timestamp();
+    let index = blind_index(nonce);
+    let Some(invite) = sqlx::query!(
+        r#"UPDATE admin_invites SET used_at = $1
+        WHERE nonce = $2 AND used_at IS NULL AND expires_at > $1 RETURNING "name""#,
+        timestamp,
+        index
+    )
+    .fetch_optional(db.as_ref())
+    .await?
+    else {
+        audit(
+            db.as_ref(),
+            &actor(&msg),
+            "authenticate_failed",
+            "used invitation",
+        )
+        .await;
+        bot.send_message(msg.chat.id, "Ce lien d'invitation a déjà été utilisé")
+            .queued()
+            .await?;
+        return Ok(());
+    };
+
+    let (id, chat_id) = (user.id.to_string(), msg.chat.id.to_string());
+    let added = admins
+        .add_admin(&id, &invite.name, "invitation", Some(&chat_id), timestamp)
+        .await?;
+    if !added {
+        bot.send_message(msg.chat.id, "Tu es déjà admin")
+            .queued()
+            .await?;
+        return Ok(());
+    }
+
+    audit(db.as_ref(), &actor(&msg), "admin_add", &invite.name).await;
+    bot.send_html(
+        msg.chat.id,
+        format!(
+            "Authentification réussie ! Tu es admin sous le nom {}",
+            bold(&invite.name)
+        ),
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+use teloxide::{types::Message, Bot};
+
+use crate::{
+    aliases::{alias_command, is_command, set_alias},
+    audit::audit_reversible,
+    cmd_undo::Undo,
+    format::{escape, HtmlMessages, MessageBuilder},
+    outbox::Queued,
+    HandlerResult,
+};
+
+const USAGE: &str = "Utilisation: /aliasadd <alias> <commande>, e.g. /aliasadd /b bureau";
+/// Maximum length of a command name accepted by Telegram.
+const MAX_ALIAS_LENGTH: usize = 32;
+
+/// Command name without its leading `/`, lowercased.
+fn normalize(name: &str) -> String {
+    name.trim_start_matches('/').to_lowercase()
+}
+
+fn is_valid_alias(alias: &str) -> bool {
+    !alias.is_empty()
+        && alias.len() <= MAX_ALIAS_LENGTH
+        && alias.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+pub async fn alias_add(bot: Bot, msg: Message, args: String, db: Arc<SqlitePool>) -> HandlerResult {
+    let words = args.split_whitespace().map(normalize).collect::<Vec<_>>();
+    let text = match &words[..] {
+        [alias, _] if !is_valid_alias(alias) => format!(
+            "L'alias doit contenir au plus {} lettres, chiffres ou _",
+            MAX_ALIAS_LENGTH
+        ),
+        [alias, _] if is_command(alias) => {
+            format!("/{} est déjà une commande du bot", escape(alias))
+        }
+        [_, command] if !is_command(command) => {
+            format!("/{} n'est pas une commande du bot", escape(command))
+        }
+        [alias, command] => {
+            let chat_id = msg.chat.id.to_string();
+            let previous = alias_command(db.as_ref(), &chat_id, alias).await?;
+            set_alias(db.as_ref(), &chat_id, alias, Some(command)).await?;
+
+            let details = format!("/{} for /{} in {}", alias, command, chat_id);
+            let undo = Undo::Alias {
+                chat_id,
+                alias: alias.clone(),
+                command: Some(command.clone()),
+                previous,
+            };
+            audit_reversible(db.as_ref(), &msg, "alias_add", &details, &undo).await;
+            format!(
+                "/{} est désormais un raccourci pour /{}",
+                escape(alias),
+                escape(command)
+            )
+        }
+        _ => escape(USAGE),
+    };
+
+    bot.send_html(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
+
+pub async fn alias_remove(
+    bot: Bot,
+    msg: Message,
+    alias: String,
+    db: Arc<SqlitePool>,
+) -> HandlerResult {
+    let alias = normalize(alias.trim());
+    let chat_id = msg.chat.id.to_string();
+    let removed = alias_command(db.as_ref(), &chat_id, &alias).await?;
+    if let Some(command) = &removed {
+        set_alias(db.as_ref(), &chat_id, &alias, None).await?;
+
+        let details = format!("/{} in {}", alias, chat_id);
+        let undo = Undo::Alias {
+            chat_id,
+            alias: alias.clone(),
+            command: None,
+            previous: Some(command.clone()),
+        };
+        audit_reversible(db.as_ref(), &msg, "alias_remove", &details, &undo).await;
+    }
+
+    bot.send_html(
+        msg.chat.id,
+        if removed.is_some() {
+            format!("Alias /{} supprimé", escape(&alias))
+        } else {
+            format!("Aucun alias /{} dans ce groupe", escape(&alias))
+        },
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
+
+pub async fn aliases(bot: Bot, msg: Message, db: Arc<SqlitePool>) -> HandlerResult {
+    let chat_id = msg.chat.id.to_string();
+    let aliases = sqlx::query!(
+        "SELECT alias, command FROM aliases WHERE chat_id = $1 ORDER BY alias",
+        chat_id
+    )
+    .fetch_all(db.as_ref())
+    .await?;
+
+    bot.send_html(
+        msg.chat.id,
+        if aliases.is_empty() {
+            "Aucun alias dans ce groupe".to_owned()
+        } else {
+            MessageBuilder::new()
+                .title("Alias de ce groupe")
+                .items(
+                    aliases
+                        .into_iter()
+                        .map(|a| format!("/{} → /{}", escape(&a.alias), escape(&a.command))),
+                )
+                .build()
+        },
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
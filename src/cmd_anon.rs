@@ -0,0 +1,231 @@
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use teloxide::{
+    requests::Requester,
+    types::{ChatId, Message},
+    Bot,
+};
+
+use crate::{
+    config::config,
+    crypto::{blind_index, derived_secret},
+    dates::now,
+    format::{code, escape, HtmlMessages},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    HandlerResult,
+};
+
+/// Maximum number of anonymous messages a user can send per window.
+const FLOOD_MAX_MESSAGES: i32 = 3;
+/// Duration of the flood protection window, in seconds.
+const FLOOD_WINDOW: i64 = 60 * 60;
+/// Length of the sender hash shown to the committee, used to block senders.
+const SENDER_HASH_LENGTH: usize = 12;
+
+/// Salted hash identifying a sender without revealing their Telegram id.
+fn sender_hash(user_id: u64) -> String {
+    let salt = config()
+        .anon_salt
+        .clone()
+        .unwrap_or_else(|| derived_secret("anon"));
+    let hash = Sha256::digest(format!("{}:{}", salt, user_id));
+    hex::encode(hash)[..SENDER_HASH_LENGTH].to_owned()
+}
+
+pub async fn anon(
+    bot: Bot,
+    msg: Message,
+    text: String,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> HandlerResult {
+    if !msg.chat.is_private() {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Pour rester anonyme, envoie /anon en message privé au bot",
+                "To stay anonymous, send /anon in a private message to the bot"
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    let Some(committee_chat) = config().committee_chat_id else {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Les messages anonymes ne sont pas configurés",
+                "Anonymous messages are not configured"
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    let (Some(user), text) = (msg.from(), text.trim()) else {
+        return Ok(());
+    };
+    if text.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Utilisation: /anon <message>",
+                "Usage: /anon <message>"
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    let hash = sender_hash(user.id.0);
+    let index = blind_index(&hash);
+    let timestamp = now().timestamp();
+    let window_start = timestamp - FLOOD_WINDOW;
+
+    let blocked = sqlx::query!(
+        "SELECT COUNT(*) AS count FROM anon_blocked WHERE sender_hash = $1",
+        index
+    )
+    .fetch_one(db.as_ref())
+    .await?
+    .count
+        > 0;
+    if blocked {
+        log::info!("Blocked anonymous sender {} tried to send a message", hash);
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Tu ne peux plus envoyer de messages anonymes",
+                "You can no longer send anonymous messages"
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    let recent = sqlx::query!(
+        "SELECT COUNT(*) AS count FROM anon_messages WHERE sender_hash = $1 AND sent_at > $2",
+        index,
+        window_start
+    )
+    .fetch_one(db.as_ref())
+    .await?
+    .count;
+    if recent >= FLOOD_MAX_MESSAGES {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Tu as envoyé trop de messages anonymes, réessaie plus tard",
+                "You sent too many anonymous messages, try again later"
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    bot.send_html(
+        ChatId(committee_chat),
+        format!(
+            "📨 Message anonyme ({}):\n{}",
+            code(&format!("#{}", hash)),
+            escape(text)
+        ),
+    )
+    .queued()
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO anon_messages(sender_hash, sent_at) VALUES($1, $2)",
+        index,
+        timestamp
+    )
+    .execute(db.as_ref())
+    .await?;
+    sqlx::query!("DELETE FROM anon_messages WHERE sent_at <= $1", window_start)
+        .execute(db.as_ref())
+        .await?;
+
+    bot.send_message(
+        msg.chat.id,
+        tr!(
+            lang,
+            "Ton message a été transmis au comité",
+            "Your message was forwarded to the committee"
+        ),
+    )
+        .queued()
+        .await?;
+
+    Ok(())
+}
+
+pub async fn anon_block(bot: Bot, msg: Message, hash: String, db: Arc<SqlitePool>) -> HandlerResult {
+    let hash = hash.trim().trim_start_matches('#').to_lowercase();
+    if hash.len() != SENDER_HASH_LENGTH || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        bot.send_message(
+            msg.chat.id,
+            "Utilisation: /anonblock <identifiant affiché avec le message>",
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    let index = blind_index(&hash);
+    sqlx::query!(
+        "INSERT OR IGNORE INTO anon_blocked(sender_hash) VALUES($1)",
+        index
+    )
+    .execute(db.as_ref())
+    .await?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!("L'expéditeur #{} ne peut plus envoyer de messages anonymes", hash),
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
+
+pub async fn anon_unblock(
+    bot: Bot,
+    msg: Message,
+    hash: String,
+    db: Arc<SqlitePool>,
+) -> HandlerResult {
+    let hash = hash.trim().trim_start_matches('#').to_lowercase();
+    let index = blind_index(&hash);
+    let removed = sqlx::query!("DELETE FROM anon_blocked WHERE sender_hash = $1", index)
+        .execute(db.as_ref())
+        .await?
+        .rows_affected();
+
+    bot.send_html(
+        msg.chat.id,
+        if removed > 0 {
+            format!("L'expéditeur #{} a été débloqué", escape(&hash))
+        } else {
+            format!("L'expéditeur #{} n'est pas bloqué", escape(&hash))
+        },
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
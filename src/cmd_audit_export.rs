@@ -0,0 +1,263 @@
+//! `/auditexport`: export of the audit log and of the command log for a period, sent as a file to
+//! the superadmin in private, for the yearly report of the association or an investigation.
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, TimeDelta, TimeZone};
+use chrono_tz::Tz;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use teloxide::{
+    payloads::SendDocumentSetters,
+    requests::Requester,
+    types::{ChatId, InputFile, Message},
+    Bot,
+};
+
+use crate::{
+    audit::{actor, audit},
+    cmd_backup::superadmin,
+    commands::Command,
+    dates::{from_timestamp, now_in},
+    outbox::Queued,
+    stats::{record_stat, Stat},
+    HandlerResult,
+};
+
+/// Period exported when none is given, in days.
+const DEFAULT_DAYS: i64 = 30;
+
+const USAGE: &str = "Utilisation: /auditexport [période] [csv|json], la période étant un nombre de jours (30j), une année (2026), un mois (2026-03) ou \"tout\"";
+
+#[derive(Serialize)]
+struct AuditEntry {
+    date: String,
+    actor: String,
+    action: String,
+    details: String,
+}
+
+#[derive(Serialize)]
+struct CommandEntry {
+    date: String,
+    chat_id: String,
+    actor: String,
+    command: String,
+}
+
+#[derive(Serialize)]
+struct Export {
+    period: String,
+    audit: Vec<AuditEntry>,
+    commands: Vec<CommandEntry>,
+}
+
+/// Midnight of the day, in the timezone.
+fn midnight(day: NaiveDate, timezone: Tz) -> Option<DateTime<Tz>> {
+    timezone
+        .from_local_datetime(&day.and_time(NaiveTime::MIN))
+        .earliest()
+}
+
+/// Start and end (excluded) timestamps of the period, and a label for the file name.
+fn parse_period(period: &str, now: DateTime<Tz>) -> Option<(i64, i64, String)> {
+    let timezone = now.timezone();
+    let end = now.timestamp();
+    let period = period.to_lowercase();
+
+    if period.is_empty() {
+        let start = now.checked_sub_signed(TimeDelta::try_days(DEFAULT_DAYS)?)?;
+        return Some((start.timestamp(), end, format!("{}j", DEFAULT_DAYS)));
+    }
+    if period == "tout" || period == "all" {
+        return Some((0, end, "tout".to_owned()));
+    }
+    if let Some(days) = period
+        .strip_suffix('j')
+        .or(period.strip_suffix('d'))
+        .and_then(|d| d.parse::<i64>().ok())
+        .filter(|d| *d > 0)
+    {
+        let start = now.checked_sub_signed(TimeDelta::try_days(days)?)?;
+        return Some((start.timestamp(), end, format!("{}j", days)));
+    }
+
+    let (start, next) = match period.split_once('-') {
+        Some((year, month)) => {
+            let (year, month) = (year.parse::<i32>().ok()?, month.parse::<u32>().ok()?);
+            let start = NaiveDate::from_ymd_opt(year, month, 1)?;
+            let next = if month == 12 {
+                NaiveDate::from_ymd_opt(year.checked_add(1)?, 1, 1)?
+            } else {
+                NaiveDate::from_ymd_opt(year, month + 1, 1)?
+            };
+            (start, next)
+        }
+        None => {
+            let year = period.parse::<i32>().ok()?;
+            (
+                NaiveDate::from_ymd_opt(year, 1, 1)?,
+                NaiveDate::from_ymd_opt(year.checked_add(1)?, 1, 1)?,
+            )
+        }
+    };
+    if start.year() > now.year() {
+        return None;
+    }
+    Some((
+        midnight(start, timezone)?.timestamp(),
+        midnight(next, timezone)?.timestamp(),
+        period,
+    ))
+}
+
+/// Field of a CSV line, quoted if needed.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+/// Both logs in a single CSV file, the `source` column telling them apart.
+fn to_csv(export: &Export) -> String {
+    let mut csv = String::from("source,date,actor,action,details\n");
+    for entry in &export.audit {
+        let fields = [
+            "audit",
+            &entry.date,
+            &entry.actor,
+            &entry.action,
+            &entry.details,
+        ];
+        csv.push_str(&fields.map(csv_field).join(","));
+        csv.push('\n');
+    }
+    for entry in &export.commands {
+        let command = format!("/{}", entry.command);
+        let fields = [
+            "command",
+            &entry.date,
+            &entry.actor,
+            &command,
+            &entry.chat_id,
+        ];
+        csv.push_str(&fields.map(csv_field).join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Records a received command in the command log, without its arguments. `/anon` is never
+/// recorded, so that the senders stay anonymous.
+pub fn log_command(command: Command, msg: Message) {
+    if matches!(command, Command::Anon(_)) {
+        return;
+    }
+    record_stat(Stat::Command {
+        chat_id: msg.chat.id.to_string(),
+        actor: actor(&msg),
+        command: command.shortand().to_owned(),
+    });
+}
+
+/// `/auditexport [period] [csv|json]` sends the audit log and the command log of the period to the
+/// superadmin, in private.
+pub async fn audit_export(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    timezone: Tz,
+) -> HandlerResult {
+    let Some(superadmin) = superadmin(&msg) else {
+        bot.send_message(msg.chat.id, "Seul le superadmin peut exporter le journal")
+            .queued()
+            .await?;
+        return Ok(());
+    };
+
+    let mut words = args.split_whitespace();
+    let (period, format) = match (words.next(), words.next()) {
+        (Some(f @ ("csv" | "json")), None) => ("", f),
+        (period, format) => (period.unwrap_or(""), format.unwrap_or("csv")),
+    };
+    let (Some((start, end, label)), "csv" | "json") =
+        (parse_period(period, now_in(timezone)), format)
+    else {
+        bot.send_message(msg.chat.id, USAGE).queued().await?;
+        return Ok(());
+    };
+
+    let date = |timestamp: i64| {
+        from_timestamp(timestamp, timezone)
+            .map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default()
+    };
+    let audit_entries = sqlx::query!(
+        r#"SELECT created_at, actor, "action", details FROM audit_log
+        WHERE created_at >= $1 AND created_at < $2 ORDER BY id"#,
+        start,
+        end
+    )
+    .fetch_all(db.as_ref())
+    .await?
+    .into_iter()
+    .map(|e| AuditEntry {
+        date: date(e.created_at),
+        actor: e.actor,
+        action: e.action,
+        details: e.details,
+    })
+    .collect::<Vec<_>>();
+    let commands = sqlx::query!(
+        "SELECT created_at, chat_id, actor, command FROM command_log
+        WHERE created_at >= $1 AND created_at < $2 ORDER BY id",
+        start,
+        end
+    )
+    .fetch_all(db.as_ref())
+    .await?
+    .into_iter()
+    .map(|c| CommandEntry {
+        date: date(c.created_at),
+        chat_id: c.chat_id,
+        actor: c.actor,
+        command: c.command,
+    })
+    .collect::<Vec<_>>();
+
+    let export = Export {
+        period: label,
+        audit: audit_entries,
+        commands,
+    };
+    let content = match format {
+        "json" => serde_json::to_vec_pretty(&export)?,
+        _ => to_csv(&export).into_bytes(),
+    };
+    let file_name = format!("roboclic-audit-{}.{}", export.period, format);
+    bot.send_document(
+        ChatId::from(superadmin),
+        InputFile::memory(content).file_name(file_name),
+    )
+    .caption(format!(
+        "Journal d'audit ({} entrées) et des commandes ({} entrées), période {}",
+        export.audit.len(),
+        export.commands.len(),
+        export.period
+    ))
+    .queued()
+    .await?;
+    let details = format!("{} ({})", export.period, format);
+    audit(db.as_ref(), &actor(&msg), "audit_export", &details).await;
+
+    if msg.chat.id != ChatId::from(superadmin) {
+        bot.send_message(msg.chat.id, "Export envoyé en message privé")
+            .queued()
+            .await?;
+    }
+
+    Ok(())
+}
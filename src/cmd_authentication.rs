@@ -1,110 +1,349 @@
-use std::sync::Arc;
+use std::{error::Error, sync::Arc};
 
+use chrono_tz::Tz;
 use sqlx::SqlitePool;
-use teloxide::{requests::Requester, types::Message, Bot};
-
-use crate::{config::config, HandlerResult};
+use teloxide::{
+    payloads::SendMessageSetters, requests::Requester, types::Message, Bot,
+};
 
+use crate::{
+    admin_token::{admin_token, rotate_admin_token},
+    approvals::require_approval,
+    audit::{actor, audit, audit_reversible},
+    chats::topic,
+    cmd_backup::superadmin,
+    cmd_undo::Undo,
+    config::config,
+    crypto::secrets_equal,
+    dates::{format_datetime, from_timestamp, now},
+    db::{admins::AdminRepo, authorizations::AuthorizationRepo},
+    format::{bold, code, escape, HtmlMessages, MessageBuilder},
+    i18n::{tr, Lang},
+    names::{did_you_mean, match_name, NameMatch},
+    outbox::Queued,
+    HandlerResult,
+};
 
+/// `/authenticate <token> <name>` only creates the first admin, in private. The next ones are
+/// invited with `/admininvite`, so that the admin token does not need to be shared.
 pub async fn authenticate(
     bot: Bot,
     msg: Message,
     (token, name): (String, String),
     db: Arc<SqlitePool>,
+    admins: Arc<dyn AdminRepo>,
 ) -> HandlerResult {
-    if token == config().admin_token {
-        let id = msg.chat.id.to_string();
-        sqlx::query!(
-            r#"INSERT INTO admins(telegram_id, "name") VALUES($1, $2)"#,
-            id,
-            name
+    if !msg.chat.is_private() {
+        bot.send_message(
+            msg.chat.id,
+            "L'authentification ne se fait qu'en message privé avec le bot",
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+    if admins.count_admins().await? > 0 {
+        bot.send_message(
+            msg.chat.id,
+            "Demande un lien d'invitation à un admin (/admininvite), le token ne sert qu'à créer le premier admin",
         )
-        .execute(db.as_ref())
+        .queued()
         .await?;
+        return Ok(());
+    }
+
+    if secrets_equal(&token, &admin_token()) {
+        let id = msg.chat.id.to_string();
+        let timestamp = now().timestamp();
+        admins.add_admin(&id, &name, "token", Some(&id), timestamp).await?;
+        audit(db.as_ref(), &actor(&msg), "admin_add", &name).await;
         bot.send_message(msg.chat.id, "Authentification réussie !")
+            .queued()
             .await?;
     } else {
+        audit(db.as_ref(), &actor(&msg), "authenticate_failed", &name).await;
         bot.send_message(msg.chat.id, "Le token est incorrect")
+            .queued()
             .await?;
     }
 
     Ok(())
 }
 
-pub async fn admin_list(bot: Bot, msg: Message, db: Arc<SqlitePool>) -> HandlerResult {
-    let admins = sqlx::query!(r#"SELECT "name" FROM admins"#)
-        .fetch_all(db.as_ref())
-        .await?;
+/// Deletes a message exposing the admin token in a group, warns its author, and tells the admins
+/// in private. With `ADMIN_TOKEN_AUTO_ROTATE`, the token is replaced and the new one is sent to
+/// the admins.
+pub async fn redact_admin_token(
+    bot: Bot,
+    msg: Message,
+    db: Arc<SqlitePool>,
+    admins: Arc<dyn AdminRepo>,
+    lang: Lang,
+) -> HandlerResult {
+    let deleted = match bot.delete_message(msg.chat.id, msg.id).await {
+        Ok(_) => true,
+        Err(e) => {
+            log::warn!("Could not delete the admin token in {}: {:?}", msg.chat.id, e);
+            false
+        }
+    };
+    let chat = msg.chat.title().map(str::to_owned).unwrap_or_else(|| msg.chat.id.to_string());
+    audit(db.as_ref(), &actor(&msg), "admin_token_leak", &chat).await;
 
-    bot.send_message(
-        msg.chat.id,
-        format!(
-            "Admin(s) actuel(s):\n{}",
-            admins
-                .into_iter()
-                .map(|r| format!(" - {}", r.name))
-                .collect::<Vec<_>>()
-                .join("\n"),
-        ),
-    )
-    .await?;
+    let rotated = if config().admin_token_auto_rotate {
+        Some(rotate_admin_token(db.as_ref()).await?)
+    } else {
+        None
+    };
+
+    let mut warning = tr!(
+        lang,
+        "⚠️ Ne partage jamais le token admin dans un groupe, utilise /authenticate en message privé avec le bot.",
+        "⚠️ Never share the admin token in a group, use /authenticate in a private chat with the bot."
+    );
+    warning += " ";
+    warning += &if deleted {
+        tr!(lang, "Le message a été supprimé.", "The message was deleted.")
+    } else {
+        tr!(
+            lang,
+            "Supprime vite le message, le bot n'a pas le droit de le faire.",
+            "Delete the message quickly, the bot is not allowed to."
+        )
+    };
+    if rotated.is_some() {
+        warning += " ";
+        warning += &tr!(lang, "Le token a été changé.", "The token was changed.");
+    }
+    bot.send_message(msg.chat.id, warning).queued().await?;
+
+    let mut notice = format!(
+        "⚠️ Le token admin a été publié dans {} par {}{}.",
+        bold(&chat),
+        bold(&msg.from().map(|u| u.full_name()).unwrap_or_default()),
+        if deleted { "" } else { " (message non supprimé)" }
+    );
+    notice += &match &rotated {
+        Some(token) => format!("\nNouveau token: {}", code(token)),
+        None => "\nPense à changer ADMIN_TOKEN.".to_owned(),
+    };
+    for id in admins.admin_private_chats().await? {
+        if let Err(e) = bot.send_html(id, &notice).queued().await {
+            log::warn!("Could not warn admin {} of the token leak: {:?}", id, e);
+        }
+    }
 
     Ok(())
 }
 
-pub async fn admin_remove(bot: Bot, msg: Message, name: String, db: Arc<SqlitePool>) -> HandlerResult {
-    let mut tx = db.begin().await?;
-
-    if sqlx::query!("SELECT COUNT(*) AS count FROM admins WHERE name = $1", name)
-        .fetch_one(tx.as_mut())
-        .await?
-        .count
-        == 0
-    {
-        bot.send_message(msg.chat.id, format!("{} n'est pas admin", name))
+pub async fn admin_list(bot: Bot, msg: Message, admins: Arc<dyn AdminRepo>) -> HandlerResult {
+    let admins = admins.admins().await?;
+
+    let text = MessageBuilder::new()
+        .title("Admin(s) actuel(s)")
+        .items(admins.into_iter().map(|a| escape(&a.name)))
+        .build();
+    bot.send_html(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
+
+/// Removes the admin, once approved. Returns the outcome to display.
+pub async fn remove_admin(db: &SqlitePool, actor: &str, name: &str) -> Result<String, sqlx::Error> {
+    if db.remove_admins(name).await?.is_empty() {
+        return Ok(format!("{} n'est pas admin", name));
+    }
+    audit(db, actor, "admin_remove", name).await;
+    Ok(format!("{} a été retiré(e) des admins", name))
+}
+
+/// Name of the admin typed by the user, ignoring the case and the accents. Replies with the closest
+/// names when there is no such admin.
+async fn find_admin(
+    bot: &Bot,
+    db: &SqlitePool,
+    msg: &Message,
+    name: &str,
+) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+    let admins = db.admins().await?;
+    match match_name(name, admins.iter().map(|a| a.name.as_str())) {
+        NameMatch::Found(name) => Ok(Some(name.to_owned())),
+        NameMatch::Suggestions(suggestions) => {
+            bot.send_html(
+                msg.chat.id,
+                format!("{} n'est pas admin{}", bold(name), did_you_mean(&suggestions)),
+            )
+            .queued()
             .await?;
-        return Ok(());
+            Ok(None)
+        }
     }
+}
 
-    sqlx::query!("DELETE FROM admins WHERE name = $1", name)
-        .execute(tx.as_mut())
-        .await?;
-    tx.commit().await?;
+/// `/adminremove <name>`, after the approval of another admin.
+pub async fn admin_remove(bot: Bot, msg: Message, name: String, db: Arc<SqlitePool>) -> HandlerResult {
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+    let Some(name) = find_admin(&bot, db.as_ref(), &msg, &name).await? else {
+        return Ok(());
+    };
+
+    let description = format!("retirer {} des admins", name);
+    require_approval(
+        &bot,
+        db.as_ref(),
+        user,
+        msg.chat.id,
+        "admin_remove",
+        &name,
+        &description,
+    )
+    .await
+}
 
-    bot.send_message(msg.chat.id, format!("{} a été retiré(e) des admins", name))
+/// `/sessions` lists when, how and from which chat each admin authenticated (superadmin only).
+pub async fn sessions(
+    bot: Bot,
+    msg: Message,
+    admins: Arc<dyn AdminRepo>,
+    timezone: Tz,
+) -> HandlerResult {
+    if superadmin(&msg).is_none() {
+        bot.send_message(
+            msg.chat.id,
+            "Seul le superadmin peut consulter les sessions",
+        )
+        .queued()
         .await?;
+        return Ok(());
+    }
+
+    let admins = admins.admin_sessions().await?;
+    if admins.is_empty() {
+        bot.send_message(msg.chat.id, "Aucun admin").queued().await?;
+        return Ok(());
+    }
+
+    let lines = admins
+        .into_iter()
+        .map(|a| {
+            let when = a
+                .authenticated_at
+                .and_then(|t| from_timestamp(t, timezone))
+                .map(|d| format_datetime(&d))
+                .unwrap_or_else(|| "date inconnue".to_owned());
+            let origin = match (a.auth_method, a.auth_chat_id) {
+                (Some(method), Some(chat)) if chat != a.telegram_id => {
+                    format!("{}, depuis {}", method, chat)
+                }
+                (Some(method), _) => method,
+                (None, _) => "méthode inconnue".to_owned(),
+            };
+            format!(
+                "{} ({}): {}, {}",
+                bold(&a.name),
+                code(&a.telegram_id),
+                when,
+                escape(&origin)
+            )
+        })
+        .collect::<Vec<_>>();
+    let text = MessageBuilder::new()
+        .title("Sessions admin")
+        .items(lines)
+        .separator()
+        .text("Pour en couper une: /revoke <nom>")
+        .build();
+    bot.send_html(msg.chat.id, text).queued().await?;
 
     Ok(())
 }
 
-pub async fn authorize(bot: Bot, msg: Message, command: String, db: Arc<SqlitePool>) -> HandlerResult {
-    let mut tx = db.begin().await?;
-
-    let chat_id_str = msg.chat.id.to_string();
-    let already_authorized = sqlx::query!(
-        r#"SELECT COUNT(*) AS count FROM authorizations WHERE chat_id = $1 AND command = $2"#,
-        chat_id_str,
-        command
-    )
-    .fetch_one(tx.as_mut())
-    .await?;
+/// Removes the admin rights of the account and cancels the pending invitations it generated, once
+/// approved. Returns the outcome to display.
+pub async fn revoke_admin_rights(
+    db: &SqlitePool,
+    actor: &str,
+    name: &str,
+) -> Result<String, sqlx::Error> {
+    let revoked = db.remove_admins(name).await?;
+    if revoked.is_empty() {
+        return Ok(format!("{} n'est pas admin", name));
+    }
 
-    if already_authorized.count == 0 {
+    // The links generated by the compromised account must not let it back in
+    let timestamp = now().timestamp();
+    for telegram_id in &revoked {
         sqlx::query!(
-            r#"INSERT INTO authorizations(command, chat_id) VALUES($1, $2)"#,
-            command,
-            chat_id_str
+            "UPDATE admin_invites SET used_at = $1 WHERE used_at IS NULL AND created_by_id = $2",
+            timestamp,
+            telegram_id
         )
-        .execute(tx.as_mut())
+        .execute(db)
         .await?;
     }
+    let details = revoked
+        .iter()
+        .map(|id| format!("{} ({})", name, id))
+        .collect::<Vec<_>>()
+        .join(", ");
+    audit(db, actor, "admin_revoke", &details).await;
+    Ok(format!("Les droits admin de {} sont révoqués", name))
+}
+
+/// `/revoke <name>` removes the admin rights of a possibly compromised account (superadmin only).
+/// It runs without approval, so that the compromised account cannot delay it.
+pub async fn revoke_admin(
+    bot: Bot,
+    msg: Message,
+    name: String,
+    db: Arc<SqlitePool>,
+) -> HandlerResult {
+    let Some(_) = superadmin(&msg) else {
+        bot.send_message(msg.chat.id, "Seul le superadmin peut révoquer un admin")
+            .queued()
+            .await?;
+        return Ok(());
+    };
+    let name = name.trim();
+    if name.is_empty() {
+        bot.send_message(msg.chat.id, "Utilisation: /revoke <nom>")
+            .queued()
+            .await?;
+        return Ok(());
+    }
+    let Some(name) = find_admin(&bot, db.as_ref(), &msg, name).await? else {
+        return Ok(());
+    };
+
+    let outcome = revoke_admin_rights(db.as_ref(), &actor(&msg), &name).await?;
+    bot.send_message(msg.chat.id, outcome).queued().await?;
+    Ok(())
+}
 
-    tx.commit().await?;
+pub async fn authorize(
+    bot: Bot,
+    msg: Message,
+    command: String,
+    db: Arc<SqlitePool>,
+    authorizations: Arc<dyn AuthorizationRepo>,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.to_string();
+    let granted = authorizations.grant(&chat_id, &command).await?;
 
-    bot.send_message(
+    let details = format!("/{} in {}", command, msg.chat.id);
+    if granted {
+        let undo = Undo::Revoke { chat_id, command: command.clone() };
+        audit_reversible(db.as_ref(), &msg, "authorize", &details, &undo).await;
+    } else {
+        audit(db.as_ref(), &actor(&msg), "authorize", &details).await;
+    }
+    bot.send_html(
         msg.chat.id,
-        format!("Ce groupe peut désormais utiliser la commande /{}", command),
+        format!("Ce groupe peut désormais utiliser la commande /{}", escape(&command)),
     )
+    .queued()
     .await?;
     Ok(())
 }
@@ -114,62 +353,98 @@ pub async fn unauthorize(
     msg: Message,
     command: String,
     db: Arc<SqlitePool>,
+    authorizations: Arc<dyn AuthorizationRepo>,
 ) -> HandlerResult {
-    let mut tx = db.begin().await?;
+    let chat_id = msg.chat.id.to_string();
+    let revoked = authorizations.revoke(&chat_id, &command).await?;
 
-    let chat_id_str = msg.chat.id.to_string();
-    let already_authorized = sqlx::query!(
-        r#"SELECT COUNT(*) AS count FROM authorizations WHERE chat_id = $1 AND command = $2"#,
-        chat_id_str,
-        command
-    )
-    .fetch_one(tx.as_mut())
-    .await?;
-
-    if already_authorized.count > 0 {
-        sqlx::query!(
-            r#"DELETE FROM authorizations WHERE command = $1 AND chat_id = $2"#,
-            command,
-            chat_id_str
-        )
-        .execute(tx.as_mut())
-        .await?;
+    let details = format!("/{} in {}", command, msg.chat.id);
+    if revoked {
+        let undo = Undo::Grant { chat_id, command: command.clone() };
+        audit_reversible(db.as_ref(), &msg, "unauthorize", &details, &undo).await;
+    } else {
+        audit(db.as_ref(), &actor(&msg), "unauthorize", &details).await;
     }
-
-    tx.commit().await?;
-
-    bot.send_message(
+    bot.send_html(
         msg.chat.id,
         format!(
             "Ce groupe ne peut désormais plus utiliser la commande /{}",
-            command
+            escape(&command)
         ),
     )
+    .queued()
     .await?;
     Ok(())
 }
 
-pub async fn authorizations(bot: Bot, msg: Message, db: Arc<SqlitePool>) -> HandlerResult {
-    let chat_id_str = msg.chat.id.to_string();
-    let authorizations = sqlx::query!(
-        r#"SELECT command FROM authorizations WHERE chat_id = $1"#,
-        chat_id_str
-    )
-    .fetch_all(db.as_ref())
-    .await?;
+/// `/topicbind <command>` restricts an allowed command to the forum topic in which it is sent, or
+/// lifts the restriction when sent from the general topic.
+pub async fn topic_bind(
+    bot: Bot,
+    msg: Message,
+    command: String,
+    db: Arc<SqlitePool>,
+    authorizations: Arc<dyn AuthorizationRepo>,
+) -> HandlerResult {
+    let command = command.trim().trim_start_matches('/');
+    if command.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            "Utilisation: /topicbind <commande>, dans le sujet auquel la restreindre",
+        )
+        .reply_to_message_id(msg.id)
+        .queued()
+        .await?;
+        return Ok(());
+    }
 
-    bot.send_message(
-        msg.chat.id,
+    let thread_id = topic(&msg);
+    let chat_id = msg.chat.id.to_string();
+    let text = if authorizations.bind_topic(&chat_id, command, thread_id).await? {
+        let details = match thread_id {
+            Some(id) => format!("/{} in {} (topic {})", command, msg.chat.id, id),
+            None => format!("/{} in {}", command, msg.chat.id),
+        };
+        audit(db.as_ref(), &actor(&msg), "topic_bind", &details).await;
+        match thread_id {
+            Some(_) => format!(
+                "La commande /{} ne peut désormais être utilisée que dans ce sujet",
+                escape(command)
+            ),
+            None => format!(
+                "La commande /{} peut désormais être utilisée dans tout le groupe",
+                escape(command)
+            ),
+        }
+    } else {
         format!(
-            "Ce groupe peut utiliser les commandes suivantes:\n{}",
-            authorizations
-                .into_iter()
-                .map(|s| format!(" - {}", s.command))
-                .collect::<Vec<_>>()
-                .join("\n")
-        ),
-    )
-    .await?;
+            "Ce groupe n'est pas autorisé à utiliser la commande /{}, voir /authorize",
+            escape(command)
+        )
+    };
+    // Replying keeps the answer in the topic
+    bot.send_html(msg.chat.id, text)
+        .reply_to_message_id(msg.id)
+        .queued()
+        .await?;
+
+    Ok(())
+}
+
+pub async fn authorizations(
+    bot: Bot,
+    msg: Message,
+    authorizations: Arc<dyn AuthorizationRepo>,
+) -> HandlerResult {
+    let authorizations = authorizations
+        .chat_authorizations(&msg.chat.id.to_string())
+        .await?;
+
+    let text = MessageBuilder::new()
+        .title("Ce groupe peut utiliser les commandes suivantes")
+        .items(authorizations.into_iter().map(|c| code(&c)))
+        .build();
+    bot.send_html(msg.chat.id, text).queued().await?;
 
     Ok(())
 }
@@ -0,0 +1,346 @@
+//! Backups of the database, sent encrypted to the superadmin in private, and restored from such a
+//! document.
+
+use std::{error::Error, sync::Arc};
+
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
+use sha2::{Digest, Sha256};
+use sqlx::{Connection, SqliteConnection, SqlitePool};
+use teloxide::{
+    net::Download,
+    payloads::{SendDocumentSetters, SendMessageSetters},
+    requests::Requester,
+    types::{
+        CallbackQuery, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, InputFile, Message,
+        MessageId, UserId,
+    },
+    Bot,
+};
+
+use crate::{
+    admin_token::load_admin_token,
+    approvals::require_approval,
+    audit::{actor, audit},
+    callbacks::{CallbackData, CallbackResult, RESTORE},
+    cmd_poll::{PollDialogue, PollState},
+    cmd_schedules::restore_schedules,
+    committee::committee_repository,
+    config::config,
+    crypto::derived_secret,
+    dates::{now, TIMEZONE},
+    db::authorizations::authorization_cache,
+    outbox::Queued,
+    HandlerResult,
+};
+
+/// Header of the backup files, followed by the nonce and the encrypted database.
+const MAGIC: &[u8] = b"RCLB1";
+const NONCE_SIZE: usize = 12;
+/// Header of the SQLite files.
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+/// Tables which are not restored: the migrations must already match, and the dialogues of the
+/// backup are outdated.
+const SKIPPED_TABLES: &[&str] = &["_sqlx_migrations", "dialogues"];
+
+fn cipher_of(secret: &str) -> Aes256Gcm {
+    let key = Sha256::digest(secret.as_bytes());
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))
+}
+
+fn cipher() -> Aes256Gcm {
+    cipher_of(
+        &config()
+            .backup_key
+            .clone()
+            .unwrap_or_else(|| derived_secret("backup")),
+    )
+}
+
+fn encrypt(database: &[u8]) -> Vec<u8> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let encrypted = cipher()
+        .encrypt(&nonce, database)
+        .expect("encryption cannot fail with a valid key");
+    [MAGIC, nonce.as_slice(), &encrypted].concat()
+}
+
+/// Decrypts a backup file. Returns `None` if it is not a backup, or was encrypted with another
+/// key.
+fn decrypt(file: &[u8]) -> Option<Vec<u8>> {
+    let file = file.strip_prefix(MAGIC)?;
+    if file.len() < NONCE_SIZE {
+        return None;
+    }
+    let (nonce, encrypted) = file.split_at(NONCE_SIZE);
+    let decrypt = |cipher: Aes256Gcm| {
+        cipher
+            .decrypt(Nonce::from_slice(nonce), encrypted)
+            .ok()
+            .filter(|d| d.starts_with(SQLITE_HEADER))
+    };
+    decrypt(cipher())
+}
+
+/// The superadmin, if they sent the message.
+pub fn superadmin(msg: &Message) -> Option<UserId> {
+    let id = UserId(config().superadmin_id?);
+    msg.from().filter(|u| u.id == id).map(|_| id)
+}
+
+/// Sends an encrypted snapshot of the database to the superadmin, in private.
+pub async fn backup(bot: Bot, msg: Message, db: Arc<SqlitePool>) -> HandlerResult {
+    let Some(superadmin) = superadmin(&msg) else {
+        bot.send_message(msg.chat.id, "Seul le superadmin peut faire des sauvegardes")
+            .queued()
+            .await?;
+        return Ok(());
+    };
+
+    // VACUUM INTO writes a consistent copy, even while the bot keeps writing
+    let path = format!("{}/backup-{}.sqlite", config().data_dir, now().timestamp());
+    sqlx::query("VACUUM INTO $1")
+        .bind(&path)
+        .execute(db.as_ref())
+        .await?;
+    let database = tokio::fs::read(&path).await;
+    if let Err(e) = tokio::fs::remove_file(&path).await {
+        log::error!("Could not remove snapshot {}: {:?}", path, e);
+    }
+    let database = database?;
+
+    let file_name = format!(
+        "roboclic-{}.sqlite.enc",
+        now().with_timezone(&TIMEZONE).format("%Y-%m-%d-%H%M")
+    );
+    bot.send_document(
+        ChatId::from(superadmin),
+        InputFile::memory(encrypt(&database)).file_name(file_name),
+    )
+    .caption("Sauvegarde de la base de données. Réponds-y avec /restore pour la restaurer.")
+    .queued()
+    .await?;
+    let details = format!("{} bytes", database.len());
+    audit(db.as_ref(), &actor(&msg), "backup", &details).await;
+
+    if msg.chat.id != ChatId::from(superadmin) {
+        bot.send_message(msg.chat.id, "Sauvegarde envoyée en message privé")
+            .queued()
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Downloads and decrypts a backup sent as a document.
+async fn fetch_backup(bot: &Bot, file_id: &str) -> Result<Option<Vec<u8>>, teloxide::RequestError> {
+    let file = bot.get_file(file_id).await?;
+    let mut content = Vec::with_capacity(file.size as usize);
+    if let Err(e) = bot.download_file(&file.path, &mut content).await {
+        log::error!("Could not download backup: {:?}", e);
+        return Ok(None);
+    }
+    Ok(decrypt(&content))
+}
+
+/// Asks the superadmin to confirm the restoration of the backup they replied to.
+pub async fn restore(bot: Bot, msg: Message, dialogue: PollDialogue) -> HandlerResult {
+    let Some(superadmin) = superadmin(&msg).filter(|id| msg.chat.id == ChatId::from(*id)) else {
+        bot.send_message(
+            msg.chat.id,
+            "Seul le superadmin peut restaurer une sauvegarde, en message privé",
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+    let Some(document) = msg.reply_to_message().and_then(|m| m.document()) else {
+        bot.send_message(msg.chat.id, "Réponds à une sauvegarde avec /restore")
+            .queued()
+            .await?;
+        return Ok(());
+    };
+
+    if fetch_backup(&bot, &document.file.id).await?.is_none() {
+        bot.send_message(
+            msg.chat.id,
+            "Ce fichier n'est pas une sauvegarde valide, ou a été chiffré avec une autre clé",
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    let sent = bot
+        .send_message(
+            msg.chat.id,
+            "⚠️ Toutes les données actuelles du bot seront remplacées par celles de la sauvegarde. Continuer ?",
+        )
+        .reply_markup(InlineKeyboardMarkup::new([[
+            InlineKeyboardButton::callback("Restaurer", CallbackData::format(RESTORE, "restore")),
+            InlineKeyboardButton::callback("Annuler", CallbackData::format(RESTORE, "cancel")),
+        ]]))
+        .queued()
+        .await?;
+
+    dialogue
+        .update(PollState::ConfirmRestore {
+            message_id: sent.id,
+            file_id: document.file.id.clone(),
+            initiator: Some(superadmin),
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Replaces the content of the tables by the one of the backup at `path`. Returns the number of
+/// restored rows, or `None` if the backup was made with other migrations.
+async fn restore_database(
+    conn: &mut SqliteConnection,
+    path: &str,
+) -> Result<Option<u64>, sqlx::Error> {
+    let version = |schema| format!("SELECT MAX(version) FROM {}._sqlx_migrations", schema);
+    let current: Option<i64> = sqlx::query_scalar(&version("main"))
+        .fetch_one(&mut *conn)
+        .await?;
+    let backup: Option<i64> = sqlx::query_scalar(&version("backup"))
+        .fetch_one(&mut *conn)
+        .await?;
+    if current != backup {
+        log::warn!(
+            "Backup {} is at migration {:?}, the database at {:?}",
+            path,
+            backup,
+            current
+        );
+        return Ok(None);
+    }
+
+    let tables: Vec<String> = sqlx::query_scalar(
+        "SELECT name FROM main.sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut tx = conn.begin().await?;
+    let mut restored = 0;
+    for table in tables
+        .iter()
+        .filter(|t| !SKIPPED_TABLES.contains(&t.as_str()))
+    {
+        sqlx::query(&format!(r#"DELETE FROM main."{}""#, table))
+            .execute(&mut *tx)
+            .await?;
+        restored += sqlx::query(&format!(
+            r#"INSERT INTO main."{}" SELECT * FROM backup."{}""#,
+            table, table
+        ))
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    }
+    tx.commit().await?;
+
+    Ok(Some(restored))
+}
+
+/// Asks another admin to approve the restoration once confirmed.
+pub async fn confirm_restore(
+    bot: Bot,
+    query: CallbackQuery,
+    data: CallbackData,
+    dialogue: PollDialogue,
+    (message_id, file_id, _): (MessageId, String, Option<UserId>),
+    db: Arc<SqlitePool>,
+) -> CallbackResult {
+    let chat_id = dialogue.chat_id();
+    dialogue.update(PollState::Start).await?;
+    match data.payload.as_str() {
+        "cancel" => {
+            bot.edit_message_text(chat_id, message_id, "Restauration annulée")
+                .queued()
+                .await?;
+            return Ok(None);
+        }
+        "restore" => {}
+        _ => return Ok(None),
+    }
+
+    bot.edit_message_reply_markup(chat_id, message_id)
+        .queued()
+        .await?;
+    require_approval(
+        &bot,
+        db.as_ref(),
+        &query.from,
+        chat_id,
+        "restore",
+        &file_id,
+        "restaurer une sauvegarde, qui remplacera toutes les données actuelles du bot",
+    )
+    .await?;
+
+    Ok(None)
+}
+
+/// Restores the backup, once approved. Returns the outcome to display.
+pub async fn restore_backup(
+    bot: &Bot,
+    db: &SqlitePool,
+    actor: &str,
+    file_id: &str,
+) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let Some(database) = fetch_backup(bot, file_id).await? else {
+        return Ok("La sauvegarde n'a pas pu être téléchargée".to_owned());
+    };
+
+    let path = format!("{}/restore-{}.sqlite", config().data_dir, now().timestamp());
+    tokio::fs::write(&path, &database).await?;
+
+    // The rows are copied from the attached backup, with the foreign keys disabled since the
+    // tables are emptied and filled in no particular order
+    let mut conn = db.acquire().await?;
+    sqlx::query("PRAGMA foreign_keys = OFF")
+        .execute(&mut *conn)
+        .await?;
+    let result = match sqlx::query("ATTACH DATABASE $1 AS backup")
+        .bind(&path)
+        .execute(&mut *conn)
+        .await
+    {
+        Ok(_) => {
+            let result = restore_database(&mut conn, &path).await;
+            if let Err(e) = sqlx::query("DETACH DATABASE backup")
+                .execute(&mut *conn)
+                .await
+            {
+                log::error!("Could not detach backup: {:?}", e);
+            }
+            result
+        }
+        Err(e) => Err(e),
+    };
+    sqlx::query("PRAGMA foreign_keys = ON")
+        .execute(&mut *conn)
+        .await?;
+    drop(conn);
+    if let Err(e) = tokio::fs::remove_file(&path).await {
+        log::error!("Could not remove restored backup {}: {:?}", path, e);
+    }
+
+    Ok(match result? {
+        Some(rows) => {
+            restore_schedules(db).await?;
+            authorization_cache().clear().await;
+            committee_repository().invalidate().await;
+            load_admin_token(db).await?;
+            let details = format!("{} row(s)", rows);
+            audit(db, actor, "restore", &details).await;
+            format!("Sauvegarde restaurée: {} ligne(s)", rows)
+        }
+        None => "La sauvegarde a été faite avec une autre version du bot, elle ne peut pas être restaurée".to_owned(),
+    })
+}
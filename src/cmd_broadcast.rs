@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use teloxide::{
+    payloads::SendMessageSetters,
+    requests::Requester,
+    types::{
+        CallbackQuery, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, Message, MessageId,
+        UserId,
+    },
+    Bot,
+};
+
+use crate::{
+    audit::audit,
+    callbacks::{CallbackData, CallbackResult, BROADCAST},
+    cmd_backup::superadmin,
+    cmd_poll::{PollDialogue, PollState},
+    config::config,
+    db::authorizations::AuthorizationRepo,
+    format::{code, escape, HtmlMessages, MessageBuilder},
+    outbox::{Priority, Queued},
+    state::AppState,
+    HandlerResult,
+};
+
+/// Shows a preview of the announcement, to be confirmed before it is sent.
+pub async fn broadcast(
+    bot: Bot,
+    msg: Message,
+    text: String,
+    dialogue: PollDialogue,
+    authorizations: Arc<dyn AuthorizationRepo>,
+) -> HandlerResult {
+    if superadmin(&msg).is_none() {
+        bot.send_message(msg.chat.id, "Seul le superadmin peut envoyer des annonces")
+            .queued()
+            .await?;
+        return Ok(());
+    }
+    let text = text.trim().to_owned();
+    if text.is_empty() {
+        bot.send_message(msg.chat.id, "Utilisation: /broadcast <message>")
+            .queued()
+            .await?;
+        return Ok(());
+    }
+
+    // The announcements go to the chats authorized to use at least one command
+    let count = authorizations.authorized_chats().await?.len();
+    let sent = bot
+        .send_message(
+            msg.chat.id,
+            format!(
+                "Aperçu de l'annonce, qui sera envoyée à {} groupe(s):\n\n{}",
+                count, text
+            ),
+        )
+        .reply_markup(InlineKeyboardMarkup::new([[
+            InlineKeyboardButton::callback("📣 Envoyer", CallbackData::format(BROADCAST, "send")),
+            InlineKeyboardButton::callback("Annuler", CallbackData::format(BROADCAST, "cancel")),
+        ]]))
+        .queued()
+        .await?;
+
+    dialogue
+        .update(PollState::ConfirmBroadcast {
+            message_id: sent.id,
+            text,
+            initiator: msg.from().map(|u| u.id),
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Sends the previewed announcement to every chat, and replaces the preview with a delivery
+/// report.
+pub async fn confirm_broadcast(
+    bot: Bot,
+    query: CallbackQuery,
+    data: CallbackData,
+    dialogue: PollDialogue,
+    (message_id, text, initiator): (MessageId, String, Option<UserId>),
+    state: AppState,
+) -> CallbackResult {
+    if config().superadmin_id != Some(query.from.id.0) {
+        return Ok(Some(
+            "Seul le superadmin peut envoyer des annonces".to_owned(),
+        ));
+    }
+    let chat_id = dialogue.chat_id();
+    match data.payload.as_str() {
+        "cancel" => {
+            dialogue.update(PollState::Start).await?;
+            bot.edit_message_text(chat_id, message_id, "Annonce annulée")
+                .queued()
+                .await?;
+            return Ok(None);
+        }
+        "send" => {}
+        _ => return Ok(None),
+    }
+
+    // Reset first, so that a second click does not send the announcement twice
+    dialogue.update(PollState::Start).await?;
+    bot.edit_message_text(chat_id, message_id, "📣 Envoi de l'annonce en cours...")
+        .queued()
+        .await?;
+
+    let chats = state.authorization_repo.authorized_chats().await?;
+    let actor = initiator.map(|id| id.to_string()).unwrap_or_default();
+    let details = format!("{} chat(s): {}", chats.len(), text);
+    audit(state.db.as_ref(), &actor, "broadcast", &details).await;
+    let mut failures = vec![];
+    for (target, title) in &chats {
+        let name = title.as_deref().unwrap_or(target);
+        let result = match target.parse::<i64>() {
+            Ok(id) => state
+                .outbox
+                .send(
+                    ChatId(id),
+                    Priority::Bulk,
+                    bot.send_message(ChatId(id), &text),
+                )
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = result {
+            log::warn!("Could not broadcast to {}: {}", target, e);
+            failures.push(format!(
+                "{} ({}): {}",
+                escape(name),
+                code(target),
+                escape(&e)
+            ));
+        }
+    }
+
+    let mut report = MessageBuilder::new().text(&format!(
+        "📣 Annonce envoyée à {}/{} groupe(s)",
+        chats.len() - failures.len(),
+        chats.len()
+    ));
+    if !failures.is_empty() {
+        report = report.separator().title("Échecs").items(failures);
+    }
+    bot.edit_html(chat_id, message_id, report.build())
+        .queued()
+        .await?;
+
+    Ok(Some("Annonce envoyée".to_owned()))
+}
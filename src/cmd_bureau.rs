@@ -1,21 +1,93 @@
-use teloxide::{payloads::SendPollSetters, requests::Requester, types::Message, Bot};
+use sqlx::SqlitePool;
+use teloxide::{
+    payloads::SendPollSetters,
+    requests::Requester,
+    types::{ChatId, Message},
+    Bot,
+};
 
-use crate::HandlerResult;
+use crate::{
+    chats::topic,
+    cmd_pin::{auto_pin, AutoPin},
+    cmd_settings::poll_settings,
+    dates::now,
+    i18n::{chat_language, Lang},
+    outbox::{Outbox, Priority},
+    permissions::pin_message,
+    poll_results::track_poll,
+    state::AppState,
+    templates::{template, BUREAU_QUESTION},
+    HandlerResult,
+};
 
-pub async fn bureau(bot: Bot, msg: Message) -> HandlerResult {
-    bot.send_poll(
+pub async fn bureau(bot: Bot, msg: Message, state: AppState) -> HandlerResult {
+    send_bureau_poll(
+        &bot,
+        &state.outbox,
+        state.db.as_ref(),
         msg.chat.id,
-        "Qui est au bureau ?",
-        [
-            "Je suis actuellement au bureau".to_owned(),
-            "Je suis à proximité du bureau".to_owned(),
-            "Je compte m'y rendre bientôt".to_owned(),
-            "J'y suis pas".to_owned(),
-            "Je suis à Satellite".to_owned(),
-            "Je suis pas en Suisse".to_owned(),
-        ],
+        topic(&msg),
+        Priority::Interactive,
     )
-    .is_anonymous(false)
-    .await?;
+    .await
+}
+
+/// Sends the poll querying who is at the desk, and records it so that the answers count in the
+/// hall of fame. Also used by the scheduled jobs.
+pub async fn send_bureau_poll(
+    bot: &Bot,
+    outbox: &Outbox,
+    db: &SqlitePool,
+    chat_id: ChatId,
+    thread_id: Option<i32>,
+    priority: Priority,
+) -> HandlerResult {
+    let lang = chat_language(db, chat_id).await;
+    let question = template(db, chat_id, BUREAU_QUESTION, lang).await;
+    let options = match lang {
+        Lang::Fr => [
+            "Je suis actuellement au bureau",
+            "Je suis à proximité du bureau",
+            "Je compte m'y rendre bientôt",
+            "J'y suis pas",
+            "Je suis à Satellite",
+            "Je suis pas en Suisse",
+        ],
+        Lang::En => [
+            "I am at the office right now",
+            "I am near the office",
+            "I will be there soon",
+            "I am not there",
+            "I am at Satellite",
+            "I am not in Switzerland",
+        ],
+    };
+    let settings = poll_settings(db, chat_id).await;
+    let mut poll = bot
+        .send_poll(chat_id, question, options.map(str::to_owned))
+        .is_anonymous(settings.bureau_anonymous)
+        .allows_multiple_answers(settings.bureau_multiple_answers);
+    if let Some(thread_id) = thread_id {
+        poll = poll.message_thread_id(thread_id);
+    }
+    let msg = outbox.send(chat_id, priority, poll).await?;
+    track_poll(db, &msg, settings.bureau_close_after).await?;
+    if auto_pin(db, chat_id, AutoPin::Bureau).await {
+        pin_message(bot, chat_id, msg.id, lang).await;
+    }
+
+    if let Some(poll) = msg.poll() {
+        let chat_id = chat_id.to_string();
+        let timestamp = now().timestamp();
+        sqlx::query!(
+            "INSERT INTO bureau_polls(poll_id, chat_id, sent_at) VALUES($1, $2, $3)",
+            poll.id,
+            chat_id,
+            timestamp
+        )
+        .execute(db)
+        .await?;
+    }
+
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,126 @@
+use std::{str::FromStr, sync::Arc};
+
+use chrono::{Duration, Utc};
+use chrono_tz::Tz;
+use cron::Schedule;
+use sqlx::SqlitePool;
+use teloxide::{
+    payloads::SendDocumentSetters,
+    requests::Requester,
+    types::{InputFile, Message},
+    Bot,
+};
+
+use crate::{
+    dates::{from_timestamp, now_in},
+    directus::get_upcoming_events,
+    i18n::{tr, Lang},
+    ics::{build_calendar, CalendarEvent},
+    outbox::Queued,
+    HandlerResult,
+};
+
+/// How far ahead the recurring messages of the chat are expanded in the calendar.
+const SCHEDULE_HORIZON_DAYS: i64 = 120;
+/// Maximum number of occurrences exported per recurring message.
+const SCHEDULE_MAX_OCCURRENCES: usize = 200;
+/// Duration given to events without an end (permanences, reminders, ...).
+const DEFAULT_EVENT_DURATION_MINUTES: i64 = 60;
+
+pub async fn calendar(
+    bot: Bot,
+    msg: Message,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+    timezone: Tz,
+) -> HandlerResult {
+    let mut events = vec![];
+
+    match get_upcoming_events().await {
+        Ok(directus_events) => events.extend(directus_events.into_iter().filter_map(|e| {
+            let start = e.start()?;
+            Some(CalendarEvent {
+                uid: format!("event-{}@clic.epfl.ch", e.id),
+                end: e
+                    .end()
+                    .unwrap_or(start + Duration::minutes(DEFAULT_EVENT_DURATION_MINUTES)),
+                start,
+                summary: e.title,
+                description: e.description,
+                location: e.location,
+            })
+        })),
+        Err(e) => log::error!("Could not fetch events: {e:#?}"),
+    }
+
+    let chat_id = msg.chat.id.to_string();
+    let now = now_in(timezone);
+    let horizon = now + Duration::days(SCHEDULE_HORIZON_DAYS);
+    let schedules = sqlx::query!(
+        r#"SELECT id AS "id!", cron, payload FROM schedules WHERE chat_id = $1"#,
+        chat_id
+    )
+    .fetch_all(db.as_ref())
+    .await?;
+    for s in schedules {
+        let Ok(schedule) = Schedule::from_str(&s.cron) else {
+            continue;
+        };
+        let summary = match s.payload.as_str() {
+            "/bureau" => tr!(lang, "Permanence au bureau", "Office hours"),
+            text => text.to_owned(),
+        };
+
+        events.extend(
+            schedule
+                .after(&now)
+                .take_while(|d| *d < horizon)
+                .take(SCHEDULE_MAX_OCCURRENCES)
+                .map(|d| CalendarEvent {
+                    uid: format!("schedule-{}-{}@clic.epfl.ch", s.id, d.timestamp()),
+                    summary: summary.clone(),
+                    description: None,
+                    location: None,
+                    start: d.with_timezone(&Utc),
+                    end: d.with_timezone(&Utc)
+                        + Duration::minutes(DEFAULT_EVENT_DURATION_MINUTES),
+                }),
+        );
+    }
+
+    let reminders = sqlx::query!(
+        r#"SELECT id AS "id!", "text", due_at FROM reminders WHERE chat_id = $1"#,
+        chat_id
+    )
+    .fetch_all(db.as_ref())
+    .await?;
+    events.extend(reminders.into_iter().filter_map(|r| {
+        let start = from_timestamp(r.due_at, timezone)?.with_timezone(&Utc);
+        Some(CalendarEvent {
+            uid: format!("reminder-{}@clic.epfl.ch", r.id),
+            summary: tr!(lang, "Rappel: {}", "Reminder: {}", r.text),
+            description: None,
+            location: None,
+            start,
+            end: start + Duration::minutes(DEFAULT_EVENT_DURATION_MINUTES),
+        })
+    }));
+
+    events.sort_by_key(|e| e.start);
+
+    log::debug!("Sending calendar with {} event(s)", events.len());
+    bot.send_document(
+        msg.chat.id,
+        InputFile::memory(build_calendar("CLIC", &events)).file_name("clic.ics"),
+    )
+    .caption(tr!(
+        lang,
+        "{} événement(s) à importer dans ton calendrier",
+        "{} event(s) to import in your calendar",
+        events.len()
+    ))
+    .queued()
+    .await?;
+
+    Ok(())
+}
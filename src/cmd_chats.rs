@@ -0,0 +1,118 @@
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+use teloxide::{
+    requests::Requester,
+    types::{ChatId, Message},
+    Bot,
+};
+
+use crate::{
+    audit::{actor, audit},
+    chats::{purge_chat, remap_chat},
+    format::{bold, code, escape, italic, HtmlMessages, MessageBuilder},
+    outbox::Queued,
+    state::AppState,
+    HandlerResult,
+};
+
+const USAGE: &str = "Utilisation:\n - /chat remap <ancien id> <nouvel id>: déplace toutes les données d'un groupe vers un autre\n - /chat purge <id>: supprime toutes les données d'un groupe";
+
+pub async fn chat(bot: Bot, msg: Message, args: String, db: Arc<SqlitePool>) -> HandlerResult {
+    let words = args.split_whitespace().collect::<Vec<_>>();
+    let parse = |id: &str| id.parse::<i64>().ok().map(ChatId);
+
+    let text = match words[..] {
+        ["remap", from, to] => match (parse(from), parse(to)) {
+            (Some(from), Some(to)) if from != to => {
+                let moved = remap_chat(db.as_ref(), from, to).await?;
+                let details = format!("{} -> {} ({} rows)", from, to, moved);
+                audit(db.as_ref(), &actor(&msg), "chat_remap", &details).await;
+                format!(
+                    "{} entrées déplacées de {} vers {}",
+                    moved,
+                    code(&from.to_string()),
+                    code(&to.to_string())
+                )
+            }
+            _ => escape(USAGE),
+        },
+        ["purge", id] => match parse(id) {
+            Some(id) => {
+                let deleted = purge_chat(db.as_ref(), id).await?;
+                let details = format!("{} ({} rows)", id, deleted);
+                audit(db.as_ref(), &actor(&msg), "chat_purge", &details).await;
+                format!(
+                    "{} entrées supprimées pour le groupe {}",
+                    deleted,
+                    code(&id.to_string())
+                )
+            }
+            None => escape(USAGE),
+        },
+        _ => escape(USAGE),
+    };
+
+    bot.send_html(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
+
+/// Lists the chats of the registry, with their authorizations.
+pub async fn chats(bot: Bot, msg: Message, state: AppState) -> HandlerResult {
+    let chats = sqlx::query!(
+        r#"SELECT chat_id AS "chat_id!", title, kind, member_count, left_at FROM chats
+        ORDER BY left_at IS NOT NULL, title"#
+    )
+    .fetch_all(state.db.as_ref())
+    .await?;
+    let authorizations = state.authorization_repo.authorizations("").await?;
+
+    if chats.is_empty() {
+        bot.send_message(msg.chat.id, "Aucun groupe enregistré")
+            .queued()
+            .await?;
+        return Ok(());
+    }
+
+    let lines = chats
+        .into_iter()
+        .map(|c| {
+            let mut line = format!(
+                "{} ({}",
+                bold(c.title.as_deref().unwrap_or("Sans titre")),
+                escape(c.kind.as_deref().unwrap_or("?"))
+            );
+            if let Some(count) = c.member_count {
+                line.push_str(&format!(", {} membres", count));
+            }
+            line.push_str(&format!("): {}", code(&c.chat_id)));
+            if c.left_at.is_some() {
+                line.push_str(" [quitté]");
+            }
+            let commands = authorizations
+                .iter()
+                .filter(|a| a.chat_id == c.chat_id)
+                .map(|a| a.command.as_str())
+                .collect::<Vec<_>>();
+            line.push_str(&format!(
+                "\n   {}: {}",
+                italic("Autorisations"),
+                if commands.is_empty() {
+                    "aucune".to_owned()
+                } else {
+                    escape(&commands.join(", "))
+                }
+            ));
+            line
+        })
+        .collect::<Vec<_>>();
+
+    let text = MessageBuilder::new()
+        .title("Groupes du bot")
+        .items(lines)
+        .build();
+    bot.send_html(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
@@ -0,0 +1,340 @@
+//! Check-in of the participants of the events. Each registration gets a code, sent to the member
+//! in private as a QR code (with the code in the caption). At the entrance, the organizers forward
+//! the message of the participant, or use `/checkin <code>` with the code read from the QR code.
+
+use std::{error::Error, sync::Arc};
+
+use qrcode::{Color, QrCode};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use sqlx::SqlitePool;
+use teloxide::{
+    payloads::{SendMessageSetters, SendPhotoSetters},
+    requests::Requester,
+    types::{ChatId, InputFile, Message, ParseMode, UserId},
+    Bot,
+};
+
+use crate::{
+    dates::now,
+    directus::{get_event, get_upcoming_events},
+    format::{bold, code, escape, HtmlMessages, MessageBuilder},
+    i18n::{chat_language, tr, Lang},
+    outbox::Queued,
+    HandlerResult,
+};
+
+const CODE_PREFIX: &str = "CHK-";
+const CODE_LENGTH: usize = 8;
+/// Size in pixels of a module (square) of the QR codes.
+const MODULE_SIZE: usize = 8;
+/// Width of the white border around the QR codes, in modules.
+const QUIET_ZONE: usize = 4;
+
+fn generate_code() -> String {
+    let suffix = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(CODE_LENGTH)
+        .map(|c| char::from(c).to_ascii_uppercase())
+        .collect::<String>();
+    format!("{}{}", CODE_PREFIX, suffix)
+}
+
+/// Check-in code in the text or caption of the message, if any.
+pub fn checkin_code(msg: &Message) -> Option<String> {
+    msg.text()
+        .or(msg.caption())?
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != '-')
+        .map(str::to_uppercase)
+        .find(|w| {
+            w.strip_prefix(CODE_PREFIX)
+                .is_some_and(|s| s.len() == CODE_LENGTH)
+        })
+}
+
+/// PNG image of the QR code of the text.
+fn qr_png(text: &str) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let qr = QrCode::new(text)?;
+    let modules = qr.width();
+    let colors = qr.to_colors();
+    let size = (modules + 2 * QUIET_ZONE) * MODULE_SIZE;
+
+    let mut pixels = vec![u8::MAX; size * size];
+    for (i, color) in colors.iter().enumerate() {
+        if *color != Color::Dark {
+            continue;
+        }
+        let (x, y) = (i % modules + QUIET_ZONE, i / modules + QUIET_ZONE);
+        for dy in 0..MODULE_SIZE {
+            let start = (y * MODULE_SIZE + dy) * size + x * MODULE_SIZE;
+            pixels[start..start + MODULE_SIZE].fill(0);
+        }
+    }
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, size as u32, size as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels)?;
+    writer.finish()?;
+
+    Ok(png)
+}
+
+/// Sends the check-in code of the registration to the member in private, unless it was already
+/// sent. Returns whether the member has received the code.
+pub async fn send_checkin_code(
+    bot: &Bot,
+    db: &SqlitePool,
+    event_id: i32,
+    user_id: UserId,
+) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    let user = user_id.to_string();
+    let Some(registration) = sqlx::query!(
+        "SELECT code, code_sent_at FROM event_registrations
+        WHERE event_id = $1 AND user_id = $2 AND attending",
+        event_id,
+        user
+    )
+    .fetch_optional(db)
+    .await?
+    else {
+        return Ok(false);
+    };
+    if registration.code_sent_at.is_some() {
+        return Ok(true);
+    }
+
+    let checkin_code = match registration.code {
+        Some(code) => code,
+        None => {
+            let code = generate_code();
+            sqlx::query!(
+                "UPDATE event_registrations SET code = $1 WHERE event_id = $2 AND user_id = $3",
+                code,
+                event_id,
+                user
+            )
+            .execute(db)
+            .await?;
+            code
+        }
+    };
+
+    let chat_id = ChatId(user_id.0 as i64);
+    let lang = chat_language(db, chat_id).await;
+    let title = match get_event(event_id).await {
+        Ok(event) => event.title,
+        Err(e) => {
+            log::error!("Could not fetch event {}: {e:#?}", event_id);
+            tr!(lang, "l'événement", "the event")
+        }
+    };
+    let caption = tr!(
+        lang,
+        "🎟 Ton code d'entrée pour {}: {}\nPrésente ce QR code à l'entrée, ou transfère ce message aux organisateurs.",
+        "🎟 Your entry code for {}: {}\nShow this QR code at the entrance, or forward this message to the organizers.",
+        bold(&title),
+        code(&checkin_code)
+    );
+    let sent = bot
+        .send_photo(
+            chat_id,
+            InputFile::memory(qr_png(&checkin_code)?).file_name("checkin.png"),
+        )
+        .caption(caption)
+        .parse_mode(ParseMode::Html)
+        .queued()
+        .await;
+    if let Err(e) = sent {
+        // The member never started a private chat with the bot
+        log::debug!("Could not send check-in code to {}: {:?}", user_id, e);
+        return Ok(false);
+    }
+
+    let timestamp = now().timestamp();
+    sqlx::query!(
+        "UPDATE event_registrations SET code_sent_at = $1 WHERE event_id = $2 AND user_id = $3",
+        timestamp,
+        event_id,
+        user
+    )
+    .execute(db)
+    .await?;
+
+    Ok(true)
+}
+
+/// Participants checked in and registered to the event.
+async fn attendance(db: &SqlitePool, event_id: i32) -> Result<(i64, i64), sqlx::Error> {
+    let counts = sqlx::query!(
+        r#"SELECT COUNT(checked_in_at) AS "present!: i64", COUNT(*) AS "registered!: i64"
+        FROM event_registrations WHERE event_id = $1 AND attending"#,
+        event_id
+    )
+    .fetch_one(db)
+    .await?;
+    Ok((counts.present, counts.registered))
+}
+
+/// Checks the participant of the code in, and answers with the attendance of the event.
+async fn check_in(
+    bot: &Bot,
+    msg: &Message,
+    db: &SqlitePool,
+    code: &str,
+    lang: Lang,
+) -> HandlerResult {
+    let timestamp = now().timestamp();
+    let checked = sqlx::query!(
+        r#"UPDATE event_registrations SET checked_in_at = $1
+        WHERE code = $2 AND attending AND checked_in_at IS NULL
+        RETURNING event_id AS "event_id: i32", user_name"#,
+        timestamp,
+        code
+    )
+    .fetch_optional(db)
+    .await?;
+
+    let text = match checked {
+        Some(checked) => {
+            let (present, registered) = attendance(db, checked.event_id).await?;
+            tr!(
+                lang,
+                "✅ {} est enregistré·e ({}/{} présent(s))",
+                "✅ {} is checked in ({}/{} present)",
+                bold(&checked.user_name),
+                present,
+                registered
+            )
+        }
+        None => {
+            let registration = sqlx::query!(
+                r#"SELECT user_name, attending AS "attending: bool" FROM event_registrations
+                WHERE code = $1"#,
+                code
+            )
+            .fetch_optional(db)
+            .await?;
+            match registration {
+                Some(r) if r.attending => tr!(
+                    lang,
+                    "⚠️ {} est déjà entré·e",
+                    "⚠️ {} is already checked in",
+                    bold(&r.user_name)
+                ),
+                Some(r) => tr!(
+                    lang,
+                    "❌ {} s'est désinscrit·e de l'événement",
+                    "❌ {} unregistered from the event",
+                    bold(&r.user_name)
+                ),
+                None => tr!(
+                    lang,
+                    "❌ Code inconnu: {}",
+                    "❌ Unknown code: {}",
+                    escape(code)
+                ),
+            }
+        }
+    };
+    bot.send_html(msg.chat.id, text)
+        .reply_to_message_id(msg.id)
+        .queued()
+        .await?;
+
+    Ok(())
+}
+
+/// `/checkin <code>` checks a participant in.
+pub async fn checkin(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> HandlerResult {
+    let code = args.trim().to_uppercase();
+    if code.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Utilisation: /checkin <code>, ou transfère ici le message du code d'un participant",
+                "Usage: /checkin <code>, or forward here the code message of a participant"
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+    check_in(&bot, &msg, db.as_ref(), &code, lang).await
+}
+
+/// Checks in the participant whose code message was forwarded.
+pub async fn checkin_forward(
+    bot: Bot,
+    msg: Message,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> HandlerResult {
+    let Some(code) = checkin_code(&msg) else {
+        return Ok(());
+    };
+    check_in(&bot, &msg, db.as_ref(), &code, lang).await
+}
+
+/// `/attendance` displays the number of participants checked in to the ongoing and upcoming
+/// events open for registration.
+pub async fn attendance_count(
+    bot: Bot,
+    msg: Message,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> HandlerResult {
+    let events = match get_upcoming_events().await {
+        Ok(events) => events,
+        Err(e) => {
+            log::error!("Could not fetch events: {e:#?}");
+            bot.send_message(
+                msg.chat.id,
+                tr!(
+                    lang,
+                    "Impossible de récupérer les événements",
+                    "Could not fetch the events"
+                ),
+            )
+            .queued()
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let mut lines = Vec::new();
+    for event in events.iter().filter(|e| e.registrable) {
+        let (present, registered) = attendance(db.as_ref(), event.id).await?;
+        lines.push(tr!(
+            lang,
+            "{}: {}/{} présent(s)",
+            "{}: {}/{} present",
+            bold(&event.title),
+            present,
+            registered
+        ));
+    }
+    let text = if lines.is_empty() {
+        tr!(
+            lang,
+            "Aucun événement en cours n'est ouvert aux inscriptions",
+            "No current event is open for registration"
+        )
+    } else {
+        MessageBuilder::new()
+            .title(&tr!(lang, "🎟 Présences", "🎟 Attendance"))
+            .items(lines)
+            .build()
+    };
+    bot.send_html(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
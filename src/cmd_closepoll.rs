@@ -0,0 +1,176 @@
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+use teloxide::{
+    requests::Requester,
+    types::{ChatId, Message, MessageId, Poll},
+    Bot,
+};
+
+use crate::{
+    dates::now,
+    format::{bold, escape, HtmlMessages},
+    i18n::{chat_language, tr, Lang},
+    outbox::{Outbox, Priority, Queued},
+    poll_results::archive,
+    HandlerResult,
+};
+
+/// Final counts of the poll, with the correct answer of the quizzes.
+fn format_results(poll: &Poll, lang: Lang) -> String {
+    let total = poll.options.iter().map(|o| o.voter_count).sum::<i32>();
+    let options = poll
+        .options
+        .iter()
+        .enumerate()
+        .map(|(i, o)| {
+            let correct = poll.correct_option_id == Some(i as u8);
+            format!(
+                "{} {}: {} ({}%)",
+                if correct { "✅" } else { "▫️" },
+                escape(&o.text),
+                o.voter_count,
+                if total > 0 {
+                    o.voter_count * 100 / total
+                } else {
+                    0
+                }
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        "📊 {}\n{}\n\n{}",
+        bold(&poll.question),
+        tr!(
+            lang,
+            "{} participant(s)",
+            "{} voter(s)",
+            poll.total_voter_count
+        ),
+        options
+    )
+}
+
+/// Stops the poll and archives its results. `None` if it could not be stopped (e.g. it was
+/// deleted), it is then no longer tracked.
+async fn stop(
+    bot: &Bot,
+    db: &SqlitePool,
+    chat_id: ChatId,
+    poll_id: &str,
+    message_id: i64,
+) -> Result<Option<Poll>, sqlx::Error> {
+    match bot
+        .stop_poll(chat_id, MessageId(message_id as i32))
+        .queued()
+        .await
+    {
+        Ok(poll) => {
+            archive(db, &poll).await?;
+            Ok(Some(poll))
+        }
+        Err(e) => {
+            log::warn!("Could not stop poll {}: {:?}", poll_id, e);
+            sqlx::query!(
+                "UPDATE sent_polls SET closed_at = sent_at WHERE poll_id = $1",
+                poll_id
+            )
+            .execute(db)
+            .await?;
+            Ok(None)
+        }
+    }
+}
+
+/// `/closepoll` stops the last open poll sent by the bot in the chat, and posts its results.
+pub async fn close_poll(bot: Bot, msg: Message, db: Arc<SqlitePool>, lang: Lang) -> HandlerResult {
+    let chat_id = msg.chat.id.to_string();
+    let Some(open) = sqlx::query!(
+        r#"SELECT poll_id AS "poll_id!", message_id FROM sent_polls WHERE chat_id = $1 AND closed_at IS NULL
+        ORDER BY sent_at DESC LIMIT 1"#,
+        chat_id
+    )
+    .fetch_optional(db.as_ref())
+    .await?
+    else {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Aucun sondage ouvert dans ce groupe",
+                "No open poll in this chat"
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    let Some(poll) = stop(
+        &bot,
+        db.as_ref(),
+        msg.chat.id,
+        &open.poll_id,
+        open.message_id,
+    )
+    .await?
+    else {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Le dernier sondage n'a pas pu être fermé, il a peut-être été supprimé",
+                "The last poll could not be closed, it may have been deleted"
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    bot.send_html(msg.chat.id, format_results(&poll, lang))
+        .queued()
+        .await?;
+
+    Ok(())
+}
+
+/// Closes the polls which reached the auto-close delay of their chat, and posts their results.
+pub async fn close_due_polls(
+    bot: &Bot,
+    outbox: &Outbox,
+    db: &SqlitePool,
+) -> Result<(), sqlx::Error> {
+    let timestamp = now().timestamp();
+    let due = sqlx::query!(
+        r#"SELECT poll_id AS "poll_id!", chat_id, message_id FROM sent_polls
+        WHERE closed_at IS NULL AND close_at <= $1"#,
+        timestamp
+    )
+    .fetch_all(db)
+    .await?;
+
+    for p in due {
+        let Ok(chat_id) = p.chat_id.parse::<i64>().map(ChatId) else {
+            continue;
+        };
+        let Some(poll) = stop(bot, db, chat_id, &p.poll_id, p.message_id).await? else {
+            continue;
+        };
+        let lang = chat_language(db, chat_id).await;
+        if let Err(e) = outbox
+            .send(
+                chat_id,
+                Priority::Bulk,
+                bot.send_html(chat_id, format_results(&poll, lang)),
+            )
+            .await
+        {
+            log::error!("Could not send results of poll {}: {:?}", p.poll_id, e);
+        }
+    }
+
+    Ok(())
+}
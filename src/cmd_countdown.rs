@@ -0,0 +1,198 @@
+use std::sync::Arc;
+
+use chrono::DateTime;
+use chrono_tz::Tz;
+use sqlx::SqlitePool;
+use teloxide::{
+    requests::Requester,
+    types::{ChatId, Message, MessageId},
+    Bot,
+};
+
+use crate::{
+    cmd_pin::{auto_pin, AutoPin},
+    dates::{chat_timezone, format_datetime, from_timestamp, now_in, parse_datetime},
+    directus::get_upcoming_events,
+    format::{bold, escape, HtmlMessages},
+    i18n::{chat_language, tr, Lang},
+    outbox::{Outbox, Priority, Queued},
+    permissions::pin_message,
+    HandlerResult,
+};
+
+fn usage(lang: Lang) -> String {
+    tr!(
+        lang,
+        "Utilisation: /countdown [pin] <événement ou date> [description], par exemple: /countdown pin 15/02 week-end ski",
+        "Usage: /countdown [pin] <event or date> [description], e.g. /countdown pin next friday 6pm ski weekend"
+    )
+}
+
+/// Number of days between today and the given date, in the timezone of the date.
+fn days_until(target: &DateTime<Tz>) -> i64 {
+    (target.date_naive() - now_in(target.timezone()).date_naive()).num_days()
+}
+
+fn render(label: &str, target: &DateTime<Tz>, lang: Lang) -> String {
+    let label = bold(label);
+    match days_until(target) {
+        days if days > 1 => tr!(
+            lang,
+            "⏳ J-{} avant {} ({})",
+            "⏳ {} days until {} ({})",
+            days,
+            label,
+            format_datetime(target)
+        ),
+        1 => tr!(
+            lang,
+            "⏳ {} c'est demain ! ({})",
+            "⏳ {} is tomorrow! ({})",
+            label,
+            format_datetime(target)
+        ),
+        0 => tr!(lang, "🎉 {} c'est aujourd'hui !", "🎉 {} is today!", label),
+        _ => tr!(lang, "{} est passé", "{} is over", label),
+    }
+}
+
+/// Finds the target of the countdown, either a date (followed by a description) or the title of
+/// an upcoming Directus event.
+async fn find_target(args: &str, lang: Lang, timezone: Tz) -> Option<(String, DateTime<Tz>)> {
+    if let Some((date, label)) = parse_datetime(args, now_in(timezone)) {
+        let label = if label.is_empty() {
+            tr!(lang, "l'événement", "the event")
+        } else {
+            label.to_owned()
+        };
+        return Some((label, date));
+    }
+
+    let query = args.to_lowercase();
+    match get_upcoming_events().await {
+        Ok(events) => events
+            .into_iter()
+            .filter(|e| e.title.to_lowercase().contains(&query))
+            .find_map(|e| Some((e.title.clone(), e.start()?.with_timezone(&timezone)))),
+        Err(e) => {
+            log::error!("Could not fetch events: {e:#?}");
+            None
+        }
+    }
+}
+
+pub async fn countdown(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+    timezone: Tz,
+) -> HandlerResult {
+    let args = args.trim();
+    let (pin, args) = match args.split_once(char::is_whitespace) {
+        Some(("pin", rest)) => (true, rest.trim()),
+        _ => (false, args),
+    };
+
+    if args.is_empty() {
+        bot.send_message(msg.chat.id, usage(lang)).queued().await?;
+        return Ok(());
+    }
+
+    let Some((label, target)) = find_target(args, lang, timezone).await else {
+        bot.send_html(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Aucun événement ou date trouvé pour \"{}\"\n{}",
+                "No event or date found for \"{}\"\n{}",
+                escape(args),
+                escape(&usage(lang))
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    let sent = bot
+        .send_html(msg.chat.id, render(&label, &target, lang))
+        .queued()
+        .await?;
+
+    if pin || auto_pin(db.as_ref(), msg.chat.id, AutoPin::Countdowns).await {
+        pin_message(&bot, msg.chat.id, sent.id, lang).await;
+
+        let chat_id = msg.chat.id.to_string();
+        let message_id = sent.id.0;
+        let timestamp = target.timestamp();
+        let days_left = days_until(&target);
+        sqlx::query!(
+            "INSERT INTO countdowns(chat_id, message_id, label, target, days_left) VALUES($1, $2, $3, $4, $5)",
+            chat_id,
+            message_id,
+            label,
+            timestamp,
+            days_left
+        )
+        .execute(db.as_ref())
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Updates the pinned countdowns whose number of days changed, and forgets the ones which are
+/// over.
+pub async fn update_countdowns(
+    bot: &Bot,
+    outbox: &Outbox,
+    db: &SqlitePool,
+) -> Result<(), sqlx::Error> {
+    let countdowns = sqlx::query!(
+        r#"SELECT id AS "id!", chat_id, message_id, label, target, days_left FROM countdowns"#
+    )
+    .fetch_all(db)
+    .await?;
+
+    for c in countdowns {
+        let Ok(chat_id) = c.chat_id.parse::<i64>().map(ChatId) else {
+            continue;
+        };
+        let timezone = chat_timezone(db, chat_id).await;
+        let Some(target) = from_timestamp(c.target, timezone) else {
+            continue;
+        };
+        let days_left = days_until(&target);
+        if days_left == c.days_left {
+            continue;
+        }
+
+        let lang = chat_language(db, chat_id).await;
+        let edit = bot.edit_html(
+            chat_id,
+            MessageId(c.message_id as i32),
+            render(&c.label, &target, lang),
+        );
+        if let Err(e) = outbox.send(chat_id, Priority::Bulk, edit).await {
+            log::error!("Could not update countdown #{}: {:?}", c.id, e);
+        }
+
+        if days_left < 0 {
+            sqlx::query!("DELETE FROM countdowns WHERE id = $1", c.id)
+                .execute(db)
+                .await?;
+        } else {
+            sqlx::query!(
+                "UPDATE countdowns SET days_left = $1 WHERE id = $2",
+                days_left,
+                c.id
+            )
+            .execute(db)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
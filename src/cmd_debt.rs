@@ -0,0 +1,173 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use sqlx::SqlitePool;
+use teloxide::{
+    requests::Requester,
+    types::{Message, User},
+    Bot,
+};
+
+use crate::{
+    dates::now,
+    format::{escape, HtmlMessages, MessageBuilder},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    HandlerResult,
+};
+
+fn usage(lang: Lang) -> String {
+    tr!(
+        lang,
+        "Utilisation:\n - /debt add @personne <nombre> <raison>: @personne te doit <nombre> <raison>\n - /debt list: liste les dettes du groupe\n - /debt settle @personne: solde les dettes entre toi et @personne",
+        "Usage:\n - /debt add @someone <count> <reason>: @someone owes you <count> <reason>\n - /debt list: list the debts of the chat\n - /debt settle @someone: settle the debts between you and @someone"
+    )
+}
+
+fn owes(lang: Lang, debtor: &str, amount: i64, reason: &str, creditor: &str) -> String {
+    tr!(
+        lang,
+        "{} doit {} {} à {}",
+        "{} owes {} {} to {}",
+        escape(debtor),
+        amount,
+        escape(reason),
+        escape(creditor)
+    )
+}
+
+/// Name identifying a user in the ledger: the username if any, so that it matches the mentions.
+fn ledger_name(user: &User) -> String {
+    user.username
+        .as_ref()
+        .map(|u| format!("@{}", u))
+        .unwrap_or_else(|| user.full_name())
+}
+
+pub async fn debt(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> HandlerResult {
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+    let me = ledger_name(user);
+    let chat_id = msg.chat.id.to_string();
+    let words = args.split_whitespace().collect::<Vec<_>>();
+
+    let text = match words[..] {
+        ["add", other, amount, ref reason @ ..] if other.starts_with('@') && !reason.is_empty() => {
+            let Ok(amount) = amount.parse::<i64>().map(|a| a.max(0)) else {
+                bot.send_message(msg.chat.id, usage(lang)).queued().await?;
+                return Ok(());
+            };
+            if amount == 0 || other == me {
+                bot.send_message(msg.chat.id, usage(lang)).queued().await?;
+                return Ok(());
+            }
+
+            let reason = reason.join(" ");
+            let timestamp = now().timestamp();
+            sqlx::query!(
+                "INSERT INTO debts(chat_id, creditor, debtor, amount, reason, created_at) VALUES($1, $2, $3, $4, $5, $6)",
+                chat_id,
+                me,
+                other,
+                amount,
+                reason,
+                timestamp
+            )
+            .execute(db.as_ref())
+            .await?;
+
+            format!(
+                "{} {}",
+                tr!(lang, "Noté:", "Noted:"),
+                owes(lang, other, amount, &reason, &me)
+            )
+        }
+        ["list"] | [] => {
+            let debts = sqlx::query!(
+                "SELECT creditor, debtor, amount, reason FROM debts WHERE chat_id = $1 AND NOT settled ORDER BY id",
+                chat_id
+            )
+            .fetch_all(db.as_ref())
+            .await?;
+
+            // Net amount per (debtor, creditor, reason), people owing each other cancel out
+            let mut balances = BTreeMap::<(String, String, String), i64>::new();
+            for d in debts {
+                if d.debtor < d.creditor {
+                    *balances
+                        .entry((d.debtor, d.creditor, d.reason))
+                        .or_default() += d.amount;
+                } else {
+                    *balances
+                        .entry((d.creditor, d.debtor, d.reason))
+                        .or_default() -= d.amount;
+                }
+            }
+
+            let lines = balances
+                .into_iter()
+                .filter(|(_, amount)| *amount != 0)
+                .map(|((a, b, reason), amount)| {
+                    if amount > 0 {
+                        owes(lang, &a, amount, &reason, &b)
+                    } else {
+                        owes(lang, &b, -amount, &reason, &a)
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            if lines.is_empty() {
+                tr!(
+                    lang,
+                    "Personne ne doit rien à personne",
+                    "Nobody owes anything to anybody"
+                )
+            } else {
+                MessageBuilder::new()
+                    .title(&tr!(lang, "Dettes en cours", "Pending debts"))
+                    .items(lines)
+                    .build()
+            }
+        }
+        ["settle", other] if other.starts_with('@') => {
+            let settled = sqlx::query!(
+                "UPDATE debts SET settled = TRUE WHERE chat_id = $1 AND NOT settled AND ((creditor = $2 AND debtor = $3) OR (creditor = $3 AND debtor = $2))",
+                chat_id,
+                me,
+                other
+            )
+            .execute(db.as_ref())
+            .await?
+            .rows_affected();
+
+            if settled == 0 {
+                tr!(
+                    lang,
+                    "Aucune dette entre {} et {}",
+                    "No debt between {} and {}",
+                    escape(&me),
+                    escape(other)
+                )
+            } else {
+                tr!(
+                    lang,
+                    "Les dettes entre {} et {} sont soldées",
+                    "The debts between {} and {} are settled",
+                    escape(&me),
+                    escape(other)
+                )
+            }
+        }
+        _ => escape(&usage(lang)),
+    };
+
+    bot.send_html(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+use teloxide::{requests::Requester, types::Message, Bot};
+
+use crate::{
+    audit::{actor, audit},
+    committee::committee_repository,
+    directus::{self, uses_login},
+    format::{escape, HtmlMessages, MessageBuilder},
+    outbox::Queued,
+    HandlerResult,
+};
+
+pub async fn directus_status(bot: Bot, msg: Message) -> HandlerResult {
+    let mode = if uses_login() {
+        "identifiants (login + refresh token)"
+    } else {
+        "token statique"
+    };
+
+    let status = match directus::status().await {
+        Ok(user) => format!("Connecté en tant que {}", user),
+        Err(e) => {
+            log::error!("Directus status check failed: {e:#?}");
+            format!("Erreur: {}", e)
+        }
+    };
+
+    let text = MessageBuilder::new()
+        .title("Directus")
+        .field("Authentification", escape(mode))
+        .field("Statut", escape(&status))
+        .build();
+    bot.send_html(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
+
+/// Fetches the committee from Directus again, e.g. after members were added or removed there.
+pub async fn committee_sync(bot: Bot, msg: Message, db: Arc<SqlitePool>) -> HandlerResult {
+    committee_repository().invalidate().await;
+    let text = match committee_repository().refresh().await {
+        Ok(committee) => {
+            let details = format!("{} member(s)", committee.len());
+            audit(db.as_ref(), &actor(&msg), "committee_sync", &details).await;
+            format!("Comité synchronisé: {} membre(s)", committee.len())
+        }
+        Err(e) => {
+            log::error!("Could not fetch committee: {e:#?}");
+            format!("Erreur: {}", e)
+        }
+    };
+    bot.send_message(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
@@ -0,0 +1,349 @@
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+use teloxide::{
+    payloads::{EditMessageTextSetters, SendMessageSetters},
+    requests::Requester,
+    types::{CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup, Message, MessageId},
+    Bot,
+};
+
+use crate::{
+    callbacks::{CallbackData, CallbackResult, DOODLE_VOTE},
+    db::retry_busy,
+    format::{bold, escape, HtmlMessages, MessageBuilder},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    HandlerResult,
+};
+
+/// Maximum number of slots of a doodle.
+const MAX_OPTIONS: usize = 10;
+
+struct Slot {
+    label: String,
+    voters: Vec<String>,
+}
+
+/// Parses `<title> | <option1>; <option2>; ...` or `<title> <option1>; <option2>; ...` (in which
+/// case the title is a single word).
+fn parse_arguments(args: &str) -> Option<(String, Vec<String>)> {
+    let (title, options) = args
+        .split_once('|')
+        .or_else(|| args.trim().split_once(char::is_whitespace))?;
+
+    let options = options
+        .split(';')
+        .map(|o| o.trim().to_owned())
+        .filter(|o| !o.is_empty())
+        .collect::<Vec<_>>();
+    let title = title.trim().to_owned();
+
+    if title.is_empty() || options.len() < 2 || options.len() > MAX_OPTIONS {
+        None
+    } else {
+        Some((title, options))
+    }
+}
+
+pub async fn doodle(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> HandlerResult {
+    let Some((title, options)) = parse_arguments(&args) else {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Utilisation: /doodle <titre> | <créneau 1>; <créneau 2>; ... (entre 2 et {} créneaux)",
+                "Usage: /doodle <title> | <slot 1>; <slot 2>; ... (between 2 and {} slots)",
+                MAX_OPTIONS
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    let chat_id = msg.chat.id.to_string();
+    let (pool, chat_id, title, options) = (db.as_ref(), &chat_id, &title, &options);
+    let id = retry_busy(|| async move {
+        let mut tx = pool.begin().await?;
+        let id = sqlx::query!(
+            "INSERT INTO doodles(chat_id, title) VALUES($1, $2)",
+            chat_id,
+            title
+        )
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+
+        for (position, label) in options.iter().enumerate() {
+            let position = position as i64;
+            sqlx::query!(
+                "INSERT INTO doodle_options(doodle_id, position, label) VALUES($1, $2, $3)",
+                id,
+                position,
+                label
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(id)
+    })
+    .await?;
+
+    let slots = options
+        .iter()
+        .map(|label| Slot {
+            label: label.clone(),
+            voters: vec![],
+        })
+        .collect::<Vec<_>>();
+
+    let sent = bot
+        .send_html(msg.chat.id, render_text(title, &slots, false, lang))
+        .reply_markup(render_keyboard(id, &slots))
+        .queued()
+        .await?;
+
+    let message_id = sent.id.0;
+    sqlx::query!(
+        "UPDATE doodles SET message_id = $1 WHERE id = $2",
+        message_id,
+        id
+    )
+    .execute(db.as_ref())
+    .await?;
+
+    Ok(())
+}
+
+/// Toggles the availability of the user for the selected slot, and updates the tallies.
+pub async fn doodle_vote(
+    bot: Bot,
+    query: CallbackQuery,
+    data: CallbackData,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> CallbackResult {
+    let Some((doodle_id, position)) = data
+        .payload
+        .split_once(':')
+        .and_then(|(id, position)| Some((id.parse::<i64>().ok()?, position.parse::<i64>().ok()?)))
+    else {
+        return Ok(None);
+    };
+
+    let doodle = sqlx::query!(
+        "SELECT title, chat_id, message_id, closed FROM doodles WHERE id = $1",
+        doodle_id
+    )
+    .fetch_optional(db.as_ref())
+    .await?;
+    let Some(doodle) = doodle.filter(|d| !d.closed) else {
+        return Ok(Some(tr!(
+            lang,
+            "Ce doodle est fermé",
+            "This doodle is closed"
+        )));
+    };
+
+    let (pool, user_id, user_name) = (
+        db.as_ref(),
+        &query.from.id.to_string(),
+        &query.from.full_name(),
+    );
+    let removed = retry_busy(|| async move {
+        let mut tx = pool.begin().await?;
+        let removed = sqlx::query!(
+            "DELETE FROM doodle_votes WHERE doodle_id = $1 AND position = $2 AND user_id = $3",
+            doodle_id,
+            position,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        if removed == 0 {
+            sqlx::query!(
+                "INSERT INTO doodle_votes(doodle_id, position, user_id, user_name) VALUES($1, $2, $3, $4)",
+                doodle_id,
+                position,
+                user_id,
+                user_name
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(removed)
+    })
+    .await?;
+
+    if let (Ok(chat_id), Some(message_id)) = (doodle.chat_id.parse::<i64>(), doodle.message_id) {
+        let slots = load_slots(db.as_ref(), doodle_id).await?;
+        bot.edit_html(
+            teloxide::types::ChatId(chat_id),
+            MessageId(message_id as i32),
+            render_text(&doodle.title, &slots, false, lang),
+        )
+        .reply_markup(render_keyboard(doodle_id, &slots))
+        .queued()
+        .await?;
+    }
+
+    Ok(Some(if removed == 0 {
+        tr!(lang, "Disponibilité ajoutée", "Availability added")
+    } else {
+        tr!(lang, "Disponibilité retirée", "Availability removed")
+    }))
+}
+
+/// Closes the last open doodle of the chat (or the one replied to), and announces the slot
+/// with the most availabilities.
+pub async fn doodle_close(
+    bot: Bot,
+    msg: Message,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.to_string();
+    let replied_id = msg.reply_to_message().map(|m| m.id.0);
+
+    let doodle = sqlx::query!(
+        r#"SELECT id AS "id!", title, message_id FROM doodles
+        WHERE chat_id = $1 AND NOT closed AND ($2 IS NULL OR message_id = $2)
+        ORDER BY id DESC LIMIT 1"#,
+        chat_id,
+        replied_id
+    )
+    .fetch_optional(db.as_ref())
+    .await?;
+
+    let Some(doodle) = doodle else {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Aucun doodle ouvert dans ce groupe",
+                "No open doodle in this chat"
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    sqlx::query!("UPDATE doodles SET closed = TRUE WHERE id = $1", doodle.id)
+        .execute(db.as_ref())
+        .await?;
+
+    let slots = load_slots(db.as_ref(), doodle.id).await?;
+    if let Some(message_id) = doodle.message_id {
+        bot.edit_html(
+            msg.chat.id,
+            MessageId(message_id as i32),
+            render_text(&doodle.title, &slots, true, lang),
+        )
+        .queued()
+        .await?;
+    }
+
+    let best = slots.iter().map(|s| s.voters.len()).max().unwrap_or(0);
+    let text = if best == 0 {
+        tr!(
+            lang,
+            "Doodle \"{}\" fermé, personne n'est disponible",
+            "Doodle \"{}\" closed, nobody is available",
+            escape(&doodle.title)
+        )
+    } else {
+        MessageBuilder::new()
+            .title(&tr!(
+                lang,
+                "Doodle \"{}\" fermé, créneau retenu ({} disponible(s))",
+                "Doodle \"{}\" closed, chosen slot ({} available)",
+                doodle.title,
+                best
+            ))
+            .items(
+                slots
+                    .iter()
+                    .filter(|s| s.voters.len() == best)
+                    .map(|s| escape(&s.label)),
+            )
+            .build()
+    };
+    bot.send_html(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
+
+async fn load_slots(db: &SqlitePool, doodle_id: i64) -> Result<Vec<Slot>, sqlx::Error> {
+    let options = sqlx::query!(
+        "SELECT position, label FROM doodle_options WHERE doodle_id = $1 ORDER BY position",
+        doodle_id
+    )
+    .fetch_all(db)
+    .await?;
+    let votes = sqlx::query!(
+        "SELECT position, user_name FROM doodle_votes WHERE doodle_id = $1 ORDER BY user_name",
+        doodle_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(options
+        .into_iter()
+        .map(|o| Slot {
+            voters: votes
+                .iter()
+                .filter(|v| v.position == o.position)
+                .map(|v| v.user_name.clone())
+                .collect(),
+            label: o.label,
+        })
+        .collect())
+}
+
+fn render_text(title: &str, slots: &[Slot], closed: bool, lang: Lang) -> String {
+    format!(
+        "📅 {}{}\n\n{}",
+        bold(title),
+        if closed {
+            tr!(lang, " (fermé)", " (closed)")
+        } else {
+            String::new()
+        },
+        slots
+            .iter()
+            .map(|s| {
+                if s.voters.is_empty() {
+                    format!("{}: 0", escape(&s.label))
+                } else {
+                    format!(
+                        "{}: {} ({})",
+                        escape(&s.label),
+                        s.voters.len(),
+                        escape(&s.voters.join(", "))
+                    )
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    )
+}
+
+fn render_keyboard(doodle_id: i64, slots: &[Slot]) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(slots.iter().enumerate().map(|(position, s)| {
+        vec![InlineKeyboardButton::callback(
+            format!("{} ({})", s.label, s.voters.len()),
+            CallbackData::format(DOODLE_VOTE, format!("{}:{}", doodle_id, position)),
+        )]
+    }))
+}
@@ -0,0 +1,252 @@
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+use teloxide::{
+    payloads::SendPhotoSetters,
+    requests::Requester,
+    types::{InputFile, Message},
+    Bot,
+};
+
+use crate::{
+    config::config,
+    dates::now,
+    format::{code, escape, HtmlMessages, MessageBuilder},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    HandlerResult,
+};
+
+/// Number of expenses shown by `/expense list`.
+const LIST_SIZE: i64 = 20;
+
+fn usage(lang: Lang) -> String {
+    tr!(
+        lang,
+        "Utilisation:\n - /expense add <montant> <description>: enregistre une dépense (en réponse à la photo du ticket pour l'y joindre)\n - /expense list: liste les dépenses\n - /expense receipt <id>: affiche le ticket d'une dépense\n - /expense approve <id>: (trésorier) valide le remboursement",
+        "Usage:\n - /expense add <amount> <description>: record an expense (in reply to the photo of the receipt to attach it)\n - /expense list: list the expenses\n - /expense receipt <id>: show the receipt of an expense\n - /expense approve <id>: (treasurer) approve the refund"
+    )
+}
+
+/// Parses an amount in CHF (`12`, `12.5`, `12,50`) into cents.
+fn parse_amount(amount: &str) -> Option<i64> {
+    let amount = amount.replace(',', ".");
+    let (francs, cents) = amount.split_once('.').unwrap_or((&amount, "0"));
+    let cents = match cents.len() {
+        1 => cents.parse::<i64>().ok()? * 10,
+        2 => cents.parse::<i64>().ok()?,
+        _ => return None,
+    };
+    let total = francs.parse::<i64>().ok()? * 100 + cents;
+    (total > 0).then_some(total)
+}
+
+fn format_amount(cents: i64) -> String {
+    format!("{}.{:02} CHF", cents / 100, cents % 100)
+}
+
+fn is_treasurer(user_id: u64) -> bool {
+    config()
+        .treasurer_ids
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .any(|id| id.trim() == user_id.to_string())
+}
+
+pub async fn expense(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> HandlerResult {
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+    let chat_id = msg.chat.id.to_string();
+    let words = args.split_whitespace().collect::<Vec<_>>();
+
+    let text = match words[..] {
+        ["add", amount, ref description @ ..] if !description.is_empty() => {
+            let Some(amount) = parse_amount(amount) else {
+                bot.send_message(msg.chat.id, usage(lang)).queued().await?;
+                return Ok(());
+            };
+
+            let description = description.join(" ");
+            let author_id = user.id.to_string();
+            let author_name = user.full_name();
+            let receipt = msg
+                .reply_to_message()
+                .and_then(|m| m.photo())
+                .and_then(|sizes| sizes.last())
+                .map(|p| p.file.id.clone());
+            let timestamp = now().timestamp();
+
+            let id = sqlx::query!(
+                "INSERT INTO expenses(chat_id, author_id, author_name, amount, description, receipt_file_id, created_at) VALUES($1, $2, $3, $4, $5, $6, $7)",
+                chat_id,
+                author_id,
+                author_name,
+                amount,
+                description,
+                receipt,
+                timestamp
+            )
+            .execute(db.as_ref())
+            .await?
+            .last_insert_rowid();
+
+            format!(
+                "{}{}",
+                tr!(
+                    lang,
+                    "Dépense #{} enregistrée: {} pour {}",
+                    "Expense #{} recorded: {} for {}",
+                    id,
+                    format_amount(amount),
+                    escape(&description)
+                ),
+                if receipt.is_some() {
+                    tr!(lang, " (ticket joint)", " (receipt attached)")
+                } else {
+                    String::new()
+                }
+            )
+        }
+        ["list"] | [] => {
+            let expenses = sqlx::query!(
+                r#"SELECT id AS "id!", author_name, amount, description, receipt_file_id, approved_by
+                FROM expenses WHERE chat_id = $1 ORDER BY approved_by IS NOT NULL, id DESC LIMIT $2"#,
+                chat_id,
+                LIST_SIZE
+            )
+            .fetch_all(db.as_ref())
+            .await?;
+
+            if expenses.is_empty() {
+                tr!(lang, "Aucune dépense enregistrée", "No expense recorded")
+            } else {
+                MessageBuilder::new()
+                    .title(&tr!(lang, "Dépenses", "Expenses"))
+                    .html(
+                        expenses
+                            .into_iter()
+                            .map(|e| format!(
+                                "{} {} {} - {} ({}){}",
+                                if e.approved_by.is_some() { "✅" } else { "⏳" },
+                                code(&format!("#{}", e.id)),
+                                format_amount(e.amount),
+                                escape(&e.description),
+                                escape(&e.author_name),
+                                if e.receipt_file_id.is_some() {
+                                    " 📎"
+                                } else {
+                                    ""
+                                }
+                            ))
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                    )
+                    .build()
+            }
+        }
+        ["receipt", id] => {
+            let Ok(id) = id.trim_start_matches('#').parse::<i64>() else {
+                bot.send_message(msg.chat.id, usage(lang)).queued().await?;
+                return Ok(());
+            };
+            let receipt = sqlx::query!(
+                "SELECT receipt_file_id, description FROM expenses WHERE id = $1 AND chat_id = $2",
+                id,
+                chat_id
+            )
+            .fetch_optional(db.as_ref())
+            .await?;
+
+            match receipt {
+                Some(r) if r.receipt_file_id.is_some() => {
+                    bot.send_photo(msg.chat.id, InputFile::file_id(r.receipt_file_id.unwrap()))
+                        .caption(tr!(
+                            lang,
+                            "Ticket de la dépense #{}: {}",
+                            "Receipt of the expense #{}: {}",
+                            id,
+                            r.description
+                        ))
+                        .queued()
+                        .await?;
+                    return Ok(());
+                }
+                Some(_) => tr!(
+                    lang,
+                    "La dépense #{} n'a pas de ticket",
+                    "The expense #{} has no receipt",
+                    id
+                ),
+                None => tr!(
+                    lang,
+                    "Aucune dépense #{} dans ce groupe",
+                    "No expense #{} in this chat",
+                    id
+                ),
+            }
+        }
+        ["approve", id] => {
+            if !is_treasurer(user.id.0) {
+                bot.send_message(
+                    msg.chat.id,
+                    tr!(
+                        lang,
+                        "Seul le trésorier peut valider les dépenses",
+                        "Only the treasurer can approve the expenses"
+                    ),
+                )
+                .queued()
+                .await?;
+                return Ok(());
+            }
+            let Ok(id) = id.trim_start_matches('#').parse::<i64>() else {
+                bot.send_message(msg.chat.id, usage(lang)).queued().await?;
+                return Ok(());
+            };
+
+            let approver = user.full_name();
+            let timestamp = now().timestamp();
+            let approved = sqlx::query!(
+                r#"UPDATE expenses SET approved_by = $1, approved_at = $2
+                WHERE id = $3 AND chat_id = $4 AND approved_by IS NULL
+                RETURNING author_name, amount"#,
+                approver,
+                timestamp,
+                id,
+                chat_id
+            )
+            .fetch_optional(db.as_ref())
+            .await?;
+
+            match approved {
+                Some(e) => tr!(
+                    lang,
+                    "Dépense #{} validée: {} à rembourser à {}",
+                    "Expense #{} approved: {} to refund to {}",
+                    id,
+                    format_amount(e.amount),
+                    escape(&e.author_name)
+                ),
+                None => tr!(
+                    lang,
+                    "Aucune dépense #{} en attente dans ce groupe",
+                    "No pending expense #{} in this chat",
+                    id
+                ),
+            }
+        }
+        _ => escape(&usage(lang)),
+    };
+
+    bot.send_html(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
@@ -0,0 +1,796 @@
+//! Exports of the logical data of the bot (admins, chats and their settings, authorizations,
+//! schedules, committee links, quotes, templates, aliases, retention durations) as a versioned
+//! JSON document, and imports of such a document. Unlike the backups, the dumps do not depend on
+//! the SQLite file nor on the migrations, so they can move the data between environments.
+
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    str::FromStr,
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use teloxide::{
+    net::Download,
+    payloads::{SendDocumentSetters, SendMessageSetters},
+    requests::Requester,
+    types::{
+        CallbackQuery, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, InputFile, Message,
+        MessageId, UserId,
+    },
+    Bot,
+};
+
+use crate::{
+    approvals::require_approval,
+    audit::{actor, audit},
+    callbacks::{CallbackData, CallbackResult, IMPORT},
+    cmd_backup::superadmin,
+    cmd_poll::{PollDialogue, PollState},
+    cmd_schedules::next_run,
+    commands::Command,
+    dates::{now, parse_timezone, TIMEZONE},
+    db::{
+        admins::{self, AdminRepo},
+        authorizations::{self, authorization_cache, AuthorizationRepo},
+        committee::{self, CommitteeRepo},
+        quotes::{self, QuoteRepo},
+    },
+    format::{escape, HtmlMessages, MessageBuilder},
+    i18n::Lang,
+    outbox::Queued,
+    retention::find_policy,
+    templates::find_template,
+    verification::MODES,
+    HandlerResult,
+};
+
+/// Invalid entries listed in the summary of an import, the others are only counted.
+const MAX_LISTED_INVALID: usize = 10;
+
+/// Version of the format of the dumps, increased when a change prevents older bots from importing
+/// them. Dumps of older versions can still be imported.
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Dump {
+    version: u32,
+    /// Unix timestamp (seconds).
+    exported_at: i64,
+    #[serde(default)]
+    admins: Vec<Admin>,
+    #[serde(default)]
+    chats: Vec<Chat>,
+    #[serde(default)]
+    authorizations: Vec<Authorization>,
+    #[serde(default)]
+    schedules: Vec<Schedule>,
+    #[serde(default)]
+    committee: Vec<CommitteeLink>,
+    #[serde(default)]
+    quotes: Vec<Quote>,
+    #[serde(default)]
+    templates: Vec<Template>,
+    #[serde(default)]
+    aliases: Vec<Alias>,
+    #[serde(default)]
+    retention: Vec<Retention>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Admin {
+    telegram_id: String,
+    name: String,
+}
+
+/// Chat of the registry, with its settings.
+#[derive(Serialize, Deserialize)]
+struct Chat {
+    chat_id: String,
+    title: Option<String>,
+    kind: Option<String>,
+    language: String,
+    timezone: String,
+    pin_bureau: bool,
+    pin_countdowns: bool,
+    bureau_anonymous: bool,
+    bureau_multiple_answers: bool,
+    bureau_close_after: Option<i64>,
+    quiz_anonymous: bool,
+    quiz_close_after: Option<i64>,
+    shame_monthly_reset: bool,
+    verification_mode: String,
+    verification_minutes: i64,
+    verification_kick: bool,
+    stats_committee_only: bool,
+    welcome_message: Option<String>,
+    /// Ids of the schedules of the dump created by the menu for the bureau poll and the digest.
+    bureau_schedule_id: Option<i64>,
+    digest_schedule_id: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Authorization {
+    chat_id: String,
+    command: String,
+    thread_id: Option<i64>,
+}
+
+/// Schedule, with its id in the database it comes from, only used by the settings of the chats.
+#[derive(Serialize, Deserialize)]
+struct Schedule {
+    id: i64,
+    chat_id: String,
+    /// Cron expression, with the seconds field.
+    cron: String,
+    payload: String,
+    thread_id: Option<i64>,
+    target_chat_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CommitteeLink {
+    name: String,
+    telegram_id: String,
+}
+
+/// Quote, without its id, which only makes sense in the database it comes from.
+#[derive(Serialize, Deserialize)]
+struct Quote {
+    chat_id: String,
+    author: String,
+    text: String,
+    created_at: i64,
+    poll_id: Option<String>,
+    correct_option: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Template {
+    chat_id: String,
+    key: String,
+    text: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Alias {
+    chat_id: String,
+    alias: String,
+    command: String,
+}
+
+/// Duration set with `/retention`, `None` keeping the data forever.
+#[derive(Serialize, Deserialize)]
+struct Retention {
+    policy: String,
+    days: Option<i64>,
+}
+
+/// Keeps the rows without error, and adds the errors of the others to `invalid`.
+fn retain_valid<T>(
+    rows: &mut Vec<T>,
+    invalid: &mut Vec<String>,
+    error: impl Fn(&T) -> Option<String>,
+) {
+    rows.retain(|row| match error(row) {
+        Some(e) => {
+            invalid.push(e);
+            false
+        }
+        None => true,
+    });
+}
+
+impl Dump {
+    fn rows(&self) -> usize {
+        self.admins.len()
+            + self.chats.len()
+            + self.authorizations.len()
+            + self.schedules.len()
+            + self.committee.len()
+            + self.quotes.len()
+            + self.templates.len()
+            + self.aliases.len()
+            + self.retention.len()
+    }
+
+    /// Removes the entries which cannot be imported, checked like the commands setting them, and
+    /// returns why. The entries of a chat must refer to a chat of the dump or of `known_chats`.
+    fn remove_invalid(&mut self, known_chats: HashSet<String>) -> Vec<String> {
+        let mut invalid = vec![];
+        retain_valid(&mut self.chats, &mut invalid, |c| {
+            if parse_timezone(&c.timezone).is_none() {
+                Some(format!(
+                    "Groupe {}: fuseau horaire inconnu {}",
+                    c.chat_id, c.timezone
+                ))
+            } else if Lang::parse(&c.language).is_none() {
+                Some(format!(
+                    "Groupe {}: langue inconnue {}",
+                    c.chat_id, c.language
+                ))
+            } else if !MODES.contains(&c.verification_mode.as_str()) {
+                Some(format!(
+                    "Groupe {}: vérification inconnue {}",
+                    c.chat_id, c.verification_mode
+                ))
+            } else {
+                None
+            }
+        });
+
+        let mut chats = known_chats;
+        chats.extend(self.chats.iter().map(|c| c.chat_id.clone()));
+        let unknown_chat = |kind: &str, chat_id: &str| {
+            (!chats.contains(chat_id)).then(|| format!("{} de {}: groupe inconnu", kind, chat_id))
+        };
+        retain_valid(&mut self.authorizations, &mut invalid, |a| {
+            unknown_chat("Autorisation", &a.chat_id).or_else(|| {
+                Command::from_name(&a.command).is_none().then(|| {
+                    format!(
+                        "Autorisation de {}: commande inconnue /{}",
+                        a.chat_id, a.command
+                    )
+                })
+            })
+        });
+        retain_valid(&mut self.schedules, &mut invalid, |s| {
+            unknown_chat("Planification", &s.chat_id).or_else(|| {
+                cron::Schedule::from_str(&s.cron)
+                    .ok()
+                    .and_then(|schedule| next_run(&schedule, TIMEZONE))
+                    .is_none()
+                    .then(|| {
+                        format!(
+                            "Planification de {}: expression invalide {}",
+                            s.chat_id, s.cron
+                        )
+                    })
+            })
+        });
+        retain_valid(&mut self.templates, &mut invalid, |t| {
+            unknown_chat("Modèle", &t.chat_id).or_else(|| match find_template(&t.key) {
+                Some(template) => template
+                    .check(&t.text)
+                    .err()
+                    .map(|e| format!("Modèle {} de {}: {}", t.key, t.chat_id, e)),
+                None => Some(format!("Modèle de {}: modèle inconnu {}", t.chat_id, t.key)),
+            })
+        });
+        retain_valid(&mut self.aliases, &mut invalid, |a| {
+            unknown_chat("Alias", &a.chat_id).or_else(|| {
+                Command::from_name(&a.command).is_none().then(|| {
+                    format!(
+                        "Alias /{} de {}: commande inconnue /{}",
+                        a.alias, a.chat_id, a.command
+                    )
+                })
+            })
+        });
+        retain_valid(&mut self.retention, &mut invalid, |r| {
+            match find_policy(&r.policy) {
+                Some(policy) if r.days.is_some_and(|days| days < policy.min_days()) => {
+                    Some(format!(
+                        "Conservation de {}: au moins {} jours",
+                        r.policy,
+                        policy.min_days()
+                    ))
+                }
+                Some(_) => None,
+                None => Some(format!(
+                    "Conservation: type de données inconnu {}",
+                    r.policy
+                )),
+            }
+        });
+
+        // The settings cannot refer to a schedule which is not imported
+        let schedules = self.schedules.iter().map(|s| s.id).collect::<HashSet<_>>();
+        for c in &mut self.chats {
+            for id in [&mut c.bureau_schedule_id, &mut c.digest_schedule_id] {
+                if id.is_some_and(|id| !schedules.contains(&id)) {
+                    *id = None;
+                }
+            }
+        }
+
+        invalid
+    }
+
+    /// Adds the number of entries of each section to the message.
+    fn summary(&self, message: MessageBuilder) -> MessageBuilder {
+        message
+            .field("Admins", self.admins.len().to_string())
+            .field("Groupes", self.chats.len().to_string())
+            .field("Autorisations", self.authorizations.len().to_string())
+            .field("Planifications", self.schedules.len().to_string())
+            .field("Comité", self.committee.len().to_string())
+            .field("Citations", self.quotes.len().to_string())
+            .field("Modèles", self.templates.len().to_string())
+            .field("Alias", self.aliases.len().to_string())
+            .field("Conservation", self.retention.len().to_string())
+    }
+}
+
+/// Adds the invalid entries, ignored by the import, to the message.
+fn invalid_summary(mut message: MessageBuilder, invalid: &[String]) -> MessageBuilder {
+    if invalid.is_empty() {
+        return message;
+    }
+    message = message.text(&format!(
+        "⚠️ {} entrée(s) invalide(s), ignorée(s):",
+        invalid.len()
+    ));
+    message = message.items(invalid.iter().take(MAX_LISTED_INVALID).map(|e| escape(e)));
+    if invalid.len() > MAX_LISTED_INVALID {
+        message = message.text(&format!(
+            "... et {} autre(s)",
+            invalid.len() - MAX_LISTED_INVALID
+        ));
+    }
+    message
+}
+
+/// Chats of the database, to which the entries of a dump may refer.
+async fn known_chats(db: &SqlitePool) -> Result<HashSet<String>, sqlx::Error> {
+    Ok(
+        sqlx::query_scalar!(r#"SELECT chat_id AS "chat_id!" FROM chats"#)
+            .fetch_all(db)
+            .await?
+            .into_iter()
+            .collect(),
+    )
+}
+
+async fn export_dump(db: &SqlitePool) -> Result<Dump, sqlx::Error> {
+    Ok(Dump {
+        version: FORMAT_VERSION,
+        exported_at: now().timestamp(),
+        admins: db
+            .admins()
+            .await?
+            .into_iter()
+            .map(|a| Admin {
+                telegram_id: a.telegram_id,
+                name: a.name,
+            })
+            .collect(),
+        chats: sqlx::query_as!(
+            Chat,
+            r#"SELECT chats.chat_id AS "chat_id!", title, kind, "language", timezone,
+                pin_bureau AS "pin_bureau: bool", pin_countdowns AS "pin_countdowns: bool",
+                bureau_anonymous AS "bureau_anonymous: bool",
+                bureau_multiple_answers AS "bureau_multiple_answers: bool", bureau_close_after,
+                quiz_anonymous AS "quiz_anonymous: bool", quiz_close_after,
+                shame_monthly_reset AS "shame_monthly_reset: bool", verification_mode,
+                verification_minutes, verification_kick AS "verification_kick: bool",
+                stats_committee_only AS "stats_committee_only: bool",
+                chat_settings.welcome_message AS "welcome_message?",
+                chat_settings.bureau_schedule_id AS "bureau_schedule_id?",
+                chat_settings.digest_schedule_id AS "digest_schedule_id?"
+            FROM chats LEFT JOIN chat_settings ON chat_settings.chat_id = chats.chat_id
+            ORDER BY chats.chat_id"#
+        )
+        .fetch_all(db)
+        .await?,
+        authorizations: db
+            .authorizations("")
+            .await?
+            .into_iter()
+            .map(|a| Authorization {
+                chat_id: a.chat_id,
+                command: a.command,
+                thread_id: a.thread_id,
+            })
+            .collect(),
+        schedules: sqlx::query_as!(
+            Schedule,
+            r#"SELECT id AS "id!", chat_id, cron, payload, thread_id, target_chat_id FROM schedules
+            ORDER BY id"#
+        )
+        .fetch_all(db)
+        .await?,
+        committee: db
+            .links()
+            .await?
+            .into_iter()
+            .map(|(name, telegram_id)| CommitteeLink { name, telegram_id })
+            .collect(),
+        quotes: db
+            .all_quotes()
+            .await?
+            .into_iter()
+            .map(|q| Quote {
+                chat_id: q.chat_id,
+                author: q.author,
+                text: q.text,
+                created_at: q.created_at,
+                poll_id: q.poll_id,
+                correct_option: q.correct_option,
+            })
+            .collect(),
+        templates: sqlx::query_as!(
+            Template,
+            r#"SELECT chat_id, "key", "text" FROM templates ORDER BY chat_id, "key""#
+        )
+        .fetch_all(db)
+        .await?,
+        aliases: sqlx::query_as!(
+            Alias,
+            "SELECT chat_id, alias, command FROM aliases ORDER BY chat_id, alias"
+        )
+        .fetch_all(db)
+        .await?,
+        retention: sqlx::query_as!(
+            Retention,
+            r#"SELECT "policy" AS "policy!", days FROM retention_policies ORDER BY "policy""#
+        )
+        .fetch_all(db)
+        .await?,
+    })
+}
+
+/// Loads the dump in a single transaction. The entries of the dump replace the ones with the same
+/// key (e.g. the same chat), the others are kept. The quotes and the schedules are added, except
+/// the ones already there (same chat, author, text and date, or same chat, expression and
+/// payload). The dump must have been checked with [`Dump::remove_invalid`].
+async fn import_rows(db: &SqlitePool, dump: &Dump) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    for a in &dump.admins {
+        admins::import_admin(&mut tx, &a.telegram_id, &a.name).await?;
+    }
+    // The chats first, since the authorizations and templates reference them
+    for c in &dump.chats {
+        sqlx::query!(
+            r#"INSERT INTO chats(chat_id, title, kind, "language", timezone, pin_bureau,
+                pin_countdowns, bureau_anonymous, bureau_multiple_answers, bureau_close_after,
+                quiz_anonymous, quiz_close_after, shame_monthly_reset, verification_mode,
+                verification_minutes, verification_kick, stats_committee_only)
+            VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+            ON CONFLICT(chat_id) DO UPDATE SET title = excluded.title, kind = excluded.kind,
+                "language" = excluded."language", timezone = excluded.timezone,
+                pin_bureau = excluded.pin_bureau, pin_countdowns = excluded.pin_countdowns,
+                bureau_anonymous = excluded.bureau_anonymous,
+                bureau_multiple_answers = excluded.bureau_multiple_answers,
+                bureau_close_after = excluded.bureau_close_after,
+                quiz_anonymous = excluded.quiz_anonymous,
+                quiz_close_after = excluded.quiz_close_after,
+                shame_monthly_reset = excluded.shame_monthly_reset,
+                verification_mode = excluded.verification_mode,
+                verification_minutes = excluded.verification_minutes,
+                verification_kick = excluded.verification_kick,
+                stats_committee_only = excluded.stats_committee_only"#,
+            c.chat_id,
+            c.title,
+            c.kind,
+            c.language,
+            c.timezone,
+            c.pin_bureau,
+            c.pin_countdowns,
+            c.bureau_anonymous,
+            c.bureau_multiple_answers,
+            c.bureau_close_after,
+            c.quiz_anonymous,
+            c.quiz_close_after,
+            c.shame_monthly_reset,
+            c.verification_mode,
+            c.verification_minutes,
+            c.verification_kick,
+            c.stats_committee_only
+        )
+        .execute(&mut *tx)
+        .await?;
+        if let Some(welcome) = &c.welcome_message {
+            sqlx::query!(
+                "INSERT INTO chat_settings(chat_id, welcome_message) VALUES($1, $2)
+                ON CONFLICT(chat_id) DO UPDATE SET welcome_message = excluded.welcome_message",
+                c.chat_id,
+                welcome
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    for a in &dump.authorizations {
+        authorizations::import_authorization(&mut tx, &a.chat_id, &a.command, a.thread_id).await?;
+    }
+    // Ids of the schedules in the database, from their id in the dump
+    let mut schedule_ids = HashMap::new();
+    for s in &dump.schedules {
+        let existing = sqlx::query_scalar!(
+            r#"SELECT id AS "id!" FROM schedules WHERE chat_id = $1 AND cron = $2 AND payload = $3
+            AND thread_id IS $4 AND target_chat_id IS $5"#,
+            s.chat_id,
+            s.cron,
+            s.payload,
+            s.thread_id,
+            s.target_chat_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let id = match existing {
+            Some(id) => id,
+            None => {
+                let timezone =
+                    sqlx::query_scalar!("SELECT timezone FROM chats WHERE chat_id = $1", s.chat_id)
+                        .fetch_optional(&mut *tx)
+                        .await?
+                        .and_then(|tz| parse_timezone(&tz))
+                        .unwrap_or(TIMEZONE);
+                let next = cron::Schedule::from_str(&s.cron)
+                    .ok()
+                    .and_then(|schedule| next_run(&schedule, timezone))
+                    // Checked by remove_invalid, up to the timezone
+                    .unwrap_or(i64::MAX);
+                sqlx::query_scalar!(
+                    r#"INSERT INTO schedules(chat_id, cron, payload, next_run, thread_id,
+                        target_chat_id)
+                    VALUES($1, $2, $3, $4, $5, $6) RETURNING id AS "id!""#,
+                    s.chat_id,
+                    s.cron,
+                    s.payload,
+                    next,
+                    s.thread_id,
+                    s.target_chat_id
+                )
+                .fetch_one(&mut *tx)
+                .await?
+            }
+        };
+        schedule_ids.insert(s.id, id);
+    }
+    for c in &dump.chats {
+        let bureau = c.bureau_schedule_id.and_then(|id| schedule_ids.get(&id));
+        let digest = c.digest_schedule_id.and_then(|id| schedule_ids.get(&id));
+        if bureau.is_none() && digest.is_none() {
+            continue;
+        }
+        sqlx::query!(
+            "INSERT INTO chat_settings(chat_id, bureau_schedule_id, digest_schedule_id)
+            VALUES($1, $2, $3)
+            ON CONFLICT(chat_id) DO UPDATE SET
+                bureau_schedule_id = COALESCE(excluded.bureau_schedule_id, bureau_schedule_id),
+                digest_schedule_id = COALESCE(excluded.digest_schedule_id, digest_schedule_id)",
+            c.chat_id,
+            bureau,
+            digest
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    for l in &dump.committee {
+        committee::import_link(&mut tx, &l.name, &l.telegram_id).await?;
+    }
+    for q in &dump.quotes {
+        quotes::import_quote(
+            &mut tx,
+            &q.chat_id,
+            &q.author,
+            &q.text,
+            q.created_at,
+            q.poll_id.as_deref(),
+            q.correct_option,
+        )
+        .await?;
+    }
+    for t in &dump.templates {
+        sqlx::query!(
+            r#"INSERT INTO templates(chat_id, "key", "text") VALUES($1, $2, $3)
+            ON CONFLICT(chat_id, "key") DO UPDATE SET "text" = excluded."text""#,
+            t.chat_id,
+            t.key,
+            t.text
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    for a in &dump.aliases {
+        sqlx::query!(
+            "INSERT INTO aliases(chat_id, alias, command) VALUES($1, $2, $3)
+            ON CONFLICT(chat_id, alias) DO UPDATE SET command = excluded.command",
+            a.chat_id,
+            a.alias,
+            a.command
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for r in &dump.retention {
+        sqlx::query!(
+            r#"INSERT INTO retention_policies("policy", days) VALUES($1, $2)
+            ON CONFLICT("policy") DO UPDATE SET days = excluded.days"#,
+            r.policy,
+            r.days
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await
+}
+
+/// `/export` sends the dump of the logical data to the superadmin, in private.
+pub async fn export(bot: Bot, msg: Message, db: Arc<SqlitePool>) -> HandlerResult {
+    let Some(superadmin) = superadmin(&msg) else {
+        bot.send_message(msg.chat.id, "Seul le superadmin peut exporter les données")
+            .queued()
+            .await?;
+        return Ok(());
+    };
+
+    let dump = export_dump(db.as_ref()).await?;
+    let json = serde_json::to_vec_pretty(&dump)?;
+    let file_name = format!(
+        "roboclic-{}.json",
+        now().with_timezone(&TIMEZONE).format("%Y-%m-%d-%H%M")
+    );
+    bot.send_document(
+        ChatId::from(superadmin),
+        InputFile::memory(json).file_name(file_name),
+    )
+    .caption("Export des données du bot. Réponds-y avec /import pour le charger, par exemple dans une autre instance.")
+    .queued()
+    .await?;
+    let details = format!("{} entries", dump.rows());
+    audit(db.as_ref(), &actor(&msg), "export", &details).await;
+
+    if msg.chat.id != ChatId::from(superadmin) {
+        bot.send_message(msg.chat.id, "Export envoyé en message privé")
+            .queued()
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Downloads and parses a dump sent as a document. Returns why it cannot be imported, if it
+/// cannot.
+async fn fetch_dump(
+    bot: &Bot,
+    file_id: &str,
+) -> Result<Result<Dump, String>, teloxide::RequestError> {
+    let file = bot.get_file(file_id).await?;
+    let mut content = Vec::with_capacity(file.size as usize);
+    if let Err(e) = bot.download_file(&file.path, &mut content).await {
+        log::error!("Could not download export: {:?}", e);
+        return Ok(Err("Le fichier n'a pas pu être téléchargé".to_owned()));
+    }
+    Ok(match serde_json::from_slice::<Dump>(&content) {
+        Ok(dump) if dump.version > FORMAT_VERSION => Err(format!(
+            "Cet export a été fait par une version plus récente du bot (format {}, ce bot lit jusqu'au format {})",
+            dump.version, FORMAT_VERSION
+        )),
+        Ok(dump) => Ok(dump),
+        Err(e) => Err(format!("Ce fichier n'est pas un export valide: {}", e)),
+    })
+}
+
+/// `/import`, in reply to an export, asks the superadmin to confirm loading it.
+pub async fn import(
+    bot: Bot,
+    msg: Message,
+    dialogue: PollDialogue,
+    db: Arc<SqlitePool>,
+) -> HandlerResult {
+    let Some(superadmin) = superadmin(&msg).filter(|id| msg.chat.id == ChatId::from(*id)) else {
+        bot.send_message(
+            msg.chat.id,
+            "Seul le superadmin peut importer des données, en message privé",
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+    let Some(document) = msg.reply_to_message().and_then(|m| m.document()) else {
+        bot.send_message(msg.chat.id, "Réponds à un export de /export avec /import")
+            .queued()
+            .await?;
+        return Ok(());
+    };
+
+    let mut dump = match fetch_dump(&bot, &document.file.id).await? {
+        Ok(dump) => dump,
+        Err(reason) => {
+            bot.send_message(msg.chat.id, reason).queued().await?;
+            return Ok(());
+        }
+    };
+    let invalid = dump.remove_invalid(known_chats(db.as_ref()).await?);
+
+    let summary = dump.summary(MessageBuilder::new().title("📥 Import"));
+    let text = invalid_summary(summary, &invalid)
+        .text("⚠️ Les entrées de l'export remplaceront celles qui ont la même clé (même groupe, même alias...), les autres seront conservées. Les citations déjà présentes ne seront pas dupliquées. Continuer ?")
+        .build();
+    let sent = bot
+        .send_html(msg.chat.id, text)
+        .reply_markup(InlineKeyboardMarkup::new([[
+            InlineKeyboardButton::callback("Importer", CallbackData::format(IMPORT, "import")),
+            InlineKeyboardButton::callback("Annuler", CallbackData::format(IMPORT, "cancel")),
+        ]]))
+        .queued()
+        .await?;
+
+    dialogue
+        .update(PollState::ConfirmImport {
+            message_id: sent.id,
+            file_id: document.file.id.clone(),
+            initiator: Some(superadmin),
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Asks another admin to approve the import once confirmed.
+pub async fn confirm_import(
+    bot: Bot,
+    query: CallbackQuery,
+    data: CallbackData,
+    dialogue: PollDialogue,
+    (message_id, file_id, _): (MessageId, String, Option<UserId>),
+    db: Arc<SqlitePool>,
+) -> CallbackResult {
+    let chat_id = dialogue.chat_id();
+    dialogue.update(PollState::Start).await?;
+    match data.payload.as_str() {
+        "cancel" => {
+            bot.edit_message_text(chat_id, message_id, "Import annulé")
+                .queued()
+                .await?;
+            return Ok(None);
+        }
+        "import" => {}
+        _ => return Ok(None),
+    }
+
+    bot.edit_message_reply_markup(chat_id, message_id)
+        .queued()
+        .await?;
+    require_approval(
+        &bot,
+        db.as_ref(),
+        &query.from,
+        chat_id,
+        "import",
+        &file_id,
+        "importer un export, qui remplacera les données du bot ayant la même clé",
+    )
+    .await?;
+
+    Ok(None)
+}
+
+/// Imports the dump, once approved. Returns the outcome to display.
+pub async fn import_dump(
+    bot: &Bot,
+    db: &SqlitePool,
+    actor: &str,
+    file_id: &str,
+) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let mut dump = match fetch_dump(bot, file_id).await? {
+        Ok(dump) => dump,
+        Err(reason) => return Ok(reason),
+    };
+    // Checked again, since the database may have changed during the approval
+    let invalid = dump.remove_invalid(known_chats(db).await?);
+
+    import_rows(db, &dump).await?;
+    authorization_cache().clear().await;
+    let details = format!(
+        "{} entries, {} invalid (format {})",
+        dump.rows(),
+        invalid.len(),
+        dump.version
+    );
+    audit(db, actor, "import", &details).await;
+
+    Ok(format!(
+        "Export importé: {} entrée(s), {} invalide(s) ignorée(s)",
+        dump.rows(),
+        invalid.len()
+    ))
+}
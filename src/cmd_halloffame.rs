@@ -0,0 +1,241 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use chrono::{Datelike, Month};
+use sqlx::SqlitePool;
+use teloxide::{
+    types::{Message, PollAnswer},
+    Bot,
+};
+
+use crate::{
+    dates::{from_timestamp, now, TIMEZONE},
+    format::{bold, escape, HtmlMessages},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    stats::{record_stat, Stat},
+    HandlerResult,
+};
+
+pub const MEDALS: [&str; 3] = ["🥇", "🥈", "🥉"];
+/// Month at which a new committee mandate starts.
+const MANDATE_START: Month = Month::September;
+
+/// Mandate (identified by the year in which it started) of the given timestamp.
+fn mandate(timestamp: i64) -> i32 {
+    let date = from_timestamp(timestamp, TIMEZONE).unwrap_or_else(now);
+    if date.month() >= MANDATE_START.number_from_month() {
+        date.year()
+    } else {
+        date.year() - 1
+    }
+}
+
+/// A record of the hall of fame, aggregated from `(name, timestamp, score)` entries.
+struct Record {
+    title: String,
+    unit: String,
+    all_time: Vec<(String, i64)>,
+    per_mandate: BTreeMap<i32, (String, i64)>,
+}
+
+impl Record {
+    /// Aggregates the entries, summing the scores (or keeping the maximum when `max` is set).
+    fn new(title: String, unit: String, entries: Vec<(String, i64, i64)>, max: bool) -> Self {
+        let combine = |a: &mut i64, b: i64| *a = if max { (*a).max(b) } else { *a + b };
+
+        let mut all_time = HashMap::<String, i64>::new();
+        let mut mandates = HashMap::<(i32, String), i64>::new();
+        for (name, timestamp, score) in entries {
+            combine(all_time.entry(name.clone()).or_default(), score);
+            combine(
+                mandates.entry((mandate(timestamp), name)).or_default(),
+                score,
+            );
+        }
+
+        let mut all_time = all_time.into_iter().collect::<Vec<_>>();
+        all_time.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        all_time.truncate(MEDALS.len());
+
+        let mut per_mandate = BTreeMap::<i32, (String, i64)>::new();
+        for ((mandate, name), score) in mandates {
+            match per_mandate.get(&mandate) {
+                Some((best_name, best))
+                    if *best > score || (*best == score && *best_name < name) => {}
+                _ => {
+                    per_mandate.insert(mandate, (name, score));
+                }
+            }
+        }
+
+        Self {
+            title,
+            unit,
+            all_time,
+            per_mandate,
+        }
+    }
+
+    fn format(&self, lang: Lang) -> String {
+        let mut text = format!("{}\n", bold(&self.title));
+        if self.all_time.is_empty() {
+            text.push_str(&tr!(lang, "Pas encore de données\n", "No data yet\n"));
+            return text;
+        }
+
+        for (medal, (name, score)) in MEDALS.iter().zip(&self.all_time) {
+            text.push_str(&format!(
+                "{} {} ({} {})\n",
+                medal,
+                escape(name),
+                score,
+                self.unit
+            ));
+        }
+        for (mandate, (name, score)) in self.per_mandate.iter().rev() {
+            text.push_str(&format!(
+                "   {}-{}: {} ({})\n",
+                mandate,
+                mandate + 1,
+                escape(name),
+                score
+            ));
+        }
+
+        text
+    }
+}
+
+/// Displays the all-time records of the chat, with the best of each mandate.
+pub async fn halloffame(bot: Bot, msg: Message, db: Arc<SqlitePool>, lang: Lang) -> HandlerResult {
+    let chat_id = msg.chat.id.to_string();
+
+    let quotes = sqlx::query!(
+        "SELECT author, created_at FROM quotes WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_all(db.as_ref())
+    .await?
+    .into_iter()
+    .map(|q| (q.author, q.created_at, 1))
+    .collect();
+
+    let guesses = sqlx::query!(
+        r#"SELECT a.user_id, a.user_name, a.answered_at, a.option = q.correct_option AS "correct!: bool"
+        FROM poll_answers a JOIN quotes q ON q.poll_id = a.poll_id
+        WHERE q.chat_id = $1 ORDER BY a.answered_at"#,
+        chat_id
+    )
+    .fetch_all(db.as_ref())
+    .await?;
+
+    // Longest run of correct guesses, reset at each new mandate
+    let mut streaks = vec![];
+    let mut current = HashMap::<String, (i32, i64)>::new();
+    for g in &guesses {
+        let run = current
+            .entry(g.user_id.clone())
+            .or_insert((mandate(g.answered_at), 0));
+        if run.0 != mandate(g.answered_at) {
+            *run = (mandate(g.answered_at), 0);
+        }
+        run.1 = if g.correct { run.1 + 1 } else { 0 };
+        streaks.push((g.user_name.clone(), g.answered_at, run.1));
+    }
+
+    let guesses = guesses
+        .into_iter()
+        .filter(|g| g.correct)
+        .map(|g| (g.user_name, g.answered_at, 1))
+        .collect();
+
+    let presence = sqlx::query!(
+        r#"SELECT a.user_name, b.sent_at FROM poll_answers a JOIN bureau_polls b ON b.poll_id = a.poll_id
+        WHERE b.chat_id = $1 AND a.option = 0"#,
+        chat_id
+    )
+    .fetch_all(db.as_ref())
+    .await?
+    .into_iter()
+    .map(|p| (p.user_name, p.sent_at, 1))
+    .collect();
+
+    let awards = sqlx::query!(
+        "SELECT author, awarded_at FROM quote_awards WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_all(db.as_ref())
+    .await?
+    .into_iter()
+    .map(|a| (a.author, a.awarded_at, 1))
+    .collect();
+
+    let records = [
+        Record::new(
+            tr!(lang, "💬 Le plus cité", "💬 Most quoted"),
+            tr!(lang, "citations", "quotes"),
+            quotes,
+            false,
+        ),
+        Record::new(
+            tr!(lang, "🏅 Citations du mois", "🏅 Quotes of the month"),
+            tr!(lang, "élections", "elections"),
+            awards,
+            false,
+        ),
+        Record::new(
+            tr!(lang, "🔮 Meilleur devin", "🔮 Best guesser"),
+            tr!(lang, "bonnes réponses", "correct answers"),
+            guesses,
+            false,
+        ),
+        Record::new(
+            tr!(lang, "🔥 Plus longue série", "🔥 Longest streak"),
+            tr!(
+                lang,
+                "bonnes réponses d'affilée",
+                "correct answers in a row"
+            ),
+            streaks,
+            true,
+        ),
+        Record::new(
+            tr!(lang, "🪑 Pilier du bureau", "🪑 Pillar of the office"),
+            tr!(lang, "présences", "presences"),
+            presence,
+            false,
+        ),
+    ];
+
+    bot.send_html(
+        msg.chat.id,
+        format!(
+            "🏆 {}\n\n{}",
+            bold("Hall of fame"),
+            records
+                .iter()
+                .map(|r| r.format(lang))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ),
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
+
+/// Records the answers to the polls sent by the bot. Retracted votes are removed.
+pub async fn record_poll_answer(answer: PollAnswer) -> HandlerResult {
+    record_stat(Stat::PollAnswer {
+        poll_id: answer.poll_id,
+        user_id: answer.user.id.to_string(),
+        user_name: answer.user.full_name(),
+        option: answer.option_ids.first().copied(),
+    });
+
+    Ok(())
+}
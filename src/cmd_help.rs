@@ -0,0 +1,249 @@
+//! Interactive `/help` menu: the commands grouped by category, filtered by the authorizations of
+//! the chat and the rights of the user, with a page per command.
+
+use teloxide::{
+    payloads::{EditMessageTextSetters, SendMessageSetters},
+    types::{CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup, Message, UserId},
+    utils::command::BotCommands,
+    Bot,
+};
+
+use crate::{
+    callbacks::{CallbackData, CallbackResult, HELP},
+    commands::{is_permitted, Access, Category, Command},
+    format::{bold, code, escape, HtmlMessages},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    state::AppState,
+    HandlerResult,
+};
+
+const CATEGORIES: [Category; 4] = [
+    Category::General,
+    Category::Fun,
+    Category::Bureau,
+    Category::Admin,
+];
+
+/// Number of command buttons per row of a category page.
+const BUTTONS_PER_ROW: usize = 3;
+
+struct Entry {
+    /// Name of the command, without the slash.
+    name: String,
+    description: String,
+    command: Command,
+}
+
+fn category_code(category: Category) -> &'static str {
+    match category {
+        Category::General => "general",
+        Category::Fun => "fun",
+        Category::Bureau => "bureau",
+        Category::Admin => "admin",
+    }
+}
+
+fn category_name(category: Category, lang: Lang) -> String {
+    match category {
+        Category::General => tr!(lang, "ℹ️ Général", "ℹ️ General"),
+        Category::Fun => tr!(lang, "🎉 Fun", "🎉 Fun"),
+        Category::Bureau => tr!(lang, "🗂 Bureau", "🗂 Bureau"),
+        Category::Admin => tr!(lang, "🔒 Admin", "🔒 Admin"),
+    }
+}
+
+fn access_text(command: &Command, lang: Lang) -> String {
+    match command.access() {
+        Access::Everyone => tr!(lang, "Disponible partout", "Available everywhere"),
+        Access::AuthorizedChat => tr!(
+            lang,
+            "Disponible dans les groupes autorisés, avec /authorize {}",
+            "Available in the authorized chats, with /authorize {}",
+            command.shortand()
+        ),
+        Access::Admin => tr!(lang, "Réservée aux admins", "Restricted to the admins"),
+        Access::Superadmin => tr!(
+            lang,
+            "Réservée au superadmin",
+            "Restricted to the superadmin"
+        ),
+        Access::ItTeam => tr!(
+            lang,
+            "Réservée à l'équipe informatique",
+            "Restricted to the IT team"
+        ),
+    }
+}
+
+fn entries() -> Vec<Entry> {
+    Command::bot_commands()
+        .into_iter()
+        .filter_map(|c| {
+            let name = c.command.trim_start_matches('/').to_owned();
+            Some(Entry {
+                command: Command::from_name(&name)?,
+                name,
+                description: c.description,
+            })
+        })
+        .collect()
+}
+
+/// Commands the user can run from the chat of the message.
+async fn available(state: &AppState, msg: &Message, user: UserId) -> Vec<Entry> {
+    let mut available = Vec::new();
+    for entry in entries() {
+        if is_permitted(state, msg, user, &entry.command).await {
+            available.push(entry);
+        }
+    }
+    available
+}
+
+fn back_button(payload: &str, lang: Lang) -> InlineKeyboardButton {
+    InlineKeyboardButton::callback(
+        tr!(lang, "⬅️ Retour", "⬅️ Back"),
+        CallbackData::format(HELP, payload),
+    )
+}
+
+fn menu_page(entries: &[Entry], lang: Lang) -> (String, InlineKeyboardMarkup) {
+    let buttons = CATEGORIES.iter().filter_map(|category| {
+        let count = entries
+            .iter()
+            .filter(|e| e.command.category() == *category)
+            .count();
+        (count > 0).then(|| {
+            vec![InlineKeyboardButton::callback(
+                format!("{} ({})", category_name(*category, lang), count),
+                CallbackData::format(HELP, format!("category:{}", category_code(*category))),
+            )]
+        })
+    });
+
+    let text = tr!(
+        lang,
+        "{}\nChoisis une catégorie. Seules les commandes que tu peux utiliser ici sont affichées.",
+        "{}\nChoose a category. Only the commands you can use here are listed.",
+        bold(&tr!(lang, "❓ Aide", "❓ Help"))
+    );
+    (text, InlineKeyboardMarkup::new(buttons))
+}
+
+fn category_page(
+    entries: &[Entry],
+    category: Category,
+    lang: Lang,
+) -> (String, InlineKeyboardMarkup) {
+    let entries = entries
+        .iter()
+        .filter(|e| e.command.category() == category)
+        .collect::<Vec<_>>();
+
+    let mut text = bold(&category_name(category, lang));
+    for entry in &entries {
+        text.push_str(&format!(
+            "\n/{} - {}",
+            escape(&entry.name),
+            escape(&entry.description)
+        ));
+    }
+    if entries.is_empty() {
+        text.push_str(&tr!(
+            lang,
+            "\nAucune commande disponible ici",
+            "\nNo command available here"
+        ));
+    }
+
+    let mut rows = entries
+        .chunks(BUTTONS_PER_ROW)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .map(|e| {
+                    InlineKeyboardButton::callback(
+                        format!("/{}", e.name),
+                        CallbackData::format(HELP, format!("command:{}", e.name)),
+                    )
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    rows.push(vec![back_button("menu", lang)]);
+
+    (text, InlineKeyboardMarkup::new(rows))
+}
+
+fn command_page(entries: &[Entry], name: &str, lang: Lang) -> (String, InlineKeyboardMarkup) {
+    let Some(entry) = entries.iter().find(|e| e.name == name) else {
+        let text = tr!(
+            lang,
+            "La commande /{} n'est pas disponible ici",
+            "The command /{} is not available here",
+            escape(name)
+        );
+        return (
+            text,
+            InlineKeyboardMarkup::new([[back_button("menu", lang)]]),
+        );
+    };
+
+    let text = format!(
+        "{}\n{}\n\n{}",
+        code(&format!("/{}", entry.name)),
+        escape(&entry.description),
+        escape(&access_text(&entry.command, lang))
+    );
+    let back = format!("category:{}", category_code(entry.command.category()));
+    (
+        text,
+        InlineKeyboardMarkup::new([[back_button(&back, lang)]]),
+    )
+}
+
+/// `/help` sends the menu of the commands the user can run in this chat.
+pub async fn help(bot: Bot, msg: Message, state: AppState, lang: Lang) -> HandlerResult {
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+
+    let entries = available(&state, &msg, user.id).await;
+    let (text, keyboard) = menu_page(&entries, lang);
+    bot.send_html(msg.chat.id, text)
+        .reply_markup(keyboard)
+        .queued()
+        .await?;
+    Ok(())
+}
+
+/// Buttons of the `/help` menu: navigates between the menu, the categories and the commands. The
+/// pages are filtered for the user pressing the button.
+pub async fn help_menu(
+    bot: Bot,
+    query: CallbackQuery,
+    data: CallbackData,
+    state: AppState,
+    lang: Lang,
+) -> CallbackResult {
+    let Some(message) = &query.message else {
+        return Ok(None);
+    };
+
+    let entries = available(&state, message, query.from.id).await;
+    let (text, keyboard) = match data.payload.split_once(':') {
+        Some(("category", code)) => match CATEGORIES.iter().find(|c| category_code(**c) == code) {
+            Some(category) => category_page(&entries, *category, lang),
+            None => menu_page(&entries, lang),
+        },
+        Some(("command", name)) => command_page(&entries, name, lang),
+        _ => menu_page(&entries, lang),
+    };
+    bot.edit_html(message.chat.id, message.id, text)
+        .reply_markup(keyboard)
+        .queued()
+        .await?;
+
+    Ok(None)
+}
@@ -0,0 +1,193 @@
+use std::sync::Arc;
+
+use teloxide::{
+    payloads::AnswerInlineQuerySetters,
+    requests::Requester,
+    types::{
+        InlineQuery, InlineQueryResult, InlineQueryResultArticle, InputMessageContent,
+        InputMessageContentText,
+    },
+    utils::command::BotCommands,
+    Bot,
+};
+
+use crate::{
+    commands::{Access, Command},
+    committee::committee_repository,
+    db::quotes::QuoteRepo,
+    names::fold,
+    state::AppState,
+    templates::TEMPLATES,
+    HandlerResult,
+};
+
+/// Maximum number of quotes or suggestions proposed for an inline query.
+const MAX_RESULTS: i64 = 20;
+
+/// Values suggested for the last argument of an admin command.
+#[derive(Clone, Copy)]
+pub enum Argument {
+    /// Commands which must be authorized in a chat.
+    Command,
+    Admin,
+    Member,
+    Chat,
+    Template,
+}
+
+/// Admin commands whose last argument is suggested inline, e.g. `@roboclic authorize bu` proposes
+/// `/authorize bureau`.
+const COMPLETIONS: &[(&str, Argument)] = &[
+    ("authorize", Argument::Command),
+    ("unauthorize", Argument::Command),
+    ("topicbind", Argument::Command),
+    ("adminremove", Argument::Admin),
+    ("revoke", Argument::Admin),
+    ("quotefix", Argument::Member),
+    ("chat", Argument::Chat),
+    ("settemplate", Argument::Template),
+];
+
+/// Formats a quote the way it is posted in chats.
+pub fn format_quote(text: &str, author: &str) -> String {
+    format!("« {} »\n— {}", text, author)
+}
+
+/// The command and the arguments typed so far, if the query starts with an admin command having
+/// suggestions, followed by a space.
+pub fn completion_request(query: InlineQuery) -> Option<(InlineQuery, Argument)> {
+    let (name, _) = query.query.trim_start().split_once(' ')?;
+    let name = name.trim_start_matches('/').to_lowercase();
+    let (_, argument) = COMPLETIONS.iter().find(|(n, _)| *n == name)?;
+    Some((query, *argument))
+}
+
+/// Possible values of the argument, with a description.
+async fn candidates(
+    state: &AppState,
+    argument: Argument,
+) -> Result<Vec<(String, String)>, Box<dyn std::error::Error + Send + Sync>> {
+    Ok(match argument {
+        Argument::Command => {
+            let mut commands = Vec::<(String, String)>::new();
+            for c in Command::bot_commands() {
+                let Some(command) = Command::from_name(c.command.trim_start_matches('/')) else {
+                    continue;
+                };
+                let shortand = command.shortand();
+                if command.access() == Access::AuthorizedChat
+                    && !commands.iter().any(|(s, _)| s == shortand)
+                {
+                    commands.push((shortand.to_owned(), c.description));
+                }
+            }
+            commands
+        }
+        Argument::Admin => state
+            .admin_repo
+            .admins()
+            .await?
+            .into_iter()
+            .map(|a| (a.name, a.telegram_id))
+            .collect(),
+        Argument::Member => committee_repository()
+            .get()
+            .await?
+            .into_iter()
+            .map(|m| (m.name, "Membre du comité".to_owned()))
+            .collect(),
+        Argument::Chat => sqlx::query!(
+            r#"SELECT chat_id AS "chat_id!", title FROM chats ORDER BY last_seen DESC"#
+        )
+        .fetch_all(state.db.as_ref())
+        .await?
+        .into_iter()
+        .map(|c| (c.chat_id, c.title.unwrap_or_default()))
+        .collect(),
+        Argument::Template => TEMPLATES
+            .iter()
+            .map(|t| (t.key.to_owned(), t.description.to_owned()))
+            .collect(),
+    })
+}
+
+/// Answers `@roboclic <command> <arguments>` of the admins with the values of the last argument
+/// starting like the typed one, so that tapping one sends the whole command without typo.
+pub async fn inline_completions(
+    bot: Bot,
+    (query, argument): (InlineQuery, Argument),
+    state: AppState,
+) -> HandlerResult {
+    let text = query.query.trim_start().trim_start_matches('/');
+    // Everything before the last argument is kept as typed
+    let (typed, partial) = text.rsplit_once(' ').unwrap_or((text, ""));
+    // The chats are only suggested once `/chat remap` or `/chat purge` is typed
+    let incomplete = matches!(argument, Argument::Chat) && !typed.contains(' ');
+    if incomplete || !state.admin_repo.is_admin(query.from.id).await {
+        bot.answer_inline_query(query.id, Vec::<InlineQueryResult>::new())
+            .is_personal(true)
+            .await?;
+        return Ok(());
+    }
+
+    let partial = fold(partial);
+    let results = candidates(&state, argument)
+        .await?
+        .into_iter()
+        .filter(|(value, description)| {
+            fold(value).starts_with(&partial) || fold(description).contains(&partial)
+        })
+        .take(MAX_RESULTS as usize)
+        .enumerate()
+        .map(|(i, (value, description))| {
+            let command = format!("/{} {}", typed, value);
+            InlineQueryResult::Article(
+                InlineQueryResultArticle::new(
+                    i.to_string(),
+                    command.clone(),
+                    InputMessageContent::Text(InputMessageContentText::new(command)),
+                )
+                .description(description),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    bot.answer_inline_query(query.id, results)
+        .cache_time(10)
+        .is_personal(true)
+        .await?;
+
+    Ok(())
+}
+
+/// Answers `@roboclic <keyword>` with the stored quotes matching the keyword (in their text or
+/// author), so they can be shared in any chat.
+pub async fn inline_quotes(
+    bot: Bot,
+    query: InlineQuery,
+    quotes: Arc<dyn QuoteRepo>,
+) -> HandlerResult {
+    let quotes = quotes
+        .search_quotes(query.query.trim(), MAX_RESULTS)
+        .await?;
+
+    let results = quotes.into_iter().map(|q| {
+        InlineQueryResult::Article(
+            InlineQueryResultArticle::new(
+                q.id.to_string(),
+                q.author.clone(),
+                InputMessageContent::Text(InputMessageContentText::new(format_quote(
+                    &q.text, &q.author,
+                ))),
+            )
+            .description(q.text),
+        )
+    });
+
+    bot.answer_inline_query(query.id, results)
+        .cache_time(10)
+        .is_personal(true)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,256 @@
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+use teloxide::{
+    requests::Requester,
+    types::{Message, User},
+    Bot,
+};
+
+use crate::{
+    dates::now,
+    db::retry_busy,
+    format::{bold, escape, HtmlMessages, MessageBuilder},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    HandlerResult,
+};
+
+/// Maximum number of karma votes per user and per chat in a day.
+const DAILY_VOTES: i32 = 5;
+const DAY: i64 = 24 * 60 * 60;
+/// Number of users shown in the ranking.
+const RANKING_SIZE: i64 = 10;
+
+fn usage(lang: Lang) -> String {
+    tr!(
+        lang,
+        "Utilisation: /karma pour le classement, /karma @personne +1|-1 pour voter (ou réponds +1/-1 à un message)",
+        "Usage: /karma for the ranking, /karma @someone +1|-1 to vote (or reply +1/-1 to a message)"
+    )
+}
+
+pub fn user_key(user: &User) -> String {
+    user.username
+        .as_ref()
+        .map(|u| mention_key(&format!("@{}", u)))
+        .unwrap_or_else(|| format!("id:{}", user.id))
+}
+
+/// Key of a typed `@username`: the usernames are case-insensitive.
+pub fn mention_key(mention: &str) -> String {
+    mention.to_lowercase()
+}
+
+/// Returns the vote if the message is a "+1" or "-1" reply.
+pub fn karma_reply_vote(msg: &Message) -> Option<i64> {
+    msg.reply_to_message()?;
+    match msg.text()?.trim() {
+        "+1" => Some(1),
+        "-1" => Some(-1),
+        _ => None,
+    }
+}
+
+pub async fn karma(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> HandlerResult {
+    let words = args.split_whitespace().collect::<Vec<_>>();
+    match words[..] {
+        [] => {
+            let chat_id = msg.chat.id.to_string();
+            let ranking = sqlx::query!(
+                "SELECT user_name, points FROM karma WHERE chat_id = $1 ORDER BY points DESC LIMIT $2",
+                chat_id,
+                RANKING_SIZE
+            )
+            .fetch_all(db.as_ref())
+            .await?;
+
+            bot.send_html(
+                msg.chat.id,
+                if ranking.is_empty() {
+                    tr!(lang, "Personne n'a encore de karma", "Nobody has karma yet")
+                } else {
+                    MessageBuilder::new()
+                        .title(&tr!(lang, "Classement karma", "Karma ranking"))
+                        .html(
+                            ranking
+                                .into_iter()
+                                .enumerate()
+                                .map(|(i, r)| {
+                                    format!("{}. {} ({})", i + 1, bold(&r.user_name), r.points)
+                                })
+                                .collect::<Vec<_>>()
+                                .join("\n"),
+                        )
+                        .build()
+                },
+            )
+            .queued()
+            .await?;
+        }
+        [target, vote] if target.starts_with('@') && target.len() > 1 => {
+            let delta = match vote {
+                "+1" => 1,
+                "-1" => -1,
+                _ => {
+                    bot.send_message(msg.chat.id, usage(lang)).queued().await?;
+                    return Ok(());
+                }
+            };
+            let Some(voter) = msg.from() else {
+                return Ok(());
+            };
+            if voter
+                .username
+                .as_deref()
+                .is_some_and(|u| u.eq_ignore_ascii_case(&target[1..]))
+            {
+                bot.send_message(
+                    msg.chat.id,
+                    tr!(lang, "Pas de karma pour soi-même", "No karma for yourself"),
+                )
+                .queued()
+                .await?;
+                return Ok(());
+            }
+
+            vote_karma(
+                &bot,
+                &msg,
+                voter,
+                (&mention_key(target), target),
+                delta,
+                db.as_ref(),
+                lang,
+            )
+            .await?;
+        }
+        _ => {
+            bot.send_message(msg.chat.id, usage(lang)).queued().await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Handles "+1"/"-1" replies to messages.
+pub async fn karma_reply(bot: Bot, msg: Message, db: Arc<SqlitePool>, lang: Lang) -> HandlerResult {
+    let (Some(delta), Some(voter), Some(target)) = (
+        karma_reply_vote(&msg),
+        msg.from(),
+        msg.reply_to_message().and_then(|m| m.from()),
+    ) else {
+        return Ok(());
+    };
+
+    if target.id == voter.id || target.is_bot {
+        return Ok(());
+    }
+
+    vote_karma(
+        &bot,
+        &msg,
+        voter,
+        (&user_key(target), &target.full_name()),
+        delta,
+        db.as_ref(),
+        lang,
+    )
+    .await
+}
+
+async fn vote_karma(
+    bot: &Bot,
+    msg: &Message,
+    voter: &User,
+    (target_key, target_name): (&str, &str),
+    delta: i64,
+    db: &SqlitePool,
+    lang: Lang,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.to_string();
+    let voter_id = voter.id.to_string();
+    let timestamp = now().timestamp();
+    let day_start = timestamp - DAY;
+
+    // None when the voter has no votes left for today
+    let (chat_id, voter_id) = (&chat_id, &voter_id);
+    let points = retry_busy(|| async move {
+        let mut tx = db.begin().await?;
+        let votes = sqlx::query!(
+            "SELECT COUNT(*) AS count FROM karma_votes WHERE chat_id = $1 AND voter_id = $2 AND voted_at > $3",
+            chat_id,
+            voter_id,
+            day_start
+        )
+        .fetch_one(&mut *tx)
+        .await?
+        .count;
+        if votes >= DAILY_VOTES {
+            return Ok(None);
+        }
+
+        sqlx::query!(
+            "INSERT INTO karma_votes(chat_id, voter_id, voted_at) VALUES($1, $2, $3)",
+            chat_id,
+            voter_id,
+            timestamp
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!("DELETE FROM karma_votes WHERE voted_at <= $1", day_start)
+            .execute(&mut *tx)
+            .await?;
+        let points = sqlx::query!(
+            r#"INSERT INTO karma(chat_id, user_key, user_name, points) VALUES($1, $2, $3, $4)
+            ON CONFLICT(chat_id, user_key) DO UPDATE SET points = points + excluded.points, user_name = excluded.user_name
+            RETURNING points"#,
+            chat_id,
+            target_key,
+            target_name,
+            delta
+        )
+        .fetch_one(&mut *tx)
+        .await?
+        .points;
+        tx.commit().await?;
+        Ok(Some(points))
+    })
+    .await?;
+
+    let Some(points) = points else {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Tu as déjà donné {} votes de karma aujourd'hui",
+                "You already gave {} karma votes today",
+                DAILY_VOTES
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    bot.send_html(
+        msg.chat.id,
+        tr!(
+            lang,
+            "Karma de {}: {}",
+            "Karma of {}: {}",
+            escape(target_name),
+            points
+        ),
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+use teloxide::{requests::Requester, types::Message, Bot};
+
+use crate::{
+    i18n::{tr, Lang},
+    outbox::Queued,
+    HandlerResult,
+};
+
+/// Sets the language of the replies in the chat, or displays the current one.
+pub async fn language(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    lang: Lang,
+    db: Arc<SqlitePool>,
+) -> HandlerResult {
+    if args.trim().is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Langue du groupe: {}\nUtilisation: /language fr|en",
+                "Language of the chat: {}\nUsage: /language fr|en",
+                lang.code()
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    let Some(new) = Lang::parse(&args) else {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Langue inconnue. Utilisation: /language fr|en",
+                "Unknown language. Usage: /language fr|en"
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    let chat_id = msg.chat.id.to_string();
+    let code = new.code();
+    sqlx::query!(
+        "UPDATE chats SET language = $1 WHERE chat_id = $2",
+        code,
+        chat_id
+    )
+    .execute(db.as_ref())
+    .await?;
+
+    bot.send_message(
+        msg.chat.id,
+        tr!(
+            new,
+            "Le bot répondra désormais en français",
+            "The bot will now reply in English"
+        ),
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
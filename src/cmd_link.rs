@@ -0,0 +1,309 @@
+use std::{
+    error::Error,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use reqwest::{header::LOCATION, redirect::Policy, Client, Url};
+use sqlx::SqlitePool;
+use teloxide::{types::Message, Bot};
+use tokio::{net::lookup_host, time::timeout};
+
+use crate::{
+    dates::now,
+    format::{escape, link as html_link, HtmlMessages},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    HandlerResult,
+};
+
+/// Time allowed to fetch the title of a saved page.
+const TITLE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Maximum number of redirections followed to fetch the title.
+const MAX_REDIRECTS: usize = 3;
+/// Only the beginning of the page is read, the `<title>` being in its head.
+const MAX_PAGE_SIZE: usize = 256 * 1024;
+/// Titles longer than that are truncated.
+const MAX_TITLE_LENGTH: usize = 200;
+/// Maximum number of links listed by `/links`.
+const MAX_LINKS: i64 = 30;
+
+fn usage(lang: Lang) -> String {
+    tr!(
+        lang,
+        "Utilisation:\n - /link save <url> [tags...]: enregistre un lien\n - /link remove <url>: supprime un lien\n - /links [tag]: liste les liens enregistrés",
+        "Usage:\n - /link save <url> [tags...]: save a link\n - /link remove <url>: remove a link\n - /links [tag]: list the saved links"
+    )
+}
+
+/// `/link save <url> [tags...]` stores a link for the chat, `/link remove <url>` deletes it.
+pub async fn link(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> HandlerResult {
+    let mut words = args.split_whitespace();
+    let chat_id = msg.chat.id.to_string();
+
+    let text = match (words.next(), words.next()) {
+        (Some("save"), Some(url)) if is_url(url) => {
+            let tags = words
+                .map(|t| t.trim_start_matches('#').to_lowercase())
+                .filter(|t| !t.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let title = fetch_title(url).await;
+            let timestamp = now().timestamp();
+
+            sqlx::query!(
+                "INSERT INTO links(chat_id, url, title, tags, created_at) VALUES($1, $2, $3, $4, $5)
+                ON CONFLICT(chat_id, url) DO UPDATE SET title = excluded.title, tags = excluded.tags",
+                chat_id,
+                url,
+                title,
+                tags,
+                timestamp
+            )
+            .execute(db.as_ref())
+            .await?;
+
+            tr!(
+                lang,
+                "🔗 Lien enregistré: {}",
+                "🔗 Link saved: {}",
+                html_link(url, title.as_deref().unwrap_or(url))
+            )
+        }
+        (Some("save"), _) => tr!(
+            lang,
+            "L'URL doit commencer par http:// ou https://",
+            "The URL must start with http:// or https://"
+        ),
+        (Some("remove"), Some(url)) => {
+            let removed = sqlx::query!(
+                "DELETE FROM links WHERE chat_id = $1 AND url = $2",
+                chat_id,
+                url
+            )
+            .execute(db.as_ref())
+            .await?
+            .rows_affected();
+
+            if removed > 0 {
+                tr!(lang, "Lien supprimé", "Link removed")
+            } else {
+                tr!(
+                    lang,
+                    "Ce lien n'est pas enregistré",
+                    "This link is not saved"
+                )
+            }
+        }
+        _ => escape(&usage(lang)),
+    };
+
+    bot.send_html(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
+
+/// Lists the links of the chat, optionally filtered by a tag or a word of their title.
+pub async fn links(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.to_string();
+    let filter = args.trim().trim_start_matches('#').to_lowercase();
+    let tag = format!("% {} %", filter);
+    let pattern = format!("%{}%", filter);
+
+    let links = sqlx::query!(
+        r#"SELECT url, title, tags FROM links
+        WHERE chat_id = $1 AND ($2 = '' OR ' ' || tags || ' ' LIKE $3 OR title LIKE $4 OR url LIKE $4)
+        ORDER BY created_at DESC LIMIT $5"#,
+        chat_id,
+        filter,
+        tag,
+        pattern,
+        MAX_LINKS
+    )
+    .fetch_all(db.as_ref())
+    .await?;
+
+    let text = if links.is_empty() {
+        if filter.is_empty() {
+            escape(&tr!(
+                lang,
+                "Aucun lien enregistré, utilisez /link save <url> [tags...]",
+                "No saved link, use /link save <url> [tags...]"
+            ))
+        } else {
+            tr!(
+                lang,
+                "Aucun lien trouvé pour « {} »",
+                "No link found for \"{}\"",
+                escape(&filter)
+            )
+        }
+    } else {
+        links
+            .into_iter()
+            .map(|l| {
+                let tags = l
+                    .tags
+                    .split_whitespace()
+                    .map(|t| format!("#{}", escape(t)))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                match l.title {
+                    Some(title) => format!("🔗 {} {}", html_link(&l.url, &title), tags),
+                    None => format!("🔗 {} {}", html_link(&l.url, &l.url), tags),
+                }
+                .trim_end()
+                .to_owned()
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    };
+
+    bot.send_html(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
+
+fn is_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// Whether the address can be reached from the internet, so that the links cannot make the bot
+/// query the services of its own network.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                // Shared address space (carrier-grade NAT)
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local and link-local addresses
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Resolves the host of the URL, refusing it if any of its addresses is not public.
+async fn public_address(url: &Url) -> Option<SocketAddr> {
+    let host = url
+        .host_str()?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let lookup = lookup_host((host, url.port_or_known_default()?));
+    let addresses = timeout(TITLE_TIMEOUT, lookup)
+        .await
+        .ok()?
+        .ok()?
+        .collect::<Vec<_>>();
+    if addresses.iter().all(|a| is_public(a.ip())) {
+        addresses.first().copied()
+    } else {
+        None
+    }
+}
+
+/// Fetches the beginning of the page, following a few redirections. Each host is resolved and
+/// checked before being connected to, with the checked address.
+async fn fetch_page(url: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let mut url = Url::parse(url)?;
+    for _ in 0..=MAX_REDIRECTS {
+        if !is_url(url.as_str()) {
+            return Err("unsupported scheme".into());
+        }
+        let address = public_address(&url)
+            .await
+            .ok_or("unknown or non-public address")?;
+        let client = Client::builder()
+            .redirect(Policy::none())
+            .timeout(TITLE_TIMEOUT)
+            .resolve(url.host_str().unwrap_or_default(), address)
+            .build()?;
+        let mut response = client.get(url.clone()).send().await?;
+
+        if response.status().is_redirection() {
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|l| l.to_str().ok())
+                .ok_or("redirection without location")?;
+            url = url.join(location)?;
+            continue;
+        }
+
+        response = response.error_for_status()?;
+        let mut body = vec![];
+        while let Some(chunk) = response.chunk().await? {
+            body.extend_from_slice(&chunk);
+            if body.len() >= MAX_PAGE_SIZE {
+                body.truncate(MAX_PAGE_SIZE);
+                break;
+            }
+        }
+        return Ok(String::from_utf8_lossy(&body).into_owned());
+    }
+    Err("too many redirections".into())
+}
+
+/// Fetches the page and extracts its `<title>`. Failures are only logged, since the link is saved
+/// anyway.
+async fn fetch_title(url: &str) -> Option<String> {
+    let body = match fetch_page(url).await {
+        Ok(body) => body,
+        Err(e) => {
+            log::warn!("Could not fetch the title of {}: {}", url, e);
+            return None;
+        }
+    };
+
+    let lowercase = body.to_lowercase();
+    let start = lowercase.find("<title")?;
+    let start = start + lowercase[start..].find('>')? + 1;
+    let end = start + lowercase[start..].find("</title")?;
+
+    let title = body
+        .get(start..end)?
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("&amp;", "&")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">");
+
+    if title.is_empty() {
+        None
+    } else {
+        Some(title.chars().take(MAX_TITLE_LENGTH).collect())
+    }
+}
@@ -0,0 +1,287 @@
+use std::sync::Arc;
+
+use chrono_tz::Tz;
+use sqlx::SqlitePool;
+use teloxide::{
+    types::{ChatId, Message},
+    Bot,
+};
+
+use crate::{
+    dates::{chat_timezone, format_datetime, from_timestamp, now},
+    db::{admins::AdminRepo, retry_busy},
+    format::{escape, HtmlMessages, MessageBuilder},
+    i18n::{chat_language, tr, Lang},
+    outbox::{Outbox, Priority, Queued},
+    HandlerResult,
+};
+
+/// Duration of a loan before the borrower gets reminded, in days.
+const LOAN_DURATION_DAYS: i64 = 14;
+const DAY: i64 = 24 * 60 * 60;
+
+fn usage(lang: Lang) -> String {
+    tr!(
+        lang,
+        "Utilisation:\n - /loan take <objet>: emprunte un objet\n - /loan return <objet>: rend un objet\n - /loan list: liste l'inventaire et les emprunts\n - /loan add|remove <objet>: (admin) gère l'inventaire",
+        "Usage:\n - /loan take <item>: borrow an item\n - /loan return <item>: return an item\n - /loan list: list the inventory and the loans\n - /loan add|remove <item>: (admin) manage the inventory"
+    )
+}
+
+fn not_in_inventory(item: &str, lang: Lang) -> String {
+    tr!(
+        lang,
+        "{} n'est pas dans l'inventaire",
+        "{} is not in the inventory",
+        escape(item)
+    )
+}
+
+fn format_timestamp(timestamp: i64, timezone: Tz) -> String {
+    from_timestamp(timestamp, timezone)
+        .map(|d| format_datetime(&d))
+        .unwrap_or_default()
+}
+
+pub async fn loan(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    admins: Arc<dyn AdminRepo>,
+    lang: Lang,
+    timezone: Tz,
+) -> HandlerResult {
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+    let args = args.trim();
+    let (action, item) = args
+        .split_once(char::is_whitespace)
+        .map(|(a, i)| (a, i.trim()))
+        .unwrap_or((args, ""));
+    let timestamp = now().timestamp();
+
+    let text = match (action, item) {
+        ("add" | "remove", _) if !item.is_empty() && !admins.is_admin(user.id).await => {
+            tr!(
+                lang,
+                "Seuls les admins peuvent modifier l'inventaire",
+                "Only the admins can edit the inventory"
+            )
+        }
+        ("add", item) if !item.is_empty() => {
+            let added = sqlx::query!("INSERT OR IGNORE INTO inventory(item) VALUES($1)", item)
+                .execute(db.as_ref())
+                .await?
+                .rows_affected();
+            if added > 0 {
+                tr!(
+                    lang,
+                    "{} a été ajouté à l'inventaire",
+                    "{} was added to the inventory",
+                    escape(item)
+                )
+            } else {
+                tr!(
+                    lang,
+                    "{} est déjà dans l'inventaire",
+                    "{} is already in the inventory",
+                    escape(item)
+                )
+            }
+        }
+        ("remove", item) if !item.is_empty() => {
+            let removed = sqlx::query!("DELETE FROM inventory WHERE item = $1", item)
+                .execute(db.as_ref())
+                .await?
+                .rows_affected();
+            if removed > 0 {
+                tr!(
+                    lang,
+                    "{} a été retiré de l'inventaire",
+                    "{} was removed from the inventory",
+                    escape(item)
+                )
+            } else {
+                not_in_inventory(item, lang)
+            }
+        }
+        ("take", item) if !item.is_empty() => {
+            let chat_id = msg.chat.id.to_string();
+            let borrower_id = user.id.to_string();
+            let borrower_name = user.full_name();
+            let due_at = timestamp + LOAN_DURATION_DAYS * DAY;
+            let (pool, chat_id, borrower_id, borrower_name) =
+                (db.as_ref(), &chat_id, &borrower_id, &borrower_name);
+
+            retry_busy(|| async move {
+                let mut tx = pool.begin().await?;
+                let status = sqlx::query!(
+                    r#"SELECT i.item AS "item!", l.borrower_name AS "borrower_name?" FROM inventory i
+                    LEFT JOIN loans l ON l.item = i.item AND l.returned_at IS NULL
+                    WHERE i.item = $1"#,
+                    item
+                )
+                .fetch_optional(&mut *tx)
+                .await?;
+
+                let text = match status {
+                    None => not_in_inventory(item, lang),
+                    Some(s) if s.borrower_name.is_some() => tr!(
+                        lang,
+                        "{} est déjà emprunté par {}",
+                        "{} is already borrowed by {}",
+                        escape(&s.item),
+                        escape(&s.borrower_name.unwrap())
+                    ),
+                    Some(s) => {
+                        sqlx::query!(
+                            "INSERT INTO loans(item, chat_id, borrower_id, borrower_name, taken_at, due_at) VALUES($1, $2, $3, $4, $5, $6)",
+                            s.item,
+                            chat_id,
+                            borrower_id,
+                            borrower_name,
+                            timestamp,
+                            due_at
+                        )
+                        .execute(&mut *tx)
+                        .await?;
+
+                        tr!(
+                            lang,
+                            "{} emprunte {}, à rendre avant le {}",
+                            "{} borrows {}, to be returned before {}",
+                            escape(borrower_name),
+                            escape(&s.item),
+                            format_timestamp(due_at, timezone)
+                        )
+                    }
+                };
+                tx.commit().await?;
+                Ok(text)
+            })
+            .await?
+        }
+        ("return", item) if !item.is_empty() => {
+            let returned = sqlx::query!(
+                "UPDATE loans SET returned_at = $1 WHERE item = $2 AND returned_at IS NULL RETURNING borrower_name",
+                timestamp,
+                item
+            )
+            .fetch_optional(db.as_ref())
+            .await?;
+
+            match returned {
+                Some(r) => tr!(
+                    lang,
+                    "{} a été rendu (emprunté par {})",
+                    "{} was returned (borrowed by {})",
+                    escape(item),
+                    escape(&r.borrower_name)
+                ),
+                None => tr!(
+                    lang,
+                    "{} n'est pas emprunté",
+                    "{} is not borrowed",
+                    escape(item)
+                ),
+            }
+        }
+        ("list" | "", _) => {
+            let items = sqlx::query!(
+                r#"SELECT i.item AS "item!", l.borrower_name AS "borrower_name?", l.due_at AS "due_at?" FROM inventory i
+                LEFT JOIN loans l ON l.item = i.item AND l.returned_at IS NULL
+                ORDER BY i.item"#
+            )
+            .fetch_all(db.as_ref())
+            .await?;
+
+            if items.is_empty() {
+                tr!(lang, "L'inventaire est vide", "The inventory is empty")
+            } else {
+                MessageBuilder::new()
+                    .title(&tr!(lang, "Inventaire", "Inventory"))
+                    .html(
+                        items
+                            .into_iter()
+                            .map(|i| match (i.borrower_name, i.due_at) {
+                                (Some(borrower), Some(due_at)) => tr!(
+                                    lang,
+                                    "{} {} (emprunté par {}, à rendre avant le {})",
+                                    "{} {} (borrowed by {}, to be returned before {})",
+                                    if due_at <= timestamp { "⚠️" } else { "📦" },
+                                    escape(&i.item),
+                                    escape(&borrower),
+                                    format_timestamp(due_at, timezone)
+                                ),
+                                _ => tr!(
+                                    lang,
+                                    "✅ {} (disponible)",
+                                    "✅ {} (available)",
+                                    escape(&i.item)
+                                ),
+                            })
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                    )
+                    .build()
+            }
+        }
+        _ => escape(&usage(lang)),
+    };
+
+    bot.send_html(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
+
+/// Reminds the borrowers of overdue loans, at most once a day, in the chat where they borrowed
+/// the item.
+pub async fn remind_overdue_loans(
+    bot: &Bot,
+    outbox: &Outbox,
+    db: &SqlitePool,
+) -> Result<(), sqlx::Error> {
+    let timestamp = now().timestamp();
+    let last_reminder = timestamp - DAY;
+    let overdue = sqlx::query!(
+        r#"SELECT id AS "id!", item, chat_id, borrower_name, due_at FROM loans
+        WHERE returned_at IS NULL AND due_at <= $1 AND (reminded_at IS NULL OR reminded_at <= $2)"#,
+        timestamp,
+        last_reminder
+    )
+    .fetch_all(db)
+    .await?;
+
+    for loan in overdue {
+        if let Ok(chat_id) = loan.chat_id.parse::<i64>().map(ChatId) {
+            let lang = chat_language(db, chat_id).await;
+            let timezone = chat_timezone(db, chat_id).await;
+            let text = tr!(
+                lang,
+                "📦 {}, tu devais rendre {} avant le {}",
+                "📦 {}, you had to return {} before {}",
+                escape(&loan.borrower_name),
+                escape(&loan.item),
+                format_timestamp(loan.due_at, timezone)
+            );
+            if let Err(e) = outbox
+                .send(chat_id, Priority::Bulk, bot.send_html(chat_id, text))
+                .await
+            {
+                log::error!("Could not remind overdue loan #{}: {:?}", loan.id, e);
+            }
+        }
+
+        sqlx::query!(
+            "UPDATE loans SET reminded_at = $1 WHERE id = $2",
+            timestamp,
+            loan.id
+        )
+        .execute(db)
+        .await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,228 @@
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+use teloxide::{requests::Requester, types::Message, Bot};
+
+use crate::{
+    dates::now,
+    format::{bold, code, escape, HtmlMessages},
+    i18n::{tr, Lang},
+    mailing::mailing_lists,
+    outbox::Queued,
+    HandlerResult,
+};
+
+fn is_email(text: &str) -> bool {
+    text.len() <= 320
+        && !text.contains(char::is_whitespace)
+        && text
+            .split_once('@')
+            .is_some_and(|(user, domain)| !user.is_empty() && domain.contains('.'))
+}
+
+/// Checks that the command is sent in private (the email addresses must not leak in the groups),
+/// and that the list exists. Returns the name of the list.
+async fn check_request(
+    bot: &Bot,
+    msg: &Message,
+    list: Option<&str>,
+    usage: &str,
+    lang: Lang,
+) -> Result<Option<String>, teloxide::RequestError> {
+    if !msg.chat.is_private() {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Pour ne pas partager ton adresse email, envoie cette commande en message privé au bot",
+                "To keep your email address private, send this command in a private message to the bot"
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(None);
+    }
+
+    let lists = mailing_lists();
+    if lists.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Aucune liste de diffusion n'est configurée",
+                "No mailing list is configured"
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(None);
+    }
+    match list.map(str::to_lowercase) {
+        Some(list) if lists.contains(&list) => Ok(Some(list)),
+        _ => {
+            bot.send_html(
+                msg.chat.id,
+                tr!(
+                    lang,
+                    "Utilisation: {}\nListes disponibles: {}",
+                    "Usage: {}\nAvailable lists: {}",
+                    escape(usage),
+                    lists.iter().map(|l| code(l)).collect::<Vec<_>>().join(", ")
+                ),
+            )
+            .queued()
+            .await?;
+            Ok(None)
+        }
+    }
+}
+
+async fn record_request(
+    db: &SqlitePool,
+    user_id: &str,
+    email: &str,
+    list: &str,
+    subscribe: bool,
+) -> Result<(), sqlx::Error> {
+    let timestamp = now().timestamp();
+    sqlx::query!(
+        "INSERT INTO mailing_requests(user_id, email, list, subscribe, created_at)
+        VALUES($1, $2, $3, $4, $5)",
+        user_id,
+        email,
+        list,
+        subscribe,
+        timestamp
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// `/subscribe <list> [email]` subscribes the member to a mailing list. The email is remembered
+/// for the next requests.
+pub async fn subscribe(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> HandlerResult {
+    let mut words = args.split_whitespace();
+    let usage = tr!(
+        lang,
+        "/subscribe <liste> [email]",
+        "/subscribe <list> [email]"
+    );
+    let Some(list) = check_request(&bot, &msg, words.next(), &usage, lang).await? else {
+        return Ok(());
+    };
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+    let user_id = user.id.to_string();
+
+    let email = match words.next() {
+        Some(email) if is_email(email) => {
+            sqlx::query!(
+                "INSERT INTO subscribers(user_id, email) VALUES($1, $2)
+                ON CONFLICT(user_id) DO UPDATE SET email = excluded.email",
+                user_id,
+                email
+            )
+            .execute(db.as_ref())
+            .await?;
+            Some(email.to_owned())
+        }
+        Some(_) => None,
+        None => sqlx::query!("SELECT email FROM subscribers WHERE user_id = $1", user_id)
+            .fetch_optional(db.as_ref())
+            .await?
+            .map(|s| s.email),
+    };
+    let Some(email) = email else {
+        bot.send_html(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Indique une adresse email valide: {}",
+                "Give a valid email address: {}",
+                code(&format!("/subscribe {} <email>", list))
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    record_request(db.as_ref(), &user_id, &email, &list, true).await?;
+    bot.send_html(
+        msg.chat.id,
+        tr!(
+            lang,
+            "C'est noté, {} sera inscrit·e à la liste {}",
+            "Noted, {} will be subscribed to the list {}",
+            bold(&email),
+            bold(&list)
+        ),
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
+
+/// `/unsubscribe <list>` unsubscribes the member from a mailing list, with the email they gave to
+/// `/subscribe`.
+pub async fn unsubscribe(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> HandlerResult {
+    let usage = tr!(lang, "/unsubscribe <liste>", "/unsubscribe <list>");
+    let Some(list) =
+        check_request(&bot, &msg, args.split_whitespace().next(), &usage, lang).await?
+    else {
+        return Ok(());
+    };
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+    let user_id = user.id.to_string();
+
+    let Some(email) = sqlx::query!("SELECT email FROM subscribers WHERE user_id = $1", user_id)
+        .fetch_optional(db.as_ref())
+        .await?
+        .map(|s| s.email)
+    else {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Aucune adresse email n'est associée à ton compte, contacte le comité pour te désinscrire",
+                "No email address is linked to your account, contact the committee to unsubscribe"
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    record_request(db.as_ref(), &user_id, &email, &list, false).await?;
+    bot.send_html(
+        msg.chat.id,
+        tr!(
+            lang,
+            "C'est noté, {} sera désinscrit·e de la liste {}",
+            "Noted, {} will be unsubscribed from the list {}",
+            bold(&email),
+            bold(&list)
+        ),
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
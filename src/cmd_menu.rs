@@ -0,0 +1,114 @@
+use teloxide::{requests::Requester, types::Message, Bot};
+
+use crate::{
+    dates::{now, parse_day},
+    i18n::{tr, Lang},
+    menus::get_menus,
+    outbox::Queued,
+    HandlerResult,
+};
+
+/// Maximum length of a Telegram message.
+const MAX_MESSAGE_LENGTH: usize = 4000;
+
+pub async fn menu(bot: Bot, msg: Message, args: String, lang: Lang) -> HandlerResult {
+    let today = now().date_naive();
+    let mut words = args.split_whitespace().collect::<Vec<_>>();
+    let day = match words.last().and_then(|w| parse_day(w, today)) {
+        Some(day) => {
+            words.pop();
+            day
+        }
+        None => today,
+    };
+    let restaurant = words.join(" ").to_lowercase();
+
+    let menus = match get_menus(day).await {
+        Ok(Some(menus)) => menus,
+        Ok(None) => {
+            bot.send_message(
+                msg.chat.id,
+                tr!(
+                    lang,
+                    "L'API des menus n'est pas configurée",
+                    "The menus API is not configured"
+                ),
+            )
+            .queued()
+            .await?;
+            return Ok(());
+        }
+        Err(e) => {
+            log::error!("Could not fetch menus: {e:#?}");
+            bot.send_message(
+                msg.chat.id,
+                tr!(
+                    lang,
+                    "Impossible de récupérer les menus",
+                    "Could not fetch the menus"
+                ),
+            )
+            .queued()
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let mut menus = menus
+        .into_iter()
+        .filter(|m| m.restaurant.to_lowercase().contains(&restaurant))
+        .collect::<Vec<_>>();
+    menus.sort_by(|a, b| a.restaurant.cmp(&b.restaurant));
+
+    if menus.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Aucun menu trouvé pour le {}",
+                "No menu found for {}",
+                day.format("%d/%m")
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    let mut text = tr!(lang, "🍽 Menus du {}", "🍽 Menus of {}", day.format("%d/%m"));
+    let mut current_restaurant = None;
+    for m in menus {
+        if current_restaurant.as_ref() != Some(&m.restaurant) {
+            text.push_str(&format!("\n\n{}:", m.restaurant));
+            current_restaurant = Some(m.restaurant.clone());
+        }
+
+        text.push_str(&format!("\n - {}", m.name));
+        if let Some(description) = m.description.filter(|d| !d.is_empty()) {
+            text.push_str(&format!(" ({})", description));
+        }
+        if !m.prices.is_empty() {
+            text.push_str(&format!(
+                " [{}]",
+                m.prices
+                    .iter()
+                    .map(|p| format!("{}: {:.2}", p.category, p.price))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+    }
+
+    if text.len() > MAX_MESSAGE_LENGTH {
+        let mut end = MAX_MESSAGE_LENGTH;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push_str("\n…");
+    }
+
+    bot.send_message(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
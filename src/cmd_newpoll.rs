@@ -0,0 +1,324 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use teloxide::{
+    payloads::SendPollSetters,
+    requests::Requester,
+    types::{Message, MessageId, PollType, UserId},
+    Bot,
+};
+
+use crate::{
+    callbacks::{CallbackData, CallbackResult, NEWPOLL},
+    cmd_poll::{PollDialogue, PollState},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    poll_results::track_poll,
+    wizard::{finish, keyboard, next_step},
+    HandlerResult,
+};
+
+/// Limits of the Telegram poll API.
+const MAX_QUESTION_LENGTH: usize = 300;
+const MAX_OPTION_LENGTH: usize = 100;
+const MIN_OPTIONS: usize = 2;
+const MAX_OPTIONS: usize = 10;
+
+/// Poll being built through the /newpoll dialogue.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct NewPoll {
+    /// ID of the last message sent by the bot in the dialogue, deleted at the next step.
+    pub message_id: Option<MessageId>,
+    /// User who started the dialogue, the only one allowed to answer the keyboards.
+    pub initiator: Option<UserId>,
+    pub question: String,
+    pub options: Vec<String>,
+    pub anonymous: bool,
+}
+
+/// Starts the /newpoll dialogue by asking for the question.
+pub async fn start_newpoll_dialogue(
+    bot: Bot,
+    msg: Message,
+    dialogue: PollDialogue,
+    lang: Lang,
+) -> HandlerResult {
+    let initiator = msg.from().map(|u| u.id);
+    next_step(
+        &bot,
+        &dialogue,
+        None,
+        tr!(
+            lang,
+            "Quelle est la question du sondage ?",
+            "What is the question of the poll?"
+        ),
+        None,
+        lang,
+        |message_id| {
+            PollState::NewPollQuestion(NewPoll {
+                message_id: Some(message_id),
+                initiator,
+                ..Default::default()
+            })
+        },
+    )
+    .await
+}
+
+/// Receives the question, and asks for the options.
+pub async fn newpoll_question(
+    bot: Bot,
+    msg: Message,
+    dialogue: PollDialogue,
+    poll: NewPoll,
+    lang: Lang,
+) -> HandlerResult {
+    let Some(question) = msg.text().map(str::trim) else {
+        return Ok(());
+    };
+
+    if question.is_empty() || question.chars().count() > MAX_QUESTION_LENGTH {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "La question doit faire entre 1 et {} caractères",
+                "The question must be between 1 and {} characters long",
+                MAX_QUESTION_LENGTH
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    next_step(
+        &bot,
+        &dialogue,
+        poll.message_id,
+        tr!(
+            lang,
+            "Quelles sont les options ? Envoie-les en un seul message, une par ligne ({} à {}).",
+            "What are the options? Send them in a single message, one per line ({} to {}).",
+            MIN_OPTIONS,
+            MAX_OPTIONS
+        ),
+        None,
+        lang,
+        |message_id| {
+            PollState::NewPollOptions(NewPoll {
+                message_id: Some(message_id),
+                question: question.to_owned(),
+                ..poll
+            })
+        },
+    )
+    .await
+}
+
+/// Receives the options, and asks whether the poll is anonymous.
+pub async fn newpoll_options(
+    bot: Bot,
+    msg: Message,
+    dialogue: PollDialogue,
+    poll: NewPoll,
+    lang: Lang,
+) -> HandlerResult {
+    let Some(text) = msg.text() else {
+        return Ok(());
+    };
+
+    let options = text
+        .lines()
+        .map(str::trim)
+        .filter(|o| !o.is_empty())
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+
+    if !(MIN_OPTIONS..=MAX_OPTIONS).contains(&options.len())
+        || options
+            .iter()
+            .any(|o| o.chars().count() > MAX_OPTION_LENGTH)
+    {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Il faut entre {} et {} options, d'au plus {} caractères chacune",
+                "There must be between {} and {} options, of at most {} characters each",
+                MIN_OPTIONS,
+                MAX_OPTIONS,
+                MAX_OPTION_LENGTH
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    next_step(
+        &bot,
+        &dialogue,
+        poll.message_id,
+        tr!(
+            lang,
+            "Le sondage est-il anonyme ?",
+            "Is the poll anonymous?"
+        ),
+        Some(keyboard(
+            NEWPOLL,
+            vec![
+                (tr!(lang, "Anonyme", "Anonymous"), "anonymous".into()),
+                (tr!(lang, "Public", "Public"), "public".into()),
+            ],
+        )),
+        lang,
+        |message_id| {
+            PollState::NewPollAnonymity(NewPoll {
+                message_id: Some(message_id),
+                options,
+                ..poll
+            })
+        },
+    )
+    .await
+}
+
+/// Receives the anonymity, and asks for the type of poll.
+pub async fn newpoll_anonymity(
+    bot: Bot,
+    data: CallbackData,
+    dialogue: PollDialogue,
+    poll: NewPoll,
+    lang: Lang,
+) -> CallbackResult {
+    let anonymous = match data.payload.as_str() {
+        "anonymous" => true,
+        "public" => false,
+        _ => return Ok(None),
+    };
+
+    next_step(
+        &bot,
+        &dialogue,
+        poll.message_id,
+        tr!(lang, "Quel type de sondage ?", "What type of poll?"),
+        Some(keyboard(
+            NEWPOLL,
+            vec![
+                (tr!(lang, "Normal", "Regular"), "regular".into()),
+                (
+                    tr!(
+                        lang,
+                        "Quiz (une seule bonne réponse)",
+                        "Quiz (a single correct answer)"
+                    ),
+                    "quiz".into(),
+                ),
+            ],
+        )),
+        lang,
+        |message_id| {
+            PollState::NewPollType(NewPoll {
+                message_id: Some(message_id),
+                anonymous,
+                ..poll
+            })
+        },
+    )
+    .await?;
+
+    Ok(None)
+}
+
+/// Receives the type of poll. Regular polls are sent directly, quizzes ask for the correct
+/// option first.
+pub async fn newpoll_type(
+    bot: Bot,
+    data: CallbackData,
+    dialogue: PollDialogue,
+    poll: NewPoll,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> CallbackResult {
+    match data.payload.as_str() {
+        "regular" => send_newpoll(&bot, &dialogue, db.as_ref(), poll, None).await?,
+        "quiz" => {
+            let options = poll
+                .options
+                .iter()
+                .enumerate()
+                .map(|(i, o)| (o.clone(), i.to_string()))
+                .collect();
+            next_step(
+                &bot,
+                &dialogue,
+                poll.message_id,
+                tr!(
+                    lang,
+                    "Quelle est la bonne réponse ?",
+                    "What is the correct answer?"
+                ),
+                Some(keyboard(NEWPOLL, options)),
+                lang,
+                |message_id| {
+                    PollState::NewPollCorrectOption(NewPoll {
+                        message_id: Some(message_id),
+                        ..poll
+                    })
+                },
+            )
+            .await?;
+        }
+        _ => {}
+    }
+
+    Ok(None)
+}
+
+/// Receives the correct option of a quiz, and sends it.
+pub async fn newpoll_correct_option(
+    bot: Bot,
+    data: CallbackData,
+    dialogue: PollDialogue,
+    poll: NewPoll,
+    db: Arc<SqlitePool>,
+) -> CallbackResult {
+    match data.payload.parse::<u8>() {
+        Ok(index) if (index as usize) < poll.options.len() => {
+            send_newpoll(&bot, &dialogue, db.as_ref(), poll, Some(index)).await?
+        }
+        _ => {}
+    }
+
+    Ok(None)
+}
+
+async fn send_newpoll(
+    bot: &Bot,
+    dialogue: &PollDialogue,
+    db: &SqlitePool,
+    poll: NewPoll,
+    correct_option: Option<u8>,
+) -> HandlerResult {
+    finish(bot, dialogue, poll.message_id).await?;
+
+    let request = bot
+        .send_poll(dialogue.chat_id(), poll.question, poll.options)
+        .is_anonymous(poll.anonymous);
+    let sent = match correct_option {
+        Some(index) => {
+            request
+                .type_(PollType::Quiz)
+                .correct_option_id(index)
+                .queued()
+                .await?
+        }
+        None => request.type_(PollType::Regular).queued().await?,
+    };
+    track_poll(db, &sent, None).await?;
+
+    Ok(())
+}
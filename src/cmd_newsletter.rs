@@ -0,0 +1,936 @@
+//! Newsletters: announcements with a title, a body, an optional image and link buttons, composed
+//! by the admins through a dialogue, and sent by the scheduler to the chosen chats and channels at
+//! the chosen time.
+//!
+//! The draft is kept in the state of the dialogue, stored in the database, so it survives a
+//! restart of the bot.
+
+use std::{collections::HashMap, sync::Arc};
+
+use chrono_tz::Tz;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use teloxide::{
+    payloads::{EditMessageReplyMarkupSetters, SendMessageSetters, SendPhotoSetters},
+    requests::Requester,
+    types::{
+        CallbackQuery, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, InputFile, Message,
+        MessageId, ParseMode, UserId,
+    },
+    Bot, RequestError,
+};
+
+use crate::{
+    audit::audit,
+    callbacks::{CallbackData, CallbackResult, NEWSLETTER},
+    cmd_poll::{PollDialogue, PollState},
+    dates::{chat_timezone, from_timestamp, now, now_in, parse_datetime},
+    format::{bold, code, escape, HtmlMessages, MessageBuilder},
+    i18n::{chat_language, format_interpreted, tr, Lang},
+    outbox::{Outbox, Priority, Queued},
+    permissions::delete_own_message,
+    wizard::{self, with_cancel},
+    HandlerResult,
+};
+
+/// Limits of the Telegram API.
+const MAX_TEXT_LENGTH: usize = 4096;
+const MAX_CAPTION_LENGTH: usize = 1024;
+const MAX_BUTTONS: usize = 10;
+
+/// Newsletter being composed through the /newsletter dialogue.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Newsletter {
+    /// ID of the last message sent by the bot in the dialogue, deleted at the next step.
+    pub message_id: Option<MessageId>,
+    /// Admin who started the dialogue, the only one allowed to answer.
+    pub initiator: Option<UserId>,
+    pub title: String,
+    /// HTML body.
+    pub body: String,
+    /// Telegram file of the image.
+    pub photo: Option<String>,
+    /// Text and url of the link buttons.
+    pub buttons: Vec<(String, String)>,
+    pub targets: Vec<String>,
+    /// Unix timestamp at which the newsletter is sent.
+    pub send_at: Option<i64>,
+}
+
+impl Newsletter {
+    fn text(&self) -> String {
+        format!("{}\n\n{}", bold(&self.title), self.body)
+    }
+
+    /// Whether the message comes from the admin composing the newsletter.
+    fn is_from_initiator(&self, msg: &Message) -> bool {
+        msg.from().map(|u| u.id) == self.initiator
+    }
+}
+
+fn link_keyboard(buttons: &[(String, String)]) -> Option<InlineKeyboardMarkup> {
+    let rows = buttons
+        .iter()
+        .filter_map(|(text, url)| {
+            Some(vec![InlineKeyboardButton::url(
+                text.clone(),
+                Url::parse(url).ok()?,
+            )])
+        })
+        .collect::<Vec<_>>();
+    (!rows.is_empty()).then(|| InlineKeyboardMarkup::new(rows))
+}
+
+/// Sends the newsletter: a photo with the text as caption, or a text message.
+async fn deliver(
+    bot: &Bot,
+    outbox: Option<&Outbox>,
+    chat_id: ChatId,
+    text: &str,
+    photo: Option<&str>,
+    buttons: &[(String, String)],
+) -> Result<Message, RequestError> {
+    let keyboard = link_keyboard(buttons);
+    match photo {
+        Some(photo) => {
+            let mut request = bot
+                .send_photo(chat_id, InputFile::file_id(photo))
+                .caption(text)
+                .parse_mode(ParseMode::Html);
+            if let Some(keyboard) = keyboard {
+                request = request.reply_markup(keyboard);
+            }
+            match outbox {
+                Some(outbox) => outbox.send(chat_id, Priority::Bulk, request).await,
+                None => request.queued().await,
+            }
+        }
+        None => {
+            let mut request = bot.send_html(chat_id, text);
+            if let Some(keyboard) = keyboard {
+                request = request.reply_markup(keyboard);
+            }
+            match outbox {
+                Some(outbox) => outbox.send(chat_id, Priority::Bulk, request).await,
+                None => request.queued().await,
+            }
+        }
+    }
+}
+
+/// Replaces the previous prompt of the dialogue with a new one.
+async fn prompt(
+    bot: &Bot,
+    dialogue: &PollDialogue,
+    newsletter: &Newsletter,
+    text: String,
+    keyboard: Option<InlineKeyboardMarkup>,
+    lang: Lang,
+) -> Result<MessageId, RequestError> {
+    wizard::prompt(bot, dialogue, newsletter.message_id, text, keyboard, lang).await
+}
+
+fn skip_keyboard(label: String) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new([[InlineKeyboardButton::callback(
+        label,
+        CallbackData::format(NEWSLETTER, "skip"),
+    )]])
+}
+
+/// Chats which can receive a newsletter: the chats authorized to use a command, and the channels.
+async fn target_chats(db: &SqlitePool) -> Result<Vec<(String, String)>, sqlx::Error> {
+    Ok(sqlx::query!(
+        r#"SELECT chat_id AS "chat_id!", title FROM chats
+        WHERE left_at IS NULL
+            AND (kind = 'channel' OR chat_id IN (SELECT chat_id FROM authorizations))
+        ORDER BY kind = 'channel' DESC, title"#
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|c| {
+        let title = c.title.unwrap_or_else(|| c.chat_id.clone());
+        (c.chat_id, title)
+    })
+    .collect())
+}
+
+/// `/newsletter` starts composing a newsletter, by asking for its title.
+pub async fn newsletter(
+    bot: Bot,
+    msg: Message,
+    dialogue: PollDialogue,
+    lang: Lang,
+) -> HandlerResult {
+    let draft = Newsletter {
+        initiator: msg.from().map(|u| u.id),
+        ..Default::default()
+    };
+    let message_id = prompt(
+        &bot,
+        &dialogue,
+        &draft,
+        tr!(
+            lang,
+            "📰 Nouvelle newsletter. Quel est son titre ?",
+            "📰 New newsletter. What is its title?"
+        ),
+        None,
+        lang,
+    )
+    .await?;
+    dialogue
+        .update(PollState::NewsletterTitle(Newsletter {
+            message_id: Some(message_id),
+            ..draft
+        }))
+        .await?;
+
+    Ok(())
+}
+
+/// Receives the title, and asks for the body.
+pub async fn newsletter_title(
+    bot: Bot,
+    msg: Message,
+    dialogue: PollDialogue,
+    draft: Newsletter,
+    lang: Lang,
+) -> HandlerResult {
+    let Some(title) = msg
+        .text()
+        .map(str::trim)
+        .filter(|_| draft.is_from_initiator(&msg))
+    else {
+        return Ok(());
+    };
+    if title.is_empty() {
+        return Ok(());
+    }
+
+    let message_id = prompt(
+        &bot,
+        &dialogue,
+        &draft,
+        escape(&tr!(
+            lang,
+            "Quel est le texte de la newsletter ? Il peut être mis en forme en HTML (<b>, <i>, <a href=\"...\">).",
+            "What is the text of the newsletter? It can be formatted in HTML (<b>, <i>, <a href=\"...\">)."
+        )),
+        None,
+        lang,
+    )
+    .await?;
+    dialogue
+        .update(PollState::NewsletterBody(Newsletter {
+            message_id: Some(message_id),
+            title: title.to_owned(),
+            ..draft
+        }))
+        .await?;
+
+    Ok(())
+}
+
+/// Receives the body, and asks for the image.
+pub async fn newsletter_body(
+    bot: Bot,
+    msg: Message,
+    dialogue: PollDialogue,
+    draft: Newsletter,
+    lang: Lang,
+) -> HandlerResult {
+    let Some(body) = msg
+        .text()
+        .map(str::trim)
+        .filter(|_| draft.is_from_initiator(&msg))
+    else {
+        return Ok(());
+    };
+    let draft = Newsletter {
+        body: body.to_owned(),
+        ..draft
+    };
+    if draft.text().chars().count() > MAX_TEXT_LENGTH {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "La newsletter doit faire au plus {} caractères",
+                "The newsletter must be at most {} characters long",
+                MAX_TEXT_LENGTH
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    let message_id = prompt(
+        &bot,
+        &dialogue,
+        &draft,
+        tr!(
+            lang,
+            "Envoie l'image de la newsletter, s'il y en a une.",
+            "Send the image of the newsletter, if there is one."
+        ),
+        Some(skip_keyboard(tr!(lang, "Pas d'image", "No image"))),
+        lang,
+    )
+    .await?;
+    dialogue
+        .update(PollState::NewsletterImage(Newsletter {
+            message_id: Some(message_id),
+            ..draft
+        }))
+        .await?;
+
+    Ok(())
+}
+
+async fn ask_buttons(
+    bot: &Bot,
+    dialogue: &PollDialogue,
+    draft: Newsletter,
+    lang: Lang,
+) -> HandlerResult {
+    let message_id = prompt(
+        bot,
+        dialogue,
+        &draft,
+        escape(&tr!(
+            lang,
+            "Envoie les boutons de liens, un par ligne au format « Texte - https://lien », s'il y en a.",
+            "Send the link buttons, one per line as \"Text - https://link\", if there are any."
+        )),
+        Some(skip_keyboard(tr!(lang, "Pas de boutons", "No buttons"))),
+        lang,
+    )
+    .await?;
+    dialogue
+        .update(PollState::NewsletterButtons(Newsletter {
+            message_id: Some(message_id),
+            ..draft
+        }))
+        .await?;
+    Ok(())
+}
+
+/// Receives the image, and asks for the buttons.
+pub async fn newsletter_image(
+    bot: Bot,
+    msg: Message,
+    dialogue: PollDialogue,
+    draft: Newsletter,
+    lang: Lang,
+) -> HandlerResult {
+    let Some(photo) = msg
+        .photo()
+        .and_then(|sizes| sizes.last())
+        .filter(|_| draft.is_from_initiator(&msg))
+    else {
+        return Ok(());
+    };
+    if draft.text().chars().count() > MAX_CAPTION_LENGTH {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Avec une image, la newsletter doit faire au plus {} caractères",
+                "With an image, the newsletter must be at most {} characters long",
+                MAX_CAPTION_LENGTH
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    let draft = Newsletter {
+        photo: Some(photo.file.id.clone()),
+        ..draft
+    };
+    ask_buttons(&bot, &dialogue, draft, lang).await
+}
+
+/// "No image" button.
+pub async fn newsletter_skip_image(
+    bot: Bot,
+    data: CallbackData,
+    dialogue: PollDialogue,
+    draft: Newsletter,
+    lang: Lang,
+) -> CallbackResult {
+    if data.payload == "skip" {
+        ask_buttons(&bot, &dialogue, draft, lang).await?;
+    }
+    Ok(None)
+}
+
+fn targets_keyboard(
+    chats: &[(String, String)],
+    selected: &[String],
+    lang: Lang,
+) -> InlineKeyboardMarkup {
+    let mut rows = chats
+        .iter()
+        .map(|(id, title)| {
+            let check = if selected.contains(id) {
+                "☑️"
+            } else {
+                "⬜️"
+            };
+            vec![InlineKeyboardButton::callback(
+                format!("{} {}", check, title),
+                CallbackData::format(NEWSLETTER, format!("toggle:{}", id)),
+            )]
+        })
+        .collect::<Vec<_>>();
+    rows.push(vec![InlineKeyboardButton::callback(
+        tr!(lang, "Suivant ➡️", "Next ➡️"),
+        CallbackData::format(NEWSLETTER, "next"),
+    )]);
+    InlineKeyboardMarkup::new(rows)
+}
+
+async fn ask_targets(
+    bot: &Bot,
+    dialogue: &PollDialogue,
+    db: &SqlitePool,
+    draft: Newsletter,
+    lang: Lang,
+) -> HandlerResult {
+    let chats = target_chats(db).await?;
+    let message_id = prompt(
+        bot,
+        dialogue,
+        &draft,
+        tr!(
+            lang,
+            "À quels groupes et canaux envoyer la newsletter ?",
+            "To which groups and channels should the newsletter be sent?"
+        ),
+        Some(targets_keyboard(&chats, &draft.targets, lang)),
+        lang,
+    )
+    .await?;
+    dialogue
+        .update(PollState::NewsletterTargets(Newsletter {
+            message_id: Some(message_id),
+            ..draft
+        }))
+        .await?;
+    Ok(())
+}
+
+/// Receives the buttons, and asks for the targets.
+pub async fn newsletter_buttons(
+    bot: Bot,
+    msg: Message,
+    dialogue: PollDialogue,
+    draft: Newsletter,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> HandlerResult {
+    let Some(text) = msg.text().filter(|_| draft.is_from_initiator(&msg)) else {
+        return Ok(());
+    };
+    let buttons = text
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| {
+            let (label, url) = l.rsplit_once(" - ")?;
+            let url = url.trim();
+            Url::parse(url).ok()?;
+            Some((label.trim().to_owned(), url.to_owned()))
+        })
+        .collect::<Option<Vec<_>>>();
+    let Some(buttons) = buttons.filter(|b| !b.is_empty() && b.len() <= MAX_BUTTONS) else {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Format invalide: au plus {} boutons, un par ligne au format « Texte - https://lien »",
+                "Invalid format: at most {} buttons, one per line as \"Text - https://link\"",
+                MAX_BUTTONS
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    let draft = Newsletter { buttons, ..draft };
+    ask_targets(&bot, &dialogue, db.as_ref(), draft, lang).await
+}
+
+/// "No buttons" button.
+pub async fn newsletter_skip_buttons(
+    bot: Bot,
+    data: CallbackData,
+    dialogue: PollDialogue,
+    draft: Newsletter,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> CallbackResult {
+    if data.payload == "skip" {
+        ask_targets(&bot, &dialogue, db.as_ref(), draft, lang).await?;
+    }
+    Ok(None)
+}
+
+/// Selection of the targets, then asks when to send the newsletter.
+pub async fn newsletter_targets(
+    bot: Bot,
+    data: CallbackData,
+    dialogue: PollDialogue,
+    draft: Newsletter,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> CallbackResult {
+    let mut draft = draft;
+    if let Some(id) = data.payload.strip_prefix("toggle:") {
+        match draft.targets.iter().position(|t| t == id) {
+            Some(i) => {
+                draft.targets.remove(i);
+            }
+            None => draft.targets.push(id.to_owned()),
+        }
+        if let Some(message_id) = draft.message_id {
+            let chats = target_chats(db.as_ref()).await?;
+            bot.edit_message_reply_markup(dialogue.chat_id(), message_id)
+                .reply_markup(with_cancel(
+                    Some(targets_keyboard(&chats, &draft.targets, lang)),
+                    lang,
+                ))
+                .queued()
+                .await?;
+        }
+        dialogue.update(PollState::NewsletterTargets(draft)).await?;
+        return Ok(None);
+    }
+    if data.payload != "next" {
+        return Ok(None);
+    }
+    if draft.targets.is_empty() {
+        return Ok(Some(tr!(
+            lang,
+            "Choisis au moins un groupe ou canal",
+            "Choose at least one group or channel"
+        )));
+    }
+
+    let message_id = prompt(
+        &bot,
+        &dialogue,
+        &draft,
+        tr!(
+            lang,
+            "Quand envoyer la newsletter ? (par exemple « demain 18h », « 25/12 9h30 »)",
+            "When should the newsletter be sent? (e.g. \"tomorrow 6pm\", \"25/12 9:30\")"
+        ),
+        Some(InlineKeyboardMarkup::new([[
+            InlineKeyboardButton::callback(
+                tr!(lang, "Maintenant", "Now"),
+                CallbackData::format(NEWSLETTER, "now"),
+            ),
+        ]])),
+        lang,
+    )
+    .await?;
+    dialogue
+        .update(PollState::NewsletterTime(Newsletter {
+            message_id: Some(message_id),
+            ..draft
+        }))
+        .await?;
+
+    Ok(None)
+}
+
+/// Sends the preview of the newsletter, to be confirmed.
+async fn preview(
+    bot: &Bot,
+    dialogue: &PollDialogue,
+    db: &SqlitePool,
+    draft: Newsletter,
+    lang: Lang,
+) -> HandlerResult {
+    if let Some(id) = draft.message_id {
+        delete_own_message(bot, dialogue.chat_id(), id).await;
+    }
+    let chat_id = dialogue.chat_id();
+    if let Err(e) = deliver(
+        bot,
+        None,
+        chat_id,
+        &draft.text(),
+        draft.photo.as_deref(),
+        &draft.buttons,
+    )
+    .await
+    {
+        // Most likely an invalid HTML in the body
+        bot.send_message(
+            chat_id,
+            tr!(
+                lang,
+                "La newsletter ne peut pas être envoyée ({}), renvoie son texte:",
+                "The newsletter cannot be sent ({}), send its text again:",
+                e
+            ),
+        )
+        .queued()
+        .await?;
+        dialogue
+            .update(PollState::NewsletterBody(Newsletter {
+                message_id: None,
+                photo: None,
+                buttons: vec![],
+                targets: vec![],
+                send_at: None,
+                ..draft
+            }))
+            .await?;
+        return Ok(());
+    }
+
+    let titles = target_chats(db)
+        .await?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    let targets = draft
+        .targets
+        .iter()
+        .map(|t| escape(titles.get(t).unwrap_or(t)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let timezone = chat_timezone(db, chat_id).await;
+    let when = match draft.send_at.and_then(|at| from_timestamp(at, timezone)) {
+        Some(at) => escape(&format_interpreted(&at, lang)),
+        None => tr!(lang, "maintenant", "now"),
+    };
+    let sent = bot
+        .send_html(
+            chat_id,
+            tr!(
+                lang,
+                "☝️ Aperçu de la newsletter, qui sera envoyée {} à: {}",
+                "☝️ Preview of the newsletter, which will be sent {} to: {}",
+                when,
+                targets
+            ),
+        )
+        .reply_markup(InlineKeyboardMarkup::new([[
+            InlineKeyboardButton::callback(
+                tr!(lang, "📣 Confirmer", "📣 Confirm"),
+                CallbackData::format(NEWSLETTER, "send"),
+            ),
+            InlineKeyboardButton::callback(
+                tr!(lang, "Annuler", "Cancel"),
+                CallbackData::format(NEWSLETTER, "cancel"),
+            ),
+        ]]))
+        .queued()
+        .await?;
+    dialogue
+        .update(PollState::NewsletterConfirm(Newsletter {
+            message_id: Some(sent.id),
+            ..draft
+        }))
+        .await?;
+
+    Ok(())
+}
+
+/// Receives the time of the sending, and previews the newsletter.
+pub async fn newsletter_time(
+    bot: Bot,
+    msg: Message,
+    dialogue: PollDialogue,
+    draft: Newsletter,
+    db: Arc<SqlitePool>,
+    timezone: Tz,
+    lang: Lang,
+) -> HandlerResult {
+    let Some(text) = msg.text().filter(|_| draft.is_from_initiator(&msg)) else {
+        return Ok(());
+    };
+    let Some((date, _)) = parse_datetime(text, now_in(timezone)) else {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Date invalide ou passée, par exemple « demain 18h », « 25/12 9h30 » ou « dans 2h »",
+                "Invalid or past date, e.g. \"tomorrow 6pm\", \"25/12 9:30\" or \"in 2h\""
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    let draft = Newsletter {
+        send_at: Some(date.timestamp()),
+        ..draft
+    };
+    preview(&bot, &dialogue, db.as_ref(), draft, lang).await
+}
+
+/// "Now" button of the time of the sending.
+pub async fn newsletter_now(
+    bot: Bot,
+    data: CallbackData,
+    dialogue: PollDialogue,
+    draft: Newsletter,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> CallbackResult {
+    if data.payload == "now" {
+        let draft = Newsletter {
+            send_at: None,
+            ..draft
+        };
+        preview(&bot, &dialogue, db.as_ref(), draft, lang).await?;
+    }
+    Ok(None)
+}
+
+/// Confirmation of the preview: schedules the newsletter.
+pub async fn confirm_newsletter(
+    bot: Bot,
+    query: CallbackQuery,
+    data: CallbackData,
+    dialogue: PollDialogue,
+    draft: Newsletter,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> CallbackResult {
+    let chat_id = dialogue.chat_id();
+    let Some(message_id) = draft.message_id else {
+        return Ok(None);
+    };
+    match data.payload.as_str() {
+        "cancel" => {
+            dialogue.update(PollState::Start).await?;
+            bot.edit_message_text(
+                chat_id,
+                message_id,
+                tr!(lang, "Newsletter annulée", "Newsletter cancelled"),
+            )
+            .queued()
+            .await?;
+            return Ok(None);
+        }
+        "send" => {}
+        _ => return Ok(None),
+    }
+
+    let author_chat_id = chat_id.to_string();
+    let author = format!("{} ({})", query.from.full_name(), query.from.id);
+    let text = draft.text();
+    let buttons = serde_json::to_string(&draft.buttons)?;
+    let send_at = draft.send_at.unwrap_or_else(|| now().timestamp());
+    let mut tx = db.begin().await?;
+    let id = sqlx::query!(
+        r#"INSERT INTO newsletters(author_chat_id, author, "text", photo, buttons, send_at)
+        VALUES($1, $2, $3, $4, $5, $6)"#,
+        author_chat_id,
+        author,
+        text,
+        draft.photo,
+        buttons,
+        send_at
+    )
+    .execute(&mut *tx)
+    .await?
+    .last_insert_rowid();
+    for target in &draft.targets {
+        sqlx::query!(
+            "INSERT INTO newsletter_targets(newsletter_id, chat_id) VALUES($1, $2)",
+            id,
+            target
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    // Reset once the newsletter is stored, so that the draft is kept if it could not be. The
+    // updates of a chat are handled one at a time, so a second click finds the dialogue reset.
+    dialogue.update(PollState::Start).await?;
+
+    let details = format!(
+        "#{} to {} chat(s): {}",
+        id,
+        draft.targets.len(),
+        draft.title
+    );
+    audit(db.as_ref(), &author, "newsletter", &details).await;
+    let timezone = chat_timezone(db.as_ref(), chat_id).await;
+    let text = match draft.send_at.and_then(|at| from_timestamp(at, timezone)) {
+        Some(at) => tr!(
+            lang,
+            "📰 Newsletter #{} programmée pour {}",
+            "📰 Newsletter #{} scheduled for {}",
+            id,
+            format_interpreted(&at, lang)
+        ),
+        None => tr!(
+            lang,
+            "📰 Newsletter #{} en cours d'envoi...",
+            "📰 Sending newsletter #{}...",
+            id
+        ),
+    };
+    bot.edit_message_text(chat_id, message_id, text)
+        .queued()
+        .await?;
+
+    Ok(None)
+}
+
+/// Number of ticks of the scheduler in which the delivery to a target is tried before it is
+/// reported as failed.
+const MAX_ATTEMPTS: i64 = 3;
+
+/// Sends the newsletters whose time has come, and reports the delivery in the chat where they
+/// were composed.
+///
+/// Each target is marked once it received the newsletter, so that a crash or a transient failure
+/// only retries the targets left. The newsletter is marked as sent, and reported, once no target
+/// is pending.
+pub async fn send_due_newsletters(bot: &Bot, outbox: &Outbox, db: &SqlitePool) -> HandlerResult {
+    let timestamp = now().timestamp();
+    let due = sqlx::query!(
+        r#"SELECT id AS "id!", author_chat_id, "text", photo, buttons FROM newsletters
+        WHERE sent_at IS NULL AND send_at <= $1"#,
+        timestamp
+    )
+    .fetch_all(db)
+    .await?;
+
+    for newsletter in due {
+        let buttons =
+            serde_json::from_str::<Vec<(String, String)>>(&newsletter.buttons).unwrap_or_default();
+        let pending = sqlx::query!(
+            "SELECT chat_id FROM newsletter_targets
+            WHERE newsletter_id = $1 AND sent_at IS NULL AND attempts < $2",
+            newsletter.id,
+            MAX_ATTEMPTS
+        )
+        .fetch_all(db)
+        .await?;
+        for target in &pending {
+            let result = match target.chat_id.parse::<i64>() {
+                Ok(id) => deliver(
+                    bot,
+                    Some(outbox),
+                    ChatId(id),
+                    &newsletter.text,
+                    newsletter.photo.as_deref(),
+                    &buttons,
+                )
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            match result {
+                Ok(()) => {
+                    let sent_at = now().timestamp();
+                    sqlx::query!(
+                        "UPDATE newsletter_targets SET sent_at = $1, error = NULL
+                        WHERE newsletter_id = $2 AND chat_id = $3",
+                        sent_at,
+                        newsletter.id,
+                        target.chat_id
+                    )
+                    .execute(db)
+                    .await?;
+                }
+                Err(e) => {
+                    log::warn!(
+                        "Could not send newsletter #{} to {}: {}",
+                        newsletter.id,
+                        target.chat_id,
+                        e
+                    );
+                    sqlx::query!(
+                        "UPDATE newsletter_targets SET attempts = attempts + 1, error = $1
+                        WHERE newsletter_id = $2 AND chat_id = $3",
+                        e,
+                        newsletter.id,
+                        target.chat_id
+                    )
+                    .execute(db)
+                    .await?;
+                }
+            }
+        }
+
+        let targets = sqlx::query!(
+            "SELECT chat_id, sent_at, attempts, error FROM newsletter_targets
+            WHERE newsletter_id = $1",
+            newsletter.id
+        )
+        .fetch_all(db)
+        .await?;
+        if targets
+            .iter()
+            .any(|t| t.sent_at.is_none() && t.attempts < MAX_ATTEMPTS)
+        {
+            continue;
+        }
+        let sent_at = now().timestamp();
+        sqlx::query!(
+            "UPDATE newsletters SET sent_at = $1 WHERE id = $2",
+            sent_at,
+            newsletter.id
+        )
+        .execute(db)
+        .await?;
+
+        let failures = targets
+            .iter()
+            .filter(|t| t.sent_at.is_none())
+            .map(|t| {
+                format!(
+                    "{}: {}",
+                    code(&t.chat_id),
+                    escape(t.error.as_deref().unwrap_or_default())
+                )
+            })
+            .collect::<Vec<_>>();
+        let Ok(author_chat) = newsletter.author_chat_id.parse::<i64>() else {
+            continue;
+        };
+        let author_chat = ChatId(author_chat);
+        let lang = chat_language(db, author_chat).await;
+        let mut report = MessageBuilder::new().text(&tr!(
+            lang,
+            "📰 Newsletter #{} envoyée à {}/{} groupe(s)",
+            "📰 Newsletter #{} sent to {}/{} chat(s)",
+            newsletter.id,
+            targets.len() - failures.len(),
+            targets.len()
+        ));
+        if !failures.is_empty() {
+            report = report
+                .separator()
+                .title(&tr!(lang, "Échecs", "Failures"))
+                .items(failures);
+        }
+        let report = report.build();
+        outbox
+            .send(
+                author_chat,
+                Priority::Bulk,
+                bot.send_html(author_chat, report),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
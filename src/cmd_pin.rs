@@ -0,0 +1,187 @@
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+use teloxide::{
+    requests::Requester,
+    types::{ChatId, Message},
+    Bot, RequestError,
+};
+
+use crate::{
+    i18n::{tr, Lang},
+    outbox::Queued,
+    permissions::{pin_message, unpin_all_messages, user_can_pin},
+    HandlerResult,
+};
+
+/// Messages of the bot which can be pinned automatically.
+#[derive(Clone, Copy)]
+pub enum AutoPin {
+    Bureau,
+    Countdowns,
+}
+
+/// Whether the messages of this kind are pinned automatically in the chat.
+pub async fn auto_pin(db: &SqlitePool, chat_id: ChatId, kind: AutoPin) -> bool {
+    let chat_id = chat_id.to_string();
+    let result = sqlx::query!(
+        r#"SELECT pin_bureau AS "pin_bureau: bool", pin_countdowns AS "pin_countdowns: bool"
+        FROM chats WHERE chat_id = $1"#,
+        chat_id
+    )
+    .fetch_optional(db)
+    .await;
+
+    match result {
+        Ok(Some(chat)) => match kind {
+            AutoPin::Bureau => chat.pin_bureau,
+            AutoPin::Countdowns => chat.pin_countdowns,
+        },
+        Ok(None) => false,
+        Err(e) => {
+            log::error!("Could not fetch the pin settings of {}: {:?}", chat_id, e);
+            false
+        }
+    }
+}
+
+/// Tells the user they are not allowed to pin messages. Returns whether they are.
+async fn check_user_can_pin(bot: &Bot, msg: &Message, lang: Lang) -> Result<bool, RequestError> {
+    if user_can_pin(bot, msg).await? {
+        return Ok(true);
+    }
+    bot.send_message(
+        msg.chat.id,
+        tr!(
+            lang,
+            "Tu n'as pas le droit d'épingler des messages dans ce groupe",
+            "You are not allowed to pin messages in this chat"
+        ),
+    )
+    .queued()
+    .await?;
+    Ok(false)
+}
+
+/// `/pin`, in reply to the message to pin.
+pub async fn pin(bot: Bot, msg: Message, lang: Lang) -> HandlerResult {
+    let Some(target) = msg.reply_to_message() else {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Réponds au message à épingler avec /pin",
+                "Reply to the message to pin with /pin"
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+    if !check_user_can_pin(&bot, &msg, lang).await? {
+        return Ok(());
+    }
+
+    pin_message(&bot, msg.chat.id, target.id, lang).await;
+    Ok(())
+}
+
+pub async fn unpin_all(bot: Bot, msg: Message, lang: Lang) -> HandlerResult {
+    if !check_user_can_pin(&bot, &msg, lang).await? {
+        return Ok(());
+    }
+
+    if unpin_all_messages(&bot, msg.chat.id, lang).await {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Tous les messages ont été désépinglés",
+                "All the messages were unpinned"
+            ),
+        )
+        .queued()
+        .await?;
+    }
+    Ok(())
+}
+
+/// `/autopin [bureau|countdown] [on|off]` displays or changes which messages of the bot are
+/// pinned automatically.
+pub async fn autopin(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> HandlerResult {
+    let words = args.split_whitespace().collect::<Vec<_>>();
+    let (kind, enabled) = match words.as_slice() {
+        [kind, value] => {
+            let kind = match kind.to_lowercase().as_str() {
+                "bureau" => Some(AutoPin::Bureau),
+                "countdown" | "countdowns" => Some(AutoPin::Countdowns),
+                _ => None,
+            };
+            let enabled = match value.to_lowercase().as_str() {
+                "on" => Some(true),
+                "off" => Some(false),
+                _ => None,
+            };
+            match kind.zip(enabled) {
+                Some(setting) => setting,
+                None => return autopin_status(&bot, &msg, db.as_ref(), lang).await,
+            }
+        }
+        _ => return autopin_status(&bot, &msg, db.as_ref(), lang).await,
+    };
+    if !check_user_can_pin(&bot, &msg, lang).await? {
+        return Ok(());
+    }
+
+    let chat_id = msg.chat.id.to_string();
+    match kind {
+        AutoPin::Bureau => {
+            sqlx::query!(
+                "UPDATE chats SET pin_bureau = $1 WHERE chat_id = $2",
+                enabled,
+                chat_id
+            )
+            .execute(db.as_ref())
+            .await?
+        }
+        AutoPin::Countdowns => {
+            sqlx::query!(
+                "UPDATE chats SET pin_countdowns = $1 WHERE chat_id = $2",
+                enabled,
+                chat_id
+            )
+            .execute(db.as_ref())
+            .await?
+        }
+    };
+
+    autopin_status(&bot, &msg, db.as_ref(), lang).await
+}
+
+async fn autopin_status(bot: &Bot, msg: &Message, db: &SqlitePool, lang: Lang) -> HandlerResult {
+    let state = |enabled| match (enabled, lang) {
+        (true, Lang::Fr) => "activé",
+        (false, Lang::Fr) => "désactivé",
+        (true, Lang::En) => "on",
+        (false, Lang::En) => "off",
+    };
+    bot.send_message(
+        msg.chat.id,
+        tr!(
+            lang,
+            "Épinglage automatique:\n - sondages du bureau: {}\n - comptes à rebours: {}\nUtilisation: /autopin bureau|countdown on|off",
+            "Automatic pinning:\n - bureau polls: {}\n - countdowns: {}\nUsage: /autopin bureau|countdown on|off",
+            state(auto_pin(db, msg.chat.id, AutoPin::Bureau).await),
+            state(auto_pin(db, msg.chat.id, AutoPin::Countdowns).await)
+        ),
+    )
+    .queued()
+    .await?;
+    Ok(())
+}
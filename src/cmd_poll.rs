@@ -1,23 +1,45 @@
-const POLL_MAX_OPTIONS_COUNT: u8 = 10; // max poll options
+use std::sync::Arc;
 
-use crate::directus::{get_committee, update_committee, Committee};
+use crate::{
+    callbacks::{CallbackData, CallbackResult, POLL_TARGET, QUOTE_TOO_LONG},
+    cmd_newpoll::NewPoll,
+    cmd_newsletter::Newsletter,
+    cmd_reactionstats::check_stats_visibility,
+    cmd_report::report_keyboard,
+    cmd_settings::poll_settings,
+    dates::now,
+    db::quotes::QuoteRepo,
+    dialogues::DialogueStorage,
+    format::{bold, italic, HtmlMessages, MessageBuilder},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    permissions::{delete_own_message, delete_user_message},
+    poll_options::{quiz_options, truncate, POLL_MAX_QUESTION_LENGTH},
+    poll_results::track_poll,
+    state::AppState,
+    templates::{render, template, QUIZ_QUESTION},
+    committee::committee_repository,
+    directus::Committee,
+    wizard::{next_step, with_cancel},
+};
 use log::error;
-use rand::{seq::SliceRandom, thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
 use teloxide::{
-    dispatching::dialogue::{GetChatId, InMemStorage},
-    payloads::{SendMessageSetters, SendPollSetters},
+    dispatching::dialogue::GetChatId,
+    payloads::{EditMessageReplyMarkupSetters, SendMessageSetters, SendPollSetters},
     prelude::Dialogue,
     requests::Requester,
     types::{
-        CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup, Message, MessageId,
-        ReplyMarkup,
+        CallbackQuery, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, Message, MessageId,
+        ReplyMarkup, UserId,
     },
     Bot,
 };
 
 use crate::HandlerResult;
 
-#[derive(Default, Clone, Debug)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub enum PollState {
     #[default]
     Start,
@@ -25,142 +47,536 @@ pub enum PollState {
         /// ID of the message querying the target of the /poll.
         /// Used to delete the message after the selection.
         message_id: MessageId,
+        /// User who started the dialogue, the only one allowed to choose.
+        initiator: Option<UserId>,
+        /// Quote of the message `/poll` replied to, the quiz is then sent once the target is
+        /// chosen.
+        #[serde(default)]
+        quote: Option<String>,
     },
     SetQuote {
         /// ID of the message querying the quote.
         /// Used to delete the message after the selection.
         message_id: MessageId,
         target: String,
+        /// User who chose the target, the only one whose quote is expected.
+        #[serde(default)]
+        initiator: Option<UserId>,
+    },
+    QuoteTooLong {
+        /// ID of the message asking what to do with the quote.
+        message_id: MessageId,
+        target: String,
+        quote: String,
+        /// User who sent the quote, the only one allowed to choose.
+        initiator: Option<UserId>,
+    },
+    NewPollQuestion(NewPoll),
+    NewPollOptions(NewPoll),
+    NewPollAnonymity(NewPoll),
+    NewPollType(NewPoll),
+    NewPollCorrectOption(NewPoll),
+    ConfirmBroadcast {
+        /// ID of the preview of the announcement, replaced by the delivery report.
+        message_id: MessageId,
+        text: String,
+        /// Admin who wrote the announcement, the only one allowed to confirm it.
+        initiator: Option<UserId>,
+    },
+    ConfirmRestore {
+        /// ID of the confirmation message, replaced by the outcome of the restoration.
+        message_id: MessageId,
+        /// Telegram file of the backup.
+        file_id: String,
+        /// Superadmin, the only one allowed to confirm.
+        initiator: Option<UserId>,
+    },
+    ConfirmImport {
+        /// ID of the confirmation message, replaced by the outcome of the import.
+        message_id: MessageId,
+        /// Telegram file of the export.
+        file_id: String,
+        /// Superadmin, the only one allowed to confirm.
+        initiator: Option<UserId>,
+    },
+    ReportMistake {
+        /// ID of the message asking to explain the mistake.
+        message_id: MessageId,
+        quote_id: i64,
+        /// Member who reported the mistake, the only one whose explanation is expected.
+        initiator: Option<UserId>,
+    },
+    NewsletterTitle(Newsletter),
+    NewsletterBody(Newsletter),
+    NewsletterImage(Newsletter),
+    NewsletterButtons(Newsletter),
+    NewsletterTargets(Newsletter),
+    NewsletterTime(Newsletter),
+    NewsletterConfirm(Newsletter),
+    ForwardQuiz {
+        /// ID of the message asking which quiz to make of the forwarded message.
+        message_id: MessageId,
+        text: String,
+        /// Author of the forwarded message, unknown if they hide their account.
+        author_id: Option<UserId>,
+        author: String,
+        /// Committee member who wrote the message, for the quote quizzes.
+        target: Option<String>,
+        /// User who forwarded the message, the only one allowed to choose.
+        initiator: Option<UserId>,
+    },
+    WelcomeMessage {
+        /// ID of the message asking for the welcome message of the chat.
+        message_id: MessageId,
+        /// Administrator who pressed the button of the `/settings` menu.
+        initiator: Option<UserId>,
     },
 }
-pub type PollDialogue = Dialogue<PollState, InMemStorage<PollState>>;
+pub type PollDialogue = Dialogue<PollState, DialogueStorage>;
+
+fn empty_committee(lang: Lang) -> String {
+    tr!(
+        lang,
+        "Le comité est vide, ses membres doivent d'abord être ajoutés dans Directus",
+        "The committee is empty, its members must first be added in Directus"
+    )
+}
+
+impl PollState {
+    /// User who started the dialogue, the only one allowed to answer its keyboards (including the
+    /// cancel button of the wizards).
+    pub fn initiator(&self) -> Option<UserId> {
+        match self {
+            Self::ChooseTarget { initiator, .. }
+            | Self::QuoteTooLong { initiator, .. }
+            | Self::ConfirmBroadcast { initiator, .. }
+            | Self::ConfirmRestore { initiator, .. }
+            | Self::ConfirmImport { initiator, .. }
+            | Self::ReportMistake { initiator, .. }
+            | Self::ForwardQuiz { initiator, .. }
+            | Self::SetQuote { initiator, .. }
+            | Self::WelcomeMessage { initiator, .. } => *initiator,
+            Self::NewPollQuestion(poll)
+            | Self::NewPollOptions(poll)
+            | Self::NewPollAnonymity(poll)
+            | Self::NewPollType(poll)
+            | Self::NewPollCorrectOption(poll) => poll.initiator,
+            Self::NewsletterTitle(draft)
+            | Self::NewsletterBody(draft)
+            | Self::NewsletterImage(draft)
+            | Self::NewsletterButtons(draft)
+            | Self::NewsletterTargets(draft)
+            | Self::NewsletterTime(draft)
+            | Self::NewsletterConfirm(draft) => draft.initiator,
+            Self::Start => None,
+        }
+    }
+
+    /// Message of the bot waiting for the answer of the current step, deleted when the dialogue
+    /// moves on or is cancelled.
+    pub fn prompt(&self) -> Option<MessageId> {
+        match self {
+            Self::Start => None,
+            Self::ChooseTarget { message_id, .. }
+            | Self::SetQuote { message_id, .. }
+            | Self::QuoteTooLong { message_id, .. }
+            | Self::ConfirmBroadcast { message_id, .. }
+            | Self::ConfirmRestore { message_id, .. }
+            | Self::ConfirmImport { message_id, .. }
+            | Self::ReportMistake { message_id, .. }
+            | Self::ForwardQuiz { message_id, .. }
+            | Self::WelcomeMessage { message_id, .. } => Some(*message_id),
+            Self::NewPollQuestion(poll)
+            | Self::NewPollOptions(poll)
+            | Self::NewPollAnonymity(poll)
+            | Self::NewPollType(poll)
+            | Self::NewPollCorrectOption(poll) => poll.message_id,
+            Self::NewsletterTitle(draft)
+            | Self::NewsletterBody(draft)
+            | Self::NewsletterImage(draft)
+            | Self::NewsletterButtons(draft)
+            | Self::NewsletterTargets(draft)
+            | Self::NewsletterTime(draft)
+            | Self::NewsletterConfirm(draft) => draft.message_id,
+        }
+    }
+}
 
 /// Starts the /poll dialogue by sending a message with an inline keyboard to select the target of the /poll.
 pub async fn start_poll_dialogue(
     bot: Bot,
     msg: Message,
     dialogue: PollDialogue,
+    lang: Lang,
 ) -> HandlerResult {
     log::info!("Starting /poll dialogue");
 
     log::debug!("Removing /poll message");
-    bot.delete_message(msg.chat.id, msg.id).await?;
+    delete_user_message(&bot, &msg, lang).await;
+
+    // In reply to a message, its text is the quote
+    let quote = msg
+        .reply_to_message()
+        .and_then(|m| m.text().or(m.caption()))
+        .map(str::to_owned);
+
+    log::debug!("Sending message with inline keyboard for callback");
+    let initiator = msg.from().map(|u| u.id);
+    let Some(msg) = send_target_keyboard(&bot, msg.chat.id, lang).await? else {
+        return Ok(());
+    };
+
+    log::debug!("Updating dialogue to ChooseTarget");
+    dialogue
+        .update(PollState::ChooseTarget {
+            message_id: msg.id,
+            initiator,
+            quote,
+        })
+        .await?;
+
+    Ok(())
+}
 
-    let committee = match get_committee().await {
+/// Sends the message asking for the target of the /poll, with a button per committee member.
+/// Returns `None` if the committee could not be fetched or is empty.
+pub async fn send_target_keyboard(
+    bot: &Bot,
+    chat_id: ChatId,
+    lang: Lang,
+) -> Result<Option<Message>, teloxide::RequestError> {
+    let committee = match committee_repository().get().await {
         Ok(v) => v,
         Err(e) => {
             error!("Could not fetch committee: {e:#?}");
-            return Ok(());
+            return Ok(None);
         }
     };
+    if committee.is_empty() {
+        bot.send_message(chat_id, empty_committee(lang)).queued().await?;
+        return Ok(None);
+    }
 
-    log::debug!("Sending message with inline keyboard for callback");
-    let msg = bot
-        .send_message(msg.chat.id, "Qui l'a dit ?")
-        .reply_markup(ReplyMarkup::InlineKeyboard(InlineKeyboardMarkup::new(
-            committee
-                .into_iter()
-                .map(|s| {
-                    InlineKeyboardButton::new(
-                        s.name.clone(),
-                        teloxide::types::InlineKeyboardButtonKind::CallbackData(s.name),
-                    )
-                })
-                .fold(vec![], |mut vec: Vec<Vec<InlineKeyboardButton>>, value| {
-                    if let Some(v) = vec.last_mut() {
-                        if v.len() < 3 {
-                            v.push(value);
-                            return vec;
-                        }
+    let keyboard = InlineKeyboardMarkup::new(
+        committee
+            .into_iter()
+            .map(|s| {
+                InlineKeyboardButton::new(
+                    s.name.clone(),
+                    teloxide::types::InlineKeyboardButtonKind::CallbackData(
+                        CallbackData::format(POLL_TARGET, s.name),
+                    ),
+                )
+            })
+            .fold(vec![], |mut vec: Vec<Vec<InlineKeyboardButton>>, value| {
+                if let Some(v) = vec.last_mut() {
+                    if v.len() < 3 {
+                        v.push(value);
+                        return vec;
                     }
-                    vec.push(vec![value]);
-                    vec
-                }),
-        )))
-        .await?;
-
-    log::debug!("Updating dialogue to ChooseTarget");
-    dialogue
-        .update(PollState::ChooseTarget { message_id: msg.id })
+                }
+                vec.push(vec![value]);
+                vec
+            }),
+    );
+    let msg = bot
+        .send_message(chat_id, tr!(lang, "Qui l'a dit ?", "Who said it?"))
+        .reply_markup(ReplyMarkup::InlineKeyboard(with_cancel(Some(keyboard), lang)))
+        .queued()
         .await?;
 
-    Ok(())
+    Ok(Some(msg))
 }
 
-/// Handles the callback from the inline keyboard, and sends a message to query the quote.
-/// The CallbackQuery data contains the name of the target.
+/// Handles the callback from the inline keyboard, and sends a message to query the quote (or
+/// creates the poll, if the quote is already known). The payload of the callback data contains
+/// the name of the target.
 pub async fn choose_target(
     bot: Bot,
     callback_query: CallbackQuery,
+    data: CallbackData,
     dialogue: PollDialogue,
-    message_id: MessageId,
-) -> HandlerResult {
-    if let Some(id) = callback_query.chat_id() {
-        log::debug!("Removing target query message");
-        bot.delete_message(dialogue.chat_id(), message_id).await?;
-
-        log::debug!("Sending quote query message");
-        let msg = bot.send_message(id, "Qu'a-t'il/elle dit ?").await?;
-
-        log::debug!("Updating dialogue to SetQuote");
-        dialogue
-            .update(PollState::SetQuote {
-                message_id: msg.id,
-                target: callback_query.data.unwrap_or_default(),
-            })
+    (message_id, _, quote): (MessageId, Option<UserId>, Option<String>),
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> CallbackResult {
+    if callback_query.chat_id().is_some() {
+        if let Some(quote) = quote {
+            log::debug!("Removing target query message");
+            delete_own_message(&bot, dialogue.chat_id(), message_id).await;
+            let initiator = Some(callback_query.from.id);
+            create_quote_poll(
+                &bot,
+                &dialogue,
+                db.as_ref(),
+                data.payload,
+                &quote,
+                initiator,
+                lang,
+            )
             .await?;
+            return Ok(None);
+        }
+
+        log::debug!("Replacing target query message with quote query message");
+        next_step(
+            &bot,
+            &dialogue,
+            Some(message_id),
+            tr!(lang, "Qu'a-t'il/elle dit ?", "What did they say?"),
+            None,
+            lang,
+            |message_id| PollState::SetQuote {
+                message_id,
+                target: data.payload,
+                initiator: Some(callback_query.from.id),
+            },
+        )
+        .await?;
     }
 
-    Ok(())
+    Ok(None)
 }
 
-/// Receives the quote and creates the poll. Since a poll can have at most 10 options,
-/// it is split in two polls, each containing half of the comittee.
+/// Receives the quote and creates the poll. If the quote does not fit in a poll question, asks
+/// whether to truncate it or to send it in a separate message.
 pub async fn set_quote(
     bot: Bot,
     msg: Message,
     dialogue: PollDialogue,
-    (message_id, target): (MessageId, String),
+    (message_id, target, initiator): (MessageId, String, Option<UserId>),
+    db: Arc<SqlitePool>,
+    lang: Lang,
 ) -> HandlerResult {
+    // Only the quote of the user who chose the target is expected
+    if msg.from().map(|u| u.id) != initiator {
+        return Ok(());
+    }
     if let Some(text) = msg.text() {
         log::debug!("Removing quote query message");
-        bot.delete_message(dialogue.chat_id(), message_id).await?;
+        delete_own_message(&bot, dialogue.chat_id(), message_id).await;
         log::debug!("Removing quote message");
-        bot.delete_message(dialogue.chat_id(), msg.id).await?;
-
-        let committee = match get_committee().await {
-            Ok(v) => v,
-            Err(e) => {
-                error!("Could not fetch committee: {e:#?}");
-                return Ok(());
-            }
-        };
-
-        let mut poll = committee.iter().map(|c| c.name.clone()).collect::<Vec<_>>();
-
-        // Splits the committee to have only 10 answers possible.
-        poll.retain(|s| -> bool { *s != target }); // filter the target from options
-        poll.shuffle(&mut thread_rng()); // shuffle the options
-        let index = thread_rng().gen_range(0..(POLL_MAX_OPTIONS_COUNT - 1)); // generate a valid index to insert target back
-        poll.insert(index as usize, target.clone()); // insert target back in options
-
-        if poll.len() > POLL_MAX_OPTIONS_COUNT as usize {
-            // split options to have only 10 options
-            poll = poll.split_at(POLL_MAX_OPTIONS_COUNT as usize).0.to_vec();
+        delete_user_message(&bot, &msg, lang).await;
+
+        create_quote_poll(&bot, &dialogue, db.as_ref(), target, text, initiator, lang).await?;
+    }
+
+    Ok(())
+}
+
+/// Creates the poll of the quote, or asks what to do if it does not fit in a poll question.
+async fn create_quote_poll(
+    bot: &Bot,
+    dialogue: &PollDialogue,
+    db: &SqlitePool,
+    target: String,
+    text: &str,
+    initiator: Option<UserId>,
+    lang: Lang,
+) -> HandlerResult {
+    let length = quote_question(db, dialogue.chat_id(), text, lang)
+        .await
+        .chars()
+        .count();
+    if length <= POLL_MAX_QUESTION_LENGTH {
+        return send_quote_poll(
+            bot,
+            dialogue,
+            dialogue.chat_id(),
+            db,
+            target,
+            text,
+            QuoteLayout::Question,
+            lang,
+        )
+        .await;
+    }
+
+    log::debug!("Quote too long ({} characters), asking what to do", length);
+    let choice = bot
+        .send_message(
+            dialogue.chat_id(),
+            tr!(
+                lang,
+                "La citation est trop longue pour un sondage ({} caractères sur {} possibles). Que faire ?",
+                "The quote is too long for a poll ({} characters out of {}). What should I do?",
+                length,
+                POLL_MAX_QUESTION_LENGTH
+            ),
+        )
+        .reply_markup(InlineKeyboardMarkup::new([
+            vec![InlineKeyboardButton::callback(
+                tr!(lang, "✂️ La tronquer", "✂️ Truncate it"),
+                CallbackData::format(QUOTE_TOO_LONG, "truncate"),
+            )],
+            vec![InlineKeyboardButton::callback(
+                tr!(
+                    lang,
+                    "📝 L'envoyer dans un message séparé",
+                    "📝 Send it in a separate message"
+                ),
+                CallbackData::format(QUOTE_TOO_LONG, "separate"),
+            )],
+            vec![InlineKeyboardButton::callback(
+                tr!(lang, "❌ Annuler", "❌ Cancel"),
+                CallbackData::format(QUOTE_TOO_LONG, "cancel"),
+            )],
+        ]))
+        .queued()
+        .await?;
+
+    dialogue
+        .update(PollState::QuoteTooLong {
+            message_id: choice.id,
+            target,
+            quote: text.to_owned(),
+            initiator,
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Handles the choice made for a quote too long for a poll question.
+pub async fn quote_too_long(
+    bot: Bot,
+    data: CallbackData,
+    dialogue: PollDialogue,
+    (message_id, target, quote, _): (MessageId, String, String, Option<UserId>),
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> CallbackResult {
+    let layout = match data.payload.as_str() {
+        "truncate" => QuoteLayout::Truncated,
+        "separate" => QuoteLayout::Separate,
+        "cancel" => {
+            delete_own_message(&bot, dialogue.chat_id(), message_id).await;
+            dialogue.update(PollState::Start).await?;
+            return Ok(Some(tr!(lang, "Citation annulée", "Quote cancelled")));
+        }
+        _ => return Ok(None),
+    };
+
+    delete_own_message(&bot, dialogue.chat_id(), message_id).await;
+    send_quote_poll(
+        &bot,
+        &dialogue,
+        dialogue.chat_id(),
+        db.as_ref(),
+        target,
+        &quote,
+        layout,
+        lang,
+    )
+    .await?;
+
+    Ok(None)
+}
+
+/// How the quote is displayed in the quiz.
+pub enum QuoteLayout {
+    /// The quote is the question of the poll.
+    Question,
+    /// The quote is truncated to fit in the question of the poll.
+    Truncated,
+    /// The quote is sent in its own message, to which the poll replies.
+    Separate,
+}
+
+/// Question of the quiz of the quote, with the template of the chat.
+pub async fn quote_question(db: &SqlitePool, chat_id: ChatId, quote: &str, lang: Lang) -> String {
+    let question = template(db, chat_id, QUIZ_QUESTION, lang).await;
+    render(&question, &[("quote", quote)])
+}
+
+/// Creates the quiz of the quote in the given chat, usually the one of the dialogue. Since a poll
+/// can have at most 10 options, only part of the committee is proposed.
+#[allow(clippy::too_many_arguments)]
+pub async fn send_quote_poll(
+    bot: &Bot,
+    dialogue: &PollDialogue,
+    chat_id: ChatId,
+    db: &SqlitePool,
+    target: String,
+    text: &str,
+    layout: QuoteLayout,
+    lang: Lang,
+) -> HandlerResult {
+    let committee = match committee_repository().get().await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Could not fetch committee: {e:#?}");
+            return Ok(());
         }
+    };
 
-        log::debug!("Sending poll");
-        bot.send_poll(
+    let names = committee.iter().map(|c| c.name.clone()).collect::<Vec<_>>();
+    let Some((poll, index)) = quiz_options(names, &target) else {
+        bot.send_message(
             dialogue.chat_id(),
-            format!(r#"Qui a dit: "{}" ?"#, text),
-            poll,
+            tr!(
+                lang,
+                "Il faut au moins deux membres dans le comité pour créer un sondage",
+                "The committee needs at least two members to create a poll"
+            ),
         )
-        .type_(teloxide::types::PollType::Quiz)
-        .is_anonymous(false)
-        .correct_option_id(index)
+        .queued()
         .await?;
+        dialogue.update(PollState::Start).await?;
+        return Ok(());
+    };
+
+    let (question, reply_to) = match layout {
+        QuoteLayout::Question => (quote_question(db, chat_id, text, lang).await, None),
+        QuoteLayout::Truncated => {
+            // Length of the question without the quote
+            let overhead = quote_question(db, chat_id, "", lang).await.chars().count();
+            let quote = truncate(text, POLL_MAX_QUESTION_LENGTH.saturating_sub(overhead));
+            (quote_question(db, chat_id, &quote, lang).await, None)
+        }
+        QuoteLayout::Separate => {
+            let quote_msg = bot
+                .send_html(chat_id, format!("« {} »", italic(text)))
+                .queued()
+                .await?;
+            (
+                tr!(lang, "Qui a dit cette citation ?", "Who said this quote?"),
+                Some(quote_msg.id),
+            )
+        }
+    };
 
-        update_committee(
+    log::debug!("Sending poll");
+    let settings = poll_settings(db, chat_id).await;
+    let mut request = bot
+        .send_poll(chat_id, question, poll)
+        .type_(teloxide::types::PollType::Quiz)
+        .is_anonymous(settings.quiz_anonymous)
+        .correct_option_id(index);
+    if let Some(id) = reply_to {
+        request = request.reply_to_message_id(id);
+    }
+    let poll_msg = request.queued().await?;
+    track_poll(db, &poll_msg, settings.quiz_close_after).await?;
+
+    let id = chat_id.to_string();
+    let timestamp = now().timestamp();
+    let poll_id = poll_msg.poll().map(|p| p.id.clone());
+    let quote_id =
+        db.insert_quote(&id, &target, text, poll_id.as_deref(), index, timestamp).await?;
+
+    // Added once the quote is stored, since the button refers to it
+    if let Err(e) = bot
+        .edit_message_reply_markup(chat_id, poll_msg.id)
+        .reply_markup(report_keyboard(quote_id, lang))
+        .queued()
+        .await
+    {
+        log::warn!("Could not add the report button to the quiz: {:?}", e);
+    }
+
+    committee_repository()
+        .update(
             committee
                 .into_iter()
                 .map(|c| {
@@ -177,34 +593,52 @@ pub async fn set_quote(
         )
         .await;
 
-        log::debug!("Resetting dialogue status");
-        dialogue.update(PollState::Start).await?;
-    }
+    log::debug!("Resetting dialogue status");
+    dialogue.update(PollState::Start).await?;
 
     Ok(())
 }
 
-pub async fn stats(bot: Bot, msg: Message) -> HandlerResult {
-    let mut committee = match get_committee().await {
+pub async fn stats(bot: Bot, msg: Message, state: AppState, lang: Lang) -> HandlerResult {
+    if !check_stats_visibility(&bot, &state, &msg, lang).await? {
+        return Ok(());
+    }
+    let mut committee = match committee_repository().get().await {
         Ok(v) => v,
         Err(e) => {
             error!("Could not fetch committee: {e:#?}");
             return Ok(());
         }
     };
+    if committee.is_empty() {
+        bot.send_message(msg.chat.id, empty_committee(lang)).queued().await?;
+        return Ok(());
+    }
 
     committee.sort_by_key(|r| r.poll_count);
+    // From the archived results, which do not depend on the polls still existing on Telegram
+    let rates = state.quote_repo.quote_success_rates().await?;
 
-    bot.send_message(
-        msg.chat.id,
-        committee
-            .into_iter()
-            .rev()
-            .map(|c| format!("- {} (polls: {})", c.name, c.poll_count))
-            .collect::<Vec<_>>()
-            .join("\n"),
-    )
-    .await?;
+    let text = MessageBuilder::new()
+        .title(&tr!(lang, "Polls par membre", "Polls per member"))
+        .items(committee.into_iter().rev().map(|c| {
+            let rate = rates
+                .iter()
+                .find(|(author, _, total)| *author == c.name && *total > 0)
+                .map(|(_, correct, total)| {
+                    tr!(
+                        lang,
+                        ", trouvé par {}% des {} réponses",
+                        ", found by {}% of {} answers",
+                        correct * 100 / total,
+                        total
+                    )
+                })
+                .unwrap_or_default();
+            format!("{} (polls: {}{})", bold(&c.name), c.poll_count, rate)
+        }))
+        .build();
+    bot.send_html(msg.chat.id, text).queued().await?;
 
     Ok(())
 }
\ No newline at end of file
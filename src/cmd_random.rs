@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use log::error;
+use rand::{seq::SliceRandom, thread_rng};
+use sqlx::SqlitePool;
+use teloxide::{requests::Requester, types::Message, Bot};
+
+use crate::{
+    committee::committee_repository,
+    dates::now,
+    format::{escape, HtmlMessages},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    HandlerResult,
+};
+
+/// Weight of a member never picked, i.e. the number of days after which a member is considered
+/// as never picked.
+const MAX_WEIGHT_DAYS: i64 = 30;
+
+fn usage(lang: Lang) -> String {
+    tr!(
+        lang,
+        "Utilisation: /random [nombre] [fair], \"fair\" favorise ceux qui ont été tirés le moins récemment",
+        "Usage: /random [count] [fair], \"fair\" favors the ones picked least recently"
+    )
+}
+
+pub async fn random(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> HandlerResult {
+    let mut count = 1;
+    let mut fair = false;
+    for word in args.split_whitespace() {
+        match word.to_lowercase().as_str() {
+            "fair" | "equitable" | "équitable" => fair = true,
+            w => match w.parse::<usize>() {
+                Ok(n) if n > 0 => count = n,
+                _ => {
+                    bot.send_message(msg.chat.id, usage(lang)).queued().await?;
+                    return Ok(());
+                }
+            },
+        }
+    }
+
+    let committee = match committee_repository().get().await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Could not fetch committee: {e:#?}");
+            return Ok(());
+        }
+    };
+    if committee.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            tr!(lang, "Le comité est vide", "The committee is empty"),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    let chat_id = msg.chat.id.to_string();
+    let timestamp = now().timestamp();
+    let picked = if fair {
+        let history = sqlx::query!(
+            r#"SELECT member AS "member!", MAX(picked_at) AS "last_pick!: i64" FROM random_picks WHERE chat_id = $1 GROUP BY member"#,
+            chat_id
+        )
+        .fetch_all(db.as_ref())
+        .await?;
+
+        let weighted = committee
+            .iter()
+            .map(|c| {
+                let days = history
+                    .iter()
+                    .find(|h| h.member == c.name)
+                    .map(|h| (timestamp - h.last_pick) / (24 * 60 * 60))
+                    .unwrap_or(MAX_WEIGHT_DAYS);
+                (c.name.clone(), days.clamp(0, MAX_WEIGHT_DAYS) + 1)
+            })
+            .collect::<Vec<_>>();
+
+        weighted
+            .choose_multiple_weighted(&mut thread_rng(), count, |(_, weight)| *weight as f64)
+            .map(|picked| picked.map(|(name, _)| name.clone()).collect::<Vec<_>>())
+            .unwrap_or_default()
+    } else {
+        committee
+            .choose_multiple(&mut thread_rng(), count)
+            .map(|c| c.name.clone())
+            .collect()
+    };
+
+    for member in &picked {
+        sqlx::query!(
+            "INSERT INTO random_picks(chat_id, member, picked_at) VALUES($1, $2, $3)",
+            chat_id,
+            member,
+            timestamp
+        )
+        .execute(db.as_ref())
+        .await?;
+    }
+
+    bot.send_html(
+        msg.chat.id,
+        tr!(
+            lang,
+            "🎲 Tiré(s) au sort: {}",
+            "🎲 Picked at random: {}",
+            escape(&picked.join(", "))
+        ),
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
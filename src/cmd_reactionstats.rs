@@ -0,0 +1,245 @@
+//! Leaderboard of the reactions received by the committee members whose Telegram account is
+//! linked with `/memberlink`.
+
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+use teloxide::{requests::Requester, types::Message, Bot};
+
+use crate::{
+    audit::audit_reversible,
+    cmd_halloffame::MEDALS,
+    cmd_undo::Undo,
+    committee::committee_repository,
+    dates::now,
+    db::committee::{links_of, CommitteeRepo},
+    format::{bold, HtmlMessages},
+    i18n::{tr, Lang},
+    names::{did_you_mean, match_name, NameMatch},
+    outbox::Queued,
+    reactions::MessageReactionUpdated,
+    state::AppState,
+    HandlerResult,
+};
+
+/// Reactions counted in the leaderboard.
+const TRACKED: &[&str] = &["😂", "❤️"];
+/// Days after which the reactions to a message are no longer counted.
+pub const MESSAGE_RETENTION_DAYS: i64 = 30;
+
+/// Whether the statistics of the committee are restricted to its linked members in the chat.
+pub async fn stats_committee_only(db: &SqlitePool, chat_id: &str) -> bool {
+    let result = sqlx::query!(
+        r#"SELECT stats_committee_only AS "committee_only: bool" FROM chats WHERE chat_id = $1"#,
+        chat_id
+    )
+    .fetch_optional(db)
+    .await;
+
+    match result {
+        Ok(chat) => chat.is_some_and(|c| c.committee_only),
+        Err(e) => {
+            log::error!("Could not fetch the stats settings of {}: {:?}", chat_id, e);
+            true
+        }
+    }
+}
+
+/// Whether the author of the message may see the statistics of the committee. With the
+/// `/settings` option, only the committee members linked with `/memberlink` and the admins can.
+/// Otherwise tells them why not.
+pub async fn check_stats_visibility(
+    bot: &Bot,
+    state: &AppState,
+    msg: &Message,
+    lang: Lang,
+) -> Result<bool, teloxide::RequestError> {
+    if !stats_committee_only(state.db.as_ref(), &msg.chat.id.to_string()).await {
+        return Ok(true);
+    }
+    if let Some(user) = msg.from() {
+        let linked = state
+            .committee_repo
+            .is_linked(&user.id.to_string())
+            .await
+            .unwrap_or(false);
+        if linked || state.admin_repo.is_admin(user.id).await {
+            return Ok(true);
+        }
+    }
+
+    bot.send_message(
+        msg.chat.id,
+        tr!(
+            lang,
+            "Les statistiques du comité sont réservées à ses membres dans ce groupe",
+            "The statistics of the committee are restricted to its members in this chat"
+        ),
+    )
+    .queued()
+    .await?;
+    Ok(false)
+}
+
+/// `/memberlink <name>`, in reply to a message of the committee member, links their Telegram
+/// account.
+pub async fn member_link(
+    bot: Bot,
+    msg: Message,
+    name: String,
+    db: Arc<SqlitePool>,
+    links: Arc<dyn CommitteeRepo>,
+) -> HandlerResult {
+    let name = name.trim();
+    let Some(user) = msg.reply_to_message().and_then(|m| m.from()) else {
+        bot.send_message(
+            msg.chat.id,
+            "Utilisation: /memberlink <nom>, en réponse à un message du membre du comité",
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    let committee = committee_repository().get().await?;
+    let member = match match_name(name, committee.iter().map(|c| c.name.as_str())) {
+        NameMatch::Found(member) => member,
+        NameMatch::Suggestions(suggestions) => {
+            bot.send_html(
+                msg.chat.id,
+                format!(
+                    "{} ne fait pas partie du comité{}",
+                    bold(name),
+                    did_you_mean(&suggestions)
+                ),
+            )
+            .queued()
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let telegram_id = user.id.to_string();
+    let previous = links_of(db.as_ref(), member, &telegram_id).await?;
+    links.link_member(member, &telegram_id).await?;
+
+    let details = format!("{} to {}", member, telegram_id);
+    let undo = Undo::MemberLink {
+        name: member.to_owned(),
+        telegram_id,
+        previous,
+    };
+    audit_reversible(db.as_ref(), &msg, "member_link", &details, &undo).await;
+    bot.send_html(
+        msg.chat.id,
+        format!(
+            "{} est désormais lié à {}",
+            bold(member),
+            bold(&user.full_name())
+        ),
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
+
+/// Records the messages of the linked members, to attribute the reactions they will receive.
+pub async fn record_message_author(msg: Message, db: Arc<SqlitePool>) {
+    let Some(user) = msg.from() else {
+        return;
+    };
+    let chat_id = msg.chat.id.to_string();
+    let telegram_id = user.id.to_string();
+    let timestamp = now().timestamp();
+    if let Err(e) = sqlx::query!(
+        "INSERT OR IGNORE INTO message_authors(chat_id, message_id, telegram_id, sent_at)
+        SELECT $1, $2, telegram_id, $3 FROM committee_links WHERE telegram_id = $4",
+        chat_id,
+        msg.id.0,
+        timestamp,
+        telegram_id
+    )
+    .execute(db.as_ref())
+    .await
+    {
+        log::error!("Could not record author of message in {}: {:?}", chat_id, e);
+    }
+}
+
+/// Counts the tracked reactions to the messages of the linked members, except their own.
+pub async fn record_reaction(
+    db: &SqlitePool,
+    reaction: &MessageReactionUpdated,
+) -> Result<(), sqlx::Error> {
+    let chat_id = reaction.chat.id.to_string();
+    let reactor = reaction.user.as_ref().map(|u| u.id.to_string());
+    for emoji in TRACKED {
+        let delta = reaction.delta(emoji);
+        if delta == 0 {
+            continue;
+        }
+        sqlx::query!(
+            "INSERT INTO reaction_stats(chat_id, telegram_id, emoji, count)
+            SELECT chat_id, telegram_id, $1, MAX($2, 0) FROM message_authors
+            WHERE chat_id = $3 AND message_id = $4 AND telegram_id IS NOT $5
+            ON CONFLICT(chat_id, telegram_id, emoji) DO UPDATE SET count = MAX(count + $2, 0)",
+            emoji,
+            delta,
+            chat_id,
+            reaction.message_id,
+            reactor
+        )
+        .execute(db)
+        .await?;
+    }
+    Ok(())
+}
+
+/// `/reactionstats` displays the committee members collecting the most reactions in the chat.
+pub async fn reaction_stats(bot: Bot, msg: Message, state: AppState, lang: Lang) -> HandlerResult {
+    if !check_stats_visibility(&bot, &state, &msg, lang).await? {
+        return Ok(());
+    }
+    let chat_id = msg.chat.id.to_string();
+    let mut text = bold(&tr!(
+        lang,
+        "Classement des réactions",
+        "Reactions leaderboard"
+    ));
+    let limit = MEDALS.len() as i64;
+    for emoji in TRACKED {
+        let ranking = sqlx::query!(
+            r#"SELECT l.name AS "name!", s.count FROM reaction_stats s
+            JOIN committee_links l ON l.telegram_id = s.telegram_id
+            WHERE s.chat_id = $1 AND s.emoji = $2 AND s.count > 0
+            ORDER BY s.count DESC, l.name LIMIT $3"#,
+            chat_id,
+            emoji,
+            limit
+        )
+        .fetch_all(state.db.as_ref())
+        .await?;
+
+        text += &format!("\n\n{}", emoji);
+        if ranking.is_empty() {
+            text += &format!("\n{}", tr!(lang, "Aucune réaction", "No reaction"));
+        }
+        for (medal, entry) in MEDALS.iter().zip(ranking) {
+            text += &format!("\n{} {}: {}", medal, bold(&entry.name), entry.count);
+        }
+    }
+    text += &format!(
+        "\n\n{}",
+        tr!(
+            lang,
+            "Seuls les membres du comité liés à leur compte Telegram sont comptés, depuis le début du suivi, pour les réactions reçues dans les {} jours suivant chaque message.",
+            "Only the committee members linked to their Telegram account are counted, since the tracking started, for the reactions received within {} days of each message.",
+            MESSAGE_RETENTION_DAYS
+        )
+    );
+
+    bot.send_html(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
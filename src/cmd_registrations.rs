@@ -0,0 +1,236 @@
+//! Registrations to the Directus events flagged as registrable, with the buttons of the events
+//! announcements.
+
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+use teloxide::{
+    requests::Requester,
+    types::{CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup, Message},
+    Bot,
+};
+
+use crate::{
+    callbacks::{CallbackData, CallbackResult, EVENT_REGISTRATION},
+    cmd_checkin::send_checkin_code,
+    dates::now,
+    directus::{get_upcoming_events, update_event_registrations, Event},
+    format::{bold, escape, HtmlMessages, MessageBuilder},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    poll_options::truncate,
+    HandlerResult,
+};
+
+/// Maximum length of the title of an event in the buttons.
+const BUTTON_TITLE_LENGTH: usize = 30;
+
+/// Buttons to register to the registrable events among the given ones, `None` if there is none.
+pub fn registration_keyboard(events: &[&Event], lang: Lang) -> Option<InlineKeyboardMarkup> {
+    let rows = events
+        .iter()
+        .filter(|e| e.registrable)
+        .map(|e| {
+            vec![
+                InlineKeyboardButton::callback(
+                    tr!(
+                        lang,
+                        "✅ Je participe: {}",
+                        "✅ I'm in: {}",
+                        truncate(&e.title, BUTTON_TITLE_LENGTH)
+                    ),
+                    CallbackData::format(EVENT_REGISTRATION, format!("yes:{}", e.id)),
+                ),
+                InlineKeyboardButton::callback(
+                    tr!(lang, "❌ Je ne viens pas", "❌ Not coming"),
+                    CallbackData::format(EVENT_REGISTRATION, format!("no:{}", e.id)),
+                ),
+            ]
+        })
+        .collect::<Vec<_>>();
+    (!rows.is_empty()).then(|| InlineKeyboardMarkup::new(rows))
+}
+
+/// Number of members attending the event.
+async fn attendance(db: &SqlitePool, event_id: i32) -> Result<i64, sqlx::Error> {
+    Ok(sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!: i64" FROM event_registrations
+        WHERE event_id = $1 AND attending"#,
+        event_id
+    )
+    .fetch_one(db)
+    .await?
+    .count)
+}
+
+/// Buttons of the events announcements: records the answer of the member, saves the number of
+/// participants in Directus, and sends the check-in code to the participant.
+pub async fn register(
+    bot: Bot,
+    query: CallbackQuery,
+    data: CallbackData,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> CallbackResult {
+    let Some((attending, Ok(event_id))) = data
+        .payload
+        .split_once(':')
+        .map(|(answer, id)| (answer == "yes", id.parse::<i32>()))
+    else {
+        return Ok(None);
+    };
+
+    let user_id = query.from.id.to_string();
+    let user_name = query.from.full_name();
+    let timestamp = now().timestamp();
+    sqlx::query!(
+        "INSERT INTO event_registrations(event_id, user_id, user_name, attending, updated_at)
+        VALUES($1, $2, $3, $4, $5)
+        ON CONFLICT(event_id, user_id) DO UPDATE SET
+            user_name = excluded.user_name,
+            attending = excluded.attending,
+            updated_at = excluded.updated_at",
+        event_id,
+        user_id,
+        user_name,
+        attending,
+        timestamp
+    )
+    .execute(db.as_ref())
+    .await?;
+
+    let count = attendance(db.as_ref(), event_id).await?;
+    if let Err(e) = update_event_registrations(event_id, count).await {
+        log::error!(
+            "Could not save the registrations of event {}: {e:#?}",
+            event_id
+        );
+    }
+
+    Ok(Some(if !attending {
+        tr!(
+            lang,
+            "C'est noté, tu ne viens pas",
+            "Noted, you are not coming"
+        )
+    } else if send_checkin_code(&bot, db.as_ref(), event_id, query.from.id).await? {
+        tr!(
+            lang,
+            "Inscription enregistrée ({} participant(s)), ton code d'entrée t'a été envoyé en privé",
+            "Registration saved ({} participant(s)), your entry code was sent to you in private",
+            count
+        )
+    } else {
+        tr!(
+            lang,
+            "Inscription enregistrée ({} participant(s)). Démarre une conversation privée avec le bot puis appuie à nouveau pour recevoir ton code d'entrée",
+            "Registration saved ({} participant(s)). Start a private chat with the bot then press again to receive your entry code",
+            count
+        )
+    }))
+}
+
+/// `/participants [event]` lists the members registered to an upcoming event (matched by title),
+/// or the registrable events with their number of participants.
+pub async fn participants(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> HandlerResult {
+    let events = match get_upcoming_events().await {
+        Ok(events) => events
+            .into_iter()
+            .filter(|e| e.registrable)
+            .collect::<Vec<_>>(),
+        Err(e) => {
+            log::error!("Could not fetch events: {e:#?}");
+            bot.send_message(
+                msg.chat.id,
+                tr!(
+                    lang,
+                    "Impossible de récupérer les événements",
+                    "Could not fetch the events"
+                ),
+            )
+            .queued()
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let query = args.trim().to_lowercase();
+    let event = events
+        .iter()
+        .find(|e| !query.is_empty() && e.title.to_lowercase().contains(&query));
+    let Some(event) = event else {
+        let mut lines = Vec::new();
+        for e in &events {
+            lines.push(format!(
+                "{}: {}",
+                bold(&e.title),
+                attendance(db.as_ref(), e.id).await?
+            ));
+        }
+        let text = if lines.is_empty() {
+            tr!(
+                lang,
+                "Aucun événement à venir n'est ouvert aux inscriptions",
+                "No upcoming event is open for registration"
+            )
+        } else {
+            MessageBuilder::new()
+                .text(&tr!(
+                    lang,
+                    "Utilisation: /participants <événement>",
+                    "Usage: /participants <event>"
+                ))
+                .separator()
+                .title(&tr!(
+                    lang,
+                    "Participants aux événements",
+                    "Participants to the events"
+                ))
+                .items(lines)
+                .build()
+        };
+        bot.send_html(msg.chat.id, text).queued().await?;
+        return Ok(());
+    };
+
+    let registrations = sqlx::query!(
+        r#"SELECT user_name, attending AS "attending: bool" FROM event_registrations
+        WHERE event_id = $1 ORDER BY updated_at"#,
+        event.id
+    )
+    .fetch_all(db.as_ref())
+    .await?;
+    let (attending, absent): (Vec<_>, Vec<_>) =
+        registrations.into_iter().partition(|r| r.attending);
+
+    let mut text = tr!(
+        lang,
+        "🎟 {}: {} participant(s)",
+        "🎟 {}: {} participant(s)",
+        bold(&event.title),
+        attending.len()
+    );
+    for r in &attending {
+        text += &format!("\n - {}", escape(&r.user_name));
+    }
+    if !absent.is_empty() {
+        text += &format!(
+            "\n\n{}",
+            tr!(
+                lang,
+                "{} personne(s) ne vien(nen)t pas",
+                "{} member(s) not coming",
+                absent.len()
+            )
+        );
+    }
+    bot.send_html(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
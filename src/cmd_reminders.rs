@@ -0,0 +1,209 @@
+use std::sync::Arc;
+
+use chrono_tz::Tz;
+use sqlx::SqlitePool;
+use teloxide::{
+    payloads::{EditMessageTextSetters, SendMessageSetters},
+    requests::Requester,
+    types::{CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup, Message},
+    Bot,
+};
+
+use crate::{
+    callbacks::{CallbackData, CallbackResult, REMINDER_CANCEL},
+    dates::{format_datetime, from_timestamp, now, now_in, parse_datetime},
+    format::{code, escape, HtmlMessages, MessageBuilder},
+    i18n::{chat_language, format_interpreted, tr, Lang},
+    outbox::{Outbox, Priority, Queued},
+    HandlerResult,
+};
+
+pub async fn remind(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+    timezone: Tz,
+) -> HandlerResult {
+    let Some((due_at, text)) =
+        parse_datetime(&args, now_in(timezone)).filter(|(_, text)| !text.is_empty())
+    else {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Utilisation: /remind <quand> <texte>, par exemple: /remind demain 14h acheter les bières",
+                "Usage: /remind <when> <text>, e.g. /remind tomorrow 2pm buy the beers"
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    let chat_id = msg.chat.id.to_string();
+    let author = msg.from().map(|u| u.full_name()).unwrap_or_default();
+    let timestamp = due_at.timestamp();
+    sqlx::query!(
+        r#"INSERT INTO reminders(chat_id, author, "text", due_at) VALUES($1, $2, $3, $4)"#,
+        chat_id,
+        author,
+        text,
+        timestamp
+    )
+    .execute(db.as_ref())
+    .await?;
+
+    bot.send_message(
+        msg.chat.id,
+        tr!(
+            lang,
+            "Rappel enregistré pour {}",
+            "Reminder set for {}",
+            format_interpreted(&due_at, lang)
+        ),
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
+
+pub async fn reminders(
+    bot: Bot,
+    msg: Message,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+    timezone: Tz,
+) -> HandlerResult {
+    let (text, keyboard) =
+        list_reminders(msg.chat.id.to_string(), db.as_ref(), lang, timezone).await?;
+    bot.send_html(msg.chat.id, text)
+        .reply_markup(keyboard)
+        .queued()
+        .await?;
+    Ok(())
+}
+
+/// Handles the cancel buttons of the /reminders list.
+pub async fn cancel_reminder(
+    bot: Bot,
+    query: CallbackQuery,
+    data: CallbackData,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+    timezone: Tz,
+) -> CallbackResult {
+    let (Some(msg), Some(id)) = (query.message.as_ref(), data.id()) else {
+        return Ok(None);
+    };
+
+    let chat_id = msg.chat.id.to_string();
+    sqlx::query!(
+        "DELETE FROM reminders WHERE id = $1 AND chat_id = $2",
+        id,
+        chat_id
+    )
+    .execute(db.as_ref())
+    .await?;
+
+    let (text, keyboard) = list_reminders(chat_id, db.as_ref(), lang, timezone).await?;
+    bot.edit_html(msg.chat.id, msg.id, text)
+        .reply_markup(keyboard)
+        .queued()
+        .await?;
+
+    Ok(Some(tr!(lang, "Rappel annulé", "Reminder cancelled")))
+}
+
+/// Builds the list of pending reminders of the chat, with a cancel button for each of them.
+async fn list_reminders(
+    chat_id: String,
+    db: &SqlitePool,
+    lang: Lang,
+    timezone: Tz,
+) -> Result<(String, InlineKeyboardMarkup), sqlx::Error> {
+    let reminders = sqlx::query!(
+        r#"SELECT id AS "id!", author, "text", due_at FROM reminders WHERE chat_id = $1 ORDER BY due_at"#,
+        chat_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    if reminders.is_empty() {
+        return Ok((
+            tr!(lang, "Aucun rappel en attente", "No pending reminder"),
+            InlineKeyboardMarkup::default(),
+        ));
+    }
+
+    let text = MessageBuilder::new()
+        .title(&tr!(lang, "Rappels en attente", "Pending reminders"))
+        .items(reminders.iter().map(|r| {
+            format!(
+                "{} {} ({}): {}",
+                code(&format!("#{}", r.id)),
+                from_timestamp(r.due_at, timezone)
+                    .map(|d| format_datetime(&d))
+                    .unwrap_or_default(),
+                escape(&r.author),
+                escape(&r.text)
+            )
+        }))
+        .build();
+
+    let keyboard = InlineKeyboardMarkup::new(reminders.iter().map(|r| {
+        vec![InlineKeyboardButton::callback(
+            tr!(lang, "❌ Annuler #{}", "❌ Cancel #{}", r.id),
+            CallbackData::format(REMINDER_CANCEL, r.id),
+        )]
+    }));
+
+    Ok((text, keyboard))
+}
+
+/// Sends the reminders which are due, and removes them from the database.
+pub async fn deliver_due_reminders(
+    bot: &Bot,
+    outbox: &Outbox,
+    db: &SqlitePool,
+) -> Result<(), sqlx::Error> {
+    let timestamp = now().timestamp();
+    let due = sqlx::query!(
+        r#"SELECT id AS "id!", chat_id, author, "text" FROM reminders WHERE due_at <= $1"#,
+        timestamp
+    )
+    .fetch_all(db)
+    .await?;
+
+    for reminder in due {
+        log::debug!("Delivering reminder #{}", reminder.id);
+        match reminder.chat_id.parse::<i64>().map(teloxide::types::ChatId) {
+            Ok(chat_id) => {
+                let lang = chat_language(db, chat_id).await;
+                let text = tr!(
+                    lang,
+                    "⏰ Rappel de {}: {}",
+                    "⏰ Reminder from {}: {}",
+                    escape(&reminder.author),
+                    escape(&reminder.text)
+                );
+                if let Err(e) = outbox
+                    .send(chat_id, Priority::Bulk, bot.send_html(chat_id, text))
+                    .await
+                {
+                    log::error!("Could not deliver reminder #{}: {:?}", reminder.id, e);
+                }
+            }
+            Err(e) => log::error!("Invalid chat id for reminder #{}: {:?}", reminder.id, e),
+        }
+
+        // Removed even on failure, to avoid retrying forever in a chat the bot has left
+        sqlx::query!("DELETE FROM reminders WHERE id = $1", reminder.id)
+            .execute(db)
+            .await?;
+    }
+
+    Ok(())
+}
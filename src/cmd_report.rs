@@ -0,0 +1,330 @@
+//! Reports of wrongly attributed quotes, from the button of the quizzes. The admins receive the
+//! reports in private, and can delete the quote or correct its author.
+
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+use teloxide::{
+    payloads::SendMessageSetters,
+    requests::Requester,
+    types::{
+        CallbackQuery, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, Message, MessageId,
+        UserId,
+    },
+    Bot,
+};
+
+use crate::{
+    audit::{actor, audit},
+    callbacks::{CallbackData, CallbackResult, QUOTE_REPORT, QUOTE_REPORT_RESOLVE},
+    cmd_poll::{PollDialogue, PollState},
+    committee::committee_repository,
+    dates::now,
+    db::quotes::QuoteRepo,
+    directus::Committee,
+    format::{bold, code, escape, italic, HtmlMessages},
+    i18n::{tr, Lang},
+    names::{did_you_mean, match_name, NameMatch},
+    outbox::Queued,
+    permissions::delete_own_message,
+    state::AppState,
+    HandlerResult,
+};
+
+/// Keyboard of the quizzes, to report a wrong attribution of the quote.
+pub fn report_keyboard(quote_id: i64, lang: Lang) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new([[InlineKeyboardButton::callback(
+        tr!(lang, "⚠️ Mauvaise attribution", "⚠️ Wrong attribution"),
+        CallbackData::format(QUOTE_REPORT, quote_id),
+    )]])
+}
+
+/// Asks the member who pressed the report button to explain the mistake.
+pub async fn report_mistake(
+    bot: Bot,
+    query: CallbackQuery,
+    data: CallbackData,
+    dialogue: PollDialogue,
+    quotes: Arc<dyn QuoteRepo>,
+    lang: Lang,
+) -> CallbackResult {
+    let Some(quote_id) = data.id() else {
+        return Ok(None);
+    };
+    if quotes.quote(quote_id).await?.is_none() {
+        return Ok(Some(tr!(
+            lang,
+            "Cette citation a été supprimée",
+            "This quote was deleted"
+        )));
+    }
+    if !matches!(dialogue.get().await?, None | Some(PollState::Start)) {
+        return Ok(Some(tr!(
+            lang,
+            "Une autre commande est en cours dans ce groupe, réessaie plus tard",
+            "Another command is ongoing in this chat, try again later"
+        )));
+    }
+
+    let sent = bot
+        .send_html(
+            dialogue.chat_id(),
+            tr!(
+                lang,
+                "{}, explique l'erreur dans ton prochain message (par exemple qui a vraiment dit cette citation)",
+                "{}, explain the mistake in your next message (e.g. who actually said this quote)",
+                bold(&query.from.full_name())
+            ),
+        )
+        .queued()
+        .await?;
+    dialogue
+        .update(PollState::ReportMistake {
+            message_id: sent.id,
+            quote_id,
+            initiator: Some(query.from.id),
+        })
+        .await?;
+
+    Ok(None)
+}
+
+/// Files the report with the explanation of the member, and sends it to the admins.
+pub async fn file_report(
+    bot: Bot,
+    msg: Message,
+    dialogue: PollDialogue,
+    (message_id, quote_id, initiator): (MessageId, i64, Option<UserId>),
+    state: AppState,
+    lang: Lang,
+) -> HandlerResult {
+    // Only the explanation of the member who reported the mistake is expected
+    let Some(user) = msg.from().filter(|u| Some(u.id) == initiator) else {
+        return Ok(());
+    };
+    let Some(reason) = msg.text().map(str::trim).filter(|t| !t.is_empty()) else {
+        return Ok(());
+    };
+    dialogue.update(PollState::Start).await?;
+    delete_own_message(&bot, msg.chat.id, message_id).await;
+
+    let Some(quote) = state.quote_repo.quote(quote_id).await? else {
+        return Ok(());
+    };
+
+    let chat_id = msg.chat.id.to_string();
+    let reporter = user.full_name();
+    let timestamp = now().timestamp();
+    let report_id = sqlx::query!(
+        "INSERT INTO quote_reports(quote_id, chat_id, reporter_name, reason, created_at)
+        VALUES($1, $2, $3, $4, $5)",
+        quote_id,
+        chat_id,
+        reporter,
+        reason,
+        timestamp
+    )
+    .execute(state.db.as_ref())
+    .await?
+    .last_insert_rowid();
+
+    let text = format!(
+        "⚠️ Signalement #{} de {} dans {}\n« {} »\nAttribuée à {}\nExplication: {}\n\nPour corriger l'auteur: {}",
+        report_id,
+        bold(&reporter),
+        escape(msg.chat.title().unwrap_or(&chat_id)),
+        italic(&quote.text),
+        bold(&quote.author),
+        escape(reason),
+        code(&format!("/quotefix {} <auteur>", report_id))
+    );
+    let keyboard = InlineKeyboardMarkup::new([[
+        InlineKeyboardButton::callback(
+            "🗑 Supprimer la citation",
+            CallbackData::format(QUOTE_REPORT_RESOLVE, format!("delete:{}", report_id)),
+        ),
+        InlineKeyboardButton::callback(
+            "Ignorer",
+            CallbackData::format(QUOTE_REPORT_RESOLVE, format!("dismiss:{}", report_id)),
+        ),
+    ]]);
+    for admin in state.admin_repo.admins().await? {
+        let Ok(id) = admin.telegram_id.parse::<i64>() else {
+            continue;
+        };
+        if let Err(e) = bot
+            .send_html(ChatId(id), &text)
+            .reply_markup(keyboard.clone())
+            .queued()
+            .await
+        {
+            log::warn!(
+                "Could not send report #{} to admin {}: {:?}",
+                report_id,
+                id,
+                e
+            );
+        }
+    }
+
+    bot.send_message(
+        msg.chat.id,
+        tr!(
+            lang,
+            "Merci, le signalement a été transmis aux admins",
+            "Thanks, the report was sent to the admins"
+        ),
+    )
+    .reply_to_message_id(msg.id)
+    .queued()
+    .await?;
+
+    Ok(())
+}
+
+/// Marks the report as resolved. Returns the quote it is about, if it was still open.
+async fn resolve(db: &SqlitePool, report_id: i64) -> Result<Option<i64>, sqlx::Error> {
+    let timestamp = now().timestamp();
+    Ok(sqlx::query!(
+        "UPDATE quote_reports SET resolved_at = $1 WHERE id = $2 AND resolved_at IS NULL
+        RETURNING quote_id",
+        timestamp,
+        report_id
+    )
+    .fetch_optional(db)
+    .await?
+    .map(|r| r.quote_id))
+}
+
+/// Buttons of the reports sent to the admins: deletes the quote, or dismisses the report.
+pub async fn resolve_report(
+    bot: Bot,
+    query: CallbackQuery,
+    data: CallbackData,
+    state: AppState,
+) -> CallbackResult {
+    if !state.admin_repo.is_admin(query.from.id).await {
+        return Ok(Some(
+            "Seuls les admins peuvent traiter les signalements".to_owned(),
+        ));
+    }
+    let Some((action, Ok(report_id))) = data
+        .payload
+        .split_once(':')
+        .map(|(action, id)| (action, id.parse::<i64>()))
+    else {
+        return Ok(None);
+    };
+
+    let Some(quote_id) = resolve(state.db.as_ref(), report_id).await? else {
+        return Ok(Some(format!(
+            "Le signalement #{} a déjà été traité",
+            report_id
+        )));
+    };
+    let actor = format!("{} ({})", query.from.full_name(), query.from.id);
+    let outcome = match action {
+        "delete" => {
+            state.quote_repo.delete_quote(quote_id).await?;
+            let details = format!("quote #{} (report #{})", quote_id, report_id);
+            audit(state.db.as_ref(), &actor, "quote_delete", &details).await;
+            "citation supprimée"
+        }
+        _ => "ignoré",
+    };
+
+    if let Some(message) = &query.message {
+        bot.edit_message_reply_markup(message.chat.id, message.id)
+            .queued()
+            .await?;
+    }
+    Ok(Some(format!("Signalement #{}: {}", report_id, outcome)))
+}
+
+/// `/quotefix <report id> <author>` attributes the quote of the report to another committee
+/// member.
+pub async fn quote_fix(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    quotes: Arc<dyn QuoteRepo>,
+) -> HandlerResult {
+    let Some((Ok(report_id), author)) = args
+        .trim()
+        .split_once(char::is_whitespace)
+        .map(|(id, author)| (id.trim_start_matches('#').parse::<i64>(), author.trim()))
+    else {
+        bot.send_message(msg.chat.id, "Utilisation: /quotefix <signalement> <auteur>")
+            .queued()
+            .await?;
+        return Ok(());
+    };
+
+    let committee = committee_repository().get().await?;
+    let author = match match_name(author, committee.iter().map(|c| c.name.as_str())) {
+        NameMatch::Found(name) => name.to_owned(),
+        NameMatch::Suggestions(suggestions) => {
+            bot.send_html(
+                msg.chat.id,
+                format!(
+                    "{} ne fait pas partie du comité{}",
+                    bold(author),
+                    did_you_mean(&suggestions)
+                ),
+            )
+            .queued()
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let Some(quote_id) = resolve(db.as_ref(), report_id).await? else {
+        bot.send_message(
+            msg.chat.id,
+            format!("Aucun signalement #{} en attente de traitement", report_id),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+    let previous = quotes.set_quote_author(quote_id, &author).await?;
+
+    // The quizzes count of the committee members follows the attribution
+    if let Some(previous) = previous.as_ref().filter(|p| **p != author) {
+        committee_repository()
+            .update(
+                committee
+                    .into_iter()
+                    .map(|c| {
+                        let poll_count = if &c.name == previous {
+                            (c.poll_count - 1).max(0)
+                        } else if c.name == author {
+                            c.poll_count + 1
+                        } else {
+                            c.poll_count
+                        };
+                        Committee { poll_count, ..c }
+                    })
+                    .collect(),
+            )
+            .await;
+    }
+    let details = format!(
+        "quote #{} from {} to {} (report #{})",
+        quote_id,
+        previous.as_deref().unwrap_or_default(),
+        author,
+        report_id
+    );
+    audit(db.as_ref(), &actor(&msg), "quote_fix", &details).await;
+
+    bot.send_html(
+        msg.chat.id,
+        format!("Citation désormais attribuée à {}", bold(&author)),
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
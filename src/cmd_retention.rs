@@ -0,0 +1,128 @@
+//! `/retention`: durations for which the data of the bot is kept, applied by the maintenance job.
+
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+use teloxide::{requests::Requester, types::Message, Bot};
+
+use crate::{
+    audit::{actor, audit},
+    format::{bold, code, escape, italic, HtmlMessages, MessageBuilder},
+    outbox::Queued,
+    retention::{custom_days, find_policy, reset_retention, set_retention, Policy, POLICIES},
+    HandlerResult,
+};
+
+const USAGE: &str = "Utilisation: /retention <type> <jours>, /retention <type> jamais pour tout garder, ou /retention <type> défaut pour revenir à la durée par défaut";
+
+fn duration_text(days: Option<i64>) -> String {
+    match days {
+        Some(days) => format!("{} jours", days),
+        None => "pour toujours".to_owned(),
+    }
+}
+
+/// What to do with the policy, from the argument of the command.
+enum Change {
+    Set(Option<i64>),
+    Reset,
+}
+
+fn parse_change(policy: Policy, arg: &str) -> Result<Change, String> {
+    match arg.to_lowercase().as_str() {
+        "jamais" | "never" | "toujours" | "forever" => Ok(Change::Set(None)),
+        "défaut" | "defaut" | "default" => Ok(Change::Reset),
+        arg => match arg.trim_end_matches(['j', 'd']).parse::<i64>() {
+            Ok(days) if days >= policy.min_days() => Ok(Change::Set(Some(days))),
+            Ok(_) => Err(format!(
+                "La durée de {} doit être d'au moins {} jours",
+                policy.key(),
+                policy.min_days()
+            )),
+            Err(_) => Err(USAGE.to_owned()),
+        },
+    }
+}
+
+/// `/retention` lists the retention durations, `/retention <policy> <days|jamais|défaut>` changes
+/// one of them.
+pub async fn retention(bot: Bot, msg: Message, args: String, db: Arc<SqlitePool>) -> HandlerResult {
+    let args = args.split_whitespace().collect::<Vec<_>>();
+    let [key, arg] = args[..] else {
+        if !args.is_empty() {
+            bot.send_message(msg.chat.id, USAGE).queued().await?;
+            return Ok(());
+        }
+        let mut text = MessageBuilder::new()
+            .title("🗄 Conservation des données")
+            .text(
+            "Les données plus anciennes sont supprimées par la maintenance de la base de données.",
+        );
+        for policy in POLICIES {
+            let (days, origin) = match custom_days(db.as_ref(), policy).await? {
+                Some(days) => (days, String::new()),
+                None => (
+                    policy.default_days(),
+                    format!(" {}", italic("(par défaut)")),
+                ),
+            };
+            text = text.item(format!(
+                "{} ({}): {}{}",
+                code(policy.key()),
+                escape(policy.description()),
+                bold(&duration_text(days)),
+                origin
+            ));
+        }
+        bot.send_html(msg.chat.id, text.text(USAGE).build())
+            .queued()
+            .await?;
+        return Ok(());
+    };
+
+    let Some(policy) = find_policy(key) else {
+        let keys = POLICIES
+            .iter()
+            .map(|p| code(p.key()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        bot.send_html(
+            msg.chat.id,
+            format!("Type de données inconnu: {}\nTypes: {}", escape(key), keys),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    let days = match parse_change(policy, arg) {
+        Ok(Change::Set(days)) => {
+            set_retention(db.as_ref(), policy, days).await?;
+            days
+        }
+        Ok(Change::Reset) => {
+            reset_retention(db.as_ref(), policy).await?;
+            policy.default_days()
+        }
+        Err(reason) => {
+            bot.send_message(msg.chat.id, reason).queued().await?;
+            return Ok(());
+        }
+    };
+
+    let details = format!("{}: {}", policy.key(), duration_text(days));
+    audit(db.as_ref(), &actor(&msg), "retention_set", &details).await;
+    bot.send_html(
+        msg.chat.id,
+        format!(
+            "Durée de conservation de {} ({}): {}",
+            code(policy.key()),
+            escape(policy.description()),
+            bold(&duration_text(days))
+        ),
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
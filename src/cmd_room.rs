@@ -0,0 +1,108 @@
+use chrono_tz::Tz;
+use teloxide::{requests::Requester, types::Message, Bot};
+
+use crate::{
+    dates::now_in,
+    format::{bold, escape, HtmlMessages},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    rooms::get_bookings,
+    HandlerResult,
+};
+
+/// `/room <name>` tells whether the room is free, and what is scheduled next today.
+pub async fn room(bot: Bot, msg: Message, name: String, lang: Lang, timezone: Tz) -> HandlerResult {
+    let name = name.trim().to_uppercase();
+    if name.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Utilisation: /room <salle> (par exemple /room INM202)",
+                "Usage: /room <room> (e.g. /room INM202)"
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    let now = now_in(timezone);
+    let bookings = match get_bookings(&name, now.date_naive()).await {
+        Ok(Some(bookings)) => bookings,
+        Ok(None) => {
+            bot.send_message(
+                msg.chat.id,
+                tr!(
+                    lang,
+                    "L'API des salles n'est pas configurée",
+                    "The rooms API is not configured"
+                ),
+            )
+            .queued()
+            .await?;
+            return Ok(());
+        }
+        Err(e) => {
+            log::error!("Could not fetch bookings of {}: {e:#?}", name);
+            bot.send_message(
+                msg.chat.id,
+                tr!(
+                    lang,
+                    "Impossible de récupérer l'occupation de la salle",
+                    "Could not fetch the occupancy of the room"
+                ),
+            )
+            .queued()
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let time = |date: chrono::DateTime<chrono::FixedOffset>| {
+        date.with_timezone(&timezone).format("%H:%M").to_string()
+    };
+    let current = bookings
+        .iter()
+        .find(|b| b.start().is_some_and(|s| s <= now) && b.end().is_some_and(|e| e > now));
+    let mut text = match current {
+        Some(booking) => tr!(
+            lang,
+            "🔴 {} est occupée jusqu'à {}: {}",
+            "🔴 {} is busy until {}: {}",
+            bold(&name),
+            booking.end().map(time).unwrap_or_default(),
+            escape(&booking.title)
+        ),
+        None => tr!(lang, "🟢 {} est libre", "🟢 {} is free", bold(&name)),
+    };
+
+    let next = bookings
+        .iter()
+        .filter(|b| b.start().is_some_and(|s| s > now))
+        .collect::<Vec<_>>();
+    if next.is_empty() {
+        text += &format!(
+            "\n{}",
+            tr!(
+                lang,
+                "Plus rien de prévu aujourd'hui",
+                "Nothing else scheduled today"
+            )
+        );
+    } else {
+        text += &format!("\n\n{}", tr!(lang, "Ensuite:", "Next:"));
+        for booking in next {
+            text += &format!(
+                "\n - {}-{} {}",
+                booking.start().map(time).unwrap_or_default(),
+                booking.end().map(time).unwrap_or_default(),
+                escape(&booking.title)
+            );
+        }
+    }
+
+    bot.send_html(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
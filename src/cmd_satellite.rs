@@ -0,0 +1,84 @@
+use chrono_tz::Tz;
+use teloxide::{requests::Requester, types::Message, Bot};
+
+use crate::{
+    dates::{format_datetime, now_in},
+    format::{bold, escape, HtmlMessages},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    satellite::get_program,
+    HandlerResult,
+};
+
+/// Number of coming events displayed.
+const EVENTS_LIMIT: usize = 5;
+
+/// `/satellite` displays the beers on tap and the coming events at Satellite.
+pub async fn satellite(bot: Bot, msg: Message, lang: Lang, timezone: Tz) -> HandlerResult {
+    let program = match get_program().await {
+        Ok(Some(program)) => program,
+        Ok(None) => {
+            bot.send_message(
+                msg.chat.id,
+                tr!(
+                    lang,
+                    "L'API de Satellite n'est pas configurée",
+                    "The Satellite API is not configured"
+                ),
+            )
+            .queued()
+            .await?;
+            return Ok(());
+        }
+        Err(e) => {
+            log::error!("Could not fetch the program of Satellite: {e:#?}");
+            bot.send_message(
+                msg.chat.id,
+                tr!(
+                    lang,
+                    "Impossible de récupérer le programme de Satellite",
+                    "Could not fetch the program of Satellite"
+                ),
+            )
+            .queued()
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let mut text = format!("🍺 {}", bold(&tr!(lang, "À la pression", "On tap")));
+    if program.beers.is_empty() {
+        text += &format!(
+            "\n{}",
+            tr!(lang, "Aucune bière annoncée", "No beer announced")
+        );
+    }
+    for beer in &program.beers {
+        text += &format!("\n - {}", bold(&beer.name));
+        if let Some(style) = &beer.style {
+            text += &format!(" ({})", escape(style));
+        }
+        if let Some(price) = beer.price {
+            text += &format!(": {:.2} CHF", price);
+        }
+    }
+
+    let now = now_in(timezone);
+    let events = program
+        .events
+        .iter()
+        .filter_map(|e| Some((e.start()?.with_timezone(&timezone), e)))
+        .filter(|(start, _)| *start >= now)
+        .take(EVENTS_LIMIT)
+        .collect::<Vec<_>>();
+    if !events.is_empty() {
+        text += &format!("\n\n📅 {}", bold(&tr!(lang, "Événements", "Events")));
+        for (start, event) in events {
+            text += &format!("\n - {} {}", format_datetime(&start), escape(&event.title));
+        }
+    }
+
+    bot.send_html(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
@@ -0,0 +1,558 @@
+use std::{str::FromStr, sync::Arc};
+
+use chrono_tz::Tz;
+use cron::Schedule;
+use sqlx::SqlitePool;
+use teloxide::{
+    payloads::SendMessageSetters,
+    requests::Requester,
+    types::{ChatId, Message},
+    Bot,
+};
+
+use crate::{
+    audit::{actor, audit},
+    channels::{linked_channel, publication, PUBLICATIONS},
+    chats::topic,
+    cmd_bureau::send_bureau_poll,
+    dates::{
+        chat_timezone, format_datetime, from_timestamp, now, now_in, parse_recurrence, TIMEZONE,
+    },
+    db::authorizations::AuthorizationRepo,
+    format::{code, escape, HtmlMessages, MessageBuilder},
+    i18n::{format_interpreted, tr, Lang},
+    outbox::{Outbox, Priority, Queued},
+    HandlerResult,
+};
+
+/// Commands which can be scheduled, since they don't depend on the message invoking them.
+const SCHEDULABLE_COMMANDS: &[&str] = &["/bureau", "/quote", "/events", "/digest"];
+/// First argument of `/scheduleadd` posting in the linked channel instead of the chat.
+const CHANNEL_KEYWORDS: &[&str] = &["channel", "canal"];
+
+/// Names of the weekdays, from their number in the standard cron (7 is also Sunday).
+const WEEKDAYS: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+/// Replaces the numbers of an item of the weekdays field by their names, expanding the ranges and
+/// steps (`1-5` becomes `MON,TUE,WED,THU,FRI`), so that `5-7` or `*/2` keep their standard meaning.
+fn weekday_names(item: &str) -> Option<String> {
+    // Names (`MON-FRI`), `*` and `?` are understood by the crate as they are
+    if !item.contains(|c: char| c.is_ascii_digit()) {
+        return Some(item.to_owned());
+    }
+
+    let (base, step) = match item.split_once('/') {
+        Some((base, step)) => (base, step.parse::<usize>().ok().filter(|s| *s > 0)?),
+        None => (item, 1),
+    };
+    let (start, end) = match base.split_once('-') {
+        _ if base == "*" => (0, 6),
+        Some((start, end)) => (start.parse().ok()?, end.parse().ok()?),
+        // `5/2` means from Friday to Saturday, every 2 days
+        None if item.contains('/') => (base.parse().ok()?, 6),
+        None => (base.parse().ok()?, base.parse().ok()?),
+    };
+    if start > end || end > 7 {
+        return None;
+    }
+
+    let mut days = (start..=end)
+        .step_by(step)
+        .map(|d| d % 7)
+        .collect::<Vec<_>>();
+    // `0-7` includes Sunday twice
+    days.sort_unstable();
+    days.dedup();
+    Some(
+        days.into_iter()
+            .map(|d| WEEKDAYS[d])
+            .collect::<Vec<_>>()
+            .join(","),
+    )
+}
+
+/// Converts a standard 5-fields cron expression to the format of the `cron` crate (which
+/// includes the seconds, and numbers the weekdays differently).
+pub fn parse_cron(expression: &str) -> Option<(String, Schedule)> {
+    let mut fields = expression.split_whitespace().collect::<Vec<_>>();
+    if fields.len() != 5 {
+        return None;
+    }
+    // The `cron` crate numbers the weekdays from 1 (Sunday) instead of 0
+    let weekdays = fields[4]
+        .split(',')
+        .map(weekday_names)
+        .collect::<Option<Vec<_>>>()?
+        .join(",");
+    fields[4] = &weekdays;
+    let expression = format!("0 {}", fields.join(" "));
+    let schedule = Schedule::from_str(&expression).ok()?;
+    Some((expression, schedule))
+}
+
+/// Timestamp of the next execution of the schedule, evaluated in the timezone of the chat.
+pub fn next_run(schedule: &Schedule, timezone: Tz) -> Option<i64> {
+    schedule
+        .after(&now_in(timezone))
+        .next()
+        .map(|d| d.timestamp())
+}
+
+/// Splits `/scheduleadd` arguments into the cron expression (first 5 words) and the payload.
+fn split_arguments(args: &str) -> Option<(String, &str)> {
+    let mut rest = args.trim_start();
+    let mut fields = vec![];
+    for _ in 0..5 {
+        let (field, tail) = rest.split_once(char::is_whitespace)?;
+        fields.push(field);
+        rest = tail.trim_start();
+    }
+
+    if rest.is_empty() {
+        None
+    } else {
+        Some((fields.join(" "), rest))
+    }
+}
+
+pub async fn schedule_add(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    authorizations: Arc<dyn AuthorizationRepo>,
+    timezone: Tz,
+    lang: Lang,
+) -> HandlerResult {
+    let (to_channel, args) = match args.trim_start().split_once(char::is_whitespace) {
+        Some((keyword, rest)) if CHANNEL_KEYWORDS.contains(&keyword.to_lowercase().as_str()) => {
+            (true, rest)
+        }
+        _ => (false, args.as_str()),
+    };
+    let recurrence = parse_recurrence(args).filter(|(_, payload)| !payload.is_empty());
+    let Some((expression, payload)) = recurrence.or_else(|| split_arguments(args)) else {
+        bot.send_message(
+            msg.chat.id,
+            "Utilisation: /scheduleadd [canal] <quand> <message ou /commande>, quand étant une expression cron (<minute> <heure> <jour> <mois> <jour de la semaine>) ou une récurrence comme « chaque lundi 9h », « tous les jours à 18h » ou « every weekday at 9am »\nPar exemple: /scheduleadd 0 9 * * Mon /bureau, ou /scheduleadd chaque lundi 9h /bureau\nAvec \"canal\", le message est publié dans le canal dont ce groupe est le groupe de discussion",
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    let Some((cron, schedule)) = parse_cron(&expression) else {
+        bot.send_html(
+            msg.chat.id,
+            format!("Expression cron invalide: {}", code(&expression)),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    if payload.starts_with('/') && !SCHEDULABLE_COMMANDS.contains(&payload) {
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "Seules les commandes suivantes peuvent être programmées: {}",
+                SCHEDULABLE_COMMANDS.join(", ")
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    let Some(next) = next_run(&schedule, timezone) else {
+        bot.send_message(msg.chat.id, "Cette expression ne s'exécutera jamais")
+            .queued()
+            .await?;
+        return Ok(());
+    };
+
+    let target = if to_channel {
+        let Some(channel) = linked_channel(&bot, msg.chat.id).await? else {
+            bot.send_message(
+                msg.chat.id,
+                "Ce groupe n'est le groupe de discussion d'aucun canal",
+            )
+            .queued()
+            .await?;
+            return Ok(());
+        };
+        // The answers to the polls are not visible in the channels
+        if payload == "/bureau" {
+            bot.send_message(
+                msg.chat.id,
+                "Le sondage du bureau ne peut pas être publié dans un canal",
+            )
+            .queued()
+            .await?;
+            return Ok(());
+        }
+        Some(channel.to_string())
+    } else {
+        None
+    };
+
+    // Posted in the topic of the message, or else in the one the command is bound to
+    let chat_id = msg.chat.id.to_string();
+    let thread_id = match (&target, topic(&msg), payload.strip_prefix('/')) {
+        (Some(_), _, _) => None,
+        (None, Some(thread_id), _) => Some(thread_id),
+        (None, None, Some(command)) => authorizations.command_topic(&chat_id, command).await?,
+        (None, None, None) => None,
+    };
+    let id = insert_schedule(
+        db.as_ref(),
+        &chat_id,
+        &cron,
+        payload,
+        next,
+        thread_id,
+        target.as_deref(),
+    )
+    .await?;
+    let details = format!(
+        "#{} {} {} in {}",
+        id,
+        expression,
+        payload,
+        target.as_deref().unwrap_or(&chat_id)
+    );
+    audit(db.as_ref(), &actor(&msg), "schedule_add", &details).await;
+
+    bot.send_html(
+        msg.chat.id,
+        tr!(
+            lang,
+            "Programmation #{} créée ({}), prochaine exécution {}",
+            "Schedule #{} created ({}), next run {}",
+            id,
+            code(&expression),
+            from_timestamp(next, timezone)
+                .map(|d| escape(&format_interpreted(&d, lang)))
+                .unwrap_or_default()
+        ),
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
+
+async fn insert_schedule(
+    db: &SqlitePool,
+    chat_id: &str,
+    cron: &str,
+    payload: &str,
+    next: i64,
+    thread_id: Option<i32>,
+    target: Option<&str>,
+) -> Result<i64, sqlx::Error> {
+    Ok(sqlx::query!(
+        "INSERT INTO schedules(chat_id, cron, payload, next_run, thread_id, target_chat_id)
+        VALUES($1, $2, $3, $4, $5, $6)",
+        chat_id,
+        cron,
+        payload,
+        next,
+        thread_id,
+        target
+    )
+    .execute(db)
+    .await?
+    .last_insert_rowid())
+}
+
+/// Schedules the command in the chat, in the topic it is bound to, e.g. for the presets of the
+/// `/settings` menu. Returns the id of the schedule, `None` if the expression never runs.
+pub async fn add_schedule(
+    db: &SqlitePool,
+    chat_id: ChatId,
+    expression: &str,
+    command: &str,
+) -> Result<Option<i64>, sqlx::Error> {
+    let timezone = chat_timezone(db, chat_id).await;
+    let Some((cron, next)) = parse_cron(expression)
+        .and_then(|(cron, schedule)| Some((cron, next_run(&schedule, timezone)?)))
+    else {
+        return Ok(None);
+    };
+    let thread_id = db
+        .command_topic(&chat_id.to_string(), command.trim_start_matches('/'))
+        .await?;
+    let id = insert_schedule(
+        db,
+        &chat_id.to_string(),
+        &cron,
+        command,
+        next,
+        thread_id,
+        None,
+    )
+    .await?;
+    Ok(Some(id))
+}
+
+pub async fn schedules(bot: Bot, msg: Message, db: Arc<SqlitePool>, timezone: Tz) -> HandlerResult {
+    let chat_id = msg.chat.id.to_string();
+    let schedules = sqlx::query!(
+        r#"SELECT id AS "id!", cron, payload, next_run, thread_id, target_chat_id FROM schedules
+        WHERE chat_id = $1 ORDER BY id"#,
+        chat_id
+    )
+    .fetch_all(db.as_ref())
+    .await?;
+
+    bot.send_html(
+        msg.chat.id,
+        if schedules.is_empty() {
+            "Aucune programmation dans ce groupe".to_owned()
+        } else {
+            MessageBuilder::new()
+                .title("Programmations de ce groupe")
+                .items(schedules.into_iter().map(|s| {
+                    format!(
+                        "{} {} {} (prochaine: {}{})",
+                        code(&format!("#{}", s.id)),
+                        code(s.cron.strip_prefix("0 ").unwrap_or(&s.cron)),
+                        escape(&s.payload),
+                        from_timestamp(s.next_run, timezone)
+                            .map(|d| format_datetime(&d))
+                            .unwrap_or_default(),
+                        match (s.target_chat_id, s.thread_id) {
+                            (Some(_), _) => ", dans le canal".to_owned(),
+                            (None, Some(id)) => format!(", sujet {}", id),
+                            (None, None) => String::new(),
+                        }
+                    )
+                }))
+                .build()
+        },
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
+
+pub async fn schedule_remove(
+    bot: Bot,
+    msg: Message,
+    id: String,
+    db: Arc<SqlitePool>,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.to_string();
+    let Ok(id) = id.trim().trim_start_matches('#').parse::<i64>() else {
+        bot.send_message(msg.chat.id, "Utilisation: /scheduleremove <id>")
+            .queued()
+            .await?;
+        return Ok(());
+    };
+
+    let removed = sqlx::query!(
+        "DELETE FROM schedules WHERE id = $1 AND chat_id = $2",
+        id,
+        chat_id
+    )
+    .execute(db.as_ref())
+    .await?
+    .rows_affected();
+    if removed > 0 {
+        let details = format!("#{} in {}", id, msg.chat.id);
+        audit(db.as_ref(), &actor(&msg), "schedule_remove", &details).await;
+    }
+
+    bot.send_message(
+        msg.chat.id,
+        if removed > 0 {
+            format!("Programmation #{} supprimée", id)
+        } else {
+            format!("Aucune programmation #{} dans ce groupe", id)
+        },
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
+
+/// Reschedules the jobs whose execution was missed while the bot was down, instead of running
+/// all of them at once on startup.
+pub async fn restore_schedules(db: &SqlitePool) -> Result<(), sqlx::Error> {
+    let timestamp = now().timestamp();
+    let schedules = sqlx::query!(r#"SELECT id AS "id!", chat_id, cron, next_run FROM schedules"#)
+        .fetch_all(db)
+        .await?;
+    log::info!("Restoring {} scheduled job(s)", schedules.len());
+
+    for s in schedules.into_iter().filter(|s| s.next_run <= timestamp) {
+        reschedule(db, s.id, &s.cron, schedule_timezone(db, &s.chat_id).await).await?;
+    }
+
+    Ok(())
+}
+
+/// Number of scheduled jobs, and the ids of those which can never run (e.g. an expression which
+/// no longer parses), which will be removed when restored.
+pub async fn check_schedules(db: &SqlitePool) -> Result<(usize, Vec<i64>), sqlx::Error> {
+    let schedules = sqlx::query!(r#"SELECT id AS "id!", chat_id, cron FROM schedules"#)
+        .fetch_all(db)
+        .await?;
+
+    let mut invalid = Vec::new();
+    for s in &schedules {
+        let timezone = schedule_timezone(db, &s.chat_id).await;
+        if Schedule::from_str(&s.cron)
+            .ok()
+            .and_then(|schedule| next_run(&schedule, timezone))
+            .is_none()
+        {
+            invalid.push(s.id);
+        }
+    }
+
+    Ok((schedules.len(), invalid))
+}
+
+/// Executes the scheduled jobs which are due, and computes their next execution.
+pub async fn run_due_schedules(
+    bot: &Bot,
+    outbox: &Outbox,
+    db: &SqlitePool,
+) -> Result<(), sqlx::Error> {
+    let timestamp = now().timestamp();
+    let due = sqlx::query!(
+        r#"SELECT id AS "id!", chat_id, cron, payload, thread_id, target_chat_id FROM schedules
+        WHERE next_run <= $1"#,
+        timestamp
+    )
+    .fetch_all(db)
+    .await?;
+
+    for job in due {
+        log::debug!("Running scheduled job #{}", job.id);
+        let thread_id = job.thread_id.map(|id| id as i32);
+        let target = job.target_chat_id.as_deref().unwrap_or(&job.chat_id);
+        match (job.chat_id.parse::<i64>(), target.parse::<i64>()) {
+            (Ok(chat_id), Ok(target)) => {
+                let result = post_payload(
+                    bot,
+                    outbox,
+                    db,
+                    ChatId(chat_id),
+                    ChatId(target),
+                    thread_id,
+                    &job.payload,
+                    Priority::Bulk,
+                )
+                .await;
+                if let Err(e) = result {
+                    log::error!("Could not run scheduled job #{}: {:?}", job.id, e);
+                }
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                log::error!("Invalid chat id for scheduled job #{}: {:?}", job.id, e)
+            }
+        }
+
+        reschedule(
+            db,
+            job.id,
+            &job.cron,
+            schedule_timezone(db, &job.chat_id).await,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Posts a message, or runs one of the [`SCHEDULABLE_COMMANDS`] with the content of the chat
+/// `source`, in `target` (the same chat, or its channel).
+#[allow(clippy::too_many_arguments)]
+pub async fn post_payload(
+    bot: &Bot,
+    outbox: &Outbox,
+    db: &SqlitePool,
+    source: ChatId,
+    target: ChatId,
+    thread_id: Option<i32>,
+    payload: &str,
+    priority: Priority,
+) -> HandlerResult {
+    let (text, html, keyboard) = match payload {
+        "/bureau" => return send_bureau_poll(bot, outbox, db, target, thread_id, priority).await,
+        command if PUBLICATIONS.contains(&command) => {
+            let Some((text, keyboard)) = publication(db, source, command).await? else {
+                log::debug!("Nothing to publish for {} in {}", command, target);
+                return Ok(());
+            };
+            (text, true, keyboard)
+        }
+        text => (text.to_owned(), false, None),
+    };
+
+    let mut message = if html {
+        bot.send_html(target, text)
+    } else {
+        bot.send_message(target, text)
+    };
+    if let Some(thread_id) = thread_id {
+        message = message.message_thread_id(thread_id);
+    }
+    if let Some(keyboard) = keyboard {
+        message = message.reply_markup(keyboard);
+    }
+    outbox.send(target, priority, message).await?;
+
+    Ok(())
+}
+
+/// Recomputes the next execution of the scheduled jobs of the chat, after its timezone changed.
+pub async fn reschedule_chat(db: &SqlitePool, chat_id: ChatId) -> Result<(), sqlx::Error> {
+    let timezone = chat_timezone(db, chat_id).await;
+    let chat_id = chat_id.to_string();
+    let schedules = sqlx::query!(
+        r#"SELECT id AS "id!", cron FROM schedules WHERE chat_id = $1"#,
+        chat_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    for s in schedules {
+        reschedule(db, s.id, &s.cron, timezone).await?;
+    }
+
+    Ok(())
+}
+
+/// Timezone of the chat of a job, as stored in the database.
+async fn schedule_timezone(db: &SqlitePool, chat_id: &str) -> Tz {
+    match chat_id.parse::<i64>() {
+        Ok(id) => chat_timezone(db, ChatId(id)).await,
+        Err(_) => TIMEZONE,
+    }
+}
+
+async fn reschedule(db: &SqlitePool, id: i64, cron: &str, timezone: Tz) -> Result<(), sqlx::Error> {
+    match Schedule::from_str(cron)
+        .ok()
+        .and_then(|s| next_run(&s, timezone))
+    {
+        Some(next) => {
+            sqlx::query!("UPDATE schedules SET next_run = $1 WHERE id = $2", next, id)
+                .execute(db)
+                .await?;
+        }
+        None => {
+            log::warn!("Scheduled job #{} will never run again, removing it", id);
+            sqlx::query!("DELETE FROM schedules WHERE id = $1", id)
+                .execute(db)
+                .await?;
+        }
+    }
+    Ok(())
+}
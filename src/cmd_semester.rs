@@ -0,0 +1,219 @@
+//! Countdowns to the periods of the academic calendar (end of the semester, exam session,
+//! holidays), which the admins keep up to date.
+
+use std::sync::Arc;
+
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+use sqlx::SqlitePool;
+use teloxide::{requests::Requester, types::Message, Bot};
+
+use crate::{
+    audit::{actor, audit},
+    dates::{now_in, parse_day},
+    format::{bold, code, escape, HtmlMessages, MessageBuilder},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    HandlerResult,
+};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+fn format_date(date: NaiveDate) -> String {
+    date.format("%d/%m/%Y").to_string()
+}
+
+/// `/semester` displays the days remaining until the current and coming periods.
+pub async fn semester(
+    bot: Bot,
+    msg: Message,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+    timezone: Tz,
+) -> HandlerResult {
+    let today = now_in(timezone).date_naive();
+    let from = today.format(DATE_FORMAT).to_string();
+    let periods = sqlx::query!(
+        r#"SELECT "name", starts_on, ends_on FROM academic_periods WHERE ends_on >= $1
+        ORDER BY starts_on"#,
+        from
+    )
+    .fetch_all(db.as_ref())
+    .await?;
+
+    let lines = periods
+        .into_iter()
+        .filter_map(|p| {
+            let start = NaiveDate::parse_from_str(&p.starts_on, DATE_FORMAT).ok()?;
+            let end = NaiveDate::parse_from_str(&p.ends_on, DATE_FORMAT).ok()?;
+            Some(if start <= today {
+                tr!(
+                    lang,
+                    "{}: en cours, encore {} jour(s) (jusqu'au {})",
+                    "{}: ongoing, {} day(s) left (until {})",
+                    bold(&p.name),
+                    (end - today).num_days(),
+                    format_date(end)
+                )
+            } else {
+                tr!(
+                    lang,
+                    "{}: dans {} jour(s) (du {} au {})",
+                    "{}: in {} day(s) (from {} to {})",
+                    bold(&p.name),
+                    (start - today).num_days(),
+                    format_date(start),
+                    format_date(end)
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let text = if lines.is_empty() {
+        tr!(
+            lang,
+            "Le calendrier académique est vide, les admins peuvent le compléter avec /semesteradd",
+            "The academic calendar is empty, the admins can fill it with /semesteradd"
+        )
+    } else {
+        MessageBuilder::new()
+            .title(&tr!(
+                lang,
+                "🎓 Calendrier académique",
+                "🎓 Academic calendar"
+            ))
+            .items(lines)
+            .build()
+    };
+    bot.send_html(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
+
+/// `/semesteradd <start> <end> <name>` adds a period to the academic calendar.
+pub async fn semester_add(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    timezone: Tz,
+) -> HandlerResult {
+    let today = now_in(timezone).date_naive();
+    let mut words = args.split_whitespace();
+    let (Some(start), Some(end)) = (
+        words.next().and_then(|w| parse_day(w, today)),
+        words.next().and_then(|w| parse_day(w, today)),
+    ) else {
+        bot.send_message(
+            msg.chat.id,
+            "Utilisation: /semesteradd <début> <fin> <nom>, avec des dates au format 31/12/2026",
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+    let name = words.collect::<Vec<_>>().join(" ");
+    if name.is_empty() || end < start {
+        bot.send_message(
+            msg.chat.id,
+            "La période doit avoir un nom, et finir après son début",
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    let (starts_on, ends_on) = (
+        start.format(DATE_FORMAT).to_string(),
+        end.format(DATE_FORMAT).to_string(),
+    );
+    let id = sqlx::query!(
+        r#"INSERT INTO academic_periods("name", starts_on, ends_on) VALUES($1, $2, $3)"#,
+        name,
+        starts_on,
+        ends_on
+    )
+    .execute(db.as_ref())
+    .await?
+    .last_insert_rowid();
+
+    let details = format!("#{} {} ({} - {})", id, name, starts_on, ends_on);
+    audit(db.as_ref(), &actor(&msg), "semester_add", &details).await;
+    bot.send_html(
+        msg.chat.id,
+        format!(
+            "Période #{} ajoutée: {} du {} au {}",
+            id,
+            bold(&name),
+            format_date(start),
+            format_date(end)
+        ),
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
+
+/// `/semesterremove <id>` removes a period, `/semesterremove` lists them with their ids.
+pub async fn semester_remove(
+    bot: Bot,
+    msg: Message,
+    id: String,
+    db: Arc<SqlitePool>,
+) -> HandlerResult {
+    let Ok(id) = id.trim().trim_start_matches('#').parse::<i64>() else {
+        let periods = sqlx::query!(
+            r#"SELECT id AS "id!", "name", starts_on, ends_on FROM academic_periods
+            ORDER BY starts_on"#
+        )
+        .fetch_all(db.as_ref())
+        .await?;
+        let list = periods
+            .into_iter()
+            .map(|p| {
+                format!(
+                    "{} {} ({} - {})",
+                    code(&format!("#{}", p.id)),
+                    escape(&p.name),
+                    p.starts_on,
+                    p.ends_on
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        bot.send_html(
+            msg.chat.id,
+            format!("Utilisation: /semesterremove <id>\n\n{}", list),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    let removed = sqlx::query!("DELETE FROM academic_periods WHERE id = $1", id)
+        .execute(db.as_ref())
+        .await?
+        .rows_affected();
+    if removed > 0 {
+        audit(
+            db.as_ref(),
+            &actor(&msg),
+            "semester_remove",
+            &format!("#{}", id),
+        )
+        .await;
+    }
+    bot.send_message(
+        msg.chat.id,
+        if removed > 0 {
+            format!("Période #{} supprimée", id)
+        } else {
+            format!("Aucune période #{}", id)
+        },
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,684 @@
+//! Settings of the chat, changed with the buttons of the `/settings` menu. Those read on every
+//! update (language, timezone, polls, verification) are columns of `chats`, the others are in
+//! `chat_settings`.
+
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+use teloxide::{
+    payloads::{EditMessageTextSetters, SendMessageSetters},
+    requests::Requester,
+    types::{
+        CallbackQuery, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, Message, MessageId,
+        UserId,
+    },
+    Bot,
+};
+
+use crate::{
+    callbacks::{CallbackData, CallbackResult, SETTINGS},
+    cmd_poll::{PollDialogue, PollState},
+    cmd_reactionstats::stats_committee_only,
+    cmd_schedules::{add_schedule, reschedule_chat},
+    cmd_shame::monthly_reset,
+    dates::{chat_timezone, parse_timezone},
+    db::admins::AdminRepo,
+    format::{bold, escape, HtmlMessages},
+    i18n::{chat_language, tr, Lang},
+    outbox::Queued,
+    permissions::delete_own_message,
+    verification::{
+        format_mode, next_delay as next_verification_delay, next_mode, verification_settings,
+    },
+    HandlerResult,
+};
+
+/// Auto-close delays offered by the menu (in seconds), in the order of the button cycle.
+const CLOSE_DELAYS: &[Option<i64>] = &[
+    None,
+    Some(3600),
+    Some(3 * 3600),
+    Some(12 * 3600),
+    Some(24 * 3600),
+];
+
+/// Schedules of the bureau poll offered by the menu, as `(cron expression, French, English)`, in
+/// the order of the button cycle after "never".
+const BUREAU_SCHEDULES: &[(&str, &str, &str)] = &[
+    ("0 9 * * Mon", "le lundi à 9h", "on Monday at 9am"),
+    ("0 9 * * Mon-Fri", "en semaine à 9h", "on weekdays at 9am"),
+];
+/// Schedule of the weekly digest, when enabled in the menu.
+const DIGEST_SCHEDULE: &str = "0 18 * * Sun";
+/// Timezones offered by the menu, in the order of the button cycle. The others are set with
+/// `/timezone`.
+const TIMEZONES: &[&str] = &[
+    "Europe/Zurich",
+    "Europe/London",
+    "UTC",
+    "America/New_York",
+    "Asia/Tokyo",
+];
+/// Replaced by the name of the new member in the welcome message.
+const NAME_PLACEHOLDER: &str = "{name}";
+
+/// Settings of the chat stored in `chat_settings`.
+#[derive(Default)]
+struct ChatSettings {
+    bureau_schedule_id: Option<i64>,
+    /// Cron expression of the bureau schedule, with the seconds.
+    bureau_cron: Option<String>,
+    digest_schedule_id: Option<i64>,
+    welcome_message: Option<String>,
+}
+
+async fn chat_settings(db: &SqlitePool, chat_id: ChatId) -> Result<ChatSettings, sqlx::Error> {
+    let chat_id = chat_id.to_string();
+    Ok(sqlx::query_as!(
+        ChatSettings,
+        r#"SELECT bureau_schedule_id, schedules.cron AS "bureau_cron?", digest_schedule_id,
+            welcome_message
+        FROM chat_settings LEFT JOIN schedules ON schedules.id = bureau_schedule_id
+        WHERE chat_settings.chat_id = $1"#,
+        chat_id
+    )
+    .fetch_optional(db)
+    .await?
+    .unwrap_or_default())
+}
+
+/// Message sent to the members joining the chat, with the name of the member in bold.
+pub async fn welcome_message(db: &SqlitePool, chat_id: ChatId, name: &str) -> Option<String> {
+    match chat_settings(db, chat_id).await {
+        Ok(settings) => settings
+            .welcome_message
+            .map(|m| escape(&m).replace(NAME_PLACEHOLDER, &bold(name))),
+        Err(e) => {
+            log::error!(
+                "Could not fetch the welcome message of {}: {:?}",
+                chat_id,
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Index in [`BUREAU_SCHEDULES`] of the bureau schedule of the chat.
+fn bureau_schedule(settings: &ChatSettings) -> Option<usize> {
+    let cron = settings.bureau_cron.as_deref()?;
+    BUREAU_SCHEDULES
+        .iter()
+        .position(|(expression, _, _)| cron == format!("0 {}", expression))
+}
+
+fn format_bureau_schedule(schedule: Option<usize>, lang: Lang) -> String {
+    match schedule {
+        Some(index) => {
+            let (_, fr, en) = BUREAU_SCHEDULES[index];
+            match lang {
+                Lang::Fr => fr.to_owned(),
+                Lang::En => en.to_owned(),
+            }
+        }
+        None => tr!(lang, "jamais", "never"),
+    }
+}
+
+/// Replaces the schedule created by the menu, removing it if `expression` is `None`. Returns the
+/// id of the new schedule.
+async fn replace_schedule(
+    db: &SqlitePool,
+    chat_id: ChatId,
+    previous: Option<i64>,
+    expression: Option<&str>,
+    command: &str,
+) -> Result<Option<i64>, sqlx::Error> {
+    if let Some(id) = previous {
+        sqlx::query!("DELETE FROM schedules WHERE id = $1", id)
+            .execute(db)
+            .await?;
+    }
+    match expression {
+        Some(expression) => add_schedule(db, chat_id, expression, command).await,
+        None => Ok(None),
+    }
+}
+
+/// How the bureau polls and the quizzes are sent in the chat.
+#[derive(Clone, Copy, Default)]
+pub struct PollSettings {
+    pub bureau_anonymous: bool,
+    pub bureau_multiple_answers: bool,
+    /// Delay after which the bureau polls are closed, in seconds.
+    pub bureau_close_after: Option<i64>,
+    pub quiz_anonymous: bool,
+    /// Delay after which the quizzes are closed, in seconds.
+    pub quiz_close_after: Option<i64>,
+}
+
+/// Poll settings of the chat, the defaults if it never changed them.
+pub async fn poll_settings(db: &SqlitePool, chat_id: ChatId) -> PollSettings {
+    let chat_id = chat_id.to_string();
+    let result = sqlx::query_as!(
+        PollSettings,
+        r#"SELECT bureau_anonymous AS "bureau_anonymous: bool",
+            bureau_multiple_answers AS "bureau_multiple_answers: bool",
+            bureau_close_after, quiz_anonymous AS "quiz_anonymous: bool", quiz_close_after
+        FROM chats WHERE chat_id = $1"#,
+        chat_id
+    )
+    .fetch_optional(db)
+    .await;
+
+    match result {
+        Ok(settings) => settings.unwrap_or_default(),
+        Err(e) => {
+            log::error!("Could not fetch the poll settings of {}: {:?}", chat_id, e);
+            PollSettings::default()
+        }
+    }
+}
+
+fn format_delay(delay: Option<i64>, lang: Lang) -> String {
+    match delay {
+        Some(seconds) => format!("{}h", seconds / 3600),
+        None => tr!(lang, "jamais", "never"),
+    }
+}
+
+/// Next delay of the button cycle.
+fn next_delay(delay: Option<i64>) -> Option<i64> {
+    let index = CLOSE_DELAYS.iter().position(|d| *d == delay).unwrap_or(0);
+    CLOSE_DELAYS[(index + 1) % CLOSE_DELAYS.len()]
+}
+
+fn settings_text(lang: Lang) -> String {
+    tr!(
+        lang,
+        "⚙️ Paramètres du groupe\nAppuie sur un bouton pour le modifier. Les autres fuseaux horaires se choisissent avec /timezone. Les réponses aux sondages anonymes ne comptent ni pour le hall of fame ni pour les tournois.",
+        "⚙️ Chat settings\nPress a button to change it. The other timezones are set with /timezone. The answers to anonymous polls count neither for the hall of fame nor for the tournaments."
+    )
+}
+
+async fn settings_keyboard(
+    db: &SqlitePool,
+    chat_id: ChatId,
+    lang: Lang,
+) -> Result<InlineKeyboardMarkup, sqlx::Error> {
+    let timezone = chat_timezone(db, chat_id).await;
+    let chat_settings = chat_settings(db, chat_id).await?;
+    let settings = poll_settings(db, chat_id).await;
+    let shame_monthly_reset = monthly_reset(db, &chat_id.to_string()).await;
+    let verification = verification_settings(db, chat_id).await;
+    let committee_only = stats_committee_only(db, &chat_id.to_string()).await;
+    let check = |value: bool| if value { "✅" } else { "❌" };
+    let button = |text: String, setting: &str| {
+        [InlineKeyboardButton::callback(
+            text,
+            CallbackData::format(SETTINGS, setting),
+        )]
+    };
+
+    Ok(InlineKeyboardMarkup::new([
+        button(
+            tr!(lang, "Langue: {}", "Language: {}", lang.code()),
+            "language",
+        ),
+        button(
+            tr!(lang, "Fuseau horaire: {}", "Timezone: {}", timezone.name()),
+            "timezone",
+        ),
+        button(
+            tr!(
+                lang,
+                "Sondage du bureau: {}",
+                "Bureau poll: {}",
+                format_bureau_schedule(bureau_schedule(&chat_settings), lang)
+            ),
+            "bureau_schedule",
+        ),
+        button(
+            tr!(
+                lang,
+                "Résumé de la semaine le dimanche: {}",
+                "Weekly digest on Sunday: {}",
+                check(chat_settings.digest_schedule_id.is_some())
+            ),
+            "digest",
+        ),
+        button(
+            tr!(
+                lang,
+                "Bureau anonyme: {}",
+                "Anonymous bureau: {}",
+                check(settings.bureau_anonymous)
+            ),
+            "bureau_anonymous",
+        ),
+        button(
+            tr!(
+                lang,
+                "Bureau à choix multiples: {}",
+                "Bureau with multiple answers: {}",
+                check(settings.bureau_multiple_answers)
+            ),
+            "bureau_multiple",
+        ),
+        button(
+            tr!(
+                lang,
+                "Fermeture du bureau: {}",
+                "Close the bureau after: {}",
+                format_delay(settings.bureau_close_after, lang)
+            ),
+            "bureau_close",
+        ),
+        button(
+            tr!(
+                lang,
+                "Quiz anonymes: {}",
+                "Anonymous quizzes: {}",
+                check(settings.quiz_anonymous)
+            ),
+            "quiz_anonymous",
+        ),
+        button(
+            tr!(
+                lang,
+                "Fermeture des quiz: {}",
+                "Close the quizzes after: {}",
+                format_delay(settings.quiz_close_after, lang)
+            ),
+            "quiz_close",
+        ),
+        button(
+            tr!(
+                lang,
+                "Compteurs de /shame remis à zéro chaque mois: {}",
+                "/shame counters reset every month: {}",
+                check(shame_monthly_reset)
+            ),
+            "shame_monthly",
+        ),
+        button(
+            tr!(
+                lang,
+                "Vérification des nouveaux membres: {}",
+                "Verification of the new members: {}",
+                format_mode(&verification.mode, lang)
+            ),
+            "verification_mode",
+        ),
+        button(
+            tr!(
+                lang,
+                "Délai de vérification: {} min",
+                "Verification delay: {} min",
+                verification.minutes
+            ),
+            "verification_delay",
+        ),
+        button(
+            tr!(
+                lang,
+                "Membres non vérifiés exclus (sinon muets): {}",
+                "Unverified members removed (else muted): {}",
+                check(verification.kick)
+            ),
+            "verification_kick",
+        ),
+        button(
+            tr!(
+                lang,
+                "Statistiques du comité réservées à ses membres: {}",
+                "Committee statistics restricted to its members: {}",
+                check(committee_only)
+            ),
+            "stats_committee_only",
+        ),
+        button(
+            if chat_settings.welcome_message.is_some() {
+                tr!(
+                    lang,
+                    "Message de bienvenue: ✅ (appuie pour le supprimer)",
+                    "Welcome message: ✅ (press to remove it)"
+                )
+            } else {
+                tr!(lang, "Message de bienvenue: ❌", "Welcome message: ❌")
+            },
+            "welcome",
+        ),
+    ]))
+}
+
+/// `/settings` displays the menu of the settings of the chat.
+pub async fn settings(bot: Bot, msg: Message, db: Arc<SqlitePool>, lang: Lang) -> HandlerResult {
+    bot.send_message(msg.chat.id, settings_text(lang))
+        .reply_markup(settings_keyboard(db.as_ref(), msg.chat.id, lang).await?)
+        .queued()
+        .await?;
+    Ok(())
+}
+
+/// Only the administrators of the group (and the admins of the bot) can change the settings.
+async fn can_change_settings(bot: &Bot, db: &SqlitePool, query: &CallbackQuery) -> bool {
+    let Some(message) = &query.message else {
+        return false;
+    };
+    if message.chat.is_private() || db.is_admin(query.from.id).await {
+        return true;
+    }
+    match bot.get_chat_member(message.chat.id, query.from.id).await {
+        Ok(member) => member.kind.is_privileged(),
+        Err(e) => {
+            log::warn!("Could not fetch member {}: {:?}", query.from.id, e);
+            false
+        }
+    }
+}
+
+/// Buttons of the `/settings` menu: toggles the setting, or moves to its next value.
+pub async fn change_setting(
+    bot: Bot,
+    query: CallbackQuery,
+    data: CallbackData,
+    dialogue: PollDialogue,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> CallbackResult {
+    let Some(message) = &query.message else {
+        return Ok(None);
+    };
+    if !can_change_settings(&bot, db.as_ref(), &query).await {
+        return Ok(Some(tr!(
+            lang,
+            "Seuls les administrateurs du groupe peuvent modifier les paramètres",
+            "Only the administrators of the chat can change the settings"
+        )));
+    }
+
+    let chat_id = message.chat.id.to_string();
+    let current = poll_settings(db.as_ref(), message.chat.id).await;
+    let current_chat = chat_settings(db.as_ref(), message.chat.id).await?;
+    match data.payload.as_str() {
+        "language" => {
+            let value = match lang {
+                Lang::Fr => Lang::En,
+                Lang::En => Lang::Fr,
+            }
+            .code();
+            sqlx::query!(
+                "UPDATE chats SET language = $1 WHERE chat_id = $2",
+                value,
+                chat_id
+            )
+            .execute(db.as_ref())
+            .await?;
+        }
+        "timezone" => {
+            let timezone = chat_timezone(db.as_ref(), message.chat.id).await;
+            let index = TIMEZONES
+                .iter()
+                .position(|t| parse_timezone(t) == Some(timezone))
+                .map_or(0, |i| (i + 1) % TIMEZONES.len());
+            let value = TIMEZONES[index];
+            sqlx::query!(
+                "UPDATE chats SET timezone = $1 WHERE chat_id = $2",
+                value,
+                chat_id
+            )
+            .execute(db.as_ref())
+            .await?;
+            reschedule_chat(db.as_ref(), message.chat.id).await?;
+        }
+        "bureau_schedule" => {
+            let next = match bureau_schedule(&current_chat) {
+                Some(index) => BUREAU_SCHEDULES.get(index + 1),
+                None => BUREAU_SCHEDULES.first(),
+            };
+            let value = replace_schedule(
+                db.as_ref(),
+                message.chat.id,
+                current_chat.bureau_schedule_id,
+                next.map(|(expression, _, _)| *expression),
+                "/bureau",
+            )
+            .await?;
+            sqlx::query!(
+                "INSERT INTO chat_settings(chat_id, bureau_schedule_id) VALUES($1, $2)
+                ON CONFLICT(chat_id) DO UPDATE SET bureau_schedule_id = excluded.bureau_schedule_id",
+                chat_id,
+                value
+            )
+            .execute(db.as_ref())
+            .await?;
+        }
+        "digest" => {
+            let value = replace_schedule(
+                db.as_ref(),
+                message.chat.id,
+                current_chat.digest_schedule_id,
+                current_chat
+                    .digest_schedule_id
+                    .is_none()
+                    .then_some(DIGEST_SCHEDULE),
+                "/digest",
+            )
+            .await?;
+            sqlx::query!(
+                "INSERT INTO chat_settings(chat_id, digest_schedule_id) VALUES($1, $2)
+                ON CONFLICT(chat_id) DO UPDATE SET digest_schedule_id = excluded.digest_schedule_id",
+                chat_id,
+                value
+            )
+            .execute(db.as_ref())
+            .await?;
+        }
+        "welcome" if current_chat.welcome_message.is_some() => {
+            sqlx::query!(
+                "UPDATE chat_settings SET welcome_message = NULL WHERE chat_id = $1",
+                chat_id
+            )
+            .execute(db.as_ref())
+            .await?;
+        }
+        "welcome" => {
+            if !matches!(dialogue.get().await?, None | Some(PollState::Start)) {
+                return Ok(Some(tr!(
+                    lang,
+                    "Une autre commande est en cours dans ce groupe, réessaie plus tard",
+                    "Another command is ongoing in this chat, try again later"
+                )));
+            }
+            let sent = bot
+                .send_html(
+                    message.chat.id,
+                    tr!(
+                        lang,
+                        "{}, envoie le message de bienvenue des nouveaux membres. {} y sera remplacé par leur nom.",
+                        "{}, send the welcome message of the new members. {} will be replaced by their name.",
+                        bold(&query.from.full_name()),
+                        escape(NAME_PLACEHOLDER)
+                    ),
+                )
+                .queued()
+                .await?;
+            dialogue
+                .update(PollState::WelcomeMessage {
+                    message_id: sent.id,
+                    initiator: Some(query.from.id),
+                })
+                .await?;
+            return Ok(None);
+        }
+        "bureau_anonymous" => {
+            let value = !current.bureau_anonymous;
+            sqlx::query!(
+                "UPDATE chats SET bureau_anonymous = $1 WHERE chat_id = $2",
+                value,
+                chat_id
+            )
+            .execute(db.as_ref())
+            .await?;
+        }
+        "bureau_multiple" => {
+            let value = !current.bureau_multiple_answers;
+            sqlx::query!(
+                "UPDATE chats SET bureau_multiple_answers = $1 WHERE chat_id = $2",
+                value,
+                chat_id
+            )
+            .execute(db.as_ref())
+            .await?;
+        }
+        "bureau_close" => {
+            let value = next_delay(current.bureau_close_after);
+            sqlx::query!(
+                "UPDATE chats SET bureau_close_after = $1 WHERE chat_id = $2",
+                value,
+                chat_id
+            )
+            .execute(db.as_ref())
+            .await?;
+        }
+        "quiz_anonymous" => {
+            let value = !current.quiz_anonymous;
+            sqlx::query!(
+                "UPDATE chats SET quiz_anonymous = $1 WHERE chat_id = $2",
+                value,
+                chat_id
+            )
+            .execute(db.as_ref())
+            .await?;
+        }
+        "quiz_close" => {
+            let value = next_delay(current.quiz_close_after);
+            sqlx::query!(
+                "UPDATE chats SET quiz_close_after = $1 WHERE chat_id = $2",
+                value,
+                chat_id
+            )
+            .execute(db.as_ref())
+            .await?;
+        }
+        "shame_monthly" => {
+            let value = !monthly_reset(db.as_ref(), &chat_id).await;
+            sqlx::query!(
+                "UPDATE chats SET shame_monthly_reset = $1 WHERE chat_id = $2",
+                value,
+                chat_id
+            )
+            .execute(db.as_ref())
+            .await?;
+        }
+        "verification_mode" => {
+            let value = next_mode(
+                &verification_settings(db.as_ref(), message.chat.id)
+                    .await
+                    .mode,
+            );
+            sqlx::query!(
+                "UPDATE chats SET verification_mode = $1 WHERE chat_id = $2",
+                value,
+                chat_id
+            )
+            .execute(db.as_ref())
+            .await?;
+        }
+        "verification_delay" => {
+            let value = next_verification_delay(
+                verification_settings(db.as_ref(), message.chat.id)
+                    .await
+                    .minutes,
+            );
+            sqlx::query!(
+                "UPDATE chats SET verification_minutes = $1 WHERE chat_id = $2",
+                value,
+                chat_id
+            )
+            .execute(db.as_ref())
+            .await?;
+        }
+        "verification_kick" => {
+            let value = !verification_settings(db.as_ref(), message.chat.id)
+                .await
+                .kick;
+            sqlx::query!(
+                "UPDATE chats SET verification_kick = $1 WHERE chat_id = $2",
+                value,
+                chat_id
+            )
+            .execute(db.as_ref())
+            .await?;
+        }
+        "stats_committee_only" => {
+            let value = !stats_committee_only(db.as_ref(), &chat_id).await;
+            sqlx::query!(
+                "UPDATE chats SET stats_committee_only = $1 WHERE chat_id = $2",
+                value,
+                chat_id
+            )
+            .execute(db.as_ref())
+            .await?;
+        }
+        _ => return Ok(None),
+    }
+
+    // The language may have changed
+    let lang = chat_language(db.as_ref(), message.chat.id).await;
+    bot.edit_message_text(message.chat.id, message.id, settings_text(lang))
+        .reply_markup(settings_keyboard(db.as_ref(), message.chat.id, lang).await?)
+        .queued()
+        .await?;
+
+    Ok(Some(tr!(lang, "Paramètre modifié", "Setting changed")))
+}
+
+/// Stores the welcome message sent after the button of the menu.
+pub async fn set_welcome_message(
+    bot: Bot,
+    msg: Message,
+    dialogue: PollDialogue,
+    (message_id, initiator): (MessageId, Option<UserId>),
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> HandlerResult {
+    // Only the message of the administrator who pressed the button is expected
+    if msg.from().map(|u| u.id) != initiator {
+        return Ok(());
+    }
+    let Some(text) = msg.text().map(str::trim).filter(|t| !t.is_empty()) else {
+        return Ok(());
+    };
+    dialogue.update(PollState::Start).await?;
+    delete_own_message(&bot, msg.chat.id, message_id).await;
+
+    let chat_id = msg.chat.id.to_string();
+    sqlx::query!(
+        "INSERT INTO chat_settings(chat_id, welcome_message) VALUES($1, $2)
+        ON CONFLICT(chat_id) DO UPDATE SET welcome_message = excluded.welcome_message",
+        chat_id,
+        text
+    )
+    .execute(db.as_ref())
+    .await?;
+
+    let name = msg.from().map(|u| u.full_name()).unwrap_or_default();
+    let preview = welcome_message(db.as_ref(), msg.chat.id, &name)
+        .await
+        .unwrap_or_default();
+    bot.send_html(
+        msg.chat.id,
+        tr!(
+            lang,
+            "Message de bienvenue enregistré, par exemple:\n\n{}",
+            "Welcome message saved, for example:\n\n{}",
+            preview
+        ),
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,293 @@
+//! Shames and compliments of the chat members, with their reasons. The counters can be reset
+//! every month with the `/settings` menu.
+
+use std::sync::Arc;
+
+use chrono_tz::Tz;
+use sqlx::SqlitePool;
+use teloxide::{requests::Requester, types::Message, Bot};
+
+use crate::{
+    cmd_karma::{mention_key, user_key},
+    dates::{from_timestamp, now, now_in, start_of_month},
+    format::{bold, escape, italic, HtmlMessages},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    HandlerResult,
+};
+
+/// Number of reasons listed for a member.
+const REASONS_SHOWN: i64 = 10;
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Shame,
+    Gg,
+}
+
+impl Kind {
+    fn code(self) -> &'static str {
+        match self {
+            Kind::Shame => "shame",
+            Kind::Gg => "gg",
+        }
+    }
+
+    fn emoji(self) -> &'static str {
+        match self {
+            Kind::Shame => "🍅",
+            Kind::Gg => "👏",
+        }
+    }
+}
+
+fn usage(kind: Kind, lang: Lang) -> String {
+    tr!(
+        lang,
+        "Utilisation: /{} @personne <raison> (ou en réponse à un message: /{} <raison>)",
+        "Usage: /{} @someone <reason> (or in reply to a message: /{} <reason>)",
+        kind.code(),
+        kind.code()
+    )
+}
+
+/// Whether the counters of the chat only include the current month.
+pub async fn monthly_reset(db: &SqlitePool, chat_id: &str) -> bool {
+    let result = sqlx::query!(
+        r#"SELECT shame_monthly_reset AS "monthly: bool" FROM chats WHERE chat_id = $1"#,
+        chat_id
+    )
+    .fetch_optional(db)
+    .await;
+
+    match result {
+        Ok(chat) => chat.is_some_and(|c| c.monthly),
+        Err(e) => {
+            log::error!("Could not fetch the shame settings of {}: {:?}", chat_id, e);
+            false
+        }
+    }
+}
+
+/// Start of the period of the counters.
+async fn counters_since(db: &SqlitePool, chat_id: &str, timezone: Tz) -> i64 {
+    if monthly_reset(db, chat_id).await {
+        start_of_month(&now_in(timezone)).timestamp()
+    } else {
+        0
+    }
+}
+
+async fn give(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+    timezone: Tz,
+    kind: Kind,
+) -> HandlerResult {
+    let Some(giver) = msg.from() else {
+        return Ok(());
+    };
+    let args = args.trim();
+    let replied = msg.reply_to_message().and_then(|m| m.from());
+    let (key, name, reason) = match (args.split_once(char::is_whitespace), replied) {
+        (Some((target, reason)), _) if target.starts_with('@') && target.len() > 1 => {
+            (mention_key(target), target.to_owned(), reason.trim())
+        }
+        (_, Some(user)) if !user.is_bot => (user_key(user), user.full_name(), args),
+        _ => ("".to_owned(), "".to_owned(), ""),
+    };
+    if key.is_empty() || reason.is_empty() {
+        bot.send_message(msg.chat.id, usage(kind, lang))
+            .queued()
+            .await?;
+        return Ok(());
+    }
+    if key == user_key(giver) {
+        bot.send_message(
+            msg.chat.id,
+            tr!(lang, "Pas pour soi-même", "Not for yourself"),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    let chat_id = msg.chat.id.to_string();
+    let code = kind.code();
+    let giver_name = giver.full_name();
+    let timestamp = now().timestamp();
+    sqlx::query!(
+        "INSERT INTO shames(chat_id, kind, user_key, user_name, reason, given_by, created_at)
+        VALUES($1, $2, $3, $4, $5, $6, $7)",
+        chat_id,
+        code,
+        key,
+        name,
+        reason,
+        giver_name,
+        timestamp
+    )
+    .execute(db.as_ref())
+    .await?;
+
+    let since = counters_since(db.as_ref(), &chat_id, timezone).await;
+    let count = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!: i64" FROM shames
+        WHERE chat_id = $1 AND kind = $2 AND user_key = $3 AND created_at >= $4"#,
+        chat_id,
+        code,
+        key,
+        since
+    )
+    .fetch_one(db.as_ref())
+    .await?
+    .count;
+
+    let text = match kind {
+        Kind::Shame => tr!(
+            lang,
+            "{} Honte à {} ({} au compteur): {}",
+            "{} Shame on {} ({} so far): {}",
+            kind.emoji(),
+            bold(&name),
+            count,
+            italic(reason)
+        ),
+        Kind::Gg => tr!(
+            lang,
+            "{} Bravo {} ({} au compteur): {}",
+            "{} Well done {} ({} so far): {}",
+            kind.emoji(),
+            bold(&name),
+            count,
+            italic(reason)
+        ),
+    };
+    bot.send_html(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
+
+/// `/shame @user <reason>`, or in reply to a message of the member.
+pub async fn shame(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+    timezone: Tz,
+) -> HandlerResult {
+    give(bot, msg, args, db, lang, timezone, Kind::Shame).await
+}
+
+/// `/gg @user <reason>`, or in reply to a message of the member.
+pub async fn gg(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+    timezone: Tz,
+) -> HandlerResult {
+    give(bot, msg, args, db, lang, timezone, Kind::Gg).await
+}
+
+/// `/shames` lists the counters of the chat, `/shames @user` the last reasons of the member.
+pub async fn shames(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+    timezone: Tz,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.to_string();
+    let since = counters_since(db.as_ref(), &chat_id, timezone).await;
+    let target = args.trim();
+    let key = mention_key(target);
+
+    let text = if target.is_empty() {
+        let counters = sqlx::query!(
+            r#"SELECT MAX(user_name) AS "user_name!: String",
+                SUM(kind = 'shame') AS "shames!: i64", SUM(kind = 'gg') AS "ggs!: i64"
+            FROM shames WHERE chat_id = $1 AND created_at >= $2
+            GROUP BY user_key ORDER BY 2 DESC, 3 DESC"#,
+            chat_id,
+            since
+        )
+        .fetch_all(db.as_ref())
+        .await?;
+        if counters.is_empty() {
+            tr!(lang, "Aucune honte ni aucun gg", "No shame nor gg")
+        } else {
+            format!(
+                "{}\n{}",
+                bold(&tr!(lang, "Compteurs", "Counters")),
+                counters
+                    .into_iter()
+                    .map(|c| {
+                        format!(
+                            "{}: {} {} · {} {}",
+                            escape(&c.user_name),
+                            Kind::Shame.emoji(),
+                            c.shames,
+                            Kind::Gg.emoji(),
+                            c.ggs
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            )
+        }
+    } else {
+        let reasons = sqlx::query!(
+            "SELECT kind, reason, given_by, created_at FROM shames
+            WHERE chat_id = $1 AND user_key = $2 AND created_at >= $3
+            ORDER BY created_at DESC LIMIT $4",
+            chat_id,
+            key,
+            since,
+            REASONS_SHOWN
+        )
+        .fetch_all(db.as_ref())
+        .await?;
+        if reasons.is_empty() {
+            tr!(
+                lang,
+                "Rien à reprocher à {}, ni à féliciter",
+                "Nothing to blame {} for, nor to praise",
+                escape(target)
+            )
+        } else {
+            format!(
+                "{}\n{}",
+                bold(target),
+                reasons
+                    .into_iter()
+                    .map(|r| {
+                        let emoji = match r.kind.as_str() {
+                            "gg" => Kind::Gg.emoji(),
+                            _ => Kind::Shame.emoji(),
+                        };
+                        format!(
+                            "{} {} ({}, {})",
+                            emoji,
+                            escape(&r.reason),
+                            escape(&r.given_by),
+                            from_timestamp(r.created_at, timezone)
+                                .map(|d| d.format("%d/%m").to_string())
+                                .unwrap_or_default()
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            )
+        }
+    };
+    bot.send_html(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
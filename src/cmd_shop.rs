@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+use teloxide::{requests::Requester, types::Message, Bot};
+
+use crate::{
+    audit::{actor, audit},
+    format::{bold, escape, HtmlMessages, MessageBuilder},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    shop::{refresh_shop, shop_items},
+    HandlerResult,
+};
+
+/// `/shop` lists the merch items of the association, with their price and stock.
+pub async fn shop(bot: Bot, msg: Message, lang: Lang) -> HandlerResult {
+    let items = match shop_items().await {
+        Ok(items) => items,
+        Err(e) => {
+            log::error!("Could not fetch the shop items: {e:#?}");
+            bot.send_message(
+                msg.chat.id,
+                tr!(
+                    lang,
+                    "Impossible de récupérer les articles de la boutique",
+                    "Could not fetch the items of the shop"
+                ),
+            )
+            .queued()
+            .await?;
+            return Ok(());
+        }
+    };
+    if items.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            tr!(lang, "La boutique est vide", "The shop is empty"),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    let lines = items
+        .iter()
+        .map(|item| {
+            let stock = match item.stock {
+                None => tr!(lang, "sur commande", "made to order"),
+                Some(0) => tr!(lang, "épuisé", "sold out"),
+                Some(n) => tr!(lang, "{} en stock", "{} in stock", n),
+            };
+            format!(
+                "{}: {:.2} CHF ({})",
+                bold(&item.name),
+                item.price,
+                escape(&stock)
+            )
+        })
+        .collect::<Vec<_>>();
+    let text = MessageBuilder::new()
+        .title(&tr!(lang, "👕 Boutique", "👕 Shop"))
+        .items(lines)
+        .build();
+    bot.send_html(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
+
+/// `/shopupdate` fetches the items of the shop from Directus again, after a change of the stock.
+pub async fn shop_update(bot: Bot, msg: Message, db: Arc<SqlitePool>) -> HandlerResult {
+    let text = match refresh_shop().await {
+        Ok(items) => {
+            let details = format!("{} item(s)", items.len());
+            audit(db.as_ref(), &actor(&msg), "shop_update", &details).await;
+            format!("Boutique mise à jour: {} article(s)", items.len())
+        }
+        Err(e) => {
+            log::error!("Could not fetch the shop items: {e:#?}");
+            format!("Erreur: {}", e)
+        }
+    };
+    bot.send_message(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
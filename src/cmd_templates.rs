@@ -0,0 +1,125 @@
+//! `/settemplate`: rewording of the phrases of the bot in the chat.
+
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+use teloxide::{types::Message, Bot};
+
+use crate::{
+    audit::{actor, audit},
+    format::{code, escape, italic, HtmlMessages, MessageBuilder},
+    i18n::Lang,
+    outbox::Queued,
+    templates::{find_template, template, TEMPLATES},
+    HandlerResult,
+};
+
+const USAGE: &str = "Utilisation: /settemplate <modèle> <texte>, ou /settemplate <modèle> pour revenir au texte par défaut";
+
+/// `/settemplate` lists the templates, `/settemplate <key> <text>` rewords one in the chat, and
+/// `/settemplate <key>` restores its default text.
+pub async fn set_template(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> HandlerResult {
+    let args = args.trim();
+    if args.is_empty() {
+        let mut text = MessageBuilder::new()
+            .text(USAGE)
+            .separator()
+            .title("Modèles de ce groupe");
+        for t in TEMPLATES {
+            let placeholders = t
+                .placeholders
+                .iter()
+                .map(|p| code(&format!("{{{}}}", p)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            text = text.item(format!(
+                "{} ({}{}): {}",
+                code(t.key),
+                escape(t.description),
+                if placeholders.is_empty() {
+                    String::new()
+                } else {
+                    format!(", avec {}", placeholders)
+                },
+                italic(&template(db.as_ref(), msg.chat.id, t.key, lang).await)
+            ));
+        }
+        bot.send_html(msg.chat.id, text.build()).queued().await?;
+        return Ok(());
+    }
+
+    let (key, text) = match args.split_once(char::is_whitespace) {
+        Some((key, text)) => (key, text.trim()),
+        None => (args, ""),
+    };
+    let Some(t) = find_template(key) else {
+        let keys = TEMPLATES
+            .iter()
+            .map(|t| code(t.key))
+            .collect::<Vec<_>>()
+            .join(", ");
+        bot.send_html(
+            msg.chat.id,
+            format!("Modèle inconnu: {}\nModèles: {}", escape(key), keys),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    let chat_id = msg.chat.id.to_string();
+    if text.is_empty() {
+        sqlx::query!(
+            r#"DELETE FROM templates WHERE chat_id = $1 AND "key" = $2"#,
+            chat_id,
+            t.key
+        )
+        .execute(db.as_ref())
+        .await?;
+
+        let details = format!("{} reset in {}", t.key, chat_id);
+        audit(db.as_ref(), &actor(&msg), "template_set", &details).await;
+        bot.send_html(
+            msg.chat.id,
+            format!(
+                "Le modèle {} est revenu au texte par défaut: {}",
+                code(t.key),
+                italic(t.default_text(lang))
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    if let Err(reason) = t.check(text) {
+        bot.send_html(msg.chat.id, escape(&reason)).queued().await?;
+        return Ok(());
+    }
+    sqlx::query!(
+        r#"INSERT INTO templates(chat_id, "key", "text") VALUES($1, $2, $3)
+        ON CONFLICT(chat_id, "key") DO UPDATE SET "text" = excluded."text""#,
+        chat_id,
+        t.key,
+        text
+    )
+    .execute(db.as_ref())
+    .await?;
+
+    let details = format!("{} in {}: {}", t.key, chat_id, text);
+    audit(db.as_ref(), &actor(&msg), "template_set", &details).await;
+    bot.send_html(
+        msg.chat.id,
+        format!("Modèle {} modifié: {}", code(t.key), italic(text)),
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
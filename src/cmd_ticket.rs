@@ -0,0 +1,159 @@
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+use teloxide::{
+    payloads::SendMessageSetters,
+    requests::Requester,
+    types::{ChatId, Message},
+    Bot,
+};
+
+use crate::{
+    audit::{actor, audit},
+    config::config,
+    directus::{close_ticket, create_ticket},
+    format::{bold, escape, italic, HtmlMessages},
+    i18n::{chat_language, tr, Lang},
+    outbox::Queued,
+    HandlerResult,
+};
+
+pub fn is_it_member(user_id: u64) -> bool {
+    config()
+        .it_team_ids
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .any(|id| id.trim() == user_id.to_string())
+}
+
+/// `/ticket <description>` files a support ticket for the IT team in Directus.
+pub async fn ticket(bot: Bot, msg: Message, description: String, lang: Lang) -> HandlerResult {
+    let (Some(user), description) = (msg.from(), description.trim()) else {
+        return Ok(());
+    };
+    if description.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Utilisation: /ticket <description du problème>",
+                "Usage: /ticket <description of the issue>"
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    let text = match create_ticket(description, &user.full_name(), user.id.0, msg.chat.id.0).await {
+        Ok(id) => tr!(
+            lang,
+            "Ticket {} créé, tu seras notifié·e quand il sera résolu",
+            "Ticket {} filed, you will be notified when it is solved",
+            bold(&format!("#{}", id))
+        ),
+        Err(e) => {
+            log::error!("Could not create ticket: {e:#?}");
+            tr!(
+                lang,
+                "Impossible de créer le ticket, réessaie plus tard",
+                "Could not file the ticket, try again later"
+            )
+        }
+    };
+    bot.send_html(msg.chat.id, text)
+        .reply_to_message_id(msg.id)
+        .queued()
+        .await?;
+
+    Ok(())
+}
+
+/// `/ticketclose <id> [message]` closes a ticket (IT team only), and notifies the reporter in
+/// private, or else in the chat where the ticket was filed.
+pub async fn ticket_close(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+) -> HandlerResult {
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+    if !is_it_member(user.id.0) {
+        bot.send_message(
+            msg.chat.id,
+            "Seule l'équipe informatique peut fermer les tickets",
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+    let args = args.trim();
+    let (id, comment) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    let Ok(id) = id.trim_start_matches('#').parse::<i32>() else {
+        bot.send_message(msg.chat.id, "Utilisation: /ticketclose <id> [message]")
+            .queued()
+            .await?;
+        return Ok(());
+    };
+
+    let ticket = match close_ticket(id).await {
+        Ok(ticket) => ticket,
+        Err(e) => {
+            log::error!("Could not close ticket #{}: {e:#?}", id);
+            bot.send_message(msg.chat.id, format!("Erreur: {}", e))
+                .queued()
+                .await?;
+            return Ok(());
+        }
+    };
+    audit(
+        db.as_ref(),
+        &actor(&msg),
+        "ticket_close",
+        &format!("#{}", id),
+    )
+    .await;
+
+    let recipients = [&ticket.reporter_telegram_id, &ticket.chat_id]
+        .into_iter()
+        .filter_map(|id| id.as_deref()?.parse::<i64>().ok())
+        .map(ChatId);
+    let mut notified = false;
+    for recipient in recipients {
+        let lang = chat_language(db.as_ref(), recipient).await;
+        let mut text = tr!(
+            lang,
+            "✅ Ton ticket {} a été résolu:\n{}",
+            "✅ Your ticket {} was solved:\n{}",
+            bold(&format!("#{}", ticket.id)),
+            italic(&ticket.description)
+        );
+        if !comment.trim().is_empty() {
+            text += &format!("\n\n{}", escape(comment.trim()));
+        }
+        match bot.send_html(recipient, text).queued().await {
+            Ok(_) => {
+                notified = true;
+                break;
+            }
+            // The reporter may never have started a private chat with the bot
+            Err(e) => log::warn!("Could not notify {} of ticket #{}: {:?}", recipient, id, e),
+        }
+    }
+
+    bot.send_message(
+        msg.chat.id,
+        if notified {
+            format!("Ticket #{} fermé, l'auteur a été notifié", id)
+        } else {
+            format!("Ticket #{} fermé, l'auteur n'a pas pu être notifié", id)
+        },
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+
+use chrono_tz::Tz;
+use sqlx::SqlitePool;
+use teloxide::{requests::Requester, types::Message, Bot};
+
+use crate::{
+    cmd_schedules::reschedule_chat,
+    dates::{format_datetime, now_in, parse_timezone},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    HandlerResult,
+};
+
+/// Sets the timezone in which the dates of the chat are interpreted and displayed, or displays
+/// the current one.
+pub async fn timezone(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    lang: Lang,
+    timezone: Tz,
+    db: Arc<SqlitePool>,
+) -> HandlerResult {
+    if args.trim().is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Fuseau horaire du groupe: {} ({})\nUtilisation: /timezone <fuseau>, par exemple: /timezone Europe/Zurich",
+                "Timezone of the chat: {} ({})\nUsage: /timezone <timezone>, e.g. /timezone Europe/Zurich",
+                timezone.name(),
+                format_datetime(&now_in(timezone))
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    let Some(new) = parse_timezone(&args) else {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Fuseau horaire inconnu: \"{}\", il doit être de la forme Continent/Ville, par exemple: Europe/Zurich",
+                "Unknown timezone: \"{}\", it must be of the form Continent/City, e.g. Europe/Zurich",
+                args.trim()
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    let chat_id = msg.chat.id.to_string();
+    let name = new.name();
+    sqlx::query!(
+        "UPDATE chats SET timezone = $1 WHERE chat_id = $2",
+        name,
+        chat_id
+    )
+    .execute(db.as_ref())
+    .await?;
+    reschedule_chat(db.as_ref(), msg.chat.id).await?;
+
+    bot.send_message(
+        msg.chat.id,
+        tr!(
+            lang,
+            "Fuseau horaire du groupe: {} (il est {})",
+            "Timezone of the chat: {} (it is {})",
+            name,
+            format_datetime(&now_in(new))
+        ),
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+use teloxide::{
+    payloads::{EditMessageTextSetters, SendMessageSetters},
+    requests::Requester,
+    types::{CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup, Message},
+    Bot,
+};
+
+use crate::{
+    callbacks::{CallbackData, CallbackResult, TODO_DONE},
+    format::{escape, HtmlMessages},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    HandlerResult,
+};
+
+fn usage(lang: Lang) -> String {
+    tr!(
+        lang,
+        "Utilisation: /todo add <tâche>, /todo done <id>, /todo list",
+        "Usage: /todo add <task>, /todo done <id>, /todo list"
+    )
+}
+
+pub async fn todo(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> HandlerResult {
+    let (action, item) = args
+        .trim()
+        .split_once(char::is_whitespace)
+        .map(|(a, i)| (a, i.trim()))
+        .unwrap_or((args.trim(), ""));
+    let chat_id = msg.chat.id.to_string();
+    let user = msg.from().map(|u| u.full_name()).unwrap_or_default();
+
+    match (action, item) {
+        ("add", item) if !item.is_empty() => {
+            let id = sqlx::query!(
+                r#"INSERT INTO todos(chat_id, "text", author) VALUES($1, $2, $3)"#,
+                chat_id,
+                item,
+                user
+            )
+            .execute(db.as_ref())
+            .await?
+            .last_insert_rowid();
+
+            bot.send_html(
+                msg.chat.id,
+                tr!(
+                    lang,
+                    "Tâche #{} ajoutée: {}",
+                    "Task #{} added: {}",
+                    id,
+                    escape(item)
+                ),
+            )
+            .queued()
+            .await?;
+        }
+        ("done", item) if !item.is_empty() => {
+            let Ok(id) = item.trim_start_matches('#').parse::<i64>() else {
+                bot.send_message(msg.chat.id, usage(lang)).queued().await?;
+                return Ok(());
+            };
+
+            let text = if mark_done(db.as_ref(), &chat_id, id, &user).await? {
+                tr!(lang, "Tâche #{} terminée", "Task #{} done", id)
+            } else {
+                tr!(
+                    lang,
+                    "Aucune tâche #{} en cours dans ce groupe",
+                    "No pending task #{} in this chat",
+                    id
+                )
+            };
+            bot.send_message(msg.chat.id, text).queued().await?;
+        }
+        ("list" | "", _) => {
+            let (text, keyboard) = list_todos(db.as_ref(), &chat_id, lang).await?;
+            bot.send_html(msg.chat.id, text)
+                .reply_markup(keyboard)
+                .queued()
+                .await?;
+        }
+        _ => {
+            bot.send_message(msg.chat.id, usage(lang)).queued().await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Handles the check buttons of the /todo list.
+pub async fn todo_done(
+    bot: Bot,
+    query: CallbackQuery,
+    data: CallbackData,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> CallbackResult {
+    let (Some(msg), Some(id)) = (query.message.as_ref(), data.id()) else {
+        return Ok(None);
+    };
+
+    let chat_id = msg.chat.id.to_string();
+    mark_done(db.as_ref(), &chat_id, id, &query.from.full_name()).await?;
+
+    let (text, keyboard) = list_todos(db.as_ref(), &chat_id, lang).await?;
+    bot.edit_html(msg.chat.id, msg.id, text)
+        .reply_markup(keyboard)
+        .queued()
+        .await?;
+
+    Ok(Some(tr!(lang, "Tâche #{} terminée", "Task #{} done", id)))
+}
+
+/// Marks the item as done. Returns whether a pending item of the chat was found.
+async fn mark_done(
+    db: &SqlitePool,
+    chat_id: &str,
+    id: i64,
+    user: &str,
+) -> Result<bool, sqlx::Error> {
+    Ok(sqlx::query!(
+        "UPDATE todos SET done = TRUE, done_by = $1 WHERE id = $2 AND chat_id = $3 AND NOT done",
+        user,
+        id,
+        chat_id
+    )
+    .execute(db)
+    .await?
+    .rows_affected()
+        > 0)
+}
+
+/// Builds the to-do list of the chat, with a check button for each pending item.
+async fn list_todos(
+    db: &SqlitePool,
+    chat_id: &str,
+    lang: Lang,
+) -> Result<(String, InlineKeyboardMarkup), sqlx::Error> {
+    let todos = sqlx::query!(
+        r#"SELECT id AS "id!", "text", author, done, done_by FROM todos WHERE chat_id = $1 ORDER BY done, id"#,
+        chat_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    if todos.is_empty() {
+        return Ok((
+            tr!(
+                lang,
+                "La liste de tâches est vide",
+                "The to-do list is empty"
+            ),
+            InlineKeyboardMarkup::default(),
+        ));
+    }
+
+    let text = format!(
+        "{}\n{}",
+        tr!(lang, "Tâches:", "Tasks:"),
+        todos
+            .iter()
+            .map(|t| {
+                if t.done {
+                    tr!(
+                        lang,
+                        "☑ #{} {} (fait par {})",
+                        "☑ #{} {} (done by {})",
+                        t.id,
+                        escape(&t.text),
+                        escape(t.done_by.as_deref().unwrap_or("?"))
+                    )
+                } else {
+                    tr!(
+                        lang,
+                        "☐ #{} {} (ajouté par {})",
+                        "☐ #{} {} (added by {})",
+                        t.id,
+                        escape(&t.text),
+                        escape(&t.author)
+                    )
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+
+    let keyboard = InlineKeyboardMarkup::new(todos.iter().filter(|t| !t.done).map(|t| {
+        vec![InlineKeyboardButton::callback(
+            format!("✅ #{} {}", t.id, t.text),
+            CallbackData::format(TODO_DONE, t.id),
+        )]
+    }));
+
+    Ok((text, keyboard))
+}
@@ -0,0 +1,388 @@
+//! Quiz tournaments, over several weeks: every quiz sent during the tournament scores points for
+//! the members guessing the author, with a bonus for the first one.
+
+use std::{collections::HashMap, sync::Arc};
+
+use chrono::Duration;
+use chrono_tz::Tz;
+use sqlx::SqlitePool;
+use teloxide::{
+    requests::Requester,
+    types::{ChatId, Message},
+    Bot,
+};
+
+use crate::{
+    audit::{actor, audit},
+    cmd_halloffame::MEDALS,
+    dates::{chat_timezone, format_datetime, from_timestamp, now},
+    format::{bold, escape, HtmlMessages},
+    i18n::{chat_language, tr, Lang},
+    outbox::{Outbox, Priority, Queued},
+    HandlerResult,
+};
+
+/// Points of a correct guess.
+const CORRECT_POINTS: i64 = 1;
+/// Additional points of the first correct guess of each quiz.
+const FIRST_BONUS: i64 = 1;
+const MAX_WEEKS: i64 = 52;
+
+struct Tournament {
+    id: i64,
+    name: String,
+    started_at: i64,
+    ends_at: i64,
+}
+
+struct Standing {
+    name: String,
+    points: i64,
+    correct: i64,
+}
+
+/// Open tournament of the chat, or else the last closed one when `include_closed` is set.
+async fn current_tournament(
+    db: &SqlitePool,
+    chat_id: &str,
+    include_closed: bool,
+) -> Result<Option<Tournament>, sqlx::Error> {
+    sqlx::query_as!(
+        Tournament,
+        r#"SELECT id AS "id!", "name", started_at, ends_at FROM tournaments
+        WHERE chat_id = $1 AND (closed_at IS NULL OR $2)
+        ORDER BY closed_at IS NULL DESC, id DESC LIMIT 1"#,
+        chat_id,
+        include_closed
+    )
+    .fetch_optional(db)
+    .await
+}
+
+/// Standings of the tournament, from the answers to its quizzes. Also returns the number of
+/// quizzes.
+async fn standings(
+    db: &SqlitePool,
+    chat_id: &str,
+    tournament: &Tournament,
+) -> Result<(Vec<Standing>, i64), sqlx::Error> {
+    let answers = sqlx::query!(
+        r#"SELECT q.poll_id AS "poll_id!", a.user_id, a.user_name,
+            a.option = q.correct_option AS "correct!: bool"
+        FROM poll_answers a JOIN quotes q ON q.poll_id = a.poll_id
+        WHERE q.chat_id = $1 AND q.created_at >= $2 AND q.created_at < $3
+        ORDER BY a.answered_at"#,
+        chat_id,
+        tournament.started_at,
+        tournament.ends_at
+    )
+    .fetch_all(db)
+    .await?;
+
+    let quizzes = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!: i64" FROM quotes
+        WHERE chat_id = $1 AND poll_id IS NOT NULL AND created_at >= $2 AND created_at < $3"#,
+        chat_id,
+        tournament.started_at,
+        tournament.ends_at
+    )
+    .fetch_one(db)
+    .await?
+    .count;
+
+    let mut scores = HashMap::<String, Standing>::new();
+    // Whether each quiz was already guessed, for the bonus of the first correct answer
+    let mut guessed = HashMap::<String, bool>::new();
+    for answer in answers {
+        let already_guessed = guessed.entry(answer.poll_id).or_default();
+        let first = answer.correct && !*already_guessed;
+        *already_guessed |= answer.correct;
+        let standing = scores.entry(answer.user_id).or_insert(Standing {
+            name: String::new(),
+            points: 0,
+            correct: 0,
+        });
+        standing.name = answer.user_name;
+        if answer.correct {
+            standing.correct += 1;
+            standing.points += CORRECT_POINTS + if first { FIRST_BONUS } else { 0 };
+        }
+    }
+
+    let mut standings = scores.into_values().collect::<Vec<_>>();
+    standings.sort_by(|a, b| {
+        b.points
+            .cmp(&a.points)
+            .then(b.correct.cmp(&a.correct))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    Ok((standings, quizzes))
+}
+
+fn format_standings(standings: &[Standing], lang: Lang) -> String {
+    if standings.is_empty() {
+        return tr!(
+            lang,
+            "Personne n'a encore marqué de points",
+            "Nobody scored yet"
+        );
+    }
+
+    standings
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            format!(
+                "{} {} ({} pts, {} {})",
+                MEDALS
+                    .get(i)
+                    .map(|m| m.to_string())
+                    .unwrap_or_else(|| format!("{}.", i + 1)),
+                escape(&s.name),
+                s.points,
+                s.correct,
+                tr!(lang, "bonne(s) réponse(s)", "correct answer(s)")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `/tournament` displays the standings of the current tournament, or of the last one.
+pub async fn tournament(
+    bot: Bot,
+    msg: Message,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+    timezone: Tz,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.to_string();
+    let Some(tournament) = current_tournament(db.as_ref(), &chat_id, true).await? else {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Aucun tournoi dans ce groupe",
+                "No tournament in this chat"
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    let (standings, quizzes) = standings(db.as_ref(), &chat_id, &tournament).await?;
+    let end = from_timestamp(tournament.ends_at, timezone)
+        .map(|d| format_datetime(&d))
+        .unwrap_or_default();
+    bot.send_html(
+        msg.chat.id,
+        format!(
+            "🏆 {}\n{}\n\n{}",
+            bold(&tournament.name),
+            tr!(
+                lang,
+                "{} quiz, jusqu'au {}",
+                "{} quizzes, until {}",
+                quizzes,
+                end
+            ),
+            format_standings(&standings, lang)
+        ),
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
+
+/// `/tournamentstart <weeks> [name]` opens a tournament in the chat.
+pub async fn tournament_start(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    timezone: Tz,
+) -> HandlerResult {
+    let (weeks, name) = match args.trim().split_once(char::is_whitespace) {
+        Some((weeks, name)) => (weeks, name.trim()),
+        None => (args.trim(), ""),
+    };
+    let Some(weeks) = weeks
+        .parse::<i64>()
+        .ok()
+        .filter(|w| (1..=MAX_WEEKS).contains(w))
+    else {
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "Utilisation: /tournamentstart <nombre de semaines, au plus {}> [nom]",
+                MAX_WEEKS
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    let chat_id = msg.chat.id.to_string();
+    if let Some(open) = current_tournament(db.as_ref(), &chat_id, false).await? {
+        bot.send_html(
+            msg.chat.id,
+            format!(
+                "Le tournoi {} est déjà en cours, voir /tournamentstop",
+                bold(&open.name)
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    let started_at = now();
+    let ends_at = started_at + Duration::weeks(weeks);
+    let name = if name.is_empty() {
+        format!("Tournoi du {}", started_at.format("%d/%m/%Y"))
+    } else {
+        name.to_owned()
+    };
+    let (start, end) = (started_at.timestamp(), ends_at.timestamp());
+    sqlx::query!(
+        r#"INSERT INTO tournaments(chat_id, "name", started_at, ends_at) VALUES($1, $2, $3, $4)"#,
+        chat_id,
+        name,
+        start,
+        end
+    )
+    .execute(db.as_ref())
+    .await?;
+    let details = format!("{} ({} weeks) in {}", name, weeks, msg.chat.id);
+    audit(db.as_ref(), &actor(&msg), "tournament_start", &details).await;
+
+    bot.send_html(
+        msg.chat.id,
+        format!(
+            "🏆 Le tournoi {} commence ! Chaque bonne réponse aux quiz rapporte {} point, et {} de plus pour la première. Fin le {}, classement avec /tournament",
+            bold(&name),
+            CORRECT_POINTS,
+            FIRST_BONUS,
+            format_datetime(&ends_at.with_timezone(&timezone))
+        ),
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
+
+/// `/tournamentstop` closes the open tournament before its end.
+pub async fn tournament_stop(bot: Bot, msg: Message, db: Arc<SqlitePool>) -> HandlerResult {
+    let chat_id = msg.chat.id.to_string();
+    let Some(tournament) = current_tournament(db.as_ref(), &chat_id, false).await? else {
+        bot.send_message(msg.chat.id, "Aucun tournoi en cours dans ce groupe")
+            .queued()
+            .await?;
+        return Ok(());
+    };
+
+    let timestamp = now().timestamp();
+    sqlx::query!(
+        "UPDATE tournaments SET ends_at = MIN(ends_at, $1) WHERE id = $2",
+        timestamp,
+        tournament.id
+    )
+    .execute(db.as_ref())
+    .await?;
+    let details = format!("{} in {}", tournament.name, msg.chat.id);
+    audit(db.as_ref(), &actor(&msg), "tournament_stop", &details).await;
+
+    // The recap is sent by the scheduler, as for the tournaments reaching their end
+    bot.send_message(
+        msg.chat.id,
+        "Tournoi arrêté, le récapitulatif va être envoyé",
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
+
+/// Crowns the winners of the tournaments which reached their end, with a recap message.
+pub async fn close_due_tournaments(
+    bot: &Bot,
+    outbox: &Outbox,
+    db: &SqlitePool,
+) -> Result<(), sqlx::Error> {
+    let timestamp = now().timestamp();
+    let due = sqlx::query!(
+        r#"SELECT id AS "id!", chat_id, "name", started_at, ends_at FROM tournaments
+        WHERE closed_at IS NULL AND ends_at <= $1"#,
+        timestamp
+    )
+    .fetch_all(db)
+    .await?;
+
+    for t in due {
+        let tournament = Tournament {
+            id: t.id,
+            name: t.name,
+            started_at: t.started_at,
+            ends_at: t.ends_at,
+        };
+        if let Ok(chat_id) = t.chat_id.parse::<i64>().map(ChatId) {
+            let lang = chat_language(db, chat_id).await;
+            let timezone = chat_timezone(db, chat_id).await;
+            let (standings, quizzes) = standings(db, &t.chat_id, &tournament).await?;
+            let winner = match standings.first() {
+                Some(winner) => tr!(
+                    lang,
+                    "👑 {} remporte le tournoi avec {} points !",
+                    "👑 {} wins the tournament with {} points!",
+                    bold(&winner.name),
+                    winner.points
+                ),
+                None => tr!(
+                    lang,
+                    "Personne n'a marqué de points, pas de vainqueur cette fois",
+                    "Nobody scored, no winner this time"
+                ),
+            };
+            let text = format!(
+                "🏆 {}\n{}\n\n{}\n\n{}",
+                tr!(
+                    lang,
+                    "Fin du tournoi {}",
+                    "End of the tournament {}",
+                    bold(&tournament.name)
+                ),
+                tr!(
+                    lang,
+                    "{} quiz depuis le {}",
+                    "{} quizzes since {}",
+                    quizzes,
+                    from_timestamp(tournament.started_at, timezone)
+                        .map(|d| format_datetime(&d))
+                        .unwrap_or_default()
+                ),
+                winner,
+                format_standings(&standings, lang)
+            );
+            if let Err(e) = outbox
+                .send(chat_id, Priority::Bulk, bot.send_html(chat_id, text))
+                .await
+            {
+                log::error!("Could not send recap of tournament #{}: {:?}", t.id, e);
+            }
+        }
+
+        sqlx::query!(
+            "UPDATE tournaments SET closed_at = $1 WHERE id = $2",
+            timestamp,
+            t.id
+        )
+        .execute(db)
+        .await?;
+    }
+
+    Ok(())
+}
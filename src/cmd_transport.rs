@@ -0,0 +1,177 @@
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+use teloxide::{requests::Requester, types::Message, Bot};
+
+use crate::{
+    dates::TIMEZONE,
+    format::{bold, escape, HtmlMessages, MessageBuilder},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    transport::get_departures,
+    HandlerResult,
+};
+
+/// Stop used when the chat has no default stop.
+const DEFAULT_STOP: &str = "EPFL";
+/// First argument of `/transport` setting the default stop.
+const DEFAULT_KEYWORDS: &[&str] = &["default", "défaut", "defaut"];
+
+async fn default_stop(db: &SqlitePool, chat_id: &str) -> Result<String, sqlx::Error> {
+    Ok(sqlx::query!(
+        "SELECT stop FROM transport_stops WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_optional(db)
+    .await?
+    .map(|r| r.stop)
+    .unwrap_or(DEFAULT_STOP.to_owned()))
+}
+
+/// Shows the next departures from the default stop of the chat.
+pub async fn metro(bot: Bot, msg: Message, db: Arc<SqlitePool>, lang: Lang) -> HandlerResult {
+    let stop = default_stop(db.as_ref(), &msg.chat.id.to_string()).await?;
+    send_departures(&bot, &msg, &stop, lang).await
+}
+
+/// `/transport <stop>` shows the next departures from the stop, `/transport default <stop>` sets
+/// the default stop of the chat.
+pub async fn transport(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> HandlerResult {
+    let args = args.trim();
+    let (keyword, stop) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    if !DEFAULT_KEYWORDS.contains(&keyword.to_lowercase().as_str()) {
+        return if args.is_empty() {
+            metro(bot, msg, db, lang).await
+        } else {
+            send_departures(&bot, &msg, args, lang).await
+        };
+    }
+
+    let chat_id = msg.chat.id.to_string();
+    let stop = stop.trim();
+    if stop.is_empty() {
+        let current = default_stop(db.as_ref(), &chat_id).await?;
+        bot.send_html(
+            msg.chat.id,
+            tr!(
+                lang,
+                "L'arrêt par défaut de /metro est {}. Pour le changer: /transport default &lt;arrêt&gt;",
+                "The default stop of /metro is {}. To change it: /transport default &lt;stop&gt;",
+                bold(&current)
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    // Stored as resolved by the API, so that a typo is noticed now rather than by /metro
+    let name = match get_departures(stop).await {
+        Ok((Some(name), _)) => name,
+        Ok((None, _)) => {
+            bot.send_html(
+                msg.chat.id,
+                tr!(lang, "Arrêt inconnu: {}", "Unknown stop: {}", escape(stop)),
+            )
+            .queued()
+            .await?;
+            return Ok(());
+        }
+        Err(e) => {
+            log::error!("Could not fetch departures: {e:#?}");
+            bot.send_message(
+                msg.chat.id,
+                tr!(
+                    lang,
+                    "Impossible de vérifier l'arrêt",
+                    "Could not check the stop"
+                ),
+            )
+            .queued()
+            .await?;
+            return Ok(());
+        }
+    };
+    sqlx::query!(
+        "INSERT INTO transport_stops(chat_id, stop) VALUES($1, $2) ON CONFLICT(chat_id) DO UPDATE SET stop = excluded.stop",
+        chat_id,
+        name
+    )
+    .execute(db.as_ref())
+    .await?;
+    bot.send_html(
+        msg.chat.id,
+        tr!(
+            lang,
+            "L'arrêt par défaut de /metro est désormais {}",
+            "The default stop of /metro is now {}",
+            bold(&name)
+        ),
+    )
+    .queued()
+    .await?;
+    Ok(())
+}
+
+async fn send_departures(bot: &Bot, msg: &Message, stop: &str, lang: Lang) -> HandlerResult {
+    let (name, departures) = match get_departures(stop).await {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("Could not fetch departures: {e:#?}");
+            bot.send_message(
+                msg.chat.id,
+                tr!(
+                    lang,
+                    "Impossible de récupérer les horaires",
+                    "Could not fetch the timetable"
+                ),
+            )
+            .queued()
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let text = if departures.is_empty() {
+        tr!(
+            lang,
+            "Aucun départ trouvé pour l'arrêt \"{}\"",
+            "No departure found from the stop \"{}\"",
+            escape(stop)
+        )
+    } else {
+        MessageBuilder::new()
+            .title(&tr!(
+                lang,
+                "🚇 Prochains départs de {}",
+                "🚇 Next departures from {}",
+                name.as_deref().unwrap_or(stop)
+            ))
+            .items(departures.iter().map(|d| {
+                format!(
+                    "{} {}{} → {}{}",
+                    d.departure_time()
+                        .map(|t| t.with_timezone(&TIMEZONE).format("%H:%M").to_string())
+                        .unwrap_or("?".into()),
+                    escape(&d.category),
+                    escape(d.number.as_deref().unwrap_or_default()),
+                    escape(&d.to),
+                    match d.stop.delay {
+                        Some(delay) if delay > 0 => format!(" (+{} min)", delay),
+                        _ => String::new(),
+                    }
+                )
+            }))
+            .build()
+    };
+
+    bot.send_html(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
@@ -0,0 +1,207 @@
+//! `/undo`: reverts the last reversible action of the admin, within a few minutes. The inverse of
+//! each action is recorded with it in the audit log, so that the log stays the source of truth and
+//! the undo survives a restart of the bot.
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use sqlx::{SqliteConnection, SqlitePool};
+use teloxide::{requests::Requester, types::Message, Bot};
+
+use crate::{
+    aliases::{alias_command, set_alias},
+    audit::{actor, audit},
+    dates::now,
+    db::{
+        authorizations::{self, authorization_cache},
+        committee,
+    },
+    format::{bold, escape, HtmlMessages},
+    outbox::Queued,
+    HandlerResult,
+};
+
+/// Time during which an action can be undone, in seconds.
+const UNDO_WINDOW: i64 = 15 * 60;
+
+/// Inverse of a reversible action, stored as JSON in the `undo` column of the audit log.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Undo {
+    /// Forbids again a command allowed by `/authorize`.
+    Revoke { chat_id: String, command: String },
+    /// Allows again a command forbidden by `/unauthorize`.
+    Grant { chat_id: String, command: String },
+    /// Sets the alias back to its `previous` command, or removes it, if it still points to
+    /// `command`.
+    Alias {
+        chat_id: String,
+        alias: String,
+        command: Option<String>,
+        previous: Option<String>,
+    },
+    /// Removes the link created by `/memberlink`, and restores the links it replaced.
+    MemberLink {
+        name: String,
+        telegram_id: String,
+        previous: Vec<(String, String)>,
+    },
+}
+
+impl Undo {
+    /// Applies the inverse action, as part of the transaction of `conn`. Returns the HTML
+    /// description of the change, or `None` if the state changed since the action, in which case
+    /// nothing is done.
+    async fn apply(&self, conn: &mut SqliteConnection) -> Result<Option<String>, sqlx::Error> {
+        Ok(Some(match self {
+            Self::Revoke { chat_id, command } => {
+                // Only if the command is still allowed
+                if !authorizations::remove_authorization(conn, chat_id, command).await? {
+                    return Ok(None);
+                }
+                format!(
+                    "La commande /{} n'est plus autorisée dans le groupe {}",
+                    escape(command),
+                    escape(chat_id)
+                )
+            }
+            Self::Grant { chat_id, command } => {
+                // Only if the command is still forbidden
+                if !authorizations::restore_authorization(conn, chat_id, command).await? {
+                    return Ok(None);
+                }
+                format!(
+                    "La commande /{} est de nouveau autorisée dans le groupe {}",
+                    escape(command),
+                    escape(chat_id)
+                )
+            }
+            Self::Alias {
+                chat_id,
+                alias,
+                command,
+                previous,
+            } => {
+                if alias_command(&mut *conn, chat_id, alias).await? != *command {
+                    return Ok(None);
+                }
+                set_alias(&mut *conn, chat_id, alias, previous.as_deref()).await?;
+                match previous {
+                    Some(previous) => format!(
+                        "/{} est de nouveau un raccourci pour /{}",
+                        escape(alias),
+                        escape(previous)
+                    ),
+                    None => format!("L'alias /{} est supprimé", escape(alias)),
+                }
+            }
+            Self::MemberLink {
+                name,
+                telegram_id,
+                previous,
+            } => {
+                let links = committee::links_of(&mut *conn, name, telegram_id).await?;
+                if links != [(name.clone(), telegram_id.clone())] {
+                    return Ok(None);
+                }
+                committee::restore_links(conn, name, telegram_id, previous).await?;
+                format!("{} n'est plus lié à ce compte Telegram", bold(name))
+            }
+        }))
+    }
+}
+
+/// `/undo` reverts the last action of the admin which can be undone: `/authorize`,
+/// `/unauthorize`, `/aliasadd`, `/aliasremove` and `/memberlink`. Repeated, it goes back through
+/// the previous ones. An action which cannot be reverted anymore is skipped, so that the next
+/// `/undo` reaches the one before.
+pub async fn undo(bot: Bot, msg: Message, db: Arc<SqlitePool>) -> HandlerResult {
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+    let actor_id = user.id.to_string();
+    let since = now().timestamp() - UNDO_WINDOW;
+    let last = sqlx::query!(
+        r#"SELECT id AS "id!", "action", details, undo AS "undo!" FROM audit_log
+        WHERE actor_id = $1 AND undo IS NOT NULL AND NOT undone AND created_at >= $2
+        ORDER BY id DESC LIMIT 1"#,
+        actor_id,
+        since
+    )
+    .fetch_optional(db.as_ref())
+    .await?;
+    let Some(last) = last else {
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "Aucune action à annuler dans les {} dernières minutes",
+                UNDO_WINDOW / 60
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+    let description = format!("{} ({})", escape(&last.action), escape(&last.details));
+
+    // Marked in the same transaction, so that two /undo at the same time do not revert the action
+    // twice
+    let mut tx = db.begin().await?;
+    let marked = sqlx::query!(
+        "UPDATE audit_log SET undone = TRUE WHERE id = $1 AND NOT undone",
+        last.id
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if marked == 0 {
+        bot.send_html(
+            msg.chat.id,
+            format!("L'action {} vient déjà d'être annulée", description),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+    let applied = match serde_json::from_str::<Undo>(&last.undo) {
+        Ok(undo) => {
+            let applied = undo.apply(&mut tx).await?;
+            if let (Some(_), Undo::Revoke { chat_id, .. } | Undo::Grant { chat_id, .. }) =
+                (&applied, &undo)
+            {
+                authorization_cache().invalidate(chat_id).await;
+            }
+            applied
+        }
+        Err(e) => {
+            log::error!("Invalid undo of the audit entry {}: {:?}", last.id, e);
+            None
+        }
+    };
+    // An action which cannot be reverted is still marked, and skipped by the next /undo
+    if let Some(text) = &applied {
+        tx.commit().await?;
+        let details = format!("#{} {}: {}", last.id, last.action, last.details);
+        audit(db.as_ref(), &actor(&msg), "undo", &details).await;
+        bot.send_html(msg.chat.id, format!("↩️ Annulé: {}", text))
+            .queued()
+            .await?;
+    } else {
+        tx.rollback().await?;
+        sqlx::query!("UPDATE audit_log SET undone = TRUE WHERE id = $1", last.id)
+            .execute(db.as_ref())
+            .await?;
+        bot.send_html(
+            msg.chat.id,
+            format!(
+                "L'action {} ne peut plus être annulée, elle a été modifiée depuis. Elle est \
+                ignorée: /undo annule désormais l'action précédente.",
+                description
+            ),
+        )
+        .queued()
+        .await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+use teloxide::{
+    requests::Requester,
+    types::{ChatId, Message, MessageId},
+    Bot,
+};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::{
+    cmd_reactionstats::record_reaction,
+    format::{bold, HtmlMessages},
+    i18n::{chat_language, tr, Lang},
+    outbox::Queued,
+    reactions::MessageReactionUpdated,
+    HandlerResult,
+};
+
+const UP: &str = "👍";
+const DOWN: &str = "👎";
+
+fn vote_text(lang: Lang, question: &str, up: i64, down: i64) -> String {
+    tr!(
+        lang,
+        "{}\n\nRéagis avec {} ou {} pour voter\n{} {} · {} {}",
+        "{}\n\nReact with {} or {} to vote\n{} {} · {} {}",
+        bold(question),
+        UP,
+        DOWN,
+        UP,
+        up,
+        DOWN,
+        down
+    )
+}
+
+/// `/vote <question>` posts a question voted on by reacting to it, lighter than a poll.
+pub async fn vote(
+    bot: Bot,
+    msg: Message,
+    question: String,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> HandlerResult {
+    let question = question.trim();
+    if question.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Utilisation: /vote <question>",
+                "Usage: /vote <question>"
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    let sent = bot
+        .send_html(msg.chat.id, vote_text(lang, question, 0, 0))
+        .queued()
+        .await?;
+    let chat_id = msg.chat.id.to_string();
+    sqlx::query!(
+        "INSERT INTO reaction_votes(chat_id, message_id, question) VALUES($1, $2, $3)",
+        chat_id,
+        sent.id.0,
+        question
+    )
+    .execute(db.as_ref())
+    .await?;
+
+    Ok(())
+}
+
+/// Updates the tally of a vote after a change of reactions.
+async fn count_reaction(
+    bot: &Bot,
+    db: &SqlitePool,
+    reaction: MessageReactionUpdated,
+) -> HandlerResult {
+    let (up, down) = (reaction.delta(UP), reaction.delta(DOWN));
+    if up == 0 && down == 0 {
+        return Ok(());
+    }
+
+    let chat_id = reaction.chat.id.to_string();
+    let Some(vote) = sqlx::query!(
+        "UPDATE reaction_votes SET up = MAX(up + $1, 0), down = MAX(down + $2, 0)
+        WHERE chat_id = $3 AND message_id = $4 RETURNING question, up, down",
+        up,
+        down,
+        chat_id,
+        reaction.message_id
+    )
+    .fetch_optional(db)
+    .await?
+    else {
+        return Ok(());
+    };
+
+    let chat_id = ChatId(reaction.chat.id);
+    let lang = chat_language(db, chat_id).await;
+    bot.edit_html(
+        chat_id,
+        MessageId(reaction.message_id),
+        vote_text(lang, &vote.question, vote.up, vote.down),
+    )
+    .queued()
+    .await?;
+
+    Ok(())
+}
+
+/// Spawns the task keeping the tallies of the votes and the reaction statistics up to date, from
+/// the reactions forwarded by the update listener.
+pub fn start_reaction_votes(
+    bot: Bot,
+    db: Arc<SqlitePool>,
+    mut reactions: UnboundedReceiver<MessageReactionUpdated>,
+) {
+    tokio::spawn(async move {
+        while let Some(reaction) = reactions.recv().await {
+            if let Err(e) = record_reaction(db.as_ref(), &reaction).await {
+                log::error!("Could not record reaction: {:?}", e);
+            }
+            if let Err(e) = count_reaction(&bot, db.as_ref(), reaction).await {
+                log::error!("Could not count reaction: {:?}", e);
+            }
+        }
+    });
+}
@@ -0,0 +1,402 @@
+//! Quizzes made from the messages forwarded to the bot in private:
+//! - quote quizzes, for the committee members linked with `/memberlink`, when the author of the
+//!   message is a committee member, sent in the groups using `/poll` where the forwarder writes,
+//! - "who wrote this?" quizzes, for the admins, with the author among the members of the group.
+
+use sqlx::SqlitePool;
+use teloxide::{
+    payloads::{SendMessageSetters, SendPollSetters},
+    requests::Requester,
+    types::{
+        ChatId, ForwardedFrom, InlineKeyboardButton, InlineKeyboardMarkup, Message, MessageId,
+        PollType, UserId,
+    },
+    Bot,
+};
+
+use crate::{
+    callbacks::{CallbackData, CallbackResult, FORWARD_QUIZ},
+    cmd_poll::{quote_question, send_quote_poll, PollDialogue, PollState, QuoteLayout},
+    cmd_settings::poll_settings,
+    committee::committee_repository,
+    dates::now,
+    db::quotes::QuoteRepo,
+    i18n::{chat_language, tr, Lang},
+    outbox::Queued,
+    poll_options::{quiz_options, truncate, POLL_MAX_QUESTION_LENGTH},
+    poll_results::track_poll,
+    state::AppState,
+    HandlerResult,
+};
+
+/// Number of recent members among which the wrong answers are picked.
+const CANDIDATES: i64 = 30;
+
+fn author_question(text: &str, lang: Lang) -> String {
+    tr!(
+        lang,
+        "Qui a écrit ça: « {} » ?",
+        "Who wrote this: “{}”?",
+        text
+    )
+}
+
+/// Committee member who wrote the forwarded message, recognized by the name of their account.
+async fn committee_author(from: &ForwardedFrom) -> Option<String> {
+    let names = match from {
+        ForwardedFrom::User(user) => [
+            Some(user.first_name.clone()),
+            Some(user.full_name()),
+            user.username.clone(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>(),
+        ForwardedFrom::SenderName(name) => name
+            .split_whitespace()
+            .next()
+            .map(str::to_owned)
+            .into_iter()
+            .chain([name.clone()])
+            .collect(),
+        ForwardedFrom::Chat(_) => return None,
+    };
+
+    let committee = match committee_repository().get().await {
+        Ok(committee) => committee,
+        Err(e) => {
+            log::error!("Could not fetch committee: {e:#?}");
+            return None;
+        }
+    };
+    committee
+        .into_iter()
+        .find(|c| {
+            names
+                .iter()
+                .any(|n| n.to_lowercase() == c.name.to_lowercase())
+        })
+        .map(|c| c.name)
+}
+
+/// Whether the user may make quizzes of forwarded messages: the admins and the linked committee
+/// members.
+pub async fn may_forward_quiz(msg: Message, state: AppState) -> bool {
+    let Some(user) = msg.from() else {
+        return false;
+    };
+    state.admin_repo.is_admin(user.id).await
+        || state
+            .committee_repo
+            .is_linked(&user.id.to_string())
+            .await
+            .unwrap_or(false)
+}
+
+/// Groups using `/poll` where the committee member writes, as `(chat_id, title)`, in which they
+/// may send a quote quiz.
+async fn quote_quiz_chats(
+    state: &AppState,
+    forwarder: UserId,
+) -> Result<Vec<(String, Option<String>)>, sqlx::Error> {
+    let forwarder = forwarder.to_string();
+    if !state.committee_repo.is_linked(&forwarder).await? {
+        return Ok(vec![]);
+    }
+    state
+        .authorization_repo
+        .member_chats_allowing(&forwarder, "poll")
+        .await
+}
+
+/// Groups of the author of the message, as `(chat_id, title)`, in which an admin may send a "who
+/// wrote this?" quiz.
+async fn author_quiz_chats(
+    state: &AppState,
+    forwarder: UserId,
+    author_id: Option<UserId>,
+    author: &str,
+) -> Result<Vec<(String, Option<String>)>, sqlx::Error> {
+    if !state.admin_repo.is_admin(forwarder).await {
+        return Ok(vec![]);
+    }
+    // The author hiding their account is only recognized by the name they display
+    let user_id = author_id.map(|id| id.to_string());
+    Ok(sqlx::query!(
+        r#"SELECT m.chat_id AS "chat_id!", c.title FROM chat_members m
+        LEFT JOIN chats c ON c.chat_id = m.chat_id
+        WHERE (($1 IS NOT NULL AND m.user_id = $1) OR ($1 IS NULL AND m.user_name = $2))
+        AND (SELECT COUNT(*) FROM chat_members o WHERE o.chat_id = m.chat_id) > 1
+        ORDER BY c.title"#,
+        user_id,
+        author
+    )
+    .fetch_all(state.db.as_ref())
+    .await?
+    .into_iter()
+    .map(|g| (g.chat_id, g.title))
+    .collect())
+}
+
+/// Offers to make a quiz of the forwarded message, in one of the groups where it makes sense.
+pub async fn offer_forward_quiz(
+    bot: Bot,
+    msg: Message,
+    dialogue: PollDialogue,
+    state: AppState,
+    lang: Lang,
+) -> HandlerResult {
+    let (Some(user), Some(from)) = (msg.from(), msg.forward_from()) else {
+        return Ok(());
+    };
+    let Some(text) = msg.text().or(msg.caption()).map(str::trim) else {
+        return Ok(());
+    };
+    let (author_id, author) = match from {
+        ForwardedFrom::User(user) => (Some(user.id), user.full_name()),
+        ForwardedFrom::SenderName(name) => (None, name.clone()),
+        ForwardedFrom::Chat(_) => {
+            bot.send_message(
+                msg.chat.id,
+                tr!(
+                    lang,
+                    "Seuls les messages écrits par un membre peuvent devenir un quiz",
+                    "Only the messages written by a member can become a quiz"
+                ),
+            )
+            .queued()
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let mut keyboard = vec![];
+    let target = committee_author(from).await;
+    if target.is_some() {
+        let groups = quote_quiz_chats(&state, user.id).await?;
+        keyboard.extend(groups.into_iter().map(|(chat_id, title)| {
+            vec![InlineKeyboardButton::callback(
+                tr!(
+                    lang,
+                    "💬 Quiz de citation dans {}",
+                    "💬 Quote quiz in {}",
+                    title.as_deref().unwrap_or(&chat_id)
+                ),
+                CallbackData::format(FORWARD_QUIZ, format!("quote:{}", chat_id)),
+            )]
+        }));
+    }
+
+    let groups = author_quiz_chats(&state, user.id, author_id, &author).await?;
+    keyboard.extend(groups.into_iter().map(|(chat_id, title)| {
+        vec![InlineKeyboardButton::callback(
+            tr!(
+                lang,
+                "❓ Qui a écrit ça, dans {}",
+                "❓ Who wrote this, in {}",
+                title.as_deref().unwrap_or(&chat_id)
+            ),
+            CallbackData::format(FORWARD_QUIZ, format!("author:{}", chat_id)),
+        )]
+    }));
+
+    if keyboard.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Impossible d'en faire un quiz: {} ne fait pas partie du comité, ou tu n'écris dans aucun groupe utilisant /poll",
+                "This message cannot become a quiz: {} is not part of the committee, or you do not write in any chat using /poll",
+                author
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+    keyboard.push(vec![InlineKeyboardButton::callback(
+        tr!(lang, "❌ Annuler", "❌ Cancel"),
+        CallbackData::format(FORWARD_QUIZ, "cancel"),
+    )]);
+
+    let sent = bot
+        .send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "En faire un quiz ? Message de {}",
+                "Make it a quiz? Message of {}",
+                target.as_ref().unwrap_or(&author)
+            ),
+        )
+        .reply_markup(InlineKeyboardMarkup::new(keyboard))
+        .reply_to_message_id(msg.id)
+        .queued()
+        .await?;
+
+    dialogue
+        .update(PollState::ForwardQuiz {
+            message_id: sent.id,
+            text: text.to_owned(),
+            author_id,
+            author,
+            target,
+            initiator: Some(user.id),
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Sends the chosen quiz of the forwarded message.
+pub async fn send_forward_quiz(
+    bot: Bot,
+    data: CallbackData,
+    dialogue: PollDialogue,
+    (message_id, text, author_id, author, target, initiator): (
+        MessageId,
+        String,
+        Option<UserId>,
+        String,
+        Option<String>,
+        Option<UserId>,
+    ),
+    state: AppState,
+    lang: Lang,
+) -> CallbackResult {
+    if data.payload == "cancel" {
+        dialogue.update(PollState::Start).await?;
+        bot.edit_message_text(
+            dialogue.chat_id(),
+            message_id,
+            tr!(lang, "Quiz annulé", "Quiz cancelled"),
+        )
+        .queued()
+        .await?;
+        return Ok(None);
+    }
+    let Some((kind, Ok(chat_id))) = data
+        .payload
+        .split_once(':')
+        .map(|(kind, id)| (kind, id.parse::<i64>().map(ChatId)))
+    else {
+        return Ok(None);
+    };
+
+    // The payload comes from the user: the chat is checked again, in case the rights changed since
+    let Some(forwarder) = initiator else {
+        return Ok(None);
+    };
+    let allowed = match kind {
+        "quote" => quote_quiz_chats(&state, forwarder).await?,
+        "author" => author_quiz_chats(&state, forwarder, author_id, &author).await?,
+        _ => vec![],
+    };
+    if !allowed.iter().any(|(id, _)| *id == chat_id.to_string()) {
+        return Ok(Some(tr!(
+            lang,
+            "Tu ne peux plus envoyer ce quiz dans ce groupe",
+            "You can no longer send this quiz in this chat"
+        )));
+    }
+
+    match (kind, target) {
+        ("quote", Some(target)) => {
+            // The quote is kept whole, in its own message if it does not fit in the question
+            let chat_lang = chat_language(state.db.as_ref(), chat_id).await;
+            let question = quote_question(state.db.as_ref(), chat_id, &text, chat_lang).await;
+            let layout = if question.chars().count() <= POLL_MAX_QUESTION_LENGTH {
+                QuoteLayout::Question
+            } else {
+                QuoteLayout::Separate
+            };
+            dialogue.update(PollState::Start).await?;
+            send_quote_poll(
+                &bot,
+                &dialogue,
+                chat_id,
+                state.db.as_ref(),
+                target,
+                &text,
+                layout,
+                chat_lang,
+            )
+            .await?;
+        }
+        ("author", _) => {
+            if !send_author_quiz(&bot, state.db.as_ref(), chat_id, &text, author_id, &author)
+                .await?
+            {
+                return Ok(Some(tr!(
+                    lang,
+                    "Pas assez de membres connus dans ce groupe pour un quiz",
+                    "Not enough known members in this group for a quiz"
+                )));
+            }
+            dialogue.update(PollState::Start).await?;
+        }
+        _ => return Ok(None),
+    }
+
+    bot.edit_message_text(
+        dialogue.chat_id(),
+        message_id,
+        tr!(lang, "Quiz envoyé", "Quiz sent"),
+    )
+    .queued()
+    .await?;
+
+    Ok(None)
+}
+
+/// Sends the "who wrote this?" quiz in the group, with the author among other members of the
+/// group. Returns whether there were enough members for a quiz.
+async fn send_author_quiz(
+    bot: &Bot,
+    db: &SqlitePool,
+    chat_id: ChatId,
+    text: &str,
+    author_id: Option<UserId>,
+    author: &str,
+) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    let id = chat_id.to_string();
+    let user_id = author_id.map(|id| id.to_string());
+    let members = sqlx::query!(
+        "SELECT user_name FROM chat_members
+        WHERE chat_id = $1 AND user_id IS NOT $2 AND user_name != $3
+        ORDER BY last_seen DESC LIMIT $4",
+        id,
+        user_id,
+        author,
+        CANDIDATES
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|m| m.user_name)
+    .collect::<Vec<_>>();
+    let Some((options, index)) = quiz_options(members, author) else {
+        return Ok(false);
+    };
+
+    let lang = chat_language(db, chat_id).await;
+    let overhead = author_question("", lang).chars().count();
+    let question = author_question(&truncate(text, POLL_MAX_QUESTION_LENGTH - overhead), lang);
+    let settings = poll_settings(db, chat_id).await;
+    let poll_msg = bot
+        .send_poll(chat_id, question, options)
+        .type_(PollType::Quiz)
+        .is_anonymous(settings.quiz_anonymous)
+        .correct_option_id(index)
+        .queued()
+        .await?;
+    track_poll(db, &poll_msg, settings.quiz_close_after).await?;
+
+    // Recorded with the quotes, so that the answers count in the hall of fame and the tournaments
+    let timestamp = now().timestamp();
+    let poll_id = poll_msg.poll().map(|p| p.id.clone());
+    db.insert_quote(&id, author, text, poll_id.as_deref(), index, timestamp)
+        .await?;
+
+    Ok(true)
+}
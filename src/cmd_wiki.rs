@@ -0,0 +1,106 @@
+use teloxide::{payloads::SendMessageSetters, requests::Requester, types::Message, Bot};
+
+use crate::{
+    directus::{search_wiki, WikiPage},
+    format::{escape, italic, link, HtmlMessages, MessageBuilder},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    HandlerResult,
+};
+
+/// Number of pages fetched from Directus, ranked before keeping the best ones.
+const SEARCH_LIMIT: usize = 50;
+/// Number of pages displayed.
+const RESULTS: usize = 5;
+
+/// Relevance of the page for the words of the query: the words found in the title count more
+/// than the ones found in the summary.
+fn score(page: &WikiPage, words: &[String]) -> usize {
+    let title = page.title.to_lowercase();
+    let summary = page.summary.as_deref().unwrap_or_default().to_lowercase();
+    words
+        .iter()
+        .map(|w| {
+            3 * usize::from(title.contains(w.as_str())) + usize::from(summary.contains(w.as_str()))
+        })
+        .sum()
+}
+
+/// `/wiki <query>` searches the documentation of the association, and links the best pages.
+pub async fn wiki(bot: Bot, msg: Message, query: String, lang: Lang) -> HandlerResult {
+    let query = query.trim();
+    if query.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Utilisation: /wiki <recherche>",
+                "Usage: /wiki <query>"
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    let mut pages = match search_wiki(query, SEARCH_LIMIT).await {
+        Ok(pages) => pages,
+        Err(e) => {
+            log::error!("Could not search the wiki: {e:#?}");
+            bot.send_message(
+                msg.chat.id,
+                tr!(
+                    lang,
+                    "Impossible de chercher dans la documentation",
+                    "Could not search the documentation"
+                ),
+            )
+            .queued()
+            .await?;
+            return Ok(());
+        }
+    };
+    if pages.is_empty() {
+        bot.send_html(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Aucune page ne correspond à {}",
+                "No page matches {}",
+                italic(query)
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    let words = query
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>();
+    // Stable sort: Directus' order breaks the ties
+    pages.sort_by_key(|p| std::cmp::Reverse(score(p, &words)));
+
+    let lines = pages
+        .iter()
+        .take(RESULTS)
+        .map(|p| {
+            let mut line = link(&p.url, &p.title);
+            if let Some(summary) = p.summary.as_deref().filter(|s| !s.is_empty()) {
+                line += &format!("\n   {}", escape(summary));
+            }
+            line
+        })
+        .collect::<Vec<_>>();
+    let text = MessageBuilder::new()
+        .title("📚 Documentation")
+        .items(lines)
+        .build();
+    bot.send_html(msg.chat.id, text)
+        .disable_web_page_preview(true)
+        .queued()
+        .await?;
+
+    Ok(())
+}
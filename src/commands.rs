@@ -1,100 +1,390 @@
 use std::sync::Arc;
 
-use sqlx::SqlitePool;
 use teloxide::{
     dispatching::DpHandlerDescription,
     prelude::*,
-    types::{Message, MessageCommon, MessageKind},
+    types::{Message, MessageCommon, MessageKind, UserId},
     utils::command::BotCommands,
     Bot,
 };
 
 use crate::{
+    admin_token::leaks_admin_token,
+    approvals::resolve_approval,
+    callbacks::{
+        action, answer_callbacks, reject_non_initiators, APPROVAL, BROADCAST, DOODLE_VOTE,
+        EVENT_REGISTRATION, FORWARD_QUIZ, HELP, MEMBER_CHALLENGE, NEWPOLL, NEWSLETTER, POLL_TARGET, QUOTE_REPORT, QUOTE_REPORT_RESOLVE,
+        QUOTE_TOO_LONG, REMINDER_CANCEL, RESTORE, SETTINGS, TODO_DONE, WIZARD, IMPORT,
+    },
+    aliases::resolve_alias,
+    db::admins::AdminRepo,
+    channels::publish,
+    cmd_admin_invite::{admin_invite, start},
+    cmd_aliases::{alias_add, alias_remove, aliases},
+    cmd_audit_export::{audit_export, log_command},
+    cmd_anon::{anon, anon_block, anon_unblock},
+    cmd_mailing::{subscribe, unsubscribe},
     cmd_authentication::{
-        admin_list, admin_remove, authenticate, authorizations, authorize, unauthorize
+        admin_list, admin_remove, authenticate, authorizations, authorize, redact_admin_token,
+        revoke_admin, sessions, topic_bind, unauthorize,
     }, 
+    cmd_backup::{backup, confirm_restore, restore},
+    cmd_broadcast::{broadcast, confirm_broadcast},
     cmd_bureau::bureau, 
+    cmd_calendar::calendar,
+    cmd_chats::{chat, chats},
+    cmd_checkin::{attendance_count, checkin, checkin_code, checkin_forward},
+    cmd_closepoll::close_poll,
+    cmd_countdown::countdown,
+    cmd_debt::debt,
+    cmd_directus::{committee_sync, directus_status},
+    cmd_doodle::{doodle, doodle_close, doodle_vote},
+    cmd_expense::expense,
+    cmd_export::{confirm_import, export, import},
+    cmd_whowrote::{may_forward_quiz, offer_forward_quiz, send_forward_quiz},
+    cmd_halloffame::halloffame,
+    cmd_help::{help, help_menu},
+    cmd_karma::{karma, karma_reply, karma_reply_vote},
+    cmd_language::language,
+    cmd_link::{link, links},
+    cmd_loan::loan,
+    cmd_menu::menu,
+    cmd_pin::{autopin, pin, unpin_all},
+    cmd_newpoll::{
+        newpoll_anonymity, newpoll_correct_option, newpoll_options, newpoll_question,
+        newpoll_type, start_newpoll_dialogue,
+    },
+    cmd_newsletter::{
+        confirm_newsletter, newsletter, newsletter_body, newsletter_buttons, newsletter_image,
+        newsletter_now, newsletter_skip_buttons, newsletter_skip_image, newsletter_targets,
+        newsletter_time, newsletter_title,
+    },
     cmd_poll::{
         choose_target, 
+        quote_too_long,
         set_quote, 
         start_poll_dialogue, 
         stats, PollState
     }, 
+    cmd_random::random,
+    cmd_registrations::{participants, register},
+    cmd_reactionstats::{member_link, reaction_stats},
+    cmd_reminders::{cancel_reminder, remind, reminders},
+    cmd_retention::retention,
+    cmd_room::room,
+    cmd_satellite::satellite,
+    cmd_shop::{shop, shop_update},
+    cmd_ticket::{is_it_member, ticket, ticket_close},
+    cmd_wiki::wiki,
+    cmd_report::{file_report, quote_fix, report_mistake, resolve_report},
+    cmd_schedules::{schedule_add, schedule_remove, schedules},
+    cmd_semester::{semester, semester_add, semester_remove},
+    cmd_settings::{change_setting, set_welcome_message, settings},
+    cmd_shame::{gg, shame, shames},
+    cmd_timezone::timezone,
+    cmd_todo::{todo, todo_done},
+    cmd_tournament::{tournament, tournament_start, tournament_stop},
+    cmd_templates::set_template,
+    cmd_transport::{metro, transport},
+    cmd_undo::undo,
+    cmd_vote::vote,
+    chats::topic,
+    metrics::{instrument, slow_log},
+    outbox::Queued,
+    state::AppState,
+    throttle::{throttle_commands, unthrottle},
+    usage::validate_arguments,
+    verification::{answer_challenge, challenge_new_members},
+    wizard::{cancel, cancel_button},
+    i18n::{tr, Lang},
     HandlerResult
 };
 
 pub fn command_message_handler(
 ) -> Endpoint<'static, DependencyMap, HandlerResult, DpHandlerDescription> {
     dptree::entry()
+        // Before anything else, even a valid `/authenticate` in a group
+        .branch(
+            dptree::filter(|msg: Message| {
+                !msg.chat.is_private() && leaks_admin_token(msg.text().or(msg.caption()))
+            })
+            .endpoint(redact_admin_token),
+        )
+        .branch(
+            dptree::filter(|msg: Message| msg.new_chat_members().is_some())
+                .endpoint(challenge_new_members),
+        )
         .branch(
             dptree::entry()
+                .map_async(resolve_alias)
+                .branch(validate_arguments())
                 .filter_command::<Command>()
-                .branch(dptree::case![Command::Help].endpoint(help))
-                .branch(dptree::case![Command::Authenticate(token, name)].endpoint(authenticate))
-                .branch(
-                    require_authorization()
-                        .branch(dptree::case![Command::Bureau].endpoint(bureau))
-                        .branch(dptree::case![Command::Poll].endpoint(start_poll_dialogue))
-                        .branch(dptree::case![Command::Stats].endpoint(stats)),
-                )
-                .branch(
-                    require_admin().chain(
-                        dptree::entry()
-                            .branch(dptree::case![Command::AdminList].endpoint(admin_list))
-                            .branch(
-                                dptree::case![Command::AdminRemove(name)].endpoint(admin_remove),
-                            )
-                            .branch(dptree::case![Command::Authorize(command)].endpoint(authorize))
-                            .branch(
-                                dptree::case![Command::Unauthorize(command)].endpoint(unauthorize),
-                            )
-                            .branch(
-                                dptree::case![Command::Authorizations].endpoint(authorizations),
-                            ),
-                    ),
-                ),
+                .chain(instrument(|c: &Command| c.shortand().to_owned()))
+                .inspect(log_command)
+                .branch(throttle_commands())
+                .branch(command_endpoints(Access::Everyone))
+                .branch(command_endpoints(Access::ItTeam))
+                .branch(command_endpoints(Access::Superadmin))
+                .branch(require_authorization().branch(command_endpoints(Access::AuthorizedChat)))
+                .branch(require_admin().chain(command_endpoints(Access::Admin))),
+        )
+        .branch(
+            dptree::case![PollState::SetQuote {
+                message_id,
+                target,
+                initiator
+            }]
+            .endpoint(set_quote),
+        )
+        .branch(dptree::case![PollState::NewPollQuestion(poll)].endpoint(newpoll_question))
+        .branch(dptree::case![PollState::NewPollOptions(poll)].endpoint(newpoll_options))
+        .branch(dptree::case![PollState::NewsletterTitle(draft)].endpoint(newsletter_title))
+        .branch(dptree::case![PollState::NewsletterBody(draft)].endpoint(newsletter_body))
+        .branch(dptree::case![PollState::NewsletterImage(draft)].endpoint(newsletter_image))
+        .branch(dptree::case![PollState::NewsletterButtons(draft)].endpoint(newsletter_buttons))
+        .branch(dptree::case![PollState::NewsletterTime(draft)].endpoint(newsletter_time))
+        .branch(
+            dptree::case![PollState::ReportMistake {
+                message_id,
+                quote_id,
+                initiator
+            }]
+            .endpoint(file_report),
+        )
+        .branch(
+            dptree::case![PollState::WelcomeMessage {
+                message_id,
+                initiator
+            }]
+            .endpoint(set_welcome_message),
+        )
+        .branch(
+            dptree::filter(|msg: Message| msg.forward().is_some() && checkin_code(&msg).is_some())
+                .chain(require_chat_authorization("checkin"))
+                .endpoint(checkin_forward),
+        )
+        .branch(
+            dptree::filter(|msg: Message| msg.chat.is_private() && msg.forward().is_some())
+                .filter_async(may_forward_quiz)
+                .endpoint(offer_forward_quiz),
+        )
+        .branch(
+            dptree::filter(|msg: Message| karma_reply_vote(&msg).is_some())
+                .chain(require_chat_authorization("karma"))
+                .endpoint(karma_reply),
         )
-        .branch(dptree::case![PollState::SetQuote { message_id, target }].endpoint(set_quote))
+}
+
+/// Edited messages are re-processed when they answer the current step of a dialogue (e.g. a typo
+/// fixed in a quote). Commands are not re-run, the user is told to send them again instead.
+pub fn command_edited_message_handler(
+) -> Endpoint<'static, DependencyMap, HandlerResult, DpHandlerDescription> {
+    dptree::entry()
+        .branch(
+            dptree::case![PollState::SetQuote {
+                message_id,
+                target,
+                initiator
+            }]
+            .endpoint(set_quote),
+        )
+        .branch(dptree::case![PollState::NewPollQuestion(poll)].endpoint(newpoll_question))
+        .branch(dptree::case![PollState::NewPollOptions(poll)].endpoint(newpoll_options))
+        .branch(
+            dptree::entry()
+                .map_async(resolve_alias)
+                .filter_command::<Command>()
+                .endpoint(edited_command),
+        )
+}
+
+/// Name of a callback query in the metrics, from the action of its button.
+fn callback_name(query: &CallbackQuery) -> String {
+    let action = query
+        .data
+        .as_deref()
+        .and_then(|d| d.split_once(':'))
+        .map_or("unknown", |(action, _)| action);
+    format!("callback:{}", action)
 }
 
 pub fn command_callback_query_handler(
 ) -> Endpoint<'static, DependencyMap, HandlerResult, DpHandlerDescription> {
-    dptree::case![PollState::ChooseTarget { message_id }].endpoint(choose_target)
+    answer_callbacks(
+        instrument(callback_name)
+            .branch(action(REMINDER_CANCEL).endpoint(cancel_reminder))
+            .branch(action(DOODLE_VOTE).endpoint(doodle_vote))
+            .branch(action(TODO_DONE).endpoint(todo_done))
+            .branch(action(QUOTE_REPORT).endpoint(report_mistake))
+            .branch(action(QUOTE_REPORT_RESOLVE).endpoint(resolve_report))
+            .branch(action(SETTINGS).endpoint(change_setting))
+            .branch(action(EVENT_REGISTRATION).endpoint(register))
+            .branch(action(APPROVAL).endpoint(resolve_approval))
+            .branch(action(MEMBER_CHALLENGE).endpoint(answer_challenge))
+            .branch(action(HELP).endpoint(help_menu))
+            // Keyboards of the dialogues, only the user who started the dialogue may answer
+            .branch(reject_non_initiators())
+            .branch(action(WIZARD).endpoint(cancel_button))
+            .branch(
+                dptree::case![PollState::ChooseTarget {
+                    message_id,
+                    initiator,
+                    quote
+                }]
+                .chain(action(POLL_TARGET))
+                .endpoint(choose_target),
+            )
+            .branch(
+                dptree::case![PollState::QuoteTooLong {
+                    message_id,
+                    target,
+                    quote,
+                    initiator
+                }]
+                .chain(action(QUOTE_TOO_LONG))
+                .endpoint(quote_too_long),
+            )
+            .branch(
+                dptree::case![PollState::NewPollAnonymity(poll)]
+                    .chain(action(NEWPOLL))
+                    .endpoint(newpoll_anonymity),
+            )
+            .branch(
+                dptree::case![PollState::NewPollType(poll)]
+                    .chain(action(NEWPOLL))
+                    .endpoint(newpoll_type),
+            )
+            .branch(
+                dptree::case![PollState::NewPollCorrectOption(poll)]
+                    .chain(action(NEWPOLL))
+                    .endpoint(newpoll_correct_option),
+            )
+            .branch(
+                dptree::case![PollState::ConfirmBroadcast {
+                    message_id,
+                    text,
+                    initiator
+                }]
+                .chain(action(BROADCAST))
+                .endpoint(confirm_broadcast),
+            )
+            .branch(
+                dptree::case![PollState::ConfirmRestore {
+                    message_id,
+                    file_id,
+                    initiator
+                }]
+                .chain(action(RESTORE))
+                .endpoint(confirm_restore),
+            )
+            .branch(
+                dptree::case![PollState::ConfirmImport {
+                    message_id,
+                    file_id,
+                    initiator
+                }]
+                .chain(action(IMPORT))
+                .endpoint(confirm_import),
+            )
+            .branch(
+                dptree::case![PollState::ForwardQuiz {
+                    message_id,
+                    text,
+                    author_id,
+                    author,
+                    target,
+                    initiator
+                }]
+                .chain(action(FORWARD_QUIZ))
+                .endpoint(send_forward_quiz),
+            )
+            .branch(
+                dptree::case![PollState::NewsletterImage(draft)]
+                    .chain(action(NEWSLETTER))
+                    .endpoint(newsletter_skip_image),
+            )
+            .branch(
+                dptree::case![PollState::NewsletterButtons(draft)]
+                    .chain(action(NEWSLETTER))
+                    .endpoint(newsletter_skip_buttons),
+            )
+            .branch(
+                dptree::case![PollState::NewsletterTargets(draft)]
+                    .chain(action(NEWSLETTER))
+                    .endpoint(newsletter_targets),
+            )
+            .branch(
+                dptree::case![PollState::NewsletterTime(draft)]
+                    .chain(action(NEWSLETTER))
+                    .endpoint(newsletter_now),
+            )
+            .branch(
+                dptree::case![PollState::NewsletterConfirm(draft)]
+                    .chain(action(NEWSLETTER))
+                    .endpoint(confirm_newsletter),
+            ),
+    )
 }
 
 // ----------------------------- ACCESS CONTROL -------------------------------
 
 /// Check that the chat from which a command originated as the authorization to use it
 ///
-/// Required dependencies: `teloxide_core::types::message::Message`, `roboclic_v2::commands::Command`
+/// Required dependencies: `teloxide_core::types::message::Message`, `roboclic_v2::commands::Command`,
+/// `AppState`
 fn require_authorization() -> Endpoint<'static, DependencyMap, HandlerResult, DpHandlerDescription>
 {
     dptree::entry().filter_async(
-        |command: Command, msg: Message, pool: Arc<SqlitePool>| async move {
-            let chat_id = msg.chat.id.to_string();
-            let shortand = command.shortand();
-            match sqlx::query!(
-                r#"SELECT COUNT(*) AS count FROM authorizations WHERE chat_id = $1 AND command = $2"#,
-                chat_id,
-                shortand
-            )
-            .fetch_one(pool.as_ref())
-            .await {
-                Ok(result) => result.count > 0,
-                Err(e) => {
-                    log::error!("Could not check authorization in database: {:?}", e);
-                    false
-                },
-            }
+        |command: Command, msg: Message, state: AppState| async move {
+            is_authorized(&state, &msg, command.shortand()).await
         },
     )
 }
 
+/// Check that the chat has the authorization to use the given feature, for handlers which are
+/// not triggered by a command (e.g. "+1" replies for the karma)
+///
+/// Required dependencies: `teloxide_core::types::message::Message`, `AppState`
+fn require_chat_authorization(
+    shortand: &'static str,
+) -> Endpoint<'static, DependencyMap, HandlerResult, DpHandlerDescription> {
+    dptree::entry().filter_async(move |msg: Message, state: AppState| async move {
+        is_authorized(&state, &msg, shortand).await
+    })
+}
+
+/// A command bound to a forum topic is only authorized in this topic.
+pub async fn is_authorized(state: &AppState, msg: &Message, shortand: &str) -> bool {
+    let chat_id = msg.chat.id.to_string();
+    match state
+        .authorizations
+        .is_authorized(state.authorization_repo.as_ref(), &chat_id, shortand, topic(msg))
+        .await
+    {
+        Ok(authorized) => authorized,
+        Err(e) => {
+            log::error!("Could not check authorization in database: {:?}", e);
+            false
+        }
+    }
+}
+
+/// Whether the user can run the command from the chat of the message.
+pub async fn is_permitted(state: &AppState, msg: &Message, user: UserId, command: &Command) -> bool {
+    match command.access() {
+        Access::Everyone => true,
+        Access::AuthorizedChat => is_authorized(state, msg, command.shortand()).await,
+        Access::Admin => state.admin_repo.is_admin(user).await,
+        Access::Superadmin => state.config.superadmin_id == Some(user.0),
+        Access::ItTeam => is_it_member(user.0),
+    }
+}
+
 /// Check that the chat is admin
 ///
-/// Required dependencies: `teloxide_core::types::message::Message`, `sqlx_sqlite::SqlitePool`
+/// Required dependencies: `teloxide_core::types::message::Message`, `Arc<dyn AdminRepo>`
 fn require_admin() -> Endpoint<'static, DependencyMap, HandlerResult, DpHandlerDescription> {
-    dptree::entry().filter_async(|msg: Message, db: Arc<SqlitePool>| async move {
+    dptree::entry().filter_async(|msg: Message, admins: Arc<dyn AdminRepo>| async move {
         let MessageKind::Common(MessageCommon {
             from: Some(user), ..
         }) = msg.kind
@@ -102,14 +392,7 @@ fn require_admin() -> Endpoint<'static, DependencyMap, HandlerResult, DpHandlerD
             return false;
         };
 
-        let id = user.id.to_string();
-        sqlx::query!(
-            "SELECT COUNT(*) AS is_admin FROM admins WHERE telegram_id = $1",
-            id
-        )
-        .fetch_one(db.as_ref())
-        .await
-        .is_ok_and(|r| r.is_admin > 0)
+        admins.is_admin(user.id).await
     })
 }
 
@@ -121,14 +404,18 @@ fn require_admin() -> Endpoint<'static, DependencyMap, HandlerResult, DpHandlerD
     description = "These commands are supported:"
 )]
 pub enum Command {
-    #[command(description = "display this text.")]
+    #[command(description = "Affiche les commandes disponibles, par catégorie")]
     Help,
+    #[command(description = "Annule la commande en cours (/poll, /newpoll, /newsletter...)")]
+    Cancel,
+    #[command(description = "Démarre la conversation avec le bot / Starts the conversation with the bot")]
+    Start(String),
     #[command(description = "Crée un sondage pour savoir qui est au bureau")]
     Bureau,
     #[command(description = "Crée un quiz sur une citation d'un des membres du comité")]
     Poll,
     #[command(
-        description = "Authentifcation admin: /auth <token> <name>",
+        description = "Authentification du premier admin, en privé: /authenticate <token> <name>",
         parse_with = "split",
         separator = " "
     )]
@@ -137,6 +424,10 @@ pub enum Command {
     AdminList,
     #[command(description = "(Admin) Supprime un admin à partir de son nom")]
     AdminRemove(String),
+    #[command(
+        description = "(Admin) Génère un lien d'invitation admin à usage unique, en privé: /admininvite <nom>"
+    )]
+    AdminInvite(String),
     #[command(description = "(Admin) Authorise le groupe à utiliser la commande donnée")]
     Authorize(String),
     #[command(
@@ -145,32 +436,590 @@ pub enum Command {
     Unauthorize(String),
     #[command(description = "(Admin) Liste les commandes que ce groupe peut utiliser")]
     Authorizations,
+    #[command(
+        description = "(Admin) Restreint une commande au sujet du forum dans lequel elle est envoyée (au groupe entier depuis le sujet général)"
+    )]
+    TopicBind(String),
+    #[command(description = "(Admin) Crée un raccourci pour une commande: /aliasadd <alias> <commande>")]
+    AliasAdd(String),
+    #[command(description = "(Admin) Supprime un raccourci: /aliasremove <alias>")]
+    AliasRemove(String),
+    #[command(description = "(Admin) Liste les raccourcis de ce groupe")]
+    Aliases,
+    #[command(
+        description = "(Admin) Annule ta dernière autorisation, modification d'alias ou liaison de membre, dans les 15 minutes"
+    )]
+    Undo,
+    #[command(
+        description = "(Admin) Modifie un texte du bot dans ce groupe: /settemplate <modèle> <texte>, sans argument pour la liste"
+    )]
+    SetTemplate(String),
+    #[command(
+        description = "(Admin) Affiche ou modifie la durée de conservation des données: /retention <type> <jours|jamais|défaut>"
+    )]
+    Retention(String),
     #[command(description = "(Admin) Affiche les stats des membres du comité")]
     Stats,
+    #[command(description = "(Admin) Vérifie la connexion à Directus")]
+    DirectusStatus,
+    #[command(description = "(Admin) Recharge le comité depuis Directus")]
+    CommitteeSync,
+    #[command(description = "Crée un rappel: /remind <quand> <texte>")]
+    Remind(String),
+    #[command(description = "Liste et permet d'annuler les rappels en attente")]
+    Reminders,
+    #[command(
+        description = "(Admin) Programme un message récurrent: /scheduleadd [canal] <cron> <message ou /commande>"
+    )]
+    ScheduleAdd(String),
+    #[command(description = "(Admin) Liste les messages programmés de ce groupe")]
+    Schedules,
+    #[command(description = "(Admin) Supprime un message programmé: /scheduleremove <id>")]
+    ScheduleRemove(String),
+    #[command(
+        description = "(Admin) Publie dans le canal lié à ce groupe: /publish <message ou /quote, /events, /digest>"
+    )]
+    Publish(String),
+    #[command(description = "Envoie un message anonyme au comité (en message privé)")]
+    Anon(String),
+    #[command(description = "(Admin) Bloque l'expéditeur d'un message anonyme")]
+    AnonBlock(String),
+    #[command(description = "(Admin) Débloque l'expéditeur d'un message anonyme")]
+    AnonUnblock(String),
+    #[command(description = "Envoie le calendrier des événements à venir (.ics)")]
+    Calendar,
+    #[command(description = "Crée un doodle: /doodle <titre> | <créneau 1>; <créneau 2>; ...")]
+    Doodle(String),
+    #[command(description = "Ferme le dernier doodle et annonce le créneau retenu")]
+    DoodleClose,
+    #[command(description = "Liste de tâches du groupe: /todo add|done|list <tâche>")]
+    Todo(String),
+    #[command(description = "Compte à rebours: /countdown [pin] <événement ou date>")]
+    Countdown(String),
+    #[command(description = "Tire au sort des membres du comité: /random [nombre] [fair]")]
+    Random(String),
+    #[command(description = "Qui doit un café à qui: /debt add|list|settle")]
+    Debt(String),
+    #[command(description = "Classement karma, ou vote: /karma [@personne +1|-1]")]
+    Karma(String),
+    #[command(description = "Menus des restaurants de l'EPFL: /menu [restaurant] [jour]")]
+    Menu(String),
+    #[command(description = "Prochains départs depuis l'arrêt par défaut du groupe")]
+    Metro,
+    #[command(description = "Prochains départs: /transport <arrêt>, ou /transport default <arrêt>")]
+    Transport(String),
+    #[command(description = "Dépenses à rembourser: /expense add|list|receipt|approve")]
+    Expense(String),
+    #[command(description = "Prêt de matériel: /loan take|return|list <objet>")]
+    Loan(String),
+    #[command(description = "Enregistre un lien: /link save <url> [tags] ou /link remove <url>")]
+    Link(String),
+    #[command(description = "Liste les liens enregistrés, filtrés par tag: /links [tag]")]
+    Links(String),
+    #[command(description = "Records de tous les temps, par mandat")]
+    HallOfFame,
+    #[command(description = "Crée un sondage personnalisé")]
+    NewPoll,
+    #[command(description = "Pose une question à laquelle voter en réagissant 👍 ou 👎: /vote <question>")]
+    Vote(String),
+    #[command(description = "Épingle le message auquel tu réponds")]
+    Pin,
+    #[command(description = "Désépingle tous les messages du groupe")]
+    UnpinAll,
+    #[command(
+        description = "Épingle automatiquement les sondages du bureau ou les comptes à rebours: /autopin bureau|countdown on|off"
+    )]
+    AutoPin(String),
+    #[command(description = "Affiche le classement du tournoi de quiz")]
+    Tournament,
+    #[command(description = "Ferme le dernier sondage envoyé par le bot et affiche ses résultats")]
+    ClosePoll,
+    #[command(description = "Affiche les paramètres du groupe")]
+    Settings,
+    #[command(description = "Affiche les membres du comité recevant le plus de réactions")]
+    ReactionStats,
+    #[command(description = "Ajoute une honte: /shame @personne <raison>, ou en réponse")]
+    Shame(String),
+    #[command(description = "Félicite un membre: /gg @personne <raison>, ou en réponse")]
+    Gg(String),
+    #[command(description = "Affiche les compteurs de /shame et /gg, ou les raisons d'un membre")]
+    Shames(String),
+    #[command(description = "Indique si une salle est libre et ce qui y est prévu: /room <salle>")]
+    Room(String),
+    #[command(description = "Affiche les bières à la pression et les événements de Satellite")]
+    Satellite,
+    #[command(description = "Liste les articles de la boutique, avec leur prix et leur stock")]
+    Shop,
+    #[command(description = "Récupère à nouveau les articles de la boutique depuis Directus")]
+    ShopUpdate,
+    #[command(description = "Cherche dans la documentation de l'association: /wiki <recherche>")]
+    Wiki(String),
+    #[command(description = "S'inscrit à une liste de diffusion (en message privé): /subscribe <liste> [email]")]
+    Subscribe(String),
+    #[command(description = "Se désinscrit d'une liste de diffusion (en message privé): /unsubscribe <liste>")]
+    Unsubscribe(String),
+    #[command(description = "Signale un problème à l'équipe informatique: /ticket <description>")]
+    Ticket(String),
+    #[command(description = "(Équipe informatique) Ferme un ticket et notifie son auteur: /ticketclose <id> [message]")]
+    TicketClose(String),
+    #[command(description = "Liste les inscrits à un événement: /participants <événement>")]
+    Participants(String),
+    #[command(description = "Enregistre l'entrée d'un participant à un événement: /checkin <code>")]
+    Checkin(String),
+    #[command(description = "Affiche le nombre de participants présents aux événements en cours")]
+    Attendance,
+    #[command(description = "Affiche les jours restants avant la fin du semestre, les examens et les vacances")]
+    Semester,
+    #[command(description = "Ajoute une période au calendrier académique: /semesteradd <début> <fin> <nom>")]
+    SemesterAdd(String),
+    #[command(description = "Supprime une période du calendrier académique: /semesterremove <id>")]
+    SemesterRemove(String),
+    #[command(description = "Lie un membre du comité à son compte: /memberlink <nom>, en réponse")]
+    MemberLink(String),
+    #[command(
+        description = "(Admin) Ouvre un tournoi de quiz: /tournamentstart <semaines> [nom]"
+    )]
+    TournamentStart(String),
+    #[command(description = "(Admin) Termine le tournoi de quiz en cours")]
+    TournamentStop,
+    #[command(
+        description = "(Admin) Corrige l'auteur de la citation d'un signalement: /quotefix <signalement> <auteur>"
+    )]
+    QuoteFix(String),
+    #[command(
+        description = "Choisit la langue du bot dans ce groupe / Sets the language of the bot in this chat: /language fr|en"
+    )]
+    Language(String),
+    #[command(
+        description = "Choisit le fuseau horaire de ce groupe / Sets the timezone of this chat: /timezone Europe/Zurich"
+    )]
+    Timezone(String),
+    #[command(
+        description = "(Admin) Déplace ou supprime les données d'un groupe: /chat remap|purge <id>"
+    )]
+    Chat(String),
+    #[command(description = "(Admin) Liste les groupes du bot et leurs autorisations")]
+    Chats,
+    #[command(
+        description = "(Admin) Envoie une annonce à tous les groupes autorisés: /broadcast <message>"
+    )]
+    Broadcast(String),
+    #[command(description = "(Admin) Compose une newsletter à envoyer aux groupes et canaux choisis")]
+    Newsletter,
+    #[command(
+        description = "(Admin) Lève la limitation d'un utilisateur qui a envoyé trop de commandes: /unthrottle <id>"
+    )]
+    Unthrottle(String),
+    #[command(
+        description = "(Admin) Liste les opérations les plus lentes, si les diagnostics sont activés"
+    )]
+    SlowLog,
+    #[command(description = "(Superadmin) Envoie une sauvegarde chiffrée de la base de données")]
+    Backup,
+    #[command(
+        description = "(Superadmin) Restaure la sauvegarde à laquelle le message répond"
+    )]
+    Restore,
+    #[command(
+        description = "(Superadmin) Envoie un export JSON des données (admins, groupes, citations...)"
+    )]
+    Export,
+    #[command(description = "(Superadmin) Importe l'export auquel le message répond")]
+    Import,
+    #[command(description = "(Superadmin) Liste les sessions des admins")]
+    Sessions,
+    #[command(description = "(Superadmin) Révoque immédiatement un admin: /revoke <nom>")]
+    Revoke(String),
+    #[command(
+        description = "(Superadmin) Exporte le journal d'audit et des commandes: /auditexport [période] [csv|json]"
+    )]
+    AuditExport(String),
 }
 
 impl Command {
+    /// Command of the given name, with empty or placeholder arguments.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::parse(&format!("/{}", name), "")
+            .or_else(|_| Self::parse(&format!("/{} _ _", name), ""))
+            .ok()
+    }
+
     // Used as key for the access control map
     pub fn shortand(&self) -> &str {
         match self {
             Self::Help => "help",
+            Self::Cancel => "cancel",
+            Self::Start(_) => "start",
             Self::Bureau => "bureau",
             Self::Poll => "poll",
             Self::Authenticate(..) => "auth",
             Self::AdminList => "adminlist",
             Self::AdminRemove(..) => "adminremove",
+            Self::AdminInvite(_) => "admininvite",
             Self::Authorize(..) => "authorize",
             Self::Unauthorize(..) => "unauthorize",
             Self::Authorizations => "authorizations",
+            Self::TopicBind(..) => "topicbind",
+            Self::AliasAdd(..) | Self::AliasRemove(..) | Self::Aliases => "alias",
+            Self::Undo => "undo",
+            Self::SetTemplate(_) => "settemplate",
+            Self::Retention(_) => "retention",
             Self::Stats => "stats",
+            Self::DirectusStatus => "directusstatus",
+            Self::CommitteeSync => "committeesync",
+            Self::Remind(..) => "remind",
+            Self::Reminders => "reminders",
+            Self::ScheduleAdd(..) => "scheduleadd",
+            Self::Schedules => "schedules",
+            Self::ScheduleRemove(..) => "scheduleremove",
+            Self::Publish(..) => "publish",
+            Self::Anon(..) => "anon",
+            Self::AnonBlock(..) => "anonblock",
+            Self::AnonUnblock(..) => "anonunblock",
+            Self::Calendar => "calendar",
+            Self::Doodle(..) => "doodle",
+            Self::DoodleClose => "doodleclose",
+            Self::Todo(..) => "todo",
+            Self::Countdown(..) => "countdown",
+            Self::Random(..) => "random",
+            Self::Debt(..) => "debt",
+            Self::Karma(..) => "karma",
+            Self::Menu(..) => "menu",
+            Self::Metro => "metro",
+            Self::Transport(..) => "transport",
+            Self::Expense(..) => "expense",
+            Self::Loan(..) => "loan",
+            Self::Link(..) | Self::Links(..) => "link",
+            Self::HallOfFame => "halloffame",
+            Self::NewPoll => "newpoll",
+            Self::Vote(..) => "vote",
+            Self::Pin => "pin",
+            Self::UnpinAll => "unpinall",
+            Self::AutoPin(..) => "autopin",
+            Self::Tournament => "tournament",
+            Self::ClosePoll => "closepoll",
+            Self::Settings => "settings",
+            Self::ReactionStats => "reactionstats",
+            Self::Shame(_) => "shame",
+            Self::Gg(_) => "gg",
+            Self::Shames(_) => "shames",
+            Self::Room(_) => "room",
+            Self::Satellite => "satellite",
+            Self::Shop => "shop",
+            Self::ShopUpdate => "shopupdate",
+            Self::Wiki(_) => "wiki",
+            Self::Subscribe(_) => "subscribe",
+            Self::Unsubscribe(_) => "unsubscribe",
+            Self::Ticket(_) => "ticket",
+            Self::TicketClose(_) => "ticketclose",
+            Self::Participants(_) => "participants",
+            Self::Checkin(_) => "checkin",
+            Self::Attendance => "attendance",
+            Self::Semester => "semester",
+            Self::SemesterAdd(_) => "semesteradd",
+            Self::SemesterRemove(_) => "semesterremove",
+            Self::MemberLink(_) => "memberlink",
+            Self::TournamentStart(..) => "tournamentstart",
+            Self::TournamentStop => "tournamentstop",
+            Self::QuoteFix(..) => "quotefix",
+            Self::Language(..) => "language",
+            Self::Timezone(..) => "timezone",
+            Self::Chat(..) => "chat",
+            Self::Chats => "chats",
+            Self::Broadcast(..) => "broadcast",
+            Self::Newsletter => "newsletter",
+            Self::Unthrottle(..) => "unthrottle",
+            Self::SlowLog => "slowlog",
+            Self::Backup => "backup",
+            Self::Restore => "restore",
+            Self::Export => "export",
+            Self::Import => "import",
+            Self::Sessions => "sessions",
+            Self::Revoke(_) => "revoke",
+            Self::AuditExport(_) => "auditexport",
         }
     }
 }
 
+/// Declares, for each command, its arguments, its endpoint, who can run it and its category in the
+/// `/help` menu. [`Command::access`], [`Command::category`] and the endpoints of
+/// [`command_message_handler`] all derive from this table, so that `/help` cannot disagree with
+/// the checks of the handler tree.
+macro_rules! command_table {
+    ($($variant:ident $(($($arg:ident),+))? => $endpoint:ident, $access:ident, $category:ident;)*) => {
+        impl Command {
+            /// Who can run the command, as checked by the branches of [`command_message_handler`].
+            pub fn access(&self) -> Access {
+                match self {
+                    $(Self::$variant { .. } => Access::$access,)*
+                }
+            }
+
+            /// Category of the command in the `/help` menu.
+            pub fn category(&self) -> Category {
+                match self {
+                    $(Self::$variant { .. } => Category::$category,)*
+                }
+            }
+        }
+
+        /// Endpoints of the commands with the given access, which the caller checks.
+        fn command_endpoints(
+            access: Access,
+        ) -> Endpoint<'static, DependencyMap, HandlerResult, DpHandlerDescription> {
+            let mut endpoints = dptree::entry();
+            $(
+                if access == Access::$access {
+                    endpoints = endpoints.branch(
+                        dptree::case![Command::$variant $(($($arg),+))?].endpoint($endpoint),
+                    );
+                }
+            )*
+            endpoints
+        }
+    };
+}
+
+command_table! {
+    Help => help, Everyone, General;
+    Cancel => cancel, Everyone, General;
+    Start(payload) => start, Everyone, General;
+    Authenticate(token, name) => authenticate, Everyone, General;
+    Anon(text) => anon, Everyone, General;
+    Subscribe(args) => subscribe, Everyone, General;
+    Unsubscribe(args) => unsubscribe, Everyone, General;
+
+    // Checked by the endpoint against the IT team, who are not necessarily admins
+    TicketClose(args) => ticket_close, ItTeam, Admin;
+
+    // Checked by the endpoints against the superadmin, who might not be admin in a restored
+    // database
+    Backup => backup, Superadmin, Admin;
+    Restore => restore, Superadmin, Admin;
+    Export => export, Superadmin, Admin;
+    Import => import, Superadmin, Admin;
+    Sessions => sessions, Superadmin, Admin;
+    Revoke(name) => revoke_admin, Superadmin, Admin;
+    AuditExport(args) => audit_export, Superadmin, Admin;
+    Broadcast(text) => broadcast, Superadmin, Admin;
+
+    Bureau => bureau, AuthorizedChat, Bureau;
+    Poll => start_poll_dialogue, AuthorizedChat, Fun;
+    Stats => stats, AuthorizedChat, Fun;
+    Remind(args) => remind, AuthorizedChat, Bureau;
+    Reminders => reminders, AuthorizedChat, Bureau;
+    Calendar => calendar, AuthorizedChat, General;
+    Doodle(args) => doodle, AuthorizedChat, Bureau;
+    DoodleClose => doodle_close, AuthorizedChat, Bureau;
+    Todo(args) => todo, AuthorizedChat, Bureau;
+    Countdown(args) => countdown, AuthorizedChat, Bureau;
+    Random(args) => random, AuthorizedChat, Fun;
+    Debt(args) => debt, AuthorizedChat, Bureau;
+    Karma(args) => karma, AuthorizedChat, Fun;
+    Menu(args) => menu, AuthorizedChat, General;
+    Metro => metro, AuthorizedChat, General;
+    Transport(args) => transport, AuthorizedChat, General;
+    Expense(args) => expense, AuthorizedChat, Bureau;
+    Loan(args) => loan, AuthorizedChat, Bureau;
+    Link(args) => link, AuthorizedChat, Bureau;
+    Links(args) => links, AuthorizedChat, Bureau;
+    HallOfFame => halloffame, AuthorizedChat, Fun;
+    Language(args) => language, AuthorizedChat, General;
+    Timezone(args) => timezone, AuthorizedChat, General;
+    NewPoll => start_newpoll_dialogue, AuthorizedChat, Fun;
+    Vote(question) => vote, AuthorizedChat, Fun;
+    Pin => pin, AuthorizedChat, Bureau;
+    UnpinAll => unpin_all, AuthorizedChat, Bureau;
+    AutoPin(args) => autopin, AuthorizedChat, Bureau;
+    Tournament => tournament, AuthorizedChat, Fun;
+    ClosePoll => close_poll, AuthorizedChat, Fun;
+    Settings => settings, AuthorizedChat, General;
+    ReactionStats => reaction_stats, AuthorizedChat, Fun;
+    Shame(args) => shame, AuthorizedChat, Fun;
+    Gg(args) => gg, AuthorizedChat, Fun;
+    Shames(args) => shames, AuthorizedChat, Fun;
+    Room(name) => room, AuthorizedChat, General;
+    Semester => semester, AuthorizedChat, General;
+    Satellite => satellite, AuthorizedChat, General;
+    Shop => shop, AuthorizedChat, Bureau;
+    Wiki(query) => wiki, AuthorizedChat, General;
+    Ticket(description) => ticket, AuthorizedChat, Bureau;
+    Participants(args) => participants, AuthorizedChat, Bureau;
+    Checkin(code) => checkin, AuthorizedChat, Bureau;
+    Attendance => attendance_count, AuthorizedChat, Bureau;
+
+    AdminList => admin_list, Admin, Admin;
+    AdminRemove(name) => admin_remove, Admin, Admin;
+    AdminInvite(name) => admin_invite, Admin, Admin;
+    Authorize(command) => authorize, Admin, Admin;
+    Unauthorize(command) => unauthorize, Admin, Admin;
+    Authorizations => authorizations, Admin, Admin;
+    TopicBind(command) => topic_bind, Admin, Admin;
+    AliasAdd(args) => alias_add, Admin, Admin;
+    AliasRemove(alias) => alias_remove, Admin, Admin;
+    Aliases => aliases, Admin, Admin;
+    Undo => undo, Admin, Admin;
+    SetTemplate(args) => set_template, Admin, Admin;
+    Retention(args) => retention, Admin, Admin;
+    DirectusStatus => directus_status, Admin, Admin;
+    CommitteeSync => committee_sync, Admin, Admin;
+    ScheduleAdd(args) => schedule_add, Admin, Admin;
+    Schedules => schedules, Admin, Admin;
+    ScheduleRemove(id) => schedule_remove, Admin, Admin;
+    Publish(payload) => publish, Admin, Admin;
+    TournamentStart(args) => tournament_start, Admin, Admin;
+    TournamentStop => tournament_stop, Admin, Admin;
+    QuoteFix(args) => quote_fix, Admin, Admin;
+    MemberLink(name) => member_link, Admin, Admin;
+    ShopUpdate => shop_update, Admin, Admin;
+    SemesterAdd(args) => semester_add, Admin, Admin;
+    SemesterRemove(id) => semester_remove, Admin, Admin;
+    AnonBlock(hash) => anon_block, Admin, Admin;
+    AnonUnblock(hash) => anon_unblock, Admin, Admin;
+    Chat(args) => chat, Admin, Admin;
+    Chats => chats, Admin, Admin;
+    Unthrottle(id) => unthrottle, Admin, Admin;
+    SlowLog => slow_log, Admin, Admin;
+    Newsletter => newsletter, Admin, Admin;
+}
+
+/// Who can run a command.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Access {
+    Everyone,
+    /// Anyone in a chat authorized to use the command.
+    AuthorizedChat,
+    Admin,
+    Superadmin,
+    ItTeam,
+}
+
+/// Categories of the `/help` menu.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Category {
+    General,
+    Fun,
+    Bureau,
+    Admin,
+}
+
 // ---------------------------- COMMAND ENDPOINTS -----------------------------
 
-async fn help(bot: Bot, msg: Message) -> HandlerResult {
-    bot.send_message(msg.chat.id, Command::descriptions().to_string())
-        .await?;
+async fn edited_command(bot: Bot, msg: Message, lang: Lang) -> HandlerResult {
+    bot.send_message(
+        msg.chat.id,
+        tr!(
+            lang,
+            "Les messages modifiés ne sont pas pris en compte, envoie la commande dans un nouveau message",
+            "Edited messages are ignored, send the command in a new message"
+        ),
+    )
+    .reply_to_message_id(msg.id)
+    .queued()
+    .await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, sync::Arc};
+
+    use async_trait::async_trait;
+    use teloxide::{
+        dptree,
+        types::{ChatId, Message, UserId},
+    };
+
+    use super::require_admin;
+    use crate::db::admins::{Admin, AdminRepo, Session};
+
+    /// Admins kept in memory, standing in for the database.
+    struct MockAdmins(HashSet<UserId>);
+
+    #[async_trait]
+    impl AdminRepo for MockAdmins {
+        async fn is_admin(&self, user: UserId) -> bool {
+            self.0.contains(&user)
+        }
+
+        async fn count_admins(&self) -> Result<i64, sqlx::Error> {
+            Ok(self.0.len() as i64)
+        }
+
+        async fn admins(&self) -> Result<Vec<Admin>, sqlx::Error> {
+            Ok(self
+                .0
+                .iter()
+                .map(|id| Admin {
+                    telegram_id: id.to_string(),
+                    name: format!("Admin {}", id),
+                })
+                .collect())
+        }
+
+        async fn admin_private_chats(&self) -> Result<Vec<ChatId>, sqlx::Error> {
+            Ok(self.0.iter().map(|id| ChatId(id.0 as i64)).collect())
+        }
+
+        async fn admin_sessions(&self) -> Result<Vec<Session>, sqlx::Error> {
+            Ok(vec![])
+        }
+
+        async fn add_admin(
+            &self,
+            _: &str,
+            _: &str,
+            _: &str,
+            _: Option<&str>,
+            _: i64,
+        ) -> Result<bool, sqlx::Error> {
+            unimplemented!("read-only mock")
+        }
+
+        async fn add_or_rename_admin(
+            &self,
+            _: &str,
+            _: &str,
+            _: &str,
+            _: i64,
+        ) -> Result<(), sqlx::Error> {
+            unimplemented!("read-only mock")
+        }
+
+        async fn remove_admins(&self, _: &str) -> Result<Vec<String>, sqlx::Error> {
+            unimplemented!("read-only mock")
+        }
+
+        async fn remove_admin_id(&self, _: &str) -> Result<bool, sqlx::Error> {
+            unimplemented!("read-only mock")
+        }
+    }
+
+    /// Command sent by the user in a private chat with the bot.
+    fn command_from(user: u64) -> Message {
+        serde_json::from_value(serde_json::json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": { "id": user, "type": "private", "first_name": "Test" },
+            "from": { "id": user, "is_bot": false, "first_name": "Test" },
+            "text": "/adminlist",
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn require_admin_only_lets_the_admins_through() {
+        let admins: Arc<dyn AdminRepo> = Arc::new(MockAdmins(HashSet::from([UserId(1)])));
+        let handler = require_admin().endpoint(|| async { Ok(()) });
+
+        // The endpoint is only reached through the filter
+        let admin = handler
+            .dispatch(dptree::deps![command_from(1), admins.clone()])
+            .await;
+        assert!(admin.is_break());
+        let member = handler
+            .dispatch(dptree::deps![command_from(2), admins])
+            .await;
+        assert!(member.is_continue());
+    }
+}
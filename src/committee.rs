@@ -0,0 +1,68 @@
+use std::{
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::RwLock;
+
+use crate::{
+    directus::{get_committee, Committee, Error},
+    stats::{record_stat, Stat},
+};
+
+/// Duration after which the committee is fetched again, to pick up the members added or removed
+/// in Directus.
+const COMMITTEE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Committee members, fetched from Directus and kept in memory, so that `/poll`, `/stats` and
+/// `/random` do not query Directus on every invocation.
+#[derive(Default)]
+pub struct CommitteeRepository {
+    cache: RwLock<Option<(Instant, Vec<Committee>)>>,
+}
+
+static REPOSITORY: OnceLock<CommitteeRepository> = OnceLock::new();
+pub fn committee_repository() -> &'static CommitteeRepository {
+    REPOSITORY.get_or_init(CommitteeRepository::default)
+}
+
+impl CommitteeRepository {
+    /// Members of the committee, fetched from Directus if the cache is empty or outdated.
+    pub async fn get(&self) -> Result<Vec<Committee>, Error> {
+        if let Some((fetched_at, committee)) = self.cache.read().await.as_ref() {
+            if fetched_at.elapsed() < COMMITTEE_TTL {
+                return Ok(committee.clone());
+            }
+        }
+        self.refresh().await
+    }
+
+    /// Fetches the committee from Directus, replacing the cached one.
+    pub async fn refresh(&self) -> Result<Vec<Committee>, Error> {
+        let committee = get_committee().await?;
+        *self.cache.write().await = Some((Instant::now(), committee.clone()));
+        Ok(committee)
+    }
+
+    /// Saves the poll counts of the members in the cache, and queues the changed ones to be saved
+    /// in Directus.
+    pub async fn update(&self, committee: Vec<Committee>) {
+        let mut cache = self.cache.write().await;
+        for member in committee {
+            let cached = cache
+                .as_mut()
+                .and_then(|(_, cached)| cached.iter_mut().find(|c| c.id == member.id));
+            match cached {
+                Some(c) if c.poll_count == member.poll_count => continue,
+                Some(c) => c.poll_count = member.poll_count,
+                None => {}
+            }
+            record_stat(Stat::PollCount(member));
+        }
+    }
+
+    /// Forgets the cached committee, so that it is fetched again on the next use.
+    pub async fn invalidate(&self) {
+        *self.cache.write().await = None;
+    }
+}
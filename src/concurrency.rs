@@ -0,0 +1,48 @@
+//! Concurrency of the dispatcher: how the updates are grouped, and how many handlers can run at
+//! the same time, so that a slow handler in one chat does not delay the other chats.
+
+use std::sync::{Arc, OnceLock};
+
+use teloxide::types::Update;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config::{config, Distribution};
+
+/// Key of the group of the update, the updates of a group being handled in order. `None` puts the
+/// update in the default group, handled one after the other.
+pub fn distribution_key(update: &Update) -> Option<i64> {
+    let chat = update.chat().map(|c| c.id.0);
+    let user = update.user().map(|u| u.id.0 as i64);
+    match config().dispatcher_distribution {
+        Distribution::Chat => chat.or(user),
+        Distribution::User => user.or(chat),
+        Distribution::Global => Some(0),
+    }
+}
+
+/// Slot of a running handler, released when the handling of the update ends.
+pub struct HandlerSlot {
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+fn semaphore() -> Option<Arc<Semaphore>> {
+    static SEMAPHORE: OnceLock<Option<Arc<Semaphore>>> = OnceLock::new();
+    SEMAPHORE
+        .get_or_init(|| {
+            config()
+                .dispatcher_max_handlers
+                .map(|max| Arc::new(Semaphore::new(max)))
+        })
+        .clone()
+}
+
+/// Waits for a slot when `DISPATCHER_MAX_HANDLERS` handlers are already running. Mapped at the
+/// entry of the dispatcher, the slot then lives in the dependencies of the update until it is
+/// handled.
+pub async fn acquire_slot() -> HandlerSlot {
+    let permit = match semaphore() {
+        Some(semaphore) => semaphore.acquire_owned().await.ok(),
+        None => None,
+    };
+    HandlerSlot { _permit: permit }
+}
@@ -1,5 +1,9 @@
 use envconfig::Envconfig;
-use std::sync::OnceLock;
+use reqwest::Url;
+use sqlx::sqlite::{SqliteJournalMode, SqliteSynchronous};
+use std::{env, fs, path::Path, str::FromStr, sync::OnceLock};
+
+use crate::db;
 
 #[derive(Envconfig)]
 pub struct Config {
@@ -9,15 +13,367 @@ pub struct Config {
     pub data_dir: String,
     #[envconfig(from = "DATABASE_URL")]
     pub database_url: Option<String>,
+    #[envconfig(from = "DATABASE_MAX_CONNECTIONS", default = "10")]
+    pub database_max_connections: u32,
+    #[envconfig(from = "DATABASE_ACQUIRE_TIMEOUT", default = "30")]
+    pub database_acquire_timeout: u64,
+    #[envconfig(from = "SQLITE_JOURNAL_MODE", default = "wal")]
+    pub sqlite_journal_mode: String,
+    #[envconfig(from = "SQLITE_SYNCHRONOUS", default = "full")]
+    pub sqlite_synchronous: String,
+    #[envconfig(from = "SQLITE_CACHE_SIZE")]
+    pub sqlite_cache_size: Option<i64>,
+    #[envconfig(from = "DISPATCHER_DISTRIBUTION", default = "chat")]
+    pub dispatcher_distribution: Distribution,
+    #[envconfig(from = "DISPATCHER_MAX_HANDLERS")]
+    pub dispatcher_max_handlers: Option<usize>,
+    #[envconfig(from = "DISPATCHER_QUEUE_SIZE", default = "64")]
+    pub dispatcher_queue_size: usize,
     #[envconfig(from = "ADMIN_TOKEN")]
     pub admin_token: String,
+    #[envconfig(from = "ADMIN_TOKEN_AUTO_ROTATE", default = "false")]
+    pub admin_token_auto_rotate: bool,
     #[envconfig(from = "DIRECTUS_URL")]
     pub directus_url: String,
     #[envconfig(from = "DIRECTUS_TOKEN")]
-    pub directus_token: String,
+    pub directus_token: Option<String>,
+    #[envconfig(from = "DIRECTUS_EMAIL")]
+    pub directus_email: Option<String>,
+    #[envconfig(from = "DIRECTUS_PASSWORD")]
+    pub directus_password: Option<String>,
+    #[envconfig(from = "COMMITTEE_CHAT_ID")]
+    pub committee_chat_id: Option<i64>,
+    #[envconfig(from = "ANON_SALT")]
+    pub anon_salt: Option<String>,
+    #[envconfig(from = "MENU_API_URL")]
+    pub menu_api_url: Option<String>,
+    #[envconfig(from = "ROOM_API_URL")]
+    pub room_api_url: Option<String>,
+    #[envconfig(from = "SATELLITE_API_URL")]
+    pub satellite_api_url: Option<String>,
+    #[envconfig(from = "MAILING_LISTS")]
+    pub mailing_lists: Option<String>,
+    #[envconfig(from = "MAILING_API_URL")]
+    pub mailing_api_url: Option<String>,
+    #[envconfig(from = "MAILING_API_TOKEN")]
+    pub mailing_api_token: Option<String>,
+    #[envconfig(from = "TREASURER_IDS")]
+    pub treasurer_ids: Option<String>,
+    #[envconfig(from = "IT_TEAM_IDS")]
+    pub it_team_ids: Option<String>,
+    #[envconfig(from = "METRICS_ADDRESS")]
+    pub metrics_address: Option<String>,
+    #[envconfig(from = "DASHBOARD_ADDRESS")]
+    pub dashboard_address: Option<String>,
+    #[envconfig(from = "API_ADDRESS")]
+    pub api_address: Option<String>,
+    #[envconfig(from = "API_TOKEN")]
+    pub api_token: Option<String>,
+    #[envconfig(from = "WEBHOOK_URL")]
+    pub webhook_url: Option<String>,
+    #[envconfig(from = "WEBHOOK_ADDRESS")]
+    pub webhook_address: Option<String>,
+    #[envconfig(from = "HTTP_ALLOWED_IPS")]
+    pub http_allowed_ips: Option<String>,
+    #[envconfig(from = "SUPERADMIN_ID")]
+    pub superadmin_id: Option<u64>,
+    #[envconfig(from = "BACKUP_KEY")]
+    pub backup_key: Option<String>,
+    #[envconfig(from = "DATA_KEY")]
+    pub data_key: Option<String>,
+    #[envconfig(from = "MAINTENANCE_CRON", default = "0 4 * * *")]
+    pub maintenance_cron: String,
+    #[envconfig(from = "AUDIT_RETENTION_DAYS", default = "365")]
+    pub audit_retention_days: i64,
+    #[envconfig(from = "DIALOGUE_RETENTION_DAYS", default = "7")]
+    pub dialogue_retention_days: i64,
+    #[envconfig(from = "DIAGNOSTICS_THRESHOLD_MS")]
+    pub diagnostics_threshold_ms: Option<u64>,
+    #[envconfig(from = "OPS_CHAT_ID")]
+    pub ops_chat_id: Option<i64>,
+}
+
+/// How the updates are grouped by the dispatcher: the updates of a group are handled one after the
+/// other, in order, while the groups are handled concurrently.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Distribution {
+    /// Per chat, or per user for the updates outside of a chat (e.g. poll answers).
+    Chat,
+    /// Per user, or per chat for the updates without a user (e.g. channel posts).
+    User,
+    /// A single group, all the updates are handled one after the other.
+    Global,
+}
+
+impl FromStr for Distribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "chat" => Ok(Self::Chat),
+            "user" => Ok(Self::User),
+            "global" => Ok(Self::Global),
+            _ => Err(format!("unknown distribution {}", s)),
+        }
+    }
 }
 
 static CONFIG: OnceLock<Config> = OnceLock::new();
 pub fn config() -> &'static Config {
     CONFIG.get_or_init(|| Config::init_from_env().unwrap())
 }
+
+/// Value of the variable, `None` if unset or empty.
+fn var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|v| !v.trim().is_empty())
+}
+
+fn check_required(problems: &mut Vec<String>, name: &str, hint: &str) {
+    if var(name).is_none() {
+        problems.push(format!("{} is missing: {}", name, hint));
+    }
+}
+
+fn check_parse<T: FromStr>(problems: &mut Vec<String>, name: &str, expected: &str) {
+    // Not `var`, since an empty value fails to parse too
+    if let Ok(value) = env::var(name) {
+        if value.parse::<T>().is_err() {
+            problems.push(format!(
+                "{} is invalid (\"{}\"): expected {}",
+                name, value, expected
+            ));
+        }
+    }
+}
+
+fn check_url(problems: &mut Vec<String>, name: &str) {
+    if let Some(value) = var(name) {
+        if !Url::parse(&value).is_ok_and(|u| matches!(u.scheme(), "http" | "https")) {
+            problems.push(format!(
+                "{} is invalid (\"{}\"): expected an http(s) url, e.g. https://example.com",
+                name, value
+            ));
+        }
+    }
+}
+
+fn check_address(problems: &mut Vec<String>, name: &str) {
+    if let Some(value) = var(name) {
+        let valid = value
+            .rsplit_once(':')
+            .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok());
+        if !valid {
+            problems.push(format!(
+                "{} is invalid (\"{}\"): expected a host and a port, e.g. 0.0.0.0:8080",
+                name, value
+            ));
+        }
+    }
+}
+
+fn check_ids(problems: &mut Vec<String>, name: &str) {
+    if let Some(value) = var(name) {
+        let invalid = value
+            .split(',')
+            .map(str::trim)
+            .filter(|id| !id.is_empty() && id.parse::<u64>().is_err())
+            .collect::<Vec<_>>();
+        if !invalid.is_empty() {
+            problems.push(format!(
+                "{} contains invalid Telegram ids ({}): expected comma-separated numbers",
+                name,
+                invalid.join(", ")
+            ));
+        }
+    }
+}
+
+/// Checks of the loaded config against the filesystem and the database: `DATA_DIR` must be
+/// writable and the database must open. Shared by the bot and `roboclic-admin`.
+pub async fn check_storage() -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let data_dir = Path::new(&config().data_dir);
+    let probe = data_dir.join(".write-check");
+    if !data_dir.is_dir() {
+        problems.push(format!(
+            "DATA_DIR ({}) does not exist: create it or change DATA_DIR",
+            data_dir.display()
+        ));
+    } else if let Err(e) = fs::write(&probe, b"").and_then(|_| fs::remove_file(&probe)) {
+        problems.push(format!(
+            "DATA_DIR ({}) is not writable ({}): give the bot write access to it",
+            data_dir.display(),
+            e
+        ));
+    }
+
+    let url = db::database_url();
+    match db::connect(&url).await {
+        Ok(pool) => {
+            if let Err(e) = sqlx::query("SELECT 1").execute(&pool).await {
+                problems.push(format!("The database ({}) cannot be queried: {}", url, e));
+            }
+            pool.close().await;
+        }
+        Err(e) => problems.push(format!(
+            "The database ({}) cannot be opened: {}. Check DATABASE_URL and DATA_DIR",
+            url, e
+        )),
+    }
+
+    problems
+}
+
+/// Checks every variable of the environment, so that all the problems are reported at once
+/// instead of the first one only. Each problem tells what to change. Empty when the config can be
+/// loaded.
+pub fn check_config() -> Vec<String> {
+    let mut problems = Vec::new();
+
+    check_required(
+        &mut problems,
+        "BOT_TOKEN",
+        "set it to the token given by @BotFather",
+    );
+    if let Some(token) = var("BOT_TOKEN") {
+        let valid = token
+            .split_once(':')
+            .is_some_and(|(id, secret)| id.parse::<u64>().is_ok() && !secret.is_empty());
+        if !valid {
+            problems.push(
+                "BOT_TOKEN is invalid: expected the <id>:<secret> token given by @BotFather"
+                    .to_owned(),
+            );
+        }
+    }
+    check_required(
+        &mut problems,
+        "ADMIN_TOKEN",
+        "set it to a long random secret, used to authenticate the first admin",
+    );
+    check_required(
+        &mut problems,
+        "DATA_DIR",
+        "set it to the directory where the bot stores its data",
+    );
+    if let Some(url) = var("DATABASE_URL") {
+        if !url.starts_with("sqlite:") {
+            problems.push(format!(
+                "DATABASE_URL is invalid (\"{}\"): expected a sqlite:// url",
+                url
+            ));
+        }
+    }
+
+    check_required(
+        &mut problems,
+        "DIRECTUS_URL",
+        "set it to the base url of the Directus instance",
+    );
+    check_url(&mut problems, "DIRECTUS_URL");
+    match (var("DIRECTUS_EMAIL"), var("DIRECTUS_PASSWORD")) {
+        (Some(_), None) => problems
+            .push("DIRECTUS_PASSWORD is missing: it is required with DIRECTUS_EMAIL".to_owned()),
+        (None, Some(_)) => problems
+            .push("DIRECTUS_EMAIL is missing: it is required with DIRECTUS_PASSWORD".to_owned()),
+        (None, None) if var("DIRECTUS_TOKEN").is_none() => problems.push(
+            "The Directus credentials are missing: set DIRECTUS_TOKEN, or DIRECTUS_EMAIL and \
+            DIRECTUS_PASSWORD"
+                .to_owned(),
+        ),
+        _ => {}
+    }
+    for name in [
+        "MENU_API_URL",
+        "ROOM_API_URL",
+        "SATELLITE_API_URL",
+        "MAILING_API_URL",
+    ] {
+        check_url(&mut problems, name);
+    }
+    for name in [
+        "METRICS_ADDRESS",
+        "DASHBOARD_ADDRESS",
+        "API_ADDRESS",
+        "WEBHOOK_ADDRESS",
+    ] {
+        check_address(&mut problems, name);
+    }
+    if let Some(url) = var("WEBHOOK_URL") {
+        if !Url::parse(&url).is_ok_and(|u| u.scheme() == "https") {
+            problems.push(format!(
+                "WEBHOOK_URL is invalid (\"{}\"): expected an https url, e.g. https://bot.example.com/telegram",
+                url
+            ));
+        }
+    }
+    match (var("WEBHOOK_URL"), var("WEBHOOK_ADDRESS")) {
+        (Some(_), None) => {
+            problems.push("WEBHOOK_ADDRESS is missing: it is required with WEBHOOK_URL".to_owned())
+        }
+        (None, Some(_)) => {
+            problems.push("WEBHOOK_URL is missing: it is required with WEBHOOK_ADDRESS".to_owned())
+        }
+        _ => {}
+    }
+
+    if env::var("DATABASE_MAX_CONNECTIONS").is_ok_and(|v| v.parse::<u32>().map_or(true, |n| n == 0))
+    {
+        problems.push(
+            "DATABASE_MAX_CONNECTIONS is invalid: expected a positive number of connections"
+                .to_owned(),
+        );
+    }
+    check_parse::<Distribution>(
+        &mut problems,
+        "DISPATCHER_DISTRIBUTION",
+        "chat, user or global",
+    );
+    for name in ["DISPATCHER_MAX_HANDLERS", "DISPATCHER_QUEUE_SIZE"] {
+        if env::var(name).is_ok_and(|v| v.parse::<usize>().map_or(true, |n| n == 0)) {
+            problems.push(format!("{} is invalid: expected a positive number", name));
+        }
+    }
+    check_parse::<u64>(
+        &mut problems,
+        "DATABASE_ACQUIRE_TIMEOUT",
+        "a number of seconds",
+    );
+    check_parse::<SqliteJournalMode>(
+        &mut problems,
+        "SQLITE_JOURNAL_MODE",
+        "delete, truncate, persist, memory, wal or off",
+    );
+    check_parse::<SqliteSynchronous>(
+        &mut problems,
+        "SQLITE_SYNCHRONOUS",
+        "off, normal, full or extra",
+    );
+    check_parse::<i64>(
+        &mut problems,
+        "SQLITE_CACHE_SIZE",
+        "a number of pages, or of KiB if negative",
+    );
+    check_parse::<bool>(&mut problems, "ADMIN_TOKEN_AUTO_ROTATE", "true or false");
+    check_parse::<u64>(
+        &mut problems,
+        "DIAGNOSTICS_THRESHOLD_MS",
+        "a number of milliseconds",
+    );
+    check_parse::<i64>(&mut problems, "COMMITTEE_CHAT_ID", "a Telegram chat id");
+    check_parse::<i64>(&mut problems, "OPS_CHAT_ID", "a Telegram chat id");
+    check_parse::<u64>(&mut problems, "SUPERADMIN_ID", "a Telegram user id");
+    check_ids(&mut problems, "TREASURER_IDS");
+    check_ids(&mut problems, "IT_TEAM_IDS");
+    for name in ["AUDIT_RETENTION_DAYS", "DIALOGUE_RETENTION_DAYS"] {
+        if env::var(name).is_ok_and(|v| v.parse::<i64>().map_or(true, |d| d <= 0)) {
+            problems.push(format!(
+                "{} is invalid: expected a positive number of days",
+                name
+            ));
+        }
+    }
+
+    problems
+}
@@ -0,0 +1,110 @@
+//! Encryption of the sensitive columns, so that a leaked copy of the database does not
+//! compromise the bot.
+//!
+//! The values which are looked up (the senders of the anonymous messages, the invitations) are
+//! stored as keyed hashes instead, see [`blind_index`].
+//!
+//! The key of the columns, the salt of the anonymous senders and the key of the backups derive
+//! from `DATA_KEY` or, without it, from a random secret stored in `DATA_DIR`, see
+//! [`derived_secret`]. Unlike the admin token, none is ever sent in a chat, nor stored in the
+//! database which is backed up, and changing `ADMIN_TOKEN` after a leak keeps the data readable.
+
+use std::{fs, io, path::Path, sync::OnceLock};
+
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use rand::{thread_rng, RngCore};
+use sha2::{Digest, Sha256};
+
+use crate::config::config;
+
+/// Prefix of the encrypted values, followed by the base64 of the nonce and the ciphertext.
+const PREFIX: &str = "enc1:";
+const NONCE_SIZE: usize = 12;
+/// File of `DATA_DIR` holding the random secret of the installation.
+const SECRET_FILE: &str = "secret";
+
+static LOCAL_SECRET: OnceLock<String> = OnceLock::new();
+
+/// Loads the random secret of the installation, generating it on the first start.
+pub fn load_local_secret() -> io::Result<()> {
+    let path = Path::new(&config().data_dir).join(SECRET_FILE);
+    let secret = match fs::read_to_string(&path) {
+        Ok(secret) => secret.trim().to_owned(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let mut bytes = [0u8; 32];
+            thread_rng().fill_bytes(&mut bytes);
+            let secret = hex::encode(bytes);
+            fs::write(&path, &secret)?;
+            log::info!(
+                "Generated the secret of the installation in {}",
+                path.display()
+            );
+            secret
+        }
+        Err(e) => return Err(e),
+    };
+    let _ = LOCAL_SECRET.set(secret);
+    Ok(())
+}
+
+/// Secret for the given purpose, derived from `DATA_KEY` or the random secret of the installation.
+pub fn derived_secret(purpose: &str) -> String {
+    let secret = config().data_key.as_deref().unwrap_or_else(|| {
+        LOCAL_SECRET
+            .get()
+            .expect("the local secret is loaded at startup")
+    });
+    hex::encode(Sha256::digest(format!("{}:{}", purpose, secret)))
+}
+
+fn key() -> Vec<u8> {
+    hex::decode(derived_secret("data")).expect("the derived secret is hex")
+}
+
+pub fn encrypt(value: &str) -> String {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key()));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let encrypted = cipher
+        .encrypt(&nonce, value.as_bytes())
+        .expect("encryption cannot fail with a valid key");
+    format!(
+        "{}{}",
+        PREFIX,
+        STANDARD.encode([nonce.as_slice(), &encrypted].concat())
+    )
+}
+
+/// Decrypts a value of [`encrypt`]. Returns `None` if it is not encrypted, or was encrypted with
+/// another key.
+pub fn decrypt(value: &str) -> Option<String> {
+    let data = STANDARD.decode(value.strip_prefix(PREFIX)?).ok()?;
+    if data.len() < NONCE_SIZE {
+        return None;
+    }
+    let (nonce, encrypted) = data.split_at(NONCE_SIZE);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key()));
+    let decrypted = cipher.decrypt(Nonce::from_slice(nonce), encrypted).ok()?;
+    String::from_utf8(decrypted).ok()
+}
+
+/// Compares the secrets in a time independent of their content, so that the response time does
+/// not tell how much of a guess is right. The digests have the same length whatever the inputs.
+pub fn secrets_equal(a: &str, b: &str) -> bool {
+    Sha256::digest(a)
+        .iter()
+        .zip(Sha256::digest(b).iter())
+        .fold(0, |diff, (x, y)| diff | (x ^ y))
+        == 0
+}
+
+/// Keyed hash of the value, which can be looked up without revealing it.
+pub fn blind_index(value: &str) -> String {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&key()).expect("HMAC accepts any key size");
+    mac.update(value.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
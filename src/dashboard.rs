@@ -0,0 +1,794 @@
+//! Web dashboard for the admins, served when `DASHBOARD_ADDRESS` is set.
+//!
+//! The pages are protected by HTTP basic authentication, with the admin token as password (the
+//! username is only used to identify the admin in the audit log). An address failing to
+//! authenticate too often is locked out for a while. The forms carry a token derived from the
+//! admin token and a random session cookie, so that other websites cannot submit them on behalf
+//! of a logged in admin.
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    net::{IpAddr, SocketAddr},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, Query, Request, State},
+    http::{
+        header::{AUTHORIZATION, COOKIE, SET_COOKIE, WWW_AUTHENTICATE},
+        request::Parts,
+        HeaderValue, StatusCode,
+    },
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Form, Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rand::Rng;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    admin_token::admin_token,
+    aliases::is_command,
+    audit::audit,
+    committee::committee_repository,
+    crypto::secrets_equal,
+    dates::{format_datetime, from_timestamp, now, TIMEZONE},
+    format::escape,
+    http::serve,
+    state::AppState,
+};
+
+/// Number of entries displayed in the long lists (quotes, audit log).
+const PAGE_SIZE: i64 = 200;
+/// Failed authentications after which an address is locked out.
+const MAX_FAILED_LOGINS: u32 = 5;
+/// Duration of the lockout, counted from the last failure.
+const LOGIN_LOCKOUT: Duration = Duration::from_secs(15 * 60);
+/// Cookie identifying the session of the browser, from which the anti-forgery token is derived.
+const SESSION_COOKIE: &str = "dashboard_session";
+
+/// Failed authentications of each address, with the time of the last one.
+static FAILED_LOGINS: Mutex<Option<HashMap<IpAddr, (u32, Instant)>>> = Mutex::new(None);
+
+#[derive(Debug)]
+enum Error {
+    Database(sqlx::Error),
+    /// The form does not carry the expected anti-forgery token.
+    Forgery,
+    /// The submitted values are invalid.
+    Invalid(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Database(e) => write!(f, "database error: {e}"),
+            Error::Forgery => write!(f, "invalid form token"),
+            Error::Invalid(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(value: sqlx::Error) -> Self {
+        Self::Database(value)
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Error::Database(_) => {
+                log::error!("Dashboard error: {}", self);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            Error::Forgery => StatusCode::FORBIDDEN,
+            Error::Invalid(_) => StatusCode::BAD_REQUEST,
+        };
+        (
+            status,
+            page("Erreur", format!("<p>{}</p>", escape(&self.to_string()))),
+        )
+            .into_response()
+    }
+}
+
+/// Whether the address failed to authenticate too often recently.
+fn locked_out(ip: IpAddr) -> bool {
+    let mut failures = FAILED_LOGINS.lock().unwrap();
+    let failures = failures.get_or_insert_with(HashMap::new);
+    failures.retain(|_, (_, last)| last.elapsed() < LOGIN_LOCKOUT);
+    failures
+        .get(&ip)
+        .is_some_and(|(count, _)| *count >= MAX_FAILED_LOGINS)
+}
+
+fn record_login(ip: IpAddr, success: bool) {
+    let mut failures = FAILED_LOGINS.lock().unwrap();
+    let failures = failures.get_or_insert_with(HashMap::new);
+    if success {
+        failures.remove(&ip);
+    } else {
+        let (count, last) = failures.entry(ip).or_insert((0, Instant::now()));
+        *count += 1;
+        *last = Instant::now();
+        if *count == MAX_FAILED_LOGINS {
+            log::warn!("Dashboard: {} locked out after {} failed logins", ip, count);
+        }
+    }
+}
+
+/// Random identifier of the session of the browser, from its cookie.
+#[derive(Clone)]
+struct Session(String);
+
+/// Reads the session cookie, or creates a session and sets its cookie on the response.
+async fn session(mut request: Request, next: Next) -> Response {
+    let existing = request
+        .headers()
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(';'))
+        .filter_map(|c| c.trim().split_once('='))
+        .find(|(name, value)| *name == SESSION_COOKIE && value.len() == 64)
+        .map(|(_, value)| value.to_owned());
+
+    let session = existing
+        .clone()
+        .unwrap_or_else(|| hex::encode(rand::thread_rng().gen::<[u8; 32]>()));
+    request.extensions_mut().insert(Session(session.clone()));
+    let mut response = next.run(request).await;
+    if existing.is_none() {
+        let cookie = format!(
+            "{}={}; Path=/; HttpOnly; SameSite=Strict",
+            SESSION_COOKIE, session
+        );
+        if let Ok(cookie) = HeaderValue::from_str(&cookie) {
+            response.headers_mut().append(SET_COOKIE, cookie);
+        }
+    }
+    response
+}
+
+/// Anti-forgery token of the forms, bound to the session of the browser.
+struct FormToken(String);
+
+impl FormToken {
+    fn new(session: &Session) -> Self {
+        Self(hex::encode(Sha256::digest(format!(
+            "dashboard:{}:{}",
+            admin_token(),
+            session.0
+        ))))
+    }
+
+    fn check(&self, token: &str) -> Result<(), Error> {
+        if secrets_equal(token, &self.0) {
+            Ok(())
+        } else {
+            Err(Error::Forgery)
+        }
+    }
+}
+
+/// Admin authenticated with HTTP basic authentication. Holds their name in the audit log, and the
+/// anti-forgery token of their session.
+struct WebAdmin(String, FormToken);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for WebAdmin {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        let ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(peer)| peer.ip().to_canonical());
+        if ip.is_some_and(locked_out) {
+            return Err((
+                StatusCode::TOO_MANY_REQUESTS,
+                "Trop de tentatives, réessayez plus tard",
+            )
+                .into_response());
+        }
+
+        let credentials = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.strip_prefix("Basic "))
+            .and_then(|c| STANDARD.decode(c).ok())
+            .and_then(|c| String::from_utf8(c).ok());
+        let session = parts.extensions.get::<Session>().cloned();
+
+        match (credentials.as_deref().and_then(|c| c.split_once(':')), session) {
+            (Some((user, token)), Some(session)) if secrets_equal(token, &admin_token()) => {
+                if let Some(ip) = ip {
+                    record_login(ip, true);
+                }
+                Ok(WebAdmin(format!("web:{}", user), FormToken::new(&session)))
+            }
+            (credentials, _) => {
+                // The browsers first try without credentials, which is not a failure
+                if let (Some(ip), Some(_)) = (ip, credentials) {
+                    record_login(ip, false);
+                }
+                Err((
+                    StatusCode::UNAUTHORIZED,
+                    [(WWW_AUTHENTICATE, "Basic realm=\"RoboCLIC\"")],
+                    "Authentification requise",
+                )
+                    .into_response())
+            }
+        }
+    }
+}
+
+/// Escapes a value placed in an HTML attribute.
+fn attribute(value: &str) -> String {
+    escape(value).replace('"', "&quot;")
+}
+
+fn date(timestamp: i64) -> String {
+    from_timestamp(timestamp, TIMEZONE)
+        .map(|d| format_datetime(&d))
+        .unwrap_or_default()
+}
+
+fn page(title: &str, body: String) -> Html<String> {
+    Html(format!(
+        r#"<!DOCTYPE html>
+<html lang="fr">
+<head>
+<meta charset="utf-8">
+<title>{title} - RoboCLIC</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em auto; max-width: 70em; }}
+nav a {{ margin-right: 1em; }}
+table {{ border-collapse: collapse; width: 100%; }}
+td, th {{ border-bottom: 1px solid #ddd; padding: 0.3em; text-align: left; vertical-align: top; }}
+form {{ display: inline; }}
+</style>
+</head>
+<body>
+<nav><a href="/">Accueil</a><a href="/admins">Admins</a><a href="/authorizations">Autorisations</a><a href="/committee">Comité</a><a href="/quotes">Citations</a><a href="/schedules">Programmations</a><a href="/stats">Stats</a><a href="/audit">Journal</a></nav>
+<h1>{title}</h1>
+{body}
+</body>
+</html>"#,
+        title = escape(title),
+        body = body
+    ))
+}
+
+/// Form posting the given hidden values (along with the anti-forgery token) and `inputs` (raw
+/// HTML) to `action`.
+fn form(
+    token: &FormToken,
+    action: &str,
+    hidden: &[(&str, &str)],
+    inputs: &str,
+    button: &str,
+) -> String {
+    let hidden = hidden
+        .iter()
+        .map(|(name, value)| {
+            format!(
+                r#"<input type="hidden" name="{}" value="{}">"#,
+                name,
+                attribute(value)
+            )
+        })
+        .collect::<String>();
+    format!(
+        r#"<form method="post" action="{}"><input type="hidden" name="token" value="{}">{}{}<button>{}</button></form>"#,
+        action,
+        token.0,
+        hidden,
+        inputs,
+        escape(button)
+    )
+}
+
+fn table(headers: &[&str], rows: Vec<Vec<String>>) -> String {
+    format!(
+        "<table><tr>{}</tr>{}</table>",
+        headers
+            .iter()
+            .map(|h| format!("<th>{}</th>", escape(h)))
+            .collect::<String>(),
+        rows.into_iter()
+            .map(|r| format!(
+                "<tr>{}</tr>",
+                r.into_iter()
+                    .map(|c| format!("<td>{}</td>", c))
+                    .collect::<String>()
+            ))
+            .collect::<String>()
+    )
+}
+
+// --------------------------------- PAGES ------------------------------------
+
+async fn index(_: WebAdmin, State(state): State<AppState>) -> Result<Html<String>, Error> {
+    let admins = state.admin_repo.count_admins().await?;
+    let quotes = state.quote_repo.count_quotes().await?;
+    let counts = sqlx::query!(
+        r#"SELECT
+            (SELECT COUNT(*) FROM chats WHERE left_at IS NULL) AS "chats!: i64",
+            (SELECT COUNT(*) FROM schedules) AS "schedules!: i64""#
+    )
+    .fetch_one(state.db.as_ref())
+    .await?;
+
+    Ok(page(
+        "RoboCLIC",
+        format!(
+            "<ul><li>{} admin(s)</li><li>{} groupe(s)</li><li>{} citation(s)</li><li>{} programmation(s)</li></ul>",
+            admins, counts.chats, quotes, counts.schedules
+        ),
+    ))
+}
+
+#[derive(Deserialize)]
+struct AdminForm {
+    token: String,
+    telegram_id: String,
+    #[serde(default)]
+    name: String,
+}
+
+async fn admins(
+    WebAdmin(_, token): WebAdmin,
+    State(state): State<AppState>,
+) -> Result<Html<String>, Error> {
+    let admins = state.admin_repo.admins().await?;
+
+    Ok(page(
+        "Admins",
+        format!(
+            "{}<h2>Ajouter</h2>{}",
+            table(
+                &["Nom", "Id Telegram", ""],
+                admins
+                    .into_iter()
+                    .map(|a| {
+                        vec![
+                            escape(&a.name),
+                            escape(&a.telegram_id),
+                            form(
+                                &token,
+                                "/admins/remove",
+                                &[("telegram_id", &a.telegram_id)],
+                                "",
+                                "Retirer",
+                            ),
+                        ]
+                    })
+                    .collect()
+            ),
+            form(
+                &token,
+                "/admins/add",
+                &[],
+                r#"<input name="telegram_id" placeholder="Id Telegram" required> <input name="name" placeholder="Nom" required> "#,
+                "Ajouter"
+            )
+        ),
+    ))
+}
+
+async fn admin_add(
+    WebAdmin(actor, token): WebAdmin,
+    State(state): State<AppState>,
+    Form(form): Form<AdminForm>,
+) -> Result<Redirect, Error> {
+    token.check(&form.token)?;
+    let (id, name) = (form.telegram_id.trim(), form.name.trim());
+    if id.parse::<u64>().is_err() || name.is_empty() {
+        return Err(Error::Invalid("Id Telegram ou nom invalide".to_owned()));
+    }
+
+    let timestamp = now().timestamp();
+    state
+        .admin_repo
+        .add_or_rename_admin(id, name, "dashboard", timestamp)
+        .await?;
+    let details = format!("{} ({})", name, id);
+    audit(state.db.as_ref(), &actor, "admin_add", &details).await;
+
+    Ok(Redirect::to("/admins"))
+}
+
+async fn admin_remove(
+    WebAdmin(actor, token): WebAdmin,
+    State(state): State<AppState>,
+    Form(form): Form<AdminForm>,
+) -> Result<Redirect, Error> {
+    token.check(&form.token)?;
+    if state.admin_repo.remove_admin_id(&form.telegram_id).await? {
+        audit(state.db.as_ref(), &actor, "admin_remove", &form.telegram_id).await;
+    }
+
+    Ok(Redirect::to("/admins"))
+}
+
+#[derive(Deserialize)]
+struct AuthorizationForm {
+    token: String,
+    chat_id: String,
+    command: String,
+}
+
+async fn authorizations(
+    WebAdmin(_, token): WebAdmin,
+    State(state): State<AppState>,
+) -> Result<Html<String>, Error> {
+    let chats = sqlx::query!(
+        r#"SELECT chat_id AS "chat_id!", title FROM chats WHERE left_at IS NULL ORDER BY title"#
+    )
+    .fetch_all(state.db.as_ref())
+    .await?;
+    let authorizations = state.authorization_repo.authorizations("").await?;
+
+    let rows = chats
+        .iter()
+        .map(|c| {
+            let commands = authorizations
+                .iter()
+                .filter(|a| a.chat_id == c.chat_id)
+                .map(|a| {
+                    format!(
+                        "/{} {}",
+                        escape(&a.command),
+                        form(
+                            &token,
+                            "/authorizations/remove",
+                            &[("chat_id", &c.chat_id), ("command", &a.command)],
+                            "",
+                            "✕"
+                        )
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("<br>");
+            vec![
+                escape(c.title.as_deref().unwrap_or_default()),
+                escape(&c.chat_id),
+                commands,
+                form(
+                    &token,
+                    "/authorizations/add",
+                    &[("chat_id", &c.chat_id)],
+                    r#"<input name="command" placeholder="commande" required> "#,
+                    "Autoriser",
+                ),
+            ]
+        })
+        .collect();
+
+    Ok(page(
+        "Autorisations",
+        table(&["Groupe", "Id", "Commandes", ""], rows),
+    ))
+}
+
+async fn authorization_add(
+    WebAdmin(actor, token): WebAdmin,
+    State(state): State<AppState>,
+    Form(form): Form<AuthorizationForm>,
+) -> Result<Redirect, Error> {
+    token.check(&form.token)?;
+    let command = form.command.trim().trim_start_matches('/').to_lowercase();
+    if !is_command(&command) {
+        return Err(Error::Invalid(format!(
+            "/{} n'est pas une commande du bot",
+            command
+        )));
+    }
+
+    state.authorization_repo.grant(&form.chat_id, &command).await?;
+    let details = format!("/{} in {}", command, form.chat_id);
+    audit(state.db.as_ref(), &actor, "authorize", &details).await;
+
+    Ok(Redirect::to("/authorizations"))
+}
+
+async fn authorization_remove(
+    WebAdmin(actor, token): WebAdmin,
+    State(state): State<AppState>,
+    Form(form): Form<AuthorizationForm>,
+) -> Result<Redirect, Error> {
+    token.check(&form.token)?;
+    if state
+        .authorization_repo
+        .revoke(&form.chat_id, &form.command)
+        .await?
+    {
+        let details = format!("/{} in {}", form.command, form.chat_id);
+        audit(state.db.as_ref(), &actor, "unauthorize", &details).await;
+    }
+
+    Ok(Redirect::to("/authorizations"))
+}
+
+#[derive(Deserialize)]
+struct TokenForm {
+    token: String,
+}
+
+async fn committee(WebAdmin(_, token): WebAdmin) -> Html<String> {
+    let body = match committee_repository().get().await {
+        Ok(mut committee) => {
+            committee.sort_by(|a, b| a.name.cmp(&b.name));
+            table(
+                &["Nom", "Sondages"],
+                committee
+                    .into_iter()
+                    .map(|c| vec![escape(&c.name), c.poll_count.to_string()])
+                    .collect(),
+            )
+        }
+        Err(e) => format!("<p>Erreur Directus: {}</p>", escape(&e.to_string())),
+    };
+
+    page(
+        "Comité",
+        format!(
+            "<p>Les membres sont gérés dans Directus.</p>{}<p>{}</p>",
+            body,
+            form(&token, "/committee/sync", &[], "", "Synchroniser avec Directus")
+        ),
+    )
+}
+
+async fn committee_sync(
+    WebAdmin(actor, token): WebAdmin,
+    State(state): State<AppState>,
+    Form(form): Form<TokenForm>,
+) -> Result<Redirect, Error> {
+    token.check(&form.token)?;
+    committee_repository().invalidate().await;
+    match committee_repository().refresh().await {
+        Ok(committee) => {
+            let details = format!("{} member(s)", committee.len());
+            audit(state.db.as_ref(), &actor, "committee_sync", &details).await;
+        }
+        Err(e) => log::error!("Could not fetch committee: {e:#?}"),
+    }
+
+    Ok(Redirect::to("/committee"))
+}
+
+#[derive(Deserialize)]
+struct QuoteFilter {
+    author: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct IdForm {
+    token: String,
+    id: i64,
+}
+
+async fn quotes(
+    WebAdmin(_, token): WebAdmin,
+    State(state): State<AppState>,
+    Query(filter): Query<QuoteFilter>,
+) -> Result<Html<String>, Error> {
+    let author = filter.author.unwrap_or_default();
+    let quotes = state
+        .quote_repo
+        .latest_quotes(&author, "", PAGE_SIZE)
+        .await?;
+
+    let rows = quotes
+        .into_iter()
+        .map(|q| {
+            vec![
+                date(q.created_at),
+                format!(
+                    r#"<a href="/quotes?author={}">{}</a>"#,
+                    attribute(&q.author),
+                    escape(&q.author)
+                ),
+                escape(&q.text),
+                escape(q.chat_title.as_deref().unwrap_or_default()),
+                form(
+                    &token,
+                    "/quotes/delete",
+                    &[("id", &q.id.to_string())],
+                    "",
+                    "Supprimer",
+                ),
+            ]
+        })
+        .collect();
+
+    Ok(page(
+        "Citations",
+        format!(
+            r#"<form method="get" action="/quotes"><input name="author" placeholder="Auteur" value="{}"> <button>Filtrer</button></form>{}"#,
+            attribute(&author),
+            table(&["Date", "Auteur", "Citation", "Groupe", ""], rows)
+        ),
+    ))
+}
+
+async fn quote_delete(
+    WebAdmin(actor, token): WebAdmin,
+    State(state): State<AppState>,
+    Form(form): Form<IdForm>,
+) -> Result<Redirect, Error> {
+    token.check(&form.token)?;
+    if state.quote_repo.delete_quote(form.id).await? {
+        let details = format!("#{}", form.id);
+        audit(state.db.as_ref(), &actor, "quote_delete", &details).await;
+    }
+
+    Ok(Redirect::to("/quotes"))
+}
+
+async fn schedules(
+    WebAdmin(_, token): WebAdmin,
+    State(state): State<AppState>,
+) -> Result<Html<String>, Error> {
+    let schedules = sqlx::query!(
+        r#"SELECT s.id AS "id!", s.chat_id, s.cron, s.payload, s.next_run, c.title FROM schedules s
+        LEFT JOIN chats c ON c.chat_id = s.chat_id ORDER BY s.next_run"#
+    )
+    .fetch_all(state.db.as_ref())
+    .await?;
+
+    let rows = schedules
+        .into_iter()
+        .map(|s| {
+            vec![
+                format!("#{}", s.id),
+                escape(s.title.as_deref().unwrap_or(&s.chat_id)),
+                format!(
+                    "<code>{}</code>",
+                    escape(s.cron.strip_prefix("0 ").unwrap_or(&s.cron))
+                ),
+                escape(&s.payload),
+                date(s.next_run),
+                form(
+                    &token,
+                    "/schedules/remove",
+                    &[("id", &s.id.to_string())],
+                    "",
+                    "Supprimer",
+                ),
+            ]
+        })
+        .collect();
+
+    Ok(page(
+        "Programmations",
+        format!(
+            "<p>Les programmations se créent dans les groupes avec /scheduleadd.</p>{}",
+            table(
+                &["", "Groupe", "Cron", "Message", "Prochaine exécution", ""],
+                rows
+            )
+        ),
+    ))
+}
+
+async fn schedule_remove(
+    WebAdmin(actor, token): WebAdmin,
+    State(state): State<AppState>,
+    Form(form): Form<IdForm>,
+) -> Result<Redirect, Error> {
+    token.check(&form.token)?;
+    let removed = sqlx::query!("DELETE FROM schedules WHERE id = $1", form.id)
+        .execute(state.db.as_ref())
+        .await?
+        .rows_affected();
+    if removed > 0 {
+        let details = format!("#{}", form.id);
+        audit(state.db.as_ref(), &actor, "schedule_remove", &details).await;
+    }
+
+    Ok(Redirect::to("/schedules"))
+}
+
+async fn stats(_: WebAdmin, State(state): State<AppState>) -> Result<Html<String>, Error> {
+    let by_author = state.quote_repo.quote_counts_by_author().await?;
+    let by_chat = state.quote_repo.quote_counts_by_chat().await?;
+    let bureau = sqlx::query!(
+        r#"SELECT COALESCE(c.title, b.chat_id) AS "chat!: String", COUNT(*) AS "count!: i64"
+        FROM bureau_polls b LEFT JOIN chats c ON c.chat_id = b.chat_id GROUP BY b.chat_id ORDER BY 2 DESC"#
+    )
+    .fetch_all(state.db.as_ref())
+    .await?;
+
+    Ok(page(
+        "Stats",
+        format!(
+            "<h2>Citations par auteur</h2>{}<h2>Citations par groupe</h2>{}<h2>Sondages /bureau par groupe</h2>{}",
+            table(
+                &["Auteur", "Citations"],
+                by_author
+                    .into_iter()
+                    .map(|(author, count)| vec![escape(&author), count.to_string()])
+                    .collect()
+            ),
+            table(
+                &["Groupe", "Citations"],
+                by_chat
+                    .into_iter()
+                    .map(|(chat, count)| vec![escape(&chat), count.to_string()])
+                    .collect()
+            ),
+            table(
+                &["Groupe", "Sondages"],
+                bureau
+                    .into_iter()
+                    .map(|r| vec![escape(&r.chat), r.count.to_string()])
+                    .collect()
+            )
+        ),
+    ))
+}
+
+async fn audit_log(
+    _: WebAdmin,
+    State(state): State<AppState>,
+) -> Result<Html<String>, Error> {
+    let entries = sqlx::query!(
+        r#"SELECT created_at, actor, "action", details FROM audit_log ORDER BY id DESC LIMIT $1"#,
+        PAGE_SIZE
+    )
+    .fetch_all(state.db.as_ref())
+    .await?;
+
+    Ok(page(
+        "Journal",
+        table(
+            &["Date", "Par", "Action", "Détails"],
+            entries
+                .into_iter()
+                .map(|e| {
+                    vec![
+                        date(e.created_at),
+                        escape(&e.actor),
+                        escape(&e.action),
+                        escape(&e.details),
+                    ]
+                })
+                .collect(),
+        ),
+    ))
+}
+
+/// Serves the dashboard at the given address.
+pub async fn serve_dashboard(address: String, state: AppState) {
+    let app = Router::new()
+        .route("/", get(index))
+        .route("/admins", get(admins))
+        .route("/admins/add", post(admin_add))
+        .route("/admins/remove", post(admin_remove))
+        .route("/authorizations", get(authorizations))
+        .route("/authorizations/add", post(authorization_add))
+        .route("/authorizations/remove", post(authorization_remove))
+        .route("/committee", get(committee))
+        .route("/committee/sync", post(committee_sync))
+        .route("/quotes", get(quotes))
+        .route("/quotes/delete", post(quote_delete))
+        .route("/schedules", get(schedules))
+        .route("/schedules/remove", post(schedule_remove))
+        .route("/stats", get(stats))
+        .route("/audit", get(audit_log))
+        .layer(middleware::from_fn(session))
+        .with_state(state);
+
+    serve("dashboard", &address, app).await;
+}
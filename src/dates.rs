@@ -0,0 +1,379 @@
+use std::sync::Arc;
+
+use chrono::{
+    DateTime, Datelike, Days, NaiveDate, NaiveTime, TimeDelta, TimeZone, Timelike, Weekday,
+};
+use chrono_tz::{Tz, TZ_VARIANTS};
+use sqlx::SqlitePool;
+use teloxide::types::{ChatId, Update};
+
+/// Default timezone of the chats, in which the dates of Directus are also given.
+pub const TIMEZONE: Tz = chrono_tz::Europe::Zurich;
+
+/// Time used when only a day is given (e.g. "demain" or "tomorrow").
+const DEFAULT_TIME: (u32, u32) = (9, 0);
+
+pub fn now() -> DateTime<Tz> {
+    now_in(TIMEZONE)
+}
+
+pub fn now_in(timezone: Tz) -> DateTime<Tz> {
+    chrono::Utc::now().with_timezone(&timezone)
+}
+
+/// Formats a date the way it is displayed to the users.
+pub fn format_datetime(date: &DateTime<Tz>) -> String {
+    date.format("%d/%m/%Y %H:%M").to_string()
+}
+
+/// Converts a unix timestamp (as stored in the database) to a date in the given timezone.
+pub fn from_timestamp(timestamp: i64, timezone: Tz) -> Option<DateTime<Tz>> {
+    DateTime::from_timestamp(timestamp, 0).map(|d| d.with_timezone(&timezone))
+}
+
+/// Midnight of the first day of the month of the date.
+pub fn start_of_month(date: &DateTime<Tz>) -> DateTime<Tz> {
+    let first = date.date_naive().with_day(1).unwrap_or(date.date_naive());
+    date.timezone()
+        .from_local_datetime(&first.and_time(NaiveTime::MIN))
+        .earliest()
+        .unwrap_or(*date)
+}
+
+/// Parses an IANA timezone name (e.g. `Europe/Zurich`), ignoring the case.
+pub fn parse_timezone(name: &str) -> Option<Tz> {
+    let name = name.trim();
+    name.parse().ok().or_else(|| {
+        TZ_VARIANTS
+            .iter()
+            .find(|tz| tz.name().eq_ignore_ascii_case(name))
+            .copied()
+    })
+}
+
+/// Timezone of the chat, [`TIMEZONE`] if it was never set.
+pub async fn chat_timezone(db: &SqlitePool, chat_id: ChatId) -> Tz {
+    let chat_id = chat_id.to_string();
+    match sqlx::query!("SELECT timezone FROM chats WHERE chat_id = $1", chat_id)
+        .fetch_optional(db)
+        .await
+    {
+        Ok(row) => row
+            .and_then(|r| parse_timezone(&r.timezone))
+            .unwrap_or(TIMEZONE),
+        Err(e) => {
+            log::error!("Could not fetch the timezone of {}: {:?}", chat_id, e);
+            TIMEZONE
+        }
+    }
+}
+
+/// Timezone of the chat of the update, injected as a dependency of the handlers.
+pub async fn update_timezone(update: Update, db: Arc<SqlitePool>) -> Tz {
+    match update.chat() {
+        Some(chat) => chat_timezone(db.as_ref(), chat.id).await,
+        None => TIMEZONE,
+    }
+}
+
+/// Parses a French or English date expression at the beginning of `input`, and returns the date
+/// along with the rest of the text. The date is interpreted in the timezone of `now`.
+///
+/// Supported expressions (which can be combined, e.g. "demain 14h", "lundi prochain à 9h30",
+/// "25/12 midi", "tomorrow at 6pm", "next friday 9:30am"):
+/// - relative days: `aujourd'hui`, `demain`, `après-demain`, `today`, `tomorrow`, weekdays
+///   (`lundi`, `monday`, ...),
+/// - dates: `25/12`, `25/12/2024`, `25.12.2024`, `2024-12-25`,
+/// - times: `14h`, `14h30`, `14:30`, `2pm`, `2:30pm`, `midi`, `minuit`, `noon`, `midnight`,
+/// - offsets: `dans 2h`, `dans 30 min`, `dans 3 jours`, `in 2 hours`, `in 3 days`.
+///
+/// Returns `None` if no date could be parsed, or if the date is in the past.
+pub fn parse_datetime(input: &str, now: DateTime<Tz>) -> Option<(DateTime<Tz>, &str)> {
+    let words = words(input);
+    let today = now.date_naive();
+
+    let mut date: Option<NaiveDate> = None;
+    let mut time: Option<NaiveTime> = None;
+    let mut offset: Option<TimeDelta> = None;
+    // Byte index of the end of the last successfully parsed word
+    let mut end = 0;
+
+    let mut i = 0;
+    while i < words.len() {
+        let (_, word_end, word) = words[i];
+        let word = normalize(word);
+
+        match word.as_str() {
+            "aujourd'hui" | "aujourdhui" | "today" => date = Some(today),
+            "demain" | "tomorrow" => date = today.succ_opt(),
+            "apres-demain" => date = today.checked_add_days(Days::new(2)),
+            "midi" | "noon" => time = NaiveTime::from_hms_opt(12, 0, 0),
+            "minuit" | "midnight" => {
+                time = NaiveTime::from_hms_opt(0, 0, 0);
+                // "minuit" alone means the end of the current day
+                date = date.or(today.succ_opt());
+            }
+            // "lundi prochain"
+            "prochain" | "prochaine" if date.is_some() => {}
+            // Filler words, only consumed if followed by a valid expression ("next" as in "next
+            // monday")
+            "a" | "le" | "ce" | "vers" | "at" | "on" | "this" | "next" => {
+                i += 1;
+                continue;
+            }
+            "dans" | "in" => {
+                let Some((duration, consumed)) = parse_offset(&words[i + 1..]) else {
+                    break;
+                };
+                offset = Some(duration);
+                i += consumed;
+                end = words[i].1;
+                i += 1;
+                continue;
+            }
+            w => {
+                if let Some(day) = parse_weekday(w) {
+                    date = Some(next_weekday(today, day));
+                } else if let Some(t) = parse_time(w) {
+                    time = Some(t);
+                } else if let Some(d) = parse_date(w, today) {
+                    date = Some(d);
+                } else {
+                    break;
+                }
+            }
+        }
+
+        end = word_end;
+        i += 1;
+    }
+
+    let result = if let Some(offset) = offset {
+        now.checked_add_signed(offset)?
+    } else {
+        if date.is_none() && time.is_none() {
+            return None;
+        }
+
+        let default_time = NaiveTime::from_hms_opt(DEFAULT_TIME.0, DEFAULT_TIME.1, 0).unwrap();
+        let mut result = now
+            .timezone()
+            .from_local_datetime(&date.unwrap_or(today).and_time(time.unwrap_or(default_time)))
+            .earliest()?;
+
+        // A time alone refers to the next occurrence of that time
+        if date.is_none() && result <= now {
+            result = result.checked_add_signed(TimeDelta::days(1))?;
+        }
+
+        result
+    };
+
+    if result <= now {
+        return None;
+    }
+
+    Some((result, input[end..].trim_start()))
+}
+
+/// Parses a recurring expression at the beginning of `input`, e.g. "chaque lundi 9h", "tous les
+/// jours à 18h" or "every weekday at 9am", and returns it as a standard 5-fields cron expression,
+/// along with the rest of the text. Without a time, it runs at the default time.
+pub fn parse_recurrence(input: &str) -> Option<(String, &str)> {
+    let words = words(input);
+    let mut i = match normalize(words.first()?.2).as_str() {
+        "chaque" | "every" | "each" => 1,
+        "tous" | "toutes" if words.get(1).is_some_and(|w| normalize(w.2) == "les") => 2,
+        _ => return None,
+    };
+
+    let (_, mut end, day) = *words.get(i)?;
+    let days = match normalize(day).as_str() {
+        "jour" | "jours" | "day" | "days" => "*".to_owned(),
+        "weekday" | "weekdays" => "Mon-Fri".to_owned(),
+        // Also in the plural, "tous les lundis"
+        w => format!(
+            "{:?}",
+            parse_weekday(w).or_else(|| parse_weekday(w.strip_suffix('s')?))?
+        ),
+    };
+    i += 1;
+
+    let mut time = NaiveTime::from_hms_opt(DEFAULT_TIME.0, DEFAULT_TIME.1, 0)?;
+    while let Some(&(_, word_end, word)) = words.get(i) {
+        let parsed = match normalize(word).as_str() {
+            "a" | "at" => {
+                i += 1;
+                continue;
+            }
+            "midi" | "noon" => NaiveTime::from_hms_opt(12, 0, 0),
+            "minuit" | "midnight" => NaiveTime::from_hms_opt(0, 0, 0),
+            w => parse_time(w),
+        };
+        if let Some(parsed) = parsed {
+            time = parsed;
+            end = word_end;
+        }
+        break;
+    }
+
+    Some((
+        format!("{} {} * * {}", time.minute(), time.hour(), days),
+        input[end..].trim_start(),
+    ))
+}
+
+/// Parses a single day: `aujourd'hui`, `demain`, `après-demain`, `today`, `tomorrow`, a weekday
+/// or a date.
+pub fn parse_day(word: &str, today: NaiveDate) -> Option<NaiveDate> {
+    match normalize(word).as_str() {
+        "aujourd'hui" | "aujourdhui" | "today" => Some(today),
+        "demain" | "tomorrow" => today.succ_opt(),
+        "apres-demain" => today.checked_add_days(Days::new(2)),
+        w => parse_weekday(w)
+            .map(|day| {
+                if today.weekday() == day {
+                    today
+                } else {
+                    next_weekday(today, day)
+                }
+            })
+            .or_else(|| parse_date(w, today)),
+    }
+}
+
+/// Splits the input in words, keeping their byte positions.
+fn words(input: &str) -> Vec<(usize, usize, &str)> {
+    let mut words = vec![];
+    let mut start = None;
+
+    for (i, c) in input.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                words.push((s, i, &input[s..i]));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        words.push((s, input.len(), &input[s..]));
+    }
+
+    words
+}
+
+/// Lowercases the word and strips the accents used in date expressions.
+fn normalize(word: &str) -> String {
+    word.to_lowercase()
+        .replace(['à', 'â'], "a")
+        .replace(['é', 'è'], "e")
+        .replace('’', "'")
+}
+
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    Some(match word {
+        "lundi" => Weekday::Mon,
+        "mardi" => Weekday::Tue,
+        "mercredi" => Weekday::Wed,
+        "jeudi" => Weekday::Thu,
+        "vendredi" => Weekday::Fri,
+        "samedi" => Weekday::Sat,
+        "dimanche" => Weekday::Sun,
+        "monday" => Weekday::Mon,
+        "tuesday" => Weekday::Tue,
+        "wednesday" => Weekday::Wed,
+        "thursday" => Weekday::Thu,
+        "friday" => Weekday::Fri,
+        "saturday" => Weekday::Sat,
+        "sunday" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+/// Next occurrence of the given weekday, strictly after `today`.
+fn next_weekday(today: NaiveDate, day: Weekday) -> NaiveDate {
+    let diff =
+        (7 + day.num_days_from_monday() as i64 - today.weekday().num_days_from_monday() as i64) % 7;
+    today + TimeDelta::days(if diff == 0 { 7 } else { diff })
+}
+
+/// Parses `14h`, `14h30`, `14:30`, `2pm` or `2:30pm`.
+fn parse_time(word: &str) -> Option<NaiveTime> {
+    let twelve_hours = word
+        .strip_suffix("am")
+        .map(|t| (t, 0))
+        .or_else(|| word.strip_suffix("pm").map(|t| (t, 12)));
+    if let Some((time, offset)) = twelve_hours {
+        let (hours, minutes) = time.split_once(':').unwrap_or((time, "0"));
+        let hours: u32 = hours.parse().ok()?;
+        if !(1..=12).contains(&hours) {
+            return None;
+        }
+        return NaiveTime::from_hms_opt(hours % 12 + offset, minutes.parse().ok()?, 0);
+    }
+
+    let (hours, minutes) = word.split_once('h').or_else(|| word.split_once(':'))?;
+    let hours = hours.parse().ok()?;
+    let minutes = if minutes.is_empty() {
+        0
+    } else {
+        minutes.parse().ok()?
+    };
+    NaiveTime::from_hms_opt(hours, minutes, 0)
+}
+
+/// Parses `25/12`, `25/12/2024`, `25.12.2024` or `2024-12-25`. Dates without a year refer to
+/// their next occurrence.
+fn parse_date(word: &str, today: NaiveDate) -> Option<NaiveDate> {
+    if let Ok(date) = NaiveDate::parse_from_str(word, "%Y-%m-%d") {
+        return Some(date);
+    }
+
+    let parts = word
+        .split(['/', '.'])
+        .map(|p| p.parse::<i32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    match parts[..] {
+        [day, month] => {
+            let date = NaiveDate::from_ymd_opt(today.year(), month as u32, day as u32)?;
+            if date < today {
+                NaiveDate::from_ymd_opt(today.year() + 1, month as u32, day as u32)
+            } else {
+                Some(date)
+            }
+        }
+        [day, month, year] => {
+            let year = if year < 100 { 2000 + year } else { year };
+            NaiveDate::from_ymd_opt(year, month as u32, day as u32)
+        }
+        _ => None,
+    }
+}
+
+/// Parses the words following "dans" or "in", e.g. `2h`, `2 heures`, `30 min`, `3 jours`,
+/// `2 hours`.
+/// Returns the duration and the number of words consumed, or `None` if the duration overflows.
+fn parse_offset(words: &[(usize, usize, &str)]) -> Option<(TimeDelta, usize)> {
+    let first = normalize(words.first()?.2);
+    let digits = first.chars().take_while(|c| c.is_ascii_digit()).count();
+    let amount: i64 = first[..digits].parse().ok()?;
+
+    let (unit, consumed) = if digits < first.len() {
+        (first[digits..].to_string(), 1)
+    } else {
+        (normalize(words.get(1)?.2), 2)
+    };
+
+    let duration = match unit.as_str() {
+        "m" | "min" | "mins" | "minute" | "minutes" => TimeDelta::try_minutes(amount)?,
+        "h" | "heure" | "heures" | "hour" | "hours" => TimeDelta::try_hours(amount)?,
+        "j" | "jour" | "jours" | "d" | "day" | "days" => TimeDelta::try_days(amount)?,
+        "semaine" | "semaines" | "week" | "weeks" => TimeDelta::try_weeks(amount)?,
+        _ => return None,
+    };
+
+    Some((duration, consumed))
+}
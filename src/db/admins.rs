@@ -0,0 +1,210 @@
+//! Admins of the bot, shared by the commands, the dashboard and `roboclic-admin`.
+
+use async_trait::async_trait;
+use sqlx::{SqliteConnection, SqlitePool};
+use teloxide::types::{ChatId, UserId};
+
+pub struct Admin {
+    pub telegram_id: String,
+    pub name: String,
+}
+
+/// How and where an admin authenticated, unknown for the admins added before it was recorded.
+pub struct Session {
+    pub telegram_id: String,
+    pub name: String,
+    pub authenticated_at: Option<i64>,
+    pub auth_chat_id: Option<String>,
+    pub auth_method: Option<String>,
+}
+
+/// Queries of the admins, implemented by the database pool. The callers only depend on the trait,
+/// so that another implementation can stand in for the database, e.g. in the tests.
+#[async_trait]
+pub trait AdminRepo: Send + Sync {
+    /// Whether the user is admin. Failures are logged, and count as not admin.
+    async fn is_admin(&self, user: UserId) -> bool;
+
+    /// Number of admins.
+    async fn count_admins(&self) -> Result<i64, sqlx::Error>;
+
+    /// Admins sorted by name.
+    async fn admins(&self) -> Result<Vec<Admin>, sqlx::Error>;
+
+    /// Private chats of the admins with the bot. The admins who authenticated in a group before
+    /// have the id of the group, and are left out.
+    async fn admin_private_chats(&self) -> Result<Vec<ChatId>, sqlx::Error>;
+
+    /// Authentications of the admins, the most recent first.
+    async fn admin_sessions(&self) -> Result<Vec<Session>, sqlx::Error>;
+
+    /// Makes the account admin, recording how (`method`) and from which chat it authenticated.
+    /// Returns whether it was not already admin.
+    async fn add_admin(
+        &self,
+        telegram_id: &str,
+        name: &str,
+        method: &str,
+        chat_id: Option<&str>,
+        timestamp: i64,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Makes the account admin, or renames it if it already is.
+    async fn add_or_rename_admin(
+        &self,
+        telegram_id: &str,
+        name: &str,
+        method: &str,
+        timestamp: i64,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Removes the admins with the name. Returns their Telegram ids.
+    async fn remove_admins(&self, name: &str) -> Result<Vec<String>, sqlx::Error>;
+
+    /// Removes the admin with the Telegram id. Returns whether it was admin.
+    async fn remove_admin_id(&self, telegram_id: &str) -> Result<bool, sqlx::Error>;
+}
+
+#[async_trait]
+impl AdminRepo for SqlitePool {
+    async fn is_admin(&self, user: UserId) -> bool {
+        let id = user.to_string();
+        match sqlx::query!(
+            r#"SELECT COUNT(*) AS "count!: i64" FROM admins WHERE telegram_id = $1"#,
+            id
+        )
+        .fetch_one(self)
+        .await
+        {
+            Ok(result) => result.count > 0,
+            Err(e) => {
+                log::error!("Could not check admin in database: {:?}", e);
+                false
+            }
+        }
+    }
+
+    async fn count_admins(&self) -> Result<i64, sqlx::Error> {
+        Ok(
+            sqlx::query!(r#"SELECT COUNT(*) AS "count!: i64" FROM admins"#)
+                .fetch_one(self)
+                .await?
+                .count,
+        )
+    }
+
+    async fn admins(&self) -> Result<Vec<Admin>, sqlx::Error> {
+        sqlx::query_as!(
+            Admin,
+            r#"SELECT telegram_id AS "telegram_id!", "name" FROM admins ORDER BY "name""#
+        )
+        .fetch_all(self)
+        .await
+    }
+
+    async fn admin_private_chats(&self) -> Result<Vec<ChatId>, sqlx::Error> {
+        Ok(self
+            .admins()
+            .await?
+            .into_iter()
+            .filter_map(|a| a.telegram_id.parse::<i64>().ok().map(ChatId))
+            .filter(|id| id.is_user())
+            .collect())
+    }
+
+    async fn admin_sessions(&self) -> Result<Vec<Session>, sqlx::Error> {
+        sqlx::query_as!(
+            Session,
+            r#"SELECT telegram_id AS "telegram_id!", "name", authenticated_at, auth_chat_id, auth_method
+            FROM admins ORDER BY authenticated_at DESC"#
+        )
+        .fetch_all(self)
+        .await
+    }
+
+    async fn add_admin(
+        &self,
+        telegram_id: &str,
+        name: &str,
+        method: &str,
+        chat_id: Option<&str>,
+        timestamp: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let added = sqlx::query!(
+            r#"INSERT OR IGNORE INTO admins(telegram_id, "name", authenticated_at, auth_chat_id, auth_method)
+            VALUES($1, $2, $3, $4, $5)"#,
+            telegram_id,
+            name,
+            timestamp,
+            chat_id,
+            method
+        )
+        .execute(self)
+        .await?
+        .rows_affected();
+
+        Ok(added > 0)
+    }
+
+    async fn add_or_rename_admin(
+        &self,
+        telegram_id: &str,
+        name: &str,
+        method: &str,
+        timestamp: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"INSERT INTO admins(telegram_id, "name", authenticated_at, auth_method)
+            VALUES($1, $2, $3, $4)
+            ON CONFLICT(telegram_id) DO UPDATE SET "name" = excluded."name""#,
+            telegram_id,
+            name,
+            timestamp,
+            method
+        )
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn remove_admins(&self, name: &str) -> Result<Vec<String>, sqlx::Error> {
+        Ok(sqlx::query!(
+            r#"DELETE FROM admins WHERE "name" = $1 RETURNING telegram_id AS "telegram_id!""#,
+            name
+        )
+        .fetch_all(self)
+        .await?
+        .into_iter()
+        .map(|a| a.telegram_id)
+        .collect())
+    }
+
+    async fn remove_admin_id(&self, telegram_id: &str) -> Result<bool, sqlx::Error> {
+        let removed = sqlx::query!("DELETE FROM admins WHERE telegram_id = $1", telegram_id)
+            .execute(self)
+            .await?
+            .rows_affected();
+
+        Ok(removed > 0)
+    }
+}
+
+/// Makes the account admin, or renames it if it already is, as part of the transaction of `conn`.
+/// Unlike [`AdminRepo::add_or_rename_admin`], no authentication is recorded.
+pub async fn import_admin(
+    conn: &mut SqliteConnection,
+    telegram_id: &str,
+    name: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"INSERT INTO admins(telegram_id, "name") VALUES($1, $2)
+        ON CONFLICT(telegram_id) DO UPDATE SET "name" = excluded."name""#,
+        telegram_id,
+        name
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
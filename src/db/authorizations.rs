@@ -0,0 +1,349 @@
+//! Commands which the chats are allowed to use, shared by the commands, the dashboard and the
+//! API.
+
+use std::{
+    collections::HashMap,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use sqlx::{SqliteConnection, SqlitePool};
+use tokio::sync::RwLock;
+
+use crate::db::retry_busy;
+
+/// Duration after which the authorizations of a chat are loaded again, to pick up the changes made
+/// by `roboclic-admin`, which runs in another process.
+const CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Commands allowed in a chat, with the forum topic they are bound to.
+type ChatAuthorizations = HashMap<String, Option<i32>>;
+
+/// Authorizations of each chat, kept in memory so that the commands do not query the database on
+/// every message.
+#[derive(Default)]
+pub struct AuthorizationCache {
+    chats: RwLock<HashMap<String, (Instant, ChatAuthorizations)>>,
+}
+
+static CACHE: OnceLock<AuthorizationCache> = OnceLock::new();
+pub fn authorization_cache() -> &'static AuthorizationCache {
+    CACHE.get_or_init(AuthorizationCache::default)
+}
+
+impl AuthorizationCache {
+    /// Whether the command is allowed in the chat, from the given forum topic. The authorizations
+    /// of the chat are loaded from the database if they are not cached or outdated.
+    pub async fn is_authorized(
+        &self,
+        db: &dyn AuthorizationRepo,
+        chat_id: &str,
+        command: &str,
+        thread_id: Option<i32>,
+    ) -> Result<bool, sqlx::Error> {
+        // A command bound to a forum topic is only authorized in this topic
+        let allowed = |commands: &ChatAuthorizations| {
+            commands
+                .get(command)
+                .is_some_and(|topic| topic.is_none() || *topic == thread_id)
+        };
+        if let Some((loaded_at, commands)) = self.chats.read().await.get(chat_id) {
+            if loaded_at.elapsed() < CACHE_TTL {
+                return Ok(allowed(commands));
+            }
+        }
+
+        let commands = db
+            .command_topics(chat_id)
+            .await?
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let authorized = allowed(&commands);
+        self.chats
+            .write()
+            .await
+            .insert(chat_id.to_owned(), (Instant::now(), commands));
+        Ok(authorized)
+    }
+
+    /// Forgets the cached authorizations of the chat, after they changed.
+    pub async fn invalidate(&self, chat_id: &str) {
+        self.chats.write().await.remove(chat_id);
+    }
+
+    /// Forgets all the cached authorizations, e.g. after a restore.
+    pub async fn clear(&self) {
+        self.chats.write().await.clear();
+    }
+}
+
+/// Command allowed in a chat.
+pub struct Authorization {
+    pub chat_id: String,
+    pub command: String,
+    /// Forum topic to which the command is restricted, if any.
+    pub thread_id: Option<i64>,
+}
+
+/// Queries of the authorizations, implemented by the database pool. The callers only depend on
+/// the trait, so that another implementation can stand in for the database, e.g. in the tests.
+#[async_trait]
+pub trait AuthorizationRepo: Send + Sync {
+    /// Commands the chat is allowed to use, with the forum topic they are bound to.
+    async fn command_topics(
+        &self,
+        chat_id: &str,
+    ) -> Result<Vec<(String, Option<i32>)>, sqlx::Error>;
+
+    /// Commands the chat is allowed to use, sorted by name.
+    async fn chat_authorizations(&self, chat_id: &str) -> Result<Vec<String>, sqlx::Error>;
+
+    /// Allows the chat to use the command. Returns whether it was not already allowed.
+    async fn grant(&self, chat_id: &str, command: &str) -> Result<bool, sqlx::Error>;
+
+    /// Forbids the chat to use the command. Returns whether it was allowed.
+    async fn revoke(&self, chat_id: &str, command: &str) -> Result<bool, sqlx::Error>;
+
+    /// Restricts an allowed command to a forum topic of the chat, or to the whole chat if
+    /// `thread_id` is `None`. Returns whether the command is allowed in the chat.
+    async fn bind_topic(
+        &self,
+        chat_id: &str,
+        command: &str,
+        thread_id: Option<i32>,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Forum topic to which the command is restricted in the chat, if any.
+    async fn command_topic(&self, chat_id: &str, command: &str)
+        -> Result<Option<i32>, sqlx::Error>;
+
+    /// Authorizations of the chat, or of all the chats if `chat_id` is empty, sorted by chat and
+    /// command.
+    async fn authorizations(&self, chat_id: &str) -> Result<Vec<Authorization>, sqlx::Error>;
+
+    /// Chats allowed to use at least one command, as `(chat_id, title)`, sorted by title.
+    async fn authorized_chats(&self) -> Result<Vec<(String, Option<String>)>, sqlx::Error>;
+
+    /// Chats allowed to use the command of which the user is a member, as `(chat_id, title)`,
+    /// sorted by title.
+    async fn member_chats_allowing(
+        &self,
+        user_id: &str,
+        command: &str,
+    ) -> Result<Vec<(String, Option<String>)>, sqlx::Error>;
+}
+
+#[async_trait]
+impl AuthorizationRepo for SqlitePool {
+    async fn command_topics(
+        &self,
+        chat_id: &str,
+    ) -> Result<Vec<(String, Option<i32>)>, sqlx::Error> {
+        Ok(sqlx::query!(
+            "SELECT command, thread_id FROM authorizations WHERE chat_id = $1",
+            chat_id
+        )
+        .fetch_all(self)
+        .await?
+        .into_iter()
+        .map(|r| (r.command, r.thread_id.map(|id| id as i32)))
+        .collect())
+    }
+
+    async fn chat_authorizations(&self, chat_id: &str) -> Result<Vec<String>, sqlx::Error> {
+        Ok(sqlx::query!(
+            "SELECT command FROM authorizations WHERE chat_id = $1 ORDER BY command",
+            chat_id
+        )
+        .fetch_all(self)
+        .await?
+        .into_iter()
+        .map(|r| r.command)
+        .collect())
+    }
+
+    async fn grant(&self, chat_id: &str, command: &str) -> Result<bool, sqlx::Error> {
+        let granted = retry_busy(|| async move {
+            let mut tx = self.begin().await?;
+            let already_authorized = sqlx::query!(
+                r#"SELECT COUNT(*) AS count FROM authorizations WHERE chat_id = $1 AND command = $2"#,
+                chat_id,
+                command
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+
+            if already_authorized.count == 0 {
+                sqlx::query!(
+                    r#"INSERT INTO authorizations(command, chat_id) VALUES($1, $2)"#,
+                    command,
+                    chat_id
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            tx.commit().await?;
+            Ok(already_authorized.count == 0)
+        })
+        .await?;
+        authorization_cache().invalidate(chat_id).await;
+
+        Ok(granted)
+    }
+
+    async fn revoke(&self, chat_id: &str, command: &str) -> Result<bool, sqlx::Error> {
+        let removed = sqlx::query!(
+            r#"DELETE FROM authorizations WHERE command = $1 AND chat_id = $2"#,
+            command,
+            chat_id
+        )
+        .execute(self)
+        .await?
+        .rows_affected();
+        authorization_cache().invalidate(chat_id).await;
+
+        Ok(removed > 0)
+    }
+
+    async fn bind_topic(
+        &self,
+        chat_id: &str,
+        command: &str,
+        thread_id: Option<i32>,
+    ) -> Result<bool, sqlx::Error> {
+        let updated = sqlx::query!(
+            "UPDATE authorizations SET thread_id = $1 WHERE chat_id = $2 AND command = $3",
+            thread_id,
+            chat_id,
+            command
+        )
+        .execute(self)
+        .await?
+        .rows_affected();
+        authorization_cache().invalidate(chat_id).await;
+
+        Ok(updated > 0)
+    }
+
+    async fn command_topic(
+        &self,
+        chat_id: &str,
+        command: &str,
+    ) -> Result<Option<i32>, sqlx::Error> {
+        Ok(sqlx::query!(
+            "SELECT thread_id FROM authorizations WHERE chat_id = $1 AND command = $2",
+            chat_id,
+            command
+        )
+        .fetch_optional(self)
+        .await?
+        .and_then(|r| r.thread_id)
+        .map(|id| id as i32))
+    }
+
+    async fn authorizations(&self, chat_id: &str) -> Result<Vec<Authorization>, sqlx::Error> {
+        sqlx::query_as!(
+            Authorization,
+            "SELECT chat_id, command, thread_id FROM authorizations WHERE $1 = '' OR chat_id = $1
+            ORDER BY chat_id, command",
+            chat_id
+        )
+        .fetch_all(self)
+        .await
+    }
+
+    async fn authorized_chats(&self) -> Result<Vec<(String, Option<String>)>, sqlx::Error> {
+        Ok(sqlx::query!(
+            r#"SELECT DISTINCT a.chat_id AS "chat_id!", c.title FROM authorizations a
+            LEFT JOIN chats c ON c.chat_id = a.chat_id ORDER BY c.title"#
+        )
+        .fetch_all(self)
+        .await?
+        .into_iter()
+        .map(|c| (c.chat_id, c.title))
+        .collect())
+    }
+
+    async fn member_chats_allowing(
+        &self,
+        user_id: &str,
+        command: &str,
+    ) -> Result<Vec<(String, Option<String>)>, sqlx::Error> {
+        Ok(sqlx::query!(
+            r#"SELECT DISTINCT a.chat_id AS "chat_id!", c.title FROM authorizations a
+            JOIN chat_members m ON m.chat_id = a.chat_id AND m.user_id = $1
+            LEFT JOIN chats c ON c.chat_id = a.chat_id
+            WHERE a.command = $2 ORDER BY c.title"#,
+            user_id,
+            command
+        )
+        .fetch_all(self)
+        .await?
+        .into_iter()
+        .map(|c| (c.chat_id, c.title))
+        .collect())
+    }
+}
+
+/// Forbids the chat to use the command, as part of the transaction of `conn`. Returns whether it
+/// was allowed. The cache is left to the caller, to be invalidated once committed.
+pub async fn remove_authorization(
+    conn: &mut SqliteConnection,
+    chat_id: &str,
+    command: &str,
+) -> Result<bool, sqlx::Error> {
+    let removed = sqlx::query!(
+        "DELETE FROM authorizations WHERE command = $1 AND chat_id = $2",
+        command,
+        chat_id
+    )
+    .execute(conn)
+    .await?
+    .rows_affected();
+
+    Ok(removed > 0)
+}
+
+/// Allows the chat to use the command, as part of the transaction of `conn`. Returns whether it
+/// was not already allowed. The cache is left to the caller, to be invalidated once committed.
+pub async fn restore_authorization(
+    conn: &mut SqliteConnection,
+    chat_id: &str,
+    command: &str,
+) -> Result<bool, sqlx::Error> {
+    let granted = sqlx::query!(
+        "INSERT INTO authorizations(command, chat_id)
+        SELECT $1, $2 WHERE NOT EXISTS
+            (SELECT 1 FROM authorizations WHERE command = $1 AND chat_id = $2)",
+        command,
+        chat_id
+    )
+    .execute(conn)
+    .await?
+    .rows_affected();
+
+    Ok(granted > 0)
+}
+
+/// Adds the authorization, unless the chat already has it for the same forum topic, as part of
+/// the transaction of `conn`.
+pub async fn import_authorization(
+    conn: &mut SqliteConnection,
+    chat_id: &str,
+    command: &str,
+    thread_id: Option<i64>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO authorizations(command, chat_id, thread_id) SELECT $1, $2, $3
+        WHERE NOT EXISTS (SELECT 1 FROM authorizations
+            WHERE command = $1 AND chat_id = $2 AND thread_id IS $3)",
+        command,
+        chat_id,
+        thread_id
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
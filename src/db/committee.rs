@@ -0,0 +1,129 @@
+//! Links between the committee members of Directus and their Telegram accounts.
+
+use async_trait::async_trait;
+use sqlx::{SqliteConnection, SqliteExecutor, SqlitePool};
+
+/// Queries of the links, implemented by the database pool. The callers only depend on the trait,
+/// so that another implementation can stand in for the database, e.g. in the tests. The helpers
+/// below run in the transaction of the caller instead.
+#[async_trait]
+pub trait CommitteeRepo: Send + Sync {
+    /// Links the Telegram account to the committee member, replacing the previous links of both.
+    async fn link_member(&self, name: &str, telegram_id: &str) -> Result<(), sqlx::Error>;
+
+    /// Whether the Telegram account is linked to a committee member.
+    async fn is_linked(&self, telegram_id: &str) -> Result<bool, sqlx::Error>;
+
+    /// All the links, as `(name, telegram_id)`, sorted by name.
+    async fn links(&self) -> Result<Vec<(String, String)>, sqlx::Error>;
+}
+
+#[async_trait]
+impl CommitteeRepo for SqlitePool {
+    async fn link_member(&self, name: &str, telegram_id: &str) -> Result<(), sqlx::Error> {
+        let mut tx = self.begin().await?;
+        // An account is linked to a single member
+        sqlx::query!(
+            "DELETE FROM committee_links WHERE telegram_id = $1",
+            telegram_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "INSERT INTO committee_links(name, telegram_id) VALUES($1, $2)
+            ON CONFLICT(name) DO UPDATE SET telegram_id = excluded.telegram_id",
+            name,
+            telegram_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await
+    }
+
+    async fn is_linked(&self, telegram_id: &str) -> Result<bool, sqlx::Error> {
+        Ok(sqlx::query!(
+            r#"SELECT COUNT(*) AS "count!: i64" FROM committee_links WHERE telegram_id = $1"#,
+            telegram_id
+        )
+        .fetch_one(self)
+        .await?
+        .count
+            > 0)
+    }
+
+    async fn links(&self) -> Result<Vec<(String, String)>, sqlx::Error> {
+        Ok(sqlx::query!(
+            r#"SELECT name AS "name!", telegram_id FROM committee_links ORDER BY name"#
+        )
+        .fetch_all(self)
+        .await?
+        .into_iter()
+        .map(|r| (r.name, r.telegram_id))
+        .collect())
+    }
+}
+
+/// Links of the member or of the Telegram account, as `(name, telegram_id)`, which
+/// [`CommitteeRepo::link_member`] replaces.
+pub async fn links_of(
+    db: impl SqliteExecutor<'_>,
+    name: &str,
+    telegram_id: &str,
+) -> Result<Vec<(String, String)>, sqlx::Error> {
+    Ok(sqlx::query!(
+        r#"SELECT name AS "name!", telegram_id FROM committee_links WHERE name = $1 OR telegram_id = $2"#,
+        name,
+        telegram_id
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|r| (r.name, r.telegram_id))
+    .collect())
+}
+
+/// Removes the link between the member and the Telegram account, and restores the `previous`
+/// links returned by [`links_of`] before it was created, as part of the transaction of `conn`.
+pub async fn restore_links(
+    conn: &mut SqliteConnection,
+    name: &str,
+    telegram_id: &str,
+    previous: &[(String, String)],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM committee_links WHERE name = $1 OR telegram_id = $2",
+        name,
+        telegram_id
+    )
+    .execute(&mut *conn)
+    .await?;
+    for (name, telegram_id) in previous {
+        sqlx::query!(
+            "INSERT INTO committee_links(name, telegram_id) VALUES($1, $2)
+            ON CONFLICT DO NOTHING",
+            name,
+            telegram_id
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Links the member to the Telegram account, replacing the links of both, as part of the
+/// transaction of `conn`.
+pub async fn import_link(
+    conn: &mut SqliteConnection,
+    name: &str,
+    telegram_id: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT OR REPLACE INTO committee_links(name, telegram_id) VALUES($1, $2)",
+        name,
+        telegram_id
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
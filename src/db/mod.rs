@@ -0,0 +1,106 @@
+use std::{future::Future, str::FromStr, time::Duration};
+
+use log::LevelFilter;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous},
+    ConnectOptions, SqlitePool,
+};
+
+use crate::config::config;
+
+pub mod admins;
+pub mod authorizations;
+pub mod committee;
+pub mod quotes;
+
+/// Time during which SQLite waits for a lock to be released before failing with `SQLITE_BUSY`.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Maximum number of attempts of an operation failing because the database is busy.
+const MAX_ATTEMPTS: u32 = 5;
+/// Delay before the first retry, doubled at each attempt.
+const RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// Url of the database, from the config.
+pub fn database_url() -> String {
+    config()
+        .database_url
+        .clone()
+        .unwrap_or_else(|| format!("sqlite://{}/db.sqlite", config().data_dir))
+}
+
+/// Opens the database, creating it if needed, with the pool size and the pragmas of the config.
+/// WAL mode (the default) lets readers proceed while a write is in progress, and the busy timeout
+/// makes concurrent writers wait for each other instead of failing.
+pub async fn connect(url: &str) -> Result<SqlitePool, sqlx::Error> {
+    let config = config();
+    let journal_mode =
+        SqliteJournalMode::from_str(&config.sqlite_journal_mode).unwrap_or(SqliteJournalMode::Wal);
+    let synchronous =
+        SqliteSynchronous::from_str(&config.sqlite_synchronous).unwrap_or(SqliteSynchronous::Full);
+    let mut options = SqliteConnectOptions::from_str(url)?
+        .create_if_missing(true)
+        .journal_mode(journal_mode)
+        .synchronous(synchronous)
+        .busy_timeout(BUSY_TIMEOUT);
+    if let Some(cache_size) = config.sqlite_cache_size {
+        options = options.pragma("cache_size", cache_size.to_string());
+    }
+    // The slow queries are logged with their statement by sqlx
+    if let Some(threshold) = config.diagnostics_threshold_ms {
+        options = options.log_slow_statements(LevelFilter::Warn, Duration::from_millis(threshold));
+    }
+
+    SqlitePoolOptions::new()
+        .max_connections(config.database_max_connections)
+        .acquire_timeout(Duration::from_secs(config.database_acquire_timeout))
+        .connect_with(options)
+        .await
+}
+
+/// Whether the error is `SQLITE_BUSY` or `SQLITE_LOCKED` (or one of their extended codes).
+fn is_busy(error: &sqlx::Error) -> bool {
+    error
+        .as_database_error()
+        .and_then(|e| e.code())
+        .and_then(|code| code.parse::<i32>().ok())
+        .is_some_and(|code| matches!(code & 0xff, 5 | 6))
+}
+
+/// Runs the operation, retrying it with an exponential backoff while the database is busy.
+///
+/// The busy timeout does not apply when a transaction which has already read needs to write
+/// while another connection is writing: SQLite fails immediately to avoid a deadlock. Write
+/// transactions are hence wrapped in this helper, which rolls them back and retries them whole:
+///
+/// ```ignore
+/// retry_busy(|| async move {
+///     let mut tx = db.begin().await?;
+///     // ...
+///     tx.commit().await?;
+///     Ok(())
+/// })
+/// .await?;
+/// ```
+pub async fn retry_busy<T, F, Fut>(mut operation: F) -> Result<T, sqlx::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    let mut delay = RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Err(e) if is_busy(&e) && attempt < MAX_ATTEMPTS => {
+                log::warn!(
+                    "Database busy (attempt {}), retrying in {:?}",
+                    attempt,
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
@@ -0,0 +1,322 @@
+//! Quotes of the quizzes, shared by the quizzes, the reports, the elections and the dashboard.
+
+use async_trait::async_trait;
+use sqlx::{SqliteConnection, SqlitePool};
+
+pub struct Quote {
+    pub author: String,
+    pub text: String,
+}
+
+/// Quote as stored, for the listings and the exports.
+pub struct StoredQuote {
+    pub id: i64,
+    pub chat_id: String,
+    pub author: String,
+    pub text: String,
+    /// Unix timestamp (seconds).
+    pub created_at: i64,
+    /// Quiz in which the quote was sent, with the index of its correct answer.
+    pub poll_id: Option<String>,
+    pub correct_option: Option<i64>,
+    /// Title of the chat, if it is in the registry.
+    pub chat_title: Option<String>,
+}
+
+/// Queries of the quotes, implemented by the database pool. The callers only depend on the trait,
+/// so that another implementation can stand in for the database, e.g. in the tests.
+#[async_trait]
+pub trait QuoteRepo: Send + Sync {
+    /// Records the quote of a quiz sent in the chat. Returns its id.
+    async fn insert_quote(
+        &self,
+        chat_id: &str,
+        author: &str,
+        text: &str,
+        poll_id: Option<&str>,
+        correct_option: u8,
+        timestamp: i64,
+    ) -> Result<i64, sqlx::Error>;
+
+    async fn quote(&self, id: i64) -> Result<Option<Quote>, sqlx::Error>;
+
+    /// Random quote of the chat.
+    async fn random_quote(&self, chat_id: &str) -> Result<Option<Quote>, sqlx::Error>;
+
+    /// Attributes the quote to another author. Returns the previous one, `None` if the quote does
+    /// not exist.
+    async fn set_quote_author(&self, id: i64, author: &str) -> Result<Option<String>, sqlx::Error>;
+
+    /// Returns whether the quote existed.
+    async fn delete_quote(&self, id: i64) -> Result<bool, sqlx::Error>;
+
+    /// Number of quotes of each author, the most quoted first.
+    async fn quote_counts_by_author(&self) -> Result<Vec<(String, i64)>, sqlx::Error>;
+
+    /// Share of the voters who found the author of the archived quizzes, by author:
+    /// the author, the number of correct answers and the total number of answers.
+    async fn quote_success_rates(&self) -> Result<Vec<(String, i64, i64)>, sqlx::Error>;
+
+    /// Number of quotes.
+    async fn count_quotes(&self) -> Result<i64, sqlx::Error>;
+
+    /// Latest quotes of the author in the chat, the most recent first. An empty `author` or
+    /// `chat_id` matches any.
+    async fn latest_quotes(
+        &self,
+        author: &str,
+        chat_id: &str,
+        limit: i64,
+    ) -> Result<Vec<StoredQuote>, sqlx::Error>;
+
+    /// Latest quotes whose text or author contains the keyword, the most recent first.
+    async fn search_quotes(
+        &self,
+        keyword: &str,
+        limit: i64,
+    ) -> Result<Vec<StoredQuote>, sqlx::Error>;
+
+    /// All the quotes, in the order they were added.
+    async fn all_quotes(&self) -> Result<Vec<StoredQuote>, sqlx::Error>;
+
+    /// Number of quotes of each chat, as its title (or its id if it is not in the registry), the
+    /// chat with the most quotes first.
+    async fn quote_counts_by_chat(&self) -> Result<Vec<(String, i64)>, sqlx::Error>;
+
+    /// Number of quotes of each author added to the chat since the timestamp, the most quoted
+    /// first.
+    async fn chat_quote_counts(
+        &self,
+        chat_id: &str,
+        since: i64,
+    ) -> Result<Vec<(String, i64)>, sqlx::Error>;
+
+    /// Removes the quotes older than the timestamp, with their reports. Returns how many.
+    async fn delete_quotes_before(&self, timestamp: i64) -> Result<u64, sqlx::Error>;
+}
+
+#[async_trait]
+impl QuoteRepo for SqlitePool {
+    async fn insert_quote(
+        &self,
+        chat_id: &str,
+        author: &str,
+        text: &str,
+        poll_id: Option<&str>,
+        correct_option: u8,
+        timestamp: i64,
+    ) -> Result<i64, sqlx::Error> {
+        Ok(sqlx::query!(
+            r#"INSERT INTO quotes(chat_id, author, "text", created_at, poll_id, correct_option) VALUES($1, $2, $3, $4, $5, $6)"#,
+            chat_id,
+            author,
+            text,
+            timestamp,
+            poll_id,
+            correct_option
+        )
+        .execute(self)
+        .await?
+        .last_insert_rowid())
+    }
+
+    async fn quote(&self, id: i64) -> Result<Option<Quote>, sqlx::Error> {
+        sqlx::query_as!(
+            Quote,
+            r#"SELECT author, "text" FROM quotes WHERE id = $1"#,
+            id
+        )
+        .fetch_optional(self)
+        .await
+    }
+
+    async fn random_quote(&self, chat_id: &str) -> Result<Option<Quote>, sqlx::Error> {
+        sqlx::query_as!(
+            Quote,
+            r#"SELECT author, "text" FROM quotes WHERE chat_id = $1 ORDER BY RANDOM() LIMIT 1"#,
+            chat_id
+        )
+        .fetch_optional(self)
+        .await
+    }
+
+    async fn set_quote_author(&self, id: i64, author: &str) -> Result<Option<String>, sqlx::Error> {
+        let mut tx = self.begin().await?;
+        let previous = sqlx::query!("SELECT author FROM quotes WHERE id = $1", id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .map(|r| r.author);
+        sqlx::query!("UPDATE quotes SET author = $1 WHERE id = $2", author, id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(previous)
+    }
+
+    async fn delete_quote(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let removed = sqlx::query!("DELETE FROM quotes WHERE id = $1", id)
+            .execute(self)
+            .await?
+            .rows_affected();
+
+        Ok(removed > 0)
+    }
+
+    async fn quote_counts_by_author(&self) -> Result<Vec<(String, i64)>, sqlx::Error> {
+        Ok(sqlx::query!(
+            r#"SELECT author, COUNT(*) AS "count!: i64" FROM quotes GROUP BY author
+            ORDER BY 2 DESC, author"#
+        )
+        .fetch_all(self)
+        .await?
+        .into_iter()
+        .map(|r| (r.author, r.count))
+        .collect())
+    }
+
+    async fn quote_success_rates(&self) -> Result<Vec<(String, i64, i64)>, sqlx::Error> {
+        Ok(sqlx::query!(
+            r#"SELECT q.author,
+                SUM(CASE WHEN r.option = q.correct_option THEN r.voter_count ELSE 0 END) AS "correct!: i64",
+                SUM(r.voter_count) AS "total!: i64"
+            FROM poll_results r JOIN quotes q ON q.poll_id = r.poll_id
+            GROUP BY q.author"#
+        )
+        .fetch_all(self)
+        .await?
+        .into_iter()
+        .map(|r| (r.author, r.correct, r.total))
+        .collect())
+    }
+
+    async fn count_quotes(&self) -> Result<i64, sqlx::Error> {
+        Ok(
+            sqlx::query!(r#"SELECT COUNT(*) AS "count!: i64" FROM quotes"#)
+                .fetch_one(self)
+                .await?
+                .count,
+        )
+    }
+
+    async fn latest_quotes(
+        &self,
+        author: &str,
+        chat_id: &str,
+        limit: i64,
+    ) -> Result<Vec<StoredQuote>, sqlx::Error> {
+        sqlx::query_as!(
+            StoredQuote,
+            r#"SELECT q.id AS "id!", q.chat_id, q.author, q."text", q.created_at, q.poll_id,
+                q.correct_option, c.title AS chat_title
+            FROM quotes q LEFT JOIN chats c ON c.chat_id = q.chat_id
+            WHERE ($1 = '' OR q.author = $1) AND ($2 = '' OR q.chat_id = $2)
+            ORDER BY q.created_at DESC LIMIT $3"#,
+            author,
+            chat_id,
+            limit
+        )
+        .fetch_all(self)
+        .await
+    }
+
+    async fn search_quotes(
+        &self,
+        keyword: &str,
+        limit: i64,
+    ) -> Result<Vec<StoredQuote>, sqlx::Error> {
+        let pattern = format!("%{}%", keyword);
+        sqlx::query_as!(
+            StoredQuote,
+            r#"SELECT q.id AS "id!", q.chat_id, q.author, q."text", q.created_at, q.poll_id,
+                q.correct_option, c.title AS chat_title
+            FROM quotes q LEFT JOIN chats c ON c.chat_id = q.chat_id
+            WHERE q."text" LIKE $1 OR q.author LIKE $1
+            ORDER BY q.created_at DESC LIMIT $2"#,
+            pattern,
+            limit
+        )
+        .fetch_all(self)
+        .await
+    }
+
+    async fn all_quotes(&self) -> Result<Vec<StoredQuote>, sqlx::Error> {
+        sqlx::query_as!(
+            StoredQuote,
+            r#"SELECT q.id AS "id!", q.chat_id, q.author, q."text", q.created_at, q.poll_id,
+                q.correct_option, c.title AS chat_title
+            FROM quotes q LEFT JOIN chats c ON c.chat_id = q.chat_id ORDER BY q.id"#
+        )
+        .fetch_all(self)
+        .await
+    }
+
+    async fn quote_counts_by_chat(&self) -> Result<Vec<(String, i64)>, sqlx::Error> {
+        Ok(sqlx::query!(
+            r#"SELECT COALESCE(c.title, q.chat_id) AS "chat!: String", COUNT(*) AS "count!: i64"
+            FROM quotes q LEFT JOIN chats c ON c.chat_id = q.chat_id GROUP BY q.chat_id ORDER BY 2 DESC"#
+        )
+        .fetch_all(self)
+        .await?
+        .into_iter()
+        .map(|r| (r.chat, r.count))
+        .collect())
+    }
+
+    async fn chat_quote_counts(
+        &self,
+        chat_id: &str,
+        since: i64,
+    ) -> Result<Vec<(String, i64)>, sqlx::Error> {
+        Ok(sqlx::query!(
+            r#"SELECT author, COUNT(*) AS "count!: i64" FROM quotes
+            WHERE chat_id = $1 AND created_at >= $2 GROUP BY author ORDER BY 2 DESC, author"#,
+            chat_id,
+            since
+        )
+        .fetch_all(self)
+        .await?
+        .into_iter()
+        .map(|r| (r.author, r.count))
+        .collect())
+    }
+
+    async fn delete_quotes_before(&self, timestamp: i64) -> Result<u64, sqlx::Error> {
+        // Their reports are removed along, by the foreign key
+        Ok(
+            sqlx::query!("DELETE FROM quotes WHERE created_at < $1", timestamp)
+                .execute(self)
+                .await?
+                .rows_affected(),
+        )
+    }
+}
+
+/// Adds the quote, unless it is already there (same chat, author, text and date), as part of the
+/// transaction of `conn`. Existing quotes are never replaced, since their reports would be
+/// deleted with them.
+pub async fn import_quote(
+    conn: &mut SqliteConnection,
+    chat_id: &str,
+    author: &str,
+    text: &str,
+    created_at: i64,
+    poll_id: Option<&str>,
+    correct_option: Option<i64>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"INSERT INTO quotes(chat_id, author, "text", created_at, poll_id, correct_option)
+        SELECT $1, $2, $3, $4, $5, $6 WHERE NOT EXISTS (SELECT 1 FROM quotes
+            WHERE chat_id = $1 AND author = $2 AND "text" = $3 AND created_at = $4)"#,
+        chat_id,
+        author,
+        text,
+        created_at,
+        poll_id,
+        correct_option
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
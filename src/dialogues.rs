@@ -0,0 +1,276 @@
+use std::{fmt::Display, future::Future, pin::Pin, sync::Arc};
+
+use sqlx::SqlitePool;
+use teloxide::{
+    dispatching::dialogue::Storage, payloads::SendMessageSetters, requests::Requester,
+    types::ChatId, Bot,
+};
+
+use crate::{
+    cmd_poll::{send_target_keyboard, PollState},
+    dates::now,
+    i18n::{chat_language, tr, Lang},
+    outbox::Queued,
+    permissions::delete_own_message,
+    wizard::with_cancel,
+};
+
+/// Dialogues untouched for longer than that are cancelled, by the scheduler or at startup instead
+/// of being resumed.
+const DIALOGUE_TIMEOUT: i64 = 24 * 60 * 60;
+
+fn cancelled(lang: Lang) -> String {
+    tr!(
+        lang,
+        "Désolé, le bot a redémarré et la commande en cours a été annulée. Tu peux la relancer.",
+        "Sorry, the bot restarted and the ongoing command was cancelled. You can start it again."
+    )
+}
+
+fn timed_out(lang: Lang) -> String {
+    tr!(
+        lang,
+        "La commande en cours a été annulée, faute de réponse depuis {} heures. Tu peux la relancer.",
+        "The ongoing command was cancelled, as it was left unanswered for {} hours. You can start it again.",
+        DIALOGUE_TIMEOUT / 3600
+    )
+}
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+#[derive(Debug)]
+pub enum Error {
+    Database(sqlx::Error),
+    Serde(serde_json::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Database(e) => write!(f, "database error: {e}"),
+            Error::Serde(e) => write!(f, "invalid dialogue state: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<sqlx::Error> for Error {
+    fn from(value: sqlx::Error) -> Self {
+        Self::Database(value)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Serde(value)
+    }
+}
+
+/// Storage of the dialogues in the database, so that they survive a restart of the bot.
+pub struct DialogueStorage {
+    db: Arc<SqlitePool>,
+}
+
+impl DialogueStorage {
+    pub fn new(db: Arc<SqlitePool>) -> Arc<Self> {
+        Arc::new(Self { db })
+    }
+}
+
+impl Storage<PollState> for DialogueStorage {
+    type Error = Error;
+
+    fn remove_dialogue(self: Arc<Self>, chat_id: ChatId) -> BoxFuture<Result<(), Error>> {
+        Box::pin(async move {
+            let chat_id = chat_id.to_string();
+            sqlx::query!("DELETE FROM dialogues WHERE chat_id = $1", chat_id)
+                .execute(self.db.as_ref())
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn update_dialogue(
+        self: Arc<Self>,
+        chat_id: ChatId,
+        dialogue: PollState,
+    ) -> BoxFuture<Result<(), Error>> {
+        Box::pin(async move {
+            let chat_id = chat_id.to_string();
+            let state = serde_json::to_string(&dialogue)?;
+            let timestamp = now().timestamp();
+            sqlx::query!(
+                r#"INSERT INTO dialogues(chat_id, "state", updated_at) VALUES($1, $2, $3)
+                ON CONFLICT(chat_id) DO UPDATE SET "state" = excluded."state", updated_at = excluded.updated_at"#,
+                chat_id,
+                state,
+                timestamp
+            )
+            .execute(self.db.as_ref())
+            .await?;
+            Ok(())
+        })
+    }
+
+    fn get_dialogue(
+        self: Arc<Self>,
+        chat_id: ChatId,
+    ) -> BoxFuture<Result<Option<PollState>, Error>> {
+        Box::pin(async move {
+            let chat_id = chat_id.to_string();
+            let state = sqlx::query!(
+                r#"SELECT "state" FROM dialogues WHERE chat_id = $1"#,
+                chat_id
+            )
+            .fetch_optional(self.db.as_ref())
+            .await?;
+
+            match state {
+                Some(s) => Ok(Some(serde_json::from_str(&s.state)?)),
+                None => Ok(None),
+            }
+        })
+    }
+}
+
+/// Resumes the /poll dialogues interrupted by a restart, by sending their pending prompt again.
+/// Dialogues which are too old, or whose prompt cannot be sent again, are cancelled with an
+/// apology.
+pub async fn resume_dialogues(bot: &Bot, db: &SqlitePool) -> Result<(), Error> {
+    let dialogues = sqlx::query!(r#"SELECT chat_id, "state", updated_at FROM dialogues"#)
+        .fetch_all(db)
+        .await?;
+    let expired = now().timestamp() - DIALOGUE_TIMEOUT;
+
+    for d in dialogues {
+        let Ok(chat_id) = d.chat_id.parse::<i64>().map(ChatId) else {
+            continue;
+        };
+        let state = match serde_json::from_str::<PollState>(&d.state) {
+            Ok(state) => state,
+            Err(e) => {
+                log::error!("Invalid dialogue state in {}: {:?}", chat_id, e);
+                sqlx::query!("DELETE FROM dialogues WHERE chat_id = $1", d.chat_id)
+                    .execute(db)
+                    .await?;
+                continue;
+            }
+        };
+
+        let lang = chat_language(db, chat_id).await;
+        let resumed = match state {
+            // Left to the scheduler, like the other expired dialogues
+            _ if d.updated_at <= expired => continue,
+            PollState::ChooseTarget {
+                message_id,
+                initiator,
+                quote,
+            } => {
+                delete_own_message(bot, chat_id, message_id).await;
+                match send_target_keyboard(bot, chat_id, lang).await {
+                    Ok(Some(sent)) => Some(PollState::ChooseTarget {
+                        message_id: sent.id,
+                        initiator,
+                        quote,
+                    }),
+                    Ok(None) => None,
+                    Err(e) => {
+                        log::error!("Could not resume dialogue in {}: {:?}", chat_id, e);
+                        None
+                    }
+                }
+            }
+            PollState::SetQuote {
+                message_id,
+                target,
+                initiator,
+            } => {
+                delete_own_message(bot, chat_id, message_id).await;
+                match bot
+                    .send_message(
+                        chat_id,
+                        tr!(lang, "Qu'a dit {} ?", "What did {} say?", target),
+                    )
+                    .reply_markup(with_cancel(None, lang))
+                    .queued()
+                    .await
+                {
+                    Ok(sent) => Some(PollState::SetQuote {
+                        message_id: sent.id,
+                        target,
+                        initiator,
+                    }),
+                    Err(e) => {
+                        log::error!("Could not resume dialogue in {}: {:?}", chat_id, e);
+                        None
+                    }
+                }
+            }
+            // The other dialogues are left as they were, their keyboards are still valid
+            _ => continue,
+        };
+
+        match resumed {
+            Some(state) => {
+                log::info!("Resumed dialogue in {}", chat_id);
+                let state = serde_json::to_string(&state)?;
+                sqlx::query!(
+                    r#"UPDATE dialogues SET "state" = $1 WHERE chat_id = $2"#,
+                    state,
+                    d.chat_id
+                )
+                .execute(db)
+                .await?;
+            }
+            None => {
+                log::info!("Cancelled interrupted dialogue in {}", chat_id);
+                if let Err(e) = bot.send_message(chat_id, cancelled(lang)).queued().await {
+                    log::error!("Could not notify {} of the cancellation: {:?}", chat_id, e);
+                }
+                sqlx::query!("DELETE FROM dialogues WHERE chat_id = $1", d.chat_id)
+                    .execute(db)
+                    .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Cancels the dialogues left unanswered for longer than [`DIALOGUE_TIMEOUT`]: their prompt is
+/// deleted, and the chat is told the command was cancelled. Run by the scheduler.
+pub async fn expire_dialogues(bot: &Bot, db: &SqlitePool) -> Result<(), Error> {
+    let expired_before = now().timestamp() - DIALOGUE_TIMEOUT;
+    let dialogues = sqlx::query!(
+        r#"SELECT chat_id, "state" FROM dialogues WHERE updated_at <= $1"#,
+        expired_before
+    )
+    .fetch_all(db)
+    .await?;
+
+    for d in dialogues {
+        sqlx::query!("DELETE FROM dialogues WHERE chat_id = $1", d.chat_id)
+            .execute(db)
+            .await?;
+        let Ok(chat_id) = d.chat_id.parse::<i64>().map(ChatId) else {
+            continue;
+        };
+        // Finished dialogues are only reset
+        let Some(message_id) = serde_json::from_str::<PollState>(&d.state)
+            .ok()
+            .and_then(|state| state.prompt())
+        else {
+            continue;
+        };
+
+        log::info!("Cancelled expired dialogue in {}", chat_id);
+        delete_own_message(bot, chat_id, message_id).await;
+        let lang = chat_language(db, chat_id).await;
+        if let Err(e) = bot.send_message(chat_id, timed_out(lang)).queued().await {
+            log::error!("Could not notify {} of the cancellation: {:?}", chat_id, e);
+        }
+    }
+
+    Ok(())
+}
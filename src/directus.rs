@@ -1,16 +1,41 @@
-use log::error;
-use reqwest::Client;
+use std::{
+    fmt::Display,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use log::{error, info, warn};
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode, Url};
 use serde::Deserialize;
-use tokio::task::JoinSet;
+use serde_json::json;
+use tokio::{sync::Mutex, task::JoinSet};
 
-use crate::config::config;
+use crate::{config::config, dates::TIMEZONE};
 
 #[derive(Debug)]
 pub enum Error {
     Request(reqwest::Error),
     Serde(serde_json::Error),
+    /// Directus rejected the credentials, even after re-authenticating.
+    Unauthorized,
+    /// No credentials are configured (neither a static token nor an email/password pair).
+    MissingCredentials,
 }
 
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Request(e) => write!(f, "request error: {e}"),
+            Error::Serde(e) => write!(f, "invalid response: {e}"),
+            Error::Unauthorized => write!(f, "credentials rejected by Directus"),
+            Error::MissingCredentials => write!(f, "no Directus credentials configured"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
 impl From<reqwest::Error> for Error {
     fn from(value: reqwest::Error) -> Self {
         Self::Request(value)
@@ -23,7 +48,7 @@ impl From<serde_json::Error> for Error {
     }
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Committee {
     pub id: i32,
     #[serde(rename = "surname")]
@@ -31,44 +56,376 @@ pub struct Committee {
     pub poll_count: i32,
 }
 
+/// A merch item sold by the association.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ShopItem {
+    pub name: String,
+    pub price: f64,
+    /// Not set for the items made to order.
+    pub stock: Option<i32>,
+}
+
+/// A page of the documentation of the association.
+#[derive(Deserialize, Debug, Clone)]
+pub struct WikiPage {
+    pub title: String,
+    pub url: String,
+    pub summary: Option<String>,
+}
+
+/// A support ticket filed for the IT team.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Ticket {
+    pub id: i32,
+    pub description: String,
+    pub reporter_telegram_id: Option<String>,
+    /// Chat in which the ticket was filed.
+    pub chat_id: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Event {
+    pub id: i32,
+    pub title: String,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub start_date: String,
+    pub end_date: Option<String>,
+    /// Whether the members register to the event through the bot.
+    #[serde(default)]
+    pub registrable: bool,
+}
+
+impl Event {
+    pub fn start(&self) -> Option<DateTime<Utc>> {
+        parse_date(&self.start_date)
+    }
+
+    pub fn end(&self) -> Option<DateTime<Utc>> {
+        self.end_date.as_deref().and_then(parse_date)
+    }
+}
+
+/// Parses a Directus date, which can be either a timestamp (with timezone) or a datetime (in
+/// the association's local time).
+fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(date)
+        .map(|d| d.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S")
+                .ok()
+                .and_then(|d| TIMEZONE.from_local_datetime(&d).earliest())
+                .map(|d| d.with_timezone(&Utc))
+        })
+}
+
 #[derive(Deserialize, Debug)]
 struct DirectusResponse<T> {
     data: T,
 }
 
-pub async fn get_committee() -> Result<Vec<Committee>, Error> {
-    #[derive(Deserialize, Debug)]
-    struct Member {
-        member: Committee,
+// ----------------------------- AUTHENTICATION -------------------------------
+
+/// Tokens obtained through `/auth/login` or `/auth/refresh`.
+#[derive(Deserialize, Debug)]
+struct Session {
+    access_token: String,
+    refresh_token: String,
+    /// Validity of the access token, in milliseconds.
+    expires: u64,
+    #[serde(skip, default = "Instant::now")]
+    obtained_at: Instant,
+}
+
+impl Session {
+    fn is_expired(&self) -> bool {
+        // Refresh slightly ahead of time to avoid racing the expiration
+        self.obtained_at.elapsed() + Duration::from_secs(30) >= Duration::from_millis(self.expires)
+    }
+}
+
+/// Time allowed to Directus to answer a request, since the statistics writer and the scheduler
+/// wait for it.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Current login session. Stays empty when using a static token.
+static SESSION: OnceLock<Mutex<Option<Session>>> = OnceLock::new();
+fn session() -> &'static Mutex<Option<Session>> {
+    SESSION.get_or_init(|| Mutex::new(None))
+}
+
+/// Whether the bot authenticates using the email/password login flow rather than the static token.
+pub fn uses_login() -> bool {
+    config().directus_email.is_some() && config().directus_password.is_some()
+}
+
+async fn login() -> Result<Session, Error> {
+    let (Some(email), Some(password)) = (&config().directus_email, &config().directus_password)
+    else {
+        return Err(Error::MissingCredentials);
+    };
+
+    info!("Logging in to Directus as {email}");
+    let response = Client::new()
+        .post(format!("{}/auth/login", config().directus_url))
+        .timeout(REQUEST_TIMEOUT)
+        .header("Content-Type", "application/json")
+        .body(json!({ "email": email, "password": password }).to_string())
+        .send()
+        .await?;
+
+    if response.status() == StatusCode::UNAUTHORIZED {
+        return Err(Error::Unauthorized);
     }
 
+    Ok(serde_json::from_str::<DirectusResponse<Session>>(
+        response.error_for_status()?.text().await?.as_str(),
+    )?
+    .data)
+}
+
+async fn refresh(refresh_token: &str) -> Result<Session, Error> {
+    log::debug!("Refreshing Directus access token");
     let response = Client::new()
-        .get(format!(
-            "{}/items/association_memberships?fields=member.id,member.surname,member.poll_count",
-            config().directus_url
-        ))
-        .bearer_auth(&config().directus_token)
+        .post(format!("{}/auth/refresh", config().directus_url))
+        .timeout(REQUEST_TIMEOUT)
+        .header("Content-Type", "application/json")
+        .body(json!({ "refresh_token": refresh_token, "mode": "json" }).to_string())
         .send()
         .await?
         .error_for_status()?;
 
+    Ok(serde_json::from_str::<DirectusResponse<Session>>(response.text().await?.as_str())?.data)
+}
+
+/// Returns a valid access token, refreshing or logging in again when needed.
+///
+/// When `force` is set, the current session is discarded (e.g. after a 401).
+async fn access_token(force: bool) -> Result<String, Error> {
+    if !uses_login() {
+        return config()
+            .directus_token
+            .clone()
+            .ok_or(Error::MissingCredentials);
+    }
+
+    let mut session = session().lock().await;
+
+    match session.as_ref() {
+        Some(s) if !force && !s.is_expired() => return Ok(s.access_token.clone()),
+        Some(s) => match refresh(&s.refresh_token).await {
+            Ok(new) => *session = Some(new),
+            Err(e) => {
+                warn!("Could not refresh Directus token, logging in again: {e}");
+                *session = Some(login().await?);
+            }
+        },
+        None => *session = Some(login().await?),
+    }
+
+    Ok(session.as_ref().unwrap().access_token.clone())
+}
+
+/// Sends an authenticated request to Directus. On a 401, the credentials are renewed and the
+/// request is retried once.
+async fn send(method: Method, path: String, body: Option<String>) -> Result<Response, Error> {
+    let build = |token: &str| -> RequestBuilder {
+        let request = Client::new()
+            .request(method.clone(), format!("{}{}", config().directus_url, path))
+            .timeout(REQUEST_TIMEOUT)
+            .bearer_auth(token);
+        match &body {
+            Some(body) => request
+                .header("Content-Type", "application/json")
+                .body(body.clone()),
+            None => request,
+        }
+    };
+
+    let response = build(&access_token(false).await?).send().await?;
+    if response.status() != StatusCode::UNAUTHORIZED {
+        return Ok(response.error_for_status()?);
+    }
+
+    if !uses_login() {
+        error!("Directus rejected the static token, it has probably been rotated");
+        return Err(Error::Unauthorized);
+    }
+
+    warn!("Directus returned 401, re-authenticating");
+    let response = build(&access_token(true).await?).send().await?;
+    if response.status() == StatusCode::UNAUTHORIZED {
+        return Err(Error::Unauthorized);
+    }
+    Ok(response.error_for_status()?)
+}
+
+/// Checks the connectivity with Directus and the validity of the credentials.
+/// Returns the email (or id) of the authenticated user.
+pub async fn status() -> Result<String, Error> {
+    #[derive(Deserialize, Debug)]
+    struct Me {
+        id: String,
+        email: Option<String>,
+    }
+
+    let response = send(Method::GET, "/users/me?fields=id,email".into(), None).await?;
+    let me = serde_json::from_str::<DirectusResponse<Me>>(response.text().await?.as_str())?.data;
+
+    Ok(me.email.unwrap_or(me.id))
+}
+
+// ------------------------------- COLLECTIONS --------------------------------
+
+pub async fn get_committee() -> Result<Vec<Committee>, Error> {
+    #[derive(Deserialize, Debug)]
+    struct Member {
+        member: Committee,
+    }
+
+    let response = send(
+        Method::GET,
+        "/items/association_memberships?fields=member.id,member.surname,member.poll_count".into(),
+        None,
+    )
+    .await?;
+
     let response =
         serde_json::from_str::<DirectusResponse<Vec<Member>>>(response.text().await?.as_str())?;
 
     Ok(response.data.into_iter().map(|m| m.member).collect())
 }
 
+/// Fetches the events which haven't ended yet, sorted by start date.
+pub async fn get_upcoming_events() -> Result<Vec<Event>, Error> {
+    let response = send(
+        Method::GET,
+        "/items/events?fields=id,title,description,location,start_date,end_date,registrable&filter[start_date][_gte]=$NOW(-1%20day)&sort=start_date&limit=-1".into(),
+        None,
+    )
+    .await?;
+
+    Ok(serde_json::from_str::<DirectusResponse<Vec<Event>>>(response.text().await?.as_str())?.data)
+}
+
+/// Fetches the merch items of the shop, sorted by name.
+pub async fn get_shop_items() -> Result<Vec<ShopItem>, Error> {
+    let response = send(
+        Method::GET,
+        "/items/shop_items?fields=name,price,stock&sort=name&limit=-1".into(),
+        None,
+    )
+    .await?;
+
+    Ok(
+        serde_json::from_str::<DirectusResponse<Vec<ShopItem>>>(response.text().await?.as_str())?
+            .data,
+    )
+}
+
+/// Searches the pages of the documentation containing the query, in any of their fields.
+pub async fn search_wiki(query: &str, limit: usize) -> Result<Vec<WikiPage>, Error> {
+    let mut url = Url::parse("http://localhost/items/wiki_pages").expect("the url is valid");
+    url.query_pairs_mut()
+        .append_pair("fields", "title,url,summary")
+        .append_pair("search", query)
+        .append_pair("limit", &limit.to_string());
+    let response = send(
+        Method::GET,
+        format!("{}?{}", url.path(), url.query().unwrap_or_default()),
+        None,
+    )
+    .await?;
+
+    Ok(
+        serde_json::from_str::<DirectusResponse<Vec<WikiPage>>>(response.text().await?.as_str())?
+            .data,
+    )
+}
+
+/// Files a support ticket, returns its id.
+pub async fn create_ticket(
+    description: &str,
+    reporter: &str,
+    reporter_id: u64,
+    chat_id: i64,
+) -> Result<i32, Error> {
+    #[derive(Deserialize, Debug)]
+    struct Created {
+        id: i32,
+    }
+
+    let body = json!({
+        "description": description,
+        "reporter_name": reporter,
+        "reporter_telegram_id": reporter_id.to_string(),
+        "chat_id": chat_id.to_string(),
+        "status": "open",
+    });
+    let response = send(
+        Method::POST,
+        "/items/tickets".into(),
+        Some(body.to_string()),
+    )
+    .await?;
+
+    Ok(
+        serde_json::from_str::<DirectusResponse<Created>>(response.text().await?.as_str())?
+            .data
+            .id,
+    )
+}
+
+/// Marks the ticket as closed, and returns it.
+pub async fn close_ticket(id: i32) -> Result<Ticket, Error> {
+    let response = send(
+        Method::PATCH,
+        format!(
+            "/items/tickets/{}?fields=id,description,reporter_telegram_id,chat_id",
+            id
+        ),
+        Some(json!({ "status": "closed" }).to_string()),
+    )
+    .await?;
+
+    Ok(serde_json::from_str::<DirectusResponse<Ticket>>(response.text().await?.as_str())?.data)
+}
+
+/// Fetches a single event.
+pub async fn get_event(id: i32) -> Result<Event, Error> {
+    let response = send(
+        Method::GET,
+        format!(
+            "/items/events/{}?fields=id,title,description,location,start_date,end_date,registrable",
+            id
+        ),
+        None,
+    )
+    .await?;
+
+    Ok(serde_json::from_str::<DirectusResponse<Event>>(response.text().await?.as_str())?.data)
+}
+
+/// Saves the number of members registered to the event.
+pub async fn update_event_registrations(id: i32, count: i64) -> Result<(), Error> {
+    send(
+        Method::PATCH,
+        format!("/items/events/{}", id),
+        Some(json!({ "registrations": count }).to_string()),
+    )
+    .await?;
+    Ok(())
+}
+
 pub async fn update_committee(committee: Vec<Committee>) {
     let mut set = JoinSet::new();
     for c in committee {
-        set.spawn(
-            Client::new()
-                .patch(format!("{}/items/members/{}", config().directus_url, c.id))
-                .bearer_auth(&config().directus_token)
-                .header("Content-Type", "application/json")
-                .body(format!(r#"{{ "poll_count": {} }}"#, c.poll_count))
-                .send(),
-        );
+        set.spawn(send(
+            Method::PATCH,
+            format!("/items/members/{}", c.id),
+            Some(format!(r#"{{ "poll_count": {} }}"#, c.poll_count)),
+        ));
     }
 
     while let Some(r) = set.join_next().await {
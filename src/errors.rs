@@ -0,0 +1,66 @@
+use std::{error::Error, ops::ControlFlow, sync::Arc};
+
+use teloxide::{
+    dispatching::DpHandlerDescription,
+    dptree::{
+        self,
+        di::{DependencyMap, DependencySupplier},
+        Handler, HandlerDescription,
+    },
+    requests::Requester,
+    types::Message,
+    Bot,
+};
+
+use crate::{
+    i18n::{tr, Lang},
+    outbox::Queued,
+    HandlerResult,
+};
+
+/// Logs the error of a handler along with a new reference, and returns the message displayed to
+/// the user, which includes the reference so that the error can be found in the logs.
+pub fn report_error(error: &(dyn Error + Send + Sync), lang: Lang) -> String {
+    let reference = format!("{:08x}", rand::random::<u32>());
+    log::error!(
+        "[{}] Error while handling an update: {:?}",
+        reference,
+        error
+    );
+    tr!(
+        lang,
+        "Une erreur est survenue (réf. {})",
+        "An error occurred (ref. {})",
+        reference
+    )
+}
+
+/// Runs the message routes, and replies to the message when they fail instead of leaving the user
+/// without an answer. The error is logged and not propagated to the dispatcher.
+pub fn reply_on_error(
+    routes: Handler<'static, DependencyMap, HandlerResult, DpHandlerDescription>,
+) -> Handler<'static, DependencyMap, HandlerResult, DpHandlerDescription> {
+    dptree::from_fn_with_description(
+        DpHandlerDescription::entry(),
+        move |deps: DependencyMap, cont| {
+            let routes = routes.clone();
+            async move {
+                let bot: Arc<Bot> = deps.get();
+                let msg: Arc<Message> = deps.get();
+                let lang: Arc<Lang> = deps.get();
+
+                match routes.dispatch(deps).await {
+                    ControlFlow::Break(Err(e)) => {
+                        let text = report_error(e.as_ref(), *lang);
+                        if let Err(e) = bot.send_message(msg.chat.id, text).queued().await {
+                            log::error!("Could not report error to {}: {:?}", msg.chat.id, e);
+                        }
+                        ControlFlow::Break(Ok(()))
+                    }
+                    ControlFlow::Break(Ok(())) => ControlFlow::Break(Ok(())),
+                    ControlFlow::Continue(deps) => cont(deps).await,
+                }
+            }
+        },
+    )
+}
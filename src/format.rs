@@ -0,0 +1,130 @@
+//! Formatting of the messages sent by the bot.
+//!
+//! Messages containing user-provided text (quotes, names, titles, command arguments...) are sent
+//! with the HTML parse mode through [`HtmlMessages`]. Every interpolated value must then go
+//! through [`escape`], or one of the helpers below which escape their content.
+
+use teloxide::{
+    payloads::{EditMessageText, EditMessageTextSetters, SendMessage, SendMessageSetters},
+    requests::{JsonRequest, Requester},
+    types::{MessageId, ParseMode, Recipient},
+    Bot,
+};
+
+/// Escapes the characters interpreted by the HTML parse mode (`&`, `<` and `>`).
+pub fn escape(text: &str) -> String {
+    teloxide::utils::html::escape(text)
+}
+
+pub fn bold(text: &str) -> String {
+    format!("<b>{}</b>", escape(text))
+}
+
+pub fn italic(text: &str) -> String {
+    format!("<i>{}</i>", escape(text))
+}
+
+pub fn code(text: &str) -> String {
+    format!("<code>{}</code>", escape(text))
+}
+
+/// Link with the given text, both the URL and the text are escaped.
+pub fn link(url: &str, text: &str) -> String {
+    teloxide::utils::html::link(url, text)
+}
+
+/// Builder of the HTML messages, so that the replies share the same layout: a bold title, then
+/// lines, bulleted items and labelled fields. The `&str` arguments are escaped, the `html` ones
+/// must already be formatted with the helpers above.
+#[derive(Default)]
+pub struct MessageBuilder {
+    lines: Vec<String>,
+}
+
+impl MessageBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn title(mut self, text: &str) -> Self {
+        self.lines.push(bold(text));
+        self
+    }
+
+    pub fn text(mut self, text: &str) -> Self {
+        self.lines.push(escape(text));
+        self
+    }
+
+    pub fn html(mut self, html: impl Into<String>) -> Self {
+        self.lines.push(html.into());
+        self
+    }
+
+    pub fn item(mut self, html: impl Into<String>) -> Self {
+        self.lines.push(format!("• {}", html.into()));
+        self
+    }
+
+    pub fn items(self, items: impl IntoIterator<Item = String>) -> Self {
+        items.into_iter().fold(self, Self::item)
+    }
+
+    /// `label: value`, with the label in bold.
+    pub fn field(mut self, label: &str, html: impl Into<String>) -> Self {
+        self.lines.push(format!("{}: {}", bold(label), html.into()));
+        self
+    }
+
+    /// Empty line between two sections.
+    pub fn separator(mut self) -> Self {
+        self.lines.push(String::new());
+        self
+    }
+
+    pub fn build(self) -> String {
+        self.lines.join("\n")
+    }
+}
+
+/// Sends and edits messages with the HTML parse mode.
+pub trait HtmlMessages {
+    fn send_html<C, T>(&self, chat_id: C, text: T) -> JsonRequest<SendMessage>
+    where
+        C: Into<Recipient>,
+        T: Into<String>;
+
+    fn edit_html<C, T>(
+        &self,
+        chat_id: C,
+        message_id: MessageId,
+        text: T,
+    ) -> JsonRequest<EditMessageText>
+    where
+        C: Into<Recipient>,
+        T: Into<String>;
+}
+
+impl HtmlMessages for Bot {
+    fn send_html<C, T>(&self, chat_id: C, text: T) -> JsonRequest<SendMessage>
+    where
+        C: Into<Recipient>,
+        T: Into<String>,
+    {
+        self.send_message(chat_id, text).parse_mode(ParseMode::Html)
+    }
+
+    fn edit_html<C, T>(
+        &self,
+        chat_id: C,
+        message_id: MessageId,
+        text: T,
+    ) -> JsonRequest<EditMessageText>
+    where
+        C: Into<Recipient>,
+        T: Into<String>,
+    {
+        self.edit_message_text(chat_id, message_id, text)
+            .parse_mode(ParseMode::Html)
+    }
+}
@@ -0,0 +1,124 @@
+//! Serving of the HTTP endpoints (metrics, dashboard, API). With `HTTP_ALLOWED_IPS`, only the
+//! given addresses or ranges can reach them, the other requests are rejected and logged.
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::OnceLock,
+};
+
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::StatusCode,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+
+use crate::config::config;
+
+/// Address or range in the CIDR notation, e.g. `10.0.0.0/8` or `::1`.
+struct IpRange {
+    address: IpAddr,
+    prefix: u32,
+}
+
+impl IpRange {
+    fn parse(range: &str) -> Option<Self> {
+        let (address, prefix) = match range.split_once('/') {
+            Some((address, prefix)) => {
+                (address.parse::<IpAddr>().ok()?, Some(prefix.parse().ok()?))
+            }
+            None => (range.parse::<IpAddr>().ok()?, None),
+        };
+        let bits = if address.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(bits);
+        (prefix <= bits).then_some(Self { address, prefix })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        // The IPv4 clients of a dual-stack socket appear as IPv4-mapped IPv6 addresses
+        match (self.address, ip.to_canonical()) {
+            (IpAddr::V4(range), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix).unwrap_or(0);
+                u32::from(range) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(range), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix).unwrap_or(0);
+                u128::from(range) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Ranges of the comma-separated list which cannot be parsed.
+pub fn invalid_ranges(ranges: &str) -> Vec<&str> {
+    ranges
+        .split(',')
+        .map(str::trim)
+        .filter(|r| !r.is_empty() && IpRange::parse(r).is_none())
+        .collect()
+}
+
+/// Ranges of `HTTP_ALLOWED_IPS`, `None` if every address is allowed.
+fn allowed_ranges() -> Option<&'static [IpRange]> {
+    static RANGES: OnceLock<Option<Vec<IpRange>>> = OnceLock::new();
+    RANGES
+        .get_or_init(|| {
+            let ranges = config().http_allowed_ips.as_ref()?;
+            Some(
+                ranges
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|r| !r.is_empty())
+                    .filter_map(|r| {
+                        let range = IpRange::parse(r);
+                        if range.is_none() {
+                            log::error!("Ignoring invalid range \"{}\" of HTTP_ALLOWED_IPS", r);
+                        }
+                        range
+                    })
+                    .collect(),
+            )
+        })
+        .as_deref()
+}
+
+async fn check_ip(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Some(ranges) = allowed_ranges() {
+        if !ranges.iter().any(|r| r.contains(peer.ip())) {
+            log::warn!(
+                "Rejected {} {} from {}, not in HTTP_ALLOWED_IPS",
+                request.method(),
+                request.uri().path(),
+                peer.ip()
+            );
+            return StatusCode::FORBIDDEN.into_response();
+        }
+    }
+    next.run(request).await
+}
+
+/// Serves the app at the given address until it stops, logging the errors. `name` identifies the
+/// server in the logs.
+pub async fn serve(name: &str, address: &str, app: Router) {
+    let app = app.layer(middleware::from_fn(check_ip));
+    match tokio::net::TcpListener::bind(address).await {
+        Ok(listener) => {
+            log::info!("Serving {} on {}", name, address);
+            if let Err(e) = axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+            {
+                log::error!("The {} server stopped: {:?}", name, e);
+            }
+        }
+        Err(e) => log::error!("Could not listen on {}: {:?}", address, e),
+    }
+}
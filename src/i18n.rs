@@ -0,0 +1,102 @@
+//! Translation of the replies of the bot.
+//!
+//! Each chat chooses its language with `/language`. The language of the chat of the update is
+//! injected in the handlers as a [`Lang`] dependency, and the replies are written in both
+//! languages with [`tr!`]. The commands reserved to the admins are only available in French.
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Datelike, Weekday};
+use chrono_tz::Tz;
+use sqlx::SqlitePool;
+use teloxide::types::{ChatId, Update};
+
+use crate::dates::format_datetime;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Lang {
+    #[default]
+    Fr,
+    En,
+}
+
+impl Lang {
+    pub fn code(self) -> &'static str {
+        match self {
+            Lang::Fr => "fr",
+            Lang::En => "en",
+        }
+    }
+
+    pub fn parse(code: &str) -> Option<Self> {
+        match code.trim().to_lowercase().as_str() {
+            "fr" | "français" | "francais" | "french" => Some(Lang::Fr),
+            "en" | "english" | "anglais" => Some(Lang::En),
+            _ => None,
+        }
+    }
+}
+
+/// Formats the text in the given language. Takes the French then the English format string,
+/// followed by the arguments shared by both.
+macro_rules! tr {
+    ($lang:expr, $fr:literal, $en:literal $(, $args:expr)* $(,)?) => {
+        match $lang {
+            $crate::i18n::Lang::Fr => format!($fr $(, $args)*),
+            $crate::i18n::Lang::En => format!($en $(, $args)*),
+        }
+    };
+}
+pub(crate) use tr;
+
+/// Language of the chat, French if it was never set.
+pub async fn chat_language(db: &SqlitePool, chat_id: ChatId) -> Lang {
+    let chat_id = chat_id.to_string();
+    match sqlx::query!("SELECT language FROM chats WHERE chat_id = $1", chat_id)
+        .fetch_optional(db)
+        .await
+    {
+        Ok(row) => row
+            .and_then(|r| Lang::parse(&r.language))
+            .unwrap_or_default(),
+        Err(e) => {
+            log::error!("Could not fetch the language of {}: {:?}", chat_id, e);
+            Lang::default()
+        }
+    }
+}
+
+/// Language of the chat of the update, injected as a dependency of the handlers.
+pub async fn update_language(update: Update, db: Arc<SqlitePool>) -> Lang {
+    match update.chat() {
+        Some(chat) => chat_language(db.as_ref(), chat.id).await,
+        None => Lang::default(),
+    }
+}
+
+/// Date interpreted from an expression of the user, echoed back with its weekday and timezone so
+/// that a misunderstanding is noticed, e.g. "lundi 20/10/2026 18:00 (Europe/Zurich)".
+pub fn format_interpreted(date: &DateTime<Tz>, lang: Lang) -> String {
+    let weekday = match (lang, date.weekday()) {
+        (Lang::Fr, Weekday::Mon) => "lundi",
+        (Lang::Fr, Weekday::Tue) => "mardi",
+        (Lang::Fr, Weekday::Wed) => "mercredi",
+        (Lang::Fr, Weekday::Thu) => "jeudi",
+        (Lang::Fr, Weekday::Fri) => "vendredi",
+        (Lang::Fr, Weekday::Sat) => "samedi",
+        (Lang::Fr, Weekday::Sun) => "dimanche",
+        (Lang::En, Weekday::Mon) => "Monday",
+        (Lang::En, Weekday::Tue) => "Tuesday",
+        (Lang::En, Weekday::Wed) => "Wednesday",
+        (Lang::En, Weekday::Thu) => "Thursday",
+        (Lang::En, Weekday::Fri) => "Friday",
+        (Lang::En, Weekday::Sat) => "Saturday",
+        (Lang::En, Weekday::Sun) => "Sunday",
+    };
+    format!(
+        "{} {} ({})",
+        weekday,
+        format_datetime(date),
+        date.timezone().name()
+    )
+}
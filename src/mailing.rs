@@ -0,0 +1,85 @@
+//! Synchronisation of the requests of `/subscribe` and `/unsubscribe` with the backend of the
+//! mailing lists.
+
+use std::time::Duration;
+
+use reqwest::Client;
+use serde_json::json;
+use sqlx::SqlitePool;
+
+use crate::{config::config, dates::now};
+
+/// Maximum number of requests sent to the backend per run.
+const SYNC_BATCH: i64 = 20;
+/// Time allowed to the backend to answer a request, since the other jobs of the scheduler wait for
+/// the sync.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Mailing lists the members can subscribe to, from `MAILING_LISTS`.
+pub fn mailing_lists() -> Vec<String> {
+    config()
+        .mailing_lists
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(|l| l.trim().to_lowercase())
+        .filter(|l| !l.is_empty())
+        .collect()
+}
+
+/// Sends the pending requests to the backend, in order. Stops at the first failure, so that a
+/// subscription and its later unsubscription cannot be applied in the wrong order.
+///
+/// Without `MAILING_API_URL`, the requests stay in the database for an external sync job.
+pub async fn sync_mailing_requests(db: &SqlitePool) -> Result<(), sqlx::Error> {
+    let Some(url) = &config().mailing_api_url else {
+        return Ok(());
+    };
+
+    let requests = sqlx::query!(
+        r#"SELECT id AS "id!", email, list, subscribe AS "subscribe: bool" FROM mailing_requests
+        WHERE synced_at IS NULL ORDER BY id LIMIT $1"#,
+        SYNC_BATCH
+    )
+    .fetch_all(db)
+    .await?;
+
+    for request in requests {
+        let action = if request.subscribe {
+            "subscribe"
+        } else {
+            "unsubscribe"
+        };
+        let mut post = Client::new()
+            .post(url)
+            .header("Content-Type", "application/json")
+            .timeout(REQUEST_TIMEOUT)
+            .body(
+                json!({ "list": request.list, "email": request.email, "action": action })
+                    .to_string(),
+            );
+        if let Some(token) = &config().mailing_api_token {
+            post = post.bearer_auth(token);
+        }
+        if let Err(e) = post
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(reqwest::Error::without_url)
+        {
+            log::warn!("Could not sync mailing request #{}: {:?}", request.id, e);
+            break;
+        }
+
+        let timestamp = now().timestamp();
+        sqlx::query!(
+            "UPDATE mailing_requests SET synced_at = $1 WHERE id = $2",
+            timestamp,
+            request.id
+        )
+        .execute(db)
+        .await?;
+    }
+
+    Ok(())
+}
@@ -1,39 +1,150 @@
 use std::sync::Arc;
 
-use config::config;
-use sqlx::{migrate::MigrateDatabase, SqlitePool};
+use sqlx::SqlitePool;
 use teloxide::{
-    dispatching::dialogue::{self, InMemStorage},
+    dispatching::dialogue,
     prelude::*,
     utils::command::BotCommands,
 };
 
 use crate::{
-    commands::{command_callback_query_handler, command_message_handler, Command},
+    admin_token::load_admin_token,
+    api::serve_api,
+    channels::channel_post,
+    chats::{register_chat, register_member, track_membership},
+    commands::{
+        command_callback_query_handler, command_edited_message_handler, command_message_handler,
+        Command,
+    },
+    concurrency::{acquire_slot, distribution_key},
+    crypto::load_local_secret,
+    dashboard::serve_dashboard,
+    dates::update_timezone,
+    dialogues::{resume_dialogues, DialogueStorage},
+    metrics::{instrument, serve_metrics},
     directus::{update_committee, Committee},
-    cmd_poll::PollState
+    errors::reply_on_error,
+    i18n::update_language,
+    cmd_halloffame::record_poll_answer,
+    cmd_reactionstats::record_message_author,
+    cmd_inline::{completion_request, inline_completions, inline_quotes},
+    cmd_poll::PollState,
+    cmd_vote::start_reaction_votes,
+    poll_results::archive_poll_results,
+    reactions::{allow_reaction_updates, ReactionListener},
+    state::AppState,
+    webhook::start_webhook,
 };
 
+mod admin_token;
+mod aliases;
+mod anomalies;
+mod api;
+mod approvals;
+mod audit;
+mod callbacks;
+mod channels;
+mod chats;
 mod commands;
+mod committee;
+mod concurrency;
 mod config;
+mod crypto;
+mod dashboard;
+mod dialogues;
 mod directus;
+mod errors;
+mod format;
+mod http;
+mod i18n;
+mod mailing;
+mod maintenance;
+mod ics;
+mod menus;
+mod metrics;
+mod names;
+mod outbox;
+mod reactions;
+mod rooms;
+mod satellite;
+mod shop;
+mod permissions;
+mod poll_options;
+mod poll_results;
+mod quote_elections;
+mod retention;
 mod cmd_poll;
+mod cmd_admin_invite;
+mod cmd_broadcast;
 mod cmd_bureau;
+mod cmd_calendar;
+mod cmd_chats;
+mod cmd_checkin;
+mod cmd_closepoll;
+mod cmd_countdown;
+mod cmd_aliases;
+mod cmd_audit_export;
+mod cmd_anon;
 mod cmd_authentication;
+mod cmd_backup;
+mod cmd_debt;
+mod cmd_directus;
+mod cmd_doodle;
+mod cmd_expense;
+mod cmd_export;
+mod cmd_halloffame;
+mod cmd_help;
+mod cmd_inline;
+mod cmd_karma;
+mod cmd_language;
+mod cmd_timezone;
+mod cmd_link;
+mod cmd_loan;
+mod cmd_mailing;
+mod cmd_menu;
+mod cmd_newpoll;
+mod cmd_newsletter;
+mod cmd_pin;
+mod cmd_random;
+mod cmd_reactionstats;
+mod cmd_registrations;
+mod cmd_reminders;
+mod cmd_retention;
+mod cmd_room;
+mod cmd_satellite;
+mod cmd_report;
+mod cmd_schedules;
+mod cmd_semester;
+mod cmd_settings;
+mod cmd_shame;
+mod cmd_shop;
+mod cmd_ticket;
+mod cmd_todo;
+mod cmd_tournament;
+mod cmd_templates;
+mod cmd_transport;
+mod cmd_undo;
+mod cmd_vote;
+mod cmd_whowrote;
+mod cmd_wiki;
+mod dates;
+mod db;
+mod scheduler;
+mod startup;
+mod state;
+mod stats;
+mod templates;
+mod throttle;
+mod transport;
+mod usage;
+mod verification;
+mod webhook;
+mod wizard;
 
 pub type HandlerResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
 
 async fn init_db() -> SqlitePool {
-    let database_url = config()
-        .database_url
-        .clone()
-        .unwrap_or_else(|| format!("sqlite://{}/db.sqlite", config::config().data_dir));
-
-    if !sqlx::Sqlite::database_exists(&database_url).await.unwrap() {
-        sqlx::Sqlite::create_database(&database_url).await.unwrap();
-    }
-
-    let database = SqlitePool::connect(&database_url).await.unwrap();
+    let database = db::connect(&db::database_url()).await.unwrap();
     sqlx::migrate!().run(&database).await.unwrap();
 
     database
@@ -43,40 +154,135 @@ async fn init_db() -> SqlitePool {
 async fn main() {
     pretty_env_logger::init();
 
+    log::info!("Loading config files");
+    startup::validate_config().await;
+
     update_committee(vec![Committee {
         id: 1,
         name: "".into(),
         poll_count: 15,
     }]).await;
 
-    log::info!("Loading config files");
-    config::config();
-    let database = init_db().await;
+    load_local_secret().expect("Could not load the secret of the installation");
+    let database = Arc::new(init_db().await);
+    if let Err(e) = load_admin_token(database.as_ref()).await {
+        log::error!("Could not load the rotated admin token: {:?}", e);
+    }
 
     let bot = Bot::new(config::config().bot_token.clone());
+    log::info!("Checking the services");
+    startup::self_check(&bot, database.as_ref()).await;
     bot.set_my_commands(Command::bot_commands()).await.unwrap();
 
+    stats::start_stats_writer(database.clone());
+    let state = AppState::new(database.clone());
+    log::info!("Starting scheduler");
+    scheduler::start(bot.clone(), state.clone());
+    maintenance::start_maintenance(database.clone());
+
+    log::info!("Fetching committee");
+    if let Err(e) = state.committee.refresh().await {
+        log::error!("Could not fetch committee: {:?}", e);
+    }
+
+    log::info!("Resuming interrupted dialogues");
+    if let Err(e) = resume_dialogues(&bot, database.as_ref()).await {
+        log::error!("Could not resume dialogues: {:?}", e);
+    }
+
+    if let Some(address) = &state.config.metrics_address {
+        tokio::spawn(serve_metrics(address.clone(), state.metrics.clone()));
+    }
+    if let Some(address) = &state.config.dashboard_address {
+        tokio::spawn(serve_dashboard(address.clone(), state.clone()));
+    }
+    match (&state.config.api_address, &state.config.api_token) {
+        (Some(address), Some(_)) => {
+            tokio::spawn(serve_api(address.clone(), state.clone()));
+        }
+        (Some(_), None) => log::error!("API_ADDRESS is set without API_TOKEN, the API is disabled"),
+        _ => {}
+    }
+
     log::info!("Initializing dispatchers");
-    let message_handler = Update::filter_message().chain(command_message_handler());
+    let message_handler = Update::filter_message()
+        .inspect_async(register_chat)
+        .inspect_async(register_member)
+        .inspect_async(record_message_author)
+        .chain(reply_on_error(command_message_handler()));
+    let edited_message_handler =
+        Update::filter_edited_message().chain(reply_on_error(command_edited_message_handler()));
     let callback_handler = Update::filter_callback_query().chain(command_callback_query_handler());
+    // Inline queries are not bound to a chat, hence handled outside of the dialogues
+    let inline_handler = Update::filter_inline_query()
+        .chain(instrument(|_: &InlineQuery| "inline".to_owned()))
+        .branch(dptree::filter_map(completion_request).endpoint(inline_completions))
+        .endpoint(inline_quotes);
+    let poll_answer_handler = Update::filter_poll_answer()
+        .chain(instrument(|_: &PollAnswer| "poll_answer".to_owned()))
+        .endpoint(record_poll_answer);
+    let channel_post_handler = Update::filter_channel_post()
+        .inspect_async(register_chat)
+        .endpoint(channel_post);
+    let poll_handler = Update::filter_poll()
+        .chain(instrument(|_: &Poll| "poll".to_owned()))
+        .endpoint(archive_poll_results);
+    let membership_handler = Update::filter_my_chat_member()
+        .chain(instrument(|_: &ChatMemberUpdated| "membership".to_owned()))
+        .endpoint(track_membership);
+
+    let (reactions, reaction_votes) = tokio::sync::mpsc::unbounded_channel();
+    start_reaction_votes(bot.clone(), database.clone(), reaction_votes);
 
     let mut bot_dispatcher = Dispatcher::builder(
-        bot,
-        dialogue::enter::<Update, InMemStorage<PollState>, PollState, _>()
-            .branch(message_handler)
-            .branch(callback_handler),
+        bot.clone(),
+        dptree::entry()
+            .map_async(acquire_slot)
+            .branch(inline_handler)
+            .branch(poll_answer_handler)
+            .branch(poll_handler)
+            .branch(membership_handler)
+            .branch(channel_post_handler)
+            .branch(
+            dialogue::enter::<Update, DialogueStorage, PollState, _>()
+                .map_async(update_language)
+                .map_async(update_timezone)
+                .branch(message_handler)
+                .branch(edited_message_handler)
+                .branch(callback_handler),
+        ),
     )
     .default_handler(|_| async move {})
     .error_handler(LoggingErrorHandler::with_custom_text(
         "An error has occurred in the dispatcher",
     ))
-    .dependencies(dptree::deps![
-        InMemStorage::<PollState>::new(),
-        Arc::new(database)
-    ])
+    .dependencies(state.dependencies())
+    .distribution_function(distribution_key)
+    .worker_queue_size(state.config.dispatcher_queue_size)
     .enable_ctrlc_handler()
     .build();
 
     log::info!("Starting command bot");
-    bot_dispatcher.dispatch().await;
+    let listener = match (&state.config.webhook_url, &state.config.webhook_address) {
+        (Some(url), Some(address)) => {
+            let webhook = start_webhook(&bot, url, address.clone())
+                .await
+                .expect("Could not set the webhook");
+            ReactionListener::with_webhook(webhook, reactions)
+        }
+        _ => {
+            let listener = ReactionListener::new(bot.clone(), reactions).await;
+            if let Err(e) = allow_reaction_updates(&bot).await {
+                log::error!("Could not subscribe to the reactions: {:?}", e);
+            }
+            listener
+        }
+    };
+    bot_dispatcher
+        .dispatch_with_listener(
+            listener,
+            LoggingErrorHandler::with_custom_text("An error from the update listener"),
+        )
+        .await;
+    stats::stop_stats_writer().await;
 }
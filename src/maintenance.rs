@@ -0,0 +1,111 @@
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+
+use crate::{
+    cmd_reactionstats::MESSAGE_RETENTION_DAYS,
+    cmd_schedules::parse_cron,
+    config::config,
+    dates::now,
+    db::{quotes::QuoteRepo, retry_busy},
+    retention::{retention_days, Policy},
+};
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// Oldest timestamp kept by the policy, `None` if its data is kept forever.
+async fn limit(
+    db: &SqlitePool,
+    policy: Policy,
+    timestamp: i64,
+) -> Result<Option<i64>, sqlx::Error> {
+    Ok(retention_days(db, policy)
+        .await?
+        .map(|days| timestamp - days * DAY))
+}
+
+/// Removes the rows outdated according to the retention durations (see `/retention`), then
+/// compacts the database and refreshes the statistics of the query planner.
+async fn run_maintenance(db: &SqlitePool) -> Result<(), sqlx::Error> {
+    let timestamp = now().timestamp();
+
+    let mut audit = 0;
+    if let Some(limit) = limit(db, Policy::AuditLog, timestamp).await? {
+        audit = sqlx::query!("DELETE FROM audit_log WHERE created_at < $1", limit)
+            .execute(db)
+            .await?
+            .rows_affected();
+    }
+    let mut commands = 0;
+    if let Some(limit) = limit(db, Policy::CommandLog, timestamp).await? {
+        commands = sqlx::query!("DELETE FROM command_log WHERE created_at < $1", limit)
+            .execute(db)
+            .await?
+            .rows_affected();
+    }
+    let mut answers = 0;
+    if let Some(limit) = limit(db, Policy::PollAnswers, timestamp).await? {
+        answers = sqlx::query!("DELETE FROM poll_answers WHERE answered_at < $1", limit)
+            .execute(db)
+            .await?
+            .rows_affected();
+    }
+    let mut quotes = 0;
+    if let Some(limit) = limit(db, Policy::Quotes, timestamp).await? {
+        quotes = db.delete_quotes_before(limit).await?;
+    }
+
+    // Dialogues abandoned halfway through, which would otherwise stay in the table forever
+    let dialogue_limit = timestamp - config().dialogue_retention_days * DAY;
+    let dialogues = sqlx::query!(
+        "DELETE FROM dialogues WHERE updated_at < $1",
+        dialogue_limit
+    )
+    .execute(db)
+    .await?
+    .rows_affected();
+
+    let message_limit = timestamp - MESSAGE_RETENTION_DAYS * DAY;
+    sqlx::query!(
+        "DELETE FROM message_authors WHERE sent_at < $1",
+        message_limit
+    )
+    .execute(db)
+    .await?;
+
+    // VACUUM needs the database to itself, and fails if another connection is writing
+    retry_busy(|| sqlx::query("VACUUM").execute(db)).await?;
+    sqlx::query("ANALYZE").execute(db).await?;
+
+    log::info!(
+        "Database maintenance done, pruned {} audit entries, {} commands, {} poll answers, {} quotes and {} dialogues",
+        audit,
+        commands,
+        answers,
+        quotes,
+        dialogues
+    );
+    Ok(())
+}
+
+/// Spawns the background task running the database maintenance, following `MAINTENANCE_CRON` (in
+/// the default timezone).
+pub fn start_maintenance(db: Arc<SqlitePool>) {
+    let Some((_, schedule)) = parse_cron(&config().maintenance_cron) else {
+        log::error!(
+            "Invalid MAINTENANCE_CRON \"{}\", the database maintenance is disabled",
+            config().maintenance_cron
+        );
+        return;
+    };
+
+    tokio::spawn(async move {
+        while let Some(next) = schedule.after(&now()).next() {
+            tokio::time::sleep((next - now()).to_std().unwrap_or_default()).await;
+
+            if let Err(e) = run_maintenance(db.as_ref()).await {
+                log::error!("Could not run the database maintenance: {:?}", e);
+            }
+        }
+    });
+}
@@ -0,0 +1,63 @@
+use std::{collections::HashMap, sync::OnceLock, time::Duration};
+
+use chrono::NaiveDate;
+use reqwest::Client;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use crate::{config::config, directus::Error};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Price {
+    /// Category of customer (e.g. "E" for students, "D" for PhD students, "V" for visitors).
+    pub category: String,
+    pub price: f64,
+}
+
+/// A menu served in one of the EPFL restaurants.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Menu {
+    pub restaurant: String,
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub prices: Vec<Price>,
+}
+
+/// Time allowed to the menu API to answer.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Menus already fetched, by day. Menus don't change during the day, so there is no need to query
+/// the API more than once per day.
+static CACHE: OnceLock<Mutex<HashMap<NaiveDate, Vec<Menu>>>> = OnceLock::new();
+
+/// Fetches the menus of all the restaurants for the given day, using the cache when possible.
+///
+/// Returns `Ok(None)` when no menu API is configured.
+pub async fn get_menus(day: NaiveDate) -> Result<Option<Vec<Menu>>, Error> {
+    let Some(url) = &config().menu_api_url else {
+        return Ok(None);
+    };
+
+    // Not locked during the request, so that a slow API does not block the cached days
+    if let Some(menus) = CACHE.get_or_init(Default::default).lock().await.get(&day) {
+        return Ok(Some(menus.clone()));
+    }
+
+    log::debug!("Fetching menus of {}", day);
+    let response = Client::new()
+        .get(url)
+        .query(&[("date", day.format("%Y-%m-%d").to_string())])
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?;
+    let menus = serde_json::from_str::<Vec<Menu>>(response.text().await?.as_str())?;
+
+    // Only keep the recent days
+    let mut cache = CACHE.get_or_init(Default::default).lock().await;
+    cache.retain(|d, _| (day - *d).num_days().abs() < 7);
+    cache.insert(day, menus.clone());
+
+    Ok(Some(menus))
+}
@@ -0,0 +1,261 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, VecDeque},
+    fmt::Write,
+    future::Future,
+    ops::ControlFlow,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{extract::State, routing::get, Router};
+use chrono_tz::Tz;
+use teloxide::{
+    dispatching::DpHandlerDescription,
+    dptree::{
+        self,
+        di::{DependencyMap, DependencySupplier},
+        Handler, HandlerDescription,
+    },
+    requests::Requester,
+    types::Message,
+    Bot,
+};
+
+use crate::{
+    config::config,
+    dates::{format_datetime, from_timestamp, now},
+    format::{bold, escape, HtmlMessages, MessageBuilder},
+    http::serve,
+    outbox::Queued,
+    HandlerResult,
+};
+
+/// Upper bounds (in seconds) of the buckets of the latency histograms.
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+/// Handlers running longer than this are logged, unless the diagnostics set another threshold.
+const SLOW_HANDLER: Duration = Duration::from_secs(1);
+/// Slow operations kept for `/slowlog`, the most recent ones.
+const SLOW_LOG_SIZE: usize = 200;
+/// Operations listed by `/slowlog`, the slowest ones.
+const SLOW_LOG_SHOWN: usize = 15;
+
+/// Threshold above which the handlers, the scheduled jobs and the database queries are logged
+/// and kept for `/slowlog`, when the diagnostics are enabled by `DIAGNOSTICS_THRESHOLD_MS`.
+pub fn diagnostics_threshold() -> Option<Duration> {
+    config().diagnostics_threshold_ms.map(Duration::from_millis)
+}
+
+struct SlowOperation {
+    name: String,
+    duration: Duration,
+    /// Unix timestamp (seconds) of the end of the operation.
+    at: i64,
+}
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+}
+
+/// Execution time and outcome of the handlers, exported in the Prometheus text format.
+#[derive(Default)]
+pub struct Metrics {
+    /// Histograms by handler and outcome (`ok` or `error`).
+    handlers: Mutex<BTreeMap<(String, &'static str), Histogram>>,
+    /// Recent operations slower than the diagnostics threshold.
+    slow: Mutex<VecDeque<SlowOperation>>,
+}
+
+impl Metrics {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    fn observe(&self, handler: String, outcome: &'static str, duration: Duration) {
+        self.handlers
+            .lock()
+            .unwrap()
+            .entry((handler, outcome))
+            .or_default()
+            .observe(duration.as_secs_f64());
+    }
+
+    /// Logs the operation if it is slow, and keeps it for `/slowlog` when the diagnostics are
+    /// enabled.
+    fn check_duration(&self, name: &str, duration: Duration) {
+        let Some(threshold) = diagnostics_threshold() else {
+            if duration >= SLOW_HANDLER {
+                log::warn!("Slow operation {}: {:?}", name, duration);
+            }
+            return;
+        };
+        if duration < threshold {
+            return;
+        }
+
+        log::warn!("Slow operation {}: {:?}", name, duration);
+        let mut slow = self.slow.lock().unwrap();
+        if slow.len() == SLOW_LOG_SIZE {
+            slow.pop_front();
+        }
+        slow.push_back(SlowOperation {
+            name: name.to_owned(),
+            duration,
+            at: now().timestamp(),
+        });
+    }
+
+    /// Runs the operation (e.g. a scheduled job), checking its duration.
+    pub async fn timed<F: Future>(&self, name: &str, operation: F) -> F::Output {
+        let start = Instant::now();
+        let output = operation.await;
+        self.check_duration(name, start.elapsed());
+        output
+    }
+
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(
+            text,
+            "# HELP roboclic_handler_duration_seconds Execution time of the handlers.\n# TYPE roboclic_handler_duration_seconds histogram"
+        );
+        for ((handler, outcome), histogram) in self.handlers.lock().unwrap().iter() {
+            let labels = format!("handler=\"{}\",outcome=\"{}\"", handler, outcome);
+            for (count, bound) in histogram.buckets.iter().zip(BUCKETS) {
+                let _ = writeln!(
+                    text,
+                    "roboclic_handler_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, count
+                );
+            }
+            let _ = writeln!(
+                text,
+                "roboclic_handler_duration_seconds_bucket{{{},le=\"+Inf\"}} {}\nroboclic_handler_duration_seconds_sum{{{}}} {}\nroboclic_handler_duration_seconds_count{{{}}} {}",
+                labels, histogram.count, labels, histogram.sum, labels, histogram.count
+            );
+        }
+        text
+    }
+}
+
+/// Measures the execution time and outcome of the handlers chained after it, under the name
+/// computed from the dependency `D` (e.g. the command). Updates which are not handled are not
+/// measured.
+///
+/// Required dependencies: `Arc<Metrics>`, `D`
+pub fn instrument<D, T, E>(
+    name: fn(&D) -> String,
+) -> Handler<'static, DependencyMap, Result<T, E>, DpHandlerDescription>
+where
+    D: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    dptree::from_fn_with_description(
+        DpHandlerDescription::entry(),
+        move |deps: DependencyMap, cont| async move {
+            let metrics: Arc<Arc<Metrics>> = deps.get();
+            let dependency: Arc<D> = deps.get();
+            let handler = name(&dependency);
+
+            let start = Instant::now();
+            let result = cont(deps).await;
+            let duration = start.elapsed();
+
+            let outcome = match &result {
+                ControlFlow::Break(Ok(_)) => "ok",
+                ControlFlow::Break(Err(_)) => "error",
+                ControlFlow::Continue(_) => return result,
+            };
+            metrics.check_duration(&format!("{} ({})", handler, outcome), duration);
+            metrics.observe(handler, outcome, duration);
+
+            result
+        },
+    )
+}
+
+/// `/slowlog` lists the slowest recent operations, when the diagnostics are enabled.
+pub async fn slow_log(
+    bot: Bot,
+    msg: Message,
+    metrics: Arc<Metrics>,
+    timezone: Tz,
+) -> HandlerResult {
+    let Some(threshold) = diagnostics_threshold() else {
+        bot.send_message(
+            msg.chat.id,
+            "Les diagnostics sont désactivés, définis DIAGNOSTICS_THRESHOLD_MS pour les activer",
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    };
+
+    let lines = {
+        let slow = metrics.slow.lock().unwrap();
+        let mut operations = slow.iter().collect::<Vec<_>>();
+        operations.sort_by_key(|o| Reverse(o.duration));
+        operations
+            .into_iter()
+            .take(SLOW_LOG_SHOWN)
+            .map(|o| {
+                let date = from_timestamp(o.at, timezone)
+                    .map(|d| format_datetime(&d))
+                    .unwrap_or_default();
+                format!(
+                    "{}: {} ms, {}",
+                    escape(&o.name),
+                    bold(&o.duration.as_millis().to_string()),
+                    date
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let text = if lines.is_empty() {
+        format!(
+            "Aucune opération lente depuis le démarrage (seuil: {} ms, défini par DIAGNOSTICS_THRESHOLD_MS)",
+            threshold.as_millis()
+        )
+    } else {
+        MessageBuilder::new()
+            .title(&format!(
+                "Opérations les plus lentes (plus de {} ms)",
+                threshold.as_millis()
+            ))
+            .items(lines)
+            .build()
+    };
+    bot.send_html(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
+
+/// Serves the metrics on `/metrics` at the given address.
+pub async fn serve_metrics(address: String, metrics: Arc<Metrics>) {
+    let app = Router::new()
+        .route(
+            "/metrics",
+            get(|State(metrics): State<Arc<Metrics>>| async move { metrics.render() }),
+        )
+        .with_state(metrics);
+
+    serve("metrics", &address, app).await;
+}
@@ -0,0 +1,89 @@
+//! Matching of the names typed in the commands (admins, committee members, authors of quotes),
+//! ignoring the case and the accents, and suggesting the closest names on typos.
+
+use strsim::normalized_levenshtein;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::format::bold;
+
+/// Minimum similarity (between 0 and 1) for a name to be suggested.
+const MIN_SIMILARITY: f64 = 0.7;
+/// Maximum number of suggested names.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Lowercases the name, strips its accents and punctuation, so that "Théo D." matches "theo d".
+pub fn fold(name: &str) -> String {
+    name.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub enum NameMatch<'a> {
+    Found(&'a str),
+    /// No name matches exactly, the closest ones (possibly none) are suggested.
+    Suggestions(Vec<&'a str>),
+}
+
+/// Similarity of the typed name with a name, also compared with each of its words so that a first
+/// name matches the full name.
+fn similarity(query: &str, name: &str) -> f64 {
+    if name.starts_with(query) || name.split(' ').any(|word| word == query) {
+        return 1.0;
+    }
+    name.split(' ')
+        .map(|word| normalized_levenshtein(query, word))
+        .fold(normalized_levenshtein(query, name), f64::max)
+}
+
+/// Finds the name typed by the user among the given ones. A name only matches when it is the same
+/// once folded, and is unique: a guess is always confirmed by the user instead.
+pub fn match_name<'a>(query: &str, names: impl IntoIterator<Item = &'a str>) -> NameMatch<'a> {
+    let query = fold(query);
+    let mut names = names.into_iter().collect::<Vec<_>>();
+    names.sort_unstable();
+    names.dedup();
+
+    let exact = names
+        .iter()
+        .filter(|n| fold(n) == query)
+        .copied()
+        .collect::<Vec<_>>();
+    if let [name] = exact[..] {
+        return NameMatch::Found(name);
+    }
+    if !exact.is_empty() || query.is_empty() {
+        return NameMatch::Suggestions(exact);
+    }
+
+    let mut scored = names
+        .into_iter()
+        .map(|n| (similarity(&query, &fold(n)), n))
+        .filter(|(score, _)| *score >= MIN_SIMILARITY)
+        .collect::<Vec<_>>();
+    scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+    NameMatch::Suggestions(
+        scored
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, n)| n)
+            .collect(),
+    )
+}
+
+/// Line suggesting the names, to append to the message telling that the name was not found.
+pub fn did_you_mean(suggestions: &[&str]) -> String {
+    match suggestions {
+        [] => String::new(),
+        [name] => format!("\nTu voulais dire: {} ?", bold(name)),
+        [names @ .., last] => format!(
+            "\nTu voulais dire: {} ou {} ?",
+            names.iter().map(|n| bold(n)).collect::<Vec<_>>().join(", "),
+            bold(last)
+        ),
+    }
+}
@@ -0,0 +1,247 @@
+//! Rate-limited queue of the outgoing messages. The messages sent in bulk (scheduled jobs,
+//! reminders, broadcasts...) are queued with [`Priority::Bulk`], and the replies to the users go
+//! through [`Queued::queued`] with [`Priority::Interactive`], so that the limits of Telegram are
+//! enforced over all of them.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
+};
+
+use teloxide::{
+    payloads::{
+        EditMessageReplyMarkup, EditMessageText, SendDocument, SendMessage, SendPhoto, SendPoll,
+        StopPoll,
+    },
+    requests::{HasPayload, Output, Request},
+    types::{ChatId, Recipient},
+    RequestError,
+};
+use tokio::time::{sleep_until, Instant};
+
+/// Messages Telegram accepts per second, over all the chats.
+const GLOBAL_LIMIT: usize = 30;
+/// Share of the global limit usable by the bulk jobs, the rest is kept for the interactive
+/// replies.
+const BULK_LIMIT: usize = 20;
+/// Messages Telegram accepts per minute in a group.
+const GROUP_LIMIT: usize = 20;
+/// Messages Telegram accepts per second in a private chat.
+const PRIVATE_LIMIT: usize = 1;
+/// Number of attempts of a request failing with a transient error.
+const MAX_ATTEMPTS: u32 = 3;
+/// Delay before the first retry, doubled on each attempt.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Priority {
+    /// Replies to a user, sent as soon as possible.
+    Interactive,
+    /// Scheduled jobs and broadcasts, which can wait.
+    Bulk,
+}
+
+/// Sliding window of the times at which the messages were (or will be) sent.
+struct Window {
+    limit: usize,
+    period: Duration,
+    slots: VecDeque<Instant>,
+}
+
+impl Window {
+    fn new(limit: usize, period: Duration) -> Self {
+        Self {
+            limit,
+            period,
+            slots: VecDeque::new(),
+        }
+    }
+
+    /// Whether no message was sent in the last period.
+    fn is_idle(&self, now: Instant) -> bool {
+        self.slots.back().is_none_or(|t| *t + self.period <= now)
+    }
+
+    /// First time from `at` at which a message can be sent without exceeding the limit.
+    fn next_slot(&self, at: Instant) -> Instant {
+        let mut slot = at;
+        loop {
+            let mut recent = self
+                .slots
+                .iter()
+                .filter(|t| **t <= slot && **t + self.period > slot);
+            if recent.clone().count() < self.limit {
+                return slot;
+            }
+            // The slots are sorted, the first one is the next to leave the window
+            slot = *recent.next().unwrap() + self.period;
+        }
+    }
+
+    fn reserve(&mut self, slot: Instant) {
+        let now = Instant::now();
+        while self.slots.front().is_some_and(|t| *t + self.period <= now) {
+            self.slots.pop_front();
+        }
+        let index = self.slots.partition_point(|t| *t <= slot);
+        self.slots.insert(index, slot);
+    }
+}
+
+struct Windows {
+    global: Window,
+    bulk: Window,
+    chats: HashMap<ChatId, Window>,
+}
+
+static OUTBOX: OnceLock<Arc<Outbox>> = OnceLock::new();
+/// The outbox of the bot, shared by the replies and the bulk jobs.
+pub fn outbox() -> Arc<Outbox> {
+    OUTBOX.get_or_init(Outbox::new).clone()
+}
+
+/// Queue of the outgoing messages, enforcing the rate limits of Telegram globally and per chat,
+/// and retrying the requests failing with a transient error. The bulk jobs only use part of the
+/// global limit, so that they never delay the interactive replies.
+pub struct Outbox {
+    windows: Mutex<Windows>,
+}
+
+impl Outbox {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            windows: Mutex::new(Windows {
+                global: Window::new(GLOBAL_LIMIT, Duration::from_secs(1)),
+                bulk: Window::new(BULK_LIMIT, Duration::from_secs(1)),
+                chats: HashMap::new(),
+            }),
+        })
+    }
+
+    /// Reserves the next time at which a message can be sent to the chat, if known.
+    fn reserve(&self, chat_id: Option<ChatId>, priority: Priority) -> Instant {
+        let mut windows = self.windows.lock().unwrap();
+        let Windows {
+            global,
+            bulk,
+            chats,
+        } = &mut *windows;
+        let now = Instant::now();
+        chats.retain(|_, w| !w.is_idle(now));
+        // A channel addressed by its username only has the global limits
+        let mut chat = chat_id.map(|chat_id| {
+            chats.entry(chat_id).or_insert_with(|| {
+                if chat_id.is_user() {
+                    Window::new(PRIVATE_LIMIT, Duration::from_secs(1))
+                } else {
+                    Window::new(GROUP_LIMIT, Duration::from_secs(60))
+                }
+            })
+        });
+
+        // Each window can only push the slot later, so this converges
+        let mut slot = now;
+        loop {
+            let mut next = global.next_slot(slot);
+            if let Some(chat) = &chat {
+                next = chat.next_slot(next);
+            }
+            if priority == Priority::Bulk {
+                next = bulk.next_slot(next);
+            }
+            if next == slot {
+                break;
+            }
+            slot = next;
+        }
+
+        global.reserve(slot);
+        if let Some(chat) = &mut chat {
+            chat.reserve(slot);
+        }
+        if priority == Priority::Bulk {
+            bulk.reserve(slot);
+        }
+        slot
+    }
+
+    /// Sends the request to the chat once the rate limits allow it, retrying on transient errors.
+    pub async fn send<R>(
+        &self,
+        chat_id: ChatId,
+        priority: Priority,
+        request: R,
+    ) -> Result<Output<R>, RequestError>
+    where
+        R: Request<Err = RequestError>,
+    {
+        self.send_to(Some(chat_id), priority, request).await
+    }
+
+    async fn send_to<R>(
+        &self,
+        chat_id: Option<ChatId>,
+        priority: Priority,
+        request: R,
+    ) -> Result<Output<R>, RequestError>
+    where
+        R: Request<Err = RequestError>,
+    {
+        let mut attempt = 1;
+        loop {
+            sleep_until(self.reserve(chat_id, priority)).await;
+
+            let delay = match request.send_ref().await {
+                Err(RequestError::RetryAfter(delay)) if attempt < MAX_ATTEMPTS => delay,
+                Err(RequestError::Network(e)) if attempt < MAX_ATTEMPTS => {
+                    log::warn!("Could not reach Telegram, retrying: {:?}", e);
+                    RETRY_DELAY * 2u32.pow(attempt - 1)
+                }
+                result => return result,
+            };
+            attempt += 1;
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// Requests sending or editing a message in a chat, which count in the rate limits.
+pub trait Target {
+    fn target(&self) -> &Recipient;
+}
+
+macro_rules! targets {
+    ($($payload:ty),*) => {
+        $(
+            impl Target for $payload {
+                fn target(&self) -> &Recipient {
+                    &self.chat_id
+                }
+            }
+        )*
+    };
+}
+
+targets!(
+    SendMessage,
+    SendDocument,
+    SendPhoto,
+    SendPoll,
+    EditMessageText,
+    EditMessageReplyMarkup,
+    StopPoll
+);
+
+/// Sends the replies through the outbox, in place of `.await` on the request.
+pub trait Queued: Request<Err = RequestError> + HasPayload<Payload: Target> + Sized {
+    async fn queued(self) -> Result<Output<Self>, RequestError> {
+        let chat_id = match self.payload_ref().target() {
+            Recipient::Id(id) => Some(*id),
+            Recipient::ChannelUsername(_) => None,
+        };
+        outbox().send_to(chat_id, Priority::Interactive, self).await
+    }
+}
+
+impl<R> Queued for R where R: Request<Err = RequestError> + HasPayload<Payload: Target> {}
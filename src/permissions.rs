@@ -0,0 +1,300 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use teloxide::{
+    payloads::PinChatMessageSetters,
+    requests::Requester,
+    types::{
+        Administrator, ChatId, ChatMemberKind, ChatPermissions, Message, MessageId, Restricted,
+        UserId,
+    },
+    Bot, RequestError,
+};
+use tokio::sync::Mutex;
+
+use crate::i18n::{tr, Lang};
+use crate::outbox::Queued;
+
+/// Duration during which the permissions of the bot in a chat are reused without asking Telegram.
+const CACHE_DURATION: Duration = Duration::from_secs(5 * 60);
+
+static BOT_ID: OnceLock<UserId> = OnceLock::new();
+static CACHE: OnceLock<Mutex<HashMap<ChatId, (Instant, Rights)>>> = OnceLock::new();
+/// Chats which have already been told that the bot lacks a permission.
+static WARNED: OnceLock<Mutex<HashSet<(ChatId, Right)>>> = OnceLock::new();
+
+/// Rights of the bot which are not granted to every member.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Right {
+    DeleteMessages,
+    PinMessages,
+    RestrictMembers,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Rights {
+    delete_messages: bool,
+    pin_messages: bool,
+    restrict_members: bool,
+}
+
+impl Rights {
+    fn has(self, right: Right) -> bool {
+        match right {
+            Right::DeleteMessages => self.delete_messages,
+            Right::PinMessages => self.pin_messages,
+            Right::RestrictMembers => self.restrict_members,
+        }
+    }
+}
+
+/// Whether the member has the right to pin messages on their own, regardless of the permissions of
+/// the chat.
+fn can_pin_messages(kind: &ChatMemberKind) -> bool {
+    match kind {
+        ChatMemberKind::Owner(_) => true,
+        ChatMemberKind::Administrator(Administrator {
+            can_pin_messages, ..
+        })
+        | ChatMemberKind::Restricted(Restricted {
+            can_pin_messages, ..
+        }) => *can_pin_messages,
+        _ => false,
+    }
+}
+
+async fn bot_id(bot: &Bot) -> Option<UserId> {
+    match BOT_ID.get() {
+        Some(id) => Some(*id),
+        None => match bot.get_me().await {
+            Ok(me) => Some(*BOT_ID.get_or_init(|| me.id)),
+            Err(e) => {
+                log::error!("Could not fetch the bot's user: {:?}", e);
+                None
+            }
+        },
+    }
+}
+
+/// Whether the bot has the right in the chat. Bots can always delete and pin messages in private
+/// chats, but need to be admin with the corresponding rights in groups.
+async fn has_right(bot: &Bot, chat_id: ChatId, right: Right) -> bool {
+    if chat_id.is_user() {
+        return true;
+    }
+
+    // Not locked during the request, which would block the other chats
+    let cached = CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .await
+        .get(&chat_id)
+        .copied();
+    if let Some((checked_at, rights)) = cached {
+        if checked_at.elapsed() < CACHE_DURATION {
+            return rights.has(right);
+        }
+    }
+
+    let Some(bot_id) = bot_id(bot).await else {
+        return false;
+    };
+    let rights = match bot.get_chat_member(chat_id, bot_id).await {
+        Ok(member) => Rights {
+            delete_messages: member.kind.can_delete_messages(),
+            pin_messages: can_pin_messages(&member.kind),
+            restrict_members: member.kind.can_restrict_members(),
+        },
+        Err(e) => {
+            log::error!(
+                "Could not fetch the bot's permissions in {}: {:?}",
+                chat_id,
+                e
+            );
+            Rights::default()
+        }
+    };
+    CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .await
+        .insert(chat_id, (Instant::now(), rights));
+
+    rights.has(right)
+}
+
+/// Tells the chat once how to grant the missing right to the bot.
+async fn warn_missing_right(bot: &Bot, chat_id: ChatId, right: Right, lang: Lang) {
+    let first = WARNED
+        .get_or_init(Default::default)
+        .lock()
+        .await
+        .insert((chat_id, right));
+    if !first {
+        return;
+    }
+
+    let text = match right {
+        Right::DeleteMessages => tr!(
+            lang,
+            "Je n'ai pas le droit de supprimer les messages de ce groupe, les commandes resteront donc visibles. Pour corriger cela, nommez-moi administrateur avec la permission « Supprimer des messages ».",
+            "I am not allowed to delete the messages of this chat, so the commands will stay visible. To fix this, make me an administrator with the \"Delete messages\" permission."
+        ),
+        Right::PinMessages => tr!(
+            lang,
+            "Je n'ai pas le droit d'épingler les messages de ce groupe. Pour corriger cela, nommez-moi administrateur avec la permission « Épingler des messages ».",
+            "I am not allowed to pin the messages of this chat. To fix this, make me an administrator with the \"Pin messages\" permission."
+        ),
+        Right::RestrictMembers => tr!(
+            lang,
+            "Je n'ai pas le droit de restreindre les membres de ce groupe, la vérification des nouveaux membres est donc inactive. Pour corriger cela, nommez-moi administrateur avec la permission « Bannir des utilisateurs ».",
+            "I am not allowed to restrict the members of this chat, so the verification of the new members is inactive. To fix this, make me an administrator with the \"Ban users\" permission."
+        ),
+    };
+    if let Err(e) = bot.send_message(chat_id, text).queued().await {
+        log::error!("Could not warn {} about permissions: {:?}", chat_id, e);
+    }
+}
+
+/// Forgets that the chat was warned about the right, once the bot has it again.
+async fn right_restored(chat_id: ChatId, right: Right) {
+    let mut warned = WARNED.get_or_init(Default::default).lock().await;
+    warned.remove(&(chat_id, right));
+}
+
+/// Deletes a message sent by a user (e.g. the command starting a dialogue), if the bot is allowed
+/// to. Otherwise the message is left as is, and the chat is told once how to fix the permissions.
+pub async fn delete_user_message(bot: &Bot, msg: &Message, lang: Lang) {
+    let chat_id = msg.chat.id;
+
+    if has_right(bot, chat_id, Right::DeleteMessages).await {
+        right_restored(chat_id, Right::DeleteMessages).await;
+        if let Err(e) = bot.delete_message(chat_id, msg.id).await {
+            log::warn!(
+                "Could not delete message {} in {}: {:?}",
+                msg.id,
+                chat_id,
+                e
+            );
+        }
+        return;
+    }
+
+    log::debug!("Missing the permission to delete messages in {}", chat_id);
+    warn_missing_right(bot, chat_id, Right::DeleteMessages, lang).await;
+}
+
+/// Deletes a message sent by the bot. Bots can delete their own messages without any particular
+/// right (for 48 hours), so failures are only logged and do not abort the flow.
+pub async fn delete_own_message(bot: &Bot, chat_id: ChatId, message_id: MessageId) {
+    if let Err(e) = bot.delete_message(chat_id, message_id).await {
+        log::warn!(
+            "Could not delete message {} in {}: {:?}",
+            message_id,
+            chat_id,
+            e
+        );
+    }
+}
+
+/// Pins a message without notifying the members, if the bot is allowed to. Otherwise the chat is
+/// told once how to fix the permissions. Returns whether the message was pinned.
+pub async fn pin_message(bot: &Bot, chat_id: ChatId, message_id: MessageId, lang: Lang) -> bool {
+    if !has_right(bot, chat_id, Right::PinMessages).await {
+        log::debug!("Missing the permission to pin messages in {}", chat_id);
+        warn_missing_right(bot, chat_id, Right::PinMessages, lang).await;
+        return false;
+    }
+
+    right_restored(chat_id, Right::PinMessages).await;
+    match bot
+        .pin_chat_message(chat_id, message_id)
+        .disable_notification(true)
+        .await
+    {
+        Ok(_) => true,
+        Err(e) => {
+            log::warn!(
+                "Could not pin message {} in {}: {:?}",
+                message_id,
+                chat_id,
+                e
+            );
+            false
+        }
+    }
+}
+
+/// Changes the permissions of a member, if the bot is allowed to. Otherwise the chat is told once
+/// how to fix the permissions. Returns whether the permissions were changed.
+pub async fn restrict_member(
+    bot: &Bot,
+    chat_id: ChatId,
+    user_id: UserId,
+    permissions: ChatPermissions,
+    lang: Lang,
+) -> bool {
+    if !has_right(bot, chat_id, Right::RestrictMembers).await {
+        warn_missing_right(bot, chat_id, Right::RestrictMembers, lang).await;
+        return false;
+    }
+
+    right_restored(chat_id, Right::RestrictMembers).await;
+    match bot
+        .restrict_chat_member(chat_id, user_id, permissions)
+        .await
+    {
+        Ok(_) => true,
+        Err(e) => {
+            log::warn!(
+                "Could not restrict member {} in {}: {:?}",
+                user_id,
+                chat_id,
+                e
+            );
+            false
+        }
+    }
+}
+
+/// Unpins every message of the chat, if the bot is allowed to. Returns whether they were unpinned.
+pub async fn unpin_all_messages(bot: &Bot, chat_id: ChatId, lang: Lang) -> bool {
+    if !has_right(bot, chat_id, Right::PinMessages).await {
+        warn_missing_right(bot, chat_id, Right::PinMessages, lang).await;
+        return false;
+    }
+
+    match bot.unpin_all_chat_messages(chat_id).await {
+        Ok(_) => true,
+        Err(e) => {
+            log::warn!("Could not unpin the messages of {}: {:?}", chat_id, e);
+            false
+        }
+    }
+}
+
+/// Whether the user who sent the message can pin messages in the chat: its admins with the
+/// corresponding right, or every member if the chat allows it.
+pub async fn user_can_pin(bot: &Bot, msg: &Message) -> Result<bool, RequestError> {
+    let Some(user) = msg.from() else {
+        return Ok(false);
+    };
+    if msg.chat.is_private() {
+        return Ok(true);
+    }
+
+    let member = bot.get_chat_member(msg.chat.id, user.id).await?;
+    if can_pin_messages(&member.kind) {
+        return Ok(true);
+    }
+    if !member.kind.is_member() {
+        return Ok(false);
+    }
+    let chat = bot.get_chat(msg.chat.id).await?;
+    Ok(chat
+        .permissions()
+        .is_some_and(|p| p.contains(ChatPermissions::PIN_MESSAGES)))
+}
@@ -0,0 +1,50 @@
+//! Options of the polls sent by the bot, within the limits of Telegram. Kept free of the other
+//! modules so that the benchmarks can include it.
+
+use rand::{seq::SliceRandom, thread_rng, Rng};
+
+pub const POLL_MAX_OPTIONS_COUNT: u8 = 10; // max poll options
+pub const POLL_MAX_QUESTION_LENGTH: usize = 300; // max characters in a poll question
+pub const POLL_MAX_OPTION_LENGTH: usize = 100; // max characters in a poll option
+
+/// Truncates the text to the given number of characters, ending it with an ellipsis if needed.
+pub fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        text.to_owned()
+    } else {
+        let mut truncated = text.chars().take(max - 1).collect::<String>();
+        truncated.push('…');
+        truncated
+    }
+}
+
+/// Options of a quiz whose answer is the target: the target at a random position among others
+/// picked at random, since a poll can have at most 10 options. Returns the options with the index
+/// of the target, `None` if there are not enough people for a quiz.
+pub fn quiz_options(mut others: Vec<String>, target: &str) -> Option<(Vec<String>, u8)> {
+    others.retain(|s| s != target); // filter the target from options
+    others.shuffle(&mut thread_rng()); // shuffle the options
+    others.truncate(POLL_MAX_OPTIONS_COUNT as usize - 1); // keep room for the target
+
+    // A quiz needs at least two options
+    if others.is_empty() {
+        return None;
+    }
+
+    let index = thread_rng().gen_range(0..=others.len()); // generate a valid index to insert target back
+    others.insert(index, target.to_owned()); // insert target back in options
+
+    let options = others
+        .into_iter()
+        .map(|o| truncate(&o, POLL_MAX_OPTION_LENGTH))
+        .collect();
+    Some((options, index as u8))
+}
+
+/// Splits the candidates in polls of similar sizes, each with at most
+/// [`POLL_MAX_OPTIONS_COUNT`] options.
+pub fn split_candidates<T>(candidates: &[T]) -> Vec<&[T]> {
+    let polls = candidates.len().div_ceil(POLL_MAX_OPTIONS_COUNT as usize);
+    let size = candidates.len().div_ceil(polls.max(1));
+    candidates.chunks(size.max(1)).collect()
+}
@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+use teloxide::types::{Message, Poll};
+
+use crate::{dates::now, HandlerResult};
+
+/// Records a poll sent by the bot, so that it can be stopped with `/closepoll`, or automatically
+/// after `close_after` seconds.
+pub async fn track_poll(
+    db: &SqlitePool,
+    msg: &Message,
+    close_after: Option<i64>,
+) -> Result<(), sqlx::Error> {
+    let Some(poll) = msg.poll() else {
+        return Ok(());
+    };
+    let chat_id = msg.chat.id.to_string();
+    let timestamp = now().timestamp();
+    let close_at = close_after.map(|d| timestamp + d);
+    sqlx::query!(
+        "INSERT OR IGNORE INTO sent_polls(poll_id, chat_id, message_id, sent_at, close_at)
+        VALUES($1, $2, $3, $4, $5)",
+        poll.id,
+        chat_id,
+        msg.id.0,
+        timestamp,
+        close_at
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Archives the final counts of a poll sent by the bot once it is closed (stopped, or at the end
+/// of its open period). Telegram sends the closed polls again when they are stopped, the archive
+/// is then overwritten.
+pub async fn archive_poll_results(poll: Poll, db: Arc<SqlitePool>) -> HandlerResult {
+    if poll.is_closed {
+        archive(db.as_ref(), &poll).await?;
+    }
+    Ok(())
+}
+
+/// Archives the counts of a closed poll, and marks it as closed.
+pub async fn archive(db: &SqlitePool, poll: &Poll) -> Result<(), sqlx::Error> {
+    let timestamp = now().timestamp();
+    sqlx::query!(
+        "UPDATE sent_polls SET closed_at = $1 WHERE poll_id = $2 AND closed_at IS NULL",
+        timestamp,
+        poll.id
+    )
+    .execute(db)
+    .await?;
+
+    let source = sqlx::query!(
+        r#"SELECT chat_id, 'quiz' AS "kind!: String" FROM quotes WHERE poll_id = $1
+        UNION ALL SELECT chat_id, 'bureau' FROM bureau_polls WHERE poll_id = $1
+        UNION ALL SELECT e.chat_id, 'election' FROM quote_election_polls p
+        JOIN quote_elections e ON e.id = p.election_id WHERE p.poll_id = $1"#,
+        poll.id
+    )
+    .fetch_optional(db)
+    .await?;
+    let Some(source) = source else {
+        log::debug!("Ignoring results of unknown poll {}", poll.id);
+        return Ok(());
+    };
+
+    let mut tx = db.begin().await?;
+    for (option, result) in poll.options.iter().enumerate() {
+        let option = option as i64;
+        sqlx::query!(
+            "INSERT INTO poll_results(poll_id, chat_id, kind, question, option, option_text, voter_count, closed_at)
+            VALUES($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT(poll_id, option) DO UPDATE SET voter_count = excluded.voter_count",
+            poll.id,
+            source.chat_id,
+            source.kind,
+            poll.question,
+            option,
+            result.text,
+            result.voter_count,
+            timestamp
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    log::debug!("Archived results of {} poll {}", source.kind, poll.id);
+
+    Ok(())
+}
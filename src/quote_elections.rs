@@ -0,0 +1,366 @@
+//! Election of the best quote of the month. When a month ends, the quotes of the month are put to
+//! the vote in polls of at most 10 quotes. The winners of the polls go to the next round, until a
+//! single poll elects the quote of the month, archived in `quote_awards`.
+
+use chrono::Duration;
+use sqlx::SqlitePool;
+use teloxide::{requests::Requester, types::ChatId, Bot};
+
+use crate::{
+    dates::{now, start_of_month},
+    db::quotes::QuoteRepo,
+    format::{bold, italic, HtmlMessages},
+    i18n::{chat_language, tr},
+    outbox::{Outbox, Priority},
+    poll_options::{split_candidates, truncate, POLL_MAX_OPTIONS_COUNT, POLL_MAX_OPTION_LENGTH},
+    poll_results::track_poll,
+    HandlerResult,
+};
+
+/// Duration of a round of the election, in seconds.
+const ROUND_DURATION: i64 = 24 * 60 * 60;
+
+struct Election {
+    id: i64,
+    chat_id: ChatId,
+    month: String,
+}
+
+/// "MM/YYYY" of a "YYYY-MM" month.
+fn format_month(month: &str) -> String {
+    match month.split_once('-') {
+        Some((year, month)) => format!("{}/{}", month, year),
+        None => month.to_owned(),
+    }
+}
+
+/// Starts the elections of the quotes of the previous month, in the chats which have some.
+async fn start_elections(bot: &Bot, outbox: &Outbox, db: &SqlitePool) -> HandlerResult {
+    let current = start_of_month(&now());
+    let previous = start_of_month(&(current - Duration::days(1)));
+    let month = previous.format("%Y-%m").to_string();
+    let (from, to) = (previous.timestamp(), current.timestamp());
+
+    let chats = sqlx::query!(
+        "SELECT DISTINCT chat_id FROM quotes WHERE created_at >= $1 AND created_at < $2
+        AND chat_id NOT IN (SELECT chat_id FROM quote_elections WHERE month = $3)",
+        from,
+        to,
+        month
+    )
+    .fetch_all(db)
+    .await?;
+
+    for chat in chats {
+        let Ok(chat_id) = chat.chat_id.parse::<i64>().map(ChatId) else {
+            continue;
+        };
+        let timestamp = now().timestamp();
+        let id = sqlx::query!(
+            "INSERT INTO quote_elections(chat_id, month, started_at) VALUES($1, $2, $3)",
+            chat.chat_id,
+            month,
+            timestamp
+        )
+        .execute(db)
+        .await?
+        .last_insert_rowid();
+        let candidates = sqlx::query!(
+            r#"SELECT id AS "id!" FROM quotes WHERE chat_id = $1 AND created_at >= $2
+            AND created_at < $3 ORDER BY created_at"#,
+            chat.chat_id,
+            from,
+            to
+        )
+        .fetch_all(db)
+        .await?
+        .into_iter()
+        .map(|q| q.id)
+        .collect::<Vec<_>>();
+
+        log::info!(
+            "Starting election of the quote of {} in {}, with {} quotes",
+            month,
+            chat_id,
+            candidates.len()
+        );
+        let election = Election {
+            id,
+            chat_id,
+            month: month.clone(),
+        };
+        start_round(bot, outbox, db, &election, 1, &candidates).await?;
+    }
+
+    Ok(())
+}
+
+/// Sends the polls of a round, or awards the quote if a single one remains.
+async fn start_round(
+    bot: &Bot,
+    outbox: &Outbox,
+    db: &SqlitePool,
+    election: &Election,
+    round: i64,
+    candidates: &[i64],
+) -> HandlerResult {
+    match candidates {
+        [] => return close_election(db, election.id).await,
+        [quote_id] => return award(bot, outbox, db, election, *quote_id, 0).await,
+        _ => {}
+    }
+
+    let mut polls: Vec<Vec<(i64, String)>> = Vec::new();
+    for chunk in split_candidates(candidates) {
+        let mut poll = Vec::new();
+        for id in chunk {
+            let Some(quote) = db.quote(*id).await? else {
+                continue;
+            };
+            poll.push((
+                *id,
+                truncate(
+                    &format!("{}: {}", quote.author, quote.text),
+                    POLL_MAX_OPTION_LENGTH,
+                ),
+            ));
+        }
+
+        // A chunk left with a single quote (the others were deleted) is merged with the previous
+        // one, which is split again if it gets too big, so that the quote stays in the running
+        if polls
+            .last()
+            .is_some_and(|last| poll.len() < 2 || last.len() < 2)
+        {
+            let mut last = polls.pop().expect("the last poll exists");
+            last.append(&mut poll);
+            if last.len() > POLL_MAX_OPTIONS_COUNT as usize {
+                poll = last.split_off(last.len() / 2);
+                polls.push(last);
+                polls.push(poll);
+            } else {
+                polls.push(last);
+            }
+        } else if !poll.is_empty() {
+            polls.push(poll);
+        }
+    }
+    match polls.as_slice() {
+        [] => return close_election(db, election.id).await,
+        [poll] if poll.len() == 1 => return award(bot, outbox, db, election, poll[0].0, 0).await,
+        _ => {}
+    }
+
+    let lang = chat_language(db, election.chat_id).await;
+    let question = if polls.len() == 1 {
+        tr!(
+            lang,
+            "🏅 Élection de la citation du mois de {} (finale)",
+            "🏅 Election of the quote of the month of {} (final)",
+            format_month(&election.month)
+        )
+    } else {
+        tr!(
+            lang,
+            "🏅 Élection de la citation du mois de {} (tour {})",
+            "🏅 Election of the quote of the month of {} (round {})",
+            format_month(&election.month),
+            round
+        )
+    };
+
+    for poll in polls {
+        let (ids, options): (Vec<_>, Vec<_>) = poll
+            .into_iter()
+            .map(|(id, option)| (id.to_string(), option))
+            .unzip();
+        let request = bot.send_poll(election.chat_id, question.clone(), options);
+        let msg = match outbox.send(election.chat_id, Priority::Bulk, request).await {
+            Ok(msg) => msg,
+            Err(e) => {
+                log::error!("Could not send poll of election #{}: {:?}", election.id, e);
+                continue;
+            }
+        };
+        track_poll(db, &msg, Some(ROUND_DURATION)).await?;
+        if let Some(poll) = msg.poll() {
+            let quote_ids = ids.join(",");
+            sqlx::query!(
+                "INSERT INTO quote_election_polls(poll_id, election_id, round, quote_ids)
+                VALUES($1, $2, $3, $4)",
+                poll.id,
+                election.id,
+                round,
+                quote_ids
+            )
+            .execute(db)
+            .await?;
+        }
+    }
+
+    sqlx::query!(
+        "UPDATE quote_elections SET round = $1 WHERE id = $2",
+        round,
+        election.id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+async fn close_election(db: &SqlitePool, id: i64) -> HandlerResult {
+    let timestamp = now().timestamp();
+    sqlx::query!(
+        "UPDATE quote_elections SET closed_at = $1 WHERE id = $2",
+        timestamp,
+        id
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Archives the winning quote and announces it.
+async fn award(
+    bot: &Bot,
+    outbox: &Outbox,
+    db: &SqlitePool,
+    election: &Election,
+    quote_id: i64,
+    votes: i64,
+) -> HandlerResult {
+    let quote = db.quote(quote_id).await?;
+    if let Some(quote) = quote {
+        let chat_id = election.chat_id.to_string();
+        let timestamp = now().timestamp();
+        sqlx::query!(
+            r#"INSERT OR REPLACE INTO quote_awards(chat_id, month, quote_id, author, "text", votes, awarded_at)
+            VALUES($1, $2, $3, $4, $5, $6, $7)"#,
+            chat_id,
+            election.month,
+            quote_id,
+            quote.author,
+            quote.text,
+            votes,
+            timestamp
+        )
+        .execute(db)
+        .await?;
+
+        let lang = chat_language(db, election.chat_id).await;
+        let text = tr!(
+            lang,
+            "🏅 La citation du mois de {} est:\n« {} »\n— {} ({} vote(s))",
+            "🏅 The quote of the month of {} is:\n“{}”\n— {} ({} vote(s))",
+            format_month(&election.month),
+            italic(&quote.text),
+            bold(&quote.author),
+            votes
+        );
+        if let Err(e) = outbox
+            .send(
+                election.chat_id,
+                Priority::Bulk,
+                bot.send_html(election.chat_id, text),
+            )
+            .await
+        {
+            log::error!(
+                "Could not announce the quote of election #{}: {:?}",
+                election.id,
+                e
+            );
+        }
+        log::info!(
+            "Quote #{} ({}) elected quote of {} in {}",
+            quote_id,
+            quote.author,
+            election.month,
+            election.chat_id
+        );
+    }
+
+    close_election(db, election.id).await
+}
+
+/// Moves the elections whose polls are all closed to their next round.
+async fn advance_elections(bot: &Bot, outbox: &Outbox, db: &SqlitePool) -> HandlerResult {
+    let open = sqlx::query!(
+        r#"SELECT id AS "id!", chat_id, month, round FROM quote_elections WHERE closed_at IS NULL"#
+    )
+    .fetch_all(db)
+    .await?;
+
+    for e in open {
+        let Ok(chat_id) = e.chat_id.parse::<i64>().map(ChatId) else {
+            continue;
+        };
+        let polls = sqlx::query!(
+            r#"SELECT p.poll_id AS "poll_id!", p.quote_ids, s.closed_at FROM quote_election_polls p
+            LEFT JOIN sent_polls s ON s.poll_id = p.poll_id
+            WHERE p.election_id = $1 AND p.round = $2"#,
+            e.id,
+            e.round
+        )
+        .fetch_all(db)
+        .await?;
+        if polls.iter().any(|p| p.closed_at.is_none()) {
+            continue;
+        }
+
+        // Winner of each poll, the first quote in case of a tie
+        let mut winners = vec![];
+        for poll in &polls {
+            let ids = poll
+                .quote_ids
+                .split(',')
+                .filter_map(|id| id.parse::<i64>().ok())
+                .collect::<Vec<_>>();
+            let results = sqlx::query!(
+                "SELECT option, voter_count FROM poll_results WHERE poll_id = $1",
+                poll.poll_id
+            )
+            .fetch_all(db)
+            .await?;
+            let best = ids
+                .iter()
+                .enumerate()
+                .map(|(i, id)| {
+                    let votes = results
+                        .iter()
+                        .find(|r| r.option == i as i64)
+                        .map_or(0, |r| r.voter_count);
+                    (*id, votes)
+                })
+                .fold(None, |best: Option<(i64, i64)>, (id, votes)| match best {
+                    Some((_, best_votes)) if best_votes >= votes => best,
+                    _ => Some((id, votes)),
+                });
+            winners.extend(best);
+        }
+
+        let election = Election {
+            id: e.id,
+            chat_id,
+            month: e.month,
+        };
+        match winners[..] {
+            [(quote_id, votes)] if polls.len() == 1 => {
+                award(bot, outbox, db, &election, quote_id, votes).await?
+            }
+            _ => {
+                let candidates = winners.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+                start_round(bot, outbox, db, &election, e.round + 1, &candidates).await?
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Starts the elections of the month which ended, and runs the rounds of the ongoing ones.
+pub async fn run_quote_elections(bot: &Bot, outbox: &Outbox, db: &SqlitePool) -> HandlerResult {
+    start_elections(bot, outbox, db).await?;
+    advance_elections(bot, outbox, db).await
+}
@@ -0,0 +1,192 @@
+//! Reactions to messages, which teloxide does not support yet.
+//!
+//! Telegram only sends the `message_reaction` updates to the bots which explicitly ask for them,
+//! and teloxide can neither ask for them nor parse them: they reach the dispatcher as unparsed
+//! updates, which it drops. The updates are hence requested with a raw `getUpdates` (or
+//! `setWebhook`) call, and picked out of the update stream by [`ReactionListener`] before the
+//! dispatcher sees them.
+
+use std::time::Duration;
+
+use futures::{future::ready, stream::BoxStream, StreamExt};
+use serde::Deserialize;
+use serde_json::json;
+use teloxide::{
+    stop::StopToken,
+    types::{Update, UpdateKind},
+    update_listeners::{AsUpdateStream, Polling, UpdateListener},
+    Bot, RequestError,
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::webhook::Webhook;
+
+/// Updates received by the bot: those handled by teloxide, and the reactions.
+pub const ALLOWED_UPDATES: &[&str] = &[
+    "message",
+    "edited_message",
+    "channel_post",
+    "edited_channel_post",
+    "inline_query",
+    "chosen_inline_result",
+    "callback_query",
+    "poll",
+    "poll_answer",
+    "my_chat_member",
+    "message_reaction",
+];
+
+#[derive(Deserialize, Debug)]
+pub struct ReactionChat {
+    pub id: i64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ReactionType {
+    /// Set for the standard emoji reactions, not for the custom ones.
+    pub emoji: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ReactionUser {
+    pub id: u64,
+}
+
+/// Change of the reactions of a user (or anonymous admin) to a message.
+#[derive(Deserialize, Debug)]
+pub struct MessageReactionUpdated {
+    pub chat: ReactionChat,
+    pub message_id: i32,
+    /// Not set for the reactions of the anonymous admins.
+    pub user: Option<ReactionUser>,
+    pub old_reaction: Vec<ReactionType>,
+    pub new_reaction: Vec<ReactionType>,
+}
+
+impl MessageReactionUpdated {
+    /// +1 if the emoji was added, -1 if it was removed, 0 otherwise.
+    pub fn delta(&self, emoji: &str) -> i64 {
+        let count = |reactions: &[ReactionType]| {
+            reactions
+                .iter()
+                .filter(|r| r.emoji.as_deref() == Some(emoji))
+                .count() as i64
+        };
+        count(&self.new_reaction) - count(&self.old_reaction)
+    }
+}
+
+/// Sets the updates sent by Telegram to the bot, including the reactions. Telegram keeps this
+/// setting for the next `getUpdates` calls, as long as they do not specify other updates.
+pub async fn allow_reaction_updates(bot: &Bot) -> Result<(), reqwest::Error> {
+    let url = bot
+        .api_url()
+        .join(&format!("bot{}/getUpdates", bot.token()))
+        .expect("the API url is valid");
+    // Neither confirms nor waits for the pending updates. The url is removed from the errors,
+    // since it contains the token
+    reqwest::Client::new()
+        .post(url)
+        .header("Content-Type", "application/json")
+        .body(json!({ "limit": 1, "timeout": 0, "allowed_updates": ALLOWED_UPDATES }).to_string())
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map(|_| ())
+        .map_err(reqwest::Error::without_url)
+}
+
+/// Source of the updates.
+enum Source {
+    Polling(Polling<Bot>),
+    Webhook(Webhook),
+}
+
+/// Listener forwarding the reactions to the given channel, and the other updates to the
+/// dispatcher.
+pub struct ReactionListener {
+    source: Source,
+    reactions: UnboundedSender<MessageReactionUpdated>,
+}
+
+impl ReactionListener {
+    /// Listener polling the updates.
+    pub async fn new(bot: Bot, reactions: UnboundedSender<MessageReactionUpdated>) -> Self {
+        let polling = Polling::builder(bot)
+            .timeout(Duration::from_secs(10))
+            .delete_webhook()
+            .await
+            .build();
+        Self {
+            source: Source::Polling(polling),
+            reactions,
+        }
+    }
+
+    /// Listener of the updates posted to the webhook.
+    pub fn with_webhook(
+        webhook: Webhook,
+        reactions: UnboundedSender<MessageReactionUpdated>,
+    ) -> Self {
+        Self {
+            source: Source::Webhook(webhook),
+            reactions,
+        }
+    }
+}
+
+impl UpdateListener for ReactionListener {
+    type Err = RequestError;
+
+    fn stop_token(&mut self) -> StopToken {
+        match &mut self.source {
+            Source::Polling(polling) => polling.stop_token(),
+            Source::Webhook(webhook) => webhook.stop_token(),
+        }
+    }
+
+    // The hint of the dispatcher is ignored, since it cannot include the reactions and would
+    // override the setting of `allow_reaction_updates`
+
+    fn timeout_hint(&self) -> Option<Duration> {
+        match &self.source {
+            Source::Polling(polling) => polling.timeout_hint(),
+            Source::Webhook(_) => None,
+        }
+    }
+}
+
+impl<'a> AsUpdateStream<'a> for ReactionListener {
+    type StreamErr = RequestError;
+    type Stream = BoxStream<'a, Result<Update, RequestError>>;
+
+    fn as_stream(&'a mut self) -> Self::Stream {
+        let reactions = self.reactions.clone();
+        let updates = match &mut self.source {
+            Source::Polling(polling) => polling.as_stream().boxed(),
+            Source::Webhook(webhook) => webhook.updates().map(Ok).boxed(),
+        };
+        updates
+            .filter(move |update| {
+                let reaction = match update {
+                    Ok(Update {
+                        kind: UpdateKind::Error(value),
+                        ..
+                    }) => value.get("message_reaction"),
+                    _ => None,
+                };
+                let Some(reaction) = reaction else {
+                    return ready(true);
+                };
+
+                match serde_json::from_value(reaction.clone()) {
+                    Ok(reaction) => {
+                        let _ = reactions.send(reaction);
+                    }
+                    Err(e) => log::error!("Invalid reaction update {}: {:?}", reaction, e),
+                }
+                ready(false)
+            })
+            .boxed()
+    }
+}
@@ -0,0 +1,116 @@
+//! Retention durations of the data growing with the use of the bot, applied by the maintenance
+//! job. The admins can change them with `/retention`, the defaults come from the config.
+
+use sqlx::SqlitePool;
+
+use crate::config::config;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Policy {
+    AuditLog,
+    CommandLog,
+    PollAnswers,
+    Quotes,
+}
+
+pub const POLICIES: [Policy; 4] = [
+    Policy::AuditLog,
+    Policy::CommandLog,
+    Policy::PollAnswers,
+    Policy::Quotes,
+];
+
+impl Policy {
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::AuditLog => "audit_log",
+            Self::CommandLog => "command_log",
+            Self::PollAnswers => "poll_answers",
+            Self::Quotes => "quotes",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::AuditLog => "Journal d'audit des actions des admins",
+            Self::CommandLog => "Journal des commandes reçues",
+            Self::PollAnswers => "Historique des réponses aux sondages",
+            Self::Quotes => "Citations des quiz",
+        }
+    }
+
+    /// Shortest duration which can be set, in days, so that `/retention` cannot be used to erase
+    /// recent data at once.
+    pub fn min_days(self) -> i64 {
+        match self {
+            // An admin cannot erase the recent log of their own actions
+            Self::AuditLog => 30,
+            // Leaves a week to look into an incident with /auditexport
+            Self::CommandLog => 7,
+            // Keeps the recent polls and quotes, which the quizzes and the stats still use
+            Self::PollAnswers | Self::Quotes => 30,
+        }
+    }
+
+    /// Duration when none is set, in days, `None` to keep the data forever.
+    pub fn default_days(self) -> Option<i64> {
+        match self {
+            Self::AuditLog | Self::CommandLog => Some(config().audit_retention_days),
+            Self::PollAnswers | Self::Quotes => None,
+        }
+    }
+}
+
+pub fn find_policy(key: &str) -> Option<Policy> {
+    POLICIES
+        .into_iter()
+        .find(|p| p.key().eq_ignore_ascii_case(key))
+}
+
+/// Duration set with `/retention`, if any: `Some(None)` when the data is kept forever.
+pub async fn custom_days(
+    db: &SqlitePool,
+    policy: Policy,
+) -> Result<Option<Option<i64>>, sqlx::Error> {
+    let key = policy.key();
+    sqlx::query_scalar!(
+        r#"SELECT days FROM retention_policies WHERE "policy" = $1"#,
+        key
+    )
+    .fetch_optional(db)
+    .await
+}
+
+/// Number of days the data of the policy is kept, `None` to keep it forever.
+pub async fn retention_days(db: &SqlitePool, policy: Policy) -> Result<Option<i64>, sqlx::Error> {
+    Ok(custom_days(db, policy)
+        .await?
+        .unwrap_or_else(|| policy.default_days()))
+}
+
+/// Sets the duration of the policy, `None` to keep the data forever.
+pub async fn set_retention(
+    db: &SqlitePool,
+    policy: Policy,
+    days: Option<i64>,
+) -> Result<(), sqlx::Error> {
+    let key = policy.key();
+    sqlx::query!(
+        r#"INSERT INTO retention_policies("policy", days) VALUES($1, $2)
+        ON CONFLICT("policy") DO UPDATE SET days = excluded.days"#,
+        key,
+        days
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Goes back to the default duration of the policy.
+pub async fn reset_retention(db: &SqlitePool, policy: Policy) -> Result<(), sqlx::Error> {
+    let key = policy.key();
+    sqlx::query!(r#"DELETE FROM retention_policies WHERE "policy" = $1"#, key)
+        .execute(db)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use std::{
+    collections::HashMap,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use reqwest::Client;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use crate::{config::config, directus::Error};
+
+/// Duration during which the bookings of a room are reused without querying the API.
+const CACHE_DURATION: Duration = Duration::from_secs(10 * 60);
+/// Time allowed to the room API to answer.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A booking of a room (lecture, exam, event...).
+#[derive(Deserialize, Debug, Clone)]
+pub struct Booking {
+    pub title: String,
+    start: String,
+    end: String,
+}
+
+impl Booking {
+    pub fn start(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc3339(&self.start).ok()
+    }
+
+    pub fn end(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc3339(&self.end).ok()
+    }
+}
+
+type CachedBookings = (Instant, Vec<Booking>);
+static CACHE: OnceLock<Mutex<HashMap<(String, NaiveDate), CachedBookings>>> = OnceLock::new();
+
+/// Fetches the bookings of the room on the given day, sorted by start, using the cache when
+/// possible.
+///
+/// Returns `Ok(None)` when no room API is configured.
+pub async fn get_bookings(room: &str, day: NaiveDate) -> Result<Option<Vec<Booking>>, Error> {
+    let Some(url) = &config().room_api_url else {
+        return Ok(None);
+    };
+
+    let key = (room.to_uppercase(), day);
+    // Not locked during the request, so that a slow API does not block the other rooms
+    let cached = CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .await
+        .get(&key)
+        .cloned();
+    if let Some((fetched_at, bookings)) = cached {
+        if fetched_at.elapsed() < CACHE_DURATION {
+            return Ok(Some(bookings));
+        }
+    }
+
+    log::debug!("Fetching bookings of {} on {}", key.0, day);
+    let response = Client::new()
+        .get(url)
+        .query(&[
+            ("room", key.0.clone()),
+            ("date", day.format("%Y-%m-%d").to_string()),
+        ])
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?;
+    let mut bookings = serde_json::from_str::<Vec<Booking>>(response.text().await?.as_str())?;
+    bookings.sort_by_key(|b| b.start());
+
+    let mut cache = CACHE.get_or_init(Default::default).lock().await;
+    cache.retain(|_, (fetched_at, _)| fetched_at.elapsed() < CACHE_DURATION);
+    cache.insert(key, (Instant::now(), bookings.clone()));
+
+    Ok(Some(bookings))
+}
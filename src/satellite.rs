@@ -0,0 +1,80 @@
+use std::{
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, FixedOffset};
+use reqwest::Client;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use crate::{config::config, directus::Error};
+
+/// Duration during which the program of Satellite is reused without querying the API. The beers
+/// on tap change a few times per day at most.
+const CACHE_DURATION: Duration = Duration::from_secs(30 * 60);
+/// Time allowed to the Satellite API to answer.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A beer on tap at Satellite.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Beer {
+    pub name: String,
+    pub style: Option<String>,
+    pub price: Option<f64>,
+}
+
+/// An event organized at Satellite (concert, quiz night...).
+#[derive(Deserialize, Debug, Clone)]
+pub struct SatelliteEvent {
+    pub title: String,
+    start: String,
+}
+
+impl SatelliteEvent {
+    pub fn start(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc3339(&self.start).ok()
+    }
+}
+
+/// What is on at Satellite: the beers on tap and the coming events.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Program {
+    #[serde(default)]
+    pub beers: Vec<Beer>,
+    #[serde(default)]
+    pub events: Vec<SatelliteEvent>,
+}
+
+static CACHE: OnceLock<Mutex<Option<(Instant, Program)>>> = OnceLock::new();
+
+/// Fetches the program of Satellite, using the cache when possible.
+///
+/// Returns `Ok(None)` when no Satellite API is configured.
+pub async fn get_program() -> Result<Option<Program>, Error> {
+    let Some(url) = &config().satellite_api_url else {
+        return Ok(None);
+    };
+
+    // Not locked during the request, so that a slow API does not queue the other calls
+    let cached = CACHE.get_or_init(Default::default).lock().await.clone();
+    if let Some((fetched_at, program)) = cached {
+        if fetched_at.elapsed() < CACHE_DURATION {
+            return Ok(Some(program));
+        }
+    }
+
+    log::debug!("Fetching the program of Satellite");
+    let response = Client::new()
+        .get(url)
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?;
+    let mut program = serde_json::from_str::<Program>(response.text().await?.as_str())?;
+    program.events.sort_by_key(|e| e.start());
+
+    *CACHE.get_or_init(Default::default).lock().await = Some((Instant::now(), program.clone()));
+
+    Ok(Some(program))
+}
@@ -0,0 +1,140 @@
+use std::time::Duration;
+
+use teloxide::Bot;
+
+use crate::{
+    anomalies::detect_anomalies,
+    cmd_closepoll::close_due_polls,
+    cmd_countdown::update_countdowns,
+    cmd_loan::remind_overdue_loans,
+    cmd_newsletter::send_due_newsletters,
+    cmd_reminders::deliver_due_reminders,
+    cmd_schedules::{restore_schedules, run_due_schedules},
+    cmd_tournament::close_due_tournaments,
+    dialogues::expire_dialogues,
+    mailing::sync_mailing_requests,
+    quote_elections::run_quote_elections,
+    state::AppState,
+    verification::expire_member_challenges,
+};
+
+/// Interval between two runs of the scheduled jobs.
+const TICK: Duration = Duration::from_secs(20);
+
+/// Spawns the background task running the scheduled jobs (e.g. delivering reminders, recurring
+/// messages).
+pub fn start(bot: Bot, state: AppState) {
+    tokio::spawn(async move {
+        let AppState {
+            db,
+            outbox,
+            metrics,
+            ..
+        } = state;
+        if let Err(e) = restore_schedules(db.as_ref()).await {
+            log::error!("Could not restore scheduled jobs: {:?}", e);
+        }
+
+        let mut interval = tokio::time::interval(TICK);
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = metrics
+                .timed(
+                    "job:reminders",
+                    deliver_due_reminders(&bot, &outbox, db.as_ref()),
+                )
+                .await
+            {
+                log::error!("Could not deliver reminders: {:?}", e);
+            }
+            if let Err(e) = metrics
+                .timed(
+                    "job:schedules",
+                    run_due_schedules(&bot, &outbox, db.as_ref()),
+                )
+                .await
+            {
+                log::error!("Could not run scheduled jobs: {:?}", e);
+            }
+            if let Err(e) = metrics
+                .timed(
+                    "job:countdowns",
+                    update_countdowns(&bot, &outbox, db.as_ref()),
+                )
+                .await
+            {
+                log::error!("Could not update countdowns: {:?}", e);
+            }
+            if let Err(e) = metrics
+                .timed(
+                    "job:loans",
+                    remind_overdue_loans(&bot, &outbox, db.as_ref()),
+                )
+                .await
+            {
+                log::error!("Could not remind overdue loans: {:?}", e);
+            }
+            if let Err(e) = metrics
+                .timed(
+                    "job:tournaments",
+                    close_due_tournaments(&bot, &outbox, db.as_ref()),
+                )
+                .await
+            {
+                log::error!("Could not close tournaments: {:?}", e);
+            }
+            if let Err(e) = metrics
+                .timed("job:polls", close_due_polls(&bot, &outbox, db.as_ref()))
+                .await
+            {
+                log::error!("Could not close polls: {:?}", e);
+            }
+            if let Err(e) = metrics
+                .timed(
+                    "job:quote_elections",
+                    run_quote_elections(&bot, &outbox, db.as_ref()),
+                )
+                .await
+            {
+                log::error!("Could not run quote elections: {:?}", e);
+            }
+            if let Err(e) = metrics
+                .timed(
+                    "job:newsletters",
+                    send_due_newsletters(&bot, &outbox, db.as_ref()),
+                )
+                .await
+            {
+                log::error!("Could not send newsletters: {:?}", e);
+            }
+            if let Err(e) = metrics
+                .timed("job:mailing", sync_mailing_requests(db.as_ref()))
+                .await
+            {
+                log::error!("Could not sync mailing requests: {:?}", e);
+            }
+            if let Err(e) = metrics
+                .timed(
+                    "job:member_challenges",
+                    expire_member_challenges(&bot, db.as_ref()),
+                )
+                .await
+            {
+                log::error!("Could not expire member challenges: {:?}", e);
+            }
+            if let Err(e) = metrics
+                .timed("job:dialogues", expire_dialogues(&bot, db.as_ref()))
+                .await
+            {
+                log::error!("Could not expire dialogues: {:?}", e);
+            }
+            if let Err(e) = metrics
+                .timed("job:anomalies", detect_anomalies(&bot, db.as_ref()))
+                .await
+            {
+                log::error!("Could not check the audit log: {:?}", e);
+            }
+        }
+    });
+}
@@ -0,0 +1,35 @@
+use std::{
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::RwLock;
+
+use crate::directus::{get_shop_items, Error, ShopItem};
+
+/// Duration after which the items are fetched again. The stock rarely changes, and the admins can
+/// refresh it right away with `/shopupdate`.
+const SHOP_TTL: Duration = Duration::from_secs(60 * 60);
+
+type CachedItems = (Instant, Vec<ShopItem>);
+static CACHE: OnceLock<RwLock<Option<CachedItems>>> = OnceLock::new();
+fn cache() -> &'static RwLock<Option<CachedItems>> {
+    CACHE.get_or_init(Default::default)
+}
+
+/// Merch items of the shop, fetched from Directus if the cache is empty or outdated.
+pub async fn shop_items() -> Result<Vec<ShopItem>, Error> {
+    if let Some((fetched_at, items)) = cache().read().await.as_ref() {
+        if fetched_at.elapsed() < SHOP_TTL {
+            return Ok(items.clone());
+        }
+    }
+    refresh_shop().await
+}
+
+/// Fetches the items from Directus, replacing the cached ones.
+pub async fn refresh_shop() -> Result<Vec<ShopItem>, Error> {
+    let items = get_shop_items().await?;
+    *cache().write().await = Some((Instant::now(), items.clone()));
+    Ok(items)
+}
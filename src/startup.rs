@@ -0,0 +1,207 @@
+//! Checks before the bot starts: validation of the configuration, reporting all the problems at
+//! once instead of panicking on the first one, then a self-check of the database, Telegram,
+//! Directus and the scheduler.
+
+use std::{process, time::Duration};
+
+use sqlx::SqlitePool;
+use teloxide::{
+    requests::{Request, Requester},
+    types::ChatId,
+    Bot,
+};
+use tokio::time::timeout;
+
+use crate::{
+    cmd_schedules::{check_schedules, parse_cron},
+    config::{check_config, check_storage, config},
+    directus,
+    format::{bold, escape, HtmlMessages, MessageBuilder},
+    http::invalid_ranges,
+    outbox::Queued,
+};
+
+/// Time after which an external service is considered unreachable by the self-check.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Checks of the loaded config which need the modules of the bot, the filesystem or the database.
+async fn check_environment() -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if parse_cron(&config().maintenance_cron).is_none() {
+        problems.push(format!(
+            "MAINTENANCE_CRON is invalid (\"{}\"): expected a 5-fields cron expression, e.g. 0 4 * * *",
+            config().maintenance_cron
+        ));
+    }
+    if let Some(ranges) = &config().http_allowed_ips {
+        let invalid = invalid_ranges(ranges);
+        if !invalid.is_empty() {
+            problems.push(format!(
+                "HTTP_ALLOWED_IPS contains invalid ranges ({}): expected addresses or ranges, e.g. 10.0.0.0/8",
+                invalid.join(", ")
+            ));
+        }
+    }
+
+    problems.extend(check_storage().await);
+    problems
+}
+
+/// Validates the configuration, and exits with a report of the problems if there are any.
+pub async fn validate_config() {
+    let mut problems = check_config();
+    // The config can only be loaded once the variables are valid
+    if problems.is_empty() {
+        problems = check_environment().await;
+    }
+    if problems.is_empty() {
+        return;
+    }
+
+    eprintln!(
+        "The configuration is invalid, the bot cannot start ({} problem{}):",
+        problems.len(),
+        if problems.len() > 1 { "s" } else { "" }
+    );
+    for problem in &problems {
+        eprintln!(" - {}", problem);
+    }
+    eprintln!("See the Configuration section of the README for the variables.");
+    process::exit(1);
+}
+
+struct Check {
+    name: &'static str,
+    /// Whether the bot cannot work at all when the check fails.
+    critical: bool,
+    result: Result<String, String>,
+}
+
+/// Whether all the migrations known to this version are applied successfully.
+async fn check_schema(db: &SqlitePool) -> Result<String, String> {
+    let latest = sqlx::migrate!()
+        .iter()
+        .map(|m| m.version)
+        .max()
+        .unwrap_or_default();
+    // The table is created by the migrator, so it is unknown to the checked queries
+    let (version, failed): (Option<i64>, i64) = sqlx::query_as(
+        "SELECT MAX(version), COUNT(*) FILTER (WHERE NOT success) FROM _sqlx_migrations",
+    )
+    .fetch_one(db)
+    .await
+    .map_err(|e| format!("cannot read the migrations: {}", e))?;
+
+    match version {
+        _ if failed > 0 => Err(format!("{} migration(s) failed", failed)),
+        Some(version) if version >= latest => Ok(format!("version {}", version)),
+        version => Err(format!(
+            "version {} instead of {}, run the migrations",
+            version.unwrap_or_default(),
+            latest
+        )),
+    }
+}
+
+async fn check_telegram(bot: &Bot) -> Result<String, String> {
+    match timeout(CHECK_TIMEOUT, bot.get_me().send()).await {
+        Ok(Ok(me)) => Ok(format!("logged in as @{}", me.username())),
+        Ok(Err(e)) => Err(format!("{}, check BOT_TOKEN", e)),
+        Err(_) => Err("no answer from Telegram".to_owned()),
+    }
+}
+
+async fn check_directus() -> Result<String, String> {
+    match timeout(CHECK_TIMEOUT, directus::status()).await {
+        Ok(Ok(user)) => Ok(format!("logged in as {}", user)),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("no answer from Directus".to_owned()),
+    }
+}
+
+async fn check_scheduler(db: &SqlitePool) -> Result<String, String> {
+    let (count, invalid) = check_schedules(db)
+        .await
+        .map_err(|e| format!("cannot load the scheduled jobs: {}", e))?;
+    if invalid.is_empty() {
+        Ok(format!("{} scheduled job(s)", count))
+    } else {
+        let ids = invalid
+            .iter()
+            .map(|id| format!("#{}", id))
+            .collect::<Vec<_>>();
+        Err(format!(
+            "{} of the {} scheduled job(s) can never run and will be removed ({})",
+            invalid.len(),
+            count,
+            ids.join(", ")
+        ))
+    }
+}
+
+/// Checks the database, Telegram, Directus and the scheduler. The report is logged, and sent to
+/// `OPS_CHAT_ID` if set. The bot exits when a critical check fails, and starts degraded when
+/// only Directus or the scheduler fail.
+pub async fn self_check(bot: &Bot, db: &SqlitePool) {
+    let checks = [
+        Check {
+            name: "Database schema",
+            critical: true,
+            result: check_schema(db).await,
+        },
+        Check {
+            name: "Telegram",
+            critical: true,
+            result: check_telegram(bot).await,
+        },
+        Check {
+            name: "Directus",
+            critical: false,
+            result: check_directus().await,
+        },
+        Check {
+            name: "Scheduler",
+            critical: false,
+            result: check_scheduler(db).await,
+        },
+    ];
+
+    let mut lines = Vec::new();
+    for check in &checks {
+        match &check.result {
+            Ok(details) => {
+                log::info!("Self-check {}: {}", check.name, details);
+                lines.push(format!("✅ {}: {}", bold(check.name), escape(details)));
+            }
+            Err(e) => {
+                log::error!("Self-check {} failed: {}", check.name, e);
+                lines.push(format!("❌ {}: {}", bold(check.name), escape(e)));
+            }
+        }
+    }
+    let critical = checks.iter().any(|c| c.critical && c.result.is_err());
+    let degraded = checks.iter().any(|c| c.result.is_err());
+    let status = match (critical, degraded) {
+        (true, _) => "Le bot ne peut pas démarrer",
+        (false, true) => "Le bot démarre en mode dégradé",
+        (false, false) => "Le bot a démarré",
+    };
+
+    if let Some(chat_id) = config().ops_chat_id {
+        let report = MessageBuilder::new()
+            .title(status)
+            .html(lines.join("\n"))
+            .build();
+        if let Err(e) = bot.send_html(ChatId(chat_id), report).queued().await {
+            log::error!("Could not send the self-check report: {:?}", e);
+        }
+    }
+    if critical {
+        log::error!("A critical self-check failed, exiting");
+        process::exit(1);
+    }
+    if degraded {
+        log::warn!("Starting degraded, some features will fail");
+    }
+}
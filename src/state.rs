@@ -0,0 +1,77 @@
+//! Services shared by the handlers, the scheduler and the web servers, built once on startup.
+
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+use teloxide::dptree::{self, di::DependencyMap};
+
+use crate::{
+    committee::{committee_repository, CommitteeRepository},
+    config::{config, Config},
+    db::{
+        admins::AdminRepo,
+        authorizations::{authorization_cache, AuthorizationCache, AuthorizationRepo},
+        committee::CommitteeRepo,
+        quotes::QuoteRepo,
+    },
+    dialogues::DialogueStorage,
+    metrics::Metrics,
+    outbox::{outbox, Outbox},
+    throttle::Throttle,
+};
+
+/// Handlers needing several services take the whole state, the others can still take the single
+/// service they need, since each one is also registered on its own.
+#[derive(Clone)]
+pub struct AppState {
+    pub db: Arc<SqlitePool>,
+    /// Queries of the tables shared by the commands, the dashboard and the API, backed by `db`.
+    /// The handlers go through them rather than through `db`, so that the tests can replace them.
+    pub admin_repo: Arc<dyn AdminRepo>,
+    pub authorization_repo: Arc<dyn AuthorizationRepo>,
+    pub committee_repo: Arc<dyn CommitteeRepo>,
+    pub quote_repo: Arc<dyn QuoteRepo>,
+    pub dialogues: Arc<DialogueStorage>,
+    pub outbox: Arc<Outbox>,
+    pub throttle: Arc<Throttle>,
+    pub metrics: Arc<Metrics>,
+    pub config: &'static Config,
+    /// Committee members of Directus, cached.
+    pub committee: &'static CommitteeRepository,
+    pub authorizations: &'static AuthorizationCache,
+}
+
+impl AppState {
+    pub fn new(db: Arc<SqlitePool>) -> Self {
+        Self {
+            dialogues: DialogueStorage::new(db.clone()),
+            admin_repo: db.clone(),
+            authorization_repo: db.clone(),
+            committee_repo: db.clone(),
+            quote_repo: db.clone(),
+            db,
+            outbox: outbox(),
+            throttle: Throttle::new(),
+            metrics: Metrics::new(),
+            config: config(),
+            committee: committee_repository(),
+            authorizations: authorization_cache(),
+        }
+    }
+
+    /// Dependencies of the dispatcher: the state and each of its services.
+    pub fn dependencies(&self) -> DependencyMap {
+        dptree::deps![
+            self.clone(),
+            self.db.clone(),
+            self.admin_repo.clone(),
+            self.authorization_repo.clone(),
+            self.committee_repo.clone(),
+            self.quote_repo.clone(),
+            self.dialogues.clone(),
+            self.outbox.clone(),
+            self.throttle.clone(),
+            self.metrics.clone()
+        ]
+    }
+}
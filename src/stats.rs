@@ -0,0 +1,156 @@
+//! Statistics recorded by the handlers (command log, answers to the polls, quizzes count of the
+//! committee members). They are sent to a background task writing them in batches, so that the
+//! handlers never wait for these writes, and bursts of updates do not contend on the database.
+
+use std::{collections::HashMap, sync::Arc, sync::Mutex};
+
+use sqlx::SqlitePool;
+use tokio::{
+    sync::mpsc::{unbounded_channel, UnboundedSender},
+    task::JoinHandle,
+};
+
+use crate::{
+    dates::now,
+    db::retry_busy,
+    directus::{update_committee, Committee},
+};
+
+/// Maximum number of statistics written in a single transaction.
+const BATCH_SIZE: usize = 200;
+
+pub enum Stat {
+    /// Command received by the bot, without its arguments.
+    Command {
+        chat_id: String,
+        actor: String,
+        command: String,
+    },
+    /// Answer to a poll sent by the bot, `None` when the vote is retracted.
+    PollAnswer {
+        poll_id: String,
+        user_id: String,
+        user_name: String,
+        option: Option<i32>,
+    },
+    /// Quizzes count of a committee member, saved in Directus.
+    PollCount(Committee),
+}
+
+static WRITER: Mutex<Option<UnboundedSender<(i64, Stat)>>> = Mutex::new(None);
+static TASK: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+
+/// Queues the statistic, timestamped now, for the background writer.
+pub fn record_stat(stat: Stat) {
+    let writer = WRITER.lock().unwrap();
+    let Some(writer) = writer.as_ref() else {
+        log::error!("The statistics writer is not running, dropping a statistic");
+        return;
+    };
+    if writer.send((now().timestamp(), stat)).is_err() {
+        log::error!("The statistics writer stopped, dropping a statistic");
+    }
+}
+
+async fn write_batch(db: &SqlitePool, batch: &[(i64, Stat)]) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+    for (timestamp, stat) in batch {
+        match stat {
+            Stat::Command {
+                chat_id,
+                actor,
+                command,
+            } => {
+                sqlx::query!(
+                    "INSERT INTO command_log(created_at, chat_id, actor, command) VALUES($1, $2, $3, $4)",
+                    timestamp,
+                    chat_id,
+                    actor,
+                    command
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            Stat::PollAnswer {
+                poll_id,
+                user_id,
+                user_name,
+                option: Some(option),
+            } => {
+                sqlx::query!(
+                    "INSERT INTO poll_answers(poll_id, user_id, user_name, option, answered_at) VALUES($1, $2, $3, $4, $5)
+                    ON CONFLICT(poll_id, user_id) DO UPDATE SET option = excluded.option, answered_at = excluded.answered_at",
+                    poll_id,
+                    user_id,
+                    user_name,
+                    option,
+                    timestamp
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            Stat::PollAnswer {
+                poll_id,
+                user_id,
+                option: None,
+                ..
+            } => {
+                sqlx::query!(
+                    "DELETE FROM poll_answers WHERE poll_id = $1 AND user_id = $2",
+                    poll_id,
+                    user_id
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            Stat::PollCount(_) => {}
+        }
+    }
+    tx.commit().await
+}
+
+/// Spawns the task writing the recorded statistics, in batches of what accumulated while the
+/// previous batch was written.
+pub fn start_stats_writer(db: Arc<SqlitePool>) {
+    let (sender, mut receiver) = unbounded_channel();
+    if WRITER.lock().unwrap().replace(sender).is_some() {
+        log::error!("The statistics writer is already started");
+        return;
+    }
+
+    let task = tokio::spawn(async move {
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        while receiver.recv_many(&mut batch, BATCH_SIZE).await > 0 {
+            // Only the last count of each member matters
+            let counts = batch
+                .iter()
+                .filter_map(|(_, stat)| match stat {
+                    Stat::PollCount(member) => Some((member.id, member.clone())),
+                    _ => None,
+                })
+                .collect::<HashMap<_, _>>();
+            if !counts.is_empty() {
+                update_committee(counts.into_values().collect()).await;
+            }
+
+            if let Err(e) = retry_busy(|| write_batch(db.as_ref(), &batch)).await {
+                log::error!("Could not write {} statistics: {:?}", batch.len(), e);
+            }
+            batch.clear();
+        }
+    });
+    *TASK.lock().unwrap() = Some(task);
+}
+
+/// Stops the writer once the queued statistics are written, on shutdown.
+pub async fn stop_stats_writer() {
+    // Closing the channel ends the task once it is empty
+    WRITER.lock().unwrap().take();
+    let task = TASK.lock().unwrap().take();
+    if let Some(task) = task {
+        log::info!("Writing the remaining statistics");
+        if let Err(e) = task.await {
+            log::error!("The statistics writer failed: {:?}", e);
+        }
+    }
+}
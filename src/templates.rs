@@ -0,0 +1,112 @@
+//! Phrases of the bot which each chat can reword with `/settemplate`, without a new release. The
+//! placeholders between braces (e.g. `{quote}`) are replaced when the phrase is used.
+
+use sqlx::SqlitePool;
+use teloxide::types::ChatId;
+
+use crate::{i18n::Lang, poll_options::POLL_MAX_QUESTION_LENGTH};
+
+pub struct Template {
+    pub key: &'static str,
+    pub description: &'static str,
+    /// Placeholders the text may contain, the first ones being required.
+    pub placeholders: &'static [&'static str],
+    pub required: usize,
+    pub max_length: usize,
+    pub fr: &'static str,
+    pub en: &'static str,
+}
+
+pub const BUREAU_QUESTION: &str = "bureau_question";
+pub const QUIZ_QUESTION: &str = "quiz_question";
+
+pub const TEMPLATES: &[Template] = &[
+    Template {
+        key: BUREAU_QUESTION,
+        description: "Question du sondage du bureau",
+        placeholders: &[],
+        required: 0,
+        max_length: POLL_MAX_QUESTION_LENGTH,
+        fr: "Qui est au bureau ?",
+        en: "Who is at the office?",
+    },
+    Template {
+        key: QUIZ_QUESTION,
+        description: "Question des quiz des citations",
+        placeholders: &["quote"],
+        required: 1,
+        // Leaves room for the quote
+        max_length: 100,
+        fr: r#"Qui a dit: "{quote}" ?"#,
+        en: r#"Who said: "{quote}"?"#,
+    },
+];
+
+pub fn find_template(key: &str) -> Option<&'static Template> {
+    TEMPLATES.iter().find(|t| t.key.eq_ignore_ascii_case(key))
+}
+
+impl Template {
+    pub fn default_text(&self, lang: Lang) -> &'static str {
+        match lang {
+            Lang::Fr => self.fr,
+            Lang::En => self.en,
+        }
+    }
+
+    /// Why the text cannot be used for this template, if it cannot.
+    pub fn check(&self, text: &str) -> Result<(), String> {
+        if text.chars().count() > self.max_length {
+            return Err(format!("Le texte dépasse {} caractères", self.max_length));
+        }
+        if let Some(missing) = self.placeholders[..self.required]
+            .iter()
+            .find(|p| !text.contains(&format!("{{{}}}", p)))
+        {
+            return Err(format!("Le texte doit contenir {{{}}}", missing));
+        }
+        if let Some(unknown) = placeholders(text).find(|p| !self.placeholders.contains(p)) {
+            return Err(format!("Paramètre inconnu: {{{}}}", unknown));
+        }
+        Ok(())
+    }
+}
+
+/// Names between braces in the text.
+fn placeholders(text: &str) -> impl Iterator<Item = &str> {
+    text.split('{')
+        .skip(1)
+        .filter_map(|part| part.split_once('}').map(|(name, _)| name))
+}
+
+/// Text of the template in the chat: its own wording, or else the default one in its language.
+pub async fn template(db: &SqlitePool, chat_id: ChatId, key: &str, lang: Lang) -> String {
+    let default = find_template(key).map_or("", |t| t.default_text(lang));
+    let id = chat_id.to_string();
+    match sqlx::query_scalar!(
+        r#"SELECT "text" FROM templates WHERE chat_id = $1 AND "key" = $2"#,
+        id,
+        key
+    )
+    .fetch_optional(db)
+    .await
+    {
+        Ok(text) => text.unwrap_or_else(|| default.to_owned()),
+        Err(e) => {
+            log::error!(
+                "Could not fetch the template {} of {}: {:?}",
+                key,
+                chat_id,
+                e
+            );
+            default.to_owned()
+        }
+    }
+}
+
+/// Replaces the placeholders of the text by their values.
+pub fn render(text: &str, values: &[(&str, &str)]) -> String {
+    values.iter().fold(text.to_owned(), |text, (name, value)| {
+        text.replace(&format!("{{{}}}", name), value)
+    })
+}
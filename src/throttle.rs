@@ -0,0 +1,180 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use teloxide::{
+    dispatching::DpHandlerDescription,
+    payloads::SendMessageSetters,
+    prelude::*,
+    types::{Message, UserId},
+    Bot,
+};
+
+use crate::{
+    i18n::{tr, Lang},
+    outbox::Queued,
+    state::AppState,
+    HandlerResult,
+};
+
+/// Maximum number of commands a user can send per window before being ignored.
+const MAX_COMMANDS: usize = 5;
+/// Duration of the window in which commands are counted.
+const WINDOW: Duration = Duration::from_secs(10);
+/// Duration of the first block, doubled on each subsequent offence.
+const BASE_BLOCK: Duration = Duration::from_secs(30);
+/// Upper bound of the duration of a block.
+const MAX_BLOCK: Duration = Duration::from_secs(60 * 60);
+/// Offences are forgotten after this long without being blocked.
+const STRIKES_RESET: Duration = Duration::from_secs(24 * 60 * 60);
+/// The tracked users are pruned once there are more than that.
+const PRUNE_THRESHOLD: usize = 1000;
+
+#[derive(Default)]
+struct Activity {
+    recent: VecDeque<Instant>,
+    strikes: u32,
+    last_strike: Option<Instant>,
+    blocked_until: Option<Instant>,
+}
+
+enum Verdict {
+    Allowed,
+    /// The user is already blocked, the command is silently ignored.
+    Ignored,
+    /// The user has just been blocked for the given duration.
+    Blocked(Duration),
+}
+
+/// Tracks the frequency of the commands of each user, to ignore the ones spamming the bot. Kept in
+/// memory only, a restart lifts all the blocks.
+#[derive(Default)]
+pub struct Throttle {
+    users: Mutex<HashMap<UserId, Activity>>,
+}
+
+impl Throttle {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    fn record(&self, user: UserId, now: Instant) -> Verdict {
+        let mut users = self.users.lock().unwrap();
+        if users.len() > PRUNE_THRESHOLD {
+            users.retain(|_, a| {
+                a.blocked_until.is_some_and(|t| t > now)
+                    || a.last_strike.is_some_and(|t| now - t < STRIKES_RESET)
+                    || a.recent.back().is_some_and(|t| now - *t < WINDOW)
+            });
+        }
+
+        let activity = users.entry(user).or_default();
+        if activity.blocked_until.is_some_and(|t| t > now) {
+            return Verdict::Ignored;
+        }
+
+        activity.recent.push_back(now);
+        while activity.recent.front().is_some_and(|t| now - *t > WINDOW) {
+            activity.recent.pop_front();
+        }
+        if activity.recent.len() <= MAX_COMMANDS {
+            return Verdict::Allowed;
+        }
+
+        if activity
+            .last_strike
+            .is_some_and(|t| now - t >= STRIKES_RESET)
+        {
+            activity.strikes = 0;
+        }
+        let duration = BASE_BLOCK
+            .saturating_mul(2u32.saturating_pow(activity.strikes))
+            .min(MAX_BLOCK);
+        activity.strikes += 1;
+        activity.last_strike = Some(now);
+        activity.blocked_until = Some(now + duration);
+        activity.recent.clear();
+
+        Verdict::Blocked(duration)
+    }
+
+    /// Lifts the block of the user and forgets their offences. Returns whether they were blocked.
+    pub fn lift(&self, user: UserId) -> bool {
+        let now = Instant::now();
+        self.users
+            .lock()
+            .unwrap()
+            .remove(&user)
+            .is_some_and(|a| a.blocked_until.is_some_and(|t| t > now))
+    }
+}
+
+/// Ignores the commands of the users sending too many of them. The user is warned once when the
+/// block starts. Admins are never throttled.
+///
+/// Required dependencies: `teloxide_core::types::message::Message`, `AppState`
+pub fn throttle_commands() -> Endpoint<'static, DependencyMap, HandlerResult, DpHandlerDescription>
+{
+    dptree::filter_map_async(|msg: Message, state: AppState| async move {
+        let user = msg.from()?.id;
+        match state.throttle.record(user, Instant::now()) {
+            Verdict::Allowed => None,
+            verdict if state.admin_repo.is_admin(user).await => {
+                if let Verdict::Blocked(_) = verdict {
+                    state.throttle.lift(user);
+                }
+                None
+            }
+            Verdict::Ignored => Some(None::<Duration>),
+            Verdict::Blocked(duration) => {
+                log::warn!("Throttling user {} for {:?}", user, duration);
+                Some(Some(duration))
+            }
+        }
+    })
+    .endpoint(
+        |bot: Bot, msg: Message, blocked: Option<Duration>, lang: Lang| async move {
+            if let Some(duration) = blocked {
+                bot.send_message(
+                    msg.chat.id,
+                    tr!(
+                        lang,
+                        "Trop de commandes ! Je t'ignore pendant {} secondes.",
+                        "Too many commands! I am ignoring you for {} seconds.",
+                        duration.as_secs()
+                    ),
+                )
+                .reply_to_message_id(msg.id)
+                .queued()
+                .await?;
+            }
+            Ok(())
+        },
+    )
+}
+
+/// Lifts the block of the user given by id, or of the author of the message replied to.
+pub async fn unthrottle(
+    bot: Bot,
+    msg: Message,
+    id: String,
+    throttle: Arc<Throttle>,
+) -> HandlerResult {
+    let user = match id.trim().parse::<u64>() {
+        Ok(id) => Some(UserId(id)),
+        Err(_) => msg.reply_to_message().and_then(|m| m.from()).map(|u| u.id),
+    };
+
+    let text = match user {
+        Some(user) if throttle.lift(user) => format!("L'utilisateur {} n'est plus ignoré", user),
+        Some(user) => format!("L'utilisateur {} n'est pas ignoré", user),
+        None => {
+            "Utilisation: /unthrottle <id>, ou en réponse à un message de l'utilisateur".to_owned()
+        }
+    };
+    bot.send_message(msg.chat.id, text).queued().await?;
+
+    Ok(())
+}
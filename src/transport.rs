@@ -0,0 +1,93 @@
+use std::{
+    collections::HashMap,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, FixedOffset};
+use reqwest::Client;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use crate::directus::Error;
+
+const API_URL: &str = "https://transport.opendata.ch/v1/stationboard";
+/// Duration during which the departures of a stop are reused without querying the API.
+const CACHE_DURATION: Duration = Duration::from_secs(60);
+/// Time allowed to the API to answer.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Number of departures fetched per stop.
+const DEPARTURES_COUNT: &str = "8";
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Stop {
+    pub departure: Option<String>,
+    /// Delay, in minutes.
+    pub delay: Option<i64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Departure {
+    pub category: String,
+    pub number: Option<String>,
+    pub to: String,
+    pub stop: Stop,
+}
+
+impl Departure {
+    pub fn departure_time(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_str(self.stop.departure.as_deref()?, "%Y-%m-%dT%H:%M:%S%z").ok()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct StationboardResponse {
+    station: Option<Station>,
+    stationboard: Vec<Departure>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Station {
+    name: Option<String>,
+}
+
+type CachedBoard = (Instant, Option<String>, Vec<Departure>);
+static CACHE: OnceLock<Mutex<HashMap<String, CachedBoard>>> = OnceLock::new();
+
+/// Fetches the next departures from the given stop. Returns the full name of the stop (as
+/// resolved by the API) and the departures.
+pub async fn get_departures(stop: &str) -> Result<(Option<String>, Vec<Departure>), Error> {
+    let key = stop.to_lowercase();
+    // Not locked during the request, so that a slow API does not block the other stops
+    let cached = CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .await
+        .get(&key)
+        .cloned();
+    if let Some((fetched_at, name, departures)) = cached {
+        if fetched_at.elapsed() < CACHE_DURATION {
+            return Ok((name, departures));
+        }
+    }
+
+    log::debug!("Fetching departures from {}", stop);
+    let response = Client::new()
+        .get(API_URL)
+        .query(&[("station", stop), ("limit", DEPARTURES_COUNT)])
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?;
+    let response = serde_json::from_str::<StationboardResponse>(response.text().await?.as_str())?;
+    let name = response.station.and_then(|s| s.name);
+
+    let mut cache = CACHE.get_or_init(Default::default).lock().await;
+    cache.retain(|_, (fetched_at, ..)| fetched_at.elapsed() < CACHE_DURATION);
+    cache.insert(
+        key,
+        (Instant::now(), name.clone(), response.stationboard.clone()),
+    );
+
+    Ok((name, response.stationboard))
+}
@@ -0,0 +1,315 @@
+//! Validation of the arguments of the commands: a command whose arguments do not parse, or sent
+//! without its required arguments, is answered with its usage and an example instead of being
+//! silently ignored.
+
+use teloxide::{
+    dispatching::DpHandlerDescription,
+    payloads::SendMessageSetters,
+    prelude::*,
+    types::{Me, Message},
+    utils::command::BotCommands,
+    Bot,
+};
+
+use crate::{
+    commands::{is_permitted, Command},
+    format::{code, HtmlMessages},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    state::AppState,
+    HandlerResult,
+};
+
+pub struct Usage {
+    pub syntax: &'static str,
+    pub example: &'static str,
+}
+
+/// Usage of the commands which cannot do anything without arguments.
+const USAGES: &[(&str, Usage)] = &[
+    (
+        "authenticate",
+        Usage {
+            syntax: "/authenticate <token> <nom>",
+            example: "/authenticate 1a2b3c4d Alice",
+        },
+    ),
+    (
+        "adminremove",
+        Usage {
+            syntax: "/adminremove <nom>",
+            example: "/adminremove Alice",
+        },
+    ),
+    (
+        "admininvite",
+        Usage {
+            syntax: "/admininvite <nom>",
+            example: "/admininvite Alice",
+        },
+    ),
+    (
+        "authorize",
+        Usage {
+            syntax: "/authorize <commande>",
+            example: "/authorize bureau",
+        },
+    ),
+    (
+        "unauthorize",
+        Usage {
+            syntax: "/unauthorize <commande>",
+            example: "/unauthorize bureau",
+        },
+    ),
+    (
+        "topicbind",
+        Usage {
+            syntax: "/topicbind <commande>",
+            example: "/topicbind bureau",
+        },
+    ),
+    (
+        "aliasadd",
+        Usage {
+            syntax: "/aliasadd <alias> <commande>",
+            example: "/aliasadd /b bureau",
+        },
+    ),
+    (
+        "aliasremove",
+        Usage {
+            syntax: "/aliasremove <alias>",
+            example: "/aliasremove /b",
+        },
+    ),
+    (
+        "remind",
+        Usage {
+            syntax: "/remind <quand> <texte>",
+            example: "/remind lundi prochain 18h acheter les bières",
+        },
+    ),
+    (
+        "scheduleadd",
+        Usage {
+            syntax: "/scheduleadd [canal] <cron ou récurrence> <message ou /commande>",
+            example: "/scheduleadd chaque lundi 9h /bureau",
+        },
+    ),
+    (
+        "scheduleremove",
+        Usage {
+            syntax: "/scheduleremove <id>",
+            example: "/scheduleremove 3",
+        },
+    ),
+    (
+        "publish",
+        Usage {
+            syntax: "/publish <message ou /quote, /events, /digest>",
+            example: "/publish /events",
+        },
+    ),
+    (
+        "anon",
+        Usage {
+            syntax: "/anon <message>",
+            example: "/anon Merci pour la soirée !",
+        },
+    ),
+    (
+        "anonblock",
+        Usage {
+            syntax: "/anonblock <identifiant affiché avec le message>",
+            example: "/anonblock 3f9a2c",
+        },
+    ),
+    (
+        "anonunblock",
+        Usage {
+            syntax: "/anonunblock <identifiant affiché avec le message>",
+            example: "/anonunblock 3f9a2c",
+        },
+    ),
+    (
+        "doodle",
+        Usage {
+            syntax: "/doodle <titre> | <créneau 1>; <créneau 2>; ...",
+            example: "/doodle Réunion | lundi 18h; mardi 12h15",
+        },
+    ),
+    (
+        "vote",
+        Usage {
+            syntax: "/vote <question>",
+            example: "/vote Pizza ce soir ?",
+        },
+    ),
+    (
+        "autopin",
+        Usage {
+            syntax: "/autopin bureau|countdown on|off",
+            example: "/autopin bureau on",
+        },
+    ),
+    (
+        "room",
+        Usage {
+            syntax: "/room <salle>",
+            example: "/room INM202",
+        },
+    ),
+    (
+        "wiki",
+        Usage {
+            syntax: "/wiki <recherche>",
+            example: "/wiki statuts",
+        },
+    ),
+    (
+        "ticket",
+        Usage {
+            syntax: "/ticket <description du problème>",
+            example: "/ticket L'imprimante du local ne répond plus",
+        },
+    ),
+    (
+        "ticketclose",
+        Usage {
+            syntax: "/ticketclose <id> [message]",
+            example: "/ticketclose 12 Toner remplacé",
+        },
+    ),
+    (
+        "semesteradd",
+        Usage {
+            syntax: "/semesteradd <début> <fin> <nom>",
+            example: "/semesteradd 15/06/2026 04/07/2026 Examens",
+        },
+    ),
+    (
+        "memberlink",
+        Usage {
+            syntax: "/memberlink <nom>, en réponse à un message du membre",
+            example: "/memberlink Alice",
+        },
+    ),
+    (
+        "tournamentstart",
+        Usage {
+            syntax: "/tournamentstart <semaines> [nom]",
+            example: "/tournamentstart 4 Tournoi d'automne",
+        },
+    ),
+    (
+        "quotefix",
+        Usage {
+            syntax: "/quotefix <signalement> <auteur>",
+            example: "/quotefix 5 Alice",
+        },
+    ),
+    (
+        "chat",
+        Usage {
+            syntax: "/chat remap <ancien id> <nouvel id>, ou /chat purge <id>",
+            example: "/chat purge -1001234567890",
+        },
+    ),
+    (
+        "broadcast",
+        Usage {
+            syntax: "/broadcast <message>",
+            example: "/broadcast Assemblée générale jeudi à 18h",
+        },
+    ),
+    (
+        "revoke",
+        Usage {
+            syntax: "/revoke <nom>",
+            example: "/revoke Alice",
+        },
+    ),
+];
+
+pub fn usage(name: &str) -> Option<&'static Usage> {
+    USAGES.iter().find(|(n, _)| *n == name).map(|(_, u)| u)
+}
+
+/// Command sent with invalid arguments.
+#[derive(Clone)]
+pub struct InvalidArguments {
+    /// Name of the command, without the slash.
+    name: String,
+    command: Command,
+}
+
+/// Detects the commands whose arguments do not parse (e.g. `/authenticate` without a name, or
+/// `/bureau` with arguments), or whose required arguments are missing.
+fn invalid_arguments(msg: Message, me: Me) -> Option<InvalidArguments> {
+    let text = msg.text()?;
+    let (head, args) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let (name, mention) = match head.strip_prefix('/')?.split_once('@') {
+        Some((name, mention)) => (name, Some(mention)),
+        None => (head.strip_prefix('/')?, None),
+    };
+    // Commands addressed to another bot
+    if mention.is_some_and(|m| !m.eq_ignore_ascii_case(me.username())) {
+        return None;
+    }
+
+    let command = Command::from_name(name)?;
+    let invalid = match Command::parse(text, me.username()) {
+        Ok(_) => usage(name).is_some() && args.trim().is_empty(),
+        Err(_) => true,
+    };
+    invalid.then(|| InvalidArguments {
+        name: name.to_owned(),
+        command,
+    })
+}
+
+async fn reply_usage(
+    bot: Bot,
+    msg: Message,
+    invalid: InvalidArguments,
+    lang: Lang,
+) -> HandlerResult {
+    let text = match usage(&invalid.name) {
+        Some(usage) => tr!(
+            lang,
+            "Utilisation: {}\nPar exemple: {}",
+            "Usage: {}\nFor example: {}",
+            code(usage.syntax),
+            code(usage.example)
+        ),
+        None => tr!(
+            lang,
+            "La commande /{} ne prend pas d'argument",
+            "The command /{} takes no argument",
+            invalid.name
+        ),
+    };
+    bot.send_html(msg.chat.id, text)
+        .reply_to_message_id(msg.id)
+        .queued()
+        .await?;
+    Ok(())
+}
+
+/// Answers the commands with invalid arguments with their usage, when the user could run them.
+///
+/// Required dependencies: `teloxide_core::types::message::Message`, `AppState`
+pub fn validate_arguments() -> Endpoint<'static, DependencyMap, HandlerResult, DpHandlerDescription>
+{
+    dptree::filter_map(invalid_arguments)
+        .filter_async(
+            |invalid: InvalidArguments, msg: Message, state: AppState| async move {
+                let Some(user) = msg.from() else {
+                    return false;
+                };
+                is_permitted(&state, &msg, user.id, &invalid.command).await
+            },
+        )
+        .endpoint(reply_usage)
+}
@@ -0,0 +1,335 @@
+//! Verification of the new members of the groups, against the spam bots joining the public
+//! groups. Enabled from the `/settings` menu: the new members are muted until they press a button
+//! (or answer a CLIC trivia question), and stay muted or are removed if they do not answer in
+//! time.
+
+use std::sync::Arc;
+
+use rand::{seq::SliceRandom, thread_rng, Rng};
+use sqlx::SqlitePool;
+use teloxide::{
+    payloads::SendMessageSetters,
+    requests::Requester,
+    types::{
+        CallbackQuery, ChatId, ChatPermissions, InlineKeyboardButton, InlineKeyboardMarkup,
+        Message, MessageId, UserId,
+    },
+    Bot,
+};
+
+use crate::{
+    callbacks::{CallbackData, CallbackResult, MEMBER_CHALLENGE},
+    cmd_settings::welcome_message,
+    dates::now,
+    format::{bold, HtmlMessages},
+    i18n::{tr, Lang},
+    outbox::Queued,
+    permissions::{delete_own_message, restrict_member},
+    HandlerResult,
+};
+
+/// Verification modes offered by the menu, in the order of the button cycle.
+pub const MODES: &[&str] = &["off", "button", "trivia"];
+/// Delays to answer offered by the menu (in minutes), in the order of the button cycle.
+const DELAYS: &[i64] = &[2, 5, 10, 30];
+
+/// Questions of the trivia mode, in French and English, with their options (the first one is the
+/// right one, they are shuffled when sent).
+const TRIVIA: &[(&str, &str, &[&str])] = &[
+    (
+        "Dans quelle école se trouve le CLIC ?",
+        "In which school is the CLIC?",
+        &["EPFL", "UNIL", "ETH Zurich", "HEIG-VD"],
+    ),
+    (
+        "Dans quelle ville se trouve l'EPFL ?",
+        "In which city is EPFL?",
+        &["Lausanne", "Genève", "Zurich", "Berne"],
+    ),
+    (
+        "De quelle faculté le CLIC est-il l'association ?",
+        "Which faculty is the CLIC the association of?",
+        &["IC", "SV", "ENAC", "SB"],
+    ),
+];
+
+pub struct VerificationSettings {
+    /// One of [`MODES`].
+    pub mode: String,
+    /// Delay to answer, in minutes.
+    pub minutes: i64,
+    /// Whether the members who do not answer are removed, instead of staying muted.
+    pub kick: bool,
+}
+
+impl Default for VerificationSettings {
+    fn default() -> Self {
+        Self {
+            mode: "off".to_owned(),
+            minutes: 5,
+            kick: false,
+        }
+    }
+}
+
+/// Verification settings of the chat, the defaults if it never changed them.
+pub async fn verification_settings(db: &SqlitePool, chat_id: ChatId) -> VerificationSettings {
+    let chat_id = chat_id.to_string();
+    let result = sqlx::query_as!(
+        VerificationSettings,
+        r#"SELECT verification_mode AS mode, verification_minutes AS minutes,
+            verification_kick AS "kick: bool"
+        FROM chats WHERE chat_id = $1"#,
+        chat_id
+    )
+    .fetch_optional(db)
+    .await;
+
+    match result {
+        Ok(settings) => settings.unwrap_or_default(),
+        Err(e) => {
+            log::error!(
+                "Could not fetch the verification settings of {}: {:?}",
+                chat_id,
+                e
+            );
+            VerificationSettings::default()
+        }
+    }
+}
+
+/// Next mode of the button cycle.
+pub fn next_mode(mode: &str) -> &'static str {
+    let index = MODES.iter().position(|m| *m == mode).unwrap_or(0);
+    MODES[(index + 1) % MODES.len()]
+}
+
+/// Next delay of the button cycle.
+pub fn next_delay(minutes: i64) -> i64 {
+    let index = DELAYS.iter().position(|d| *d == minutes).unwrap_or(0);
+    DELAYS[(index + 1) % DELAYS.len()]
+}
+
+pub fn format_mode(mode: &str, lang: Lang) -> String {
+    match mode {
+        "button" => tr!(lang, "bouton", "button"),
+        "trivia" => tr!(lang, "question", "question"),
+        _ => tr!(lang, "aucune", "none"),
+    }
+}
+
+/// Mutes the new members of the chat, and asks them to prove that they are human.
+pub async fn challenge_new_members(
+    bot: Bot,
+    msg: Message,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> HandlerResult {
+    let Some(members) = msg.new_chat_members() else {
+        return Ok(());
+    };
+    let settings = verification_settings(db.as_ref(), msg.chat.id).await;
+    if settings.mode == "off" {
+        for user in members.iter().filter(|u| !u.is_bot) {
+            send_welcome(&bot, db.as_ref(), msg.chat.id, &user.full_name()).await?;
+        }
+        return Ok(());
+    }
+
+    for user in members.iter().filter(|u| !u.is_bot) {
+        if !restrict_member(&bot, msg.chat.id, user.id, ChatPermissions::empty(), lang).await {
+            // Without the right to restrict, the verification would be pointless
+            return Ok(());
+        }
+
+        let (question, keyboard, answer) = if settings.mode == "trivia" {
+            let (fr, en, options) = TRIVIA[thread_rng().gen_range(0..TRIVIA.len())];
+            let mut options = options.iter().enumerate().collect::<Vec<_>>();
+            options.shuffle(&mut thread_rng());
+            let answer = options.iter().position(|(i, _)| *i == 0).map(|a| a as i64);
+            let buttons = options
+                .iter()
+                .enumerate()
+                .map(|(index, (_, option))| {
+                    [InlineKeyboardButton::callback(
+                        option.to_string(),
+                        CallbackData::format(MEMBER_CHALLENGE, format!("{}:{}", user.id, index)),
+                    )]
+                })
+                .collect::<Vec<_>>();
+            let question = match lang {
+                Lang::Fr => fr,
+                Lang::En => en,
+            };
+            (
+                question.to_owned(),
+                InlineKeyboardMarkup::new(buttons),
+                answer,
+            )
+        } else {
+            (
+                tr!(
+                    lang,
+                    "Appuie sur le bouton pour prouver que tu n'es pas un robot.",
+                    "Press the button to prove that you are not a robot."
+                ),
+                InlineKeyboardMarkup::new([[InlineKeyboardButton::callback(
+                    tr!(lang, "🤖 Je ne suis pas un robot", "🤖 I am not a robot"),
+                    CallbackData::format(MEMBER_CHALLENGE, format!("{}:0", user.id)),
+                )]]),
+                None,
+            )
+        };
+
+        let sent = bot
+            .send_html(
+                msg.chat.id,
+                tr!(
+                    lang,
+                    "Bienvenue {} ! {}\nTu pourras écrire une fois que tu auras répondu, dans les {} minutes.",
+                    "Welcome {}! {}\nYou will be able to write once you answer, within {} minutes.",
+                    bold(&user.full_name()),
+                    question,
+                    settings.minutes
+                ),
+            )
+            .reply_markup(keyboard)
+            .queued()
+            .await?;
+
+        let (chat_id, user_id) = (msg.chat.id.to_string(), user.id.to_string());
+        let expires_at = now().timestamp() + settings.minutes * 60;
+        sqlx::query!(
+            "INSERT OR REPLACE INTO member_challenges(chat_id, user_id, message_id, answer, expires_at)
+            VALUES($1, $2, $3, $4, $5)",
+            chat_id,
+            user_id,
+            sent.id.0,
+            answer,
+            expires_at
+        )
+        .execute(db.as_ref())
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Removes the member who failed the verification, if the chat asks for it. Otherwise they stay
+/// muted, until an administrator lifts the restriction.
+async fn sanction(bot: &Bot, chat_id: ChatId, user_id: UserId, kick: bool) {
+    if !kick {
+        log::info!("Member {} of {} stays muted", user_id, chat_id);
+        return;
+    }
+    // Banning then unbanning removes the member without preventing them from joining again
+    let result = match bot.ban_chat_member(chat_id, user_id).await {
+        Ok(_) => bot.unban_chat_member(chat_id, user_id).await.map(|_| ()),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(_) => log::info!("Removed unverified member {} from {}", user_id, chat_id),
+        Err(e) => log::warn!(
+            "Could not remove member {} from {}: {:?}",
+            user_id,
+            chat_id,
+            e
+        ),
+    }
+}
+
+/// Buttons of the challenges: lifts the restriction of the new member if they answered right.
+pub async fn answer_challenge(
+    bot: Bot,
+    query: CallbackQuery,
+    data: CallbackData,
+    db: Arc<SqlitePool>,
+    lang: Lang,
+) -> CallbackResult {
+    let Some(message) = &query.message else {
+        return Ok(None);
+    };
+    let Some((user_id, Ok(choice))) = data
+        .payload
+        .split_once(':')
+        .map(|(user, choice)| (user, choice.parse::<i64>()))
+    else {
+        return Ok(None);
+    };
+    if query.from.id.to_string() != user_id {
+        return Ok(Some(tr!(
+            lang,
+            "Cette question ne t'est pas destinée",
+            "This question is not for you"
+        )));
+    }
+
+    let chat_id = message.chat.id.to_string();
+    let Some(challenge) = sqlx::query!(
+        "DELETE FROM member_challenges WHERE chat_id = $1 AND user_id = $2 RETURNING answer",
+        chat_id,
+        user_id
+    )
+    .fetch_optional(db.as_ref())
+    .await?
+    else {
+        return Ok(None);
+    };
+    delete_own_message(&bot, message.chat.id, message.id).await;
+
+    if challenge.answer.is_some_and(|a| a != choice) {
+        let settings = verification_settings(db.as_ref(), message.chat.id).await;
+        sanction(&bot, message.chat.id, query.from.id, settings.kick).await;
+        return Ok(Some(tr!(lang, "Mauvaise réponse", "Wrong answer")));
+    }
+
+    // The member gets the default permissions of the chat back
+    let permissions = bot
+        .get_chat(message.chat.id)
+        .await?
+        .permissions()
+        .unwrap_or(ChatPermissions::all());
+    restrict_member(&bot, message.chat.id, query.from.id, permissions, lang).await;
+    send_welcome(&bot, db.as_ref(), message.chat.id, &query.from.full_name()).await?;
+
+    Ok(Some(tr!(lang, "Bienvenue !", "Welcome!")))
+}
+
+/// Sends the welcome message of the chat to the new member, if the chat has one.
+async fn send_welcome(
+    bot: &Bot,
+    db: &SqlitePool,
+    chat_id: ChatId,
+    name: &str,
+) -> Result<(), teloxide::RequestError> {
+    if let Some(text) = welcome_message(db, chat_id, name).await {
+        bot.send_html(chat_id, text).queued().await?;
+    }
+    Ok(())
+}
+
+/// Sanctions the new members who did not answer their challenge in time.
+pub async fn expire_member_challenges(bot: &Bot, db: &SqlitePool) -> Result<(), sqlx::Error> {
+    let timestamp = now().timestamp();
+    let expired = sqlx::query!(
+        r#"DELETE FROM member_challenges WHERE expires_at <= $1
+        RETURNING chat_id AS "chat_id!", user_id AS "user_id!", message_id"#,
+        timestamp
+    )
+    .fetch_all(db)
+    .await?;
+
+    for challenge in expired {
+        let (Ok(chat_id), Ok(user_id)) = (
+            challenge.chat_id.parse::<i64>().map(ChatId),
+            challenge.user_id.parse::<u64>().map(UserId),
+        ) else {
+            continue;
+        };
+        delete_own_message(bot, chat_id, MessageId(challenge.message_id as i32)).await;
+        let settings = verification_settings(db, chat_id).await;
+        sanction(bot, chat_id, user_id, settings.kick).await;
+    }
+
+    Ok(())
+}
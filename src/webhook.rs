@@ -0,0 +1,132 @@
+//! Webhook mode, enabled by `WEBHOOK_URL`: Telegram posts the updates to the bot instead of the
+//! bot polling them. The webhook is set with a random secret token, which Telegram sends back in
+//! the `X-Telegram-Bot-Api-Secret-Token` header of each update: the requests without it are
+//! rejected and logged, so that only Telegram can post updates.
+
+use std::net::SocketAddr;
+
+use axum::{
+    body::Bytes,
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, StatusCode},
+    routing::post,
+    Router,
+};
+use futures::{stream::BoxStream, StreamExt};
+use rand::{thread_rng, RngCore};
+use reqwest::Url;
+use serde_json::json;
+use teloxide::{
+    stop::{mk_stop_token, StopFlag, StopToken},
+    types::Update,
+    Bot,
+};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use crate::{crypto::secrets_equal, http::serve, reactions::ALLOWED_UPDATES};
+
+const SECRET_HEADER: &str = "X-Telegram-Bot-Api-Secret-Token";
+
+#[derive(Clone)]
+struct WebhookState {
+    secret: String,
+    updates: UnboundedSender<Update>,
+}
+
+/// Updates posted to the webhook, until the dispatcher stops.
+pub struct Webhook {
+    updates: UnboundedReceiver<Update>,
+    stop_token: StopToken,
+    stop_flag: StopFlag,
+}
+
+impl Webhook {
+    pub fn stop_token(&self) -> StopToken {
+        self.stop_token.clone()
+    }
+
+    pub fn updates(&mut self) -> BoxStream<'_, Update> {
+        let updates = &mut self.updates;
+        futures::stream::poll_fn(move |cx| updates.poll_recv(cx))
+            .take_until(self.stop_flag.clone())
+            .boxed()
+    }
+}
+
+async fn receive(
+    State(state): State<WebhookState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    let secret = headers.get(SECRET_HEADER).and_then(|h| h.to_str().ok());
+    if !secret.is_some_and(|s| secrets_equal(s, &state.secret)) {
+        log::warn!(
+            "Rejected a webhook request from {}, invalid secret token",
+            peer.ip()
+        );
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    match serde_json::from_slice::<Update>(&body) {
+        Ok(update) => {
+            let _ = state.updates.send(update);
+            StatusCode::OK
+        }
+        Err(e) => {
+            log::error!("Invalid update posted to the webhook: {:?}", e);
+            StatusCode::BAD_REQUEST
+        }
+    }
+}
+
+/// Sets the webhook, with the updates of `ALLOWED_UPDATES` and the secret token. The url is
+/// removed from the errors, since it contains the bot token.
+async fn set_webhook(bot: &Bot, url: &str, secret: &str) -> Result<(), reqwest::Error> {
+    let api_url = bot
+        .api_url()
+        .join(&format!("bot{}/setWebhook", bot.token()))
+        .expect("the API url is valid");
+    reqwest::Client::new()
+        .post(api_url)
+        .header("Content-Type", "application/json")
+        .body(
+            json!({ "url": url, "secret_token": secret, "allowed_updates": ALLOWED_UPDATES })
+                .to_string(),
+        )
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map(|_| ())
+        .map_err(reqwest::Error::without_url)
+}
+
+/// Serves the webhook at the given address, on the path of `url`, and sets it.
+pub async fn start_webhook(
+    bot: &Bot,
+    url: &str,
+    address: String,
+) -> Result<Webhook, reqwest::Error> {
+    let mut bytes = [0u8; 32];
+    thread_rng().fill_bytes(&mut bytes);
+    let secret = hex::encode(bytes);
+
+    let (sender, updates) = unbounded_channel();
+    let path = Url::parse(url).map_or("/".to_owned(), |u| u.path().to_owned());
+    let app = Router::new()
+        .route(&path, post(receive))
+        .with_state(WebhookState {
+            secret: secret.clone(),
+            updates: sender,
+        });
+    tokio::spawn(async move { serve("webhook", &address, app).await });
+
+    set_webhook(bot, url, &secret).await?;
+    log::info!("Receiving the updates at {}", url);
+    let (stop_token, stop_flag) = mk_stop_token();
+    Ok(Webhook {
+        updates,
+        stop_token,
+        stop_flag,
+    })
+}
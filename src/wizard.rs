@@ -0,0 +1,172 @@
+//! Building blocks of the multi-step dialogues (wizards), such as `/newpoll` or `/newsletter`.
+//!
+//! Each step is a variant of [`PollState`], persisted by the [`DialogueStorage`] so that the
+//! wizard survives a restart. A step sends a prompt which replaces the prompt of the previous
+//! step, with a button to cancel the wizard. The wizards can also be cancelled with `/cancel`, and
+//! those left unanswered are cancelled by the scheduler (see [`expire_dialogues`]).
+//!
+//! [`DialogueStorage`]: crate::dialogues::DialogueStorage
+//! [`expire_dialogues`]: crate::dialogues::expire_dialogues
+
+use std::sync::Arc;
+
+use teloxide::{
+    payloads::SendMessageSetters,
+    requests::Requester,
+    types::{CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup, Message, MessageId},
+    Bot, RequestError,
+};
+
+use crate::{
+    callbacks::{CallbackData, CallbackResult, WIZARD},
+    cmd_poll::{PollDialogue, PollState},
+    db::admins::AdminRepo,
+    format::HtmlMessages,
+    i18n::{tr, Lang},
+    outbox::Queued,
+    permissions::delete_own_message,
+    HandlerResult,
+};
+
+/// Payload of the cancel button of the prompts.
+const CANCEL: &str = "cancel";
+
+/// Keyboard of a step with one button per row, from `(label, payload)` pairs of the action.
+pub fn keyboard(action: &str, buttons: Vec<(String, String)>) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(buttons.into_iter().map(|(label, payload)| {
+        vec![InlineKeyboardButton::callback(
+            label,
+            CallbackData::format(action, payload),
+        )]
+    }))
+}
+
+/// Keyboard of a prompt: the one of the step, if any, followed by the cancel button.
+pub fn with_cancel(keyboard: Option<InlineKeyboardMarkup>, lang: Lang) -> InlineKeyboardMarkup {
+    keyboard
+        .unwrap_or_default()
+        .append_row([InlineKeyboardButton::callback(
+            tr!(lang, "✖️ Annuler", "✖️ Cancel"),
+            CallbackData::format(WIZARD, CANCEL),
+        )])
+}
+
+/// Sends the HTML prompt of a step, with its keyboard and the cancel button, after deleting the
+/// prompt of the previous step. Returns the ID of the prompt, to store in the next state.
+pub async fn prompt(
+    bot: &Bot,
+    dialogue: &PollDialogue,
+    previous: Option<MessageId>,
+    text: String,
+    keyboard: Option<InlineKeyboardMarkup>,
+    lang: Lang,
+) -> Result<MessageId, RequestError> {
+    if let Some(id) = previous {
+        delete_own_message(bot, dialogue.chat_id(), id).await;
+    }
+    let sent = bot
+        .send_html(dialogue.chat_id(), text)
+        .reply_markup(with_cancel(keyboard, lang))
+        .queued()
+        .await?;
+    Ok(sent.id)
+}
+
+/// Sends the prompt of the next step, and moves the dialogue to the state built from its ID.
+pub async fn next_step(
+    bot: &Bot,
+    dialogue: &PollDialogue,
+    previous: Option<MessageId>,
+    text: String,
+    keyboard: Option<InlineKeyboardMarkup>,
+    lang: Lang,
+    step: impl FnOnce(MessageId) -> PollState,
+) -> HandlerResult {
+    let message_id = prompt(bot, dialogue, previous, text, keyboard, lang).await?;
+    dialogue.update(step(message_id)).await?;
+    Ok(())
+}
+
+/// Ends the wizard: deletes the prompt of its last step, and resets the dialogue.
+pub async fn finish(
+    bot: &Bot,
+    dialogue: &PollDialogue,
+    previous: Option<MessageId>,
+) -> HandlerResult {
+    if let Some(id) = previous {
+        delete_own_message(bot, dialogue.chat_id(), id).await;
+    }
+    dialogue.update(PollState::Start).await?;
+    Ok(())
+}
+
+/// `/cancel` cancels the ongoing wizard of the chat. Only the user who started it, or an admin,
+/// may cancel it.
+pub async fn cancel(
+    bot: Bot,
+    msg: Message,
+    dialogue: PollDialogue,
+    admins: Arc<dyn AdminRepo>,
+    lang: Lang,
+) -> HandlerResult {
+    let state = dialogue.get_or_default().await?;
+    if matches!(state, PollState::Start) {
+        bot.send_message(
+            msg.chat.id,
+            tr!(lang, "Aucune commande en cours", "No ongoing command"),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+    // The dialogues saved before their initiator was stored can only be cancelled by an admin
+    if state.initiator() != Some(user.id) && !admins.is_admin(user.id).await {
+        bot.send_message(
+            msg.chat.id,
+            tr!(
+                lang,
+                "Seule la personne ayant lancé la commande peut l'annuler",
+                "Only the person who started the command can cancel it"
+            ),
+        )
+        .queued()
+        .await?;
+        return Ok(());
+    }
+
+    finish(&bot, &dialogue, state.prompt()).await?;
+    bot.send_message(
+        msg.chat.id,
+        tr!(lang, "Commande annulée", "Command cancelled"),
+    )
+    .queued()
+    .await?;
+    Ok(())
+}
+
+/// Cancel button of the prompts, answered once the non-initiators are rejected.
+pub async fn cancel_button(
+    bot: Bot,
+    query: CallbackQuery,
+    data: CallbackData,
+    dialogue: PollDialogue,
+    state: PollState,
+    lang: Lang,
+) -> CallbackResult {
+    // The buttons of the prompts replaced since are no longer active
+    let current = query.message.as_ref().map(|m| m.id);
+    if data.payload != CANCEL || current.is_none() || current != state.prompt() {
+        return Ok(Some(tr!(
+            lang,
+            "Ce bouton n'est plus actif",
+            "This button is no longer active"
+        )));
+    }
+
+    finish(&bot, &dialogue, state.prompt()).await?;
+    Ok(Some(tr!(lang, "Commande annulée", "Command cancelled")))
+}